@@ -143,12 +143,12 @@ fn bench_engine_e2e(c: &mut Criterion) {
         group.bench_with_input(BenchmarkId::new("put_get_cycle", size), size, |b, &size| {
             b.iter(|| {
                 let dir = tempfile::tempdir().unwrap();
-                let config = oblivion::config::Config {
-                    data_dir: dir.path().to_path_buf(),
-                    memtable_max_size: 64 * 1024, // 64KB
-                    sync_writes: true,
-                };
-                let mut engine = oblivion::engine::Oblivion::open(config).unwrap();
+                let config = oblivion::config::Config::builder(dir.path())
+                    .memtable_max_size(64 * 1024) // 64KB
+                    .sync_writes(true)
+                    .build()
+                    .unwrap();
+                let engine = oblivion::engine::Oblivion::open(config).unwrap();
 
                 for i in 0..size {
                     let key = format!("key_{:06}", i).into_bytes();
@@ -0,0 +1,99 @@
+//! OBLIVION - RESP Server Binary
+//! Opens a database and serves it to Redis clients.
+//!
+//! ```text
+//! oblivion-server [--data-dir <dir>] [--bind <addr>] [--http <addr>]
+//!                 [--requirepass <password>] [--tls-cert <pem> --tls-key <pem>]
+//! ```
+//!
+//! `--http` also serves the JSON REST API (feature `http`). The
+//! password can also come from `OBLIVION_SERVER_PASSWORD`, which keeps
+//! it out of the process list; `--tls-cert` and `--tls-key` need the
+//! `tls` feature.
+
+use std::sync::Arc;
+
+use oblivion::config::Config;
+use oblivion::engine::Oblivion;
+use oblivion::server::RespServer;
+
+/// Address served when `--bind` is not given: Redis' port, local only.
+const DEFAULT_BIND: &str = "127.0.0.1:6379";
+
+/// Environment variable holding the client password.
+const PASSWORD_ENV: &str = "OBLIVION_SERVER_PASSWORD";
+
+const USAGE: &str = "usage: oblivion-server [--data-dir <dir>] [--bind <addr>] [--http <addr>] \
+                     [--requirepass <password>] [--tls-cert <pem> --tls-key <pem>]";
+
+fn main() {
+    env_logger::init();
+
+    let mut config = Config::default();
+    if let Ok(password) = std::env::var(PASSWORD_ENV) {
+        config = config.with_server_password(password);
+    }
+    let mut bind = DEFAULT_BIND.to_string();
+    let mut http: Option<String> = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--data-dir", Some(dir)) => config.data_dir = dir.into(),
+            ("--bind", Some(addr)) => bind = addr,
+            ("--http", Some(addr)) => http = Some(addr),
+            ("--requirepass", Some(password)) => config = config.with_server_password(password),
+            ("--tls-cert", Some(path)) => config.server_tls_cert = Some(path.into()),
+            ("--tls-key", Some(path)) => config.server_tls_key = Some(path.into()),
+            ("--help" | "-h", _) => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ => {
+                eprintln!("{}", USAGE);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    let engine = match Oblivion::open(config) {
+        Ok(engine) => Arc::new(engine),
+        Err(err) => {
+            eprintln!("[ERROR] Failed to open engine: {}", err);
+            std::process::exit(1);
+        }
+    };
+    let _http_server = http.map(|addr| start_http(&engine, &addr));
+    let server = match RespServer::start(engine, bind.as_str()) {
+        Ok(server) => server,
+        Err(err) => {
+            eprintln!("[ERROR] Failed to listen on {}: {}", bind, err);
+            std::process::exit(1);
+        }
+    };
+    println!("OBLIVION serving RESP on {}", server.local_addr());
+
+    // Serve until the process is killed
+    loop {
+        std::thread::park();
+    }
+}
+
+#[cfg(feature = "http")]
+fn start_http(engine: &Arc<Oblivion>, addr: &str) -> oblivion::server::http::HttpServer {
+    match oblivion::server::http::HttpServer::start(Arc::clone(engine), addr) {
+        Ok(server) => {
+            println!("OBLIVION serving HTTP on {}", server.local_addr());
+            server
+        }
+        Err(err) => {
+            eprintln!("[ERROR] Failed to listen on {}: {}", addr, err);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(not(feature = "http"))]
+fn start_http(_engine: &Arc<Oblivion>, _addr: &str) {
+    eprintln!("[ERROR] --http requires building with the `http` feature");
+    std::process::exit(2);
+}
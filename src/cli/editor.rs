@@ -0,0 +1,126 @@
+//! OBLIVION - CLI Line Editor
+//! Line editing for the interactive shell, on top of `rustyline`.
+//!
+//! ## Features
+//! - Emacs-style editing, arrow-key history and `Ctrl-R` reverse search
+//! - History kept across sessions in [`history_path`]
+//! - `Tab` completes command names in the first word, and keys used
+//!   earlier in the session in later words
+//!
+//! Completed keys are escaped as the [`tokenizer`](super::tokenizer)
+//! reads them, so binary keys complete to something that can be typed.
+
+use std::collections::VecDeque;
+use std::path::PathBuf;
+
+use rustyline::completion::Completer;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+
+use super::tokenizer::escape;
+use super::COMMANDS;
+
+/// Keys remembered for completion, most recent first.
+const RECENT_KEYS: usize = 256;
+
+/// Environment variable overriding the history file location.
+pub const HISTORY_ENV: &str = "OBLIVION_HISTFILE";
+
+/// History file of the shell: `$OBLIVION_HISTFILE`, or
+/// `~/.oblivion_history`. `None` when neither is known, which keeps
+/// history in memory only.
+pub fn history_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os(HISTORY_ENV) {
+        return Some(PathBuf::from(path));
+    }
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".oblivion_history"))
+}
+
+/// Completion state of the shell.
+#[derive(Default)]
+pub struct ShellHelper {
+    recent_keys: VecDeque<Vec<u8>>,
+}
+
+impl ShellHelper {
+    /// Remember `key` for completion.
+    pub fn record_key(&mut self, key: &[u8]) {
+        self.recent_keys.retain(|k| k != key);
+        self.recent_keys.push_front(key.to_vec());
+        self.recent_keys.truncate(RECENT_KEYS);
+    }
+
+    /// Candidates for the word ending at `pos`, and where it starts.
+    fn candidates(&self, line: &str, pos: usize) -> (usize, Vec<String>) {
+        let start = line[..pos]
+            .rfind(|c: char| c.is_ascii_whitespace())
+            .map_or(0, |i| i + 1);
+        let word = &line[start..pos];
+        let candidates = if line[..start].trim().is_empty() {
+            let word = word.to_ascii_lowercase();
+            COMMANDS
+                .iter()
+                .filter(|name| name.starts_with(&word))
+                .map(|name| name.to_string())
+                .collect()
+        } else {
+            self.recent_keys
+                .iter()
+                .map(|key| escape(key))
+                .filter(|key| key.starts_with(word))
+                .collect()
+        };
+        (start, candidates)
+    }
+}
+
+impl Completer for ShellHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.candidates(line, pos))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_completes_commands_and_recent_keys() {
+        let mut helper = ShellHelper::default();
+        assert_eq!(helper.candidates("SC", 2), (0, vec!["scan".to_string()]));
+        assert_eq!(helper.candidates("  pe", 4).1, vec!["persist".to_string()]);
+
+        helper.record_key(b"user:1");
+        helper.record_key(b"user 2");
+        helper.record_key(b"order:1");
+        helper.record_key(b"user:1");
+        assert_eq!(
+            helper.candidates("get us", 6),
+            (4, vec!["user:1".to_string()])
+        );
+        assert_eq!(
+            helper.candidates("del \"us", 7),
+            (4, vec!["\"user 2\"".to_string()])
+        );
+        assert_eq!(helper.candidates("get ", 4).1.len(), 3);
+    }
+}
@@ -0,0 +1,209 @@
+//! OBLIVION - CLI Info
+//! Builds the sections of the `info` command from the engine's
+//! metrics snapshot, as Redis' `INFO` does.
+//!
+//! ## Sections
+//! | Section       | Contents                                           |
+//! |---------------|----------------------------------------------------|
+//! | `server`      | Version, data directory, uptime, role              |
+//! | `persistence` | WAL size and sync mode, last recovery              |
+//! | `memory`      | MemTable, row and block caches, open table files   |
+//! | `stats`       | Operation counts, ops/sec, bytes, hit rates        |
+//! | `compaction`  | SSTables per tier, pending and obsolete bytes, age |
+//!
+//! Sizes are in bytes and durations in seconds unless the field name
+//! says otherwise. Counters and `ops_per_sec` cover the time since the
+//! engine was opened.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::engine::metrics::MetricsSnapshot;
+use crate::engine::properties::LiveFile;
+use crate::engine::Oblivion;
+
+/// Section names, in display order.
+pub const SECTIONS: &[&str] = &["server", "persistence", "memory", "stats", "compaction"];
+
+/// One titled group of `info` fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    /// Section name, as accepted by `info <section>`.
+    pub name: &'static str,
+    /// Field names and values, in display order.
+    pub fields: Vec<(&'static str, String)>,
+}
+
+/// Build the sections named by `filter` (every section when `None`).
+/// Returns `None` if `filter` names no section.
+pub fn sections(engine: &Oblivion, filter: Option<&str>) -> Option<Vec<Section>> {
+    let names: Vec<&'static str> = match filter {
+        None | Some("all" | "everything" | "default") => SECTIONS.to_vec(),
+        Some(name) => vec![*SECTIONS.iter().find(|s| **s == name)?],
+    };
+    let metrics = engine.metrics().snapshot();
+    Some(
+        names
+            .into_iter()
+            .map(|name| Section {
+                name,
+                fields: fields(engine, &metrics, name),
+            })
+            .collect(),
+    )
+}
+
+fn fields(engine: &Oblivion, m: &MetricsSnapshot, section: &str) -> Vec<(&'static str, String)> {
+    let config = engine.config();
+    match section {
+        "server" => vec![
+            ("version", env!("CARGO_PKG_VERSION").to_string()),
+            ("data_dir", config.data_dir.display().to_string()),
+            ("uptime_in_seconds", format!("{:.0}", m.uptime_secs)),
+            (
+                "role",
+                if engine.is_read_only() {
+                    "follower"
+                } else {
+                    "primary"
+                }
+                .to_string(),
+            ),
+            ("background_threads", config.background_threads.to_string()),
+        ],
+        "persistence" => vec![
+            ("wal_bytes", m.wal_bytes.to_string()),
+            ("sync_writes", config.sync_writes.to_string()),
+            ("latest_sequence", engine.latest_sequence().to_string()),
+            ("wal_recoveries", m.wal_recoveries.to_string()),
+            (
+                "last_recovery_records",
+                m.recovery_records_applied.to_string(),
+            ),
+            (
+                "last_recovery_skipped",
+                m.recovery_records_skipped.to_string(),
+            ),
+            ("last_recovery_bytes", m.recovery_bytes_replayed.to_string()),
+            (
+                "last_recovery_ms",
+                format!("{:.3}", m.recovery_duration_micros as f64 / 1000.0),
+            ),
+            (
+                "background_error",
+                engine.background_error().unwrap_or_default(),
+            ),
+        ],
+        "memory" => {
+            let (cache_entries, cache_bytes) = engine
+                .row_cache()
+                .map_or((0, 0), |cache| (cache.len(), cache.usage()));
+            let (block_entries, block_bytes) = engine
+                .block_cache()
+                .map_or((0, 0), |cache| (cache.len(), cache.usage()));
+            vec![
+                ("memtable_entries", engine.len().to_string()),
+                ("memtable_bytes", m.memtable_bytes.to_string()),
+                ("memtable_max_bytes", config.memtable_max_size.to_string()),
+                ("row_cache_entries", cache_entries.to_string()),
+                ("row_cache_bytes", cache_bytes.to_string()),
+                ("block_cache_entries", block_entries.to_string()),
+                ("block_cache_bytes", block_bytes.to_string()),
+                ("open_table_files", engine.open_table_files().to_string()),
+            ]
+        }
+        "stats" => vec![
+            ("total_ops", m.total_ops.to_string()),
+            ("ops_per_sec", format!("{:.2}", m.ops_per_sec)),
+            ("puts", m.puts.to_string()),
+            ("gets", m.gets.to_string()),
+            ("deletes", m.deletes.to_string()),
+            ("scans", m.scans.to_string()),
+            ("bytes_written", m.bytes_written.to_string()),
+            ("bytes_read", m.bytes_read.to_string()),
+            ("row_cache_hit_rate", format!("{:.4}", m.row_cache_hit_rate)),
+            (
+                "block_cache_hit_rate",
+                format!("{:.4}", m.block_cache_hit_rate),
+            ),
+            ("filter_skip_rate", format!("{:.4}", m.filter_skip_rate)),
+            (
+                "avg_sstables_per_get",
+                format!("{:.2}", m.avg_sstables_per_get),
+            ),
+        ],
+        _ => {
+            let files = engine.live_files();
+            vec![
+                ("flushes", m.flushes.to_string()),
+                ("sstables", engine.sstable_count().to_string()),
+                ("sstable_bytes", m.sstable_bytes.to_string()),
+                ("l0_tables", m.l0_tables.to_string()),
+                (
+                    "sstables_per_tier",
+                    m.sstables_per_tier
+                        .iter()
+                        .map(u64::to_string)
+                        .collect::<Vec<_>>()
+                        .join(","),
+                ),
+                (
+                    "pending_compaction_bytes",
+                    m.pending_compaction_bytes.to_string(),
+                ),
+                ("obsolete_bytes", m.obsolete_bytes.to_string()),
+                (
+                    "oldest_sstable_age_secs",
+                    oldest_age_secs(&files).to_string(),
+                ),
+                (
+                    "unread_sstables",
+                    files
+                        .iter()
+                        .filter(|file| file.num_reads == 0)
+                        .count()
+                        .to_string(),
+                ),
+            ]
+        }
+    }
+}
+
+/// Seconds since the oldest of `files` with a known creation time was
+/// written; 0 if there is none.
+fn oldest_age_secs(files: &[LiveFile]) -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    files
+        .iter()
+        .map(|file| file.creation_time)
+        .filter(|&created| created > 0)
+        .min()
+        .map_or(0, |created| now.saturating_sub(created))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn test_sections() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = Oblivion::open(Config::new(dir.path())).unwrap();
+        engine.put(b"k".to_vec(), b"v".to_vec()).unwrap();
+
+        let all = sections(&engine, None).unwrap();
+        let names: Vec<&str> = all.iter().map(|s| s.name).collect();
+        assert_eq!(names, SECTIONS);
+        let stats = sections(&engine, Some("stats")).unwrap();
+        assert_eq!(stats.len(), 1);
+        assert!(stats[0].fields.contains(&("puts", "1".to_string())));
+        engine.flush().unwrap();
+        let compaction = sections(&engine, Some("compaction")).unwrap();
+        assert!(compaction[0]
+            .fields
+            .contains(&("unread_sstables", "1".to_string())));
+        assert!(sections(&engine, Some("keyspace")).is_none());
+    }
+}
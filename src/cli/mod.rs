@@ -0,0 +1,1011 @@
+//! OBLIVION - CLI Commands
+//! Parses REPL command lines and runs them against the engine.
+//!
+//! ## Commands
+//! | Command                                        | Reply                         |
+//! |------------------------------------------------|-------------------------------|
+//! | `set <key> <value>`                            | `OK`                          |
+//! | `get <key>`                                    | The value, or nil             |
+//! | `del <key>`                                    | `OK (deleted)`                |
+//! | `setex <key> <ms> <value>`                     | `OK`                          |
+//! | `expire <key> <ms>`                            | 1, or 0 if the key is missing |
+//! | `ttl <key>`                                    | Remaining ms, -1, or -2       |
+//! | `persist <key>`                                | 1, or 0 if there was no TTL   |
+//! | `append <key> <value>`                         | Length of the new value       |
+//! | `getset <key> <value>`                         | The old value, or nil         |
+//! | `getdel <key>`                                 | The deleted value, or nil     |
+//! | `strlen <key>`                                 | Length of the value, or 0     |
+//! | `scan [<cursor> [match <glob>] [where <filter>] [count <n>]]` | A page of pairs and a cursor |
+//! | `keys <glob>`                                  | Every key matching the glob   |
+//! | `hset <key> <field> <value> [<field> <value>]` | Number of new fields          |
+//! | `hget <key> <field>`                           | The field's value, or nil     |
+//! | `hdel <key> <field>...`                        | Number of fields deleted      |
+//! | `hgetall <key>`                                | Every field and its value     |
+//! | `lpush <key> <value>...`, `rpush ...`          | Length of the list            |
+//! | `lpop <key>`                                   | The first item, or nil        |
+//! | `lrange <key> <start> <stop>`                  | The items in the range        |
+//! | `sadd <key> <member>...`, `srem ...`           | Number of members changed     |
+//! | `sismember <key> <member>`                     | 1, or 0 if it is no member    |
+//! | `smembers <key>`                               | Every member                  |
+//! | `zadd <key> <score> <member> [...]`            | Number of new members         |
+//! | `zscore <key> <member>`                        | The score, or nil             |
+//! | `zrem <key> <member>...`                       | Number of members removed     |
+//! | `zrangebyscore <key> <min> <max>`              | Members and scores by score   |
+//! | `select <n>`                                   | `OK`; switches the database   |
+//! | `info [<section>]`                             | Engine statistics by section  |
+//! | `dbsize [exact]`                               | Number of keys                |
+//! | `flush`                                        | `OK (flushed)`                |
+//! | `compact [<start> <end>]`                      | `OK (compacted)`              |
+//! | `bgsave <dir>`                                 | `OK (checkpoint in <dir>)`    |
+//! | `metrics`                                      | The full metrics report       |
+//! | `output [pretty\|raw\|json]`                   | Shows or sets the format      |
+//! | `exit`                                         | Stops the session             |
+//!
+//! ## Databases
+//! A session starts in logical database 0; `select` switches the key
+//! commands (`set` through `zrangebyscore`) to another of the configured
+//! `databases` (see `engine::database`), as Redis' `SELECT` does. The
+//! administration commands cover every database at once.
+//!
+//! ## Administration
+//! `flush`, `compact` and `bgsave` run on the shell's thread and
+//! return once done. `compact` with a range only merges the SSTables
+//! holding keys in `[start, end]`. `bgsave` writes a checkpoint (see
+//! `Oblivion::create_checkpoint`) into a directory that must not exist
+//! yet; unlike Redis' `BGSAVE` it blocks writes while it links files.
+//!
+//! `dbsize` answers at once with an estimate from table properties and
+//! MemTable counters (see `Oblivion::dbsize_estimate`), which overwrites
+//! and deletes not yet compacted can throw off. `dbsize exact` counts
+//! the keys with a full scan instead, which takes as long as reading
+//! the whole store.
+//!
+//! ## Expiry
+//! TTLs are given and reported in milliseconds, the engine's unit,
+//! where Redis uses seconds for `SETEX`, `EXPIRE` and `TTL`. `ttl`
+//! answers -1 for a key without a TTL and -2 for a missing key, as in
+//! Redis.
+//!
+//! ## Data Types
+//! `hset`, `hget`, `hdel` and `hgetall` work on hashes (see
+//! `engine::hash`): several fields under one key, changed one field at
+//! a time. `hset` and `hdel` apply all their fields in one batch.
+//! `lpush`, `rpush`, `lpop` and `lrange` work on lists (see
+//! `engine::list`); `rpush` and `lpop` make a queue. `lrange` indexes
+//! start at 0 and may be negative to count from the end, so
+//! `lrange jobs 0 -1` lists everything. The `s` commands work on sets
+//! (`engine::set`) and the `z` commands on sorted sets
+//! (`engine::sorted_set`), whose scores are floats; `zrangebyscore`
+//! bounds are inclusive and may be `-inf` or `inf`. None of these are
+//! plain keys, so `get`, `del`, `scan` and `keys` do not see them.
+//!
+//! ## Scanning
+//! `scan` pages through the store as Redis' `SCAN` does: start with
+//! cursor 0 and pass each returned cursor back until it is 0 again.
+//! A page reads about `count` (default [`DEFAULT_SCAN_COUNT`]) pairs
+//! and keeps those matching the `match` glob and the `where` filter
+//! expression (see the [`predicate` module](crate::engine::predicate)),
+//! so it can come back short or empty before the end. Cursors belong to the [`Session`],
+//! which remembers the latest [`MAX_CURSORS`].
+//!
+//! `keys` returns every matching key at once (see `Oblivion::keys`).
+//! Like `scan`, it only reads keys sharing the pattern's literal
+//! prefix, so `keys user:*:settings` stays cheap when `user:` is a
+//! small part of the store; `keys *` reads everything.
+//!
+//! Lines are split by [`tokenizer`], so keys and values can be quoted
+//! and hold any byte (`set "user name" "a\x00b"`). For compatibility
+//! with unquoted values, `set` joins words after the key with single
+//! spaces.
+//!
+//! Commands return a [`Reply`], which [`output`] renders for a person
+//! at the prompt or for a script in batch mode.
+//!
+//! Offline tools that inspect files without opening a database, such
+//! as [`sst_dump`], [`wal_dump`] and [`repair`], live here too and are run as subcommands.
+
+pub mod editor;
+pub mod info;
+pub mod output;
+pub mod repair;
+pub mod sst_dump;
+pub mod tokenizer;
+pub mod wal_dump;
+
+use std::collections::HashMap;
+use std::sync::atomic::AtomicBool;
+
+use crate::engine::database::Database;
+use crate::engine::hash::Field;
+use crate::engine::options::ReadOptions;
+use crate::engine::predicate::Predicate;
+use crate::engine::Oblivion;
+use crate::types::{Key, Value};
+use output::Format;
+
+/// Pairs read per `scan` page without `count`, as in Redis.
+pub const DEFAULT_SCAN_COUNT: usize = 10;
+
+/// Scan cursors a session remembers; older ones become invalid.
+pub const MAX_CURSORS: u64 = 1024;
+
+const SCAN_USAGE: &str = "scan <cursor> [match <pattern>] [where <filter>] [count <n>]";
+
+const HSET_USAGE: &str = "hset <key> <field> <value> [<field> <value> ...]";
+
+const LRANGE_USAGE: &str = "lrange <key> <start> <stop>";
+
+const ZADD_USAGE: &str = "zadd <key> <score> <member> [<score> <member> ...]";
+
+const ZRANGEBYSCORE_USAGE: &str = "zrangebyscore <key> <min> <max>";
+
+/// Command names, as completed by the shell (aliases are left out).
+pub const COMMANDS: &[&str] = &[
+    "set",
+    "get",
+    "del",
+    "setex",
+    "expire",
+    "ttl",
+    "persist",
+    "append",
+    "getset",
+    "getdel",
+    "strlen",
+    "scan",
+    "keys",
+    "hset",
+    "hget",
+    "hdel",
+    "hgetall",
+    "lpush",
+    "rpush",
+    "lpop",
+    "lrange",
+    "sadd",
+    "srem",
+    "sismember",
+    "smembers",
+    "zadd",
+    "zscore",
+    "zrem",
+    "zrangebyscore",
+    "select",
+    "info",
+    "dbsize",
+    "flush",
+    "compact",
+    "bgsave",
+    "metrics",
+    "output",
+    "exit",
+];
+
+/// The outcome of one command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reply {
+    /// The command succeeded; the text describes what it did.
+    Status(String),
+    /// A value that was found.
+    Value(Value),
+    /// A lookup that found nothing.
+    Nil,
+    /// A count or a duration.
+    Integer(i64),
+    /// One page of a scan: pairs in key order, and the cursor of the
+    /// next page (0 once the scan is complete).
+    Page {
+        cursor: u64,
+        entries: Vec<(Key, Value)>,
+    },
+    /// Keys, in order.
+    Keys(Vec<Key>),
+    /// The fields of a hash and their values, or the members of a
+    /// sorted set and their scores, in order.
+    Fields(Vec<Field>),
+    /// Items of a list or members of a set, in order.
+    Values(Vec<Value>),
+    /// Preformatted text, printed as is.
+    Text(String),
+    /// Sections of named statistics, in display order.
+    Info(Vec<info::Section>),
+    /// The command was called with the wrong arguments.
+    Usage(&'static str),
+    /// The command is not known.
+    Unknown(String),
+    /// The engine refused or failed the command.
+    Error(String),
+    /// The session should end.
+    Exit,
+}
+
+impl Reply {
+    /// Returns whether the command failed, which stops a batch.
+    pub fn is_failure(&self) -> bool {
+        matches!(self, Reply::Usage(_) | Reply::Unknown(_) | Reply::Error(_))
+    }
+}
+
+/// The state one shell or batch keeps between commands.
+#[derive(Debug, Default)]
+pub struct Session {
+    /// How replies are rendered; changed by `output`.
+    format: Format,
+    /// Logical database the key commands use; changed by `select`.
+    database: usize,
+    /// Id of the latest scan cursor handed out.
+    last_cursor: u64,
+    /// Where each remembered cursor resumes.
+    resume_keys: HashMap<u64, Key>,
+}
+
+impl Session {
+    /// Create a session that renders replies in `format`, with no open
+    /// scans.
+    pub fn new(format: Format) -> Self {
+        Self {
+            format,
+            ..Self::default()
+        }
+    }
+
+    /// Returns the output format, as last set by `output`.
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Returns the logical database, as last set by `select`.
+    pub fn database(&self) -> usize {
+        self.database
+    }
+
+    /// Run one command line. Returns `None` for a blank line or a
+    /// comment (a line starting with `#`).
+    pub fn execute(&mut self, engine: &Oblivion, line: &str) -> Option<Reply> {
+        if line.trim_start().starts_with('#') {
+            return None;
+        }
+        let parts = match tokenizer::tokenize(line) {
+            Ok(parts) if parts.is_empty() => return None,
+            Ok(parts) => parts,
+            Err(e) => return Some(Reply::Error(format!("syntax error: {}", e))),
+        };
+        let name = String::from_utf8_lossy(&parts[0]).to_lowercase();
+        let db = match engine.database(self.database) {
+            Ok(db) => db,
+            Err(e) => return Some(Reply::Error(e.to_string())),
+        };
+
+        let reply = match name.as_str() {
+            "set" | "put" => {
+                if parts.len() < 3 {
+                    return Some(Reply::Usage("set <key> <value>"));
+                }
+                let key = parts[1].clone();
+                let value = parts[2..].join(&b' ');
+                match db.put(key, value) {
+                    Ok(()) => Reply::Status("OK".to_string()),
+                    Err(e) => Reply::Error(e.to_string()),
+                }
+            }
+            "get" => {
+                if parts.len() < 2 {
+                    return Some(Reply::Usage("get <key>"));
+                }
+                match db.get(&parts[1]) {
+                    Ok(Some(value)) => Reply::Value(value),
+                    Ok(None) => Reply::Nil,
+                    Err(e) => Reply::Error(e.to_string()),
+                }
+            }
+            "del" | "delete" => {
+                if parts.len() < 2 {
+                    return Some(Reply::Usage("del <key>"));
+                }
+                match db.delete(parts[1].clone()) {
+                    Ok(()) => Reply::Status("OK (deleted)".to_string()),
+                    Err(e) => Reply::Error(e.to_string()),
+                }
+            }
+            "setex" => {
+                if parts.len() < 4 {
+                    return Some(Reply::Usage("setex <key> <ttl_ms> <value>"));
+                }
+                let Some(ttl_ms) = parse_number(&parts[2]) else {
+                    return Some(Reply::Usage("setex <key> <ttl_ms> <value>"));
+                };
+                let value = parts[3..].join(&b' ');
+                match db.put_with_ttl(parts[1].clone(), value, ttl_ms) {
+                    Ok(()) => Reply::Status("OK".to_string()),
+                    Err(e) => Reply::Error(e.to_string()),
+                }
+            }
+            "expire" => {
+                let [_, key, ttl_ms] = parts.as_slice() else {
+                    return Some(Reply::Usage("expire <key> <ttl_ms>"));
+                };
+                let Some(ttl_ms) = parse_number(ttl_ms) else {
+                    return Some(Reply::Usage("expire <key> <ttl_ms>"));
+                };
+                match db.expire(key, ttl_ms) {
+                    Ok(found) => Reply::Integer(found as i64),
+                    Err(e) => Reply::Error(e.to_string()),
+                }
+            }
+            "ttl" => {
+                let [_, key] = parts.as_slice() else {
+                    return Some(Reply::Usage("ttl <key>"));
+                };
+                match (db.ttl(key), db.get(key)) {
+                    (_, Err(e)) => Reply::Error(e.to_string()),
+                    (_, Ok(None)) => Reply::Integer(-2),
+                    (Some(ttl_ms), Ok(Some(_))) => Reply::Integer(ttl_ms as i64),
+                    (None, Ok(Some(_))) => Reply::Integer(-1),
+                }
+            }
+            "persist" => {
+                let [_, key] = parts.as_slice() else {
+                    return Some(Reply::Usage("persist <key>"));
+                };
+                match db.persist(key) {
+                    Ok(removed) => Reply::Integer(removed as i64),
+                    Err(e) => Reply::Error(e.to_string()),
+                }
+            }
+            "append" => {
+                let [_, key, suffix] = parts.as_slice() else {
+                    return Some(Reply::Usage("append <key> <value>"));
+                };
+                match db.append(key.clone(), suffix) {
+                    Ok(len) => Reply::Integer(len as i64),
+                    Err(e) => Reply::Error(e.to_string()),
+                }
+            }
+            "getset" => {
+                let [_, key, value] = parts.as_slice() else {
+                    return Some(Reply::Usage("getset <key> <value>"));
+                };
+                match db.getset(key.clone(), value.clone()) {
+                    Ok(Some(old)) => Reply::Value(old),
+                    Ok(None) => Reply::Nil,
+                    Err(e) => Reply::Error(e.to_string()),
+                }
+            }
+            "getdel" => {
+                let [_, key] = parts.as_slice() else {
+                    return Some(Reply::Usage("getdel <key>"));
+                };
+                match db.getdel(key.clone()) {
+                    Ok(Some(old)) => Reply::Value(old),
+                    Ok(None) => Reply::Nil,
+                    Err(e) => Reply::Error(e.to_string()),
+                }
+            }
+            "strlen" => {
+                let [_, key] = parts.as_slice() else {
+                    return Some(Reply::Usage("strlen <key>"));
+                };
+                match db.strlen(key) {
+                    Ok(len) => Reply::Integer(len as i64),
+                    Err(e) => Reply::Error(e.to_string()),
+                }
+            }
+            "scan" | "list" => self.scan(&db, &parts[1..]),
+            "keys" => {
+                let [_, pattern] = parts.as_slice() else {
+                    return Some(Reply::Usage("keys <pattern>"));
+                };
+                match db.keys(pattern) {
+                    Ok(keys) => Reply::Keys(keys),
+                    Err(e) => Reply::Error(e.to_string()),
+                }
+            }
+            "hset" => {
+                let [_, key, fields @ ..] = parts.as_slice() else {
+                    return Some(Reply::Usage(HSET_USAGE));
+                };
+                if fields.is_empty() || fields.len() % 2 != 0 {
+                    return Some(Reply::Usage(HSET_USAGE));
+                }
+                let fields = fields
+                    .chunks(2)
+                    .map(|pair| (pair[0].clone(), pair[1].clone()))
+                    .collect();
+                match db.hset(key, fields) {
+                    Ok(added) => Reply::Integer(added as i64),
+                    Err(e) => Reply::Error(e.to_string()),
+                }
+            }
+            "hget" => {
+                let [_, key, field] = parts.as_slice() else {
+                    return Some(Reply::Usage("hget <key> <field>"));
+                };
+                match db.hget(key, field) {
+                    Ok(Some(value)) => Reply::Value(value),
+                    Ok(None) => Reply::Nil,
+                    Err(e) => Reply::Error(e.to_string()),
+                }
+            }
+            "hdel" => {
+                let [_, key, fields @ ..] = parts.as_slice() else {
+                    return Some(Reply::Usage("hdel <key> <field>..."));
+                };
+                if fields.is_empty() {
+                    return Some(Reply::Usage("hdel <key> <field>..."));
+                }
+                match db.hdel(key, fields) {
+                    Ok(removed) => Reply::Integer(removed as i64),
+                    Err(e) => Reply::Error(e.to_string()),
+                }
+            }
+            "hgetall" => {
+                let [_, key] = parts.as_slice() else {
+                    return Some(Reply::Usage("hgetall <key>"));
+                };
+                match db.hgetall(key) {
+                    Ok(fields) => Reply::Fields(fields),
+                    Err(e) => Reply::Error(e.to_string()),
+                }
+            }
+            "lpush" | "rpush" => {
+                let [_, key, values @ ..] = parts.as_slice() else {
+                    return Some(Reply::Usage("lpush|rpush <key> <value>..."));
+                };
+                if values.is_empty() {
+                    return Some(Reply::Usage("lpush|rpush <key> <value>..."));
+                }
+                let pushed = match name.as_str() {
+                    "lpush" => db.lpush(key, values.to_vec()),
+                    _ => db.rpush(key, values.to_vec()),
+                };
+                match pushed {
+                    Ok(len) => Reply::Integer(len as i64),
+                    Err(e) => Reply::Error(e.to_string()),
+                }
+            }
+            "lpop" => {
+                let [_, key] = parts.as_slice() else {
+                    return Some(Reply::Usage("lpop <key>"));
+                };
+                match db.lpop(key) {
+                    Ok(Some(value)) => Reply::Value(value),
+                    Ok(None) => Reply::Nil,
+                    Err(e) => Reply::Error(e.to_string()),
+                }
+            }
+            "lrange" => {
+                let [_, key, start, stop] = parts.as_slice() else {
+                    return Some(Reply::Usage(LRANGE_USAGE));
+                };
+                let (Some(start), Some(stop)) = (parse_index(start), parse_index(stop)) else {
+                    return Some(Reply::Usage(LRANGE_USAGE));
+                };
+                match db.lrange(key, start, stop) {
+                    Ok(values) => Reply::Values(values),
+                    Err(e) => Reply::Error(e.to_string()),
+                }
+            }
+            "sadd" | "srem" | "zrem" => {
+                let [_, key, members @ ..] = parts.as_slice() else {
+                    return Some(Reply::Usage("sadd|srem|zrem <key> <member>..."));
+                };
+                if members.is_empty() {
+                    return Some(Reply::Usage("sadd|srem|zrem <key> <member>..."));
+                }
+                let changed = match name.as_str() {
+                    "sadd" => db.sadd(key, members),
+                    "srem" => db.srem(key, members),
+                    _ => db.zrem(key, members),
+                };
+                match changed {
+                    Ok(count) => Reply::Integer(count as i64),
+                    Err(e) => Reply::Error(e.to_string()),
+                }
+            }
+            "sismember" => {
+                let [_, key, member] = parts.as_slice() else {
+                    return Some(Reply::Usage("sismember <key> <member>"));
+                };
+                match db.sismember(key, member) {
+                    Ok(found) => Reply::Integer(found as i64),
+                    Err(e) => Reply::Error(e.to_string()),
+                }
+            }
+            "smembers" => {
+                let [_, key] = parts.as_slice() else {
+                    return Some(Reply::Usage("smembers <key>"));
+                };
+                match db.smembers(key) {
+                    Ok(members) => Reply::Values(members),
+                    Err(e) => Reply::Error(e.to_string()),
+                }
+            }
+            "zadd" => {
+                let [_, key, pairs @ ..] = parts.as_slice() else {
+                    return Some(Reply::Usage(ZADD_USAGE));
+                };
+                if pairs.is_empty() || pairs.len() % 2 != 0 {
+                    return Some(Reply::Usage(ZADD_USAGE));
+                }
+                let mut members = Vec::with_capacity(pairs.len() / 2);
+                for pair in pairs.chunks(2) {
+                    let Some(score) = parse_score(&pair[0]) else {
+                        return Some(Reply::Usage(ZADD_USAGE));
+                    };
+                    members.push((pair[1].clone(), score));
+                }
+                match db.zadd(key, members) {
+                    Ok(added) => Reply::Integer(added as i64),
+                    Err(e) => Reply::Error(e.to_string()),
+                }
+            }
+            "zscore" => {
+                let [_, key, member] = parts.as_slice() else {
+                    return Some(Reply::Usage("zscore <key> <member>"));
+                };
+                match db.zscore(key, member) {
+                    Ok(Some(score)) => Reply::Value(score.to_string().into_bytes()),
+                    Ok(None) => Reply::Nil,
+                    Err(e) => Reply::Error(e.to_string()),
+                }
+            }
+            "zrangebyscore" => {
+                let [_, key, min, max] = parts.as_slice() else {
+                    return Some(Reply::Usage(ZRANGEBYSCORE_USAGE));
+                };
+                let (Some(min), Some(max)) = (parse_score(min), parse_score(max)) else {
+                    return Some(Reply::Usage(ZRANGEBYSCORE_USAGE));
+                };
+                match db.zrangebyscore(key, min, max) {
+                    Ok(members) => Reply::Fields(
+                        members
+                            .into_iter()
+                            .map(|(member, score)| (member, score.to_string().into_bytes()))
+                            .collect(),
+                    ),
+                    Err(e) => Reply::Error(e.to_string()),
+                }
+            }
+            "select" => {
+                let [_, index] = parts.as_slice() else {
+                    return Some(Reply::Usage("select <n>"));
+                };
+                let Some(index) = parse_number(index) else {
+                    return Some(Reply::Usage("select <n>"));
+                };
+                match engine.database(index as usize) {
+                    Ok(db) => {
+                        self.database = db.index();
+                        Reply::Status("OK".to_string())
+                    }
+                    Err(e) => Reply::Error(e.to_string()),
+                }
+            }
+            "info" | "stats" => {
+                let filter = match parts.as_slice() {
+                    [_] => None,
+                    [_, section] => Some(String::from_utf8_lossy(section).to_lowercase()),
+                    _ => return Some(Reply::Usage("info [section]")),
+                };
+                match info::sections(engine, filter.as_deref()) {
+                    Some(sections) => Reply::Info(sections),
+                    None => Reply::Error(format!(
+                        "unknown section; expected one of: {}",
+                        info::SECTIONS.join(", ")
+                    )),
+                }
+            }
+            "dbsize" => {
+                let result = match parts.as_slice() {
+                    [_] => Ok(engine.dbsize_estimate()),
+                    [_, mode] if mode.eq_ignore_ascii_case(b"exact") => {
+                        engine.dbsize_exact(&AtomicBool::new(false))
+                    }
+                    _ => return Some(Reply::Usage("dbsize [exact]")),
+                };
+                match result {
+                    Ok(count) => Reply::Integer(count as i64),
+                    Err(e) => Reply::Error(e.to_string()),
+                }
+            }
+            "flush" => match engine.flush() {
+                Ok(()) => Reply::Status("OK (flushed)".to_string()),
+                Err(e) => Reply::Error(e.to_string()),
+            },
+            "compact" => {
+                let result = match parts.as_slice() {
+                    [_] => engine.compact(),
+                    [_, start, end] => engine.compact_range(Some(start), Some(end)),
+                    _ => return Some(Reply::Usage("compact [<start> <end>]")),
+                };
+                match result {
+                    Ok(()) => Reply::Status("OK (compacted)".to_string()),
+                    Err(e) => Reply::Error(e.to_string()),
+                }
+            }
+            "bgsave" | "checkpoint" => {
+                let [_, dir] = parts.as_slice() else {
+                    return Some(Reply::Usage("bgsave <dir>"));
+                };
+                let dir = String::from_utf8_lossy(dir).into_owned();
+                match engine.create_checkpoint(&dir) {
+                    Ok(()) => Reply::Status(format!("OK (checkpoint in {})", dir)),
+                    Err(e) => Reply::Error(e.to_string()),
+                }
+            }
+            "metrics" => Reply::Text(engine.metrics().report()),
+            "output" => match parts.as_slice() {
+                [_] => Reply::Status(self.format.to_string()),
+                [_, format] => match String::from_utf8_lossy(format).to_lowercase().parse() {
+                    Ok(format) => {
+                        self.format = format;
+                        Reply::Status("OK".to_string())
+                    }
+                    Err(e) => Reply::Error(e),
+                },
+                _ => return Some(Reply::Usage("output [pretty|raw|json]")),
+            },
+            "exit" | "quit" | "q" => Reply::Exit,
+            _ => Reply::Unknown(String::from_utf8_lossy(&parts[0]).into_owned()),
+        };
+        Some(reply)
+    }
+
+    /// `scan [<cursor> [match <glob>] [where <filter>] [count <n>]]`:
+    /// one page of pairs.
+    fn scan(&mut self, db: &Database, args: &[Vec<u8>]) -> Reply {
+        let (cursor, mut options) = match args {
+            [] => (0, [].iter()),
+            [cursor, options @ ..] => match parse_number(cursor) {
+                Some(cursor) => (cursor, options.iter()),
+                None => return Reply::Usage(SCAN_USAGE),
+            },
+        };
+        let mut filters = Vec::new();
+        let mut count = DEFAULT_SCAN_COUNT;
+        while let Some(option) = options.next() {
+            let Some(value) = options.next() else {
+                return Reply::Usage(SCAN_USAGE);
+            };
+            match (option.to_ascii_lowercase().as_slice(), parse_number(value)) {
+                (b"match", _) => filters.push(Predicate::key_glob(value.as_slice())),
+                (b"where", _) => match std::str::from_utf8(value).map(Predicate::parse) {
+                    Ok(Ok(filter)) => filters.push(filter),
+                    Ok(Err(e)) => return Reply::Error(e.to_string()),
+                    Err(_) => return Reply::Error("filter expression is not UTF-8".into()),
+                },
+                (b"count", Some(n)) if n >= 1 => count = n as usize,
+                _ => return Reply::Usage(SCAN_USAGE),
+            }
+        }
+        let filter = Predicate::And(filters);
+
+        // Only keys starting with the filter's required prefix can match
+        let comparator = db.engine().comparator();
+        let mut opts = ReadOptions::new().fill_cache(false);
+        filter.narrow(&mut opts, comparator);
+        if cursor != 0 {
+            let Some(resume) = self.resume_keys.get(&cursor) else {
+                return Reply::Error(format!("invalid or expired cursor {}", cursor));
+            };
+            if opts
+                .lower_bound
+                .as_ref()
+                .is_none_or(|lower| comparator.compare(resume, lower).is_gt())
+            {
+                opts.lower_bound = Some(resume.clone());
+            }
+        }
+        match db.scan_page(&opts, count) {
+            Ok((rows, resume)) => Reply::Page {
+                cursor: resume.map_or(0, |resume| self.save_cursor(resume)),
+                entries: rows
+                    .into_iter()
+                    .filter(|(key, value)| filter.matches(key, value))
+                    .collect(),
+            },
+            Err(e) => Reply::Error(e.to_string()),
+        }
+    }
+
+    /// Remember `resume` under a new cursor id, forgetting the oldest
+    /// beyond [`MAX_CURSORS`].
+    fn save_cursor(&mut self, resume: Key) -> u64 {
+        self.last_cursor += 1;
+        self.resume_keys.insert(self.last_cursor, resume);
+        if self.last_cursor > MAX_CURSORS {
+            self.resume_keys.remove(&(self.last_cursor - MAX_CURSORS));
+        }
+        self.last_cursor
+    }
+}
+
+fn parse_number(arg: &[u8]) -> Option<u64> {
+    std::str::from_utf8(arg).ok()?.parse().ok()
+}
+
+/// A list index, which may be negative.
+fn parse_index(arg: &[u8]) -> Option<i64> {
+    std::str::from_utf8(arg).ok()?.parse().ok()
+}
+
+/// A sorted-set score, such as `2.5` or `-inf`.
+fn parse_score(arg: &[u8]) -> Option<f64> {
+    std::str::from_utf8(arg).ok()?.parse().ok()
+}
+
+/// Returns the key a command line names, for the shell to remember.
+pub fn key_argument(line: &str) -> Option<Vec<u8>> {
+    let mut parts = tokenizer::tokenize(line).ok()?.into_iter();
+    let name = String::from_utf8_lossy(&parts.next()?).to_lowercase();
+    match name.as_str() {
+        "set" | "put" | "get" | "del" | "delete" | "setex" | "expire" | "ttl" | "persist"
+        | "append" | "getset" | "getdel" | "strlen" | "hset" | "hget" | "hdel" | "hgetall"
+        | "lpush" | "rpush" | "lpop" | "lrange" | "sadd" | "srem" | "sismember" | "smembers"
+        | "zadd" | "zscore" | "zrem" | "zrangebyscore" => parts.next(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn test_execute_commands() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = Oblivion::open(Config::new(dir.path())).unwrap();
+        let mut session = Session::new(Format::Pretty);
+        let mut execute = |line: &str| session.execute(&engine, line);
+        assert_eq!(execute("   "), None);
+        assert_eq!(execute("# setup"), None);
+        assert_eq!(
+            execute("set greeting hello world"),
+            Some(Reply::Status("OK".to_string()))
+        );
+        assert_eq!(
+            execute("GET greeting"),
+            Some(Reply::Value(b"hello world".to_vec()))
+        );
+        assert_eq!(execute("get"), Some(Reply::Usage("get <key>")));
+        assert!(execute("frobnicate").unwrap().is_failure());
+        assert_eq!(
+            execute("scan"),
+            Some(Reply::Page {
+                cursor: 0,
+                entries: vec![(b"greeting".to_vec(), b"hello world".to_vec())]
+            })
+        );
+        execute("del greeting");
+        assert_eq!(execute("get greeting"), Some(Reply::Nil));
+
+        execute(r#"set "two words\x00" '  padded  '"#);
+        assert_eq!(
+            execute(r"get two\ words\x00"),
+            Some(Reply::Value(b"  padded  ".to_vec()))
+        );
+        execute(r#"del "two words\x00""#);
+        assert_eq!(
+            execute("scan"),
+            Some(Reply::Page {
+                cursor: 0,
+                entries: Vec::new()
+            })
+        );
+        execute("set user:1:name ada");
+        execute("set user:1:settings {}");
+        execute("set user:2:settings {}");
+        assert_eq!(
+            execute("keys user:*:settings"),
+            Some(Reply::Keys(vec![
+                b"user:1:settings".to_vec(),
+                b"user:2:settings".to_vec()
+            ]))
+        );
+        assert_eq!(execute("keys nothing*"), Some(Reply::Keys(Vec::new())));
+        assert_eq!(execute("keys"), Some(Reply::Usage("keys <pattern>")));
+        assert!(matches!(
+            execute(r#"get "open"#),
+            Some(Reply::Error(e)) if e.contains("unbalanced quotes")
+        ));
+        assert_eq!(
+            execute("output json"),
+            Some(Reply::Status("OK".to_string()))
+        );
+        assert_eq!(execute("output"), Some(Reply::Status("json".to_string())));
+        assert!(execute("output yaml").unwrap().is_failure());
+        assert_eq!(execute("select 1"), Some(Reply::Status("OK".to_string())));
+        assert_eq!(execute("keys *"), Some(Reply::Keys(Vec::new())));
+        execute("set user:1:name grace");
+        assert_eq!(execute("select 0"), Some(Reply::Status("OK".to_string())));
+        assert_eq!(
+            execute("get user:1:name"),
+            Some(Reply::Value(b"ada".to_vec()))
+        );
+        assert!(execute("select 16").unwrap().is_failure());
+        assert_eq!(execute("hset h b 1 a 2"), Some(Reply::Integer(2)));
+        assert_eq!(execute("hset h a 3"), Some(Reply::Integer(0)));
+        assert_eq!(execute("hget h a"), Some(Reply::Value(b"3".to_vec())));
+        assert_eq!(execute("hdel h b z"), Some(Reply::Integer(1)));
+        assert_eq!(
+            execute("hgetall h"),
+            Some(Reply::Fields(vec![(b"a".to_vec(), b"3".to_vec())]))
+        );
+        assert_eq!(execute("hget h b"), Some(Reply::Nil));
+        assert_eq!(execute("hset h a"), Some(Reply::Usage(HSET_USAGE)));
+        assert_eq!(execute("keys h*"), Some(Reply::Keys(Vec::new())));
+        assert_eq!(execute("rpush jobs b c"), Some(Reply::Integer(2)));
+        assert_eq!(execute("lpush jobs a"), Some(Reply::Integer(3)));
+        assert_eq!(execute("lpop jobs"), Some(Reply::Value(b"a".to_vec())));
+        assert_eq!(
+            execute("lrange jobs 0 -1"),
+            Some(Reply::Values(vec![b"b".to_vec(), b"c".to_vec()]))
+        );
+        assert_eq!(execute("lrange jobs 0 x"), Some(Reply::Usage(LRANGE_USAGE)));
+        assert_eq!(execute("sadd tags b a b"), Some(Reply::Integer(2)));
+        assert_eq!(execute("sismember tags a"), Some(Reply::Integer(1)));
+        assert_eq!(
+            execute("smembers tags"),
+            Some(Reply::Values(vec![b"a".to_vec(), b"b".to_vec()]))
+        );
+        assert_eq!(
+            execute("zadd board 2.5 bob 10 ada"),
+            Some(Reply::Integer(2))
+        );
+        assert_eq!(
+            execute("zscore board bob"),
+            Some(Reply::Value(b"2.5".to_vec()))
+        );
+        assert_eq!(
+            execute("zrangebyscore board 5 inf"),
+            Some(Reply::Fields(vec![(b"ada".to_vec(), b"10".to_vec())]))
+        );
+        assert_eq!(execute("zrem board ada"), Some(Reply::Integer(1)));
+        assert_eq!(execute("zadd board x bob"), Some(Reply::Usage(ZADD_USAGE)));
+        assert_eq!(execute("select"), Some(Reply::Usage("select <n>")));
+        assert_eq!(execute("quit"), Some(Reply::Exit));
+        assert_eq!(key_argument("GET 'a b'"), Some(b"a b".to_vec()));
+        assert_eq!(key_argument("scan"), None);
+    }
+
+    #[test]
+    fn test_admin_commands() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = Oblivion::open(Config::new(dir.path().join("db"))).unwrap();
+        let mut session = Session::new(Format::Pretty);
+        let mut execute = |line: &str| session.execute(&engine, line).unwrap();
+
+        execute("set a 1");
+        assert_eq!(execute("flush"), Reply::Status("OK (flushed)".into()));
+        execute("set b 2");
+        execute("flush");
+        assert_eq!(engine.sstable_count(), 2);
+        execute("set c 3");
+        execute("del a");
+        assert_eq!(execute("dbsize"), Reply::Integer(2));
+        assert_eq!(execute("dbsize EXACT"), Reply::Integer(2));
+        assert_eq!(execute("dbsize all"), Reply::Usage("dbsize [exact]"));
+        assert_eq!(
+            execute("compact a a"),
+            Reply::Status("OK (compacted)".into())
+        );
+        assert_eq!(engine.sstable_count(), 2);
+        execute("compact");
+        assert_eq!(engine.sstable_count(), 1);
+        assert_eq!(
+            execute("compact a"),
+            Reply::Usage("compact [<start> <end>]")
+        );
+
+        let checkpoint = dir.path().join("checkpoint");
+        let line = format!("bgsave {}", checkpoint.display());
+        assert!(matches!(execute(&line), Reply::Status(_)));
+        assert!(execute(&line).is_failure());
+        let copy = Oblivion::open(Config::new(&checkpoint)).unwrap();
+        assert_eq!(copy.get(b"b"), Some(b"2".to_vec()));
+        assert!(matches!(execute("metrics"), Reply::Text(report) if report.contains("puts")));
+    }
+
+    #[test]
+    fn test_ttl_commands() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = Oblivion::open(Config::new(dir.path())).unwrap();
+        let mut session = Session::new(Format::Pretty);
+        let mut execute = |line: &str| session.execute(&engine, line).unwrap();
+
+        assert_eq!(execute("setex token 60000 a b"), Reply::Status("OK".into()));
+        assert_eq!(execute("get token"), Reply::Value(b"a b".to_vec()));
+        assert!(matches!(execute("ttl token"), Reply::Integer(ms) if ms > 59_000 && ms <= 60_000));
+        assert_eq!(execute("persist token"), Reply::Integer(1));
+        assert_eq!(execute("persist token"), Reply::Integer(0));
+        assert_eq!(execute("ttl token"), Reply::Integer(-1));
+        assert_eq!(execute("ttl missing"), Reply::Integer(-2));
+        assert_eq!(execute("expire missing 10"), Reply::Integer(0));
+        assert_eq!(execute("expire token 1"), Reply::Integer(1));
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert_eq!(execute("get token"), Reply::Nil);
+        assert_eq!(
+            execute("setex k soon v"),
+            Reply::Usage("setex <key> <ttl_ms> <value>")
+        );
+        assert_eq!(execute("ttl"), Reply::Usage("ttl <key>"));
+    }
+
+    #[test]
+    fn test_string_commands() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = Oblivion::open(Config::new(dir.path())).unwrap();
+        let mut session = Session::new(Format::Pretty);
+        let mut execute = |line: &str| session.execute(&engine, line).unwrap();
+
+        assert_eq!(execute("append log \"a \""), Reply::Integer(2));
+        assert_eq!(execute("append log b"), Reply::Integer(3));
+        assert_eq!(execute("strlen log"), Reply::Integer(3));
+        assert_eq!(execute("strlen missing"), Reply::Integer(0));
+        assert_eq!(execute("getset log c"), Reply::Value(b"a b".to_vec()));
+        assert_eq!(execute("getset fresh x"), Reply::Nil);
+        assert_eq!(execute("getdel log"), Reply::Value(b"c".to_vec()));
+        assert_eq!(execute("getdel log"), Reply::Nil);
+        assert_eq!(execute("get log"), Reply::Nil);
+        assert_eq!(execute("append log"), Reply::Usage("append <key> <value>"));
+    }
+
+    #[test]
+    fn test_scan_pages_with_cursor() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = Oblivion::open(Config::new(dir.path())).unwrap();
+        for i in 0..25 {
+            engine
+                .put(format!("user:{:02}", i).into_bytes(), b"v".to_vec())
+                .unwrap();
+            engine
+                .put(format!("order:{:02}", i).into_bytes(), b"v".to_vec())
+                .unwrap();
+        }
+        let mut session = Session::new(Format::Pretty);
+
+        let mut cursor = 0;
+        let mut keys = Vec::new();
+        loop {
+            let line = format!("scan {} match user:*[05] count 7", cursor);
+            let Some(Reply::Page {
+                cursor: next,
+                entries,
+            }) = session.execute(&engine, &line)
+            else {
+                panic!("scan failed");
+            };
+            assert!(entries.len() <= 7);
+            keys.extend(entries.into_iter().map(|(key, _)| key));
+            cursor = next;
+            if cursor == 0 {
+                break;
+            }
+        }
+        let expected: Vec<Key> = [0, 5, 10, 15, 20]
+            .iter()
+            .map(|i| format!("user:{:02}", i).into_bytes())
+            .collect();
+        assert_eq!(keys, expected);
+
+        let line = r#"scan 0 match order:* where "key glob '*1?' and value prefix v" count 100"#;
+        let Some(Reply::Page { cursor: 0, entries }) = session.execute(&engine, line) else {
+            panic!("filtered scan failed");
+        };
+        assert_eq!(entries.len(), 10);
+        assert!(matches!(
+            session.execute(&engine, "scan 0 where 'key like x'"),
+            Some(Reply::Error(e)) if e.contains("Invalid filter")
+        ));
+
+        assert_eq!(
+            session.execute(&engine, "scan 0 count 0"),
+            Some(Reply::Usage(SCAN_USAGE))
+        );
+        assert!(matches!(
+            session.execute(&engine, "scan 999"),
+            Some(Reply::Error(e)) if e.contains("invalid")
+        ));
+    }
+}
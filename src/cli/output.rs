@@ -0,0 +1,435 @@
+//! OBLIVION - CLI Output
+//! Renders command replies for a person or for a script.
+//!
+//! ## Formats
+//! - [`Format::Pretty`]: the interactive shell's indented, quoted form,
+//!   with keys and values escaped as the tokenizer reads them
+//! - [`Format::Raw`]: batch mode's form for scripts, like
+//!   `redis-cli --raw`: values and keys verbatim on a line of their
+//!   own, nil as an empty line, pairs and statistics as
+//!   `name<TAB>value` lines. A scan's next cursor comes on the line
+//!   before its pairs, and statistics under `# Section` lines. Failures
+//!   go to stderr, so stdout only carries results.
+//! - [`Format::Json`]: one JSON object per reply and line, for `jq`.
+//!   Values and pairs are encoded as in NDJSON exports: as text when
+//!   valid UTF-8, else in base64, named by an `encoding` field.
+//!
+//! ```text
+//! {"status":"OK"}
+//! {"encoding":"utf8","value":"alice"}
+//! {"value":null}
+//! {"integer":59998}
+//! {"cursor":0,"items":[{"encoding":"utf8","key":"user:1","value":"alice"}]}
+//! {"keys":[{"encoding":"utf8","key":"user:1"}]}
+//! {"fields":[{"encoding":"utf8","field":"name","value":"ada"}]}
+//! {"values":[{"encoding":"utf8","value":"job:1"}]}
+//! {"error":"unknown command 'frobnicate'"}
+//! ```
+
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use serde_json::{json, Map, Value as Json};
+
+use super::tokenizer::{escape, quote};
+use super::Reply;
+use crate::engine::export::encode_pair;
+
+/// How replies are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    /// Indented and quoted, for the interactive shell.
+    #[default]
+    Pretty,
+    /// Unadorned values, for scripts.
+    Raw,
+    /// JSON lines, for tools.
+    Json,
+}
+
+impl Format {
+    /// Returns the name parsed by `FromStr`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Pretty => "pretty",
+            Format::Raw => "raw",
+            Format::Json => "json",
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pretty" => Ok(Format::Pretty),
+            "raw" => Ok(Format::Raw),
+            "json" => Ok(Format::Json),
+            other => Err(format!(
+                "unknown output format '{}' (expected pretty, raw or json)",
+                other
+            )),
+        }
+    }
+}
+
+/// Write `reply` in `format`: results to `out`, failures of the raw
+/// format to `err`.
+pub fn render<O: Write, E: Write>(
+    reply: &Reply,
+    format: Format,
+    out: &mut O,
+    err: &mut E,
+) -> io::Result<()> {
+    match format {
+        Format::Pretty => pretty(reply, out),
+        Format::Raw => raw(reply, out, err),
+        Format::Json => match json(reply) {
+            Some(line) => writeln!(out, "{}", line),
+            None => Ok(()),
+        },
+    }
+}
+
+fn pretty<W: Write>(reply: &Reply, out: &mut W) -> io::Result<()> {
+    match reply {
+        Reply::Status(status) => writeln!(out, "  {}", status),
+        Reply::Value(value) => writeln!(out, "  {}", quote(value)),
+        Reply::Nil => writeln!(out, "  (nil)"),
+        Reply::Text(text) => writeln!(out, "{}", text.trim_end()),
+        Reply::Integer(n) => writeln!(out, "  (integer) {}", n),
+        Reply::Page { cursor, entries } => {
+            for (key, value) in entries {
+                writeln!(out, "  {} -> {}", escape(key), escape(value))?;
+            }
+            match (entries.len(), cursor) {
+                (0, 0) => writeln!(out, "  (empty)"),
+                (count, 0) => writeln!(out, "  ({} entries)", count),
+                (count, cursor) => {
+                    writeln!(out, "  ({} entries, next cursor {})", count, cursor)
+                }
+            }
+        }
+        Reply::Keys(keys) => {
+            for (i, key) in keys.iter().enumerate() {
+                writeln!(out, "  {}) {}", i + 1, escape(key))?;
+            }
+            match keys.len() {
+                0 => writeln!(out, "  (empty)"),
+                count => writeln!(out, "  ({} keys)", count),
+            }
+        }
+        Reply::Fields(fields) => {
+            for (field, value) in fields {
+                writeln!(out, "  {} -> {}", escape(field), escape(value))?;
+            }
+            match fields.len() {
+                0 => writeln!(out, "  (empty)"),
+                count => writeln!(out, "  ({} fields)", count),
+            }
+        }
+        Reply::Values(values) => {
+            for (i, value) in values.iter().enumerate() {
+                writeln!(out, "  {}) {}", i + 1, escape(value))?;
+            }
+            match values.len() {
+                0 => writeln!(out, "  (empty)"),
+                count => writeln!(out, "  ({} items)", count),
+            }
+        }
+        Reply::Info(sections) => {
+            for (i, section) in sections.iter().enumerate() {
+                if i > 0 {
+                    writeln!(out)?;
+                }
+                writeln!(out, "  # {}", title(section.name))?;
+                let fields = &section.fields;
+                let width = fields.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+                for (name, value) in fields {
+                    let label = format!("{}:", name);
+                    writeln!(out, "  {:<width$} {}", label, value, width = width + 1)?;
+                }
+            }
+            Ok(())
+        }
+        Reply::Usage(usage) => writeln!(out, "  Usage: {}", usage),
+        Reply::Unknown(name) => {
+            writeln!(out, "  Unknown command: '{}'. Type 'exit' to quit.", name)
+        }
+        Reply::Error(message) => writeln!(out, "  ERROR: {}", message),
+        Reply::Exit => writeln!(out, "  Shutting down OBLIVION..."),
+    }
+}
+
+fn raw<O: Write, E: Write>(reply: &Reply, out: &mut O, err: &mut E) -> io::Result<()> {
+    match reply {
+        Reply::Status(_) => writeln!(out, "OK"),
+        Reply::Value(value) => {
+            out.write_all(value)?;
+            writeln!(out)
+        }
+        Reply::Nil => writeln!(out),
+        Reply::Text(text) => writeln!(out, "{}", text.trim()),
+        Reply::Integer(n) => writeln!(out, "{}", n),
+        Reply::Page { cursor, entries } => {
+            writeln!(out, "{}", cursor)?;
+            for (key, value) in entries {
+                out.write_all(key)?;
+                out.write_all(b"\t")?;
+                out.write_all(value)?;
+                writeln!(out)?;
+            }
+            Ok(())
+        }
+        Reply::Keys(keys) => {
+            for key in keys {
+                out.write_all(key)?;
+                writeln!(out)?;
+            }
+            Ok(())
+        }
+        Reply::Values(values) => {
+            for value in values {
+                out.write_all(value)?;
+                writeln!(out)?;
+            }
+            Ok(())
+        }
+        Reply::Fields(fields) => {
+            for (field, value) in fields {
+                out.write_all(field)?;
+                out.write_all(b"\t")?;
+                out.write_all(value)?;
+                writeln!(out)?;
+            }
+            Ok(())
+        }
+        Reply::Info(sections) => {
+            for section in sections {
+                writeln!(out, "# {}", title(section.name))?;
+                for (name, value) in &section.fields {
+                    writeln!(out, "{}\t{}", name, value)?;
+                }
+            }
+            Ok(())
+        }
+        Reply::Usage(usage) => writeln!(err, "usage: {}", usage),
+        Reply::Unknown(name) => writeln!(err, "unknown command '{}'", name),
+        Reply::Error(message) => writeln!(err, "ERROR: {}", message),
+        Reply::Exit => Ok(()),
+    }
+}
+
+/// The JSON line for `reply`, or `None` when it prints nothing.
+fn json(reply: &Reply) -> Option<Json> {
+    let line = match reply {
+        Reply::Status(status) => json!({ "status": status }),
+        Reply::Value(value) => {
+            let (_, text, encoding) = encode_pair(b"", value);
+            json!({ "value": text, "encoding": encoding })
+        }
+        Reply::Nil => json!({ "value": null }),
+        Reply::Integer(n) => json!({ "integer": n }),
+        Reply::Page { cursor, entries } => {
+            let items: Vec<Json> = entries
+                .iter()
+                .map(|(key, value)| {
+                    let (key, value, encoding) = encode_pair(key, value);
+                    json!({ "key": key, "value": value, "encoding": encoding })
+                })
+                .collect();
+            json!({ "cursor": cursor, "items": items })
+        }
+        Reply::Keys(keys) => {
+            let keys: Vec<Json> = keys
+                .iter()
+                .map(|key| {
+                    let (key, _, encoding) = encode_pair(key, b"");
+                    json!({ "key": key, "encoding": encoding })
+                })
+                .collect();
+            json!({ "keys": keys })
+        }
+        Reply::Fields(fields) => {
+            let fields: Vec<Json> = fields
+                .iter()
+                .map(|(field, value)| {
+                    let (field, value, encoding) = encode_pair(field, value);
+                    json!({ "field": field, "value": value, "encoding": encoding })
+                })
+                .collect();
+            json!({ "fields": fields })
+        }
+        Reply::Values(values) => {
+            let values: Vec<Json> = values
+                .iter()
+                .map(|value| {
+                    let (_, value, encoding) = encode_pair(b"", value);
+                    json!({ "value": value, "encoding": encoding })
+                })
+                .collect();
+            json!({ "values": values })
+        }
+        Reply::Text(text) => json!({ "text": text }),
+        Reply::Info(sections) => {
+            let sections: Map<String, Json> = sections
+                .iter()
+                .map(|section| {
+                    let fields: Map<String, Json> = section
+                        .fields
+                        .iter()
+                        .map(|(name, value)| (name.to_string(), json!(value)))
+                        .collect();
+                    (section.name.to_string(), Json::Object(fields))
+                })
+                .collect();
+            Json::Object(sections)
+        }
+        Reply::Usage(usage) => json!({ "error": format!("usage: {}", usage) }),
+        Reply::Unknown(name) => json!({ "error": format!("unknown command '{}'", name) }),
+        Reply::Error(message) => json!({ "error": message }),
+        Reply::Exit => return None,
+    };
+    Some(line)
+}
+
+/// `stats` -> `Stats`, as Redis titles `INFO` sections.
+fn title(name: &str) -> String {
+    let mut chars = name.chars();
+    chars.next().map_or_else(String::new, |first| {
+        first.to_ascii_uppercase().to_string() + chars.as_str()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::info::Section;
+
+    fn rendered(reply: &Reply, format: Format) -> (String, String) {
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        render(reply, format, &mut out, &mut err).unwrap();
+        (
+            String::from_utf8(out).unwrap(),
+            String::from_utf8(err).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_pretty_and_raw_formats() {
+        let entries = Reply::Page {
+            cursor: 0,
+            entries: vec![(b"k".to_vec(), b"v".to_vec())],
+        };
+        assert_eq!(
+            rendered(&entries, Format::Pretty).0,
+            "  k -> v\n  (1 entries)\n"
+        );
+        assert_eq!(rendered(&entries, Format::Raw).0, "0\nk\tv\n");
+        let binary = Reply::Page {
+            cursor: 3,
+            entries: vec![(b"a b".to_vec(), b"\x00".to_vec())],
+        };
+        assert_eq!(
+            rendered(&binary, Format::Pretty).0,
+            "  \"a b\" -> \\x00\n  (1 entries, next cursor 3)\n"
+        );
+        let keys = Reply::Keys(vec![b"a".to_vec(), b"b c".to_vec()]);
+        assert_eq!(
+            rendered(&keys, Format::Pretty).0,
+            "  1) a\n  2) \"b c\"\n  (2 keys)\n"
+        );
+        assert_eq!(rendered(&keys, Format::Raw).0, "a\nb c\n");
+        let fields = Reply::Fields(vec![(b"name".to_vec(), b"ada".to_vec())]);
+        assert_eq!(
+            rendered(&fields, Format::Pretty).0,
+            "  name -> ada\n  (1 fields)\n"
+        );
+        assert_eq!(rendered(&fields, Format::Raw).0, "name\tada\n");
+        let values = Reply::Values(vec![b"job:1".to_vec(), b"a b".to_vec()]);
+        assert_eq!(
+            rendered(&values, Format::Pretty).0,
+            "  1) job:1\n  2) \"a b\"\n  (2 items)\n"
+        );
+        assert_eq!(
+            rendered(&values, Format::Json).0,
+            "{\"values\":[{\"encoding\":\"utf8\",\"value\":\"job:1\"},{\"encoding\":\"utf8\",\"value\":\"a b\"}]}\n"
+        );
+        assert_eq!(
+            rendered(&fields, Format::Json).0,
+            "{\"fields\":[{\"encoding\":\"utf8\",\"field\":\"name\",\"value\":\"ada\"}]}\n"
+        );
+        assert_eq!(
+            rendered(&keys, Format::Json).0,
+            "{\"keys\":[{\"encoding\":\"utf8\",\"key\":\"a\"},{\"encoding\":\"utf8\",\"key\":\"b c\"}]}\n"
+        );
+        assert_eq!(
+            rendered(&Reply::Value(b"hi\n".to_vec()), Format::Pretty).0,
+            "  \"hi\\n\"\n"
+        );
+
+        let info = Reply::Info(vec![
+            Section {
+                name: "memory",
+                fields: vec![("memtable_entries", "2".into()), ("row_cache", "9".into())],
+            },
+            Section {
+                name: "stats",
+                fields: vec![("puts", "2".into())],
+            },
+        ]);
+        assert_eq!(
+            rendered(&info, Format::Pretty).0,
+            "  # Memory\n  memtable_entries: 2\n  row_cache:        9\n\n  # Stats\n  puts: 2\n"
+        );
+        assert_eq!(
+            rendered(&info, Format::Raw).0,
+            "# Memory\nmemtable_entries\t2\nrow_cache\t9\n# Stats\nputs\t2\n"
+        );
+        assert_eq!(rendered(&Reply::Nil, Format::Raw).0, "\n");
+        assert_eq!(
+            rendered(&info, Format::Json).0,
+            "{\"memory\":{\"memtable_entries\":\"2\",\"row_cache\":\"9\"},\"stats\":{\"puts\":\"2\"}}\n"
+        );
+        assert_eq!(
+            rendered(&Reply::Error("disk full".into()), Format::Raw),
+            (String::new(), "ERROR: disk full\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_json_format() {
+        let json = |reply: Reply| rendered(&reply, Format::Json).0;
+        assert_eq!(
+            json(Reply::Value(b"alice".to_vec())),
+            "{\"encoding\":\"utf8\",\"value\":\"alice\"}\n"
+        );
+        assert_eq!(
+            json(Reply::Value(vec![0xff])),
+            "{\"encoding\":\"base64\",\"value\":\"/w==\"}\n"
+        );
+        assert_eq!(json(Reply::Nil), "{\"value\":null}\n");
+        assert_eq!(
+            json(Reply::Page {
+                cursor: 4,
+                entries: vec![(b"k".to_vec(), b"v".to_vec())],
+            }),
+            "{\"cursor\":4,\"items\":[{\"encoding\":\"utf8\",\"key\":\"k\",\"value\":\"v\"}]}\n"
+        );
+        assert_eq!(
+            json(Reply::Unknown("frob".into())),
+            "{\"error\":\"unknown command 'frob'\"}\n"
+        );
+        assert_eq!(json(Reply::Exit), "");
+        assert_eq!("json".parse(), Ok(Format::Json));
+        assert!("yaml".parse::<Format>().is_err());
+    }
+}
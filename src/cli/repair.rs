@@ -0,0 +1,82 @@
+//! OBLIVION - Repair Tool
+//! `oblivion repair <data_dir>` checks a closed database and salvages
+//! what it can (see `engine::repair`): unreadable SSTables are moved to
+//! `<data_dir>/lost/`, WAL logs are cut at their first damaged record
+//! and the manifest is rewritten from the surviving tables.
+//!
+//! ```text
+//! oblivion repair <data_dir>
+//! ```
+//!
+//! Exit codes: 0 when the database was repaired (or needed nothing),
+//! 1 when it could not be (missing, locked by a running engine, or an
+//! I/O error), 2 for invalid arguments.
+
+use std::io::Write;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::engine::repair::{self, RepairReport};
+
+const USAGE: &str = "usage: oblivion repair <data_dir>";
+
+/// Run the tool over the arguments after `repair`. Returns the process
+/// exit code.
+pub fn run(mut args: impl Iterator<Item = String>) -> i32 {
+    let (Some(dir), None) = (args.next(), args.next()) else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+    if dir == "--help" || dir == "-h" {
+        println!("{}", USAGE);
+        return 0;
+    }
+
+    match repair::repair(&Config::new(&dir)) {
+        Ok(report) => {
+            let _ = print_report(Path::new(&dir), &report, &mut std::io::stdout().lock());
+            0
+        }
+        Err(err) => {
+            eprintln!("[ERROR] Cannot repair {}: {}", dir, err);
+            1
+        }
+    }
+}
+
+/// Write a summary of `report` for the database at `dir` to `out`.
+pub fn print_report<W: Write>(
+    dir: &Path,
+    report: &RepairReport,
+    out: &mut W,
+) -> std::io::Result<()> {
+    writeln!(out, "Repaired {}", dir.display())?;
+    writeln!(out, "  sstables kept         {}", report.tables_kept.len())?;
+    writeln!(
+        out,
+        "  sstables quarantined  {}",
+        report.tables_quarantined.len()
+    )?;
+    for (path, err) in &report.tables_quarantined {
+        writeln!(out, "    {}: {}", path.display(), err)?;
+    }
+    writeln!(out, "  logs checked          {}", report.logs_checked)?;
+    writeln!(
+        out,
+        "  logs truncated        {}",
+        report.logs_truncated.len()
+    )?;
+    for (path, bytes) in &report.logs_truncated {
+        writeln!(out, "    {}: {} byte(s) dropped", path.display(), bytes)?;
+    }
+    writeln!(out, "  records salvaged      {}", report.records_salvaged)?;
+    writeln!(
+        out,
+        "  manifest              {}",
+        if report.manifest_rebuilt {
+            "rebuilt"
+        } else {
+            "rewritten"
+        }
+    )
+}
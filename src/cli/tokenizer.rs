@@ -0,0 +1,214 @@
+//! OBLIVION - CLI Tokenizer
+//! Splits a command line into binary-safe arguments, as `redis-cli`
+//! does.
+//!
+//! ## Syntax
+//! - Arguments are separated by whitespace.
+//! - `"double quotes"` keep whitespace and understand the escapes
+//!   `\n`, `\r`, `\t`, `\0`, `\\`, `\"` and `\xNN` (any byte, in hex).
+//! - `'single quotes'` keep everything verbatim except `\'`.
+//! - Unquoted arguments understand the same escapes as double quotes,
+//!   so `key\x00` is a four-byte key.
+//!
+//! A quoted argument must end at whitespace or the end of the line:
+//! `"a"b` is an error rather than `ab`.
+
+use std::fmt;
+
+/// Why a command line could not be split.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxError {
+    /// Byte offset in the line where the problem was found.
+    pub position: usize,
+    /// What is wrong.
+    pub message: &'static str,
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at column {}", self.message, self.position + 1)
+    }
+}
+
+impl std::error::Error for SyntaxError {}
+
+/// Split `line` into arguments.
+pub fn tokenize(line: &str) -> Result<Vec<Vec<u8>>, SyntaxError> {
+    let bytes = line.as_bytes();
+    let mut args = Vec::new();
+    let mut pos = 0;
+    loop {
+        while bytes.get(pos).is_some_and(u8::is_ascii_whitespace) {
+            pos += 1;
+        }
+        if pos == bytes.len() {
+            return Ok(args);
+        }
+        let mut arg = Vec::new();
+        while let Some(&byte) = bytes.get(pos) {
+            match byte {
+                b if b.is_ascii_whitespace() => break,
+                b'"' | b'\'' => {
+                    pos = quoted(bytes, pos, &mut arg)?;
+                    if bytes.get(pos).is_some_and(|b| !b.is_ascii_whitespace()) {
+                        return Err(error(pos, "closing quote must be followed by a space"));
+                    }
+                }
+                b'\\' => pos = unescape(bytes, pos, &mut arg)?,
+                b => {
+                    arg.push(b);
+                    pos += 1;
+                }
+            }
+        }
+        args.push(arg);
+    }
+}
+
+/// Escape `bytes` so that [`tokenize`] reads it back as one argument:
+/// printable ASCII verbatim, anything else escaped, and the whole
+/// argument double-quoted if it is empty or holds whitespace or quotes.
+pub fn escape(bytes: &[u8]) -> String {
+    let needs_quotes = bytes.is_empty()
+        || bytes
+            .iter()
+            .any(|b| b.is_ascii_whitespace() || *b == b'"' || *b == b'\'');
+    escaped(bytes, needs_quotes)
+}
+
+/// Like [`escape`], but always double-quoted, as `redis-cli` shows
+/// values.
+pub fn quote(bytes: &[u8]) -> String {
+    escaped(bytes, true)
+}
+
+fn escaped(bytes: &[u8], needs_quotes: bool) -> String {
+    let mut out = String::with_capacity(bytes.len() + 2);
+    if needs_quotes {
+        out.push('"');
+    }
+    for &byte in bytes {
+        match byte {
+            b'\\' => out.push_str("\\\\"),
+            b'"' if needs_quotes => out.push_str("\\\""),
+            b'\n' => out.push_str("\\n"),
+            b'\r' => out.push_str("\\r"),
+            b'\t' => out.push_str("\\t"),
+            b' '..=b'~' => out.push(byte as char),
+            _ => out.push_str(&format!("\\x{:02x}", byte)),
+        }
+    }
+    if needs_quotes {
+        out.push('"');
+    }
+    out
+}
+
+/// Read the quoted section starting at the quote at `start` into
+/// `arg`. Returns the position after the closing quote.
+fn quoted(bytes: &[u8], start: usize, arg: &mut Vec<u8>) -> Result<usize, SyntaxError> {
+    let quote = bytes[start];
+    let mut pos = start + 1;
+    loop {
+        match bytes.get(pos) {
+            None => return Err(error(start, "unbalanced quotes")),
+            Some(&b) if b == quote => return Ok(pos + 1),
+            Some(b'\\') if quote == b'"' => pos = unescape(bytes, pos, arg)?,
+            Some(b'\\') if bytes.get(pos + 1) == Some(&b'\'') => {
+                arg.push(b'\'');
+                pos += 2;
+            }
+            Some(&b) => {
+                arg.push(b);
+                pos += 1;
+            }
+        }
+    }
+}
+
+/// Decode the escape sequence at `pos` (a backslash) into `arg`.
+/// Returns the position after it.
+fn unescape(bytes: &[u8], pos: usize, arg: &mut Vec<u8>) -> Result<usize, SyntaxError> {
+    let byte = match bytes.get(pos + 1) {
+        None => return Err(error(pos, "trailing backslash")),
+        Some(b'x') => {
+            let digits = bytes
+                .get(pos + 2..pos + 4)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            return match digits {
+                Some(byte) => {
+                    arg.push(byte);
+                    Ok(pos + 4)
+                }
+                None => Err(error(pos, "\\x must be followed by two hex digits")),
+            };
+        }
+        Some(b'n') => b'\n',
+        Some(b'r') => b'\r',
+        Some(b't') => b'\t',
+        Some(b'0') => 0,
+        // `\\`, `\"` and any other escaped byte stand for themselves
+        Some(&b) => b,
+    };
+    arg.push(byte);
+    Ok(pos + 2)
+}
+
+fn error(position: usize, message: &'static str) -> SyntaxError {
+    SyntaxError { position, message }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(line: &str) -> Vec<Vec<u8>> {
+        tokenize(line).unwrap()
+    }
+
+    #[test]
+    fn test_tokenize_quotes_and_escapes() {
+        assert_eq!(
+            words("  get   key "),
+            vec![b"get".to_vec(), b"key".to_vec()]
+        );
+        assert_eq!(
+            words(r#"set "a key" "tab\there\x00\xFF""#),
+            vec![
+                b"set".to_vec(),
+                b"a key".to_vec(),
+                b"tab\there\x00\xff".to_vec()
+            ]
+        );
+        assert_eq!(words(r"'c:\dir\' x'"), vec![br"c:\dir' x".to_vec()]);
+        assert_eq!(words(r"key\x01\ ok"), vec![b"key\x01 ok".to_vec()]);
+        assert_eq!(words(r#""""#), vec![Vec::<u8>::new()]);
+
+        assert_eq!(
+            tokenize(r#"get "open"#).unwrap_err().message,
+            "unbalanced quotes"
+        );
+        assert_eq!(tokenize(r#"get "a"b"#).unwrap_err().position, 7);
+        assert!(tokenize(r"get \xZZ").is_err());
+        assert!(tokenize("get key\\").is_err());
+    }
+
+    #[test]
+    fn test_quote_round_trips() {
+        for bytes in [
+            &b"plain"[..],
+            b"with space",
+            b"",
+            b"\x00\xff\n\"'\\",
+            b"back\\slash",
+        ] {
+            assert_eq!(words(&escape(bytes)), vec![bytes.to_vec()]);
+            assert_eq!(words(&quote(bytes)), vec![bytes.to_vec()]);
+        }
+        assert_eq!(escape(b"user:1"), "user:1");
+        assert_eq!(escape(b"a b"), "\"a b\"");
+        assert_eq!(escape(b"\x00k"), "\\x00k");
+        assert_eq!(quote(b"user:1"), "\"user:1\"");
+    }
+}
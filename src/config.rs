@@ -1,19 +1,302 @@
 //! OBLIVION - Engine Configuration
 //! Defines tunable parameters for the LSM storage engine.
+//!
+//! Build a configuration with [`Config::builder`], which validates
+//! option combinations and reports the first invalid one as
+//! [`OblivionError::Config`]. `Oblivion::open` re-validates, so
+//! configurations edited field by field are checked too.
+//!
+//! ## Options Files
+//! [`Config::from_file`] reads one `name = value` option per line, using
+//! the field names above; blank lines and lines starting with `#` are
+//! skipped, and options not in the file keep their defaults:
+//!
+//! ```text
+//! # oblivion.conf
+//! data_dir = /var/lib/oblivion
+//! memtable_max_size = 8388608
+//! sync_writes = false
+//! ```
 
-use std::path::PathBuf;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::engine::comparator::Comparator;
+use crate::engine::env::Env;
+#[cfg(feature = "failpoints")]
+use crate::engine::failpoint::FailPoints;
+use crate::engine::filter::{FilterPolicy, FilterSizing, FilterType};
+use crate::engine::io::SyncMethod;
+use crate::error::{OblivionError, Result};
+
+/// Options that can be changed on a live engine with `Oblivion::set_options`.
+pub const MUTABLE_OPTIONS: &[&str] = &[
+    "memtable_max_size",
+    "adaptive_memtable_sizing",
+    "adaptive_memtable_min_size",
+    "adaptive_memtable_max_size",
+    "sync_writes",
+    "wal_sync_method",
+    "wal_sync_interval_ms",
+    "compaction_threshold",
+    "compaction_size_ratio",
+    "target_file_size",
+    "compaction_readahead_size",
+    "compression_dict_size",
+    "periodic_compaction_interval_secs",
+    "row_cache_capacity",
+    "block_cache_capacity",
+    "max_open_files",
+    "max_key_size",
+    "max_value_size",
+    "stats_dump_period_secs",
+    "wal_retention_size",
+    "history_retention_ms",
+];
+
+/// File name of the write-ahead log inside `wal/`.
+pub(crate) const WAL_FILE_NAME: &str = "oblivion.wal";
+
+/// Largest key or value the on-disk formats can encode (u32 lengths,
+/// with `u32::MAX` reserved as the tombstone marker).
+pub const MAX_ENCODABLE_SIZE: usize = u32::MAX as usize - 1;
+
+/// Most logical databases a configuration may ask for; database numbers
+/// are stored in two bytes.
+pub const MAX_DATABASES: usize = 1 << 16;
 
 /// Configuration for the Oblivion storage engine.
+///
+/// Marked `#[non_exhaustive]` so new options can be added without
+/// breaking callers: construct it via [`Config::builder`],
+/// [`Config::new`] or [`Config::default`].
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct Config {
-    /// Base directory for all data files (WAL, SSTables).
+    /// Base directory for all data files.
+    ///
+    /// ```text
+    /// data_dir/
+    /// ├── wal/        write-ahead log (and `archive/`, see `wal_retention_size`)
+    /// ├── sst/        SSTables
+    /// ├── cf/         column families (see `Oblivion::open_with_cfs`)
+    /// ├── MANIFEST    live SSTable set and key comparator
+    /// └── LOCK        guards against concurrent opens
+    /// ```
     pub data_dir: PathBuf,
 
     /// Maximum size of the MemTable in bytes before triggering a flush.
     pub memtable_max_size: usize,
 
+    /// Move the flush threshold between `adaptive_memtable_min_size` and
+    /// `adaptive_memtable_max_size` as the write rate and flush times
+    /// change, starting from `memtable_max_size`: a MemTable is sized to
+    /// take about twice as long to fill as a flush takes, so one waits
+    /// at most while another is written out.
+    pub adaptive_memtable_sizing: bool,
+
+    /// Smallest flush threshold `adaptive_memtable_sizing` picks.
+    pub adaptive_memtable_min_size: usize,
+
+    /// Largest flush threshold `adaptive_memtable_sizing` picks.
+    pub adaptive_memtable_max_size: usize,
+
     /// Whether to sync WAL writes to disk immediately (fsync).
     pub sync_writes: bool,
+
+    /// How synced WAL writes reach disk: `fsync` (data and metadata)
+    /// or the cheaper `fdatasync` (data only).
+    pub wal_sync_method: SyncMethod,
+
+    /// With `sync_writes` off, how often a background thread syncs the
+    /// WAL, bounding the writes a power loss can take (0 leaves them to
+    /// the OS until the log is rotated).
+    pub wal_sync_interval_ms: u64,
+
+    /// Read and write SSTables with `O_DIRECT`, bypassing the OS page
+    /// cache during flushes, compactions and lookups.
+    pub use_direct_io: bool,
+
+    /// Issue WAL appends and syncs, and SSTable reads, through io_uring
+    /// (`io-uring` feature, Linux). Falls back to regular syscalls where
+    /// the kernel does not support it.
+    pub use_io_uring: bool,
+
+    /// Filter type used for SSTables at each compaction tier.
+    /// Index 0 is the freshly flushed tier; the last entry applies
+    /// to every deeper tier.
+    pub filter_per_tier: Vec<FilterType>,
+
+    /// Filter sizing (bits per key or target FPR) at each compaction tier.
+    /// The last entry applies to every deeper tier.
+    pub filter_sizing_per_tier: Vec<FilterSizing>,
+
+    /// Order of the keys in MemTable flushes, SSTables, compactions and
+    /// scans; byte order by default. Recorded in the manifest when the
+    /// database is created, which then only opens with a comparator of
+    /// the same name. See the `comparator` module.
+    pub comparator: Comparator,
+
+    /// File system every file of the database is read from and written
+    /// to: the WAL, SSTables, manifest and `LOCK`. The local file system
+    /// by default. See the `env` module.
+    pub env: Env,
+
+    /// Fail points tests arm to make the engine fail or crash between
+    /// two steps (`failpoints` feature). See the `failpoint` module.
+    #[cfg(feature = "failpoints")]
+    pub failpoints: FailPoints,
+
+    /// Number of SSTables in a tier that triggers a compaction.
+    pub compaction_threshold: usize,
+
+    /// Size multiplier between consecutive compaction tiers.
+    pub compaction_size_ratio: usize,
+
+    /// Raw key + value bytes after which flushes and compactions start a
+    /// new output SSTable, so no single table grows without bound
+    /// (0 writes one table per flush or compaction).
+    pub target_file_size: usize,
+
+    /// Bytes compaction reads from an input SSTable at a time, so it
+    /// reads large sequential runs of blocks instead of one block per
+    /// read (0 reads block by block).
+    pub compaction_readahead_size: usize,
+
+    /// Maximum size of the zstd dictionary each flush and compaction
+    /// trains over a sample of its entries and compresses its output's
+    /// data blocks against (`zstd` feature; 0 writes uncompressed blocks).
+    pub compression_dict_size: usize,
+
+    /// Age in seconds after which an SSTable is rewritten by compaction
+    /// even if its tier is below the threshold, so expired TTL keys,
+    /// tombstones and old table formats do not linger in cold tables
+    /// (0 disables periodic compaction).
+    pub periodic_compaction_interval_secs: u64,
+
+    /// Capacity of the row cache in bytes (0 disables it).
+    pub row_cache_capacity: usize,
+
+    /// Capacity of the block cache, which keeps decoded SSTable data
+    /// blocks, in bytes (0 disables it).
+    pub block_cache_capacity: usize,
+
+    /// Maximum number of SSTable file handles kept open at once.
+    /// Tables beyond this limit are reopened on demand.
+    pub max_open_files: usize,
+
+    /// Load every SSTable's index and filter blocks while opening,
+    /// instead of lazily on the first lookup that touches the table.
+    pub preload_index_and_filter: bool,
+
+    /// Verify every SSTable data block a read touches, whatever
+    /// `ReadOptions::verify_checksums` says: its CRC, and that its
+    /// records decode in order up to the key the index records. Reads
+    /// of a damaged block fail with `Corruption` instead of returning
+    /// its bytes.
+    pub paranoid_checks: bool,
+
+    /// Largest accepted key in bytes; larger writes are rejected.
+    pub max_key_size: usize,
+
+    /// Largest accepted value in bytes; larger writes are rejected
+    /// before they reach the WAL.
+    pub max_value_size: usize,
+
+    /// Create a new database if `data_dir` does not hold one.
+    /// When false, opening a missing database fails.
+    pub create_if_missing: bool,
+
+    /// Fail to open if `data_dir` already holds a database.
+    pub error_if_exists: bool,
+
+    /// When the manifest is missing or unreadable, rebuild the table
+    /// list on open from the SSTables on disk, ordered by sequence
+    /// range, instead of failing (or adopting every table unchecked).
+    /// Tables that cannot be opened are moved to `lost/`.
+    pub rebuild_manifest_on_open: bool,
+
+    /// Log a compact stats line every this many seconds (0 disables it).
+    pub stats_dump_period_secs: u64,
+
+    /// Number of independent engines a `ShardedOblivion` partitions the
+    /// keyspace over, each in `data_dir/shard_NNN/`. Fixed when the
+    /// database is created.
+    pub shard_count: usize,
+
+    /// Number of numbered logical databases (Redis `SELECT n`) addressable
+    /// through `Oblivion::database`, from 0 to `databases - 1`.
+    pub databases: usize,
+
+    /// Worker threads running flushes, compactions and TTL sweeps off
+    /// the write path. 0 runs them inline on the writing thread.
+    pub background_threads: usize,
+
+    /// Threads replaying WAL segments at open. Segments are replayed
+    /// side by side and merged in log order, so a backlog of unflushed
+    /// segments recovers faster; 1 replays them one after another.
+    pub recovery_threads: usize,
+
+    /// Bytes of recent writes kept in memory for replication followers
+    /// to catch up from; a follower further behind needs a full sync.
+    pub replication_backlog_size: usize,
+
+    /// Bytes of flushed WAL segments kept in `wal/archive/` so
+    /// `Oblivion::subscribe_changes` can replay older writes. 0 deletes
+    /// segments as soon as their MemTable is flushed.
+    pub wal_retention_size: u64,
+
+    /// Milliseconds of history compaction keeps for timestamped keys
+    /// (see `Oblivion::put_at`): older versions shadowed by one at or
+    /// before that horizon are dropped. 0 keeps every version.
+    pub history_retention_ms: u64,
+
+    /// Password clients of the RESP and HTTP servers must present
+    /// (`AUTH`, or an `Authorization: Bearer` header). `None` leaves
+    /// them open to anyone who can connect.
+    pub server_password: Option<Secret>,
+
+    /// PEM certificate chain of the RESP and HTTP servers. Together with
+    /// `server_tls_key` it makes them accept TLS connections only
+    /// (`tls` feature).
+    pub server_tls_cert: Option<PathBuf>,
+
+    /// PEM private key (PKCS#8, PKCS#1 or SEC1) for `server_tls_cert`.
+    pub server_tls_key: Option<PathBuf>,
+}
+
+/// A password or token, redacted from `Debug` output so configurations
+/// can be logged.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    /// Wrap a secret value.
+    pub fn new(secret: impl Into<String>) -> Self {
+        Self(secret.into())
+    }
+
+    /// Returns the secret value.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    /// Compare with `candidate` in time independent of where they differ.
+    pub fn matches(&self, candidate: &[u8]) -> bool {
+        let secret = self.0.as_bytes();
+        let diff = secret
+            .iter()
+            .zip(candidate)
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b));
+        diff == 0 && secret.len() == candidate.len()
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(..)")
+    }
 }
 
 impl Default for Config {
@@ -21,7 +304,47 @@ impl Default for Config {
         Self {
             data_dir: PathBuf::from("./data"),
             memtable_max_size: 4 * 1024 * 1024, // 4 MB
+            adaptive_memtable_sizing: false,
+            adaptive_memtable_min_size: 1024 * 1024, // 1 MB
+            adaptive_memtable_max_size: 64 * 1024 * 1024, // 64 MB
             sync_writes: true,
+            wal_sync_method: SyncMethod::Fsync,
+            wal_sync_interval_ms: 1000,
+            use_direct_io: false,
+            filter_per_tier: vec![FilterType::Bloom],
+            filter_sizing_per_tier: vec![FilterSizing::default()],
+            comparator: Comparator::default(),
+            env: Env::default(),
+            #[cfg(feature = "failpoints")]
+            failpoints: FailPoints::default(),
+            compaction_threshold: 4,
+            compaction_size_ratio: 10,
+            target_file_size: 64 * 1024 * 1024,         // 64MB
+            compaction_readahead_size: 2 * 1024 * 1024, // 2MB
+            compression_dict_size: 0,
+            periodic_compaction_interval_secs: 0,
+            row_cache_capacity: 0,
+            block_cache_capacity: 0,
+            max_open_files: 1000,
+            preload_index_and_filter: false,
+            paranoid_checks: false,
+            max_key_size: 64 * 1024,          // 64 KB
+            max_value_size: 64 * 1024 * 1024, // 64 MB
+            create_if_missing: true,
+            error_if_exists: false,
+            rebuild_manifest_on_open: false,
+            stats_dump_period_secs: 0,
+            shard_count: 1,
+            databases: 16,
+            use_io_uring: false,
+            background_threads: 0,
+            recovery_threads: 4,
+            replication_backlog_size: 16 * 1024 * 1024, // 16 MB
+            wal_retention_size: 0,
+            history_retention_ms: 0,
+            server_password: None,
+            server_tls_cert: None,
+            server_tls_key: None,
         }
     }
 }
@@ -35,14 +358,1029 @@ impl Config {
         }
     }
 
+    /// Start building a validated configuration for `data_dir`.
+    pub fn builder(data_dir: impl Into<PathBuf>) -> ConfigBuilder {
+        ConfigBuilder {
+            config: Config::new(data_dir),
+        }
+    }
+
+    /// Set a runtime-mutable option (see [`MUTABLE_OPTIONS`]) from its
+    /// string form. Does not validate the resulting configuration.
+    pub fn set_option(&mut self, name: &str, value: &str) -> Result<()> {
+        match name {
+            "memtable_max_size" => self.memtable_max_size = parse_option(name, value)?,
+            "adaptive_memtable_sizing" => {
+                self.adaptive_memtable_sizing = parse_option(name, value)?
+            }
+            "adaptive_memtable_min_size" => {
+                self.adaptive_memtable_min_size = parse_option(name, value)?
+            }
+            "adaptive_memtable_max_size" => {
+                self.adaptive_memtable_max_size = parse_option(name, value)?
+            }
+            "sync_writes" => self.sync_writes = parse_option(name, value)?,
+            "wal_sync_method" => self.wal_sync_method = parse_option(name, value)?,
+            "wal_sync_interval_ms" => self.wal_sync_interval_ms = parse_option(name, value)?,
+            "compaction_threshold" => self.compaction_threshold = parse_option(name, value)?,
+            "compaction_size_ratio" => self.compaction_size_ratio = parse_option(name, value)?,
+            "target_file_size" => self.target_file_size = parse_option(name, value)?,
+            "compaction_readahead_size" => {
+                self.compaction_readahead_size = parse_option(name, value)?
+            }
+            "compression_dict_size" => self.compression_dict_size = parse_option(name, value)?,
+            "periodic_compaction_interval_secs" => {
+                self.periodic_compaction_interval_secs = parse_option(name, value)?
+            }
+            "row_cache_capacity" => self.row_cache_capacity = parse_option(name, value)?,
+            "block_cache_capacity" => self.block_cache_capacity = parse_option(name, value)?,
+            "max_open_files" => self.max_open_files = parse_option(name, value)?,
+            "max_key_size" => self.max_key_size = parse_option(name, value)?,
+            "max_value_size" => self.max_value_size = parse_option(name, value)?,
+            "stats_dump_period_secs" => self.stats_dump_period_secs = parse_option(name, value)?,
+            "wal_retention_size" => self.wal_retention_size = parse_option(name, value)?,
+            "history_retention_ms" => self.history_retention_ms = parse_option(name, value)?,
+            "data_dir"
+            | "filter_per_tier"
+            | "filter_sizing_per_tier"
+            | "comparator"
+            | "env"
+            | "preload_index_and_filter"
+            | "paranoid_checks"
+            | "use_direct_io"
+            | "use_io_uring"
+            | "shard_count"
+            | "databases"
+            | "background_threads"
+            | "recovery_threads"
+            | "replication_backlog_size"
+            | "create_if_missing"
+            | "error_if_exists"
+            | "rebuild_manifest_on_open"
+            | "server_password"
+            | "server_tls_cert"
+            | "server_tls_key" => {
+                return Err(config_error(format!(
+                    "option '{}' cannot be changed on a live engine",
+                    name
+                )));
+            }
+            _ => return Err(config_error(format!("unknown option '{}'", name))),
+        }
+        Ok(())
+    }
+
+    /// Load and validate a configuration from an options file (see the
+    /// module docs). Errors name the file and line of the bad option.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Config> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| config_error(format!("cannot read {}: {}", path.display(), err)))?;
+        let mut config = Config::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let result = match line.split_once('=') {
+                Some((name, value)) => config.set_startup_option(name.trim(), value.trim()),
+                None => Err(config_error(format!(
+                    "expected 'name = value', got '{}'",
+                    line
+                ))),
+            };
+            result.map_err(|err| match err {
+                OblivionError::Config(message) => {
+                    config_error(format!("{}:{}: {}", path.display(), number + 1, message))
+                }
+                err => err,
+            })?;
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Set any option that has a string form, including those fixed once
+    /// the engine is open. Does not validate the resulting configuration.
+    fn set_startup_option(&mut self, name: &str, value: &str) -> Result<()> {
+        match name {
+            "data_dir" => self.data_dir = value.into(),
+            "preload_index_and_filter" => {
+                self.preload_index_and_filter = parse_option(name, value)?
+            }
+            "paranoid_checks" => self.paranoid_checks = parse_option(name, value)?,
+            "use_direct_io" => self.use_direct_io = parse_option(name, value)?,
+            "use_io_uring" => self.use_io_uring = parse_option(name, value)?,
+            "shard_count" => self.shard_count = parse_option(name, value)?,
+            "databases" => self.databases = parse_option(name, value)?,
+            "background_threads" => self.background_threads = parse_option(name, value)?,
+            "recovery_threads" => self.recovery_threads = parse_option(name, value)?,
+            "replication_backlog_size" => {
+                self.replication_backlog_size = parse_option(name, value)?
+            }
+            "create_if_missing" => self.create_if_missing = parse_option(name, value)?,
+            "error_if_exists" => self.error_if_exists = parse_option(name, value)?,
+            "rebuild_manifest_on_open" => {
+                self.rebuild_manifest_on_open = parse_option(name, value)?
+            }
+            "server_password" => self.server_password = Some(Secret::new(value)),
+            "server_tls_cert" => self.server_tls_cert = Some(value.into()),
+            "server_tls_key" => self.server_tls_key = Some(value.into()),
+            "filter_per_tier" | "filter_sizing_per_tier" | "comparator" | "env" => {
+                return Err(config_error(format!(
+                    "option '{}' can only be set in code",
+                    name
+                )));
+            }
+            _ => return self.set_option(name, value),
+        }
+        Ok(())
+    }
+
+    /// Check that all options are in range and consistent with each other.
+    pub fn validate(&self) -> Result<()> {
+        if self.data_dir.as_os_str().is_empty() {
+            return Err(config_error("data_dir must not be empty"));
+        }
+        if self.memtable_max_size == 0 {
+            return Err(config_error("memtable_max_size must be greater than 0"));
+        }
+        if self.adaptive_memtable_sizing
+            && !(1..=self.adaptive_memtable_max_size).contains(&self.adaptive_memtable_min_size)
+        {
+            return Err(config_error(format!(
+                "adaptive_memtable_min_size must be between 1 and adaptive_memtable_max_size (got {} and {})",
+                self.adaptive_memtable_min_size, self.adaptive_memtable_max_size
+            )));
+        }
+        if self.compaction_threshold < 2 {
+            return Err(config_error(format!(
+                "compaction_threshold must be at least 2 (got {})",
+                self.compaction_threshold
+            )));
+        }
+        if self.compaction_size_ratio < 2 {
+            return Err(config_error(format!(
+                "compaction_size_ratio must be at least 2 (got {})",
+                self.compaction_size_ratio
+            )));
+        }
+        if self.max_open_files == 0 {
+            return Err(config_error("max_open_files must be greater than 0"));
+        }
+        if self.shard_count == 0 {
+            return Err(config_error("shard_count must be greater than 0"));
+        }
+        if self.databases == 0 || self.databases > MAX_DATABASES {
+            return Err(config_error(format!(
+                "databases must be between 1 and {} (got {})",
+                MAX_DATABASES, self.databases
+            )));
+        }
+        if self.recovery_threads == 0 {
+            return Err(config_error("recovery_threads must be greater than 0"));
+        }
+        if self.replication_backlog_size == 0 {
+            return Err(config_error(
+                "replication_backlog_size must be greater than 0",
+            ));
+        }
+        if self.use_io_uring && !cfg!(all(target_os = "linux", feature = "io-uring")) {
+            return Err(config_error(
+                "use_io_uring requires the io-uring feature on Linux",
+            ));
+        }
+        if self.server_tls_cert.is_some() != self.server_tls_key.is_some() {
+            return Err(config_error(
+                "server_tls_cert and server_tls_key must be set together",
+            ));
+        }
+        if self.compression_dict_size > 0 && !cfg!(feature = "zstd") {
+            return Err(config_error(
+                "compression_dict_size requires the zstd feature",
+            ));
+        }
+        if self.server_tls_cert.is_some() && !cfg!(feature = "tls") {
+            return Err(config_error("server_tls_cert requires the tls feature"));
+        }
+        for (name, size) in [
+            ("max_key_size", self.max_key_size),
+            ("max_value_size", self.max_value_size),
+        ] {
+            if size == 0 || size > MAX_ENCODABLE_SIZE {
+                return Err(config_error(format!(
+                    "{} must be between 1 and {} (got {})",
+                    name, MAX_ENCODABLE_SIZE, size
+                )));
+            }
+        }
+        if self.filter_per_tier.is_empty() {
+            return Err(config_error("filter_per_tier must list at least one tier"));
+        }
+        if self.filter_sizing_per_tier.is_empty() {
+            return Err(config_error(
+                "filter_sizing_per_tier must list at least one tier",
+            ));
+        }
+        for (tier, sizing) in self.filter_sizing_per_tier.iter().enumerate() {
+            match *sizing {
+                FilterSizing::BitsPerKey(bits) if !(bits > 0.0 && bits.is_finite()) => {
+                    return Err(config_error(format!(
+                        "filter_sizing_per_tier[{}]: bits per key must be positive (got {})",
+                        tier, bits
+                    )));
+                }
+                FilterSizing::FalsePositiveRate(rate) if !(rate > 0.0 && rate < 1.0) => {
+                    return Err(config_error(format!(
+                        "filter_sizing_per_tier[{}]: false positive rate must be in (0, 1) (got {})",
+                        tier, rate
+                    )));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
     /// Set the maximum MemTable size before flush.
     pub fn with_memtable_max_size(mut self, size: usize) -> Self {
         self.memtable_max_size = size;
         self
     }
 
-    /// Ensure the data directory exists.
+    /// Let the flush threshold move between `min` and `max` bytes with
+    /// the write rate and flush times.
+    pub fn with_adaptive_memtable_sizing(mut self, min: usize, max: usize) -> Self {
+        self.adaptive_memtable_sizing = true;
+        self.adaptive_memtable_min_size = min;
+        self.adaptive_memtable_max_size = max;
+        self
+    }
+
+    /// Set the filter type for each compaction tier (last entry repeats).
+    pub fn with_filter_per_tier(mut self, filters: Vec<FilterType>) -> Self {
+        self.filter_per_tier = filters;
+        self
+    }
+
+    /// Filter type used for SSTables in the given compaction tier.
+    pub fn filter_type_for_tier(&self, tier: usize) -> FilterType {
+        self.filter_per_tier
+            .get(tier)
+            .or(self.filter_per_tier.last())
+            .copied()
+            .unwrap_or(FilterType::Bloom)
+    }
+
+    /// Set the filter sizing for each compaction tier (last entry repeats).
+    pub fn with_filter_sizing_per_tier(mut self, sizing: Vec<FilterSizing>) -> Self {
+        self.filter_sizing_per_tier = sizing;
+        self
+    }
+
+    /// Filter sizing used for SSTables in the given compaction tier.
+    pub fn filter_sizing_for_tier(&self, tier: usize) -> FilterSizing {
+        self.filter_sizing_per_tier
+            .get(tier)
+            .or(self.filter_sizing_per_tier.last())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Set the raw key + value bytes after which flush and compaction
+    /// output is split into another SSTable (0 disables splitting).
+    pub fn with_target_file_size(mut self, size: usize) -> Self {
+        self.target_file_size = size;
+        self
+    }
+
+    /// Set how many bytes compaction reads from an input SSTable at a
+    /// time (0 reads block by block).
+    pub fn with_compaction_readahead_size(mut self, size: usize) -> Self {
+        self.compaction_readahead_size = size;
+        self
+    }
+
+    /// Set the maximum size of the zstd dictionary trained per flush and
+    /// compaction to compress data blocks (0 disables compression).
+    pub fn with_compression_dict_size(mut self, size: usize) -> Self {
+        self.compression_dict_size = size;
+        self
+    }
+
+    /// Set the age in seconds after which SSTables are rewritten by
+    /// compaction (0 disables periodic compaction).
+    pub fn with_periodic_compaction_interval_secs(mut self, secs: u64) -> Self {
+        self.periodic_compaction_interval_secs = secs;
+        self
+    }
+
+    /// Enable the row cache with the given capacity in bytes.
+    pub fn with_row_cache_capacity(mut self, capacity: usize) -> Self {
+        self.row_cache_capacity = capacity;
+        self
+    }
+
+    /// Enable the block cache with the given capacity in bytes.
+    pub fn with_block_cache_capacity(mut self, capacity: usize) -> Self {
+        self.block_cache_capacity = capacity;
+        self
+    }
+
+    /// Set the maximum number of open SSTable file handles.
+    pub fn with_max_open_files(mut self, max_open_files: usize) -> Self {
+        self.max_open_files = max_open_files;
+        self
+    }
+
+    /// Preload SSTable index and filter blocks when opening the engine.
+    pub fn with_preload_index_and_filter(mut self, preload: bool) -> Self {
+        self.preload_index_and_filter = preload;
+        self
+    }
+
+    /// Verify every SSTable data block a read touches.
+    pub fn with_paranoid_checks(mut self, paranoid: bool) -> Self {
+        self.paranoid_checks = paranoid;
+        self
+    }
+
+    /// Set how synced WAL writes are persisted.
+    pub fn with_wal_sync_method(mut self, method: SyncMethod) -> Self {
+        self.wal_sync_method = method;
+        self
+    }
+
+    /// Set how often the WAL is synced in the background while
+    /// `sync_writes` is off (0 disables).
+    pub fn with_wal_sync_interval_ms(mut self, interval_ms: u64) -> Self {
+        self.wal_sync_interval_ms = interval_ms;
+        self
+    }
+
+    /// Use direct I/O for SSTable reads and writes.
+    pub fn with_direct_io(mut self, direct: bool) -> Self {
+        self.use_direct_io = direct;
+        self
+    }
+
+    /// Log a stats line every `secs` seconds (0 disables it).
+    pub fn with_stats_dump_period_secs(mut self, secs: u64) -> Self {
+        self.stats_dump_period_secs = secs;
+        self
+    }
+
+    /// Set the number of shards used by `ShardedOblivion`.
+    pub fn with_shard_count(mut self, shards: usize) -> Self {
+        self.shard_count = shards;
+        self
+    }
+
+    /// Set the number of logical databases.
+    pub fn with_databases(mut self, databases: usize) -> Self {
+        self.databases = databases;
+        self
+    }
+
+    /// Enable io_uring for WAL and SSTable I/O.
+    pub fn with_io_uring(mut self, io_uring: bool) -> Self {
+        self.use_io_uring = io_uring;
+        self
+    }
+
+    /// Set the number of background worker threads (0 runs jobs inline).
+    pub fn with_background_threads(mut self, threads: usize) -> Self {
+        self.background_threads = threads;
+        self
+    }
+
+    /// Set the number of threads replaying WAL segments at open.
+    pub fn with_recovery_threads(mut self, threads: usize) -> Self {
+        self.recovery_threads = threads;
+        self
+    }
+
+    /// Set the bytes of recent writes kept for replication followers.
+    pub fn with_replication_backlog_size(mut self, size: usize) -> Self {
+        self.replication_backlog_size = size;
+        self
+    }
+
+    /// Set the bytes of flushed WAL segments kept for change subscribers.
+    pub fn with_wal_retention_size(mut self, size: u64) -> Self {
+        self.wal_retention_size = size;
+        self
+    }
+
+    /// Set the milliseconds of history kept for timestamped keys.
+    pub fn with_history_retention_ms(mut self, ms: u64) -> Self {
+        self.history_retention_ms = ms;
+        self
+    }
+
+    /// Require clients of the network servers to present `password`.
+    pub fn with_server_password(mut self, password: impl Into<String>) -> Self {
+        self.server_password = Some(Secret::new(password));
+        self
+    }
+
+    /// Order keys by `comparator` instead of byte by byte.
+    pub fn with_comparator(mut self, comparator: Comparator) -> Self {
+        self.comparator = comparator;
+        self
+    }
+
+    /// Keep the database's files on `env` instead of the local file
+    /// system.
+    pub fn with_env(mut self, env: Env) -> Self {
+        self.env = env;
+        self
+    }
+
+    /// Use `failpoints`, shared with the caller, as the engine's fail
+    /// points.
+    #[cfg(feature = "failpoints")]
+    pub fn with_failpoints(mut self, failpoints: FailPoints) -> Self {
+        self.failpoints = failpoints;
+        self
+    }
+
+    /// Serve the network servers over TLS with a PEM certificate chain
+    /// and private key.
+    pub fn with_server_tls(mut self, cert: impl Into<PathBuf>, key: impl Into<PathBuf>) -> Self {
+        self.server_tls_cert = Some(cert.into());
+        self.server_tls_key = Some(key.into());
+        self
+    }
+
+    /// Build the filter policy for SSTables in the given compaction tier.
+    pub fn filter_policy_for_tier(&self, tier: usize) -> Box<dyn FilterPolicy> {
+        let filter_type = self.filter_type_for_tier(tier);
+        let fpr = self
+            .filter_sizing_for_tier(tier)
+            .false_positive_rate(filter_type);
+        filter_type.policy(fpr)
+    }
+
+    /// Directory holding the write-ahead log.
+    pub fn wal_dir(&self) -> PathBuf {
+        self.data_dir.join("wal")
+    }
+
+    /// Directory holding the SSTables.
+    pub fn sst_dir(&self) -> PathBuf {
+        self.data_dir.join("sst")
+    }
+
+    /// Directory holding flushed WAL segments kept for change subscribers.
+    pub fn wal_archive_dir(&self) -> PathBuf {
+        self.wal_dir().join("archive")
+    }
+
+    /// Path of the write-ahead log file.
+    pub fn wal_path(&self) -> PathBuf {
+        self.wal_dir().join(WAL_FILE_NAME)
+    }
+
+    /// Path of the manifest file.
+    pub fn manifest_path(&self) -> PathBuf {
+        self.data_dir.join("MANIFEST")
+    }
+
+    /// Data directory of one shard of a `ShardedOblivion`.
+    pub fn shard_dir(&self, shard: usize) -> PathBuf {
+        self.data_dir.join(format!("shard_{:03}", shard))
+    }
+
+    /// Path of the lock file.
+    pub fn lock_path(&self) -> PathBuf {
+        self.data_dir.join("LOCK")
+    }
+
+    /// Returns true if `data_dir` already holds a database
+    /// (in the current layout or the old flat one).
+    pub fn database_exists(&self) -> bool {
+        self.env.exists(&self.manifest_path())
+            || self.env.exists(&self.wal_path())
+            || self.env.exists(&self.data_dir.join(WAL_FILE_NAME))
+    }
+
+    /// Ensure the data directory and its `wal/` and `sst/` subdirectories
+    /// exist, moving files left by the old flat layout into place.
     pub fn ensure_dirs(&self) -> std::io::Result<()> {
-        std::fs::create_dir_all(&self.data_dir)
+        self.env.create_dir_all(&self.wal_dir())?;
+        self.env.create_dir_all(&self.sst_dir())?;
+        self.env.sync_dir(&self.data_dir)?;
+        self.migrate_flat_layout()
+    }
+
+    /// Move a WAL and SSTables written directly into `data_dir` by older
+    /// releases into their subdirectories. File names are kept, so an
+    /// interrupted migration simply resumes on the next open.
+    fn migrate_flat_layout(&self) -> std::io::Result<()> {
+        let mut migrated = false;
+        for path in self.env.list(&self.data_dir)? {
+            let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            let target = if name == WAL_FILE_NAME {
+                self.wal_path()
+            } else if name.starts_with("sstable_") && name.ends_with(".sst") {
+                self.sst_dir().join(name)
+            } else if name.starts_with("sstable_") && name.ends_with(".sst.tmp") {
+                self.env.remove_file(&path)?;
+                continue;
+            } else {
+                continue;
+            };
+            if self.env.exists(&target) {
+                log::warn!("Not migrating {:?}: {:?} already exists", path, target);
+                continue;
+            }
+            log::info!("Migrating {:?} to {:?}", path, target);
+            self.env.rename(&path, &target)?;
+            migrated = true;
+        }
+        if migrated {
+            self.env.sync_dir(&self.wal_dir())?;
+            self.env.sync_dir(&self.sst_dir())?;
+            self.env.sync_dir(&self.data_dir)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse the string form of an option value.
+fn parse_option<T: FromStr>(name: &str, value: &str) -> Result<T> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| config_error(format!("invalid value '{}' for option '{}'", value, name)))
+}
+
+/// Build an [`OblivionError::Config`] from a message.
+fn config_error(message: impl Into<String>) -> OblivionError {
+    OblivionError::Config(message.into())
+}
+
+/// Builder for [`Config`] that validates the result.
+///
+/// # Example
+/// ```no_run
+/// use oblivion::config::Config;
+///
+/// let config = Config::builder("./data")
+///     .memtable_max_size(8 * 1024 * 1024)
+///     .compaction_threshold(6)
+///     .build()
+///     .expect("valid configuration");
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConfigBuilder {
+    config: Config,
+}
+
+impl ConfigBuilder {
+    /// Set the maximum MemTable size in bytes before flush.
+    pub fn memtable_max_size(mut self, size: usize) -> Self {
+        self.config.memtable_max_size = size;
+        self
+    }
+
+    /// Let the flush threshold move between `min` and `max` bytes with
+    /// the write rate and flush times.
+    pub fn adaptive_memtable_sizing(mut self, min: usize, max: usize) -> Self {
+        self.config.adaptive_memtable_sizing = true;
+        self.config.adaptive_memtable_min_size = min;
+        self.config.adaptive_memtable_max_size = max;
+        self
+    }
+
+    /// Set whether WAL writes are synced to disk immediately.
+    pub fn sync_writes(mut self, sync: bool) -> Self {
+        self.config.sync_writes = sync;
+        self
+    }
+
+    /// Set how synced WAL writes are persisted.
+    pub fn wal_sync_method(mut self, method: SyncMethod) -> Self {
+        self.config.wal_sync_method = method;
+        self
+    }
+
+    /// Set how often the WAL is synced in the background while
+    /// `sync_writes` is off (0 disables).
+    pub fn wal_sync_interval_ms(mut self, interval_ms: u64) -> Self {
+        self.config.wal_sync_interval_ms = interval_ms;
+        self
+    }
+
+    /// Set whether every SSTable data block a read touches is verified.
+    pub fn paranoid_checks(mut self, paranoid: bool) -> Self {
+        self.config.paranoid_checks = paranoid;
+        self
+    }
+
+    /// Set whether SSTables are read and written with direct I/O.
+    pub fn use_direct_io(mut self, direct: bool) -> Self {
+        self.config.use_direct_io = direct;
+        self
+    }
+
+    /// Set the filter type for each compaction tier (last entry repeats).
+    pub fn filter_per_tier(mut self, filters: Vec<FilterType>) -> Self {
+        self.config.filter_per_tier = filters;
+        self
+    }
+
+    /// Set the filter sizing for each compaction tier (last entry repeats).
+    pub fn filter_sizing_per_tier(mut self, sizing: Vec<FilterSizing>) -> Self {
+        self.config.filter_sizing_per_tier = sizing;
+        self
+    }
+
+    /// Set the number of SSTables in a tier that triggers a compaction.
+    pub fn compaction_threshold(mut self, threshold: usize) -> Self {
+        self.config.compaction_threshold = threshold;
+        self
+    }
+
+    /// Set the size multiplier between consecutive compaction tiers.
+    pub fn compaction_size_ratio(mut self, ratio: usize) -> Self {
+        self.config.compaction_size_ratio = ratio;
+        self
+    }
+
+    /// Set the output SSTable size flushes and compactions split at
+    /// (0 disables splitting).
+    pub fn target_file_size(mut self, size: usize) -> Self {
+        self.config.target_file_size = size;
+        self
+    }
+
+    /// Set how many bytes compaction reads from an input SSTable at a
+    /// time (0 reads block by block).
+    pub fn compaction_readahead_size(mut self, size: usize) -> Self {
+        self.config.compaction_readahead_size = size;
+        self
+    }
+
+    /// Set the maximum size of the zstd dictionary trained per flush and
+    /// compaction to compress data blocks (0 disables compression).
+    pub fn compression_dict_size(mut self, size: usize) -> Self {
+        self.config.compression_dict_size = size;
+        self
+    }
+
+    /// Set the age in seconds after which SSTables are rewritten by
+    /// compaction (0 disables periodic compaction).
+    pub fn periodic_compaction_interval_secs(mut self, secs: u64) -> Self {
+        self.config.periodic_compaction_interval_secs = secs;
+        self
+    }
+
+    /// Set the row cache capacity in bytes (0 disables it).
+    pub fn row_cache_capacity(mut self, capacity: usize) -> Self {
+        self.config.row_cache_capacity = capacity;
+        self
+    }
+
+    /// Set the block cache capacity in bytes (0 disables it).
+    pub fn block_cache_capacity(mut self, capacity: usize) -> Self {
+        self.config.block_cache_capacity = capacity;
+        self
+    }
+
+    /// Set the maximum number of open SSTable file handles.
+    pub fn max_open_files(mut self, max_open_files: usize) -> Self {
+        self.config.max_open_files = max_open_files;
+        self
+    }
+
+    /// Preload SSTable index and filter blocks when opening the engine.
+    pub fn preload_index_and_filter(mut self, preload: bool) -> Self {
+        self.config.preload_index_and_filter = preload;
+        self
+    }
+
+    /// Set the largest accepted key in bytes.
+    pub fn max_key_size(mut self, size: usize) -> Self {
+        self.config.max_key_size = size;
+        self
+    }
+
+    /// Set the largest accepted value in bytes.
+    pub fn max_value_size(mut self, size: usize) -> Self {
+        self.config.max_value_size = size;
+        self
+    }
+
+    /// Set whether a missing database is created on open.
+    pub fn create_if_missing(mut self, create: bool) -> Self {
+        self.config.create_if_missing = create;
+        self
+    }
+
+    /// Set whether opening an existing database is an error.
+    pub fn error_if_exists(mut self, error: bool) -> Self {
+        self.config.error_if_exists = error;
+        self
+    }
+
+    /// Set whether a missing or unreadable manifest is rebuilt from the
+    /// SSTables on disk when opening.
+    pub fn rebuild_manifest_on_open(mut self, rebuild: bool) -> Self {
+        self.config.rebuild_manifest_on_open = rebuild;
+        self
+    }
+
+    /// Log a stats line every `secs` seconds (0 disables it).
+    pub fn stats_dump_period_secs(mut self, secs: u64) -> Self {
+        self.config.stats_dump_period_secs = secs;
+        self
+    }
+
+    /// Set the number of shards used by `ShardedOblivion`.
+    pub fn shard_count(mut self, shards: usize) -> Self {
+        self.config.shard_count = shards;
+        self
+    }
+
+    /// Set the number of logical databases.
+    pub fn databases(mut self, databases: usize) -> Self {
+        self.config.databases = databases;
+        self
+    }
+
+    /// Set the order of keys.
+    pub fn comparator(mut self, comparator: Comparator) -> Self {
+        self.config.comparator = comparator;
+        self
+    }
+
+    /// Set the file system.
+    pub fn env(mut self, env: Env) -> Self {
+        self.config.env = env;
+        self
+    }
+
+    /// Use `failpoints`, shared with the caller, as the engine's fail
+    /// points.
+    #[cfg(feature = "failpoints")]
+    pub fn failpoints(mut self, failpoints: FailPoints) -> Self {
+        self.config.failpoints = failpoints;
+        self
+    }
+
+    /// Enable io_uring for WAL and SSTable I/O.
+    pub fn use_io_uring(mut self, io_uring: bool) -> Self {
+        self.config.use_io_uring = io_uring;
+        self
+    }
+
+    /// Set the number of background worker threads (0 runs jobs inline).
+    pub fn background_threads(mut self, threads: usize) -> Self {
+        self.config.background_threads = threads;
+        self
+    }
+
+    /// Set the number of threads replaying WAL segments at open.
+    pub fn recovery_threads(mut self, threads: usize) -> Self {
+        self.config.recovery_threads = threads;
+        self
+    }
+
+    /// Set the bytes of recent writes kept for replication followers.
+    pub fn replication_backlog_size(mut self, size: usize) -> Self {
+        self.config.replication_backlog_size = size;
+        self
+    }
+
+    /// Set the bytes of flushed WAL segments kept for change subscribers.
+    pub fn wal_retention_size(mut self, size: u64) -> Self {
+        self.config.wal_retention_size = size;
+        self
+    }
+
+    /// Set the milliseconds of history kept for timestamped keys.
+    pub fn history_retention_ms(mut self, ms: u64) -> Self {
+        self.config.history_retention_ms = ms;
+        self
+    }
+
+    /// Require clients of the network servers to present `password`.
+    pub fn server_password(mut self, password: impl Into<String>) -> Self {
+        self.config.server_password = Some(Secret::new(password));
+        self
+    }
+
+    /// Serve the network servers over TLS with a PEM certificate chain
+    /// and private key.
+    pub fn server_tls(mut self, cert: impl Into<PathBuf>, key: impl Into<PathBuf>) -> Self {
+        self.config.server_tls_cert = Some(cert.into());
+        self.config.server_tls_key = Some(key.into());
+        self
+    }
+
+    /// Validate the options and return the finished configuration.
+    pub fn build(self) -> Result<Config> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_defaults_are_valid() {
+        let config = Config::builder("/tmp/oblivion").build().unwrap();
+        assert_eq!(config.data_dir, PathBuf::from("/tmp/oblivion"));
+        assert_eq!(config.compaction_threshold, 4);
+        assert!(Config::default().validate().is_ok());
+    }
+
+    #[test]
+    fn test_builder_sets_options() {
+        let config = Config::builder("/tmp/oblivion")
+            .memtable_max_size(1024)
+            .sync_writes(false)
+            .compaction_threshold(8)
+            .max_open_files(16)
+            .build()
+            .unwrap();
+        assert_eq!(config.memtable_max_size, 1024);
+        assert!(!config.sync_writes);
+        assert_eq!(config.compaction_threshold, 8);
+        assert_eq!(config.max_open_files, 16);
+    }
+
+    #[test]
+    fn test_builder_rejects_invalid_options() {
+        let err = Config::builder("/tmp/oblivion")
+            .memtable_max_size(0)
+            .build()
+            .unwrap_err();
+        assert!(err.to_string().contains("memtable_max_size"));
+
+        let err = Config::builder("/tmp/oblivion")
+            .compaction_threshold(1)
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, OblivionError::Config(ref m) if m.contains("at least 2")));
+
+        let err = Config::builder("/tmp/oblivion")
+            .adaptive_memtable_sizing(1 << 20, 1 << 10)
+            .build()
+            .unwrap_err();
+        assert!(err.to_string().contains("adaptive_memtable_min_size"));
+
+        let err = Config::builder("").build().unwrap_err();
+        assert!(err.to_string().contains("data_dir"));
+
+        assert!(Config::builder("/tmp/oblivion")
+            .filter_per_tier(vec![])
+            .build()
+            .is_err());
+
+        let err = Config::builder("/tmp/oblivion")
+            .max_value_size(usize::MAX)
+            .build()
+            .unwrap_err();
+        assert!(err.to_string().contains("max_value_size"));
+
+        assert!(Config::builder("/tmp/oblivion")
+            .recovery_threads(0)
+            .build()
+            .is_err());
+        let err = Config::builder("/tmp/oblivion")
+            .shard_count(0)
+            .build()
+            .unwrap_err();
+        assert!(err.to_string().contains("shard_count"));
+        assert!(Config::builder("/tmp/oblivion")
+            .databases(MAX_DATABASES + 1)
+            .build()
+            .is_err());
+
+        let mut config = Config::new("/tmp/oblivion");
+        config.server_tls_key = Some(PathBuf::from("server.key"));
+        assert!(config
+            .validate()
+            .unwrap_err()
+            .to_string()
+            .contains("together"));
+
+        let config = Config::new("/tmp/oblivion").with_compression_dict_size(16 * 1024);
+        assert_eq!(config.validate().is_ok(), cfg!(feature = "zstd"));
+    }
+
+    #[test]
+    fn test_server_password_is_redacted() {
+        let config = Config::builder("/tmp/oblivion")
+            .server_password("hunter2")
+            .build()
+            .unwrap();
+        assert!(!format!("{:?}", config).contains("hunter2"));
+        let password = config.server_password.unwrap();
+        assert!(password.matches(b"hunter2"));
+        assert!(!password.matches(b"hunter"));
+        assert!(!password.matches(b"hunter22"));
+        assert_eq!(password.expose(), "hunter2");
+    }
+
+    #[test]
+    fn test_ensure_dirs_migrates_flat_layout() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("oblivion.wal"), b"wal").unwrap();
+        std::fs::write(dir.path().join("sstable_000003.sst"), b"sst").unwrap();
+        std::fs::write(dir.path().join("sstable_000004.sst.tmp"), b"tmp").unwrap();
+
+        let config = Config::new(dir.path());
+        config.ensure_dirs().unwrap();
+
+        assert_eq!(std::fs::read(config.wal_path()).unwrap(), b"wal");
+        assert!(config.sst_dir().join("sstable_000003.sst").exists());
+        assert!(!dir.path().join("oblivion.wal").exists());
+        assert!(!dir.path().join("sstable_000004.sst.tmp").exists());
+        assert!(!config.sst_dir().join("sstable_000004.sst.tmp").exists());
+    }
+
+    #[test]
+    fn test_set_option() {
+        let mut config = Config::default();
+        config.set_option("memtable_max_size", "2048").unwrap();
+        config.set_option("sync_writes", "false").unwrap();
+        config.set_option("wal_sync_method", "fdatasync").unwrap();
+        config.set_option("target_file_size", "0").unwrap();
+        config.set_option("wal_sync_interval_ms", "50").unwrap();
+        assert_eq!(config.wal_sync_interval_ms, 50);
+        config
+            .set_option("periodic_compaction_interval_secs", "3600")
+            .unwrap();
+        assert_eq!(config.periodic_compaction_interval_secs, 3600);
+        assert_eq!(config.memtable_max_size, 2048);
+        assert_eq!(config.target_file_size, 0);
+        assert!(!config.sync_writes);
+        assert_eq!(config.wal_sync_method, SyncMethod::Fdatasync);
+        assert!(config.set_option("wal_sync_method", "never").is_err());
+        assert!(config.set_option("use_direct_io", "true").is_err());
+        assert!(config.set_option("shard_count", "4").is_err());
+        assert!(config.set_option("background_threads", "4").is_err());
+        assert!(config.set_option("use_io_uring", "true").is_err());
+
+        let err = config.set_option("memtable_max_size", "big").unwrap_err();
+        assert!(err.to_string().contains("invalid value 'big'"));
+        let err = config.set_option("data_dir", "/elsewhere").unwrap_err();
+        assert!(err.to_string().contains("cannot be changed"));
+        let err = config.set_option("no_such_option", "1").unwrap_err();
+        assert!(err.to_string().contains("unknown option"));
+    }
+
+    #[test]
+    fn test_from_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("oblivion.conf");
+        std::fs::write(
+            &path,
+            "# engine options\n\ndata_dir = /srv/oblivion\nmemtable_max_size=4096\n\
+             sync_writes = false\nshard_count = 2\n",
+        )
+        .unwrap();
+        let config = Config::from_file(&path).unwrap();
+        assert_eq!(config.data_dir, PathBuf::from("/srv/oblivion"));
+        assert_eq!(config.memtable_max_size, 4096);
+        assert!(!config.sync_writes);
+        assert_eq!(config.shard_count, 2);
+        assert_eq!(
+            config.compaction_threshold,
+            Config::default().compaction_threshold
+        );
+
+        std::fs::write(&path, "sync_writes = false\nmemtable_max_size = big\n").unwrap();
+        let err = Config::from_file(&path).unwrap_err().to_string();
+        assert!(
+            err.contains("oblivion.conf:2: invalid value 'big'"),
+            "{}",
+            err
+        );
+        std::fs::write(&path, "compaction_threshold = 1\n").unwrap();
+        assert!(Config::from_file(&path).is_err());
+        assert!(Config::from_file(dir.path().join("missing.conf")).is_err());
+    }
+
+    #[test]
+    fn test_builder_rejects_invalid_filter_sizing() {
+        for sizing in [
+            FilterSizing::BitsPerKey(0.0),
+            FilterSizing::FalsePositiveRate(1.5),
+        ] {
+            let err = Config::builder("/tmp/oblivion")
+                .filter_sizing_per_tier(vec![FilterSizing::default(), sizing])
+                .build()
+                .unwrap_err();
+            assert!(err.to_string().contains("filter_sizing_per_tier[1]"));
+        }
     }
 }
@@ -0,0 +1,358 @@
+//! OBLIVION - Async Engine
+//! `async` API over [`ConcurrentOblivion`] for tokio applications
+//! (`tokio` feature).
+//!
+//! ## Blocking Work
+//! Writes may append to and fsync the WAL, flush a MemTable or compact
+//! SSTables; reads may load SSTable blocks from disk. Each call therefore
+//! runs on tokio's blocking thread pool via `spawn_blocking`, so an async
+//! server never stalls its reactor on engine I/O. Only in-memory queries
+//! (`ttl`, `len`, ...) are plain methods.
+//!
+//! ## Streaming Scans
+//! [`AsyncOblivion::scan_stream`] yields a range scan as a
+//! [`Stream`](futures_core::Stream). A blocking task reads the range a
+//! page at a time into a bounded channel and waits whenever the channel
+//! is full, so a slow consumer holds back the scan instead of the whole
+//! result being buffered.
+
+use std::pin::Pin;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+use tokio::sync::mpsc;
+use tokio::task::{self, JoinError};
+
+use crate::config::Config;
+use crate::error::{OblivionError, Result};
+use crate::types::{Key, Value};
+
+use super::concurrent::ConcurrentOblivion;
+use super::options::ReadOptions;
+use super::predicate::Predicate;
+use super::snapshot::Snapshot;
+use super::version::ReadState;
+
+/// Records read from disk per source for each page of a streaming scan,
+/// and the number of rows buffered ahead of the consumer.
+const SCAN_PAGE_SIZE: usize = 256;
+
+/// Async handle to the Oblivion storage engine.
+///
+/// Must be used from within a tokio runtime.
+///
+/// ## Example
+/// ```no_run
+/// use oblivion::config::Config;
+/// use oblivion::engine::async_engine::AsyncOblivion;
+///
+/// # async fn run() -> oblivion::error::Result<()> {
+/// let engine = AsyncOblivion::open(Config::new("./data")).await?;
+/// engine.put(b"key".to_vec(), b"value".to_vec()).await?;
+/// assert_eq!(engine.get(b"key".to_vec()).await?, Some(b"value".to_vec()));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct AsyncOblivion {
+    inner: ConcurrentOblivion,
+}
+
+impl AsyncOblivion {
+    /// Open or create an engine; WAL recovery runs on a blocking thread.
+    pub async fn open(config: Config) -> Result<Self> {
+        let inner = Self::blocking(move || ConcurrentOblivion::open(config)).await?;
+        Ok(Self { inner })
+    }
+
+    /// Wrap an engine that is already open.
+    pub fn from_concurrent(inner: ConcurrentOblivion) -> Self {
+        Self { inner }
+    }
+
+    /// The underlying thread-safe engine, for synchronous callers.
+    pub fn concurrent(&self) -> &ConcurrentOblivion {
+        &self.inner
+    }
+
+    /// Insert a key-value pair.
+    pub async fn put(&self, key: impl Into<Key>, value: impl Into<Value>) -> Result<()> {
+        let (key, value) = (key.into(), value.into());
+        let engine = self.inner.clone();
+        Self::blocking(move || engine.put(key, value)).await
+    }
+
+    /// Insert a key-value pair with TTL.
+    pub async fn put_with_ttl(
+        &self,
+        key: impl Into<Key>,
+        value: impl Into<Value>,
+        ttl_ms: u64,
+    ) -> Result<()> {
+        let (key, value) = (key.into(), value.into());
+        let engine = self.inner.clone();
+        Self::blocking(move || engine.put_with_ttl(key, value, ttl_ms)).await
+    }
+
+    /// Get a value by key.
+    pub async fn get(&self, key: impl Into<Key>) -> Result<Option<Value>> {
+        self.get_opt(key, ReadOptions::default()).await
+    }
+
+    /// Get a value by key with per-read options.
+    pub async fn get_opt(&self, key: impl Into<Key>, opts: ReadOptions) -> Result<Option<Value>> {
+        let key = key.into();
+        let engine = self.inner.clone();
+        Self::blocking(move || engine.get_opt(&key, &opts)).await
+    }
+
+    /// Delete a key.
+    pub async fn delete(&self, key: impl Into<Key>) -> Result<()> {
+        let key = key.into();
+        let engine = self.inner.clone();
+        Self::blocking(move || engine.delete(key)).await
+    }
+
+    /// Scan all key-value pairs in sorted order.
+    pub async fn scan(&self) -> Result<Vec<(Key, Value)>> {
+        self.scan_opt(ReadOptions::default()).await
+    }
+
+    /// Scan key-value pairs in sorted order with per-read options.
+    pub async fn scan_opt(&self, opts: ReadOptions) -> Result<Vec<(Key, Value)>> {
+        let engine = self.inner.clone();
+        Self::blocking(move || engine.scan_opt(&opts)).await
+    }
+
+    /// Returns the key-value pairs passing `predicate` within the bounds
+    /// of `opts`, filtered on a blocking thread.
+    pub async fn scan_filtered(
+        &self,
+        predicate: Predicate,
+        opts: ReadOptions,
+    ) -> Result<Vec<(Key, Value)>> {
+        let engine = self.inner.clone();
+        Self::blocking(move || engine.scan_filtered_opt(&predicate, &opts)).await
+    }
+
+    /// Stream the key-value pairs selected by `opts` in key order.
+    ///
+    /// The scan reads a snapshot (the one in `opts`, or one taken now),
+    /// so it is consistent however long the consumer takes; tables
+    /// compacted away meanwhile keep their files until the stream is
+    /// dropped.
+    pub fn scan_stream(&self, opts: ReadOptions) -> ScanStream {
+        let (tx, rx) = mpsc::channel(SCAN_PAGE_SIZE);
+        let state = self.inner.read_state();
+        task::spawn_blocking(move || Self::produce(&state, opts, &tx));
+        ScanStream { rows: rx }
+    }
+
+    /// Feed the pages of a scan into `tx` until the range or the
+    /// consumer is exhausted.
+    fn produce(state: &ReadState, mut opts: ReadOptions, tx: &mpsc::Sender<Result<(Key, Value)>>) {
+        if opts.snapshot.is_none() {
+            opts.snapshot = Some(state.snapshot());
+        }
+        state.metrics.record_scan();
+        loop {
+            let (rows, resume) = match state.scan_page(&opts, SCAN_PAGE_SIZE) {
+                Ok(page) => page,
+                Err(e) => {
+                    let _ = tx.blocking_send(Err(e));
+                    return;
+                }
+            };
+            for row in rows {
+                // Blocks while the channel is full; fails once the
+                // stream is dropped
+                if tx.blocking_send(Ok(row)).is_err() {
+                    return;
+                }
+            }
+            match resume {
+                Some(resume) => opts.lower_bound = Some(resume),
+                None => return,
+            }
+        }
+    }
+
+    /// Take a point-in-time snapshot for use with [`ReadOptions::snapshot`].
+    pub fn snapshot(&self) -> Snapshot {
+        self.inner.snapshot()
+    }
+
+    /// Get the remaining TTL for a key in milliseconds.
+    pub fn ttl(&self, key: impl AsRef<[u8]>) -> Option<u64> {
+        self.inner.ttl(key)
+    }
+
+    /// Get number of entries in the active MemTable, tombstones included.
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Estimate the number of keys without reading any data.
+    pub fn estimate_live_keys(&self) -> u64 {
+        self.inner.estimate_live_keys()
+    }
+
+    /// Estimate the number of keys from counters alone.
+    pub fn dbsize_estimate(&self) -> u64 {
+        self.inner.dbsize_estimate()
+    }
+
+    /// Count the keys with a full scan on a blocking thread, until
+    /// `cancel` is set.
+    pub async fn dbsize_exact(&self, cancel: Arc<AtomicBool>) -> Result<u64> {
+        let engine = self.inner.clone();
+        Self::blocking(move || engine.dbsize_exact(&cancel)).await
+    }
+
+    /// Check if engine is empty.
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Change runtime-mutable options; may flush or compact.
+    pub async fn set_options(&self, options: Vec<(String, String)>) -> Result<()> {
+        let engine = self.inner.clone();
+        Self::blocking(move || engine.set_options(options)).await
+    }
+
+    /// Run `f` on tokio's blocking thread pool.
+    async fn blocking<F, T>(f: F) -> Result<T>
+    where
+        F: FnOnce() -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        task::spawn_blocking(f)
+            .await
+            .unwrap_or_else(|e| Err(Self::task_error(e)))
+    }
+
+    /// Turn a panicked or cancelled blocking task into an error.
+    fn task_error(e: JoinError) -> OblivionError {
+        OblivionError::AsyncTask(e.to_string())
+    }
+}
+
+/// Key-value pairs of a range scan, produced as they are consumed.
+///
+/// Dropping the stream stops the scan.
+pub struct ScanStream {
+    rows: mpsc::Receiver<Result<(Key, Value)>>,
+}
+
+impl Stream for ScanStream {
+    type Item = Result<(Key, Value)>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rows.poll_recv(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(dir: &std::path::Path) -> Config {
+        Config::builder(dir)
+            .memtable_max_size(1024)
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_async_put_get_delete_scan() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = AsyncOblivion::open(config(dir.path())).await.unwrap();
+
+        for i in 0..100 {
+            let key = format!("key_{:03}", i).into_bytes();
+            engine.put(key, vec![b'v'; 32]).await.unwrap();
+        }
+        engine.delete(b"key_000".to_vec()).await.unwrap();
+
+        assert_eq!(engine.get(b"key_000".to_vec()).await.unwrap(), None);
+        assert_eq!(
+            engine.get(b"key_099".to_vec()).await.unwrap(),
+            Some(vec![b'v'; 32])
+        );
+        assert_eq!(engine.scan().await.unwrap().len(), 99);
+        assert!(engine.concurrent().with_metrics(|m| m.total_ops()) > 0);
+    }
+
+    async fn next(stream: &mut ScanStream) -> Option<Result<(Key, Value)>> {
+        std::future::poll_fn(|cx| Pin::new(&mut *stream).poll_next(cx)).await
+    }
+
+    #[tokio::test]
+    async fn test_scan_stream_pages_through_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = AsyncOblivion::open(config(dir.path())).await.unwrap();
+        for i in 0..1000 {
+            let key = format!("key_{:04}", i).into_bytes();
+            engine.put(key, vec![b'v'; 16]).await.unwrap();
+        }
+        for i in (0..1000).step_by(3) {
+            engine
+                .delete(format!("key_{:04}", i).into_bytes())
+                .await
+                .unwrap();
+        }
+        assert!(!engine
+            .concurrent()
+            .read_state()
+            .current()
+            .sstables()
+            .is_empty());
+
+        let opts = ReadOptions {
+            lower_bound: Some(b"key_0100".to_vec()),
+            upper_bound: Some(b"key_0900".to_vec()),
+            ..ReadOptions::default()
+        };
+        let mut stream = engine.scan_stream(opts.clone());
+        let mut rows = Vec::new();
+        while let Some(row) = next(&mut stream).await {
+            rows.push(row.unwrap());
+        }
+        assert_eq!(rows, engine.scan_opt(opts).await.unwrap());
+        assert_eq!(rows.len(), 534);
+    }
+
+    #[tokio::test]
+    async fn test_dropped_scan_stream_releases_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = AsyncOblivion::open(config(dir.path())).await.unwrap();
+        for i in 0..2000 {
+            let key = format!("key_{:04}", i).into_bytes();
+            engine.put(key, b"v".to_vec()).await.unwrap();
+        }
+
+        let mut stream = engine.scan_stream(ReadOptions::default());
+        assert!(next(&mut stream).await.unwrap().is_ok());
+        drop(stream);
+        while engine.concurrent().read_state().live_snapshots() > 0 {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_async_errors_are_returned() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = AsyncOblivion::open(config(dir.path())).await.unwrap();
+
+        let err = engine
+            .set_options(vec![("shard_count".to_string(), "4".to_string())])
+            .await
+            .unwrap_err();
+        assert!(matches!(err, OblivionError::Config(_)));
+        assert!(AsyncOblivion::blocking(|| -> Result<()> { panic!("boom") })
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,296 @@
+//! OBLIVION - Background Jobs
+//! Worker pool running flushes, compactions, TTL sweeps and WAL syncs
+//! off the write path, sized by `background_threads`.
+//!
+//! ## Failures
+//! A job that returns an error or panics does not take its worker down:
+//! the failure is caught, logged and passed to the pool's error handler,
+//! which the engine uses to record a background error that fails later
+//! writes instead of silently losing work.
+//!
+//! ## Shutdown
+//! Dropping the pool stops periodic jobs, lets the workers finish every
+//! job already queued, and joins them.
+
+use std::any::Any;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use parking_lot::{Condvar, Mutex};
+
+use crate::error::Result;
+
+/// Kinds of background work, used to name threads and report failures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobKind {
+    /// Write frozen MemTables to SSTables.
+    Flush,
+    /// Merge SSTables selected by the compaction strategy.
+    Compaction,
+    /// Tombstone expired keys and drop them from the TTL index.
+    TtlSweep,
+    /// Sync a rotated WAL segment to disk.
+    WalSync,
+}
+
+impl JobKind {
+    /// Returns a short name for logs.
+    pub fn name(self) -> &'static str {
+        match self {
+            JobKind::Flush => "flush",
+            JobKind::Compaction => "compaction",
+            JobKind::TtlSweep => "ttl-sweep",
+            JobKind::WalSync => "wal-sync",
+        }
+    }
+}
+
+impl fmt::Display for JobKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A queued unit of background work.
+type Job = Box<dyn FnOnce() -> Result<()> + Send>;
+
+/// Called with the kind and message of every failed or panicked job.
+pub type ErrorHandler = Arc<dyn Fn(JobKind, &str) + Send + Sync>;
+
+/// Jobs queued or running, with a condition variable signalled when it drops to zero.
+type Outstanding = Arc<(Mutex<usize>, Condvar)>;
+
+/// Fixed-size pool of background worker threads.
+pub struct BackgroundPool {
+    /// Dropping the sender lets the workers drain the queue and exit.
+    queue: Option<Sender<(JobKind, Job)>>,
+    workers: Vec<JoinHandle<()>>,
+    /// Stop channels and threads of periodic jobs.
+    tickers: Vec<(Sender<()>, JoinHandle<()>)>,
+    outstanding: Outstanding,
+}
+
+impl BackgroundPool {
+    /// Start `threads` workers (at least one), reporting failures to `on_error`.
+    pub fn new(threads: usize, on_error: ErrorHandler) -> Self {
+        let (queue, jobs) = mpsc::channel::<(JobKind, Job)>();
+        let jobs = Arc::new(Mutex::new(jobs));
+        let outstanding: Outstanding = Arc::new((Mutex::new(0), Condvar::new()));
+        let workers = (0..threads.max(1))
+            .filter_map(|i| {
+                let jobs = Arc::clone(&jobs);
+                let outstanding = Arc::clone(&outstanding);
+                let on_error = Arc::clone(&on_error);
+                thread::Builder::new()
+                    .name(format!("oblivion-bg-{}", i))
+                    .spawn(move || Self::work(&jobs, &outstanding, &on_error))
+                    .map_err(|e| log::warn!("Failed to spawn background worker: {}", e))
+                    .ok()
+            })
+            .collect();
+        Self {
+            queue: Some(queue),
+            workers,
+            tickers: Vec::new(),
+            outstanding,
+        }
+    }
+
+    /// Worker loop: run jobs until the queue is closed and drained.
+    fn work(
+        jobs: &Mutex<Receiver<(JobKind, Job)>>,
+        outstanding: &Outstanding,
+        on_error: &ErrorHandler,
+    ) {
+        loop {
+            // The guard is dropped before the job runs
+            let next = jobs.lock().recv();
+            let Ok((kind, job)) = next else {
+                return;
+            };
+            if let Some(failure) = Self::run(job) {
+                log::error!("Background {} job failed: {}", kind, failure);
+                on_error(kind, &failure);
+            }
+            let (count, idle) = &**outstanding;
+            let mut count = count.lock();
+            *count -= 1;
+            if *count == 0 {
+                idle.notify_all();
+            }
+        }
+    }
+
+    /// Run a job, turning an error or panic into a message.
+    fn run(job: Job) -> Option<String> {
+        match panic::catch_unwind(AssertUnwindSafe(job)) {
+            Ok(Ok(())) => None,
+            Ok(Err(e)) => Some(e.to_string()),
+            Err(payload) => Some(format!("panicked: {}", panic_message(&*payload))),
+        }
+    }
+
+    /// Returns the number of worker threads.
+    pub fn threads(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Queue a job; it runs on the first idle worker.
+    pub fn submit<F>(&self, kind: JobKind, job: F)
+    where
+        F: FnOnce() -> Result<()> + Send + 'static,
+    {
+        if let Some(queue) = &self.queue {
+            *self.outstanding.0.lock() += 1;
+            if queue.send((kind, Box::new(job))).is_err() {
+                *self.outstanding.0.lock() -= 1;
+            }
+        }
+    }
+
+    /// Queue `job` every `period` until the pool is dropped.
+    pub fn schedule_every<F>(&mut self, kind: JobKind, period: Duration, job: F)
+    where
+        F: Fn() -> Result<()> + Send + Sync + 'static,
+    {
+        let Some(queue) = self.queue.clone() else {
+            return;
+        };
+        let job = Arc::new(job);
+        let outstanding = Arc::clone(&self.outstanding);
+        let (stop, stopped) = mpsc::channel::<()>();
+        let ticker = thread::Builder::new()
+            .name(format!("oblivion-{}", kind))
+            .spawn(move || {
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(period) {
+                    let job = Arc::clone(&job);
+                    *outstanding.0.lock() += 1;
+                    if queue.send((kind, Box::new(move || job()))).is_err() {
+                        *outstanding.0.lock() -= 1;
+                        return;
+                    }
+                }
+            });
+        match ticker {
+            Ok(handle) => self.tickers.push((stop, handle)),
+            Err(e) => log::warn!("Failed to spawn {} ticker: {}", kind, e),
+        }
+    }
+
+    /// Block until no job is queued or running.
+    pub fn wait_idle(&self) {
+        let (count, idle) = &*self.outstanding;
+        let mut count = count.lock();
+        while *count > 0 {
+            idle.wait(&mut count);
+        }
+    }
+}
+
+impl Drop for BackgroundPool {
+    fn drop(&mut self) {
+        for (stop, ticker) in self.tickers.drain(..) {
+            drop(stop);
+            let _ = ticker.join();
+        }
+        self.queue.take();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Extract the message from a panic payload.
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::OblivionError;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn collecting_pool(threads: usize) -> (BackgroundPool, Arc<Mutex<Vec<String>>>) {
+        let failures = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&failures);
+        let pool = BackgroundPool::new(
+            threads,
+            Arc::new(move |kind, message| {
+                sink.lock().push(format!("{}: {}", kind, message));
+            }),
+        );
+        (pool, failures)
+    }
+
+    #[test]
+    fn test_runs_jobs_and_drains_on_drop() {
+        let (pool, failures) = collecting_pool(3);
+        assert_eq!(pool.threads(), 3);
+        let done = Arc::new(AtomicUsize::new(0));
+        for _ in 0..50 {
+            let done = Arc::clone(&done);
+            pool.submit(JobKind::Compaction, move || {
+                thread::sleep(Duration::from_millis(1));
+                done.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            });
+        }
+
+        // Queued jobs still run during shutdown
+        drop(pool);
+        assert_eq!(done.load(Ordering::SeqCst), 50);
+        assert!(failures.lock().is_empty());
+    }
+
+    #[test]
+    fn test_failures_and_panics_are_reported() {
+        let (pool, failures) = collecting_pool(1);
+        pool.submit(JobKind::Flush, || {
+            Err(OblivionError::Corruption("bad block".to_string()))
+        });
+        pool.submit(JobKind::TtlSweep, || panic!("sweep exploded"));
+
+        // The worker survives the panic and keeps running jobs
+        let ran = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&ran);
+        pool.submit(JobKind::WalSync, move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        });
+        pool.wait_idle();
+
+        assert_eq!(ran.load(Ordering::SeqCst), 1);
+        let failures = failures.lock();
+        assert_eq!(failures.len(), 2);
+        assert!(failures[0].starts_with("flush: Data corruption detected: bad block"));
+        assert_eq!(failures[1], "ttl-sweep: panicked: sweep exploded");
+    }
+
+    #[test]
+    fn test_periodic_jobs_stop_on_drop() {
+        let (mut pool, _) = collecting_pool(1);
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&ticks);
+        pool.schedule_every(JobKind::TtlSweep, Duration::from_millis(5), move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        });
+        while ticks.load(Ordering::SeqCst) < 3 {
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        drop(pool);
+        let after_drop = ticks.load(Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(30));
+        assert_eq!(ticks.load(Ordering::SeqCst), after_drop);
+    }
+}
@@ -0,0 +1,344 @@
+//! OBLIVION - Backup Engine
+//! Full backups of a live engine, verified by checksums and restorable
+//! into a fresh data directory.
+//!
+//! ## Layout
+//! ```text
+//! backup_dir/
+//! ├── 1/
+//! │   ├── BACKUP      [crc: 4 bytes LE][bincode(BackupMeta)]
+//! │   ├── MANIFEST
+//! │   ├── wal/oblivion.wal
+//! │   └── sst/sstable_NNNNNN.sst
+//! └── 2/ ...
+//! ```
+//!
+//! A backup is taken with [`Oblivion::create_checkpoint`] into
+//! `<id>.tmp`, checksummed, and renamed into place once its `BACKUP`
+//! file is written, so an interrupted backup never shows up in the list.
+//! On the database's filesystem the SSTables are hard links: they are
+//! immutable, so later compactions deleting them leave the backup
+//! intact. Restores always copy. Like checkpoints, backups need the
+//! database to be on the local file system.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{OblivionError, Result};
+
+use super::io;
+use super::Oblivion;
+
+/// Name of the metadata file inside each backup.
+const META_FILE: &str = "BACKUP";
+
+/// Suffix of backups still being written.
+const TMP_SUFFIX: &str = ".tmp";
+
+/// Size and checksum of one backed-up file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BackupFile {
+    /// Path relative to the backup's directory, `/`-separated.
+    name: String,
+    size: u64,
+    crc: u32,
+}
+
+/// Contents of a backup's `BACKUP` file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BackupMeta {
+    /// Creation time, in seconds since the Unix epoch.
+    timestamp: u64,
+    files: Vec<BackupFile>,
+}
+
+/// Summary of a stored backup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupInfo {
+    /// Backup id, increasing with each backup.
+    pub id: u64,
+    /// Creation time, in seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// Total size of the backed-up files in bytes.
+    pub size: u64,
+    /// Number of backed-up files.
+    pub file_count: usize,
+}
+
+/// Creates, verifies, restores and deletes full backups in one directory.
+///
+/// ## Example
+/// ```no_run
+/// use oblivion::{config::Config, engine::{backup::BackupEngine, Oblivion}};
+///
+/// let engine = Oblivion::open(Config::new("./oblivion_data")).unwrap();
+/// let backups = BackupEngine::open("./oblivion_backups").unwrap();
+/// let info = backups.create_backup(&engine).unwrap();
+/// drop(engine);
+/// backups.restore(info.id, "./oblivion_restored").unwrap();
+/// ```
+pub struct BackupEngine {
+    dir: PathBuf,
+}
+
+impl BackupEngine {
+    /// Open (creating if needed) the backup directory `dir`, removing
+    /// backups left incomplete by a crash.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.ends_with(TMP_SUFFIX))
+            {
+                log::warn!("Removing incomplete backup {:?}", path);
+                fs::remove_dir_all(&path)?;
+            }
+        }
+        Ok(Self { dir })
+    }
+
+    /// Back up `engine` as a new backup, flushing its MemTable first.
+    pub fn create_backup(&self, engine: &Oblivion) -> Result<BackupInfo> {
+        let id = self.backup_ids()?.last().map_or(1, |last| last + 1);
+        let tmp = self.dir.join(format!("{}{}", id, TMP_SUFFIX));
+        engine.create_checkpoint(&tmp)?;
+
+        let mut files = Vec::new();
+        Self::collect_files(&tmp, "", &mut files)?;
+        let meta = BackupMeta {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            files,
+        };
+        Self::write_meta(&tmp.join(META_FILE), &meta)?;
+        fs::rename(&tmp, self.backup_path(id))?;
+        io::sync_dir(&self.dir)?;
+
+        let info = Self::info(id, &meta);
+        log::info!(
+            "Backup {} created ({} files, {} bytes)",
+            id,
+            info.file_count,
+            info.size
+        );
+        Ok(info)
+    }
+
+    /// List the stored backups, oldest first.
+    pub fn list_backups(&self) -> Result<Vec<BackupInfo>> {
+        self.backup_ids()?
+            .into_iter()
+            .map(|id| Ok(Self::info(id, &self.read_meta(id)?)))
+            .collect()
+    }
+
+    /// Check that every file of backup `id` is present with the recorded
+    /// size and checksum.
+    pub fn verify_backup(&self, id: u64) -> Result<()> {
+        let meta = self.read_meta(id)?;
+        let root = self.backup_path(id);
+        for file in &meta.files {
+            let data = fs::read(root.join(&file.name))?;
+            if data.len() as u64 != file.size || crc32fast::hash(&data) != file.crc {
+                return Err(OblivionError::Corruption(format!(
+                    "backup {}: {} does not match its checksum",
+                    id, file.name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Verify backup `id` and copy it into `target_dir`, which must not
+    /// exist yet or be empty. The result opens like any data directory.
+    pub fn restore(&self, id: u64, target_dir: impl AsRef<Path>) -> Result<()> {
+        let target_dir = target_dir.as_ref();
+        self.verify_backup(id)?;
+        fs::create_dir_all(target_dir)?;
+        if fs::read_dir(target_dir)?.next().is_some() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("restore target {:?} is not empty", target_dir),
+            )
+            .into());
+        }
+
+        let root = self.backup_path(id);
+        for file in &self.read_meta(id)?.files {
+            let dst = target_dir.join(&file.name);
+            if let Some(parent) = dst.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::copy(root.join(&file.name), &dst)?;
+            fs::File::open(&dst)?.sync_all()?;
+        }
+        log::info!("Backup {} restored to {:?}", id, target_dir);
+        Ok(())
+    }
+
+    /// Delete backup `id`.
+    pub fn delete_backup(&self, id: u64) -> Result<()> {
+        self.read_meta(id)?;
+        fs::remove_dir_all(self.backup_path(id))?;
+        Ok(())
+    }
+
+    /// Delete all but the newest `keep` backups. Returns how many were
+    /// deleted.
+    pub fn purge_old_backups(&self, keep: usize) -> Result<usize> {
+        let ids = self.backup_ids()?;
+        let excess = ids.len().saturating_sub(keep);
+        for &id in &ids[..excess] {
+            self.delete_backup(id)?;
+        }
+        Ok(excess)
+    }
+
+    /// Ids of the complete backups, ascending.
+    fn backup_ids(&self) -> Result<Vec<u64>> {
+        let mut ids = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            let id = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .and_then(|n| n.parse::<u64>().ok());
+            if let Some(id) = id {
+                ids.push(id);
+            }
+        }
+        ids.sort_unstable();
+        Ok(ids)
+    }
+
+    /// Directory of backup `id`.
+    fn backup_path(&self, id: u64) -> PathBuf {
+        self.dir.join(id.to_string())
+    }
+
+    /// Record every file under `dir`, with names relative to the backup.
+    fn collect_files(dir: &Path, prefix: &str, files: &mut Vec<BackupFile>) -> Result<()> {
+        let mut entries = fs::read_dir(dir)?.collect::<std::io::Result<Vec<_>>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+            if entry.file_type()?.is_dir() {
+                Self::collect_files(&entry.path(), &format!("{}/", name), files)?;
+            } else {
+                let data = fs::read(entry.path())?;
+                files.push(BackupFile {
+                    name,
+                    size: data.len() as u64,
+                    crc: crc32fast::hash(&data),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn info(id: u64, meta: &BackupMeta) -> BackupInfo {
+        BackupInfo {
+            id,
+            timestamp: meta.timestamp,
+            size: meta.files.iter().map(|f| f.size).sum(),
+            file_count: meta.files.len(),
+        }
+    }
+
+    fn write_meta(path: &Path, meta: &BackupMeta) -> Result<()> {
+        let payload =
+            bincode::serialize(meta).map_err(|e| OblivionError::Serialization(e.to_string()))?;
+        let mut data = crc32fast::hash(&payload).to_le_bytes().to_vec();
+        data.extend_from_slice(&payload);
+        fs::write(path, data)?;
+        fs::File::open(path)?.sync_all()?;
+        Ok(())
+    }
+
+    fn read_meta(&self, id: u64) -> Result<BackupMeta> {
+        let path = self.backup_path(id).join(META_FILE);
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(OblivionError::BackupNotFound(id))
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if data.len() < 4 || crc32fast::hash(&data[4..]).to_le_bytes() != data[..4] {
+            return Err(OblivionError::Corruption(format!(
+                "backup {}: {:?} failed CRC check",
+                id, path
+            )));
+        }
+        bincode::deserialize(&data[4..])
+            .map_err(|e| OblivionError::Corruption(format!("backup {}: {}", id, e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn config(dir: &Path) -> Config {
+        Config::builder(dir)
+            .memtable_max_size(1024)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_backup_and_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = Oblivion::open(config(&dir.path().join("db"))).unwrap();
+        let backups = BackupEngine::open(dir.path().join("backups")).unwrap();
+
+        for i in 0..100 {
+            let key = format!("key_{:04}", i).into_bytes();
+            engine.put(key, vec![b'v'; 20]).unwrap();
+        }
+        let first = backups.create_backup(&engine).unwrap();
+        engine.put(b"later".to_vec(), b"x".to_vec()).unwrap();
+        let second = backups.create_backup(&engine).unwrap();
+        assert_eq!((first.id, second.id), (1, 2));
+        assert_eq!(backups.list_backups().unwrap(), vec![first.clone(), second]);
+        backups.verify_backup(1).unwrap();
+
+        let restored = dir.path().join("restored");
+        backups.restore(1, &restored).unwrap();
+        assert!(backups.restore(1, &restored).is_err());
+        let copy = Oblivion::open(config(&restored)).unwrap();
+        assert_eq!(copy.scan().len(), 100);
+        assert_eq!(copy.get(b"later"), None);
+
+        assert_eq!(backups.purge_old_backups(1).unwrap(), 1);
+        assert!(matches!(
+            backups.restore(1, dir.path().join("gone")),
+            Err(OblivionError::BackupNotFound(1))
+        ));
+    }
+
+    #[test]
+    fn test_verify_detects_corruption() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = Oblivion::open(config(&dir.path().join("db"))).unwrap();
+        engine.put(b"k".to_vec(), b"v".to_vec()).unwrap();
+        let backups = BackupEngine::open(dir.path().join("backups")).unwrap();
+        let info = backups.create_backup(&engine).unwrap();
+
+        fs::write(dir.path().join("backups/1/MANIFEST"), b"garbage").unwrap();
+        let err = backups.verify_backup(info.id).unwrap_err();
+        assert!(matches!(err, OblivionError::Corruption(_)));
+        assert!(backups
+            .restore(info.id, dir.path().join("restored"))
+            .is_err());
+    }
+}
@@ -0,0 +1,154 @@
+//! OBLIVION - Write Batches
+//! Groups of puts and deletes applied with one WAL append and, with
+//! `sync_writes`, one fsync.
+//!
+//! ## Guarantees
+//! [`Oblivion::write`](super::Oblivion::write) applies a batch under the
+//! writer lock and the MemTable lock, so readers see either none or all
+//! of its writes, and no other write interleaves with it. Each write
+//! still takes its own sequence number and WAL record, so change
+//! subscribers and followers receive them one by one. A crash during
+//! the append can leave a prefix of the batch in the WAL, which
+//! recovery replays.
+
+use crate::types::{Key, Value};
+
+/// One write of a [`WriteBatch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchOp {
+    /// A put, with its TTL if added by [`WriteBatch::put_with_ttl`].
+    Put {
+        key: Key,
+        value: Value,
+        ttl_ms: Option<u64>,
+    },
+    /// A delete.
+    Delete { key: Key },
+}
+
+impl BatchOp {
+    /// Returns the key the write is about.
+    pub fn key(&self) -> &[u8] {
+        match self {
+            BatchOp::Put { key, .. } | BatchOp::Delete { key } => key,
+        }
+    }
+}
+
+/// An ordered group of writes to apply together.
+///
+/// ## Example
+/// ```no_run
+/// use oblivion::config::Config;
+/// use oblivion::engine::{batch::WriteBatch, Oblivion};
+///
+/// let engine = Oblivion::open(Config::default()).unwrap();
+/// let mut batch = WriteBatch::new();
+/// batch.put(b"user:1".to_vec(), b"alice".to_vec());
+/// batch.delete(b"user:0".to_vec());
+/// engine.write(batch).unwrap();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WriteBatch {
+    ops: Vec<BatchOp>,
+}
+
+impl WriteBatch {
+    /// Create an empty batch.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a put.
+    pub fn put(&mut self, key: impl Into<Key>, value: impl Into<Value>) -> &mut Self {
+        self.ops.push(BatchOp::Put {
+            key: key.into(),
+            value: value.into(),
+            ttl_ms: None,
+        });
+        self
+    }
+
+    /// Add a put that expires after `ttl_ms` milliseconds.
+    pub fn put_with_ttl(
+        &mut self,
+        key: impl Into<Key>,
+        value: impl Into<Value>,
+        ttl_ms: u64,
+    ) -> &mut Self {
+        self.ops.push(BatchOp::Put {
+            key: key.into(),
+            value: value.into(),
+            ttl_ms: Some(ttl_ms),
+        });
+        self
+    }
+
+    /// Add a delete.
+    pub fn delete(&mut self, key: impl Into<Key>) -> &mut Self {
+        self.ops.push(BatchOp::Delete { key: key.into() });
+        self
+    }
+
+    /// Returns the number of writes in the batch.
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// Returns whether the batch holds no writes.
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// Remove every write, keeping the allocation.
+    pub fn clear(&mut self) {
+        self.ops.clear();
+    }
+
+    /// Returns the writes in the order they are applied.
+    pub fn ops(&self) -> &[BatchOp] {
+        &self.ops
+    }
+
+    /// Approximate bytes of keys and values in the batch.
+    pub fn size(&self) -> usize {
+        self.ops
+            .iter()
+            .map(|op| match op {
+                BatchOp::Put { key, value, .. } => key.len() + value.len(),
+                BatchOp::Delete { key } => key.len(),
+            })
+            .sum()
+    }
+
+    pub(crate) fn into_ops(self) -> Vec<BatchOp> {
+        self.ops
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_keeps_order() {
+        let mut batch = WriteBatch::new();
+        batch
+            .put(b"a".to_vec(), b"1".to_vec())
+            .put_with_ttl(b"b".to_vec(), b"22".to_vec(), 1000)
+            .delete(b"a".to_vec());
+        assert_eq!(batch.len(), 3);
+        assert_eq!(batch.size(), 1 + 1 + 1 + 2 + 1);
+        let keys: Vec<&[u8]> = batch.ops().iter().map(BatchOp::key).collect();
+        assert_eq!(keys, vec![&b"a"[..], b"b", b"a"]);
+        assert!(matches!(
+            batch.ops()[1],
+            BatchOp::Put {
+                ttl_ms: Some(1000),
+                ..
+            }
+        ));
+        batch.clear();
+        assert!(batch.is_empty());
+    }
+}
@@ -6,8 +6,7 @@
 //! Used in LSM-Trees to skip SSTable reads for keys that
 //! definitely do not exist in a given table.
 
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
+use super::filter::hash64;
 
 /// A Bloom filter for probabilistic set membership testing.
 ///
@@ -130,6 +129,44 @@ impl BloomFilter {
         (1.0 - (-k * n / m).exp()).powf(k)
     }
 
+    /// Serialize the filter into a self-describing byte buffer.
+    ///
+    /// ## Format
+    /// ```text
+    /// [num_bits: 8 bytes LE][num_hashes: 4 bytes LE][count: 8 bytes LE][bits: N bytes]
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(20 + self.bits.len());
+        buf.extend_from_slice(&(self.num_bits as u64).to_le_bytes());
+        buf.extend_from_slice(&self.num_hashes.to_le_bytes());
+        buf.extend_from_slice(&(self.count as u64).to_le_bytes());
+        buf.extend_from_slice(&self.bits);
+        buf
+    }
+
+    /// Deserialize a filter produced by [`BloomFilter::to_bytes`].
+    /// Returns `None` if the buffer is truncated or inconsistent.
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        if data.len() < 20 {
+            return None;
+        }
+        let num_bits = u64::from_le_bytes(data[0..8].try_into().ok()?) as usize;
+        let num_hashes = u32::from_le_bytes(data[8..12].try_into().ok()?);
+        let count = u64::from_le_bytes(data[12..20].try_into().ok()?) as usize;
+        let bits = data[20..].to_vec();
+
+        if num_bits == 0 || bits.len() != num_bits.div_ceil(8) {
+            return None;
+        }
+
+        Some(Self {
+            bits,
+            num_bits,
+            num_hashes,
+            count,
+        })
+    }
+
     /// Generate a bit index using double hashing.
     /// Uses the technique: `h(i) = h1 + i * h2` (mod m)
     /// where h1 and h2 are derived from two independent hashes.
@@ -140,12 +177,10 @@ impl BloomFilter {
         (combined % self.num_bits as u64) as usize
     }
 
-    /// Hash a key with a given seed using SipHash.
+    /// Hash a key with a given seed.
+    /// Uses a stable hash so serialized filters stay valid across builds.
     fn hash_with_seed(&self, key: &[u8], seed: u64) -> u64 {
-        let mut hasher = DefaultHasher::new();
-        seed.hash(&mut hasher);
-        key.hash(&mut hasher);
-        hasher.finish()
+        hash64(key, seed)
     }
 }
 
@@ -231,4 +266,19 @@ mod tests {
         assert!(bf.num_bits() >= 64);
         assert!(bf.num_hashes() >= 2);
     }
+
+    #[test]
+    fn test_bytes_roundtrip() {
+        let mut bf = BloomFilter::new(100, 0.01);
+        bf.insert(b"alpha");
+        bf.insert(b"bravo");
+
+        let restored = BloomFilter::from_bytes(&bf.to_bytes()).unwrap();
+        assert!(restored.may_contain(b"alpha"));
+        assert!(restored.may_contain(b"bravo"));
+        assert_eq!(restored.count(), 2);
+        assert_eq!(restored.num_bits(), bf.num_bits());
+
+        assert!(BloomFilter::from_bytes(&[0u8; 4]).is_none());
+    }
 }
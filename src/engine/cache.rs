@@ -0,0 +1,638 @@
+//! OBLIVION - Caches
+//! In-memory caches sitting above the SSTable layer.
+//!
+//! ## Row Cache
+//! Maps keys to their current value, so hot point lookups in
+//! read-heavy, skewed workloads skip filter probes, index navigation
+//! and block reads entirely. Entries are invalidated on every write
+//! to the key, so the cache never serves stale data.
+//!
+//! ## Block Cache
+//! Keeps decoded data blocks of SSTables, so repeated reads of the
+//! same blocks skip the file read, checksum and decompression. Blocks
+//! are cached under the id of the table they belong to, which is never
+//! reused, so blocks of replaced tables are never served and simply
+//! age out. Only blocks whose checksum was verified are cached, and
+//! reads with `fill_cache` off look blocks up without inserting them.
+//!
+//! ## Table Cache
+//! Bounds the number of SSTable file descriptors held open at once.
+//! Handles are opened on demand and the least recently used one is
+//! closed when `max_open_files` is reached. With direct I/O enabled,
+//! handles are opened with `O_DIRECT`. It also carries the file system
+//! tables live on, the comparator the tables it opens are sorted by,
+//! and whether their blocks are read with paranoid checks.
+
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use arc_swap::ArcSwapOption;
+use parking_lot::Mutex;
+
+use crate::engine::comparator::Comparator;
+use crate::engine::env::{Env, RandomAccessFile};
+use crate::engine::metrics::EngineMetrics;
+use crate::error::Result;
+use crate::types::Key;
+
+/// Fixed per-entry bookkeeping overhead charged against the capacity.
+const ENTRY_OVERHEAD: usize = 64;
+
+/// Least-recently-used cache bounded by total charge (usually bytes).
+///
+/// ## Design
+/// - `HashMap<K, slot>` for O(1) lookups
+/// - `BTreeMap<tick, K>` ordering entries by last use, for O(log n) eviction
+pub struct LruCache<K, V> {
+    /// Key -> (value, last-use tick, charge).
+    entries: HashMap<K, (V, u64, usize)>,
+    /// Last-use tick -> key, oldest first.
+    order: BTreeMap<u64, K>,
+    /// Monotonic use counter.
+    tick: u64,
+    /// Sum of charges of all entries.
+    usage: usize,
+    /// Maximum total charge.
+    capacity: usize,
+}
+
+impl<K: Hash + Eq + Clone, V> LruCache<K, V> {
+    /// Create an empty cache holding at most `capacity` total charge.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            tick: 0,
+            usage: 0,
+            capacity,
+        }
+    }
+
+    /// Look up a key, marking it as most recently used.
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let entry = self.entries.get_mut(key)?;
+        self.tick += 1;
+        if let Some(key) = self.order.remove(&entry.1) {
+            self.order.insert(self.tick, key);
+        }
+        entry.1 = self.tick;
+        Some(&entry.0)
+    }
+
+    /// Insert or replace an entry, evicting least recently used entries
+    /// until the total charge fits. Entries larger than the whole cache
+    /// are not admitted.
+    pub fn insert(&mut self, key: K, value: V, charge: usize) {
+        self.remove(&key);
+        if charge > self.capacity {
+            return;
+        }
+        self.tick += 1;
+        self.order.insert(self.tick, key.clone());
+        self.entries.insert(key, (value, self.tick, charge));
+        self.usage += charge;
+        self.evict_to_fit();
+    }
+
+    /// Remove an entry, returning its value.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (value, last_use, charge) = self.entries.remove(key)?;
+        self.order.remove(&last_use);
+        self.usage -= charge;
+        Some(value)
+    }
+
+    /// Remove every entry.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+        self.usage = 0;
+    }
+
+    /// Returns the number of cached entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if the cache holds no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the total charge of all entries.
+    pub fn usage(&self) -> usize {
+        self.usage
+    }
+
+    /// Returns the maximum total charge.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Change the capacity, evicting least recently used entries to fit.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.evict_to_fit();
+    }
+
+    /// Evict least recently used entries until usage fits the capacity.
+    fn evict_to_fit(&mut self) {
+        while self.usage > self.capacity {
+            let Some((_, oldest)) = self.order.pop_first() else {
+                break;
+            };
+            if let Some((_, _, charge)) = self.entries.remove(&oldest) {
+                self.usage -= charge;
+            }
+        }
+    }
+}
+
+/// Thread-safe key → value cache for point lookups served from SSTables.
+pub struct RowCache {
+    inner: Mutex<LruCache<Key, Arc<[u8]>>>,
+    /// Bumped (under `inner`'s lock) by every invalidation.
+    generation: AtomicU64,
+}
+
+impl RowCache {
+    /// Create a row cache holding at most `capacity` bytes.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Mutex::new(LruCache::new(capacity)),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    /// Look up a cached value.
+    pub fn get(&self, key: &[u8]) -> Option<Arc<[u8]>> {
+        self.inner.lock().get(key).cloned()
+    }
+
+    /// Cache the current value of a key.
+    pub fn insert(&self, key: Key, value: Arc<[u8]>) {
+        let charge = key.len() + value.len() + ENTRY_OVERHEAD;
+        self.inner.lock().insert(key, value, charge);
+    }
+
+    /// Current invalidation generation, for [`insert_if_current`](Self::insert_if_current).
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// Cache a value read while the cache was at `generation`, unless a
+    /// write has invalidated the cache since; the value may be stale then.
+    pub fn insert_if_current(&self, key: Key, value: Arc<[u8]>, generation: u64) -> bool {
+        let charge = key.len() + value.len() + ENTRY_OVERHEAD;
+        let mut inner = self.inner.lock();
+        if self.generation.load(Ordering::Acquire) != generation {
+            return false;
+        }
+        inner.insert(key, value, charge);
+        true
+    }
+
+    /// Drop a key from the cache (called on every write to the key).
+    pub fn invalidate(&self, key: &[u8]) {
+        let mut inner = self.inner.lock();
+        inner.remove(key);
+        self.generation.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// Drop every cached entry.
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock();
+        inner.clear();
+        self.generation.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// Returns the number of cached rows.
+    pub fn len(&self) -> usize {
+        self.inner.lock().len()
+    }
+
+    /// Returns true if no rows are cached.
+    pub fn is_empty(&self) -> bool {
+        self.inner.lock().is_empty()
+    }
+
+    /// Returns the bytes currently charged against the capacity.
+    pub fn usage(&self) -> usize {
+        self.inner.lock().usage()
+    }
+
+    /// Resize the cache, evicting rows if it shrinks.
+    pub fn set_capacity(&self, capacity: usize) {
+        self.inner.lock().set_capacity(capacity);
+    }
+}
+
+/// Id of the table a cached block belongs to, and the block's offset.
+type BlockKey = (u64, u64);
+
+/// Thread-safe cache of decoded SSTable data blocks, keyed by table id
+/// and block offset.
+pub struct BlockCache {
+    inner: Mutex<LruCache<BlockKey, Arc<[u8]>>>,
+}
+
+impl BlockCache {
+    /// Create a block cache holding at most `capacity` bytes.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// Look up the block at `offset` of the table `table`.
+    pub fn get(&self, table: u64, offset: u64) -> Option<Arc<[u8]>> {
+        self.inner.lock().get(&(table, offset)).cloned()
+    }
+
+    /// Cache the block at `offset` of the table `table`.
+    pub fn insert(&self, table: u64, offset: u64, block: Arc<[u8]>) {
+        let charge = block.len() + ENTRY_OVERHEAD;
+        self.inner.lock().insert((table, offset), block, charge);
+    }
+
+    /// Drop every cached block.
+    pub fn clear(&self) {
+        self.inner.lock().clear();
+    }
+
+    /// Returns the number of cached blocks.
+    pub fn len(&self) -> usize {
+        self.inner.lock().len()
+    }
+
+    /// Returns true if no blocks are cached.
+    pub fn is_empty(&self) -> bool {
+        self.inner.lock().is_empty()
+    }
+
+    /// Returns the bytes currently charged against the capacity.
+    pub fn usage(&self) -> usize {
+        self.inner.lock().usage()
+    }
+
+    /// Resize the cache, evicting blocks if it shrinks.
+    pub fn set_capacity(&self, capacity: usize) {
+        self.inner.lock().set_capacity(capacity);
+    }
+}
+
+/// Shared SSTable file handle.
+pub type TableFile = Arc<Mutex<Box<dyn RandomAccessFile>>>;
+
+/// LRU cache of open SSTable file handles, bounded by `max_open_files`.
+///
+/// Evicted handles are closed once the last in-flight read using them
+/// finishes; the next read of that table simply reopens the file.
+pub struct TableCache {
+    inner: Mutex<LruCache<PathBuf, TableFile>>,
+    /// Decoded data blocks of the tables, if enabled.
+    block_cache: ArcSwapOption<BlockCache>,
+    /// Id handed to the next table opened, for block cache keys.
+    next_table_id: AtomicU64,
+    /// Metrics receiving hit/miss counts, if attached.
+    metrics: Option<Arc<EngineMetrics>>,
+    /// File system the tables are read from and written to.
+    env: Env,
+    /// Whether tables are read and written with direct I/O.
+    direct_io: bool,
+    /// Order of the keys in the tables.
+    comparator: Comparator,
+    /// Whether every data block read is fully verified.
+    paranoid_checks: bool,
+    /// io_uring used for block reads, if enabled.
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    ring: Option<Arc<crate::engine::uring::Ring>>,
+}
+
+impl TableCache {
+    /// Create a table cache keeping at most `max_open_files` handles open.
+    pub fn new(max_open_files: usize) -> Self {
+        Self {
+            inner: Mutex::new(LruCache::new(max_open_files)),
+            block_cache: ArcSwapOption::empty(),
+            next_table_id: AtomicU64::new(1),
+            metrics: None,
+            env: Env::default(),
+            direct_io: false,
+            comparator: Comparator::default(),
+            paranoid_checks: false,
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            ring: None,
+        }
+    }
+
+    /// Read and write table files on `env`.
+    pub fn with_env(mut self, env: Env) -> Self {
+        self.env = env;
+        self
+    }
+
+    /// Returns the file system tables live on.
+    pub fn env(&self) -> &Env {
+        &self.env
+    }
+
+    /// Open table files with `O_DIRECT`, bypassing the page cache.
+    pub fn with_direct_io(mut self, direct_io: bool) -> Self {
+        self.direct_io = direct_io;
+        self
+    }
+
+    /// Returns true if tables use direct I/O.
+    pub fn direct_io(&self) -> bool {
+        self.direct_io
+    }
+
+    /// Read and write tables sorted by `comparator`.
+    pub fn with_comparator(mut self, comparator: Comparator) -> Self {
+        self.comparator = comparator;
+        self
+    }
+
+    /// Returns the comparator tables are sorted by.
+    pub fn comparator(&self) -> &Comparator {
+        &self.comparator
+    }
+
+    /// Verify the checksum and records of every data block read,
+    /// whatever the read asks for.
+    pub fn with_paranoid_checks(mut self, paranoid: bool) -> Self {
+        self.paranoid_checks = paranoid;
+        self
+    }
+
+    /// Returns true if every data block read is fully verified.
+    pub fn paranoid_checks(&self) -> bool {
+        self.paranoid_checks
+    }
+
+    /// Read blocks through `ring`. Ignored with direct I/O, whose reads
+    /// must be aligned.
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    pub fn with_ring(mut self, ring: Arc<crate::engine::uring::Ring>) -> Self {
+        self.ring = Some(ring);
+        self
+    }
+
+    /// The ring block reads go through, if any.
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    pub fn ring(&self) -> Option<&crate::engine::uring::Ring> {
+        self.ring.as_deref().filter(|_| !self.direct_io)
+    }
+
+    /// Report hits and misses to `metrics`.
+    pub fn with_metrics(mut self, metrics: Arc<EngineMetrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Cache up to `capacity` bytes of data blocks (0 disables the
+    /// block cache).
+    pub fn with_block_cache(self, capacity: usize) -> Self {
+        self.set_block_cache_capacity(capacity);
+        self
+    }
+
+    /// Returns the block cache, if enabled.
+    pub fn block_cache(&self) -> Option<Arc<BlockCache>> {
+        self.block_cache.load_full()
+    }
+
+    /// Resize the block cache, creating it if it was disabled and
+    /// dropping it if `capacity` is 0.
+    pub fn set_block_cache_capacity(&self, capacity: usize) {
+        match (self.block_cache.load_full(), capacity) {
+            (_, 0) => self.block_cache.store(None),
+            (Some(cache), capacity) => cache.set_capacity(capacity),
+            (None, capacity) => self
+                .block_cache
+                .store(Some(Arc::new(BlockCache::new(capacity)))),
+        }
+    }
+
+    /// Returns a new id for a table being opened. Ids are never
+    /// reused, so blocks cached for a replaced table are never served.
+    pub(crate) fn new_table_id(&self) -> u64 {
+        self.next_table_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Returns the cached block at `offset` of the table `table`, if the
+    /// block cache is enabled and holds it.
+    pub(crate) fn cached_block(&self, table: u64, offset: u64) -> Option<Arc<[u8]>> {
+        let cache = self.block_cache.load();
+        let cached = cache.as_ref()?.get(table, offset);
+        if let Some(metrics) = &self.metrics {
+            metrics.record_block_cache(cached.is_some());
+        }
+        cached
+    }
+
+    /// Cache the block at `offset` of the table `table`, if the block
+    /// cache is enabled.
+    pub(crate) fn cache_block(&self, table: u64, offset: u64, block: Arc<[u8]>) {
+        if let Some(cache) = &*self.block_cache.load() {
+            cache.insert(table, offset, block);
+        }
+    }
+
+    /// Returns the open handle for `path`, opening the file on a miss.
+    pub fn file(&self, path: &Path) -> Result<TableFile> {
+        let mut inner = self.inner.lock();
+        let cached = inner.get(path).map(Arc::clone);
+        if let Some(metrics) = &self.metrics {
+            metrics.record_table_cache(cached.is_some());
+        }
+        if let Some(file) = cached {
+            return Ok(file);
+        }
+        let file = Arc::new(Mutex::new(self.env.open_read(path, self.direct_io)?));
+        inner.insert(path.to_path_buf(), Arc::clone(&file), 1);
+        Ok(file)
+    }
+
+    /// Close the cached handle for `path` (the file was replaced or removed).
+    pub fn evict(&self, path: &Path) {
+        self.inner.lock().remove(path);
+    }
+
+    /// Returns the number of open handles.
+    pub fn len(&self) -> usize {
+        self.inner.lock().len()
+    }
+
+    /// Returns true if no handles are open.
+    pub fn is_empty(&self) -> bool {
+        self.inner.lock().is_empty()
+    }
+
+    /// Returns the maximum number of open handles.
+    pub fn capacity(&self) -> usize {
+        self.inner.lock().capacity()
+    }
+
+    /// Change `max_open_files`, closing handles if the limit shrinks.
+    pub fn set_capacity(&self, max_open_files: usize) {
+        self.inner.lock().set_capacity(max_open_files);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lru_get_and_insert() {
+        let mut cache = LruCache::new(100);
+        cache.insert("a", 1, 10);
+        cache.insert("b", 2, 10);
+
+        assert_eq!(cache.get(&"a"), Some(&1));
+        assert_eq!(cache.get(&"missing"), None);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.usage(), 20);
+    }
+
+    #[test]
+    fn test_lru_evicts_least_recently_used() {
+        let mut cache = LruCache::new(30);
+        cache.insert("a", 1, 10);
+        cache.insert("b", 2, 10);
+        cache.insert("c", 3, 10);
+
+        // Touch "a" so "b" becomes the eviction candidate
+        cache.get(&"a");
+        cache.insert("d", 4, 10);
+
+        assert_eq!(cache.get(&"b"), None);
+        assert_eq!(cache.get(&"a"), Some(&1));
+        assert_eq!(cache.get(&"d"), Some(&4));
+        assert_eq!(cache.usage(), 30);
+    }
+
+    #[test]
+    fn test_lru_replace_and_remove() {
+        let mut cache = LruCache::new(100);
+        cache.insert("a", 1, 10);
+        cache.insert("a", 2, 20);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.usage(), 20);
+        assert_eq!(cache.get(&"a"), Some(&2));
+
+        assert_eq!(cache.remove(&"a"), Some(2));
+        assert!(cache.is_empty());
+        assert_eq!(cache.usage(), 0);
+    }
+
+    #[test]
+    fn test_lru_shrink_capacity() {
+        let mut cache = LruCache::new(30);
+        cache.insert("a", 1, 10);
+        cache.insert("b", 2, 10);
+        cache.insert("c", 3, 10);
+
+        cache.set_capacity(15);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.get(&"c"), Some(&3));
+        assert_eq!(cache.capacity(), 15);
+    }
+
+    #[test]
+    fn test_lru_rejects_oversized_entry() {
+        let mut cache = LruCache::new(10);
+        cache.insert("small", 1, 5);
+        cache.insert("huge", 2, 50);
+        assert_eq!(cache.get(&"huge"), None);
+        assert_eq!(cache.get(&"small"), Some(&1));
+    }
+
+    #[test]
+    fn test_row_cache_invalidate() {
+        let cache = RowCache::new(1024);
+        cache.insert(b"key".to_vec(), Arc::from(&b"value"[..]));
+        assert_eq!(cache.get(b"key").as_deref(), Some(&b"value"[..]));
+
+        cache.invalidate(b"key");
+        assert!(cache.get(b"key").is_none());
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_row_cache_rejects_stale_fill() {
+        let cache = RowCache::new(1024);
+        let generation = cache.generation();
+
+        // A write lands between the reader's lookup and its cache fill
+        cache.invalidate(b"key");
+        assert!(!cache.insert_if_current(b"key".to_vec(), Arc::from(&b"old"[..]), generation));
+        assert!(cache.get(b"key").is_none());
+
+        assert!(cache.insert_if_current(
+            b"key".to_vec(),
+            Arc::from(&b"new"[..]),
+            cache.generation()
+        ));
+        assert_eq!(cache.get(b"key").as_deref(), Some(&b"new"[..]));
+    }
+
+    #[test]
+    fn test_block_cache_serves_blocks_of_the_same_table_only() {
+        let cache = TableCache::new(4).with_block_cache(1024);
+        let (old, new) = (cache.new_table_id(), cache.new_table_id());
+        assert_ne!(old, new);
+
+        cache.cache_block(old, 0, Arc::from(&b"block"[..]));
+        assert_eq!(cache.cached_block(old, 0).as_deref(), Some(&b"block"[..]));
+        assert!(cache.cached_block(old, 64).is_none());
+        assert!(cache.cached_block(new, 0).is_none());
+
+        // Disabling drops the blocks; re-enabling starts empty
+        cache.set_block_cache_capacity(0);
+        assert!(cache.block_cache().is_none());
+        assert!(cache.cached_block(old, 0).is_none());
+        cache.set_block_cache_capacity(1024);
+        assert!(cache.block_cache().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_table_cache_bounds_open_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths: Vec<PathBuf> = (0..5)
+            .map(|i| {
+                let path = dir.path().join(format!("{}.sst", i));
+                std::fs::write(&path, b"data").unwrap();
+                path
+            })
+            .collect();
+
+        let cache = TableCache::new(2);
+        for path in &paths {
+            cache.file(path).unwrap();
+        }
+        assert_eq!(cache.len(), 2);
+
+        // Evicted handles are transparently reopened
+        let first = cache.file(&paths[0]).unwrap();
+        assert!(Arc::ptr_eq(&first, &cache.file(&paths[0]).unwrap()));
+        cache.evict(&paths[0]);
+        assert!(!Arc::ptr_eq(&first, &cache.file(&paths[0]).unwrap()));
+
+        assert!(cache.file(&dir.path().join("missing.sst")).is_err());
+    }
+}
@@ -0,0 +1,537 @@
+//! OBLIVION - Change Data Capture
+//! Ordered streams of committed writes for downstream systems (search
+//! indexes, caches) that must follow the engine without double-writing.
+//!
+//! ## History and Live Writes
+//! [`Oblivion::subscribe_changes`](super::Oblivion::subscribe_changes)
+//! notes the latest sequence number under the writer lock and registers
+//! a live channel at the same moment. Older writes are read back from
+//! the WAL: the archived segments kept by `wal_retention_size`, the
+//! segments of MemTables not flushed yet, and the live log. Later writes
+//! arrive on the channel, so the stream has no gaps or duplicates.
+//!
+//! ## Limits
+//! - The WAL does not store TTLs, so puts read from history report
+//!   `ttl_ms: None`; live puts carry the TTL they were written with
+//! - Rows loaded by `import` or `ingest_foreign_tables` bypass the WAL
+//!   and are not reported
+//! - Writes to column families are reported as [`ChangeOp::PutCf`] and
+//!   [`ChangeOp::DeleteCf`], numbered along with the default keyspace's;
+//!   watches cover the default keyspace only
+//! - A subscriber more than [`SUBSCRIBER_BUFFER`] writes behind is
+//!   dropped; its stream then fails with
+//!   [`OblivionError::ChangesUnavailable`] naming the sequence number
+//!   to resubscribe from
+//!
+//! ## Watches
+//! [`Oblivion::watch_prefix`](super::Oblivion::watch_prefix) delivers
+//! [`ChangeEvent`]s for the keys under a prefix, like an etcd watch:
+//! puts and deletes as they commit, and expirations when the engine
+//! removes an expired key (the TTL sweep with `background_threads`,
+//! otherwise the next flush). Watches start at the current write and
+//! do not replay history. Dropping the receiver cancels the watch; a
+//! watcher more than [`WATCH_BUFFER`] events behind is cancelled by the
+//! engine, which disconnects its receiver as closing the engine does.
+
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::error::{OblivionError, Result};
+use crate::types::{Key, Value};
+
+use super::env::RandomAccessFile;
+use super::wal::{LoggedWrite, WriteAheadLog};
+
+/// Live writes buffered per subscriber before it is dropped as too slow.
+pub const SUBSCRIBER_BUFFER: usize = 16 * 1024;
+
+/// Events buffered per watcher before it is cancelled as too slow.
+pub const WATCH_BUFFER: usize = 16 * 1024;
+
+/// A committed write, as streamed to subscribers and followers.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangeRecord {
+    /// WAL sequence number of the write.
+    pub sequence: u64,
+    /// What the write did.
+    pub op: ChangeOp,
+}
+
+/// Operation of a [`ChangeRecord`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChangeOp {
+    /// A put, with its TTL if written by `put_with_ttl`.
+    Put {
+        key: Key,
+        value: Value,
+        ttl_ms: Option<u64>,
+    },
+    /// A delete.
+    Delete { key: Key },
+    /// A put into the column family `cf`.
+    PutCf { cf: String, key: Key, value: Value },
+    /// A delete from the column family `cf`.
+    DeleteCf { cf: String, key: Key },
+}
+
+impl ChangeRecord {
+    /// Approximate memory held by the record.
+    pub(crate) fn size(&self) -> usize {
+        16 + match &self.op {
+            ChangeOp::Put { key, value, .. } => key.len() + value.len(),
+            ChangeOp::Delete { key } => key.len(),
+            ChangeOp::PutCf { cf, key, value } => cf.len() + key.len() + value.len(),
+            ChangeOp::DeleteCf { cf, key } => cf.len() + key.len(),
+        }
+    }
+}
+
+/// The live channels of an engine's subscribers and watchers.
+pub(crate) struct ChangeFeed {
+    subscribers: Mutex<Vec<Subscriber>>,
+    watchers: Mutex<Vec<Watcher>>,
+    /// Number of subscribers and watchers, read without the locks on
+    /// every write.
+    listeners: AtomicUsize,
+    /// Number of watchers, read on every expiration.
+    watching: AtomicUsize,
+}
+
+struct Subscriber {
+    sender: SyncSender<ChangeRecord>,
+    /// Set when the subscriber is dropped for falling behind.
+    lagged: Arc<AtomicBool>,
+}
+
+struct Watcher {
+    prefix: Key,
+    sender: SyncSender<ChangeEvent>,
+}
+
+/// A change to a watched key, from [`Oblivion::watch_prefix`](super::Oblivion::watch_prefix).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangeEvent {
+    /// The key was written.
+    Put {
+        /// WAL sequence number of the write.
+        sequence: u64,
+        key: Key,
+        value: Value,
+    },
+    /// The key was deleted (whether or not it existed).
+    Delete {
+        /// WAL sequence number of the delete.
+        sequence: u64,
+        key: Key,
+    },
+    /// The key's TTL ran out and the engine removed it.
+    Expired { key: Key },
+}
+
+impl ChangeEvent {
+    /// Returns the key the event is about.
+    pub fn key(&self) -> &[u8] {
+        match self {
+            ChangeEvent::Put { key, .. }
+            | ChangeEvent::Delete { key, .. }
+            | ChangeEvent::Expired { key } => key,
+        }
+    }
+}
+
+impl ChangeFeed {
+    /// Create a feed without subscribers or watchers.
+    pub(crate) fn new() -> Self {
+        Self {
+            subscribers: Mutex::new(Vec::new()),
+            watchers: Mutex::new(Vec::new()),
+            listeners: AtomicUsize::new(0),
+            watching: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns whether any subscriber or watcher is registered.
+    pub(crate) fn is_active(&self) -> bool {
+        self.listeners.load(Ordering::Acquire) > 0
+    }
+
+    /// Register a subscriber for the writes after the current one. Must
+    /// be called under the writer lock.
+    fn subscribe(&self) -> (Receiver<ChangeRecord>, Arc<AtomicBool>) {
+        let (sender, receiver) = mpsc::sync_channel(SUBSCRIBER_BUFFER);
+        let lagged = Arc::new(AtomicBool::new(false));
+        self.subscribers.lock().push(Subscriber {
+            sender,
+            lagged: Arc::clone(&lagged),
+        });
+        self.listeners.fetch_add(1, Ordering::AcqRel);
+        (receiver, lagged)
+    }
+
+    /// Register a watcher for the keys starting with `prefix`.
+    pub(crate) fn watch(&self, prefix: Key) -> Receiver<ChangeEvent> {
+        let (sender, receiver) = mpsc::sync_channel(WATCH_BUFFER);
+        self.watchers.lock().push(Watcher { prefix, sender });
+        self.listeners.fetch_add(1, Ordering::AcqRel);
+        self.watching.fetch_add(1, Ordering::AcqRel);
+        receiver
+    }
+
+    /// Send a committed write to every subscriber and matching watcher,
+    /// dropping those that are gone or too far behind. Called under the
+    /// writer lock, so records go out in sequence order.
+    pub(crate) fn publish(&self, record: &ChangeRecord) {
+        let mut subscribers = self.subscribers.lock();
+        let before = subscribers.len();
+        subscribers.retain(
+            |subscriber| match subscriber.sender.try_send(record.clone()) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) => {
+                    subscriber.lagged.store(true, Ordering::Release);
+                    false
+                }
+                Err(TrySendError::Disconnected(_)) => false,
+            },
+        );
+        self.listeners
+            .fetch_sub(before - subscribers.len(), Ordering::AcqRel);
+        drop(subscribers);
+
+        if self.watching.load(Ordering::Acquire) == 0 {
+            return;
+        }
+        let sequence = record.sequence;
+        let event = match &record.op {
+            ChangeOp::Put { key, value, .. } => ChangeEvent::Put {
+                sequence,
+                key: key.clone(),
+                value: value.clone(),
+            },
+            ChangeOp::Delete { key } => ChangeEvent::Delete {
+                sequence,
+                key: key.clone(),
+            },
+            // Watches cover the default keyspace only
+            ChangeOp::PutCf { .. } | ChangeOp::DeleteCf { .. } => return,
+        };
+        self.notify(event.key(), || event.clone());
+    }
+
+    /// Tell the matching watchers that `key` expired.
+    pub(crate) fn publish_expired(&self, key: &[u8]) {
+        if self.watching.load(Ordering::Acquire) > 0 {
+            self.notify(key, || ChangeEvent::Expired { key: key.to_vec() });
+        }
+    }
+
+    /// Send an event about `key` to the watchers of its prefixes,
+    /// cancelling those that are gone or too far behind.
+    fn notify(&self, key: &[u8], event: impl Fn() -> ChangeEvent) {
+        let mut watchers = self.watchers.lock();
+        let before = watchers.len();
+        watchers.retain(|watcher| {
+            !key.starts_with(&watcher.prefix) || watcher.sender.try_send(event()).is_ok()
+        });
+        let cancelled = before - watchers.len();
+        self.listeners.fetch_sub(cancelled, Ordering::AcqRel);
+        self.watching.fetch_sub(cancelled, Ordering::AcqRel);
+    }
+}
+
+/// Committed writes from a starting sequence number on, in order; from
+/// [`Oblivion::subscribe_changes`](super::Oblivion::subscribe_changes).
+///
+/// Iterating blocks until the next write is committed, and ends when
+/// the engine is dropped. After an error the stream is over.
+///
+/// ## Example
+/// ```no_run
+/// use oblivion::{config::Config, engine::{changes::ChangeOp, Oblivion}};
+///
+/// let engine = Oblivion::open(Config::new("./data")).unwrap();
+/// // Only writes from now on
+/// let changes = engine.subscribe_changes(engine.latest_sequence() + 1).unwrap();
+/// for record in changes {
+///     match record.unwrap().op {
+///         ChangeOp::Put { key, .. } => println!("put {:?}", key),
+///         ChangeOp::Delete { key } => println!("delete {:?}", key),
+///         ChangeOp::PutCf { cf, key, .. } => println!("put {:?} in {}", key, cf),
+///         ChangeOp::DeleteCf { cf, key } => println!("delete {:?} in {}", key, cf),
+///     }
+/// }
+/// ```
+pub struct ChangeStream {
+    /// Sequence number of the next write to return.
+    next: u64,
+    /// Last write covered by history; later ones arrive on `live`.
+    until: u64,
+    /// Logs still to read history from, oldest first.
+    logs: VecDeque<Box<dyn RandomAccessFile>>,
+    /// Sequence number of the last write read from history.
+    log_sequence: u64,
+    /// Writes read from history and not returned yet.
+    pending: VecDeque<ChangeRecord>,
+    /// Names of the column families, by the id their WAL records carry.
+    families: HashMap<u32, String>,
+    live: Receiver<ChangeRecord>,
+    lagged: Arc<AtomicBool>,
+    finished: bool,
+}
+
+impl ChangeStream {
+    /// Skip the logs that end before the first requested write and
+    /// check the rest of history reaches back to it. Runs after the
+    /// writer lock is released.
+    pub(crate) fn seek(mut self) -> Result<Self> {
+        if self.next > self.until {
+            return Ok(self);
+        }
+        let mut starts = Vec::with_capacity(self.logs.len());
+        for file in self.logs.iter_mut() {
+            starts.push(WriteAheadLog::first_sequence(file.as_mut())?);
+        }
+        // A log without a marker continues the previous one; only the
+        // database's first log has none and starts at 1
+        let skip = starts
+            .iter()
+            .skip(1)
+            .take_while(|start| start.is_some_and(|start| start <= self.next))
+            .count();
+        self.logs.drain(..skip);
+        let oldest = match starts.get(skip) {
+            Some(Some(start)) => *start,
+            Some(None) if skip == 0 => 1,
+            _ => self.until + 1,
+        };
+        if self.next < oldest {
+            return Err(OblivionError::ChangesUnavailable(self.next));
+        }
+        self.log_sequence = oldest - 1;
+        Ok(self)
+    }
+
+    /// Returns the sequence number of the next write the stream yields.
+    pub fn next_sequence(&self) -> u64 {
+        self.next
+    }
+
+    /// Like [`Iterator::next`], but gives up after `timeout` without a
+    /// new write. Returns `None` on timeout and once the stream ended.
+    pub fn next_timeout(&mut self, timeout: Duration) -> Option<Result<ChangeRecord>> {
+        self.advance(Some(timeout))
+    }
+
+    fn advance(&mut self, timeout: Option<Duration>) -> Option<Result<ChangeRecord>> {
+        if self.finished {
+            return None;
+        }
+        let result = self.read(timeout);
+        match &result {
+            Some(Ok(record)) => self.next = record.sequence + 1,
+            Some(Err(_)) => self.finished = true,
+            None => {}
+        }
+        result
+    }
+
+    fn read(&mut self, timeout: Option<Duration>) -> Option<Result<ChangeRecord>> {
+        while self.next <= self.until {
+            if let Some(record) = self.pending.pop_front() {
+                if record.sequence >= self.next {
+                    return Some(Ok(record));
+                }
+                continue;
+            }
+            let Some(mut file) = self.logs.pop_front() else {
+                return Some(Err(OblivionError::Corruption(format!(
+                    "WAL history ends at sequence {}, before {}",
+                    self.log_sequence, self.until
+                ))));
+            };
+            let writes = match WriteAheadLog::read_writes(file.as_mut(), self.log_sequence) {
+                Ok((writes, last)) => {
+                    self.log_sequence = last;
+                    writes
+                }
+                Err(e) => return Some(Err(e)),
+            };
+            for write in writes {
+                let LoggedWrite {
+                    sequence,
+                    cf,
+                    key,
+                    value,
+                } = write;
+                let op = match (cf, value) {
+                    (None, Some(value)) => ChangeOp::Put {
+                        key,
+                        value,
+                        ttl_ms: None,
+                    },
+                    (None, None) => ChangeOp::Delete { key },
+                    (Some(id), value) => {
+                        let Some(cf) = self.families.get(&id).cloned() else {
+                            return Some(Err(OblivionError::Corruption(format!(
+                                "WAL write {} names unknown column family id {}",
+                                sequence, id
+                            ))));
+                        };
+                        match value {
+                            Some(value) => ChangeOp::PutCf { cf, key, value },
+                            None => ChangeOp::DeleteCf { cf, key },
+                        }
+                    }
+                };
+                self.pending.push_back(ChangeRecord { sequence, op });
+            }
+        }
+        // History is done; release its files
+        self.logs.clear();
+        self.pending.clear();
+        loop {
+            let record = match timeout {
+                None => self.live.recv().ok(),
+                Some(timeout) => match self.live.recv_timeout(timeout) {
+                    Ok(record) => Some(record),
+                    Err(RecvTimeoutError::Timeout) => return None,
+                    Err(RecvTimeoutError::Disconnected) => None,
+                },
+            };
+            match record {
+                Some(record) if record.sequence < self.next => continue,
+                Some(record) => return Some(Ok(record)),
+                None if self.lagged.load(Ordering::Acquire) => {
+                    return Some(Err(OblivionError::ChangesUnavailable(self.next)))
+                }
+                None => {
+                    self.finished = true;
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+impl Iterator for ChangeStream {
+    type Item = Result<ChangeRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.advance(None)
+    }
+}
+
+/// Subscribe to the writes from `from_sequence` on, where `until` is
+/// the latest sequence number and `families` names the column families
+/// by id. Must be called under the writer lock.
+pub(crate) fn subscribe(
+    feed: &ChangeFeed,
+    config: &Config,
+    families: HashMap<u32, String>,
+    from_sequence: u64,
+    until: u64,
+) -> Result<ChangeStream> {
+    let next = from_sequence.max(1);
+    let logs = if next <= until {
+        open_logs(config)?
+    } else {
+        Vec::new()
+    };
+    let (live, lagged) = feed.subscribe();
+    Ok(ChangeStream {
+        next,
+        until,
+        logs: logs.into(),
+        log_sequence: 0,
+        pending: VecDeque::new(),
+        families,
+        live,
+        lagged,
+        finished: false,
+    })
+}
+
+/// Open every log holding writes, oldest first: archived segments,
+/// segments of MemTables not flushed yet, then the live log.
+///
+/// Open files survive a flush moving or deleting them; a segment that
+/// moves before it is opened is looked for again.
+fn open_logs(config: &Config) -> Result<Vec<Box<dyn RandomAccessFile>>> {
+    let env = &config.env;
+    'retry: loop {
+        // Segments move from `wal/` to the archive, so list `wal/` first
+        let mut segments = WriteAheadLog::segments_in(env, &config.wal_dir())?;
+        match WriteAheadLog::segments_in(env, &config.wal_archive_dir()) {
+            Ok(archived) => segments.extend(archived),
+            Err(OblivionError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        segments.sort_unstable();
+        segments.dedup_by_key(|(id, _)| *id);
+        let mut paths: Vec<PathBuf> = segments.into_iter().map(|(_, path)| path).collect();
+        paths.push(config.wal_path());
+        let mut files = Vec::with_capacity(paths.len());
+        for path in paths {
+            match env.open_read(&path, false) {
+                Ok(file) => files.push(file),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue 'retry,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        return Ok(files);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put(sequence: u64) -> ChangeRecord {
+        ChangeRecord {
+            sequence,
+            op: ChangeOp::Put {
+                key: b"k".to_vec(),
+                value: b"v".to_vec(),
+                ttl_ms: None,
+            },
+        }
+    }
+
+    #[test]
+    fn test_slow_subscriber_is_dropped() {
+        let feed = ChangeFeed::new();
+        assert!(!feed.is_active());
+        let (receiver, lagged) = feed.subscribe();
+        let mut stream = ChangeStream {
+            next: 1,
+            until: 0,
+            logs: VecDeque::new(),
+            log_sequence: 0,
+            pending: VecDeque::new(),
+            families: HashMap::new(),
+            live: receiver,
+            lagged,
+            finished: false,
+        };
+        for sequence in 1..=SUBSCRIBER_BUFFER as u64 + 1 {
+            feed.publish(&put(sequence));
+        }
+        assert!(!feed.is_active());
+        for sequence in 1..=SUBSCRIBER_BUFFER as u64 {
+            assert_eq!(stream.next().unwrap().unwrap().sequence, sequence);
+        }
+        let next = SUBSCRIBER_BUFFER as u64 + 1;
+        assert!(matches!(
+            stream.next(),
+            Some(Err(OblivionError::ChangesUnavailable(n))) if n == next
+        ));
+        assert!(stream.next().is_none());
+    }
+}
@@ -0,0 +1,210 @@
+//! OBLIVION - Typed Values
+//! Store and load Rust values instead of byte strings, with a pluggable
+//! serialization format.
+//!
+//! [`Oblivion::put_as`] and [`Oblivion::get_as`] encode values with
+//! [`Bincode`]; [`Oblivion::typed`] returns a [`Typed`] view of the
+//! engine with the same calls over another [`Codec`]:
+//!
+//! | Codec         | Format                                        | Feature   |
+//! |---------------|-----------------------------------------------|-----------|
+//! | [`Bincode`]   | Compact binary, not self-describing           |           |
+//! | [`Json`]      | JSON text, readable in the CLI and HTTP API   |           |
+//! | `MessagePack` | Compact, self-describing binary               | `msgpack` |
+//!
+//! Keys stay byte strings. A value that fails to encode or decode is
+//! reported as [`OblivionError::Serialization`]. Reading a value with
+//! another codec or type than it was written with usually fails the
+//! same way, though `Bincode`, which is not self-describing, may decode
+//! it into a wrong value instead.
+//!
+//! ## Example
+//! ```no_run
+//! use serde::{Deserialize, Serialize};
+//! use oblivion::config::Config;
+//! use oblivion::engine::codec::Json;
+//! use oblivion::engine::Oblivion;
+//!
+//! #[derive(Serialize, Deserialize, PartialEq, Debug)]
+//! struct User {
+//!     name: String,
+//!     admin: bool,
+//! }
+//!
+//! let engine = Oblivion::open(Config::new("./data")).unwrap();
+//! let user = User { name: "ada".into(), admin: true };
+//! engine.put_as(b"user:1".to_vec(), &user).unwrap();
+//! assert_eq!(engine.get_as::<User>(b"user:1").unwrap(), Some(user));
+//!
+//! let json = engine.typed::<Json>();
+//! json.put_as(b"user:2".to_vec(), &User { name: "grace".into(), admin: false })
+//!     .unwrap();
+//! assert_eq!(engine.get(b"user:2").unwrap(), br#"{"name":"grace","admin":false}"#);
+//! ```
+
+use std::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::error::{OblivionError, Result};
+use crate::types::{Key, Value};
+
+use super::options::ReadOptions;
+use super::Oblivion;
+
+/// A serialization format for typed values.
+pub trait Codec {
+    /// Encode `value` into the bytes stored for it.
+    fn encode<T: Serialize + ?Sized>(value: &T) -> Result<Value>;
+
+    /// Decode a stored value.
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T>;
+}
+
+/// [bincode](https://docs.rs/bincode/1) 1.x, the format the engine's
+/// own metadata uses.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Bincode;
+
+impl Codec for Bincode {
+    fn encode<T: Serialize + ?Sized>(value: &T) -> Result<Value> {
+        bincode::serialize(value).map_err(|e| OblivionError::Serialization(e.to_string()))
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+        bincode::deserialize(bytes).map_err(|e| OblivionError::Serialization(e.to_string()))
+    }
+}
+
+/// Compact JSON text.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Json;
+
+impl Codec for Json {
+    fn encode<T: Serialize + ?Sized>(value: &T) -> Result<Value> {
+        serde_json::to_vec(value).map_err(|e| OblivionError::Serialization(e.to_string()))
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+        serde_json::from_slice(bytes).map_err(|e| OblivionError::Serialization(e.to_string()))
+    }
+}
+
+/// MessagePack, with structs encoded as maps so fields can be added
+/// (`msgpack` feature).
+#[cfg(feature = "msgpack")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MessagePack;
+
+#[cfg(feature = "msgpack")]
+impl Codec for MessagePack {
+    fn encode<T: Serialize + ?Sized>(value: &T) -> Result<Value> {
+        rmp_serde::to_vec_named(value).map_err(|e| OblivionError::Serialization(e.to_string()))
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+        rmp_serde::from_slice(bytes).map_err(|e| OblivionError::Serialization(e.to_string()))
+    }
+}
+
+/// An engine whose values are encoded with the codec `C`; see
+/// [`Oblivion::typed`].
+pub struct Typed<'a, C: Codec = Bincode> {
+    engine: &'a Oblivion,
+    codec: PhantomData<C>,
+}
+
+impl<'a, C: Codec> Typed<'a, C> {
+    /// View `engine` through the codec `C`.
+    pub(crate) fn new(engine: &'a Oblivion) -> Self {
+        Self {
+            engine,
+            codec: PhantomData,
+        }
+    }
+
+    /// Encode `value` and store it under `key`.
+    pub fn put_as<T: Serialize + ?Sized>(&self, key: impl Into<Key>, value: &T) -> Result<()> {
+        self.engine.put(key, C::encode(value)?)
+    }
+
+    /// Encode `value` and store it under `key` for `ttl_ms` milliseconds.
+    pub fn put_as_with_ttl<T: Serialize + ?Sized>(
+        &self,
+        key: impl Into<Key>,
+        value: &T,
+        ttl_ms: u64,
+    ) -> Result<()> {
+        self.engine.put_with_ttl(key, C::encode(value)?, ttl_ms)
+    }
+
+    /// Load and decode the value of `key`, or `None` if it is missing
+    /// or expired.
+    pub fn get_as<T: DeserializeOwned>(&self, key: impl AsRef<[u8]>) -> Result<Option<T>> {
+        match self.engine.get_opt(key, &ReadOptions::default())? {
+            Some(bytes) => C::decode(&bytes).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use serde::Deserialize;
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct User {
+        name: String,
+        roles: Vec<String>,
+        age: Option<u32>,
+    }
+
+    fn user() -> User {
+        User {
+            name: "ada".to_string(),
+            roles: vec!["admin".to_string()],
+            age: Some(36),
+        }
+    }
+
+    fn round_trip<C: Codec>() {
+        let bytes = C::encode(&user()).unwrap();
+        assert_eq!(C::decode::<User>(&bytes).unwrap(), user());
+        assert!(matches!(
+            C::decode::<User>(b"\x01"),
+            Err(OblivionError::Serialization(_))
+        ));
+    }
+
+    #[test]
+    fn test_codecs_round_trip() {
+        round_trip::<Bincode>();
+        round_trip::<Json>();
+        #[cfg(feature = "msgpack")]
+        round_trip::<MessagePack>();
+        assert_eq!(
+            Json::encode(&user()).unwrap(),
+            br#"{"name":"ada","roles":["admin"],"age":36}"#
+        );
+    }
+
+    #[test]
+    fn test_typed_puts_and_gets() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = Oblivion::open(Config::new(dir.path())).unwrap();
+        engine.put_as(b"user:1".to_vec(), &user()).unwrap();
+        assert_eq!(engine.get_as::<User>(b"user:1").unwrap(), Some(user()));
+        assert_eq!(engine.get_as::<User>(b"missing").unwrap(), None);
+
+        let json = engine.typed::<Json>();
+        json.put_as_with_ttl(b"count".to_vec(), &42u64, 60_000)
+            .unwrap();
+        assert_eq!(engine.get(b"count"), Some(b"42".to_vec()));
+        assert_eq!(json.get_as::<u64>(b"count").unwrap(), Some(42));
+        assert!(engine.ttl(b"count").is_some());
+        assert!(json.get_as::<User>(b"count").is_err());
+    }
+}
@@ -0,0 +1,318 @@
+//! OBLIVION - Column Families
+//! Named keyspaces with their own MemTables, SSTables and flush and
+//! compaction settings, sharing the engine's WAL.
+//!
+//! A database opened with [`Oblivion::open_with_cfs`](super::Oblivion::open_with_cfs)
+//! keeps the unnamed default keyspace (the one `put`/`get` use) and any
+//! number of named families, each an LSM tree of its own:
+//!
+//! ```text
+//! data_dir/
+//! ├── COLUMN_FAMILIES    "<id> <name>" per line
+//! └── cf/<name>/
+//!     ├── sst/           the family's SSTables
+//!     └── MANIFEST       its live SSTable set
+//! ```
+//!
+//! Writes to a family go to the shared WAL, tagged with the family's id,
+//! so a crash never loses them and one sync covers every keyspace. When
+//! any MemTable reaches its family's `memtable_max_size`, every non-empty
+//! MemTable is frozen together and the WAL rotated once; the segment is
+//! deleted after all of them have been flushed, named families first.
+//!
+//! Column family writes take their sequence numbers from the same
+//! counter as the default keyspace's, and reach change subscriptions
+//! and followers like any other write. TTLs, snapshots, prefix watches,
+//! `disk_usage` and the tree metrics cover the default keyspace only. A
+//! family cannot be dropped once created, and every family must be
+//! listed when the database is opened.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use arc_swap::{ArcSwap, ArcSwapOption};
+use parking_lot::RwLock;
+
+use crate::config::Config;
+use crate::engine::filter::{FilterSizing, FilterType};
+use crate::error::{OblivionError, Result};
+
+use super::cache::TableCache;
+use super::changes::ChangeFeed;
+use super::env::Env;
+use super::io::SyncMethod;
+use super::memtable::MemTable;
+use super::metrics::EngineMetrics;
+use super::tree::Tree;
+use super::ttl::TtlIndex;
+use super::version::{ReadState, Version};
+use super::Oblivion;
+
+/// Name of the column family registry inside `data_dir`.
+pub const REGISTRY_FILE_NAME: &str = "COLUMN_FAMILIES";
+
+/// Directory, inside `data_dir`, holding one directory per family.
+pub const FAMILIES_DIR: &str = "cf";
+
+/// Name reserved for the default keyspace.
+pub const DEFAULT_COLUMN_FAMILY: &str = "default";
+
+/// Flush and compaction settings of one column family. Options left
+/// unset are taken from the engine's [`Config`].
+///
+/// ## Example
+/// ```no_run
+/// use oblivion::config::Config;
+/// use oblivion::engine::column_family::ColumnFamilyOptions;
+/// use oblivion::engine::Oblivion;
+///
+/// let events = ColumnFamilyOptions::new()
+///     .memtable_max_size(16 * 1024 * 1024)
+///     .compaction_threshold(8);
+/// let engine = Oblivion::open_with_cfs(
+///     Config::new("./data"),
+///     [("events", events), ("users", ColumnFamilyOptions::new())],
+/// )
+/// .unwrap();
+/// engine.put_cf("users", b"alice".to_vec(), b"admin".to_vec()).unwrap();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ColumnFamilyOptions {
+    /// MemTable size in bytes that triggers a flush.
+    pub memtable_max_size: Option<usize>,
+    /// Number of SSTables in a tier that triggers a compaction.
+    pub compaction_threshold: Option<usize>,
+    /// Size multiplier between consecutive compaction tiers.
+    pub compaction_size_ratio: Option<usize>,
+    /// Filter type per compaction tier.
+    pub filter_per_tier: Option<Vec<FilterType>>,
+    /// Filter sizing per compaction tier.
+    pub filter_sizing_per_tier: Option<Vec<FilterSizing>>,
+}
+
+impl ColumnFamilyOptions {
+    /// Create options inheriting every setting from the engine.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the MemTable size that triggers a flush.
+    pub fn memtable_max_size(mut self, size: usize) -> Self {
+        self.memtable_max_size = Some(size);
+        self
+    }
+
+    /// Set the number of SSTables in a tier that triggers a compaction.
+    pub fn compaction_threshold(mut self, threshold: usize) -> Self {
+        self.compaction_threshold = Some(threshold);
+        self
+    }
+
+    /// Set the size multiplier between compaction tiers.
+    pub fn compaction_size_ratio(mut self, ratio: usize) -> Self {
+        self.compaction_size_ratio = Some(ratio);
+        self
+    }
+
+    /// Set the filter type per compaction tier.
+    pub fn filter_per_tier(mut self, filters: Vec<FilterType>) -> Self {
+        self.filter_per_tier = Some(filters);
+        self
+    }
+
+    /// Set the filter sizing per compaction tier.
+    pub fn filter_sizing_per_tier(mut self, sizing: Vec<FilterSizing>) -> Self {
+        self.filter_sizing_per_tier = Some(sizing);
+        self
+    }
+
+    /// The configuration the family `name` of the engine configured by
+    /// `config` runs with, rooted at its own directory. Families keep no
+    /// WAL or row cache of their own.
+    pub(crate) fn apply(&self, config: &Config, name: &str) -> Result<Config> {
+        let mut family = config.clone();
+        family.data_dir = family_dir(config, name);
+        family.row_cache_capacity = 0;
+        family.wal_retention_size = 0;
+        family.stats_dump_period_secs = 0;
+        if let Some(size) = self.memtable_max_size {
+            family.memtable_max_size = size;
+        }
+        if let Some(threshold) = self.compaction_threshold {
+            family.compaction_threshold = threshold;
+        }
+        if let Some(ratio) = self.compaction_size_ratio {
+            family.compaction_size_ratio = ratio;
+        }
+        if let Some(filters) = &self.filter_per_tier {
+            family.filter_per_tier = filters.clone();
+        }
+        if let Some(sizing) = &self.filter_sizing_per_tier {
+            family.filter_sizing_per_tier = sizing.clone();
+        }
+        family
+            .validate()
+            .map_err(|e| OblivionError::Config(format!("column family '{}': {}", name, e)))?;
+        Ok(family)
+    }
+}
+
+/// An open column family.
+pub(crate) struct ColumnFamily {
+    /// Id tagging the family's WAL records.
+    pub(crate) id: u32,
+    /// MemTables and SSTables shared with readers.
+    pub(crate) state: Arc<ReadState>,
+    /// Flush and compaction state.
+    pub(crate) tree: Arc<Tree>,
+}
+
+impl ColumnFamily {
+    /// Open the family `id` configured by `config` (see
+    /// [`ColumnFamilyOptions::apply`]), with the writes recovered from
+    /// the WAL in `memtable`.
+    pub(crate) fn open(
+        config: Config,
+        id: u32,
+        memtable: MemTable,
+        table_cache: &Arc<TableCache>,
+    ) -> Result<Self> {
+        config.env.create_dir_all(&config.sst_dir())?;
+        let (sstables, manifest) = Oblivion::load_sstables(&config, table_cache)?;
+        if config.preload_index_and_filter {
+            for table in &sstables {
+                table.preload()?;
+            }
+        }
+        let state = Arc::new(ReadState {
+            memtable: RwLock::new(memtable),
+            version: ArcSwap::from_pointee(Version::new(Vec::new(), sstables)),
+            ttl_index: RwLock::new(TtlIndex::new()),
+            row_cache: ArcSwapOption::empty(),
+            metrics: Arc::new(EngineMetrics::new()),
+            snapshot_pins: Arc::new(()),
+            comparator: config.comparator.clone(),
+        });
+        let tree = Arc::new(Tree::new(
+            Arc::clone(&state),
+            config,
+            manifest,
+            Arc::clone(table_cache),
+            Arc::new(ChangeFeed::new()),
+        ));
+        Ok(Self { id, state, tree })
+    }
+}
+
+/// Directory of the family `name`.
+pub(crate) fn family_dir(config: &Config, name: &str) -> PathBuf {
+    config.data_dir.join(FAMILIES_DIR).join(name)
+}
+
+/// Check that `name` can be used as a family name: non-empty, at most
+/// 64 characters of `[A-Za-z0-9_.-]`, not starting with a dot, and not
+/// the default keyspace's name.
+pub(crate) fn validate_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= 64
+        && !name.starts_with('.')
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"_.-".contains(&b));
+    if !valid || name == DEFAULT_COLUMN_FAMILY {
+        return Err(OblivionError::Config(format!(
+            "invalid column family name '{}'",
+            name
+        )));
+    }
+    Ok(())
+}
+
+/// Read the registry at `path` on `env`: the families created so far,
+/// by id. A missing registry lists none.
+pub(crate) fn load_registry(env: &Env, path: &Path) -> Result<Vec<(u32, String)>> {
+    let contents = match env.read(path) {
+        Ok(contents) => String::from_utf8(contents)
+            .map_err(|_| OblivionError::Corruption(format!("registry {:?} is not UTF-8", path)))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            line.split_once(' ')
+                .and_then(|(id, name)| Some((id.parse().ok()?, name.to_string())))
+                .ok_or_else(|| {
+                    OblivionError::Corruption(format!("invalid line {:?} in {:?}", line, path))
+                })
+        })
+        .collect()
+}
+
+/// Atomically replace the registry at `path` (write temp file, fsync,
+/// rename), like the manifest.
+pub(crate) fn save_registry(env: &Env, path: &Path, families: &[(u32, String)]) -> Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    {
+        let mut file = env.create(&tmp_path, false)?;
+        for (id, name) in families {
+            writeln!(file, "{} {}", id, name)?;
+        }
+        file.sync(SyncMethod::Fsync)?;
+    }
+    env.rename(&tmp_path, path)?;
+    if let Some(dir) = path.parent() {
+        env.sync_dir(dir)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_registry_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(REGISTRY_FILE_NAME);
+        let env = Env::default();
+        assert!(load_registry(&env, &path).unwrap().is_empty());
+
+        let families = vec![(1, "users".to_string()), (2, "events.v2".to_string())];
+        save_registry(&env, &path, &families).unwrap();
+        assert_eq!(load_registry(&env, &path).unwrap(), families);
+
+        fs::write(&path, "x users\n").unwrap();
+        assert!(matches!(
+            load_registry(&env, &path),
+            Err(OblivionError::Corruption(_))
+        ));
+    }
+
+    #[test]
+    fn test_names_and_options() {
+        for name in ["users", "events.v2", "a-b_c"] {
+            assert!(validate_name(name).is_ok(), "{}", name);
+        }
+        for name in ["", "default", "a/b", "..", "has space"] {
+            assert!(validate_name(name).is_err(), "{}", name);
+        }
+
+        let config = Config::new("/tmp/db");
+        let family = ColumnFamilyOptions::new()
+            .memtable_max_size(1024)
+            .apply(&config, "users")
+            .unwrap();
+        assert_eq!(family.data_dir, PathBuf::from("/tmp/db/cf/users"));
+        assert_eq!(family.memtable_max_size, 1024);
+        assert_eq!(family.compaction_threshold, config.compaction_threshold);
+        assert!(ColumnFamilyOptions::new()
+            .compaction_threshold(0)
+            .apply(&config, "users")
+            .is_err());
+    }
+}
@@ -65,6 +65,8 @@ pub struct SizeTieredCompaction {
     threshold: usize,
     /// Size multiplier between tiers (default: 10x).
     size_ratio: usize,
+    /// Upper size bound of tier 0 (default: 4MB).
+    base_size: usize,
 }
 
 impl SizeTieredCompaction {
@@ -77,17 +79,25 @@ impl SizeTieredCompaction {
         Self {
             threshold,
             size_ratio,
+            base_size: 4 * 1024 * 1024, // 4MB base
         }
     }
 
+    /// Set the upper size bound of tier 0.
+    /// The engine uses the MemTable flush threshold, so fresh flushes land in T0.
+    pub fn with_base_size(mut self, base_size: usize) -> Self {
+        self.base_size = base_size.max(1);
+        self
+    }
+
     /// Get the tier level for a given SSTable size.
-    fn tier_for_size(&self, size: usize) -> usize {
+    pub fn tier_for_size(&self, size: usize) -> usize {
         if size == 0 {
             return 0;
         }
-        let base = self.size_ratio;
+        let base = self.size_ratio.max(2);
         let mut tier = 0;
-        let mut upper_bound = 4 * 1024 * 1024; // 4MB base
+        let mut upper_bound = self.base_size;
 
         while size > upper_bound {
             tier += 1;
@@ -193,6 +203,10 @@ mod tests {
         assert_eq!(strategy.tier_for_size(10 * 1024 * 1024), 1); // 10MB → T1
         assert_eq!(strategy.tier_for_size(40 * 1024 * 1024), 1); // 40MB → T1
         assert_eq!(strategy.tier_for_size(100 * 1024 * 1024), 2); // 100MB → T2
+
+        let small = SizeTieredCompaction::new(4, 10).with_base_size(1024);
+        assert_eq!(small.tier_for_size(1000), 0);
+        assert_eq!(small.tier_for_size(5000), 1);
     }
 
     #[test]
@@ -0,0 +1,198 @@
+//! OBLIVION - Key Comparators
+//! The order keys are kept in, so a database can sort them
+//! case-insensitively or by an application's own collation instead of
+//! byte by byte.
+//!
+//! The comparator is set with `Config::comparator` when a database is
+//! created. Its name is recorded in the manifest, and opening the
+//! database with a comparator of another name fails, since its SSTables
+//! are sorted by the old one. Flushes and compactions write SSTables in
+//! its order, and SSTable lookups, range scans and engine scans follow
+//! it. MemTables stay in byte order for point lookups; under a custom
+//! comparator, flushes and scans sort what they read from them.
+//!
+//! ## Contract
+//! [`KeyComparator::compare`] must be a total order under which only
+//! identical keys are equal, as lookups, filters and caches still match
+//! keys byte for byte. [`CaseInsensitive`] therefore keeps `User` and
+//! `user` apart, ordering them by their bytes.
+//!
+//! ## Byte-Ordered Keys
+//! Keys starting with [`RESERVED_PREFIX`], which hold logical databases
+//! other than 0 and data types (see the
+//! [`database` module](super::database)), rely on byte order. Any
+//! comparator sorts them after every other key, byte by byte.
+//!
+//! Only byte order keeps the keys sharing a prefix together. Under a
+//! custom comparator the bounds of `ReadOptions::prefix` do not select a
+//! prefix, and glob matches (`keys`, `SCAN ... MATCH`) read the whole
+//! range rather than the keys starting with the pattern's literal
+//! prefix. Replication followers must use their primary's comparator.
+//!
+//! ## Example
+//! ```no_run
+//! use oblivion::config::Config;
+//! use oblivion::engine::comparator::{CaseInsensitive, Comparator};
+//! use oblivion::engine::Oblivion;
+//!
+//! let config = Config::new("./data").with_comparator(Comparator::new(CaseInsensitive));
+//! let engine = Oblivion::open(config).unwrap();
+//! for key in ["b", "a", "C", "A"] {
+//!     engine.put(key, "v").unwrap();
+//! }
+//! let keys: Vec<_> = engine.scan().into_iter().map(|(key, _)| key).collect();
+//! assert_eq!(keys, [b"A", b"a", b"b", b"C"]);
+//! ```
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::types::Key;
+
+/// First bytes of the keys every comparator orders byte by byte, after
+/// all other keys.
+pub const RESERVED_PREFIX: &[u8] = b"\xff\xff";
+
+/// Name of the default comparator, which orders keys byte by byte.
+pub const BYTEWISE: &str = "oblivion.bytewise";
+
+/// An order of keys other than byte order.
+pub trait KeyComparator: Send + Sync {
+    /// Returns the stable name of this order.
+    /// Recorded in the manifest, so it must never change once files exist.
+    fn name(&self) -> &str;
+
+    /// Order `a` before, after or (only if they are identical) as `b`.
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering;
+}
+
+/// Orders keys ignoring ASCII case; keys differing only in case are
+/// ordered by their bytes, so `A` comes right before `a`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CaseInsensitive;
+
+impl KeyComparator for CaseInsensitive {
+    fn name(&self) -> &str {
+        "oblivion.case_insensitive"
+    }
+
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        let fold = |key: &[u8]| key.iter().map(u8::to_ascii_lowercase).collect::<Vec<_>>();
+        fold(a).cmp(&fold(b)).then_with(|| a.cmp(b))
+    }
+}
+
+/// The order of an engine's keys: byte order (the default), or a
+/// [`KeyComparator`].
+#[derive(Clone, Default)]
+pub struct Comparator {
+    custom: Option<Arc<dyn KeyComparator>>,
+}
+
+impl Comparator {
+    /// Order keys by `comparator`.
+    pub fn new(comparator: impl KeyComparator + 'static) -> Self {
+        Self {
+            custom: Some(Arc::new(comparator)),
+        }
+    }
+
+    /// Order keys byte by byte.
+    pub fn bytewise() -> Self {
+        Self::default()
+    }
+
+    /// Returns the name recorded in the manifest.
+    pub fn name(&self) -> &str {
+        self.custom
+            .as_ref()
+            .map_or(BYTEWISE, |custom| custom.name())
+    }
+
+    /// Returns true for byte order.
+    pub fn is_bytewise(&self) -> bool {
+        self.custom.is_none()
+    }
+
+    /// Order two keys, keeping [`RESERVED_PREFIX`] keys last.
+    pub fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        let Some(custom) = &self.custom else {
+            return a.cmp(b);
+        };
+        match (
+            a.starts_with(RESERVED_PREFIX),
+            b.starts_with(RESERVED_PREFIX),
+        ) {
+            (false, false) => custom.compare(a, b),
+            (true, true) => a.cmp(b),
+            (a_reserved, b_reserved) => a_reserved.cmp(&b_reserved),
+        }
+    }
+
+    /// Returns true if `key` lies within `[lower, upper)`.
+    pub fn in_bounds(&self, key: &[u8], lower: Option<&[u8]>, upper: Option<&[u8]>) -> bool {
+        lower.is_none_or(|lower| self.compare(key, lower).is_ge())
+            && upper.is_none_or(|upper| self.compare(key, upper).is_lt())
+    }
+
+    /// Returns true if the keys from `lower` on are in byte order, so
+    /// byte-ordered maps can be read as a range and a key's successor is
+    /// the key followed by a 0 byte.
+    pub fn is_bytewise_from(&self, lower: Option<&[u8]>) -> bool {
+        self.is_bytewise() || lower.is_some_and(|lower| lower.starts_with(RESERVED_PREFIX))
+    }
+
+    /// Sort `rows` by key.
+    pub fn sort<T>(&self, rows: &mut [(Key, T)]) {
+        rows.sort_by(|a, b| self.compare(&a.0, &b.0));
+    }
+}
+
+impl fmt::Debug for Comparator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Comparator").field(&self.name()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_case_insensitive_order() {
+        let comparator = Comparator::new(CaseInsensitive);
+        assert_eq!(comparator.name(), "oblivion.case_insensitive");
+        assert!(!comparator.is_bytewise());
+        let mut rows: Vec<(Key, ())> = ["b", "a", "C", "A", "ab"]
+            .iter()
+            .map(|key| (key.as_bytes().to_vec(), ()))
+            .collect();
+        comparator.sort(&mut rows);
+        let keys: Vec<&[u8]> = rows.iter().map(|(key, _)| key.as_slice()).collect();
+        assert_eq!(keys, [&b"A"[..], b"a", b"ab", b"b", b"C"]);
+        assert!(comparator.compare(b"a", b"a").is_eq());
+        assert!(comparator.in_bounds(b"B", Some(b"a"), Some(b"c")));
+        assert!(comparator.in_bounds(b"C", Some(b"a"), Some(b"c")));
+        assert!(!comparator.in_bounds(b"c", Some(b"a"), Some(b"C")));
+    }
+
+    #[test]
+    fn test_reserved_keys_sort_last_in_byte_order() {
+        let comparator = Comparator::new(CaseInsensitive);
+        assert!(comparator.compare(b"\xff\xffdb", b"zzz").is_gt());
+        assert!(comparator.compare(b"\xff\xffdbA", b"\xff\xffdba").is_lt());
+        assert!(comparator.compare(b"\xff\xffta", b"\xff\xffdbZ").is_gt());
+        assert!(comparator.is_bytewise_from(Some(b"\xff\xffdb")));
+        assert!(!comparator.is_bytewise_from(Some(b"user")));
+
+        let bytewise = Comparator::default();
+        assert_eq!(bytewise.name(), BYTEWISE);
+        assert!(bytewise.compare(b"B", b"a").is_lt());
+        assert!(bytewise.is_bytewise_from(None));
+        assert_eq!(
+            format!("{:?}", bytewise),
+            "Comparator(\"oblivion.bytewise\")"
+        );
+    }
+}
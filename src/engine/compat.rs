@@ -0,0 +1,758 @@
+//! OBLIVION - LevelDB/RocksDB Compatibility
+//! Reads LevelDB and RocksDB block-based table files, so an existing
+//! dataset can be ingested table by table instead of through a logical
+//! dump and load. Enabled by the `sst-compat` feature.
+//!
+//! ## Supported Tables
+//! - LevelDB `.ldb`/`.sst` files and RocksDB `.sst` files with
+//!   `format_version` 0 to 7, as written by flushes, compactions or
+//!   `SstFileWriter`
+//! - Uncompressed and Snappy-compressed blocks
+//! - Binary search and hash indexes, with or without delta-encoded values
+//!   and first keys; data blocks with a hash index
+//!
+//! Tables must use the default bytewise comparator. Partitioned indexes,
+//! range deletions, merge operands, wide columns, blob references and
+//! other compression types are reported as [`OblivionError::Unsupported`].
+//!
+//! ## Records
+//! Files hold internal keys (`user_key` followed by a sequence number and
+//! record type), several versions of a key newest first. Only the newest
+//! version of each key is kept; deletions become tombstones, so ingesting
+//! the files of a LevelDB/RocksDB database oldest first (its deepest level
+//! first, level 0 by file number) reproduces its contents.
+//!
+//! ## Checksums
+//! Block checksums are verified when they are CRC32C: always in LevelDB,
+//! and in RocksDB tables written with `checksum = kCRC32c`. xxHash and
+//! XXH3 checksums are not checked.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crate::error::{OblivionError, Result};
+use crate::types::{Key, Value};
+
+use super::sstable::{SSTable, SSTableBuilder};
+use super::Oblivion;
+
+/// Footer magic of LevelDB tables and RocksDB `format_version` 0.
+const LEGACY_MAGIC: u64 = 0xdb47_7524_8b80_fb57;
+
+/// Footer magic of RocksDB block-based tables, `format_version` 1+.
+const ROCKSDB_MAGIC: u64 = 0x88e2_41b7_85f4_cff7;
+
+/// `[metaindex handle][index handle][padding to 40 bytes][magic]`
+const LEGACY_FOOTER_LEN: usize = 48;
+
+/// `[checksum type][handles or fields, 40 bytes][format_version][magic]`
+const FOOTER_LEN: usize = 53;
+
+/// Marks the `format_version` 6+ footer layout.
+const EXTENDED_MAGIC: [u8; 4] = [0x3e, 0x00, 0x7a, 0x00];
+
+/// Compression type byte and checksum after every block.
+const BLOCK_TRAILER_LEN: usize = 5;
+
+/// Checksum type byte of CRC32C in the RocksDB footer.
+const CHECKSUM_CRC32C: u8 = 1;
+
+/// Only the bytewise comparator orders keys the way OBLIVION does.
+const BYTEWISE_COMPARATOR: &[u8] = b"leveldb.BytewiseComparator";
+
+/// Layout of a table file, from its footer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForeignFormat {
+    /// LevelDB, or RocksDB with `format_version` 0.
+    LevelDb,
+    /// RocksDB block-based table.
+    RocksDb {
+        /// `BlockBasedTableOptions::format_version` the file was written with.
+        format_version: u32,
+    },
+}
+
+/// Location of a block in the file.
+#[derive(Debug, Clone, Copy)]
+struct BlockHandle {
+    offset: u64,
+    size: u64,
+}
+
+impl BlockHandle {
+    fn decode(reader: &mut Reader<'_>) -> Result<Self> {
+        Ok(Self {
+            offset: reader.varint()?,
+            size: reader.varint()?,
+        })
+    }
+}
+
+/// An open LevelDB or RocksDB table file.
+///
+/// ## Example
+/// ```no_run
+/// use oblivion::engine::compat::ForeignTable;
+///
+/// let table = ForeignTable::open("./leveldb/000042.ldb").unwrap();
+/// for entry in table.entries() {
+///     let (key, value) = entry.unwrap();
+///     println!("{:?} => {:?}", key, value);
+/// }
+/// ```
+pub struct ForeignTable {
+    path: PathBuf,
+    file: File,
+    file_len: u64,
+    format: ForeignFormat,
+    /// Base context of CRC32C block checksums (0 before `format_version`
+    /// 6), or `None` if checksums are not verified.
+    crc32c_context: Option<u32>,
+    /// Data blocks in key order.
+    data_blocks: Vec<BlockHandle>,
+}
+
+impl ForeignTable {
+    /// Open the table at `path`, reading its footer, properties and index.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = File::open(&path)?;
+        let file_len = file.metadata()?.len();
+        let mut table = Self {
+            path,
+            file,
+            file_len,
+            format: ForeignFormat::LevelDb,
+            crc32c_context: None,
+            data_blocks: Vec::new(),
+        };
+
+        if file_len < LEGACY_FOOTER_LEN as u64 {
+            return Err(table.corruption("file too short for a table footer"));
+        }
+        let tail_len = FOOTER_LEN.min(file_len as usize);
+        let tail = table.read_at(file_len - tail_len as u64, tail_len)?;
+        let magic = u64::from_le_bytes(tail[tail_len - 8..].try_into().unwrap());
+        let (metaindex, index) = match magic {
+            LEGACY_MAGIC => {
+                table.crc32c_context = Some(0);
+                let mut footer = Reader::new(&tail[tail_len - LEGACY_FOOTER_LEN..]);
+                let metaindex = BlockHandle::decode(&mut footer)?;
+                (metaindex, Some(BlockHandle::decode(&mut footer)?))
+            }
+            ROCKSDB_MAGIC if tail_len == FOOTER_LEN => {
+                let format_version = u32::from_le_bytes(tail[41..45].try_into().unwrap());
+                table.format = ForeignFormat::RocksDb { format_version };
+                let (context, handles) = if format_version >= 6 {
+                    if tail[1..5] != EXTENDED_MAGIC {
+                        return Err(table.corruption("bad extended footer magic"));
+                    }
+                    let context = u32::from_le_bytes(tail[9..13].try_into().unwrap());
+                    let size = u32::from_le_bytes(tail[13..17].try_into().unwrap()) as u64;
+                    let end = file_len - (FOOTER_LEN + BLOCK_TRAILER_LEN) as u64;
+                    let offset = end
+                        .checked_sub(size)
+                        .ok_or_else(|| table.corruption("bad metaindex size"))?;
+                    (context, (BlockHandle { offset, size }, None))
+                } else {
+                    let mut footer = Reader::new(&tail[1..41]);
+                    let metaindex = BlockHandle::decode(&mut footer)?;
+                    (0, (metaindex, Some(BlockHandle::decode(&mut footer)?)))
+                };
+                table.crc32c_context = (tail[0] == CHECKSUM_CRC32C).then_some(context);
+                handles
+            }
+            _ => return Err(table.corruption("not a LevelDB or RocksDB block-based table")),
+        };
+
+        let metaindex: HashMap<Vec<u8>, BlockHandle> =
+            block_entries(&table.read_block(metaindex)?)?
+                .into_iter()
+                .map(|(name, value)| Ok((name, BlockHandle::decode(&mut Reader::new(&value))?)))
+                .collect::<Result<_>>()?;
+        if metaindex.contains_key(b"rocksdb.range_del".as_slice()) {
+            return Err(table.unsupported("range deletions"));
+        }
+        let properties: HashMap<Vec<u8>, Vec<u8>> =
+            match metaindex.get(b"rocksdb.properties".as_slice()) {
+                Some(&handle) => block_entries(&table.read_block(handle)?)?
+                    .into_iter()
+                    .collect(),
+                None => HashMap::new(),
+            };
+        if let Some(comparator) = properties.get(b"rocksdb.comparator".as_slice()) {
+            if comparator != BYTEWISE_COMPARATOR {
+                return Err(table.unsupported(&format!(
+                    "comparator {}",
+                    String::from_utf8_lossy(comparator)
+                )));
+            }
+        }
+
+        let index_type = match properties.get(b"rocksdb.block.based.table.index.type".as_slice()) {
+            Some(value) if value.len() == 4 => u32::from_le_bytes(value[..].try_into().unwrap()),
+            Some(_) => return Err(table.corruption("bad index type property")),
+            None => 0,
+        };
+        let first_key = match index_type {
+            0 | 1 => false,
+            3 => true,
+            2 => return Err(table.unsupported("partitioned indexes")),
+            other => return Err(table.unsupported(&format!("index type {}", other))),
+        };
+        let value_delta = properties
+            .get(b"rocksdb.index.value.is.delta.encoded".as_slice())
+            .map(|value| Reader::new(value).varint())
+            .transpose()?
+            .is_some_and(|flag| flag != 0);
+        let index = match index.or_else(|| metaindex.get(b"rocksdb.index".as_slice()).copied()) {
+            Some(index) => index,
+            None => return Err(table.corruption("no index block")),
+        };
+        table.data_blocks = index_handles(&table.read_block(index)?, value_delta, first_key)?;
+        Ok(table)
+    }
+
+    /// Returns the table's layout.
+    pub fn format(&self) -> ForeignFormat {
+        self.format
+    }
+
+    /// Returns the path the table was opened from.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Iterate the newest version of every key in sorted order, `None`
+    /// marking a deletion. Stops after the first error.
+    pub fn entries(&self) -> ForeignEntries<'_> {
+        ForeignEntries {
+            table: self,
+            next_block: 0,
+            block: Vec::new().into_iter(),
+            last_key: None,
+            failed: false,
+        }
+    }
+
+    /// Read, verify and decompress one block.
+    fn read_block(&self, handle: BlockHandle) -> Result<Vec<u8>> {
+        let end = handle
+            .offset
+            .checked_add(handle.size)
+            .and_then(|end| end.checked_add(BLOCK_TRAILER_LEN as u64));
+        if end.is_none_or(|end| end > self.file_len) {
+            return Err(self.corruption(&format!(
+                "block at offset {} runs past the end of the file",
+                handle.offset
+            )));
+        }
+        let size = handle.size as usize;
+        let mut block = self.read_at(handle.offset, size + BLOCK_TRAILER_LEN)?;
+        if let Some(context) = self.crc32c_context {
+            let stored = u32::from_le_bytes(block[size + 1..].try_into().unwrap());
+            // format_version 6+ ties each checksum to the block's offset
+            let modifier = match context {
+                0 => 0,
+                _ => context ^ (handle.offset as u32).wrapping_add((handle.offset >> 32) as u32),
+            };
+            if mask_crc(crc32c(&block[..size + 1])).wrapping_add(modifier) != stored {
+                return Err(self.corruption(&format!(
+                    "block at offset {} failed CRC check",
+                    handle.offset
+                )));
+            }
+        }
+        match block[size] {
+            0 => {
+                block.truncate(size);
+                Ok(block)
+            }
+            1 => snappy_decompress(&block[..size])
+                .map_err(|e| self.corruption(&format!("block at offset {}: {}", handle.offset, e))),
+            other => Err(self.unsupported(&format!("compression type {}", other))),
+        }
+    }
+
+    fn read_at(&self, offset: u64, len: usize) -> Result<Vec<u8>> {
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut buf = vec![0; len];
+        file.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
+    fn corruption(&self, msg: &str) -> OblivionError {
+        OblivionError::Corruption(format!("{:?}: {}", self.path, msg))
+    }
+
+    fn unsupported(&self, what: &str) -> OblivionError {
+        OblivionError::Unsupported(format!("{:?} uses {}", self.path, what))
+    }
+}
+
+/// Iterator over a [`ForeignTable`]'s keys, from [`ForeignTable::entries`].
+pub struct ForeignEntries<'a> {
+    table: &'a ForeignTable,
+    next_block: usize,
+    /// Remaining internal keys and values of the current data block.
+    block: std::vec::IntoIter<(Vec<u8>, Vec<u8>)>,
+    last_key: Option<Key>,
+    failed: bool,
+}
+
+impl ForeignEntries<'_> {
+    /// Decode one record, or `None` if it is an older version of the
+    /// previous key.
+    fn decode(&mut self, mut key: Vec<u8>, value: Vec<u8>) -> Result<Option<(Key, Option<Value>)>> {
+        let Some(user_len) = key.len().checked_sub(8) else {
+            return Err(self.table.corruption("internal key shorter than 8 bytes"));
+        };
+        let kind = key[user_len];
+        key.truncate(user_len);
+        match self.last_key.as_ref().map(|last| last.cmp(&key)) {
+            Some(std::cmp::Ordering::Equal) => return Ok(None),
+            Some(std::cmp::Ordering::Greater) => {
+                return Err(self.table.corruption("keys out of order"))
+            }
+            _ => {}
+        }
+        self.last_key = Some(key.clone());
+        match kind {
+            // Value
+            0x01 => Ok(Some((key, Some(value)))),
+            // Deletion, single deletion
+            0x00 | 0x07 => Ok(Some((key, None))),
+            other => Err(self
+                .table
+                .unsupported(&format!("record type {:#04x}", other))),
+        }
+    }
+}
+
+impl Iterator for ForeignEntries<'_> {
+    type Item = Result<(Key, Option<Value>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.failed {
+            let Some((key, value)) = self.block.next() else {
+                let handle = *self.table.data_blocks.get(self.next_block)?;
+                self.next_block += 1;
+                match self
+                    .table
+                    .read_block(handle)
+                    .and_then(|b| block_entries(&b))
+                {
+                    Ok(entries) => self.block = entries.into_iter(),
+                    Err(e) => {
+                        self.failed = true;
+                        return Some(Err(e));
+                    }
+                }
+                continue;
+            };
+            match self.decode(key, value) {
+                Ok(Some(entry)) => return Some(Ok(entry)),
+                Ok(None) => {}
+                Err(e) => {
+                    self.failed = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Convert the tables at `paths` into SSTables, oldest first, and ingest
+/// them into `engine`. Returns the number of keys read.
+pub(super) fn ingest_foreign_tables<P: AsRef<Path>>(engine: &Oblivion, paths: &[P]) -> Result<u64> {
+    let mut tables = Vec::with_capacity(paths.len());
+    let result = paths.iter().try_fold(0, |keys, path| {
+        let (count, table) = convert_table(engine, path.as_ref())?;
+        tables.extend(table);
+        Ok(keys + count)
+    });
+    match result {
+        Ok(keys) => {
+            engine.ingest(tables, Vec::new())?;
+            Ok(keys)
+        }
+        Err(e) => {
+            for (_, table) in tables {
+                engine.table_cache.evict(table.path());
+                if let Err(remove) = engine.table_cache.env().remove_file(table.path()) {
+                    log::warn!("Failed to remove {:?}: {}", table.path(), remove);
+                }
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Copy the newest version of every key of the table at `path` into a
+/// new SSTable, or none if the table is empty.
+fn convert_table(engine: &Oblivion, path: &Path) -> Result<(u64, Option<(u64, SSTable)>)> {
+    let foreign = ForeignTable::open(path)?;
+    let config = engine.tree.config();
+    let mut builder = None;
+    let mut keys = 0;
+    for entry in foreign.entries() {
+        let (key, value) = entry?;
+        engine.check_sizes(&key, value.as_deref())?;
+        let builder = match &mut builder {
+            Some(builder) => builder,
+            None => {
+                let id = engine.tree.new_file_number();
+                let builder = builder.insert((
+                    id,
+                    SSTableBuilder::create_in(
+                        &config.env,
+                        Oblivion::sstable_path(&config, id),
+                        config.filter_policy_for_tier(0),
+                        config.use_direct_io,
+                    )?,
+                ));
+                builder
+            }
+        };
+        builder.1.add(&key, value.as_deref())?;
+        keys += 1;
+    }
+    let Some((id, builder)) = builder else {
+        return Ok((0, None));
+    };
+    let table = builder.finish(&engine.table_cache)?;
+    log::info!(
+        "Converted {} keys of {:?} ({:?}) to SSTable {}",
+        keys,
+        path,
+        foreign.format(),
+        id
+    );
+    Ok((keys, Some((id, table))))
+}
+
+/// Bounds-checked cursor over a byte slice.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos == self.data.len()
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.data.len())
+            .ok_or_else(|| OblivionError::Corruption("truncated table block".to_string()))?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn le(&mut self, len: usize) -> Result<usize> {
+        let bytes = self.bytes(len)?;
+        Ok(bytes.iter().rev().fold(0, |n, &b| (n << 8) | b as usize))
+    }
+
+    fn varint(&mut self) -> Result<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(OblivionError::Corruption("varint too long".to_string()))
+    }
+
+    fn len_varint(&mut self) -> Result<usize> {
+        Ok(self.varint()? as usize)
+    }
+}
+
+/// Split a block into its entries and restart offsets.
+fn split_block(block: &[u8]) -> Result<(&[u8], Vec<usize>)> {
+    let corrupt = || OblivionError::Corruption("bad block footer".to_string());
+    let footer_start = block.len().checked_sub(4).ok_or_else(corrupt)?;
+    let packed = u32::from_le_bytes(block[footer_start..].try_into().unwrap());
+    let mut end = footer_start;
+    if packed >> 31 == 1 {
+        // RocksDB data block hash index: [buckets][num_buckets: u16]
+        let buckets_start = end.checked_sub(2).ok_or_else(corrupt)?;
+        let buckets = u16::from_le_bytes(block[buckets_start..end].try_into().unwrap());
+        end = buckets_start
+            .checked_sub(buckets as usize)
+            .ok_or_else(corrupt)?;
+    }
+    let restarts = (packed & 0x7fff_ffff) as usize;
+    let start = restarts
+        .checked_mul(4)
+        .and_then(|len| end.checked_sub(len))
+        .ok_or_else(corrupt)?;
+    let offsets = block[start..end]
+        .chunks_exact(4)
+        .map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize)
+        .collect();
+    Ok((&block[..start], offsets))
+}
+
+/// Decode the keys and values of a data, metaindex or properties block.
+fn block_entries(block: &[u8]) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+    let (entries, _) = split_block(block)?;
+    let mut reader = Reader::new(entries);
+    let mut key = Vec::new();
+    let mut out = Vec::new();
+    while !reader.is_empty() {
+        let shared = reader.len_varint()?;
+        let non_shared = reader.len_varint()?;
+        let value_len = reader.len_varint()?;
+        if shared > key.len() {
+            return Err(OblivionError::Corruption(
+                "bad shared key length".to_string(),
+            ));
+        }
+        key.truncate(shared);
+        key.extend_from_slice(reader.bytes(non_shared)?);
+        out.push((key.clone(), reader.bytes(value_len)?.to_vec()));
+    }
+    Ok(out)
+}
+
+/// Decode the data block handles of an index block.
+///
+/// With delta-encoded values, entries carry no value length and only
+/// those at restart points hold a full handle; the others hold the size
+/// change from the previous block, which directly precedes them.
+fn index_handles(block: &[u8], value_delta: bool, first_key: bool) -> Result<Vec<BlockHandle>> {
+    let (entries, restarts) = split_block(block)?;
+    let mut reader = Reader::new(entries);
+    let mut handles: Vec<BlockHandle> = Vec::new();
+    while !reader.is_empty() {
+        let at_restart = restarts.binary_search(&reader.pos).is_ok();
+        let _shared = reader.len_varint()?;
+        let non_shared = reader.len_varint()?;
+        if !value_delta {
+            let value_len = reader.len_varint()?;
+            reader.bytes(non_shared)?;
+            // A first key, if any, follows the handle
+            handles.push(BlockHandle::decode(&mut Reader::new(
+                reader.bytes(value_len)?,
+            ))?);
+            continue;
+        }
+        reader.bytes(non_shared)?;
+        let handle = match handles.last() {
+            Some(prev) if !at_restart => {
+                let zigzag = reader.varint()?;
+                let delta = (zigzag >> 1) as i64 ^ -((zigzag & 1) as i64);
+                BlockHandle {
+                    offset: prev.offset + prev.size + BLOCK_TRAILER_LEN as u64,
+                    size: (prev.size as i64).wrapping_add(delta) as u64,
+                }
+            }
+            _ => BlockHandle::decode(&mut reader)?,
+        };
+        if first_key {
+            let len = reader.len_varint()?;
+            reader.bytes(len)?;
+        }
+        handles.push(handle);
+    }
+    Ok(handles)
+}
+
+/// Decompress a raw Snappy block.
+fn snappy_decompress(input: &[u8]) -> Result<Vec<u8>> {
+    let corrupt = |msg: &str| OblivionError::Corruption(format!("snappy: {}", msg));
+    let mut reader = Reader::new(input);
+    let len = reader.len_varint()?;
+    // Snappy expands at most ~21x; don't trust a corrupt length further
+    let mut out = Vec::with_capacity(len.min(input.len().saturating_mul(24)));
+    while !reader.is_empty() {
+        let tag = reader.byte()?;
+        let (copy_len, offset) = match tag & 3 {
+            0 => {
+                let mut literal = (tag >> 2) as usize;
+                if literal >= 60 {
+                    literal = reader.le(literal - 59)?;
+                }
+                out.extend_from_slice(reader.bytes(literal + 1)?);
+                continue;
+            }
+            1 => (
+                ((tag >> 2) & 7) as usize + 4,
+                ((tag as usize >> 5) << 8) | reader.byte()? as usize,
+            ),
+            2 => ((tag >> 2) as usize + 1, reader.le(2)?),
+            _ => ((tag >> 2) as usize + 1, reader.le(4)?),
+        };
+        if offset == 0 || offset > out.len() {
+            return Err(corrupt("copy offset out of range"));
+        }
+        // Copies may overlap their own output
+        let start = out.len() - offset;
+        for i in 0..copy_len {
+            out.push(out[start + i]);
+        }
+    }
+    if out.len() != len {
+        return Err(corrupt("length mismatch"));
+    }
+    Ok(out)
+}
+
+/// CRC32C (Castagnoli) lookup table.
+const CRC32C_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0x82f6_3b78
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+fn crc32c(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &b| {
+        CRC32C_TABLE[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+/// LevelDB's checksum masking, so CRCs of data containing CRCs differ.
+fn mask_crc(crc: u32) -> u32 {
+    crc.rotate_right(15).wrapping_add(0xa282_ead8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/data/compat")
+            .join(name)
+    }
+
+    /// The fixtures hold `key_0000`..`key_0199`, every tenth deleted.
+    fn check_fixture(name: &str, overwritten: bool) -> ForeignFormat {
+        let table = ForeignTable::open(fixture(name)).unwrap();
+        let entries: Vec<_> = table.entries().collect::<Result<_>>().unwrap();
+        assert_eq!(entries.len(), 200, "{}", name);
+        for (i, (key, value)) in entries.iter().enumerate() {
+            assert_eq!(key, format!("key_{:04}", i).as_bytes());
+            let expected = match i {
+                _ if i % 10 == 0 => None,
+                1 if overwritten => Some(b"overwritten".to_vec()),
+                _ => Some(format!("value_{:04}_aaaaaaaaaaaaaaaaaaaa", i).into_bytes()),
+            };
+            assert_eq!(value, &expected, "{} key {}", name, i);
+        }
+        table.format()
+    }
+
+    #[test]
+    fn test_snappy_and_crc32c() {
+        assert_eq!(crc32c(b"123456789"), 0xe306_9283);
+        // Literal "abcd", then a 1-byte-offset copy of 8 bytes at offset 4
+        let compressed = [12, 3 << 2, b'a', b'b', b'c', b'd', 1 | (4 << 2), 4];
+        assert_eq!(snappy_decompress(&compressed).unwrap(), b"abcdabcdabcd");
+        assert!(snappy_decompress(&[4, 1 | (4 << 2), 4]).is_err());
+    }
+
+    #[test]
+    fn test_read_leveldb_tables() {
+        for name in ["leveldb_plain.ldb", "leveldb_snappy.ldb"] {
+            assert_eq!(check_fixture(name, true), ForeignFormat::LevelDb);
+        }
+    }
+
+    #[test]
+    fn test_read_rocksdb_tables() {
+        for fv in [2, 5, 6, 7] {
+            for kind in ["plain", "snappy"] {
+                let name = format!("fv{}_{}.sst", fv, kind);
+                let expected = ForeignFormat::RocksDb { format_version: fv };
+                assert_eq!(check_fixture(&name, false), expected);
+            }
+        }
+        // Default XXH3 checksums are not verified but must still parse
+        let expected = ForeignFormat::RocksDb { format_version: 7 };
+        assert_eq!(check_fixture("fv7_xxh3.sst", false), expected);
+    }
+
+    #[test]
+    fn test_ingest_foreign_tables() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = crate::config::Config::builder(dir.path())
+            .memtable_max_size(1024)
+            .build()
+            .unwrap();
+        let engine = Oblivion::open(config).unwrap();
+        engine.put(b"key_0002".to_vec(), b"old".to_vec()).unwrap();
+        engine.put(b"key_0010".to_vec(), b"old".to_vec()).unwrap();
+        engine.put(b"other".to_vec(), b"kept".to_vec()).unwrap();
+
+        let keys = engine
+            .ingest_foreign_tables(&[fixture("leveldb_snappy.ldb")])
+            .unwrap();
+        assert_eq!(keys, 200);
+        assert_eq!(
+            engine.get(b"key_0002"),
+            Some(b"value_0002_aaaaaaaaaaaaaaaaaaaa".to_vec())
+        );
+        assert_eq!(engine.get(b"key_0010"), None);
+        assert_eq!(engine.get(b"key_0001"), Some(b"overwritten".to_vec()));
+        assert_eq!(engine.get(b"other"), Some(b"kept".to_vec()));
+        assert_eq!(engine.scan().len(), 181);
+
+        // A failed conversion leaves the engine unchanged
+        let sstables = engine.sstable_count();
+        assert!(engine
+            .ingest_foreign_tables(&[fixture("leveldb_plain.ldb"), dir.path().join("missing")])
+            .is_err());
+        assert_eq!(engine.sstable_count(), sstables);
+        assert_eq!(engine.get(b"key_0002").unwrap().len(), 31);
+    }
+
+    #[test]
+    fn test_corrupt_table_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("000005.ldb");
+        let mut data = std::fs::read(fixture("leveldb_plain.ldb")).unwrap();
+        data[100] ^= 0xff;
+        std::fs::write(&path, &data).unwrap();
+        let table = ForeignTable::open(&path).unwrap();
+        let result: Result<Vec<_>> = table.entries().collect();
+        assert!(matches!(result, Err(OblivionError::Corruption(_))));
+
+        std::fs::write(&path, vec![0; 100]).unwrap();
+        assert!(ForeignTable::open(&path).is_err());
+    }
+}
@@ -0,0 +1,221 @@
+//! OBLIVION - Dictionary Compression
+//! Zstd compression of SSTable data blocks with a trained dictionary.
+//!
+//! Values of a few dozen bytes barely compress on their own, and a 4 KB
+//! block holds too few of them for zstd to learn much either. Each flush
+//! or compaction therefore trains a dictionary over a sample of the
+//! entries it writes (see `compression_dict_size`), stores it in the
+//! properties of every output table, and compresses each data block
+//! against it. Readers load the dictionary together with the properties.
+//!
+//! Needs the `zstd` feature; without it tables are written uncompressed,
+//! and compressed tables fail to read with [`OblivionError::Unsupported`].
+
+#[cfg(feature = "zstd")]
+use zstd::bulk::{Compressor, Decompressor};
+#[cfg(feature = "zstd")]
+use zstd::dict::DecoderDictionary;
+
+use crate::error::{OblivionError, Result};
+
+/// Zstd level data blocks are compressed at.
+#[cfg(feature = "zstd")]
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// Bytes of samples to train on per byte of dictionary, as the zstd
+/// documentation recommends.
+const SAMPLE_RATIO: usize = 100;
+
+/// Train a dictionary of at most `max_size` bytes over an evenly spread
+/// sample of `entries`. Returns `None` if dictionaries are disabled
+/// (`max_size` of 0 or no `zstd` feature) or there is too little data to
+/// train on, in which case the table is written uncompressed.
+pub fn train_dictionary<'a>(
+    entries: &[(&'a [u8], Option<&'a [u8]>)],
+    max_size: usize,
+) -> Option<Vec<u8>> {
+    if max_size == 0 || !cfg!(feature = "zstd") || entries.is_empty() {
+        return None;
+    }
+    let total: usize = entries
+        .iter()
+        .map(|(key, value)| key.len() + value.map_or(0, <[u8]>::len))
+        .sum();
+    let step = total.div_ceil(max_size.saturating_mul(SAMPLE_RATIO)).max(1);
+    let samples: Vec<Vec<u8>> = entries
+        .iter()
+        .step_by(step)
+        .map(|(key, value)| [*key, value.unwrap_or_default()].concat())
+        .collect();
+    train(&samples, max_size)
+}
+
+#[cfg(feature = "zstd")]
+fn train(samples: &[Vec<u8>], max_size: usize) -> Option<Vec<u8>> {
+    match zstd::dict::from_samples(samples, max_size) {
+        Ok(dictionary) => Some(dictionary),
+        Err(e) => {
+            log::debug!(
+                "No compression dictionary from {} samples: {}",
+                samples.len(),
+                e
+            );
+            None
+        }
+    }
+}
+
+#[cfg(not(feature = "zstd"))]
+fn train(_samples: &[Vec<u8>], _max_size: usize) -> Option<Vec<u8>> {
+    None
+}
+
+/// Compresses the data blocks of one table with its dictionary.
+pub(crate) struct BlockCompressor {
+    #[cfg(feature = "zstd")]
+    compressor: Compressor<'static>,
+}
+
+impl BlockCompressor {
+    /// Prepare to compress against `dictionary`.
+    pub(crate) fn new(dictionary: &[u8]) -> Result<Self> {
+        #[cfg(feature = "zstd")]
+        {
+            Ok(Self {
+                compressor: Compressor::with_dictionary(COMPRESSION_LEVEL, dictionary)?,
+            })
+        }
+        #[cfg(not(feature = "zstd"))]
+        {
+            let _ = dictionary;
+            Err(unsupported())
+        }
+    }
+
+    /// Compress `block` into `[raw_len: 4 bytes LE][zstd frame]`.
+    pub(crate) fn compress(&mut self, block: &[u8]) -> Result<Vec<u8>> {
+        #[cfg(feature = "zstd")]
+        {
+            let mut compressed = (block.len() as u32).to_le_bytes().to_vec();
+            compressed.extend_from_slice(&self.compressor.compress(block)?);
+            Ok(compressed)
+        }
+        #[cfg(not(feature = "zstd"))]
+        {
+            let _ = block;
+            Err(unsupported())
+        }
+    }
+}
+
+/// Decompresses the data blocks of one table with its dictionary.
+pub(crate) struct BlockDecompressor {
+    #[cfg(feature = "zstd")]
+    dictionary: DecoderDictionary<'static>,
+}
+
+impl BlockDecompressor {
+    /// Prepare to decompress against `dictionary`.
+    pub(crate) fn new(dictionary: &[u8]) -> Result<Self> {
+        #[cfg(feature = "zstd")]
+        {
+            Ok(Self {
+                dictionary: DecoderDictionary::copy(dictionary),
+            })
+        }
+        #[cfg(not(feature = "zstd"))]
+        {
+            let _ = dictionary;
+            Err(unsupported())
+        }
+    }
+
+    /// Decompress a block written by [`BlockCompressor::compress`].
+    pub(crate) fn decompress(&self, block: &[u8]) -> Result<Vec<u8>> {
+        let (len, frame) = block
+            .split_first_chunk::<4>()
+            .ok_or_else(|| OblivionError::Corruption("truncated compressed block".into()))?;
+        let len = u32::from_le_bytes(*len) as usize;
+        #[cfg(feature = "zstd")]
+        {
+            let mut decompressor = Decompressor::with_prepared_dictionary(&self.dictionary)?;
+            let raw = decompressor
+                .decompress(frame, len)
+                .map_err(|e| OblivionError::Corruption(format!("compressed block: {}", e)))?;
+            if raw.len() != len {
+                return Err(OblivionError::Corruption(format!(
+                    "compressed block holds {} bytes, expected {}",
+                    raw.len(),
+                    len
+                )));
+            }
+            Ok(raw)
+        }
+        #[cfg(not(feature = "zstd"))]
+        {
+            let _ = (len, frame);
+            Err(unsupported())
+        }
+    }
+}
+
+#[cfg(not(feature = "zstd"))]
+fn unsupported() -> OblivionError {
+    OblivionError::Unsupported("zstd compressed SSTables require the zstd feature".into())
+}
+
+#[cfg(all(test, feature = "zstd"))]
+mod tests {
+    use super::*;
+
+    fn entries() -> Vec<(Vec<u8>, Vec<u8>)> {
+        (0..2000)
+            .map(|i| {
+                let key = format!("user:{:06}", i).into_bytes();
+                let value = format!(
+                    "{{\"id\":{},\"name\":\"user {}\",\"active\":{}}}",
+                    i,
+                    i % 97,
+                    i % 2 == 0
+                )
+                .into_bytes();
+                (key, value)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_trained_dictionary_round_trips_blocks() {
+        let entries = entries();
+        let refs: Vec<(&[u8], Option<&[u8]>)> = entries
+            .iter()
+            .map(|(k, v)| (k.as_slice(), Some(v.as_slice())))
+            .collect();
+        let dictionary = train_dictionary(&refs, 4096).expect("enough samples to train on");
+        assert!(!dictionary.is_empty() && dictionary.len() <= 4096);
+
+        let block: Vec<u8> = entries[..40]
+            .iter()
+            .flat_map(|(k, v)| [k.clone(), v.clone()].concat())
+            .collect();
+        let compressed = BlockCompressor::new(&dictionary)
+            .unwrap()
+            .compress(&block)
+            .unwrap();
+        assert!(compressed.len() < block.len() / 2);
+        let decompressor = BlockDecompressor::new(&dictionary).unwrap();
+        assert_eq!(decompressor.decompress(&compressed).unwrap(), block);
+        assert!(decompressor.decompress(&compressed[..2]).is_err());
+    }
+
+    #[test]
+    fn test_no_dictionary_when_disabled_or_empty() {
+        let entries = entries();
+        let refs: Vec<(&[u8], Option<&[u8]>)> = entries
+            .iter()
+            .map(|(k, v)| (k.as_slice(), Some(v.as_slice())))
+            .collect();
+        assert!(train_dictionary(&refs, 0).is_none());
+        assert!(train_dictionary(&[], 4096).is_none());
+    }
+}
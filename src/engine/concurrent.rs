@@ -1,22 +1,41 @@
 //! OBLIVION - Concurrent Engine Wrapper
-//! Thread-safe wrapper around the Oblivion engine using Arc + RwLock.
+//! Thread-safe wrapper around the Oblivion engine with lock-free reads.
 //!
 //! ## Concurrency Model
-//! - **Read operations** (`get`, `scan`, `len`, etc.) acquire a **read lock** (shared)
-//! - **Write operations** (`put`, `delete`) acquire a **write lock** (exclusive)
-//! - Multiple concurrent readers allowed, writers block all
+//! - **Read operations** (`get`, `scan`, `len`, etc.) never take the engine
+//!   lock: they load the current immutable [`Version`](super::version::Version)
+//!   (frozen MemTables + SSTables) atomically and only briefly lock the
+//!   active MemTable for an in-memory lookup
+//! - **Write operations** (`put`, `delete`) serialize on the engine's
+//!   writer lock
+//! - A write, even one that flushes or compacts, never blocks readers
+//!
+//! [`Oblivion`] itself takes `&self` everywhere, so this wrapper is only
+//! a cloneable `Arc<Oblivion>` handle.
+//!
+//! ## Failure Isolation
+//! The engine's locks are `parking_lot` locks, which are never poisoned:
+//! a thread that panics while holding one does not make the engine
+//! unusable for every other caller. Failures are returned as `Result`s.
 //!
 //! ## Use Case
 //! This wrapper enables safe concurrent access to the engine from multiple threads,
 //! making it suitable for server applications with concurrent client requests.
 
-use std::sync::{Arc, RwLock};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
 
 use crate::config::Config;
 use crate::error::Result;
 use crate::types::{Key, Value};
 
 use super::metrics::EngineMetrics;
+use super::options::ReadOptions;
+use super::predicate::Predicate;
+use super::snapshot::Snapshot;
+use super::transaction::Transaction;
+#[cfg(feature = "tokio")]
+use super::version::ReadState;
 use super::Oblivion;
 
 /// Thread-safe wrapper around the Oblivion storage engine.
@@ -43,71 +62,164 @@ use super::Oblivion;
 /// ```
 #[derive(Clone)]
 pub struct ConcurrentOblivion {
-    inner: Arc<RwLock<Oblivion>>,
+    inner: Arc<Oblivion>,
 }
 
 impl ConcurrentOblivion {
     /// Open or create a concurrent Oblivion storage engine.
     pub fn open(config: Config) -> Result<Self> {
-        let engine = Oblivion::open(config)?;
         Ok(Self {
-            inner: Arc::new(RwLock::new(engine)),
+            inner: Arc::new(Oblivion::open(config)?),
         })
     }
 
-    /// Insert a key-value pair (write lock).
-    pub fn put(&self, key: Key, value: Value) -> Result<()> {
-        self.inner.write().unwrap().put(key, value)
+    /// Insert a key-value pair (writer lock).
+    pub fn put(&self, key: impl Into<Key>, value: impl Into<Value>) -> Result<()> {
+        self.inner.put(key, value)
+    }
+
+    /// Insert a key-value pair with TTL (writer lock).
+    pub fn put_with_ttl(
+        &self,
+        key: impl Into<Key>,
+        value: impl Into<Value>,
+        ttl_ms: u64,
+    ) -> Result<()> {
+        self.inner.put_with_ttl(key, value, ttl_ms)
     }
 
-    /// Insert a key-value pair with TTL (write lock).
-    pub fn put_with_ttl(&self, key: Key, value: Value, ttl_ms: u64) -> Result<()> {
-        self.inner.write().unwrap().put_with_ttl(key, value, ttl_ms)
+    /// Get a value by key (lock-free).
+    pub fn get(&self, key: impl AsRef<[u8]>) -> Option<Value> {
+        let key = key.as_ref();
+        self.get_opt(key, &ReadOptions::default())
+            .unwrap_or_else(|e| {
+                log::error!("Read failed for key {:?}: {}", key, e);
+                None
+            })
     }
 
-    /// Get a value by key (read lock).
-    pub fn get(&self, key: &[u8]) -> Option<Value> {
-        self.inner.read().unwrap().get(key)
+    /// Get a value by key with per-read options (lock-free).
+    pub fn get_opt(&self, key: impl AsRef<[u8]>, opts: &ReadOptions) -> Result<Option<Value>> {
+        self.inner.get_opt(key, opts)
     }
 
-    /// Delete a key (write lock).
-    pub fn delete(&self, key: Key) -> Result<()> {
-        self.inner.write().unwrap().delete(key)
+    /// Take a point-in-time snapshot (lock-free).
+    pub fn snapshot(&self) -> Snapshot {
+        self.inner.snapshot()
     }
 
-    /// Scan all key-value pairs (read lock).
+    /// Delete a key (writer lock).
+    pub fn delete(&self, key: impl Into<Key>) -> Result<()> {
+        self.inner.delete(key)
+    }
+
+    /// Scan all key-value pairs (lock-free).
     pub fn scan(&self) -> Vec<(Key, Value)> {
-        self.inner.read().unwrap().scan()
+        self.scan_opt(&ReadOptions::default()).unwrap_or_else(|e| {
+            log::error!("Scan failed: {}", e);
+            Vec::new()
+        })
+    }
+
+    /// Scan key-value pairs with per-read options (lock-free).
+    pub fn scan_opt(&self, opts: &ReadOptions) -> Result<Vec<(Key, Value)>> {
+        self.inner.scan_opt(opts)
+    }
+
+    /// Returns the keys matching a glob pattern (lock-free).
+    pub fn keys(&self, pattern: &[u8]) -> Result<Vec<Key>> {
+        self.inner.keys(pattern)
+    }
+
+    /// Returns a live key picked at random (lock-free).
+    pub fn random_key(&self) -> Result<Option<Key>> {
+        self.inner.random_key()
     }
 
-    /// Get remaining TTL for a key (read lock).
-    pub fn ttl(&self, key: &[u8]) -> Option<u64> {
-        self.inner.read().unwrap().ttl(key)
+    /// Returns up to `count` live keys picked at random (lock-free).
+    pub fn random_keys(&self, count: usize) -> Result<Vec<Key>> {
+        self.inner.random_keys(count)
     }
 
-    /// Get number of entries (read lock).
+    /// Returns the key-value pairs passing a predicate (lock-free).
+    pub fn scan_filtered(&self, predicate: &Predicate) -> Result<Vec<(Key, Value)>> {
+        self.inner.scan_filtered(predicate)
+    }
+
+    /// Returns the key-value pairs passing a predicate within the
+    /// bounds of `opts` (lock-free).
+    pub fn scan_filtered_opt(
+        &self,
+        predicate: &Predicate,
+        opts: &ReadOptions,
+    ) -> Result<Vec<(Key, Value)>> {
+        self.inner.scan_filtered_opt(predicate, opts)
+    }
+
+    /// Get remaining TTL for a key (lock-free).
+    pub fn ttl(&self, key: impl AsRef<[u8]>) -> Option<u64> {
+        self.inner.ttl(key)
+    }
+
+    /// Get number of entries in the active MemTable, tombstones
+    /// included (lock-free).
     pub fn len(&self) -> usize {
-        self.inner.read().unwrap().len()
+        self.inner.len()
+    }
+
+    /// Begin a transaction; see [`Oblivion::transaction`].
+    pub fn transaction(&self) -> Transaction<'_> {
+        self.inner.transaction()
+    }
+
+    /// Estimate the number of keys without reading any data.
+    pub fn estimate_live_keys(&self) -> u64 {
+        self.inner.estimate_live_keys()
+    }
+
+    /// Estimate the number of keys from counters alone (lock-free).
+    pub fn dbsize_estimate(&self) -> u64 {
+        self.inner.dbsize_estimate()
+    }
+
+    /// Count the keys with a full scan, until `cancel` is set
+    /// (lock-free).
+    pub fn dbsize_exact(&self, cancel: &AtomicBool) -> Result<u64> {
+        self.inner.dbsize_exact(cancel)
     }
 
-    /// Check if engine is empty (read lock).
+    /// Check if engine is empty (lock-free).
     pub fn is_empty(&self) -> bool {
-        self.inner.read().unwrap().is_empty()
+        self.len() == 0
     }
 
-    /// Get MemTable size in bytes (read lock).
+    /// Get MemTable size in bytes (lock-free).
     pub fn memtable_size(&self) -> usize {
-        self.inner.read().unwrap().memtable_size()
+        self.inner.memtable_size()
+    }
+
+    /// Change runtime-mutable options (writer lock).
+    pub fn set_options<I, K, V>(&self, options: I) -> Result<()>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        self.inner.set_options(options)
+    }
+
+    /// State shared with the engine (see [`version`](super::version)).
+    #[cfg(feature = "tokio")]
+    pub(crate) fn read_state(&self) -> Arc<ReadState> {
+        Arc::clone(&self.inner.state)
     }
 
-    /// Get a snapshot of the engine metrics (read lock).
-    /// Returns a reference that must be used within the read lock scope.
+    /// Run `f` with the engine metrics (lock-free).
     pub fn with_metrics<F, R>(&self, f: F) -> R
     where
         F: FnOnce(&EngineMetrics) -> R,
     {
-        let engine = self.inner.read().unwrap();
-        f(engine.metrics())
+        f(self.inner.metrics())
     }
 }
 
@@ -122,6 +234,7 @@ mod tests {
             data_dir: dir.path().to_path_buf(),
             memtable_max_size: 64 * 1024,
             sync_writes: true,
+            ..Default::default()
         }
     }
 
@@ -233,4 +346,76 @@ mod tests {
             assert!(metrics.total_ops() > 0);
         });
     }
+
+    #[test]
+    fn test_reads_do_not_wait_for_writers() {
+        let engine = ConcurrentOblivion::open(temp_config()).unwrap();
+        engine.put(b"key".to_vec(), b"value".to_vec()).unwrap();
+
+        // Hold the engine lock as a long-running write would
+        let _writer = engine.inner.writer.lock();
+        let reader = engine.clone();
+        let handle = thread::spawn(move || (reader.get(b"key"), reader.scan().len()));
+        assert_eq!(handle.join().unwrap(), (Some(b"value".to_vec()), 1));
+    }
+
+    #[test]
+    fn test_panicked_writer_does_not_poison_engine() {
+        let engine = ConcurrentOblivion::open(temp_config()).unwrap();
+        engine.put(b"key".to_vec(), b"value".to_vec()).unwrap();
+
+        let writer = engine.clone();
+        let panicked = thread::spawn(move || {
+            let _writer = writer.inner.writer.lock();
+            let _memtable = writer.inner.state.memtable.write();
+            panic!("writer failed mid-update");
+        })
+        .join();
+        assert!(panicked.is_err());
+
+        engine.put(b"other".to_vec(), b"value".to_vec()).unwrap();
+        assert_eq!(engine.get(b"key"), Some(b"value".to_vec()));
+        assert_eq!(engine.scan().len(), 2);
+    }
+
+    #[test]
+    fn test_reads_during_flush_and_compaction() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let config = Config {
+            memtable_max_size: 1024,
+            compaction_threshold: 2,
+            ..temp_config()
+        };
+        let engine = ConcurrentOblivion::open(config).unwrap();
+        let written = Arc::new(AtomicUsize::new(0));
+
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let engine = engine.clone();
+                let written = Arc::clone(&written);
+                thread::spawn(move || {
+                    while written.load(Ordering::Acquire) < 500 {
+                        // Every acknowledged write stays visible across freezes,
+                        // flushes and compactions
+                        let upto = written.load(Ordering::Acquire);
+                        for i in (0..upto).step_by(17) {
+                            let key = format!("key_{:04}", i).into_bytes();
+                            assert_eq!(engine.get(&key), Some(vec![b'v'; 64]), "key_{:04}", i);
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        for i in 0..500 {
+            let key = format!("key_{:04}", i).into_bytes();
+            engine.put(key, vec![b'v'; 64]).unwrap();
+            written.store(i + 1, Ordering::Release);
+        }
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert_eq!(engine.scan().len(), 500);
+    }
 }
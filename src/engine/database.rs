@@ -0,0 +1,404 @@
+//! OBLIVION - Logical Databases
+//! Numbered databases in one keyspace, as Redis' `SELECT n`, so test
+//! and production-like data can live side by side in one directory.
+//!
+//! A [`Database`] handle from [`Oblivion::database`] reads and writes
+//! database `n` of the `databases` the configuration allows. Database 0
+//! is the plain keyspace `put`/`get` use; database `n >= 1` stores its
+//! keys behind a six-byte tag:
+//!
+//! ```text
+//! \xff\xffdb <n: u16 BE> <key>
+//! ```
+//!
+//! The tag range is reserved: database 0 refuses writes to keys starting
+//! with [`DATABASE_PREFIX`] and hides them from reads and scans, so each
+//! database only sees its own keys. Every database likewise reserves
+//! keys starting with [`DATA_TYPE_PREFIX`] for the records of data
+//! types such as [hashes](super::hash). The engine-level calls
+//! (`Oblivion::put`, `scan`, `export`, ...) still see the raw keyspace,
+//! tags included, as do flushes, compactions, checkpoints and backups,
+//! which cover every database at once.
+
+use crate::engine::batch::{BatchOp, WriteBatch};
+use crate::engine::glob::matching_keys;
+use crate::engine::options::{prefix_end, ReadOptions};
+use crate::error::{OblivionError, Result};
+use crate::types::{Key, Value};
+
+use super::version::ScanPage;
+use super::Oblivion;
+
+/// First bytes of every key stored in a database other than 0.
+pub const DATABASE_PREFIX: &[u8] = b"\xff\xffdb";
+
+/// First bytes of the keys data types store within a database.
+pub const DATA_TYPE_PREFIX: &[u8] = b"\xff\xfft";
+
+/// One numbered database of an engine.
+///
+/// ## Example
+/// ```no_run
+/// use oblivion::config::Config;
+/// use oblivion::engine::Oblivion;
+///
+/// let engine = Oblivion::open(Config::new("./data")).unwrap();
+/// let test = engine.database(1).unwrap();
+/// test.put(b"user:1".to_vec(), b"fixture".to_vec()).unwrap();
+/// assert_eq!(engine.get(b"user:1"), None);
+/// ```
+#[derive(Clone)]
+pub struct Database<'a> {
+    engine: &'a Oblivion,
+    index: usize,
+    /// Tag of the database's keys; empty for database 0.
+    prefix: Vec<u8>,
+}
+
+impl<'a> Database<'a> {
+    /// Handle for database `index`, which the caller has checked against
+    /// the configured number of databases.
+    pub(crate) fn new(engine: &'a Oblivion, index: usize) -> Self {
+        let prefix = match index {
+            0 => Vec::new(),
+            _ => [DATABASE_PREFIX, &(index as u16).to_be_bytes()].concat(),
+        };
+        Self {
+            engine,
+            index,
+            prefix,
+        }
+    }
+
+    /// Returns the database's number.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Get a value by key. Keys with an expired TTL return `None`.
+    pub fn get(&self, key: impl AsRef<[u8]>) -> Result<Option<Value>> {
+        let key = key.as_ref();
+        if self.is_reserved(key) {
+            return Ok(None);
+        }
+        self.engine
+            .get_opt(self.stored(key), &ReadOptions::default())
+    }
+
+    /// Returns whether `key` holds a live value, without reading it out.
+    pub fn exists(&self, key: impl AsRef<[u8]>) -> Result<bool> {
+        let key = key.as_ref();
+        if self.is_reserved(key) {
+            return Ok(false);
+        }
+        self.engine
+            .exists_opt(self.stored(key), &ReadOptions::default())
+    }
+
+    /// Insert a key-value pair.
+    pub fn put(&self, key: impl AsRef<[u8]>, value: impl Into<Value>) -> Result<()> {
+        let key = self.writable(key.as_ref())?;
+        self.engine.put(key, value)
+    }
+
+    /// Insert a key-value pair that expires after `ttl_ms` milliseconds.
+    pub fn put_with_ttl(
+        &self,
+        key: impl AsRef<[u8]>,
+        value: impl Into<Value>,
+        ttl_ms: u64,
+    ) -> Result<()> {
+        let key = self.writable(key.as_ref())?;
+        self.engine.put_with_ttl(key, value, ttl_ms)
+    }
+
+    /// Delete a key.
+    pub fn delete(&self, key: impl AsRef<[u8]>) -> Result<()> {
+        let key = self.writable(key.as_ref())?;
+        self.engine.delete(key)
+    }
+
+    /// Append `suffix` to the value of `key` and return the new length,
+    /// as [`Oblivion::append`] does.
+    pub fn append(&self, key: impl AsRef<[u8]>, suffix: impl AsRef<[u8]>) -> Result<usize> {
+        let key = self.writable(key.as_ref())?;
+        self.engine.append(key, suffix)
+    }
+
+    /// Store `value` under `key` and return the value it replaced.
+    pub fn getset(&self, key: impl AsRef<[u8]>, value: impl Into<Value>) -> Result<Option<Value>> {
+        let key = self.writable(key.as_ref())?;
+        self.engine.getset(key, value)
+    }
+
+    /// Delete `key` and return the value it had.
+    pub fn getdel(&self, key: impl AsRef<[u8]>) -> Result<Option<Value>> {
+        let key = self.writable(key.as_ref())?;
+        self.engine.getdel(key)
+    }
+
+    /// Returns the length of the value of `key`, or 0 if it is missing.
+    pub fn strlen(&self, key: impl AsRef<[u8]>) -> Result<usize> {
+        let key = key.as_ref();
+        if self.is_reserved(key) {
+            return Ok(0);
+        }
+        self.engine.strlen(self.stored(key))
+    }
+
+    /// Set a TTL on an existing key, returning whether the key was live.
+    pub fn expire(&self, key: impl AsRef<[u8]>, ttl_ms: u64) -> Result<bool> {
+        let key = key.as_ref();
+        if self.is_reserved(key) {
+            return Ok(false);
+        }
+        self.engine.expire(self.stored(key), ttl_ms)
+    }
+
+    /// Remove the TTL of a live key, returning whether it had one.
+    pub fn persist(&self, key: impl AsRef<[u8]>) -> Result<bool> {
+        let key = key.as_ref();
+        if self.is_reserved(key) {
+            return Ok(false);
+        }
+        self.engine.persist(self.stored(key))
+    }
+
+    /// Get the remaining TTL for a key in milliseconds.
+    pub fn ttl(&self, key: impl AsRef<[u8]>) -> Option<u64> {
+        let key = key.as_ref();
+        if self.is_reserved(key) {
+            return None;
+        }
+        self.engine.ttl(self.stored(key))
+    }
+
+    /// Apply the writes of `batch` to this database, as
+    /// [`Oblivion::write`] does.
+    pub fn write(&self, batch: WriteBatch) -> Result<()> {
+        let mut stored = WriteBatch::new();
+        for op in batch.into_ops() {
+            match op {
+                BatchOp::Put {
+                    key,
+                    value,
+                    ttl_ms: Some(ttl_ms),
+                } => stored.put_with_ttl(self.writable(&key)?, value, ttl_ms),
+                BatchOp::Put { key, value, .. } => stored.put(self.writable(&key)?, value),
+                BatchOp::Delete { key } => stored.delete(self.writable(&key)?),
+            };
+        }
+        self.engine.write(stored)
+    }
+
+    /// Scan one page of the database's range selected by `opts`, as
+    /// [`Oblivion::scan_page`] does. Bounds and the resume key are keys
+    /// of this database.
+    ///
+    /// A range reaching into a reserved one is read up to it, then goes
+    /// on after it within the same page.
+    pub fn scan_page(&self, opts: &ReadOptions, limit: usize) -> Result<ScanPage> {
+        let mut lower = self.stored(opts.lower_bound.as_deref().unwrap_or_default());
+        let upper = match &opts.upper_bound {
+            Some(upper) => Some(self.stored(upper)),
+            None => prefix_end(&self.prefix),
+        };
+        let mut rows = Vec::new();
+        for reserved in self.reserved() {
+            let start = self.stored(reserved);
+            let end = prefix_end(&start);
+            if end.as_ref().is_some_and(|end| *end <= lower) {
+                continue;
+            }
+            let below = match &upper {
+                Some(upper) if *upper < start => upper.clone(),
+                _ => start,
+            };
+            if self.engine.comparator().compare(&lower, &below).is_lt() {
+                let limit = limit.saturating_sub(rows.len()).max(1);
+                let (page, resume) = self.scan_stored(opts, lower.clone(), Some(below), limit)?;
+                rows.extend(page);
+                if resume.is_some() {
+                    return Ok((rows, resume));
+                }
+            }
+            match end {
+                Some(end) if upper.as_ref().is_none_or(|upper| end < *upper) => {
+                    lower = lower.max(end)
+                }
+                _ => return Ok((rows, None)),
+            }
+        }
+        let limit = limit.saturating_sub(rows.len()).max(1);
+        let (page, resume) = self.scan_stored(opts, lower, upper, limit)?;
+        rows.extend(page);
+        Ok((rows, resume))
+    }
+
+    /// One page of the stored keys in `[lower, upper)`, with the prefix
+    /// stripped from the rows and the resume key.
+    fn scan_stored(
+        &self,
+        opts: &ReadOptions,
+        lower: Key,
+        upper: Option<Key>,
+        limit: usize,
+    ) -> Result<ScanPage> {
+        let mut stored = opts.clone();
+        stored.lower_bound = Some(lower);
+        stored.upper_bound = upper;
+        let (rows, resume) = self.engine.scan_page(&stored, limit)?;
+        let rows = rows
+            .into_iter()
+            .map(|(key, value)| (key[self.prefix.len()..].to_vec(), value))
+            .collect();
+        let resume = resume.map(|resume| resume[self.prefix.len()..].to_vec());
+        Ok((rows, resume))
+    }
+
+    /// Returns the live keys of this database matching the glob
+    /// `pattern`, in sorted order, as [`Oblivion::keys`] does.
+    pub fn keys(&self, pattern: &[u8]) -> Result<Vec<Key>> {
+        let mut opts = ReadOptions::new().fill_cache(false);
+        opts.snapshot = Some(self.engine.snapshot());
+        matching_keys(self.engine.comparator(), pattern, opts, |opts, limit| {
+            self.scan_page(opts, limit)
+        })
+    }
+
+    /// The engine the database belongs to.
+    pub(crate) fn engine(&self) -> &'a Oblivion {
+        self.engine
+    }
+
+    /// The key `key` is stored under.
+    pub(crate) fn stored(&self, key: &[u8]) -> Key {
+        [&self.prefix, key].concat()
+    }
+
+    /// Get the value stored under the raw key `stored`.
+    pub(crate) fn get_stored(&self, stored: &[u8]) -> Result<Option<Value>> {
+        self.engine.get_opt(stored, &ReadOptions::default())
+    }
+
+    /// Prefixes of the keys this database cannot address, in order.
+    fn reserved(&self) -> &'static [&'static [u8]] {
+        match self.index {
+            0 => &[DATABASE_PREFIX, DATA_TYPE_PREFIX],
+            _ => &[DATA_TYPE_PREFIX],
+        }
+    }
+
+    /// Returns whether `key` is one this database cannot address.
+    pub(crate) fn is_reserved(&self, key: &[u8]) -> bool {
+        self.reserved().iter().any(|prefix| key.starts_with(prefix))
+    }
+
+    /// The key `key` is written under, or an error if it is reserved.
+    pub(crate) fn writable(&self, key: &[u8]) -> Result<Key> {
+        if let Some(prefix) = self
+            .reserved()
+            .iter()
+            .find(|prefix| key.starts_with(prefix))
+        {
+            return Err(OblivionError::Unsupported(format!(
+                "keys starting with {:?} are reserved for {}",
+                String::from_utf8_lossy(prefix),
+                match *prefix {
+                    DATABASE_PREFIX => "logical databases",
+                    _ => "data types",
+                }
+            )));
+        }
+        Ok(self.stored(key))
+    }
+}
+
+/// Split a key of the raw keyspace into its database number and the key
+/// within that database.
+pub fn split_key(key: &[u8]) -> (usize, &[u8]) {
+    match key.strip_prefix(DATABASE_PREFIX) {
+        Some([high, low, key @ ..]) => (u16::from_be_bytes([*high, *low]) as usize, key),
+        _ => (0, key),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn test_databases_are_isolated() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = Oblivion::open(Config::new(dir.path()).with_databases(4)).unwrap();
+        let (zero, one, two) = (
+            engine.database(0).unwrap(),
+            engine.database(1).unwrap(),
+            engine.database(2).unwrap(),
+        );
+        zero.put(b"k", b"0".to_vec()).unwrap();
+        one.put(b"k", b"1".to_vec()).unwrap();
+        one.put_with_ttl(b"t", b"x".to_vec(), 60_000).unwrap();
+        let mut batch = WriteBatch::new();
+        batch.put(b"a".to_vec(), b"2".to_vec());
+        batch.delete(b"k".to_vec());
+        two.write(batch).unwrap();
+
+        assert_eq!(zero.get(b"k").unwrap(), Some(b"0".to_vec()));
+        assert_eq!(one.get(b"k").unwrap(), Some(b"1".to_vec()));
+        assert_eq!(two.get(b"k").unwrap(), None);
+        assert_eq!(engine.get(b"k"), Some(b"0".to_vec()));
+        assert!(one.ttl(b"t").is_some());
+        assert!(zero.ttl(b"t").is_none());
+        assert_eq!(one.keys(b"*").unwrap(), vec![b"k".to_vec(), b"t".to_vec()]);
+        assert_eq!(zero.keys(b"*").unwrap(), vec![b"k".to_vec()]);
+        assert!(engine.database(4).is_err());
+
+        let reserved = [DATABASE_PREFIX, b"\x00\x01k"].concat();
+        assert_eq!(zero.get(&reserved).unwrap(), None);
+        assert!(zero.put(reserved.clone(), b"v".to_vec()).is_err());
+        assert_eq!(split_key(&reserved), (1, &b"k"[..]));
+        assert_eq!(split_key(b"k"), (0, &b"k"[..]));
+    }
+
+    #[test]
+    fn test_scan_pages_skip_other_databases() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = Oblivion::open(Config::new(dir.path())).unwrap();
+        let (zero, one) = (engine.database(0).unwrap(), engine.database(1).unwrap());
+        for key in [&b"a"[..], b"b", b"\xff\xff\xff"] {
+            zero.put(key, b"0".to_vec()).unwrap();
+        }
+        for i in 0..5 {
+            one.put(vec![b'k', i], b"1".to_vec()).unwrap();
+        }
+
+        let scan_all = |db: &Database, limit| {
+            let mut opts = ReadOptions::new();
+            let mut keys = Vec::new();
+            loop {
+                let (rows, resume) = db.scan_page(&opts, limit).unwrap();
+                keys.extend(rows.into_iter().map(|(key, _)| key));
+                match resume {
+                    Some(resume) => opts.lower_bound = Some(resume),
+                    None => return keys,
+                }
+            }
+        };
+        for limit in [1, 2, 100] {
+            assert_eq!(
+                scan_all(&zero, limit),
+                vec![b"a".to_vec(), b"b".to_vec(), b"\xff\xff\xff".to_vec()]
+            );
+            assert_eq!(
+                scan_all(&one, limit),
+                (0..5).map(|i| vec![b'k', i]).collect::<Vec<_>>()
+            );
+        }
+        let (rows, _) = one
+            .scan_page(&ReadOptions::new().prefix(b"k\x03"), 10)
+            .unwrap();
+        assert_eq!(rows, vec![(b"k\x03".to_vec(), b"1".to_vec())]);
+    }
+}
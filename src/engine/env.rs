@@ -0,0 +1,610 @@
+//! OBLIVION - File System Environment
+//! The file operations the engine performs, behind a trait, so the same
+//! engine can run on the local disk, in memory for tests, or on a
+//! backend that injects faults or stores files elsewhere.
+//!
+//! A [`FileSystem`] opens, appends to, renames, syncs, lists and
+//! deletes files. The engine reaches it through the [`Env`] in
+//! `Config::env`: the WAL, SSTables, the table cache, the manifest, the
+//! column family registry and the `LOCK` file all go through it. The
+//! default is the local file system; [`MemoryFileSystem`] keeps a whole
+//! database in memory, `fault::FaultFileSystem` injects faults and power
+//! cuts, and `object_store::ObjectFileSystem` (`object-store` feature)
+//! keeps SSTables in an S3-compatible object store.
+//!
+//! Tools that hand files to the outside world or read them from it
+//! (backups, checkpoints, repair, secondary instances, change
+//! subscriptions replaying logs, and ingesting external SSTables) work
+//! on local paths only, and fail with `Unsupported` under another
+//! file system.
+//!
+//! ## Semantics
+//! Implementations follow POSIX: a rename replaces its target
+//! atomically, and open handles keep working after their file is
+//! renamed or deleted. Data is only durable once synced, and a
+//! directory's entries (creations, renames, deletions) once the
+//! directory is.
+//!
+//! ## Example
+//! ```no_run
+//! use oblivion::config::Config;
+//! use oblivion::engine::env::Env;
+//! use oblivion::engine::Oblivion;
+//!
+//! // Clones of an Env share its files, so the database survives a reopen
+//! let env = Env::memory();
+//! let config = Config::new("/db").with_env(env.clone());
+//! let engine = Oblivion::open(config.clone()).unwrap();
+//! engine.put(b"k".to_vec(), b"v".to_vec()).unwrap();
+//! drop(engine);
+//! let engine = Oblivion::open(config).unwrap();
+//! assert_eq!(engine.get(b"k"), Some(b"v".to_vec()));
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use parking_lot::{Mutex, RwLock};
+
+use crate::error::{OblivionError, Result};
+
+use super::io::{self as file_io, SyncMethod, TableWriter};
+
+/// Name of the local file system.
+pub const LOCAL: &str = "oblivion.local";
+
+/// Name of the in-memory file system.
+pub const MEMORY: &str = "oblivion.memory";
+
+/// An exclusive lock on a file, released when dropped.
+pub type FileLock = Box<dyn Send + Sync>;
+
+/// File operations the engine performs.
+pub trait FileSystem: Send + Sync {
+    /// Returns the name of this file system, for logs.
+    fn name(&self) -> &str;
+
+    /// Open `path` for reads at any offset, bypassing the OS page cache
+    /// if `direct` is set and the file system supports it.
+    fn open_read(&self, path: &Path, direct: bool) -> io::Result<Box<dyn RandomAccessFile>>;
+
+    /// Create `path`, or truncate it, for writing front to back;
+    /// `direct` as for [`open_read`](Self::open_read).
+    fn create(&self, path: &Path, direct: bool) -> io::Result<Box<dyn WritableFile>>;
+
+    /// Open `path` for appending, creating it if needed.
+    fn open_append(&self, path: &Path) -> io::Result<Box<dyn WritableFile>>;
+
+    /// Read the whole file at `path`.
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+    /// Move the file `from` to `to`, atomically replacing any file there.
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    /// Delete the file at `path`.
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+
+    /// Returns the paths of the files and directories in `dir`.
+    fn list(&self, dir: &Path) -> io::Result<Vec<PathBuf>>;
+
+    /// Create `dir` and any missing parents.
+    fn create_dir_all(&self, dir: &Path) -> io::Result<()>;
+
+    /// Delete the empty directory `dir`.
+    fn remove_dir(&self, dir: &Path) -> io::Result<()>;
+
+    /// Returns the size of the file at `path` in bytes.
+    fn file_size(&self, path: &Path) -> io::Result<u64>;
+
+    /// Returns true if a file or directory exists at `path`.
+    fn exists(&self, path: &Path) -> bool;
+
+    /// Make the contents of the file at `path` durable with `method`,
+    /// for a file written through a handle since closed.
+    fn sync_file(&self, path: &Path, method: SyncMethod) -> io::Result<()>;
+
+    /// Make the entries of `dir` durable: files created, renamed into or
+    /// deleted from it.
+    fn sync_dir(&self, dir: &Path) -> io::Result<()>;
+
+    /// Take an exclusive lock on the file at `path`, creating it if
+    /// needed. Returns `None` if the lock is already held.
+    fn try_lock(&self, path: &Path) -> io::Result<Option<FileLock>>;
+}
+
+/// A file written front to back.
+pub trait WritableFile: Write + Send {
+    /// Write out anything buffered and make the contents durable with
+    /// `method`.
+    fn sync(&mut self, method: SyncMethod) -> io::Result<()>;
+
+    /// Returns the local file underneath, which io_uring writes to
+    /// directly; `None` for other file systems.
+    fn as_file(&self) -> Option<&File> {
+        None
+    }
+}
+
+/// A file read at arbitrary offsets.
+pub trait RandomAccessFile: Send {
+    /// Read exactly `len` bytes at `offset`, failing with
+    /// `UnexpectedEof` past the end of the file.
+    fn read_at(&mut self, offset: u64, len: usize) -> io::Result<Vec<u8>>;
+
+    /// Returns the size of the file in bytes.
+    fn size(&self) -> io::Result<u64>;
+
+    /// Returns the local file underneath, which io_uring reads from
+    /// directly; `None` for other file systems.
+    fn as_file(&self) -> Option<&File> {
+        None
+    }
+}
+
+/// The file system an engine works on: the local one (the default), or
+/// any [`FileSystem`]. Dereferences to the file system.
+#[derive(Clone)]
+pub struct Env {
+    fs: Arc<dyn FileSystem>,
+}
+
+impl Env {
+    /// Work on `fs`.
+    pub fn new(fs: impl FileSystem + 'static) -> Self {
+        Self { fs: Arc::new(fs) }
+    }
+
+    /// Work on the local file system.
+    pub fn local() -> Self {
+        Self::new(LocalFileSystem)
+    }
+
+    /// Work on a new, empty [`MemoryFileSystem`]; clones share it.
+    pub fn memory() -> Self {
+        Self::new(MemoryFileSystem::new())
+    }
+
+    /// Returns true for the local file system.
+    pub fn is_local(&self) -> bool {
+        self.fs.name() == LOCAL
+    }
+
+    /// Make the entry of `path` in its directory durable, after creating
+    /// or renaming it.
+    pub(crate) fn sync_parent(&self, path: &Path) -> io::Result<()> {
+        match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => self.fs.sync_dir(dir),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fail with [`OblivionError::Unsupported`] unless this is the local
+    /// file system, which `operation` needs.
+    pub(crate) fn require_local(&self, operation: &str) -> Result<()> {
+        if self.is_local() {
+            return Ok(());
+        }
+        Err(OblivionError::Unsupported(format!(
+            "{} needs the local file system, not '{}'",
+            operation,
+            self.fs.name()
+        )))
+    }
+}
+
+impl Default for Env {
+    fn default() -> Self {
+        Self::local()
+    }
+}
+
+impl Deref for Env {
+    type Target = dyn FileSystem;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.fs
+    }
+}
+
+impl fmt::Debug for Env {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Env").field(&self.fs.name()).finish()
+    }
+}
+
+/// The local file system, through `std::fs`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LocalFileSystem;
+
+/// A local file opened for reading.
+struct LocalFile {
+    file: File,
+    direct: bool,
+}
+
+impl RandomAccessFile for LocalFile {
+    fn read_at(&mut self, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        file_io::read_at(&mut self.file, offset, len, self.direct)
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        Ok(self.file.metadata()?.len())
+    }
+
+    fn as_file(&self) -> Option<&File> {
+        Some(&self.file)
+    }
+}
+
+impl WritableFile for TableWriter {
+    fn sync(&mut self, _method: SyncMethod) -> io::Result<()> {
+        // Tables are written once, so always fsync
+        self.finish()
+    }
+}
+
+/// A local file opened for appending, unbuffered.
+struct AppendFile(File);
+
+impl Write for AppendFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl WritableFile for AppendFile {
+    fn sync(&mut self, method: SyncMethod) -> io::Result<()> {
+        method.sync(&self.0)
+    }
+
+    fn as_file(&self) -> Option<&File> {
+        Some(&self.0)
+    }
+}
+
+impl FileSystem for LocalFileSystem {
+    fn name(&self) -> &str {
+        LOCAL
+    }
+
+    fn open_read(&self, path: &Path, direct: bool) -> io::Result<Box<dyn RandomAccessFile>> {
+        let file = file_io::open_read(path, direct)?;
+        Ok(Box::new(LocalFile { file, direct }))
+    }
+
+    fn create(&self, path: &Path, direct: bool) -> io::Result<Box<dyn WritableFile>> {
+        Ok(Box::new(TableWriter::create(path, direct)?))
+    }
+
+    fn open_append(&self, path: &Path) -> io::Result<Box<dyn WritableFile>> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Box::new(AppendFile(file)))
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        std::fs::read(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        std::fs::rename(from, to)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        std::fs::remove_file(path)
+    }
+
+    fn list(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        std::fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect()
+    }
+
+    fn create_dir_all(&self, dir: &Path) -> io::Result<()> {
+        std::fs::create_dir_all(dir)
+    }
+
+    fn remove_dir(&self, dir: &Path) -> io::Result<()> {
+        std::fs::remove_dir(dir)
+    }
+
+    fn file_size(&self, path: &Path) -> io::Result<u64> {
+        Ok(std::fs::metadata(path)?.len())
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn sync_file(&self, path: &Path, method: SyncMethod) -> io::Result<()> {
+        method.sync(&File::open(path)?)
+    }
+
+    fn sync_dir(&self, dir: &Path) -> io::Result<()> {
+        file_io::sync_dir(dir)
+    }
+
+    fn try_lock(&self, path: &Path) -> io::Result<Option<FileLock>> {
+        Ok(file_io::try_lock_file(path)?.map(|file| Box::new(file) as FileLock))
+    }
+}
+
+/// Contents of a file of a [`MemoryFileSystem`], shared by its handles.
+type MemoryFile = Arc<RwLock<Vec<u8>>>;
+
+/// Files, directories and locks of a [`MemoryFileSystem`].
+#[derive(Default)]
+struct MemoryTree {
+    files: BTreeMap<PathBuf, MemoryFile>,
+    dirs: BTreeSet<PathBuf>,
+    locks: BTreeSet<PathBuf>,
+}
+
+impl MemoryTree {
+    fn file(&self, path: &Path) -> io::Result<MemoryFile> {
+        self.files.get(path).cloned().ok_or_else(|| not_found(path))
+    }
+
+    /// Fail unless the directory `path` would be created in exists.
+    fn check_parent(&self, path: &Path) -> io::Result<()> {
+        match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() && !self.dirs.contains(parent) => {
+                Err(not_found(parent))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A file system held in memory, for tests and throwaway databases.
+/// Nothing is ever lost: every write is durable as soon as it is made.
+#[derive(Clone, Default)]
+pub struct MemoryFileSystem {
+    tree: Arc<Mutex<MemoryTree>>,
+}
+
+impl MemoryFileSystem {
+    /// Create an empty file system.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// A handle on a file of a [`MemoryFileSystem`].
+struct MemoryHandle(MemoryFile);
+
+impl RandomAccessFile for MemoryHandle {
+    fn read_at(&mut self, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        let data = self.0.read();
+        let start = usize::try_from(offset).unwrap_or(usize::MAX);
+        match data.get(start..start.saturating_add(len)) {
+            Some(bytes) => Ok(bytes.to_vec()),
+            None => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "failed to fill whole buffer",
+            )),
+        }
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        Ok(self.0.read().len() as u64)
+    }
+}
+
+impl Write for MemoryHandle {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl WritableFile for MemoryHandle {
+    fn sync(&mut self, _method: SyncMethod) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Lock on a file of a [`MemoryFileSystem`].
+struct MemoryLock {
+    tree: Arc<Mutex<MemoryTree>>,
+    path: PathBuf,
+}
+
+impl Drop for MemoryLock {
+    fn drop(&mut self) {
+        self.tree.lock().locks.remove(&self.path);
+    }
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn name(&self) -> &str {
+        MEMORY
+    }
+
+    fn open_read(&self, path: &Path, _direct: bool) -> io::Result<Box<dyn RandomAccessFile>> {
+        Ok(Box::new(MemoryHandle(self.tree.lock().file(path)?)))
+    }
+
+    fn create(&self, path: &Path, _direct: bool) -> io::Result<Box<dyn WritableFile>> {
+        let mut tree = self.tree.lock();
+        tree.check_parent(path)?;
+        let file = MemoryFile::default();
+        tree.files.insert(path.to_path_buf(), Arc::clone(&file));
+        Ok(Box::new(MemoryHandle(file)))
+    }
+
+    fn open_append(&self, path: &Path) -> io::Result<Box<dyn WritableFile>> {
+        let mut tree = self.tree.lock();
+        tree.check_parent(path)?;
+        let file = tree.files.entry(path.to_path_buf()).or_default();
+        Ok(Box::new(MemoryHandle(Arc::clone(file))))
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let file = self.tree.lock().file(path)?;
+        let data = file.read().clone();
+        Ok(data)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let mut tree = self.tree.lock();
+        tree.check_parent(to)?;
+        let file = tree.files.remove(from).ok_or_else(|| not_found(from))?;
+        tree.files.insert(to.to_path_buf(), file);
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        match self.tree.lock().files.remove(path) {
+            Some(_) => Ok(()),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn list(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        let tree = self.tree.lock();
+        if !tree.dirs.contains(dir) {
+            return Err(not_found(dir));
+        }
+        Ok(tree
+            .files
+            .keys()
+            .chain(&tree.dirs)
+            .filter(|path| path.parent() == Some(dir))
+            .cloned()
+            .collect())
+    }
+
+    fn create_dir_all(&self, dir: &Path) -> io::Result<()> {
+        let mut tree = self.tree.lock();
+        for ancestor in dir.ancestors().filter(|a| !a.as_os_str().is_empty()) {
+            tree.dirs.insert(ancestor.to_path_buf());
+        }
+        Ok(())
+    }
+
+    fn remove_dir(&self, dir: &Path) -> io::Result<()> {
+        let mut tree = self.tree.lock();
+        if !tree.dirs.contains(dir) {
+            return Err(not_found(dir));
+        }
+        if tree
+            .files
+            .keys()
+            .chain(&tree.dirs)
+            .any(|path| path.parent() == Some(dir))
+        {
+            return Err(not_empty(dir));
+        }
+        tree.dirs.remove(dir);
+        Ok(())
+    }
+
+    fn file_size(&self, path: &Path) -> io::Result<u64> {
+        let file = self.tree.lock().file(path)?;
+        let size = file.read().len() as u64;
+        Ok(size)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        let tree = self.tree.lock();
+        tree.files.contains_key(path) || tree.dirs.contains(path)
+    }
+
+    fn sync_file(&self, path: &Path, _method: SyncMethod) -> io::Result<()> {
+        self.tree.lock().file(path).map(|_| ())
+    }
+
+    fn sync_dir(&self, _dir: &Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn try_lock(&self, path: &Path) -> io::Result<Option<FileLock>> {
+        let mut tree = self.tree.lock();
+        tree.check_parent(path)?;
+        tree.files.entry(path.to_path_buf()).or_default();
+        if !tree.locks.insert(path.to_path_buf()) {
+            return Ok(None);
+        }
+        Ok(Some(Box::new(MemoryLock {
+            tree: Arc::clone(&self.tree),
+            path: path.to_path_buf(),
+        })))
+    }
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("{:?} not found", path))
+}
+
+pub(crate) fn not_empty(dir: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::DirectoryNotEmpty,
+        format!("{:?} is not empty", dir),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_file_system() {
+        let env = Env::memory();
+        let dir = Path::new("/db/wal");
+        assert!(env.create(&dir.join("log"), false).is_err());
+        env.create_dir_all(dir).unwrap();
+        assert!(env.exists(Path::new("/db")));
+
+        let mut log = env.open_append(&dir.join("log")).unwrap();
+        log.write_all(b"hello").unwrap();
+        let mut reader = env.open_read(&dir.join("log"), false).unwrap();
+        assert_eq!(reader.read_at(1, 3).unwrap(), b"ell");
+        assert_eq!(
+            reader.read_at(3, 3).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+
+        // Handles follow their file through a rename
+        env.rename(&dir.join("log"), &dir.join("segment")).unwrap();
+        log.write_all(b" world").unwrap();
+        assert_eq!(env.read(&dir.join("segment")).unwrap(), b"hello world");
+        assert_eq!(env.file_size(&dir.join("segment")).unwrap(), 11);
+        assert!(!env.exists(&dir.join("log")));
+        assert_eq!(env.list(dir).unwrap(), [dir.join("segment")]);
+        assert_eq!(env.list(Path::new("/db")).unwrap(), [dir.to_path_buf()]);
+        env.remove_file(&dir.join("segment")).unwrap();
+        assert_eq!(
+            env.remove_file(&dir.join("segment")).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+        assert_eq!(
+            env.remove_dir(Path::new("/db")).unwrap_err().kind(),
+            io::ErrorKind::DirectoryNotEmpty
+        );
+        env.remove_dir(dir).unwrap();
+        assert!(!env.exists(dir));
+
+        let lock = env.try_lock(Path::new("/db/LOCK")).unwrap();
+        assert!(lock.is_some());
+        assert!(env.try_lock(Path::new("/db/LOCK")).unwrap().is_none());
+        drop(lock);
+        assert!(env.try_lock(Path::new("/db/LOCK")).unwrap().is_some());
+
+        assert!(!env.is_local());
+        assert!(env.require_local("backups").is_err());
+        assert_eq!(format!("{:?}", Env::default()), "Env(\"oblivion.local\")");
+    }
+}
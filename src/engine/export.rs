@@ -0,0 +1,255 @@
+//! OBLIVION - Export
+//! Dumps live key-value pairs as JSON lines or CSV.
+//!
+//! ## Formats
+//! ```text
+//! ndjson: {"key":"user:1","value":"alice","ttl_ms":5000}
+//! csv:    key,value,ttl_ms,encoding
+//!         user:1,alice,5000,utf8
+//! ```
+//! `ttl_ms` is the remaining TTL at export time, omitted (or left
+//! empty) for keys without one.
+//!
+//! ## Binary Data
+//! Keys and values are written as text when both are valid UTF-8, with
+//! JSON string escaping or RFC 4180 quoting. Otherwise both are written
+//! in standard base64 and the row is marked `"encoding":"base64"` (the
+//! `encoding` column in CSV), so every row round-trips exactly.
+
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use crate::error::Result;
+
+use super::options::ReadOptions;
+use super::version::ReadState;
+
+/// Rows read from the tree per page while exporting.
+const EXPORT_PAGE_SIZE: usize = 1024;
+
+/// Output format of [`Oblivion::export`](super::Oblivion::export).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// One JSON object per line.
+    #[default]
+    Ndjson,
+    /// Comma-separated values with a header row.
+    Csv,
+}
+
+impl ExportFormat {
+    /// Returns the name parsed by `FromStr`.
+    pub fn name(self) -> &'static str {
+        match self {
+            ExportFormat::Ndjson => "ndjson",
+            ExportFormat::Csv => "csv",
+        }
+    }
+
+    /// Write the lines that precede the first row, if any.
+    pub(crate) fn write_header<W: Write>(self, out: &mut W) -> io::Result<()> {
+        match self {
+            ExportFormat::Ndjson => Ok(()),
+            ExportFormat::Csv => out.write_all(b"key,value,ttl_ms,encoding\n"),
+        }
+    }
+
+    /// Append one row to `out`.
+    pub(crate) fn write_row(
+        self,
+        out: &mut Vec<u8>,
+        key: &[u8],
+        value: &[u8],
+        ttl_ms: Option<u64>,
+    ) {
+        let (key, value, encoding) = encode_pair(key, value);
+        match self {
+            ExportFormat::Ndjson => {
+                out.extend_from_slice(b"{\"key\":");
+                json_string(out, &key);
+                out.extend_from_slice(b",\"value\":");
+                json_string(out, &value);
+                if let Some(ttl) = ttl_ms {
+                    out.extend_from_slice(format!(",\"ttl_ms\":{}", ttl).as_bytes());
+                }
+                if encoding != "utf8" {
+                    out.extend_from_slice(format!(",\"encoding\":\"{}\"", encoding).as_bytes());
+                }
+                out.extend_from_slice(b"}\n");
+            }
+            ExportFormat::Csv => {
+                csv_field(out, &key);
+                out.push(b',');
+                csv_field(out, &value);
+                out.push(b',');
+                if let Some(ttl) = ttl_ms {
+                    out.extend_from_slice(ttl.to_string().as_bytes());
+                }
+                out.push(b',');
+                out.extend_from_slice(encoding.as_bytes());
+                out.push(b'\n');
+            }
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "ndjson" | "jsonl" => Ok(ExportFormat::Ndjson),
+            "csv" => Ok(ExportFormat::Csv),
+            other => Err(format!("unknown export format '{}'", other)),
+        }
+    }
+}
+
+/// A row as text if both key and value are UTF-8, else both in
+/// base64, with the encoding's name (`utf8` or `base64`).
+pub(crate) fn encode_pair(key: &[u8], value: &[u8]) -> (String, String, &'static str) {
+    let text = std::str::from_utf8(key)
+        .and_then(|key| Ok((key, std::str::from_utf8(value)?)))
+        .ok();
+    match text {
+        Some((key, value)) => (key.to_string(), value.to_string(), "utf8"),
+        None => (base64(key), base64(value), "base64"),
+    }
+}
+
+/// Write the live rows `opts` selects to `out`, page by page from a
+/// snapshot so memory use stays bounded. Returns the number of rows.
+pub(crate) fn export_rows<W: Write>(
+    state: &ReadState,
+    out: &mut W,
+    format: ExportFormat,
+    mut opts: ReadOptions,
+) -> Result<u64> {
+    if opts.snapshot.is_none() {
+        opts.snapshot = Some(state.snapshot());
+    }
+    state.metrics.record_scan();
+    format.write_header(out)?;
+    let mut rows = 0;
+    let mut buf = Vec::new();
+    loop {
+        let (page, resume) = state.scan_page(&opts, EXPORT_PAGE_SIZE)?;
+        buf.clear();
+        for (key, value) in &page {
+            format.write_row(&mut buf, key, value, state.ttl(key));
+        }
+        out.write_all(&buf)?;
+        rows += page.len() as u64;
+        match resume {
+            Some(resume) => opts.lower_bound = Some(resume),
+            None => break,
+        }
+    }
+    out.flush()?;
+    Ok(rows)
+}
+
+/// Append `s` as a quoted JSON string.
+fn json_string(out: &mut Vec<u8>, s: &str) {
+    out.push(b'"');
+    for c in s.chars() {
+        match c {
+            '"' => out.extend_from_slice(b"\\\""),
+            '\\' => out.extend_from_slice(b"\\\\"),
+            '\n' => out.extend_from_slice(b"\\n"),
+            '\r' => out.extend_from_slice(b"\\r"),
+            '\t' => out.extend_from_slice(b"\\t"),
+            c if (c as u32) < 0x20 => {
+                out.extend_from_slice(format!("\\u{:04x}", c as u32).as_bytes());
+            }
+            c => {
+                let mut buf = [0; 4];
+                out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            }
+        }
+    }
+    out.push(b'"');
+}
+
+/// Append `s` as a CSV field, quoted if it contains a delimiter, quote
+/// or line break.
+fn csv_field(out: &mut Vec<u8>, s: &str) {
+    if s.contains([',', '"', '\n', '\r']) {
+        out.push(b'"');
+        out.extend_from_slice(s.replace('"', "\"\"").as_bytes());
+        out.push(b'"');
+    } else {
+        out.extend_from_slice(s.as_bytes());
+    }
+}
+
+/// Standard base64 with padding.
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(format: ExportFormat, key: &[u8], value: &[u8], ttl: Option<u64>) -> String {
+        let mut out = Vec::new();
+        format.write_row(&mut out, key, value, ttl);
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_ndjson_rows() {
+        assert_eq!(
+            row(ExportFormat::Ndjson, b"user:1", b"say \"hi\"\n", Some(5)),
+            "{\"key\":\"user:1\",\"value\":\"say \\\"hi\\\"\\n\",\"ttl_ms\":5}\n"
+        );
+        assert_eq!(
+            row(ExportFormat::Ndjson, b"k", b"\x00\xff", None),
+            "{\"key\":\"aw==\",\"value\":\"AP8=\",\"encoding\":\"base64\"}\n"
+        );
+    }
+
+    #[test]
+    fn test_csv_rows() {
+        assert_eq!(row(ExportFormat::Csv, b"a", b"b", None), "a,b,,utf8\n");
+        assert_eq!(
+            row(ExportFormat::Csv, b"a,b", b"x\"y", Some(7)),
+            "\"a,b\",\"x\"\"y\",7,utf8\n"
+        );
+        assert_eq!(
+            row(ExportFormat::Csv, b"\xfe", b"foo", None),
+            "/g==,Zm9v,,base64\n"
+        );
+    }
+
+    #[test]
+    fn test_format_parse() {
+        assert_eq!("csv".parse::<ExportFormat>(), Ok(ExportFormat::Csv));
+        assert_eq!("jsonl".parse::<ExportFormat>(), Ok(ExportFormat::Ndjson));
+        assert!("xml".parse::<ExportFormat>().is_err());
+        assert_eq!(ExportFormat::Ndjson.to_string(), "ndjson");
+    }
+}
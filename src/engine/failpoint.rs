@@ -0,0 +1,256 @@
+//! OBLIVION - Fail Points
+//! Named points between the critical steps of the write path, flushes,
+//! compactions and manifest updates, where tests can make the engine
+//! fail or crash at an exact moment.
+//!
+//! Unlike [`fault`](super::fault), which counts file system operations,
+//! a fail point names a step of the engine's own protocol, so a test
+//! can say "power fails after the flush wrote its table but before the
+//! manifest lists it" without knowing how many syncs come first.
+//!
+//! Fail points are compiled in only with the `failpoints` feature; the
+//! `fail_point!` hooks expand to nothing otherwise. Each engine reads
+//! them from [`Config::failpoints`](crate::config::Config), so tests
+//! running side by side do not trip each other's. Faults are armed for
+//! the `n`th hit of a point from now:
+//!
+//! - [`fail`](FailPoints::fail): the step fails with an I/O error.
+//! - [`run_at`](FailPoints::run_at): a callback runs, then the step
+//!   goes on. Passing one that cuts the power of a
+//!   [`FaultFileSystem`](super::fault::FaultFileSystem) crashes the
+//!   engine right there.
+//!
+//! ## Example
+//! ```
+//! # #[cfg(feature = "failpoints")]
+//! # {
+//! use oblivion::config::Config;
+//! use oblivion::engine::env::Env;
+//! use oblivion::engine::failpoint::{FailPoint, FailPoints};
+//! use oblivion::engine::fault::{CrashMode, FaultFileSystem};
+//! use oblivion::engine::Oblivion;
+//!
+//! let fs = FaultFileSystem::new();
+//! let failpoints = FailPoints::new();
+//! let config = Config::new("/db")
+//!     .with_env(Env::new(fs.clone()))
+//!     .with_failpoints(failpoints.clone());
+//! let engine = Oblivion::open(config.clone()).unwrap();
+//! engine.put("k", "v").unwrap();
+//!
+//! // Lose power once the flush has written its table
+//! let power = fs.clone();
+//! failpoints.run_at(FailPoint::MidFlush, 1, move || power.crash());
+//! assert!(engine.flush().is_err());
+//! drop(engine);
+//!
+//! fs.restart(CrashMode::DropUnsynced);
+//! let engine = Oblivion::open(config).unwrap();
+//! assert_eq!(engine.get("k"), Some(b"v".to_vec()));
+//! # }
+//! ```
+
+use std::fmt;
+
+/// A point in the engine where faults can be injected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FailPoint {
+    /// A write is in the WAL but not yet in the MemTable.
+    AfterWalAppend,
+    /// A flush has written its SSTables, which the manifest does not
+    /// list yet.
+    MidFlush,
+    /// A compaction has written its output, which the manifest does not
+    /// list yet; the inputs are still live.
+    MidCompaction,
+    /// A new manifest is written and synced under its temporary name,
+    /// but not yet renamed into place.
+    MidManifestWrite,
+}
+
+impl FailPoint {
+    /// Returns a short name for logs and errors.
+    pub fn name(self) -> &'static str {
+        match self {
+            FailPoint::AfterWalAppend => "after-wal-append",
+            FailPoint::MidFlush => "mid-flush",
+            FailPoint::MidCompaction => "mid-compaction",
+            FailPoint::MidManifestWrite => "mid-manifest-write",
+        }
+    }
+}
+
+impl fmt::Display for FailPoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Hit the fail point [`FailPoint::$point`] of the engine configured by
+/// `$config`, returning the error it is armed with, if any. Expands to
+/// nothing without the `failpoints` feature.
+macro_rules! fail_point {
+    ($config:expr, $point:ident) => {
+        #[cfg(feature = "failpoints")]
+        $config
+            .failpoints
+            .hit($crate::engine::failpoint::FailPoint::$point)?;
+    };
+}
+
+pub(crate) use fail_point;
+
+#[cfg(feature = "failpoints")]
+pub use self::points::FailPoints;
+
+#[cfg(feature = "failpoints")]
+mod points {
+    use std::collections::BTreeMap;
+    use std::fmt;
+    use std::io;
+    use std::sync::Arc;
+
+    use parking_lot::Mutex;
+
+    use super::FailPoint;
+    use crate::error::Result;
+
+    /// What an armed fail point does when its countdown runs out.
+    #[derive(Clone)]
+    enum Action {
+        Fail,
+        Run(Arc<dyn Fn() + Send + Sync>),
+    }
+
+    /// A fault waiting for the `countdown`th hit of `point`.
+    struct Armed {
+        point: FailPoint,
+        countdown: u64,
+        action: Action,
+    }
+
+    #[derive(Default)]
+    struct State {
+        armed: Vec<Armed>,
+        hits: BTreeMap<FailPoint, u64>,
+    }
+
+    /// The fail points of an engine; clones share them.
+    #[derive(Clone, Default)]
+    pub struct FailPoints {
+        state: Arc<Mutex<State>>,
+    }
+
+    impl FailPoints {
+        /// Create fail points with nothing armed.
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Make the `n`th hit of `point` from now (1 for the next) fail
+        /// with an I/O error; later hits pass.
+        pub fn fail(&self, point: FailPoint, n: u64) {
+            self.arm(point, n, Action::Fail);
+        }
+
+        /// Run `callback` at the `n`th hit of `point` from now, then let
+        /// the step go on.
+        pub fn run_at<F>(&self, point: FailPoint, n: u64, callback: F)
+        where
+            F: Fn() + Send + Sync + 'static,
+        {
+            self.arm(point, n, Action::Run(Arc::new(callback)));
+        }
+
+        /// Disarm every fault. Hit counts are kept.
+        pub fn clear(&self) {
+            self.state.lock().armed.clear();
+        }
+
+        /// Returns how often `point` was reached.
+        pub fn hits(&self, point: FailPoint) -> u64 {
+            self.state.lock().hits.get(&point).copied().unwrap_or(0)
+        }
+
+        fn arm(&self, point: FailPoint, n: u64, action: Action) {
+            self.state.lock().armed.push(Armed {
+                point,
+                countdown: n.max(1),
+                action,
+            });
+        }
+
+        /// Record a hit of `point` and fire the faults armed for it.
+        pub(crate) fn hit(&self, point: FailPoint) -> Result<()> {
+            let mut state = self.state.lock();
+            *state.hits.entry(point).or_default() += 1;
+            let mut fired = Vec::new();
+            state.armed.retain_mut(|armed| {
+                if armed.point != point {
+                    return true;
+                }
+                armed.countdown -= 1;
+                if armed.countdown > 0 {
+                    return true;
+                }
+                fired.push(armed.action.clone());
+                false
+            });
+            // Callbacks may touch the engine's file system; run them unlocked
+            drop(state);
+            let mut fail = false;
+            for action in fired {
+                match action {
+                    Action::Fail => fail = true,
+                    Action::Run(callback) => callback(),
+                }
+            }
+            if fail {
+                log::warn!("Fail point {} triggered", point);
+                return Err(io::Error::other(format!("fail point {} triggered", point)).into());
+            }
+            Ok(())
+        }
+    }
+
+    impl fmt::Debug for FailPoints {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let state = self.state.lock();
+            f.debug_struct("FailPoints")
+                .field("armed", &state.armed.len())
+                .field("hits", &state.hits)
+                .finish()
+        }
+    }
+}
+
+#[cfg(all(test, feature = "failpoints"))]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_faults_fire_at_their_hit() {
+        let failpoints = FailPoints::new();
+        failpoints.fail(FailPoint::MidFlush, 2);
+        let calls = Arc::new(AtomicU64::new(0));
+        let counter = Arc::clone(&calls);
+        failpoints.run_at(FailPoint::AfterWalAppend, 1, move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+
+        assert!(failpoints.hit(FailPoint::MidFlush).is_ok());
+        assert!(failpoints.hit(FailPoint::MidFlush).is_err());
+        assert!(failpoints.hit(FailPoint::MidFlush).is_ok());
+        assert!(failpoints.hit(FailPoint::AfterWalAppend).is_ok());
+        assert!(failpoints.hit(FailPoint::AfterWalAppend).is_ok());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(failpoints.hits(FailPoint::MidFlush), 3);
+        assert_eq!(failpoints.hits(FailPoint::MidCompaction), 0);
+
+        failpoints.fail(FailPoint::MidCompaction, 1);
+        failpoints.clear();
+        assert!(failpoints.hit(FailPoint::MidCompaction).is_ok());
+    }
+}
@@ -0,0 +1,822 @@
+//! OBLIVION - Fault Injection
+//! A file system that fails chosen operations and loses power on
+//! demand, and a harness that cuts power at every point of a workload
+//! and checks what recovery makes of each crash.
+//!
+//! [`FaultFileSystem`] keeps files in memory like `MemoryFileSystem`,
+//! but tracks what is durable the way a disk does: the contents of a
+//! file up to its last sync, and the entries of a directory (files
+//! created, renamed into or deleted from it) as of its last
+//! `sync_dir`. Directories themselves, created or removed, are always
+//! durable. With
+//! [`with_journaled_metadata`](FaultFileSystem::with_journaled_metadata),
+//! any sync also makes every earlier creation, rename and deletion
+//! durable, as file systems journaling metadata in order (ext4's
+//! `data=ordered`) mostly do. Faults are armed for the `n`th operation
+//! of a [`FaultOp`] kind from now:
+//!
+//! - [`fail`](FaultFileSystem::fail): the operation fails with an
+//!   error and does nothing; later ones work.
+//!   [`fail_with`](FaultFileSystem::fail_with) picks the error kind.
+//! - [`tear`](FaultFileSystem::tear): the write stores the first half
+//!   of its bytes, then fails.
+//! - [`power_cut`](FaultFileSystem::power_cut): the operation and every
+//!   one after it fail, reads included, as if the machine had died.
+//!
+//! [`set_full`](FaultFileSystem::set_full) fills the disk: writes and
+//! creations fail with `StorageFull` until space is freed again.
+//!
+//! [`restart`](FaultFileSystem::restart) brings the machine back up:
+//! what was not durable is lost, as the [`CrashMode`] says, and locks
+//! are released. A sync of a file written before its rename is not
+//! enough to keep the rename; only syncing the directory is.
+//!
+//! ## Crash Testing
+//! [`CrashHarness`] runs a workload against a fresh file system once for
+//! every operation of the chosen kind, cutting power at that operation,
+//! then restarts, reopens the database and checks these invariants:
+//!
+//! 1. The database opens.
+//! 2. `verify_integrity` finds no problem.
+//! 3. The caller's check passes, such as every write acknowledged
+//!    with `sync_writes` being there.
+//!
+//! It stops once the workload completes before power is cut. Run it
+//! with `background_threads` at 0 (the default), and with
+//! `wal_sync_interval_ms` at 0 if `sync_writes` is off, so each run
+//! performs the same operations in the same order.
+//!
+//! ## Example
+//! ```no_run
+//! use oblivion::config::Config;
+//! use oblivion::engine::fault::{CrashHarness, FaultOp};
+//! use oblivion::error::OblivionError;
+//!
+//! let harness = CrashHarness::new(Config::new("/db")).cut_at(FaultOp::Sync);
+//! let crash_points = harness
+//!     .run(
+//!         |engine| engine.put(b"k".to_vec(), b"v".to_vec()),
+//!         |engine, _point| match engine.get(b"k") {
+//!             Some(v) if v != b"v" => Err(OblivionError::Corruption("wrong value".into())),
+//!             _ => Ok(()),
+//!         },
+//!     )
+//!     .unwrap();
+//! println!("survived {} crashes", crash_points);
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use crate::config::Config;
+use crate::error::{OblivionError, Result};
+
+use super::env::{not_empty, Env, FileLock, FileSystem, RandomAccessFile, WritableFile};
+use super::filter::hash64;
+use super::io::SyncMethod;
+use super::Oblivion;
+
+/// Name of the fault injection file system.
+pub const FAULT: &str = "oblivion.fault";
+
+/// Kinds of operations faults are armed for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultOp {
+    /// A write through a file handle.
+    Write,
+    /// A file sync, through a handle or `sync_file`.
+    Sync,
+    /// A directory sync.
+    SyncDir,
+    /// A rename.
+    Rename,
+    /// A file or directory deletion.
+    Remove,
+    /// A file created, truncated or opened for appending.
+    Create,
+    /// Any of the above.
+    Any,
+}
+
+impl FaultOp {
+    fn matches(self, op: FaultOp) -> bool {
+        self == FaultOp::Any || self == op
+    }
+}
+
+/// What a power cut loses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrashMode {
+    /// Everything written since the last sync of its file or directory.
+    DropUnsynced,
+    /// Entries as for `DropUnsynced`, but each file keeps a prefix of
+    /// its unsynced bytes, of a length picked from `seed`, as a disk
+    /// tearing its last writes would.
+    TornWrites {
+        /// Seed of the lengths kept.
+        seed: u64,
+    },
+}
+
+/// What an armed fault does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FaultKind {
+    Fail(io::ErrorKind),
+    Tear,
+    PowerCut,
+}
+
+/// A fault waiting for its operation.
+#[derive(Debug)]
+struct Armed {
+    op: FaultOp,
+    /// Matching operations still to go before it fires.
+    remaining: u64,
+    kind: FaultKind,
+}
+
+/// A file: its contents, and how many of its bytes are durable.
+#[derive(Default)]
+struct Node {
+    data: Vec<u8>,
+    synced: usize,
+}
+
+type NodeRef = Arc<Mutex<Node>>;
+
+#[derive(Default)]
+struct Disk {
+    /// Files by path, as the running system sees them.
+    files: BTreeMap<PathBuf, NodeRef>,
+    /// Files by path, as they would be found after a power cut.
+    durable: BTreeMap<PathBuf, NodeRef>,
+    dirs: BTreeSet<PathBuf>,
+    locks: BTreeSet<PathBuf>,
+    armed: Vec<Armed>,
+    /// Set from a power cut until the restart.
+    down: bool,
+    /// Bumped by every restart, so locks from before it are not
+    /// released by their guards.
+    boot: u64,
+    /// Faults fired so far.
+    fired: u64,
+    /// Any sync makes every entry durable.
+    journaled: bool,
+    /// Writes and creations fail for lack of space.
+    full: bool,
+}
+
+impl Disk {
+    /// Count an operation against the armed faults, and fail it if the
+    /// power is out or a fault fires. A tear is returned for the caller
+    /// to carry out.
+    fn operation(&mut self, op: FaultOp) -> io::Result<Option<FaultKind>> {
+        if self.down {
+            return Err(power_is_out());
+        }
+        if self.full && matches!(op, FaultOp::Write | FaultOp::Create) {
+            return Err(io::Error::new(
+                io::ErrorKind::StorageFull,
+                "no space left on device",
+            ));
+        }
+        let mut fired = None;
+        self.armed.retain_mut(|armed| {
+            if !armed.op.matches(op) {
+                return true;
+            }
+            armed.remaining -= 1;
+            if armed.remaining > 0 {
+                return true;
+            }
+            fired = fired.or(Some(armed.kind));
+            false
+        });
+        match fired {
+            None => Ok(None),
+            Some(kind) => {
+                self.fired += 1;
+                match kind {
+                    FaultKind::Fail(kind) => Err(injected(op, kind)),
+                    FaultKind::PowerCut => {
+                        self.down = true;
+                        Err(power_is_out())
+                    }
+                    FaultKind::Tear => Ok(Some(FaultKind::Tear)),
+                }
+            }
+        }
+    }
+
+    /// Fail if the power is out.
+    fn check_power(&self) -> io::Result<()> {
+        if self.down {
+            return Err(power_is_out());
+        }
+        Ok(())
+    }
+
+    /// Make the contents of `node` durable, and with journaled
+    /// metadata every entry.
+    fn sync(&mut self, node: &NodeRef) {
+        let mut node = node.lock();
+        node.synced = node.data.len();
+        if self.journaled {
+            self.durable = self.files.clone();
+        }
+    }
+
+    fn file(&self, path: &Path) -> io::Result<NodeRef> {
+        self.files.get(path).cloned().ok_or_else(|| not_found(path))
+    }
+
+    /// Fail unless the directory `path` would be created in exists.
+    fn check_parent(&self, path: &Path) -> io::Result<()> {
+        match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() && !self.dirs.contains(parent) => {
+                Err(not_found(parent))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// An in-memory file system that injects faults and simulates power
+/// cuts. Clones share their files, so a test can keep one to arm faults
+/// and restart while the engine works on another.
+#[derive(Clone, Default)]
+pub struct FaultFileSystem {
+    disk: Arc<Mutex<Disk>>,
+}
+
+impl FaultFileSystem {
+    /// Create an empty file system.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Make every sync commit the creations, renames and deletions
+    /// before it, not only a directory sync.
+    pub fn with_journaled_metadata(self) -> Self {
+        self.disk.lock().journaled = true;
+        self
+    }
+
+    /// Make the `n`th operation of kind `op` from now fail (`n` counts
+    /// from 1).
+    pub fn fail(&self, op: FaultOp, n: u64) {
+        self.fail_with(op, n, io::ErrorKind::Other);
+    }
+
+    /// Like [`fail`](Self::fail), with an error of the given kind, such
+    /// as `StorageFull` for a disk filling up under one operation.
+    pub fn fail_with(&self, op: FaultOp, n: u64, kind: io::ErrorKind) {
+        self.arm(op, n, FaultKind::Fail(kind));
+    }
+
+    /// Fill the disk up, or free space again: while full, every write
+    /// and creation fails with `StorageFull`.
+    pub fn set_full(&self, full: bool) {
+        self.disk.lock().full = full;
+    }
+
+    /// Make the `n`th write from now store half its bytes, then fail.
+    pub fn tear(&self, n: u64) {
+        self.arm(FaultOp::Write, n, FaultKind::Tear);
+    }
+
+    /// Cut the power at the `n`th operation of kind `op` from now.
+    pub fn power_cut(&self, op: FaultOp, n: u64) {
+        self.arm(op, n, FaultKind::PowerCut);
+    }
+
+    fn arm(&self, op: FaultOp, n: u64, kind: FaultKind) {
+        assert!(n > 0, "operations are counted from 1");
+        self.disk.lock().armed.push(Armed {
+            op,
+            remaining: n,
+            kind,
+        });
+    }
+
+    /// Disarm every fault that has not fired.
+    pub fn clear_faults(&self) {
+        self.disk.lock().armed.clear();
+    }
+
+    /// Returns the number of faults fired so far.
+    pub fn faults_fired(&self) -> u64 {
+        self.disk.lock().fired
+    }
+
+    /// Returns true if the power is out.
+    pub fn is_down(&self) -> bool {
+        self.disk.lock().down
+    }
+
+    /// Cut the power now.
+    pub fn crash(&self) {
+        self.disk.lock().down = true;
+    }
+
+    /// Bring the file system back after a power cut (cutting it first
+    /// if it is still up): keep only what was durable, as `mode` says,
+    /// release every lock and disarm every fault.
+    ///
+    /// Handles opened before the restart see the old files; close them
+    /// (drop the engine) first.
+    pub fn restart(&self, mode: CrashMode) {
+        let mut disk = self.disk.lock();
+        let mut files = BTreeMap::new();
+        for (path, node) in &disk.durable {
+            let old = node.lock();
+            let mut keep = old.synced;
+            if let CrashMode::TornWrites { seed } = mode {
+                let unsynced = (old.data.len() - old.synced) as u64;
+                let pick = hash64(path.as_os_str().as_encoded_bytes(), seed);
+                keep += (pick % (unsynced + 1)) as usize;
+            }
+            let data = old.data[..keep].to_vec();
+            let synced = data.len();
+            files.insert(path.clone(), Arc::new(Mutex::new(Node { data, synced })));
+        }
+        disk.durable = files.clone();
+        disk.files = files;
+        disk.locks.clear();
+        disk.armed.clear();
+        disk.down = false;
+        disk.boot += 1;
+    }
+}
+
+/// A handle on a file of a [`FaultFileSystem`].
+struct FaultHandle {
+    disk: Arc<Mutex<Disk>>,
+    node: NodeRef,
+}
+
+impl RandomAccessFile for FaultHandle {
+    fn read_at(&mut self, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        self.disk.lock().check_power()?;
+        let node = self.node.lock();
+        let start = usize::try_from(offset).unwrap_or(usize::MAX);
+        match node.data.get(start..start.saturating_add(len)) {
+            Some(bytes) => Ok(bytes.to_vec()),
+            None => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "failed to fill whole buffer",
+            )),
+        }
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        self.disk.lock().check_power()?;
+        let size = self.node.lock().data.len() as u64;
+        Ok(size)
+    }
+}
+
+impl Write for FaultHandle {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let fault = self.disk.lock().operation(FaultOp::Write)?;
+        let mut node = self.node.lock();
+        if fault == Some(FaultKind::Tear) {
+            node.data.extend_from_slice(&buf[..buf.len() / 2]);
+            return Err(injected(FaultOp::Write, io::ErrorKind::Other));
+        }
+        node.data.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.disk.lock().check_power()
+    }
+}
+
+impl WritableFile for FaultHandle {
+    fn sync(&mut self, _method: SyncMethod) -> io::Result<()> {
+        let mut disk = self.disk.lock();
+        disk.operation(FaultOp::Sync)?;
+        disk.sync(&self.node);
+        Ok(())
+    }
+}
+
+/// Lock on a file of a [`FaultFileSystem`].
+struct FaultLock {
+    disk: Arc<Mutex<Disk>>,
+    path: PathBuf,
+    boot: u64,
+}
+
+impl Drop for FaultLock {
+    fn drop(&mut self) {
+        let mut disk = self.disk.lock();
+        if disk.boot == self.boot {
+            disk.locks.remove(&self.path);
+        }
+    }
+}
+
+impl FaultFileSystem {
+    fn handle(&self, node: NodeRef) -> FaultHandle {
+        FaultHandle {
+            disk: Arc::clone(&self.disk),
+            node,
+        }
+    }
+}
+
+impl FileSystem for FaultFileSystem {
+    fn name(&self) -> &str {
+        FAULT
+    }
+
+    fn open_read(&self, path: &Path, _direct: bool) -> io::Result<Box<dyn RandomAccessFile>> {
+        let disk = self.disk.lock();
+        disk.check_power()?;
+        let node = disk.file(path)?;
+        Ok(Box::new(self.handle(node)))
+    }
+
+    fn create(&self, path: &Path, _direct: bool) -> io::Result<Box<dyn WritableFile>> {
+        let mut disk = self.disk.lock();
+        disk.operation(FaultOp::Create)?;
+        disk.check_parent(path)?;
+        let node = NodeRef::default();
+        disk.files.insert(path.to_path_buf(), Arc::clone(&node));
+        Ok(Box::new(self.handle(node)))
+    }
+
+    fn open_append(&self, path: &Path) -> io::Result<Box<dyn WritableFile>> {
+        let mut disk = self.disk.lock();
+        disk.operation(FaultOp::Create)?;
+        disk.check_parent(path)?;
+        let node = Arc::clone(disk.files.entry(path.to_path_buf()).or_default());
+        Ok(Box::new(self.handle(node)))
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let disk = self.disk.lock();
+        disk.check_power()?;
+        let data = disk.file(path)?.lock().data.clone();
+        Ok(data)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let mut disk = self.disk.lock();
+        disk.operation(FaultOp::Rename)?;
+        disk.check_parent(to)?;
+        let node = disk.files.remove(from).ok_or_else(|| not_found(from))?;
+        disk.files.insert(to.to_path_buf(), node);
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        let mut disk = self.disk.lock();
+        disk.operation(FaultOp::Remove)?;
+        match disk.files.remove(path) {
+            Some(_) => Ok(()),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn list(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        let disk = self.disk.lock();
+        disk.check_power()?;
+        if !disk.dirs.contains(dir) {
+            return Err(not_found(dir));
+        }
+        Ok(disk
+            .files
+            .keys()
+            .chain(&disk.dirs)
+            .filter(|path| path.parent() == Some(dir))
+            .cloned()
+            .collect())
+    }
+
+    fn create_dir_all(&self, dir: &Path) -> io::Result<()> {
+        let mut disk = self.disk.lock();
+        disk.check_power()?;
+        for ancestor in dir.ancestors().filter(|a| !a.as_os_str().is_empty()) {
+            disk.dirs.insert(ancestor.to_path_buf());
+        }
+        Ok(())
+    }
+
+    fn remove_dir(&self, dir: &Path) -> io::Result<()> {
+        let mut disk = self.disk.lock();
+        disk.operation(FaultOp::Remove)?;
+        if !disk.dirs.contains(dir) {
+            return Err(not_found(dir));
+        }
+        if disk
+            .files
+            .keys()
+            .chain(&disk.dirs)
+            .any(|path| path.parent() == Some(dir))
+        {
+            return Err(not_empty(dir));
+        }
+        disk.dirs.remove(dir);
+        Ok(())
+    }
+
+    fn file_size(&self, path: &Path) -> io::Result<u64> {
+        let disk = self.disk.lock();
+        disk.check_power()?;
+        let size = disk.file(path)?.lock().data.len() as u64;
+        Ok(size)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        let disk = self.disk.lock();
+        !disk.down && (disk.files.contains_key(path) || disk.dirs.contains(path))
+    }
+
+    fn sync_file(&self, path: &Path, _method: SyncMethod) -> io::Result<()> {
+        let mut disk = self.disk.lock();
+        disk.operation(FaultOp::Sync)?;
+        let node = disk.file(path)?;
+        disk.sync(&node);
+        Ok(())
+    }
+
+    fn sync_dir(&self, dir: &Path) -> io::Result<()> {
+        let mut disk = self.disk.lock();
+        disk.operation(FaultOp::SyncDir)?;
+        if !disk.dirs.contains(dir) {
+            return Err(not_found(dir));
+        }
+        let in_dir = |path: &PathBuf| path.parent() == Some(dir);
+        disk.durable.retain(|path, _| !in_dir(path));
+        let entries: Vec<(PathBuf, NodeRef)> = disk
+            .files
+            .iter()
+            .filter(|(path, _)| in_dir(path))
+            .map(|(path, node)| (path.clone(), Arc::clone(node)))
+            .collect();
+        disk.durable.extend(entries);
+        if disk.journaled {
+            disk.durable = disk.files.clone();
+        }
+        Ok(())
+    }
+
+    fn try_lock(&self, path: &Path) -> io::Result<Option<FileLock>> {
+        let mut disk = self.disk.lock();
+        disk.check_power()?;
+        disk.check_parent(path)?;
+        disk.files.entry(path.to_path_buf()).or_default();
+        if !disk.locks.insert(path.to_path_buf()) {
+            return Ok(None);
+        }
+        Ok(Some(Box::new(FaultLock {
+            disk: Arc::clone(&self.disk),
+            path: path.to_path_buf(),
+            boot: disk.boot,
+        })))
+    }
+}
+
+/// Runs a workload once per crash point and checks recovery from each;
+/// see the [module documentation](self).
+pub struct CrashHarness {
+    config: Config,
+    op: FaultOp,
+    mode: CrashMode,
+    journaled: bool,
+}
+
+impl CrashHarness {
+    /// Crash-test databases opened with `config`, whose `env` is
+    /// replaced by a fresh [`FaultFileSystem`] for every run. Power is
+    /// cut at every operation, and unsynced data is dropped.
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            op: FaultOp::Any,
+            mode: CrashMode::DropUnsynced,
+            journaled: false,
+        }
+    }
+
+    /// Only cut power at operations of kind `op`.
+    pub fn cut_at(mut self, op: FaultOp) -> Self {
+        self.op = op;
+        self
+    }
+
+    /// Lose what `mode` says at each power cut. A `TornWrites` seed is
+    /// combined with the crash point, so every run tears differently.
+    pub fn with_mode(mut self, mode: CrashMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Run on file systems with journaled metadata; see
+    /// [`FaultFileSystem::with_journaled_metadata`].
+    pub fn with_journaled_metadata(mut self) -> Self {
+        self.journaled = true;
+        self
+    }
+
+    /// Run `workload` with power cut at its first, second, ...
+    /// operation until it completes first, and after each cut check the
+    /// invariants and `verify`, which is passed the reopened engine and
+    /// the crash point. Errors of the workload are expected and
+    /// ignored.
+    ///
+    /// Returns the number of crash points checked, or the first broken
+    /// invariant, naming its crash point.
+    pub fn run<W, V>(&self, workload: W, verify: V) -> Result<u64>
+    where
+        W: Fn(&Oblivion) -> Result<()>,
+        V: Fn(&Oblivion, u64) -> Result<()>,
+    {
+        for point in 1.. {
+            let mut fs = FaultFileSystem::new();
+            if self.journaled {
+                fs = fs.with_journaled_metadata();
+            }
+            let config = self.config.clone().with_env(Env::new(fs.clone()));
+            fs.power_cut(self.op, point);
+            if let Ok(engine) = Oblivion::open(config.clone()) {
+                let _ = workload(&engine);
+            }
+            if !fs.is_down() {
+                return Ok(point - 1);
+            }
+
+            fs.restart(match self.mode {
+                CrashMode::TornWrites { seed } => CrashMode::TornWrites { seed: seed ^ point },
+                mode => mode,
+            });
+            let failed = |what: &str, e: OblivionError| {
+                OblivionError::RecoveryFailed(format!(
+                    "crash at {:?} #{}: {}: {}",
+                    self.op, point, what, e
+                ))
+            };
+            let engine = Oblivion::open(config).map_err(|e| failed("reopen failed", e))?;
+            let report = engine
+                .verify_integrity()
+                .map_err(|e| failed("integrity check failed", e))?;
+            if let Some(problem) = report.problems.first() {
+                let detail = format!(
+                    "{:?} at {:?}: {}",
+                    problem.kind, problem.path, problem.detail
+                );
+                return Err(failed(
+                    "integrity check failed",
+                    OblivionError::Corruption(detail),
+                ));
+            }
+            verify(&engine, point).map_err(|e| failed("check failed", e))?;
+        }
+        unreachable!("crash points are unbounded")
+    }
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("{:?} not found", path))
+}
+
+fn power_is_out() -> io::Error {
+    io::Error::other("injected power cut")
+}
+
+fn injected(op: FaultOp, kind: io::ErrorKind) -> io::Error {
+    io::Error::new(kind, format!("injected {:?} fault", op))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_power_cut_keeps_only_durable_state() {
+        let fs = FaultFileSystem::new();
+        let dir = Path::new("/db");
+        fs.create_dir_all(dir).unwrap();
+
+        // Synced contents of a synced entry survive; the rest is lost
+        let mut log = fs.create(&dir.join("log"), false).unwrap();
+        log.write_all(b"durable").unwrap();
+        log.sync(SyncMethod::Fsync).unwrap();
+        fs.sync_dir(dir).unwrap();
+        log.write_all(b" lost").unwrap();
+        let mut tmp = fs.create(&dir.join("table.tmp"), false).unwrap();
+        tmp.write_all(b"table").unwrap();
+        tmp.sync(SyncMethod::Fsync).unwrap();
+        fs.rename(&dir.join("table.tmp"), &dir.join("table"))
+            .unwrap();
+        drop((log, tmp));
+
+        fs.power_cut(FaultOp::Any, 1);
+        assert!(fs.remove_file(&dir.join("log")).is_err());
+        assert!(fs.read(&dir.join("log")).is_err());
+        fs.restart(CrashMode::DropUnsynced);
+        assert_eq!(fs.read(&dir.join("log")).unwrap(), b"durable");
+        assert!(!fs.exists(&dir.join("table")), "rename was never synced");
+
+        // A torn crash keeps part of the unsynced tail
+        let mut log = fs.open_append(&dir.join("log")).unwrap();
+        log.write_all(b"0123456789").unwrap();
+        drop(log);
+        fs.restart(CrashMode::TornWrites { seed: 7 });
+        let data = fs.read(&dir.join("log")).unwrap();
+        assert!(data.starts_with(b"durable") && data.len() <= 17);
+        assert_eq!(&data[7..], &b"0123456789"[..data.len() - 7]);
+    }
+
+    #[test]
+    fn test_failures_and_tears() {
+        let fs = FaultFileSystem::new();
+        fs.create_dir_all(Path::new("/db")).unwrap();
+        let mut file = fs.create(Path::new("/db/f"), false).unwrap();
+        fs.fail(FaultOp::Sync, 2);
+        fs.tear(2);
+        file.write_all(b"ab").unwrap();
+        file.sync(SyncMethod::Fsync).unwrap();
+        assert!(file.write_all(b"cdef").is_err());
+        assert!(file.sync(SyncMethod::Fsync).is_err());
+        file.sync(SyncMethod::Fsync).unwrap();
+        assert_eq!(fs.read(Path::new("/db/f")).unwrap(), b"abcd");
+        assert_eq!(fs.faults_fired(), 2);
+        assert!(!fs.is_down());
+
+        let lock = fs.try_lock(Path::new("/db/LOCK")).unwrap();
+        assert!(lock.is_some());
+        fs.crash();
+        fs.restart(CrashMode::DropUnsynced);
+        assert!(fs.try_lock(Path::new("/db/LOCK")).unwrap().is_some());
+        drop(lock);
+    }
+
+    #[test]
+    fn test_full_disk() {
+        let fs = FaultFileSystem::new();
+        fs.create_dir_all(Path::new("/db")).unwrap();
+        let mut file = fs.create(Path::new("/db/f"), false).unwrap();
+        fs.set_full(true);
+        let err = file.write_all(b"ab").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::StorageFull);
+        assert!(fs.create(Path::new("/db/g"), false).is_err());
+        file.sync(SyncMethod::Fsync).unwrap();
+        fs.set_full(false);
+        file.write_all(b"ab").unwrap();
+
+        fs.fail_with(FaultOp::Create, 1, io::ErrorKind::StorageFull);
+        let err = fs.create(Path::new("/db/g"), false).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::StorageFull);
+        assert_eq!(fs.faults_fired(), 1);
+    }
+
+    #[test]
+    fn test_crash_harness_keeps_acknowledged_writes() {
+        let acknowledged = Mutex::new(0usize);
+        let key = |i: usize| format!("key_{:03}", i).into_bytes();
+        let mut config = Config::new("/db");
+        config.memtable_max_size = 2 * 1024;
+        config.compaction_threshold = 2;
+
+        let crash_points = CrashHarness::new(config)
+            .with_mode(CrashMode::TornWrites { seed: 42 })
+            .run(
+                |engine| {
+                    *acknowledged.lock() = 0;
+                    for i in 0..60 {
+                        engine.put(key(i), vec![b'v'; 100])?;
+                        *acknowledged.lock() = i + 1;
+                    }
+                    Ok(())
+                },
+                |engine, point| {
+                    // Every acknowledged write survives, and writes are
+                    // recovered in order
+                    let found = (0..60)
+                        .take_while(|&i| engine.get(key(i)).is_some())
+                        .count();
+                    let lost = (found..60).find(|&i| engine.get(key(i)).is_some());
+                    if found < *acknowledged.lock() || lost.is_some() {
+                        return Err(OblivionError::Corruption(format!(
+                            "point {}: {} of {} acknowledged writes, then {:?}",
+                            point,
+                            found,
+                            acknowledged.lock(),
+                            lost
+                        )));
+                    }
+                    Ok(())
+                },
+            )
+            .unwrap();
+        assert!(crash_points > 100, "{} crash points", crash_points);
+    }
+}
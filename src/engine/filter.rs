@@ -0,0 +1,261 @@
+//! OBLIVION - Filter Policies
+//! Pluggable membership filters consulted before reading an SSTable.
+//!
+//! A filter policy turns the sorted key set of a table into an opaque
+//! byte buffer, and later answers "may this key be in the table?" from
+//! that buffer alone. Different tiers of the tree can use different
+//! policies (e.g. Bloom for hot upper tiers, Ribbon for large cold tiers).
+
+use super::bloom::BloomFilter;
+use super::ribbon::{RibbonFilter, SLOT_OVERHEAD};
+
+/// Default target false positive rate for newly built filters.
+pub const DEFAULT_FILTER_FPR: f64 = 0.01;
+
+/// Trait defining how membership filters are built and probed.
+///
+/// The policy name is written into every SSTable next to the filter
+/// bytes, and readers resolve it through [`policy_by_name`]. New filter
+/// types can therefore be added without breaking existing files.
+pub trait FilterPolicy: Send + Sync {
+    /// Returns the stable name of this policy.
+    /// Recorded in SSTables, so it must never change once files exist.
+    fn name(&self) -> &str;
+
+    /// Build a filter over `keys` and return its serialized form.
+    fn create_filter(&self, keys: &[&[u8]]) -> Vec<u8>;
+
+    /// Check if `key` **may** be in the set encoded by `filter`.
+    /// Unreadable filters must answer `true` so that lookups stay correct.
+    fn may_contain(&self, filter: &[u8], key: &[u8]) -> bool;
+}
+
+/// Stable 64-bit hash of a byte string.
+/// Filters are persisted inside SSTables, so this must never depend on
+/// the Rust release (unlike `DefaultHasher`).
+pub(crate) fn hash64(data: &[u8], seed: u64) -> u64 {
+    let mut h = seed ^ (data.len() as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    let mut chunks = data.chunks_exact(8);
+    for chunk in &mut chunks {
+        h = mix64(h ^ u64::from_le_bytes(chunk.try_into().unwrap()));
+    }
+    let mut tail = [0u8; 8];
+    tail[..chunks.remainder().len()].copy_from_slice(chunks.remainder());
+    mix64(h ^ u64::from_le_bytes(tail))
+}
+
+/// SplitMix64 finalizer.
+pub(crate) fn mix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+/// Classic Bloom filter policy.
+#[derive(Debug, Clone)]
+pub struct BloomFilterPolicy {
+    /// Target false positive rate.
+    false_positive_rate: f64,
+}
+
+impl BloomFilterPolicy {
+    /// Create a Bloom policy targeting `false_positive_rate`.
+    pub fn new(false_positive_rate: f64) -> Self {
+        Self {
+            false_positive_rate,
+        }
+    }
+}
+
+impl FilterPolicy for BloomFilterPolicy {
+    fn name(&self) -> &str {
+        "oblivion.BloomFilter"
+    }
+
+    fn create_filter(&self, keys: &[&[u8]]) -> Vec<u8> {
+        let mut filter = BloomFilter::new(keys.len(), self.false_positive_rate);
+        for key in keys {
+            filter.insert(key);
+        }
+        filter.to_bytes()
+    }
+
+    fn may_contain(&self, filter: &[u8], key: &[u8]) -> bool {
+        BloomFilter::from_bytes(filter).is_none_or(|f| f.may_contain(key))
+    }
+}
+
+/// Ribbon filter policy: same FPR as Bloom with roughly 30% less memory.
+#[derive(Debug, Clone)]
+pub struct RibbonFilterPolicy {
+    /// Target false positive rate.
+    false_positive_rate: f64,
+}
+
+impl RibbonFilterPolicy {
+    /// Create a Ribbon policy targeting `false_positive_rate`.
+    pub fn new(false_positive_rate: f64) -> Self {
+        Self {
+            false_positive_rate,
+        }
+    }
+}
+
+impl FilterPolicy for RibbonFilterPolicy {
+    fn name(&self) -> &str {
+        "oblivion.RibbonFilter"
+    }
+
+    fn create_filter(&self, keys: &[&[u8]]) -> Vec<u8> {
+        RibbonFilter::build(keys, self.false_positive_rate).to_bytes()
+    }
+
+    fn may_contain(&self, filter: &[u8], key: &[u8]) -> bool {
+        RibbonFilter::from_bytes(filter).is_none_or(|f| f.may_contain(key))
+    }
+}
+
+/// Filter implementation selectable per compaction tier in [`Config`](crate::config::Config).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterType {
+    /// Bloom filter (mutable, fast to build).
+    Bloom,
+    /// Ribbon filter (static, ~30% smaller at equal FPR).
+    Ribbon,
+}
+
+impl FilterType {
+    /// Instantiate the policy for this filter type.
+    pub fn policy(self, false_positive_rate: f64) -> Box<dyn FilterPolicy> {
+        match self {
+            FilterType::Bloom => Box::new(BloomFilterPolicy::new(false_positive_rate)),
+            FilterType::Ribbon => Box::new(RibbonFilterPolicy::new(false_positive_rate)),
+        }
+    }
+}
+
+/// How large newly built filters should be, selectable per compaction tier.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterSizing {
+    /// Memory budget per key, in bits.
+    BitsPerKey(f64),
+    /// Target false positive rate.
+    FalsePositiveRate(f64),
+}
+
+impl FilterSizing {
+    /// Translate this sizing into the target FPR for a given filter type.
+    ///
+    /// # Formulas
+    /// - Bloom (optimal `k`): `FPR = e^(-bits * ln(2)^2)`
+    /// - Ribbon: `FPR = 2^-r` with `r = floor(bits / (1 + overhead))`
+    pub fn false_positive_rate(self, filter_type: FilterType) -> f64 {
+        match self {
+            FilterSizing::FalsePositiveRate(rate) => rate,
+            FilterSizing::BitsPerKey(bits) => match filter_type {
+                FilterType::Bloom => (-bits * 2.0_f64.ln().powi(2)).exp(),
+                FilterType::Ribbon => {
+                    let result_bits = (bits / (1.0 + SLOT_OVERHEAD)).floor().max(1.0);
+                    0.5f64.powf(result_bits)
+                }
+            },
+        }
+    }
+}
+
+impl Default for FilterSizing {
+    fn default() -> Self {
+        FilterSizing::FalsePositiveRate(DEFAULT_FILTER_FPR)
+    }
+}
+
+/// Resolve a filter policy from the name recorded in an SSTable.
+/// Returns `None` for unknown policies (e.g. files written by a newer
+/// release); such tables are simply read without filtering.
+pub fn policy_by_name(name: &str) -> Option<Box<dyn FilterPolicy>> {
+    // Filters are self-describing, so the FPR only matters for building
+    match name {
+        "oblivion.BloomFilter" => Some(Box::new(BloomFilterPolicy::new(DEFAULT_FILTER_FPR))),
+        "oblivion.RibbonFilter" => Some(Box::new(RibbonFilterPolicy::new(DEFAULT_FILTER_FPR))),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(n: usize) -> Vec<Vec<u8>> {
+        (0..n).map(|i| format!("key_{}", i).into_bytes()).collect()
+    }
+
+    #[test]
+    fn test_policies_have_no_false_negatives() {
+        let keys = keys(1000);
+        let refs: Vec<&[u8]> = keys.iter().map(|k| k.as_slice()).collect();
+
+        for filter_type in [FilterType::Bloom, FilterType::Ribbon] {
+            let policy = filter_type.policy(DEFAULT_FILTER_FPR);
+            let filter = policy.create_filter(&refs);
+            for key in &refs {
+                assert!(policy.may_contain(&filter, key), "{}", policy.name());
+            }
+        }
+    }
+
+    #[test]
+    fn test_ribbon_smaller_than_bloom() {
+        let keys = keys(10_000);
+        let refs: Vec<&[u8]> = keys.iter().map(|k| k.as_slice()).collect();
+
+        let bloom = FilterType::Bloom.policy(0.01).create_filter(&refs);
+        let ribbon = FilterType::Ribbon.policy(0.01).create_filter(&refs);
+        assert!(ribbon.len() < bloom.len());
+    }
+
+    #[test]
+    fn test_policy_by_name() {
+        for filter_type in [FilterType::Bloom, FilterType::Ribbon] {
+            let name = filter_type.policy(DEFAULT_FILTER_FPR).name().to_string();
+            let resolved = policy_by_name(&name).unwrap();
+            assert_eq!(resolved.name(), name);
+        }
+        assert!(policy_by_name("unknown.Filter").is_none());
+    }
+
+    #[test]
+    fn test_bits_per_key_sizing() {
+        let keys = keys(10_000);
+        let refs: Vec<&[u8]> = keys.iter().map(|k| k.as_slice()).collect();
+
+        for filter_type in [FilterType::Bloom, FilterType::Ribbon] {
+            let small = FilterSizing::BitsPerKey(5.0).false_positive_rate(filter_type);
+            let large = FilterSizing::BitsPerKey(15.0).false_positive_rate(filter_type);
+            assert!(small > large);
+
+            // The resulting filter stays within ~10% of the requested budget
+            let filter = filter_type.policy(large).create_filter(&refs);
+            let bits_per_key = filter.len() as f64 * 8.0 / refs.len() as f64;
+            assert!(
+                bits_per_key < 15.0 * 1.1,
+                "{:?}: {}",
+                filter_type,
+                bits_per_key
+            );
+        }
+
+        assert_eq!(
+            FilterSizing::FalsePositiveRate(0.05).false_positive_rate(FilterType::Ribbon),
+            0.05
+        );
+    }
+
+    #[test]
+    fn test_corrupt_filter_is_permissive() {
+        for filter_type in [FilterType::Bloom, FilterType::Ribbon] {
+            let policy = filter_type.policy(DEFAULT_FILTER_FPR);
+            assert!(policy.may_contain(&[0xFF, 0x01], b"key"));
+        }
+    }
+}
@@ -0,0 +1,174 @@
+//! OBLIVION - Glob Patterns
+//! Redis-style glob matching of keys, for `SCAN ... MATCH` and pub/sub
+//! patterns.
+//!
+//! ## Syntax
+//! `*` matches any run of bytes, `?` any one byte, `[abc]`, `[a-z]`
+//! and `[^a]` a byte in (or not in) a class, and `\` escapes the next
+//! byte. Patterns match whole keys, byte by byte.
+//!
+//! [`Oblivion::keys`](super::Oblivion::keys) runs a pattern over the
+//! merged view of the tree: only the range sharing the pattern's
+//! [`literal_prefix`] is read, so `user:*:settings` never touches keys
+//! outside `user:`. Under a custom [comparator](super::comparator) keys
+//! sharing a prefix are not contiguous, and the whole range is read.
+
+use crate::error::Result;
+use crate::types::Key;
+
+use super::comparator::Comparator;
+use super::options::ReadOptions;
+use super::version::ScanPage;
+
+/// Rows read per page while matching keys.
+const KEYS_PAGE_SIZE: usize = 1024;
+
+/// The bytes every key matching `pattern` starts with.
+pub fn literal_prefix(pattern: &[u8]) -> &[u8] {
+    let end = pattern
+        .iter()
+        .position(|b| matches!(b, b'*' | b'?' | b'[' | b'\\'))
+        .unwrap_or(pattern.len());
+    &pattern[..end]
+}
+
+/// Read options selecting the keys `pattern` can match: those starting
+/// with its literal prefix if `comparator` is byte order, else all.
+pub fn match_options(pattern: &[u8], comparator: &Comparator) -> ReadOptions {
+    if comparator.is_bytewise() {
+        ReadOptions::new().prefix(literal_prefix(pattern))
+    } else {
+        ReadOptions::new()
+    }
+}
+
+/// Match `text` against a Redis glob `pattern`: `*`, `?`, `[abc]`,
+/// `[^a-z]` and `\` escapes.
+pub fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // Pattern position after the last `*` and the text position it
+    // currently stands for
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if pattern.get(p) == Some(&b'*') {
+            p += 1;
+            backtrack = Some((p, t));
+            continue;
+        }
+        if let Some(next) = match_one(pattern, p, text[t]) {
+            p = next;
+            t += 1;
+            continue;
+        }
+        // Let the last `*` absorb one more byte
+        match backtrack {
+            Some((star_p, star_t)) => {
+                p = star_p;
+                t = star_t + 1;
+                backtrack = Some((star_p, t));
+            }
+            None => return false,
+        }
+    }
+    pattern[p..].iter().all(|&b| b == b'*')
+}
+
+/// Match one byte against the pattern element at `p` (not `*`),
+/// returning the position after the element.
+fn match_one(pattern: &[u8], p: usize, c: u8) -> Option<usize> {
+    match *pattern.get(p)? {
+        b'?' => Some(p + 1),
+        b'\\' if p + 1 < pattern.len() => (pattern[p + 1] == c).then_some(p + 2),
+        b'[' => {
+            let mut i = p + 1;
+            let negate = pattern.get(i) == Some(&b'^');
+            if negate {
+                i += 1;
+            }
+            let mut matched = false;
+            // An unterminated class ends with the pattern, as in Redis
+            while let Some(&b) = pattern.get(i) {
+                match b {
+                    b']' => {
+                        i += 1;
+                        break;
+                    }
+                    b'\\' if i + 1 < pattern.len() => {
+                        matched |= pattern[i + 1] == c;
+                        i += 2;
+                    }
+                    _ if pattern.get(i + 1) == Some(&b'-') && i + 2 < pattern.len() => {
+                        let (lo, hi) = (b.min(pattern[i + 2]), b.max(pattern[i + 2]));
+                        matched |= (lo..=hi).contains(&c);
+                        i += 3;
+                    }
+                    _ => {
+                        matched |= b == c;
+                        i += 1;
+                    }
+                }
+            }
+            (matched != negate).then_some(i)
+        }
+        literal => (literal == c).then_some(p + 1),
+    }
+}
+
+/// The keys within the bounds of `opts` that match `pattern`, in
+/// sorted order. In byte order (per `comparator`) the bounds are
+/// narrowed to the pattern's literal prefix, and the range is read page
+/// by page with `scan_page`, keeping only the matching keys of each
+/// page. Callers set a snapshot in `opts` so pages agree.
+pub(crate) fn matching_keys<F>(
+    comparator: &Comparator,
+    pattern: &[u8],
+    mut opts: ReadOptions,
+    mut scan_page: F,
+) -> Result<Vec<Key>>
+where
+    F: FnMut(&ReadOptions, usize) -> Result<ScanPage>,
+{
+    if comparator.is_bytewise() {
+        let prefix = ReadOptions::new().prefix(literal_prefix(pattern));
+        opts.lower_bound = opts.lower_bound.max(prefix.lower_bound);
+        opts.upper_bound = match (opts.upper_bound, prefix.upper_bound) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+    }
+    let mut keys = Vec::new();
+    loop {
+        let (page, resume) = scan_page(&opts, KEYS_PAGE_SIZE)?;
+        keys.extend(
+            page.into_iter()
+                .map(|(key, _)| key)
+                .filter(|key| glob_match(pattern, key)),
+        );
+        match resume {
+            Some(resume) => opts.lower_bound = Some(resume),
+            None => return Ok(keys),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match(b"*", b""));
+        assert!(glob_match(b"user:*", b"user:42"));
+        assert!(!glob_match(b"user:*", b"users"));
+        assert!(glob_match(b"h?llo", b"hallo"));
+        assert!(glob_match(b"h[ae]llo", b"hello"));
+        assert!(!glob_match(b"h[^e]llo", b"hello"));
+        assert!(glob_match(b"h[a-c]llo", b"hbllo"));
+        assert!(glob_match(b"*a*b*c", b"xxaxbxxbc"));
+        assert!(!glob_match(b"*a*b*c", b"xxaxbxxbcd"));
+        assert!(glob_match(b"a\\*b", b"a*b"));
+        assert!(!glob_match(b"a\\*b", b"axb"));
+        assert_eq!(literal_prefix(b"user:*:name"), b"user:");
+        assert_eq!(literal_prefix(b"plain"), b"plain");
+    }
+}
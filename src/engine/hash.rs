@@ -0,0 +1,177 @@
+//! OBLIVION - Hashes
+//! Maps of fields to values under one key, as Redis' hashes, so a
+//! record's attributes can be changed one at a time instead of
+//! rewriting a serialized blob.
+//!
+//! Each field is its own entry in the database's data-type range (see
+//! [`DATA_TYPE_PREFIX`](super::database::DATA_TYPE_PREFIX)), with the
+//! hash key and the field encoded as an ordered tuple (see
+//! [`keys`](super::keys)):
+//!
+//! ```text
+//! \xff\xffth <encode(&(key, field))>
+//! ```
+//!
+//! so the fields of one hash are adjacent and sorted, and `hgetall` is a
+//! prefix scan. `hset` and `hdel` apply all their fields in one
+//! [`WriteBatch`]; the counts they return are read just before it.
+//!
+//! Hashes and plain keys are separate namespaces: `get`, `delete`,
+//! `keys` and scans of a [`Database`] never see a hash, and a hash
+//! exists as long as it has a field.
+//!
+//! ## Example
+//! ```no_run
+//! use oblivion::config::Config;
+//! use oblivion::engine::Oblivion;
+//!
+//! let engine = Oblivion::open(Config::new("./data")).unwrap();
+//! engine
+//!     .hset(b"user:1", vec![
+//!         (b"name".to_vec(), b"ada".to_vec()),
+//!         (b"role".to_vec(), b"admin".to_vec()),
+//!     ])
+//!     .unwrap();
+//! engine.hdel(b"user:1", &[b"role".to_vec()]).unwrap();
+//! assert_eq!(engine.hget(b"user:1", b"name").unwrap(), Some(b"ada".to_vec()));
+//! assert_eq!(engine.hgetall(b"user:1").unwrap().len(), 1);
+//! ```
+
+use std::collections::BTreeSet;
+
+use crate::engine::batch::WriteBatch;
+use crate::engine::database::Database;
+use crate::engine::keys::{decode, encode};
+use crate::engine::options::ReadOptions;
+use crate::error::Result;
+use crate::types::{Key, Value};
+
+/// First bytes of every hash field, within a database.
+pub const HASH_PREFIX: &[u8] = b"\xff\xffth";
+
+/// A field of a hash and its value.
+pub type Field = (Vec<u8>, Value);
+
+impl Database<'_> {
+    /// Set `fields` of the hash `key` in one batch, returning how many
+    /// of them were new.
+    pub fn hset(&self, key: &[u8], fields: Vec<Field>) -> Result<usize> {
+        let mut batch = WriteBatch::new();
+        let mut seen = BTreeSet::new();
+        let mut added = 0;
+        for (field, value) in fields {
+            let stored = self.stored(&field_key(key, &field));
+            if seen.insert(stored.clone()) && self.get_stored(&stored)?.is_none() {
+                added += 1;
+            }
+            batch.put(stored, value);
+        }
+        self.engine().write(batch)?;
+        Ok(added)
+    }
+
+    /// Get the value of `field` in the hash `key`.
+    pub fn hget(&self, key: &[u8], field: &[u8]) -> Result<Option<Value>> {
+        self.get_stored(&self.stored(&field_key(key, field)))
+    }
+
+    /// Delete `fields` of the hash `key` in one batch, returning how many
+    /// of them existed.
+    pub fn hdel(&self, key: &[u8], fields: &[Vec<u8>]) -> Result<usize> {
+        let mut batch = WriteBatch::new();
+        let mut removed = 0;
+        for field in fields.iter().collect::<BTreeSet<_>>() {
+            let stored = self.stored(&field_key(key, field));
+            if self.get_stored(&stored)?.is_some() {
+                removed += 1;
+                batch.delete(stored);
+            }
+        }
+        if removed > 0 {
+            self.engine().write(batch)?;
+        }
+        Ok(removed)
+    }
+
+    /// Returns every field of the hash `key` with its value, in field
+    /// order; empty if the hash does not exist.
+    pub fn hgetall(&self, key: &[u8]) -> Result<Vec<Field>> {
+        let prefix = self.stored(&[HASH_PREFIX, &encode(key)].concat());
+        let rows = self
+            .engine()
+            .scan_opt(&ReadOptions::new().prefix(&prefix))?;
+        rows.into_iter()
+            .map(|(stored, value)| Ok((decode::<Vec<u8>>(&stored[prefix.len()..])?, value)))
+            .collect()
+    }
+}
+
+/// The key `field` of the hash `key` is stored under, within a
+/// database.
+fn field_key(key: &[u8], field: &[u8]) -> Key {
+    [HASH_PREFIX, &encode(&(key, field))].concat()
+}
+
+/// Split a key within a database into the hash key and field it stores,
+/// or `None` if it is not a hash field.
+pub fn split_field_key(key: &[u8]) -> Option<(Vec<u8>, Vec<u8>)> {
+    decode(key.strip_prefix(HASH_PREFIX)?).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::engine::database::DATA_TYPE_PREFIX;
+    use crate::engine::Oblivion;
+
+    fn fields(pairs: &[(&str, &str)]) -> Vec<Field> {
+        pairs
+            .iter()
+            .map(|(field, value)| (field.as_bytes().to_vec(), value.as_bytes().to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn test_hash_fields() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = Oblivion::open(Config::new(dir.path())).unwrap();
+        let db = engine.database(0).unwrap();
+        assert!(HASH_PREFIX.starts_with(DATA_TYPE_PREFIX));
+
+        assert_eq!(db.hset(b"u", fields(&[("b", "1"), ("a", "2")])).unwrap(), 2);
+        assert_eq!(db.hset(b"u", fields(&[("b", "3"), ("c", "4")])).unwrap(), 1);
+        assert_eq!(db.hset(b"v", fields(&[("a", "1"), ("a", "2")])).unwrap(), 1);
+        assert_eq!(db.hget(b"v", b"a").unwrap(), Some(b"2".to_vec()));
+        // A hash whose key extends another's keeps its own fields
+        db.hset(b"u\0x", fields(&[("a", "5")])).unwrap();
+        db.put(b"u", b"plain".to_vec()).unwrap();
+
+        assert_eq!(db.hget(b"u", b"b").unwrap(), Some(b"3".to_vec()));
+        assert_eq!(db.hget(b"u", b"z").unwrap(), None);
+        assert_eq!(
+            db.hgetall(b"u").unwrap(),
+            fields(&[("a", "2"), ("b", "3"), ("c", "4")])
+        );
+        assert_eq!(db.hdel(b"u", &[b"a".to_vec(), b"z".to_vec()]).unwrap(), 1);
+        assert_eq!(db.hgetall(b"u").unwrap(), fields(&[("b", "3"), ("c", "4")]));
+        assert_eq!(db.hgetall(b"missing").unwrap(), vec![]);
+
+        assert_eq!(db.get(b"u").unwrap(), Some(b"plain".to_vec()));
+        assert_eq!(db.keys(b"*").unwrap(), vec![b"u".to_vec()]);
+        let stored = field_key(b"u", b"b");
+        assert_eq!(db.get(&stored).unwrap(), None);
+        assert!(db.put(stored.clone(), b"v".to_vec()).is_err());
+        assert_eq!(
+            split_field_key(&stored),
+            Some((b"u".to_vec(), b"b".to_vec()))
+        );
+        assert_eq!(split_field_key(b"u"), None);
+
+        let other = engine.database(1).unwrap();
+        assert_eq!(other.hgetall(b"u").unwrap(), vec![]);
+        other.hset(b"u", fields(&[("a", "1")])).unwrap();
+        assert_eq!(other.keys(b"*").unwrap(), Vec::<Key>::new());
+        assert_eq!(db.hget(b"u", b"a").unwrap(), None);
+    }
+}
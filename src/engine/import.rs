@@ -0,0 +1,371 @@
+//! OBLIVION - Bulk Import
+//! Loads NDJSON or CSV dumps in the [`export`](super::export) formats
+//! straight into SSTables, bypassing the WAL and MemTable.
+//!
+//! ## Pipeline
+//! 1. Rows are parsed and buffered in a sorted chunk until it holds
+//!    [`ImportOptions::chunk_bytes`] of keys and values
+//! 2. Each full chunk is written out as one SSTable; later rows for the
+//!    same key replace earlier ones within a chunk, and later chunks
+//!    shadow earlier ones since their tables are newer
+//! 3. Once the input is exhausted, the engine flushes its MemTable and
+//!    adds the tables to the manifest in one update, so the import
+//!    becomes visible (and durable) all at once
+//!
+//! Memory use is bounded by the chunk size regardless of the input's.
+//! Chunks may overlap; compaction merges them like any other tables.
+//! If the import fails, the tables built so far are deleted and the
+//! engine is unchanged.
+
+use std::collections::BTreeMap;
+use std::io::BufRead;
+
+use crate::error::{OblivionError, Result};
+use crate::types::{Key, Value};
+
+use super::export::ExportFormat;
+use super::sstable::SSTable;
+use super::Oblivion;
+
+/// Default bytes of keys and values sorted in memory per SSTable.
+pub const DEFAULT_IMPORT_CHUNK_BYTES: usize = 64 * 1024 * 1024;
+
+/// Options for [`Oblivion::import_opt`].
+#[derive(Debug, Clone)]
+pub struct ImportOptions {
+    /// Bytes of keys and values buffered and sorted in memory before
+    /// they are written out as an SSTable.
+    pub chunk_bytes: usize,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            chunk_bytes: DEFAULT_IMPORT_CHUNK_BYTES,
+        }
+    }
+}
+
+impl ImportOptions {
+    /// Create options with default values.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the bytes sorted in memory per SSTable.
+    pub fn chunk_bytes(mut self, chunk_bytes: usize) -> Self {
+        self.chunk_bytes = chunk_bytes.max(1);
+        self
+    }
+}
+
+/// One parsed row of a dump.
+struct Row {
+    key: Key,
+    value: Value,
+    ttl_ms: Option<u64>,
+}
+
+/// Import the rows of `reader` into `engine`. Returns the number of rows.
+pub(super) fn import_rows<R: BufRead>(
+    engine: &Oblivion,
+    reader: R,
+    format: ExportFormat,
+    opts: &ImportOptions,
+) -> Result<u64> {
+    let mut tables = Vec::new();
+    let mut ttls = Vec::new();
+    match build_tables(engine, reader, format, opts, &mut tables, &mut ttls) {
+        Ok(rows) => {
+            engine.ingest(tables, ttls)?;
+            Ok(rows)
+        }
+        Err(e) => {
+            for (_, table) in tables {
+                engine.table_cache.evict(table.path());
+                if let Err(remove) = engine.table_cache.env().remove_file(table.path()) {
+                    log::warn!("Failed to remove {:?}: {}", table.path(), remove);
+                }
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Parse `reader` and write its rows out as sorted SSTables, oldest
+/// first, collecting the TTLs to apply once they are ingested.
+fn build_tables<R: BufRead>(
+    engine: &Oblivion,
+    mut reader: R,
+    format: ExportFormat,
+    opts: &ImportOptions,
+    tables: &mut Vec<(u64, SSTable)>,
+    ttls: &mut Vec<(Key, u64)>,
+) -> Result<u64> {
+    let mut parser = Parser::new(&mut reader, format)?;
+    let mut chunk: BTreeMap<Key, Value> = BTreeMap::new();
+    let mut chunk_bytes = 0;
+    let mut rows = 0;
+    while let Some(row) = parser.next_row()? {
+        engine.check_sizes(&row.key, Some(&row.value))?;
+        if let Some(ttl) = row.ttl_ms {
+            ttls.push((row.key.clone(), ttl));
+        }
+        chunk_bytes += row.key.len() + row.value.len();
+        chunk.insert(row.key, row.value);
+        rows += 1;
+        if chunk_bytes >= opts.chunk_bytes {
+            tables.push(write_table(engine, &chunk)?);
+            chunk.clear();
+            chunk_bytes = 0;
+        }
+    }
+    if !chunk.is_empty() {
+        tables.push(write_table(engine, &chunk)?);
+    }
+    Ok(rows)
+}
+
+/// Write one sorted chunk as a new SSTable.
+fn write_table(engine: &Oblivion, chunk: &BTreeMap<Key, Value>) -> Result<(u64, SSTable)> {
+    let config = engine.tree.config();
+    let id = engine.tree.new_file_number();
+    let table = SSTable::flush_from_memtable(
+        Oblivion::sstable_path(&config, id),
+        chunk
+            .iter()
+            .map(|(k, v)| (k.as_slice(), Some(v.as_slice()))),
+        config.filter_policy_for_tier(0),
+        &engine.table_cache,
+    )?;
+    log::info!(
+        "Import wrote {} rows to SSTable {}",
+        table.entry_count(),
+        id
+    );
+    Ok((id, table))
+}
+
+/// Reads rows in either format, tracking line numbers for errors.
+struct Parser<'a, R> {
+    reader: &'a mut R,
+    format: ExportFormat,
+    line: usize,
+    /// CSV column positions of key, value, ttl_ms and encoding.
+    columns: [Option<usize>; 4],
+}
+
+impl<'a, R: BufRead> Parser<'a, R> {
+    fn new(reader: &'a mut R, format: ExportFormat) -> Result<Self> {
+        let mut parser = Self {
+            reader,
+            format,
+            line: 0,
+            columns: [None; 4],
+        };
+        if format == ExportFormat::Csv {
+            let header = parser.csv_record()?.unwrap_or_default();
+            for (i, name) in ["key", "value", "ttl_ms", "encoding"].iter().enumerate() {
+                parser.columns[i] = header.iter().position(|column| column == name);
+            }
+            if parser.columns[0].is_none() || parser.columns[1].is_none() {
+                return Err(parser.error("CSV header must name key and value columns"));
+            }
+        }
+        Ok(parser)
+    }
+
+    fn error(&self, message: &str) -> OblivionError {
+        OblivionError::Serialization(format!("import line {}: {}", self.line, message))
+    }
+
+    /// Read the next row, or `None` at the end of the input.
+    fn next_row(&mut self) -> Result<Option<Row>> {
+        match self.format {
+            ExportFormat::Ndjson => self.ndjson_row(),
+            ExportFormat::Csv => self.csv_row(),
+        }
+    }
+
+    fn ndjson_row(&mut self) -> Result<Option<Row>> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            self.line += 1;
+            if !line.trim().is_empty() {
+                break;
+            }
+        }
+        let object: serde_json::Value =
+            serde_json::from_str(&line).map_err(|e| self.error(&e.to_string()))?;
+        let field = |name: &str| object.get(name).filter(|v| !v.is_null());
+        let text = |name: &str| -> Result<&str> {
+            field(name)
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| self.error(&format!("missing string field '{}'", name)))
+        };
+        let ttl_ms = match field("ttl_ms") {
+            Some(ttl) => Some(ttl.as_u64().ok_or_else(|| self.error("invalid ttl_ms"))?),
+            None => None,
+        };
+        let encoding = match field("encoding") {
+            Some(_) => text("encoding")?,
+            None => "utf8",
+        };
+        Ok(Some(Row {
+            key: self.decode(text("key")?, encoding)?,
+            value: self.decode(text("value")?, encoding)?,
+            ttl_ms,
+        }))
+    }
+
+    fn csv_row(&mut self) -> Result<Option<Row>> {
+        let record = loop {
+            match self.csv_record()? {
+                None => return Ok(None),
+                Some(record) if record.len() == 1 && record[0].is_empty() => continue,
+                Some(record) => break record,
+            }
+        };
+        let column = |i: usize| {
+            self.columns[i]
+                .and_then(|c| record.get(c))
+                .map(String::as_str)
+                .unwrap_or("")
+        };
+        let ttl_ms = match column(2) {
+            "" => None,
+            ttl => Some(ttl.parse().map_err(|_| self.error("invalid ttl_ms"))?),
+        };
+        let encoding = match column(3) {
+            "" => "utf8",
+            encoding => encoding,
+        };
+        Ok(Some(Row {
+            key: self.decode(column(0), encoding)?,
+            value: self.decode(column(1), encoding)?,
+            ttl_ms,
+        }))
+    }
+
+    /// Read one RFC 4180 record, which spans several lines when a
+    /// quoted field contains line breaks.
+    fn csv_record(&mut self) -> Result<Option<Vec<String>>> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        self.line += 1;
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        loop {
+            let mut chars = line.chars().peekable();
+            while let Some(c) = chars.next() {
+                match (quoted, c) {
+                    (true, '"') if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    (true, '"') => quoted = false,
+                    (true, c) => field.push(c),
+                    (false, '"') if field.is_empty() => quoted = true,
+                    (false, ',') => fields.push(std::mem::take(&mut field)),
+                    (false, '\n') => {}
+                    (false, '\r') if chars.peek() == Some(&'\n') => {}
+                    (false, c) => field.push(c),
+                }
+            }
+            if !quoted {
+                break;
+            }
+            line.clear();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Err(self.error("unterminated quoted field"));
+            }
+            self.line += 1;
+        }
+        fields.push(field);
+        Ok(Some(fields))
+    }
+
+    /// Turn a field back into bytes according to the row's encoding.
+    fn decode(&self, field: &str, encoding: &str) -> Result<Vec<u8>> {
+        match encoding {
+            "utf8" => Ok(field.as_bytes().to_vec()),
+            "base64" => base64_decode(field).ok_or_else(|| self.error("invalid base64")),
+            other => Err(self.error(&format!("unknown encoding '{}'", other))),
+        }
+    }
+}
+
+/// Decode standard base64 with padding.
+fn base64_decode(s: &str) -> Option<Vec<u8>> {
+    fn sextet(c: u8) -> Option<u32> {
+        match c {
+            b'A'..=b'Z' => Some((c - b'A') as u32),
+            b'a'..=b'z' => Some((c - b'a' + 26) as u32),
+            b'0'..=b'9' => Some((c - b'0' + 52) as u32),
+            b'+' => Some(62),
+            b'/' => Some(63),
+            _ => None,
+        }
+    }
+    let bytes = s.as_bytes();
+    if !bytes.len().is_multiple_of(4) {
+        return None;
+    }
+    let mut out = Vec::with_capacity(bytes.len() / 4 * 3);
+    for (i, quad) in bytes.chunks(4).enumerate() {
+        let last = i + 1 == bytes.len() / 4;
+        let padding = quad.iter().rev().take_while(|&&c| c == b'=').count();
+        if padding > 2 || (padding > 0 && !last) {
+            return None;
+        }
+        let mut n = 0;
+        for &c in &quad[..4 - padding] {
+            n = n << 6 | sextet(c)?;
+        }
+        n <<= 6 * padding;
+        out.extend_from_slice(&n.to_be_bytes()[1..4 - padding]);
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64_decode() {
+        assert_eq!(base64_decode("").unwrap(), b"");
+        assert_eq!(base64_decode("Zm9v").unwrap(), b"foo");
+        assert_eq!(base64_decode("Zm8=").unwrap(), b"fo");
+        assert_eq!(base64_decode("/wA=").unwrap(), [0xff, 0x00]);
+        assert!(base64_decode("Zm9").is_none());
+        assert!(base64_decode("Zm=v").is_none());
+        assert!(base64_decode("Zm9v!!!!").is_none());
+    }
+
+    #[test]
+    fn test_csv_records_span_lines() {
+        let input = "key,value\n\"a,\"\"b\"\"\",\"x\ny\"\nplain,\n";
+        let mut reader = input.as_bytes();
+        let mut parser = Parser::new(&mut reader, ExportFormat::Csv).unwrap();
+        let row = parser.next_row().unwrap().unwrap();
+        assert_eq!(
+            (row.key, row.value),
+            (b"a,\"b\"".to_vec(), b"x\ny".to_vec())
+        );
+        let row = parser.next_row().unwrap().unwrap();
+        assert_eq!((row.key, row.value), (b"plain".to_vec(), Vec::new()));
+        assert!(parser.next_row().unwrap().is_none());
+
+        let mut reader = "key,value\n\"open\n".as_bytes();
+        let mut parser = Parser::new(&mut reader, ExportFormat::Csv).unwrap();
+        assert!(parser.next_row().is_err());
+    }
+}
@@ -0,0 +1,421 @@
+//! OBLIVION - I/O Layer
+//! File primitives shared by the WAL and SSTables.
+//!
+//! ## Sync Methods
+//! `fsync` persists file data and all metadata. `fdatasync` skips
+//! metadata that is not needed to read the data back (such as mtime),
+//! which saves a journal write per WAL append on most filesystems.
+//!
+//! Both go through `std`, which picks the primitive that reaches stable
+//! storage on each platform: `fsync`/`fdatasync` on Linux,
+//! `fcntl(F_FULLFSYNC)` on macOS and iOS (where `fsync` stops at the
+//! drive's volatile cache), and `FlushFileBuffers` on Windows.
+//!
+//! Syncing a file does not persist its name. Creating, renaming or
+//! deleting a file is only durable once its directory is synced too
+//! ([`sync_dir`]); the engine does so after creating or rotating a WAL,
+//! archiving a segment, sealing an SSTable and replacing a manifest.
+//! Deleting a flushed segment is persisted by the next rotation, before
+//! any later flush can make its writes stale.
+//!
+//! ## Direct I/O
+//! With `use_direct_io`, SSTable reads and writes (flushes and
+//! compactions included) bypass the OS page cache via `O_DIRECT` on
+//! Linux, so large background writes do not evict the hot working set.
+//! `O_DIRECT` requires offsets, lengths and buffers aligned to
+//! [`DIRECT_IO_ALIGNMENT`]; the helpers here take care of that. Where
+//! the platform or filesystem does not support it, files silently fall
+//! back to buffered I/O.
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Alignment of offsets, lengths and buffers for direct I/O.
+pub const DIRECT_IO_ALIGNMENT: usize = 4096;
+
+/// Size of the staging buffer used by direct writes.
+const DIRECT_WRITE_BUFFER: usize = 64 * DIRECT_IO_ALIGNMENT;
+
+/// How a file is made durable.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SyncMethod {
+    /// `fsync`: flush data and all metadata.
+    #[default]
+    Fsync,
+    /// `fdatasync`: flush data and only the metadata needed to read it.
+    Fdatasync,
+}
+
+impl SyncMethod {
+    /// Flush `file` to stable storage using this method.
+    pub fn sync(self, file: &File) -> io::Result<()> {
+        match self {
+            SyncMethod::Fsync => file.sync_all(),
+            SyncMethod::Fdatasync => file.sync_data(),
+        }
+    }
+
+    /// Returns the option value naming this method.
+    pub fn name(self) -> &'static str {
+        match self {
+            SyncMethod::Fsync => "fsync",
+            SyncMethod::Fdatasync => "fdatasync",
+        }
+    }
+}
+
+impl fmt::Display for SyncMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for SyncMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fsync" => Ok(SyncMethod::Fsync),
+            "fdatasync" => Ok(SyncMethod::Fdatasync),
+            other => Err(format!("unknown sync method '{}'", other)),
+        }
+    }
+}
+
+/// Open a file for reading, with `O_DIRECT` if `direct` is set.
+pub fn open_read(path: &Path, direct: bool) -> io::Result<File> {
+    open_with(OpenOptions::new().read(true), path, direct)
+}
+
+/// Create (or truncate) a file for writing, with `O_DIRECT` if `direct` is set.
+pub fn create_write(path: &Path, direct: bool) -> io::Result<File> {
+    open_with(
+        OpenOptions::new().create(true).write(true).truncate(true),
+        path,
+        direct,
+    )
+}
+
+/// Hard-link `src` to `dst`, copying it instead where links are not
+/// possible (e.g. across filesystems).
+pub fn link_or_copy(src: &Path, dst: &Path) -> io::Result<()> {
+    match std::fs::hard_link(src, dst) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Err(e),
+        Err(_) => std::fs::copy(src, dst).map(|_| ()),
+    }
+}
+
+/// Open (creating if needed) the file at `path` and take an exclusive
+/// advisory lock on it, held until the returned handle is dropped.
+/// Returns `None` if another handle already holds the lock, whether
+/// in another process or this one.
+pub fn try_lock_file(path: &Path) -> io::Result<Option<File>> {
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)?;
+    match file.try_lock() {
+        Ok(()) => Ok(Some(file)),
+        Err(std::fs::TryLockError::WouldBlock) => Ok(None),
+        Err(std::fs::TryLockError::Error(e)) => Err(e),
+    }
+}
+
+/// Make the entries of `dir` durable: files created, renamed into or
+/// deleted from it. On Windows, where NTFS journals directory entries
+/// and a directory cannot be opened as a file, this does nothing.
+pub fn sync_dir(dir: &Path) -> io::Result<()> {
+    #[cfg(windows)]
+    {
+        let _ = dir;
+        Ok(())
+    }
+    #[cfg(not(windows))]
+    File::open(dir)?.sync_all()
+}
+
+/// Open `path`, trying `O_DIRECT` first when requested and supported.
+fn open_with(options: &OpenOptions, path: &Path, direct: bool) -> io::Result<File> {
+    #[cfg(target_os = "linux")]
+    if direct {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        let mut direct_options = options.clone();
+        direct_options.custom_flags(libc::O_DIRECT);
+        match direct_options.open(path) {
+            Ok(file) => return Ok(file),
+            // The filesystem (e.g. tmpfs) does not support O_DIRECT
+            Err(e) if e.raw_os_error() == Some(libc::EINVAL) => {
+                log::warn!("O_DIRECT unsupported for {:?}, using buffered I/O", path);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = direct;
+    options.open(path)
+}
+
+/// Tell the OS that `len` bytes at `offset` of `file` will be read
+/// soon, so it can start reading them in the background. Only a hint:
+/// it does nothing outside Linux, and failures are ignored.
+pub fn advise_will_need(file: &File, offset: u64, len: u64) {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::io::AsRawFd;
+
+        // SAFETY: the descriptor stays open for the call; posix_fadvise
+        // only reads its arguments
+        unsafe {
+            libc::posix_fadvise(
+                file.as_raw_fd(),
+                offset as libc::off_t,
+                len as libc::off_t,
+                libc::POSIX_FADV_WILLNEED,
+            );
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = (file, offset, len);
+}
+
+/// Read `len` bytes at `offset`. In direct mode the read is widened to
+/// aligned boundaries and the requested range is copied out.
+pub fn read_at(file: &mut File, offset: u64, len: usize, direct: bool) -> io::Result<Vec<u8>> {
+    if !direct {
+        let mut data = vec![0u8; len];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut data)?;
+        return Ok(data);
+    }
+
+    let align = DIRECT_IO_ALIGNMENT as u64;
+    let start = offset - offset % align;
+    let end = align_up(offset + len as u64);
+    let mut buf = AlignedBuf::zeroed((end - start) as usize);
+    file.seek(SeekFrom::Start(start))?;
+
+    let wanted = (offset - start) as usize + len;
+    let mut filled = 0;
+    while filled < wanted {
+        let n = file.read(&mut buf.as_mut_slice()[filled..])?;
+        filled += n;
+        // A short, unaligned read means end of file
+        if n == 0 || n % DIRECT_IO_ALIGNMENT != 0 {
+            break;
+        }
+    }
+    if filled < wanted {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "failed to fill whole buffer",
+        ));
+    }
+    let skip = (offset - start) as usize;
+    Ok(buf.as_slice()[skip..skip + len].to_vec())
+}
+
+/// Round `n` up to a multiple of [`DIRECT_IO_ALIGNMENT`].
+fn align_up(n: u64) -> u64 {
+    let align = DIRECT_IO_ALIGNMENT as u64;
+    n.div_ceil(align) * align
+}
+
+/// Zero-initialized byte buffer whose start is aligned for direct I/O.
+struct AlignedBuf {
+    /// Over-allocated backing storage; never resized.
+    storage: Vec<u8>,
+    /// Offset of the first aligned byte in `storage`.
+    start: usize,
+    /// Usable length.
+    len: usize,
+}
+
+impl AlignedBuf {
+    fn zeroed(len: usize) -> Self {
+        let storage = vec![0u8; len + DIRECT_IO_ALIGNMENT];
+        let misalignment = storage.as_ptr() as usize % DIRECT_IO_ALIGNMENT;
+        let start = (DIRECT_IO_ALIGNMENT - misalignment) % DIRECT_IO_ALIGNMENT;
+        Self {
+            storage,
+            start,
+            len,
+        }
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        &self.storage[self.start..self.start + self.len]
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.storage[self.start..self.start + self.len]
+    }
+}
+
+/// Sequential writer for SSTable files, buffered or direct.
+pub enum TableWriter {
+    /// Regular writes through the page cache.
+    Buffered(BufWriter<File>),
+    /// Aligned `O_DIRECT` writes.
+    Direct(DirectWriter),
+}
+
+impl TableWriter {
+    /// Create `path` for writing, using direct I/O if `direct` is set.
+    pub fn create(path: &Path, direct: bool) -> io::Result<Self> {
+        let file = create_write(path, direct)?;
+        Ok(if direct {
+            TableWriter::Direct(DirectWriter::new(file))
+        } else {
+            TableWriter::Buffered(BufWriter::new(file))
+        })
+    }
+
+    /// Write out everything buffered and fsync the file.
+    pub fn finish(&mut self) -> io::Result<()> {
+        match self {
+            TableWriter::Buffered(writer) => {
+                writer.flush()?;
+                writer.get_ref().sync_all()
+            }
+            TableWriter::Direct(writer) => writer.finish(),
+        }
+    }
+}
+
+impl Write for TableWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            TableWriter::Buffered(writer) => writer.write(buf),
+            TableWriter::Direct(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            TableWriter::Buffered(writer) => writer.flush(),
+            TableWriter::Direct(writer) => writer.flush(),
+        }
+    }
+}
+
+/// Writer staging data in an aligned buffer and issuing only
+/// aligned, full-size writes. The tail is padded on [`finish`](Self::finish)
+/// and the file truncated back to its logical length.
+pub struct DirectWriter {
+    file: File,
+    /// Aligned staging buffer.
+    buf: AlignedBuf,
+    /// Bytes staged in `buf`.
+    pos: usize,
+    /// Bytes already written to the file.
+    written: u64,
+}
+
+impl DirectWriter {
+    fn new(file: File) -> Self {
+        Self {
+            file,
+            buf: AlignedBuf::zeroed(DIRECT_WRITE_BUFFER),
+            pos: 0,
+            written: 0,
+        }
+    }
+
+    /// Write the padded tail, trim the padding and fsync.
+    fn finish(&mut self) -> io::Result<()> {
+        let logical_len = self.written + self.pos as u64;
+        if self.pos > 0 {
+            let padded = align_up(self.pos as u64) as usize;
+            self.buf.as_mut_slice()[self.pos..padded].fill(0);
+            self.file.write_all(&self.buf.as_slice()[..padded])?;
+            self.written += padded as u64;
+            self.pos = 0;
+        }
+        self.file.set_len(logical_len)?;
+        self.written = logical_len;
+        self.file.sync_all()
+    }
+}
+
+impl Write for DirectWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let n = data.len().min(DIRECT_WRITE_BUFFER - self.pos);
+        self.buf.as_mut_slice()[self.pos..self.pos + n].copy_from_slice(&data[..n]);
+        self.pos += n;
+        if self.pos == DIRECT_WRITE_BUFFER {
+            self.file.write_all(self.buf.as_slice())?;
+            self.written += DIRECT_WRITE_BUFFER as u64;
+            self.pos = 0;
+        }
+        Ok(n)
+    }
+
+    /// Partial buffers cannot be written with `O_DIRECT`; the tail is
+    /// written by `finish`.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_file_is_exclusive() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("LOCK");
+
+        let held = try_lock_file(&path).unwrap().unwrap();
+        assert!(try_lock_file(&path).unwrap().is_none());
+        drop(held);
+        assert!(try_lock_file(&path).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_sync_method_parse() {
+        assert_eq!("fsync".parse::<SyncMethod>(), Ok(SyncMethod::Fsync));
+        assert_eq!("fdatasync".parse::<SyncMethod>(), Ok(SyncMethod::Fdatasync));
+        assert!("fsink".parse::<SyncMethod>().is_err());
+        assert_eq!(SyncMethod::Fdatasync.to_string(), "fdatasync");
+    }
+
+    #[test]
+    fn test_aligned_buffer() {
+        let buf = AlignedBuf::zeroed(100);
+        assert_eq!(buf.as_slice().as_ptr() as usize % DIRECT_IO_ALIGNMENT, 0);
+        assert_eq!(buf.as_slice().len(), 100);
+    }
+
+    #[test]
+    fn test_write_and_read_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let data: Vec<u8> = (0..DIRECT_WRITE_BUFFER + 5000)
+            .map(|i| (i % 251) as u8)
+            .collect();
+
+        for direct in [false, true] {
+            let path = dir.path().join(format!("table_{}", direct));
+            let mut writer = TableWriter::create(&path, direct).unwrap();
+            writer.write_all(&data).unwrap();
+            writer.finish().unwrap();
+
+            // Padding is trimmed back to the logical length
+            assert_eq!(std::fs::metadata(&path).unwrap().len(), data.len() as u64);
+
+            let mut file = open_read(&path, direct).unwrap();
+            for (offset, len) in [(0, 10), (4090, 20), (DIRECT_WRITE_BUFFER - 3, 5000)] {
+                let read = read_at(&mut file, offset as u64, len, direct).unwrap();
+                assert_eq!(read, &data[offset..offset + len]);
+            }
+            let past_end = read_at(&mut file, data.len() as u64 - 4, 8, direct);
+            assert_eq!(past_end.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        }
+    }
+}
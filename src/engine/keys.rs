@@ -0,0 +1,306 @@
+//! OBLIVION - Ordered Key Encoding
+//! Byte encodings of numbers, strings and tuples whose byte order is the
+//! values' natural order, so range scans over numeric and composite keys
+//! return them sorted.
+//!
+//! ## Encodings
+//! | Type               | Bytes                                                    |
+//! |--------------------|----------------------------------------------------------|
+//! | `u64`, `u32`       | Big-endian                                               |
+//! | `i64`, `i32`       | Big-endian with the sign bit flipped                     |
+//! | `f64`              | Big-endian bits; sign bit flipped, all bits for negatives |
+//! | `str`, `[u8]`      | `0x00` escaped as `0x00 0xFF`, then a `0x00 0x01` terminator |
+//! | Tuples             | Their elements' encodings, one after the other           |
+//!
+//! Strings are terminated rather than length-prefixed: a length prefix
+//! would sort `"b"` before `"ab"`. The terminator sorts below every
+//! escaped byte, so a string sorts before its extensions and a tuple's
+//! encoding starts with the encoding of each of its prefixes:
+//! `ReadOptions::prefix(&encode(&("user", 42u64)))` selects every
+//! `("user", 42, ...)` key. Floats order `-inf < ... < -0.0 < 0.0 <
+//! ... < inf`, with NaNs at both ends by sign.
+//!
+//! ## Example
+//! ```no_run
+//! use oblivion::config::Config;
+//! use oblivion::engine::keys::{decode, encode};
+//! use oblivion::engine::options::ReadOptions;
+//! use oblivion::engine::Oblivion;
+//!
+//! let engine = Oblivion::open(Config::new("./data")).unwrap();
+//! for (user, ts) in [(7u64, 1_700_000_000i64), (7, -5), (12, 3)] {
+//!     engine.put(encode(&("event", user, ts)), b"...".to_vec()).unwrap();
+//! }
+//! let events = engine
+//!     .scan_opt(&ReadOptions::new().prefix(&encode(&("event", 7u64))))
+//!     .unwrap();
+//! let (_, _, ts): (String, u64, i64) = decode(&events[0].0).unwrap();
+//! assert_eq!(ts, -5);
+//! ```
+
+use crate::error::{OblivionError, Result};
+use crate::types::Key;
+
+/// Escapes a `0x00` byte inside a string.
+const ESCAPE: u8 = 0xFF;
+
+/// Follows `0x00` to end a string.
+const TERMINATOR: u8 = 0x01;
+
+/// A value with an order-preserving key encoding.
+pub trait EncodeKey {
+    /// Append the value's encoding to `out`.
+    fn encode_key(&self, out: &mut Vec<u8>);
+}
+
+/// A value that can be read back from its key encoding.
+pub trait DecodeKey: Sized {
+    /// Read a value from the front of `input`, advancing past it.
+    fn decode_key(input: &mut &[u8]) -> Result<Self>;
+}
+
+/// Encode `value` as a key.
+pub fn encode<T: EncodeKey + ?Sized>(value: &T) -> Key {
+    let mut out = Vec::new();
+    value.encode_key(&mut out);
+    out
+}
+
+/// Decode a key written by [`encode`], which must hold exactly one `T`.
+pub fn decode<T: DecodeKey>(mut key: &[u8]) -> Result<T> {
+    let value = T::decode_key(&mut key)?;
+    if !key.is_empty() {
+        return Err(invalid(format!("{} trailing byte(s)", key.len())));
+    }
+    Ok(value)
+}
+
+fn invalid(message: impl Into<String>) -> OblivionError {
+    OblivionError::Serialization(format!("invalid key encoding: {}", message.into()))
+}
+
+/// Take the first `N` bytes of `input`.
+fn take<const N: usize>(input: &mut &[u8]) -> Result<[u8; N]> {
+    let Some((bytes, rest)) = input.split_first_chunk::<N>() else {
+        return Err(invalid(format!(
+            "expected {} bytes, found {}",
+            N,
+            input.len()
+        )));
+    };
+    *input = rest;
+    Ok(*bytes)
+}
+
+macro_rules! unsigned_key {
+    ($($ty:ty),*) => {$(
+        impl EncodeKey for $ty {
+            fn encode_key(&self, out: &mut Vec<u8>) {
+                out.extend_from_slice(&self.to_be_bytes());
+            }
+        }
+
+        impl DecodeKey for $ty {
+            fn decode_key(input: &mut &[u8]) -> Result<Self> {
+                Ok(<$ty>::from_be_bytes(take(input)?))
+            }
+        }
+    )*};
+}
+
+macro_rules! signed_key {
+    ($($ty:ty => $unsigned:ty),*) => {$(
+        impl EncodeKey for $ty {
+            fn encode_key(&self, out: &mut Vec<u8>) {
+                // Flipping the sign bit puts negatives below positives
+                let flipped = (*self as $unsigned) ^ (1 << (<$unsigned>::BITS - 1));
+                out.extend_from_slice(&flipped.to_be_bytes());
+            }
+        }
+
+        impl DecodeKey for $ty {
+            fn decode_key(input: &mut &[u8]) -> Result<Self> {
+                let flipped = <$unsigned>::from_be_bytes(take(input)?);
+                Ok((flipped ^ (1 << (<$unsigned>::BITS - 1))) as $ty)
+            }
+        }
+    )*};
+}
+
+unsigned_key!(u32, u64);
+signed_key!(i32 => u32, i64 => u64);
+
+impl EncodeKey for f64 {
+    fn encode_key(&self, out: &mut Vec<u8>) {
+        let bits = self.to_bits();
+        // Negative floats order backwards by their bits, so flip them all
+        let ordered = if bits >> 63 == 1 {
+            !bits
+        } else {
+            bits | 1 << 63
+        };
+        out.extend_from_slice(&ordered.to_be_bytes());
+    }
+}
+
+impl DecodeKey for f64 {
+    fn decode_key(input: &mut &[u8]) -> Result<Self> {
+        let ordered = u64::from_be_bytes(take(input)?);
+        let bits = if ordered >> 63 == 1 {
+            ordered & !(1 << 63)
+        } else {
+            !ordered
+        };
+        Ok(f64::from_bits(bits))
+    }
+}
+
+impl EncodeKey for [u8] {
+    fn encode_key(&self, out: &mut Vec<u8>) {
+        for &byte in self {
+            out.push(byte);
+            if byte == 0 {
+                out.push(ESCAPE);
+            }
+        }
+        out.extend_from_slice(&[0, TERMINATOR]);
+    }
+}
+
+impl DecodeKey for Vec<u8> {
+    fn decode_key(input: &mut &[u8]) -> Result<Self> {
+        let mut bytes = Vec::new();
+        let mut rest = input.iter();
+        loop {
+            match rest.next() {
+                Some(0) => match rest.next() {
+                    Some(&ESCAPE) => bytes.push(0),
+                    Some(&TERMINATOR) => break,
+                    _ => return Err(invalid("bad escape in string")),
+                },
+                Some(&byte) => bytes.push(byte),
+                None => return Err(invalid("unterminated string")),
+            }
+        }
+        *input = rest.as_slice();
+        Ok(bytes)
+    }
+}
+
+impl EncodeKey for str {
+    fn encode_key(&self, out: &mut Vec<u8>) {
+        self.as_bytes().encode_key(out);
+    }
+}
+
+impl DecodeKey for String {
+    fn decode_key(input: &mut &[u8]) -> Result<Self> {
+        String::from_utf8(Vec::decode_key(input)?).map_err(|_| invalid("string is not UTF-8"))
+    }
+}
+
+impl EncodeKey for Vec<u8> {
+    fn encode_key(&self, out: &mut Vec<u8>) {
+        self.as_slice().encode_key(out);
+    }
+}
+
+impl EncodeKey for String {
+    fn encode_key(&self, out: &mut Vec<u8>) {
+        self.as_str().encode_key(out);
+    }
+}
+
+impl<T: EncodeKey + ?Sized> EncodeKey for &T {
+    fn encode_key(&self, out: &mut Vec<u8>) {
+        (**self).encode_key(out);
+    }
+}
+
+macro_rules! tuple_key {
+    ($($name:ident),+) => {
+        impl<$($name: EncodeKey),+> EncodeKey for ($($name,)+) {
+            fn encode_key(&self, out: &mut Vec<u8>) {
+                #[allow(non_snake_case)]
+                let ($($name,)+) = self;
+                $($name.encode_key(out);)+
+            }
+        }
+
+        impl<$($name: DecodeKey),+> DecodeKey for ($($name,)+) {
+            fn decode_key(input: &mut &[u8]) -> Result<Self> {
+                Ok(($($name::decode_key(input)?,)+))
+            }
+        }
+    };
+}
+
+tuple_key!(A);
+tuple_key!(A, B);
+tuple_key!(A, B, C);
+tuple_key!(A, B, C, D);
+tuple_key!(A, B, C, D, E);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Assert that `values`, given in natural order, encode to strictly
+    /// increasing keys that decode back to them.
+    fn assert_ordered<T: EncodeKey + DecodeKey + PartialEq + std::fmt::Debug>(values: &[T]) {
+        let keys: Vec<Key> = values.iter().map(encode).collect();
+        for pair in keys.windows(2) {
+            assert!(pair[0] < pair[1], "{:?} !< {:?}", pair[0], pair[1]);
+        }
+        for (value, key) in values.iter().zip(&keys) {
+            assert_eq!(&decode::<T>(key).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn test_numbers_keep_their_order() {
+        assert_ordered(&[0u64, 1, 255, 256, u64::MAX]);
+        assert_ordered(&[i64::MIN, -256, -1, 0, 1, 255, i64::MAX]);
+        assert_ordered(&[i32::MIN, -1, 0, i32::MAX]);
+        assert_ordered(&[
+            f64::NEG_INFINITY,
+            -1e300,
+            -1.5,
+            -f64::MIN_POSITIVE,
+            -0.0,
+            0.0,
+            f64::MIN_POSITIVE,
+            2.5,
+            f64::INFINITY,
+        ]);
+        assert!(decode::<f64>(&encode(&f64::NAN)).unwrap().is_nan());
+    }
+
+    #[test]
+    fn test_strings_and_tuples_keep_their_order() {
+        let strings: Vec<String> = ["", "\0", "\0\0", "a", "a\0", "a\0b", "ab", "b"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_ordered(&strings);
+        assert_ordered(&[
+            ("a".to_string(), 2u64),
+            ("a".to_string(), 10),
+            ("a\0".to_string(), 0),
+            ("ab".to_string(), 1),
+        ]);
+        assert_ordered(&[(-1i64, vec![0xffu8]), (-1, vec![0xff, 0]), (0, vec![])]);
+
+        let key = encode(&("user", 42u64, -7i64));
+        assert!(key.starts_with(&encode(&("user", 42u64))));
+        assert!(!key.starts_with(&encode("use")));
+        assert_eq!(
+            decode::<(String, u64, i64)>(&key).unwrap(),
+            ("user".to_string(), 42, -7)
+        );
+
+        assert!(decode::<u64>(&[1, 2]).is_err());
+        assert!(decode::<String>(b"abc").is_err());
+        assert!(decode::<u32>(&encode(&7u64)).is_err());
+    }
+}
@@ -0,0 +1,216 @@
+//! OBLIVION - Lists
+//! Sequences of values under one key, as Redis' lists, pushed and popped
+//! at either end, so a database can serve as a simple persistent queue.
+//!
+//! A list is a metadata record holding the sequence numbers of its first
+//! item and of the slot after its last, and one entry per item, in the
+//! database's data-type range (see
+//! [`DATA_TYPE_PREFIX`](super::database::DATA_TYPE_PREFIX)):
+//!
+//! ```text
+//! \xff\xfftl <encode(&key)>              -> (head, tail), bincode
+//! \xff\xfftl <encode(&(key, seq: i64))>  -> item
+//! ```
+//!
+//! `lpush` writes below `head`, `rpush` at `tail`, so items stay in list
+//! order by key (see [`keys`](super::keys)) and `lrange` is one range
+//! scan. Each call applies its items and the new metadata in one
+//! [`WriteBatch`]; pushes and pops of all lists are serialized by an
+//! engine-wide lock, as each reads the metadata it rewrites. A list
+//! whose last item is popped is removed.
+//!
+//! As hashes, lists are not plain keys: `get`, `delete`, `keys` and
+//! scans of a [`Database`] never see them.
+//!
+//! ## Example
+//! ```no_run
+//! use oblivion::config::Config;
+//! use oblivion::engine::Oblivion;
+//!
+//! let engine = Oblivion::open(Config::new("./data")).unwrap();
+//! engine.rpush(b"jobs", vec![b"a".to_vec(), b"b".to_vec()]).unwrap();
+//! engine.lpush(b"jobs", vec![b"urgent".to_vec()]).unwrap();
+//! assert_eq!(engine.lrange(b"jobs", 0, -1).unwrap().len(), 3);
+//! assert_eq!(engine.lpop(b"jobs").unwrap(), Some(b"urgent".to_vec()));
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+use crate::engine::batch::WriteBatch;
+use crate::engine::database::Database;
+use crate::engine::keys::encode;
+use crate::engine::options::ReadOptions;
+use crate::error::{OblivionError, Result};
+use crate::types::{Key, Value};
+
+/// First bytes of every list record, within a database.
+pub const LIST_PREFIX: &[u8] = b"\xff\xfftl";
+
+/// Where the items of a list are.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct ListMeta {
+    /// Sequence number of the first item.
+    head: i64,
+    /// Sequence number after the last item.
+    tail: i64,
+}
+
+impl ListMeta {
+    fn len(&self) -> usize {
+        (self.tail - self.head) as usize
+    }
+}
+
+impl Database<'_> {
+    /// Insert `values` at the head of the list `key`, one after the
+    /// other, returning the list's new length.
+    pub fn lpush(&self, key: &[u8], values: Vec<Value>) -> Result<usize> {
+        let _guard = self.engine().data_types.lock();
+        let mut meta = self.list_meta(key)?.unwrap_or_default();
+        let mut batch = WriteBatch::new();
+        for value in values {
+            meta.head -= 1;
+            batch.put(self.stored(&item_key(key, meta.head)), value);
+        }
+        self.write_list(key, meta, batch)
+    }
+
+    /// Append `values` to the tail of the list `key`, returning the
+    /// list's new length.
+    pub fn rpush(&self, key: &[u8], values: Vec<Value>) -> Result<usize> {
+        let _guard = self.engine().data_types.lock();
+        let mut meta = self.list_meta(key)?.unwrap_or_default();
+        let mut batch = WriteBatch::new();
+        for value in values {
+            batch.put(self.stored(&item_key(key, meta.tail)), value);
+            meta.tail += 1;
+        }
+        self.write_list(key, meta, batch)
+    }
+
+    /// Remove and return the first item of the list `key`, or `None` if
+    /// the list does not exist.
+    pub fn lpop(&self, key: &[u8]) -> Result<Option<Value>> {
+        let _guard = self.engine().data_types.lock();
+        let Some(mut meta) = self.list_meta(key)? else {
+            return Ok(None);
+        };
+        let item = self.stored(&item_key(key, meta.head));
+        let value = self.get_stored(&item)?.ok_or_else(|| {
+            OblivionError::Corruption(format!(
+                "list {:?} is missing its item {}",
+                String::from_utf8_lossy(key),
+                meta.head
+            ))
+        })?;
+        let mut batch = WriteBatch::new();
+        batch.delete(item);
+        meta.head += 1;
+        self.write_list(key, meta, batch)?;
+        Ok(Some(value))
+    }
+
+    /// Returns the items of the list `key` from index `start` to `stop`,
+    /// both inclusive. Negative indexes count from the end (-1 is the
+    /// last item) and out-of-range ones are clamped, as in Redis'
+    /// `LRANGE`.
+    pub fn lrange(&self, key: &[u8], start: i64, stop: i64) -> Result<Vec<Value>> {
+        let _guard = self.engine().data_types.lock();
+        let Some(meta) = self.list_meta(key)? else {
+            return Ok(Vec::new());
+        };
+        let len = meta.len() as i64;
+        let start = if start < 0 { start + len } else { start }.max(0);
+        let stop = if stop < 0 { stop + len } else { stop }.min(len - 1);
+        if start > stop {
+            return Ok(Vec::new());
+        }
+        let mut opts = ReadOptions::new();
+        opts.lower_bound = Some(self.stored(&item_key(key, meta.head + start)));
+        opts.upper_bound = Some(self.stored(&item_key(key, meta.head + stop + 1)));
+        let rows = self.engine().scan_opt(&opts)?;
+        Ok(rows.into_iter().map(|(_, value)| value).collect())
+    }
+
+    fn list_meta(&self, key: &[u8]) -> Result<Option<ListMeta>> {
+        let stored = self.stored(&meta_key(key));
+        match self.get_stored(&stored)? {
+            Some(bytes) => bincode::deserialize(&bytes)
+                .map(Some)
+                .map_err(|e| OblivionError::Serialization(e.to_string())),
+            None => Ok(None),
+        }
+    }
+
+    /// Apply `batch` with `meta` as the list's new metadata, removing it
+    /// once the list is empty, and return the list's length.
+    fn write_list(&self, key: &[u8], meta: ListMeta, mut batch: WriteBatch) -> Result<usize> {
+        let stored = self.stored(&meta_key(key));
+        if meta.len() == 0 {
+            batch.delete(stored);
+        } else {
+            let bytes = bincode::serialize(&meta)
+                .map_err(|e| OblivionError::Serialization(e.to_string()))?;
+            batch.put(stored, bytes);
+        }
+        self.engine().write(batch)?;
+        Ok(meta.len())
+    }
+}
+
+/// The key of the metadata of the list `key`, within a database.
+fn meta_key(key: &[u8]) -> Key {
+    [LIST_PREFIX, &encode(key)].concat()
+}
+
+/// The key of item `seq` of the list `key`, within a database.
+fn item_key(key: &[u8], seq: i64) -> Key {
+    [LIST_PREFIX, &encode(&(key, seq))].concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::engine::database::DATA_TYPE_PREFIX;
+    use crate::engine::Oblivion;
+
+    fn values(items: &[&str]) -> Vec<Value> {
+        items.iter().map(|item| item.as_bytes().to_vec()).collect()
+    }
+
+    #[test]
+    fn test_push_pop_and_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = Oblivion::open(Config::new(dir.path())).unwrap();
+        let db = engine.database(0).unwrap();
+        assert!(LIST_PREFIX.starts_with(DATA_TYPE_PREFIX));
+
+        assert_eq!(db.rpush(b"q", values(&["c", "d"])).unwrap(), 2);
+        assert_eq!(db.lpush(b"q", values(&["b", "a"])).unwrap(), 4);
+        assert_eq!(
+            db.lrange(b"q", 0, -1).unwrap(),
+            values(&["a", "b", "c", "d"])
+        );
+        assert_eq!(db.lrange(b"q", 1, 2).unwrap(), values(&["b", "c"]));
+        assert_eq!(db.lrange(b"q", -2, 100).unwrap(), values(&["c", "d"]));
+        assert_eq!(db.lrange(b"q", -100, 0).unwrap(), values(&["a"]));
+        assert_eq!(db.lrange(b"q", 3, 1).unwrap(), Vec::<Value>::new());
+        assert_eq!(db.lrange(b"q", 4, 10).unwrap(), Vec::<Value>::new());
+        assert_eq!(db.lrange(b"missing", 0, -1).unwrap(), Vec::<Value>::new());
+
+        assert_eq!(db.lpop(b"q").unwrap(), Some(b"a".to_vec()));
+        assert_eq!(db.lpop(b"q").unwrap(), Some(b"b".to_vec()));
+        assert_eq!(db.rpush(b"q", values(&["e"])).unwrap(), 3);
+        assert_eq!(db.lrange(b"q", 0, -1).unwrap(), values(&["c", "d", "e"]));
+        assert!(db.keys(b"*").unwrap().is_empty());
+
+        for _ in 0..3 {
+            assert!(db.lpop(b"q").unwrap().is_some());
+        }
+        assert_eq!(db.lpop(b"q").unwrap(), None);
+        // An emptied list leaves nothing behind
+        assert!(engine.scan().is_empty());
+        assert_eq!(db.lpush(b"q", values(&["x"])).unwrap(), 1);
+    }
+}
@@ -0,0 +1,226 @@
+//! OBLIVION - Manifest
+//! Durable record of which SSTables make up the tree, and of the
+//! comparator they are sorted by.
+//!
+//! Flushes and compactions first write their output tables, then switch
+//! the manifest atomically (write temp file, fsync, rename), and only
+//! then delete obsolete inputs. A crash at any point leaves either the
+//! old or the new set of tables live; files the manifest does not
+//! mention are leftovers and are removed on the next open.
+//!
+//! ## File Format
+//! ```text
+//! [crc: 4 bytes LE][bincode(Manifest)]
+//! ```
+//!
+//! Manifests written before the comparator was recorded end after the
+//! table list and load as byte-ordered.
+
+use std::io::Write;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::engine::comparator::{Comparator, BYTEWISE};
+use crate::engine::env::Env;
+use crate::engine::failpoint::fail_point;
+use crate::engine::io::SyncMethod;
+use crate::error::{OblivionError, Result};
+
+/// Live SSTable set and file number allocator.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Next unused SSTable file number.
+    pub next_file_number: u64,
+    /// File numbers of the live SSTables, oldest first.
+    pub tables: Vec<u64>,
+    /// Name of the comparator the tables are sorted by.
+    pub comparator: String,
+}
+
+/// A manifest from before the comparator was recorded.
+#[derive(Deserialize)]
+struct LegacyManifest {
+    next_file_number: u64,
+    tables: Vec<u64>,
+}
+
+impl Default for Manifest {
+    fn default() -> Self {
+        Self {
+            next_file_number: 0,
+            tables: Vec::new(),
+            comparator: BYTEWISE.to_string(),
+        }
+    }
+}
+
+impl Manifest {
+    /// Load the manifest at `path` on `env`. Returns `None` if it does
+    /// not exist.
+    pub fn load(env: &Env, path: &Path) -> Result<Option<Self>> {
+        let data = match env.read(path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        if data.len() < 4 {
+            return Err(OblivionError::Corruption(format!(
+                "manifest {:?} is truncated",
+                path
+            )));
+        }
+        let stored_crc = u32::from_le_bytes(data[0..4].try_into().unwrap());
+        if crc32fast::hash(&data[4..]) != stored_crc {
+            return Err(OblivionError::Corruption(format!(
+                "manifest {:?} failed CRC check",
+                path
+            )));
+        }
+        let manifest = bincode::deserialize(&data[4..]).or_else(|e| {
+            bincode::deserialize::<LegacyManifest>(&data[4..])
+                .map(|legacy| Manifest {
+                    next_file_number: legacy.next_file_number,
+                    tables: legacy.tables,
+                    comparator: BYTEWISE.to_string(),
+                })
+                .map_err(|_| OblivionError::Corruption(format!("manifest {:?}: {}", path, e)))
+        })?;
+        Ok(Some(manifest))
+    }
+
+    /// Atomically replace the manifest at `path` on `env` with this one.
+    pub fn save(&self, env: &Env, path: &Path) -> Result<()> {
+        self.write(env, path, || Ok(()))
+    }
+
+    /// Save as the manifest of the engine configured by `config`.
+    pub(crate) fn commit(&self, config: &Config) -> Result<()> {
+        self.write(&config.env, &config.manifest_path(), || {
+            fail_point!(config, MidManifestWrite);
+            Ok(())
+        })
+    }
+
+    /// Write and sync the manifest under a temporary name, call
+    /// `before_rename`, then rename it over `path`.
+    fn write(
+        &self,
+        env: &Env,
+        path: &Path,
+        before_rename: impl FnOnce() -> Result<()>,
+    ) -> Result<()> {
+        let payload =
+            bincode::serialize(self).map_err(|e| OblivionError::Serialization(e.to_string()))?;
+        let tmp_path = path.with_extension("tmp");
+        {
+            let mut file = env.create(&tmp_path, false)?;
+            file.write_all(&crc32fast::hash(&payload).to_le_bytes())?;
+            file.write_all(&payload)?;
+            file.sync(SyncMethod::Fsync)?;
+        }
+        before_rename()?;
+        env.rename(&tmp_path, path)?;
+        if let Some(dir) = path.parent() {
+            // Persist the rename itself
+            env.sync_dir(dir)?;
+        }
+        Ok(())
+    }
+
+    /// Fail unless the tables are sorted by `comparator`.
+    pub fn check_comparator(&self, comparator: &Comparator) -> Result<()> {
+        if self.comparator != comparator.name() {
+            return Err(OblivionError::Config(format!(
+                "database was created with the '{}' comparator, not '{}'",
+                self.comparator,
+                comparator.name()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Allocate a fresh SSTable file number.
+    pub fn new_file_number(&mut self) -> u64 {
+        let id = self.next_file_number;
+        self.next_file_number += 1;
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_missing_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(
+            Manifest::load(&Env::default(), &dir.path().join("MANIFEST")).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("MANIFEST");
+
+        let mut manifest = Manifest::default();
+        let a = manifest.new_file_number();
+        let b = manifest.new_file_number();
+        manifest.tables = vec![a, b];
+        manifest.save(&Env::default(), &path).unwrap();
+
+        let loaded = Manifest::load(&Env::default(), &path).unwrap().unwrap();
+        assert_eq!(loaded, manifest);
+        assert_eq!(loaded.next_file_number, 2);
+        assert!(!path.with_extension("tmp").exists());
+    }
+
+    #[test]
+    fn test_legacy_manifest_is_bytewise() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("MANIFEST");
+        let payload = bincode::serialize(&(4u64, vec![1u64, 3])).unwrap();
+        let mut data = crc32fast::hash(&payload).to_le_bytes().to_vec();
+        data.extend_from_slice(&payload);
+        fs::write(&path, &data).unwrap();
+
+        let loaded = Manifest::load(&Env::default(), &path).unwrap().unwrap();
+        assert_eq!(loaded.tables, vec![1, 3]);
+        assert_eq!(loaded.comparator, BYTEWISE);
+        assert!(loaded.check_comparator(&Comparator::default()).is_ok());
+        let other = Comparator::new(crate::engine::comparator::CaseInsensitive);
+        assert!(matches!(
+            loaded.check_comparator(&other),
+            Err(OblivionError::Config(_))
+        ));
+    }
+
+    #[test]
+    fn test_corruption_detected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("MANIFEST");
+        Manifest {
+            next_file_number: 7,
+            tables: vec![3, 5],
+            ..Manifest::default()
+        }
+        .save(&Env::default(), &path)
+        .unwrap();
+
+        let mut data = fs::read(&path).unwrap();
+        let last = data.len() - 1;
+        data[last] ^= 0xFF;
+        fs::write(&path, &data).unwrap();
+
+        assert!(matches!(
+            Manifest::load(&Env::default(), &path),
+            Err(OblivionError::Corruption(_))
+        ));
+    }
+}
@@ -8,12 +8,15 @@ use crate::types::{Key, Value};
 
 /// In-memory sorted key-value store backed by a BTreeMap.
 /// Serves as the write buffer in the LSM-Tree architecture.
+#[derive(Clone)]
 pub struct MemTable {
     /// Sorted map storing key-value pairs.
     /// A `None` value represents a tombstone (deletion marker).
     entries: BTreeMap<Key, Option<Value>>,
     /// Current approximate size in bytes.
     size_bytes: usize,
+    /// Number of entries that are tombstones.
+    tombstones: usize,
 }
 
 impl Default for MemTable {
@@ -28,6 +31,7 @@ impl MemTable {
         Self {
             entries: BTreeMap::new(),
             size_bytes: 0,
+            tombstones: 0,
         }
     }
 
@@ -41,6 +45,11 @@ impl MemTable {
         self.entries.len()
     }
 
+    /// Returns the number of entries that are tombstones.
+    pub fn tombstone_count(&self) -> usize {
+        self.tombstones
+    }
+
     /// Returns true if the MemTable is empty.
     pub fn is_empty(&self) -> bool {
         self.entries.is_empty()
@@ -52,6 +61,9 @@ impl MemTable {
         if let Some(old_val) = self.entries.get(&key) {
             let old_size = key.len() + old_val.as_ref().map_or(0, |v| v.len());
             self.size_bytes = self.size_bytes.saturating_sub(old_size);
+            if old_val.is_none() {
+                self.tombstones -= 1;
+            }
         }
         self.size_bytes += entry_size;
         self.entries.insert(key, Some(value));
@@ -66,6 +78,14 @@ impl MemTable {
         }
     }
 
+    /// Look up a key, distinguishing deleted keys from absent ones.
+    /// - `None` → key is not in the MemTable
+    /// - `Some(None)` → key is deleted (tombstone)
+    /// - `Some(Some(v))` → key maps to `v`
+    pub fn lookup(&self, key: &[u8]) -> Option<Option<&Value>> {
+        self.entries.get(key).map(|value| value.as_ref())
+    }
+
     /// Check if a key exists in the MemTable (including tombstones).
     pub fn contains_key(&self, key: &[u8]) -> bool {
         self.entries.contains_key(key)
@@ -74,18 +94,40 @@ impl MemTable {
     /// Delete a key by inserting a tombstone marker.
     pub fn delete(&mut self, key: Key) {
         let key_size = key.len();
-        if let Some(old_val) = self.entries.get(&key) {
-            let old_size = key.len() + old_val.as_ref().map_or(0, |v| v.len());
-            self.size_bytes = self.size_bytes.saturating_sub(old_size);
+        match self.entries.get(&key) {
+            Some(old_val) => {
+                let old_size = key.len() + old_val.as_ref().map_or(0, |v| v.len());
+                self.size_bytes = self.size_bytes.saturating_sub(old_size);
+                if old_val.is_some() {
+                    self.tombstones += 1;
+                }
+            }
+            None => self.tombstones += 1,
         }
         self.size_bytes += key_size;
         self.entries.insert(key, None);
     }
 
+    /// Apply the entries of `newer`, tombstones included, over this
+    /// MemTable's, as if its writes had come after this one's.
+    pub fn merge(&mut self, newer: MemTable) {
+        if self.is_empty() {
+            *self = newer;
+            return;
+        }
+        for (key, value) in newer.entries {
+            match value {
+                Some(value) => self.insert(key, value),
+                None => self.delete(key),
+            }
+        }
+    }
+
     /// Clear all entries from the MemTable and reset size.
     pub fn clear(&mut self) {
         self.entries.clear();
         self.size_bytes = 0;
+        self.tombstones = 0;
     }
 
     /// Returns a reference to the inner BTreeMap for iteration.
@@ -156,8 +198,15 @@ mod tests {
         let mut table = MemTable::new();
         table.insert(b"key".to_vec(), b"value".to_vec());
         table.delete(b"key".to_vec());
+        table.delete(b"key".to_vec());
         assert_eq!(table.get(b"key"), None);
         assert!(table.contains_key(b"key"));
+        assert_eq!(table.tombstone_count(), 1);
+        table.insert(b"key".to_vec(), b"again".to_vec());
+        assert_eq!(table.tombstone_count(), 0);
+        table.delete(b"key".to_vec());
+        assert_eq!(table.lookup(b"key"), Some(None));
+        assert_eq!(table.lookup(b"other"), None);
     }
 
     #[test]
@@ -178,6 +227,26 @@ mod tests {
         assert_eq!(table.size(), 0);
     }
 
+    #[test]
+    fn test_merge_applies_newer_writes() {
+        let mut older = MemTable::new();
+        older.insert(b"kept".to_vec(), b"1".to_vec());
+        older.insert(b"overwritten".to_vec(), b"old".to_vec());
+        older.insert(b"deleted".to_vec(), b"old".to_vec());
+        let mut newer = MemTable::new();
+        newer.insert(b"overwritten".to_vec(), b"new".to_vec());
+        newer.delete(b"deleted".to_vec());
+        newer.delete(b"absent".to_vec());
+
+        older.merge(newer);
+        assert_eq!(older.get(b"kept"), Some(&b"1".to_vec()));
+        assert_eq!(older.get(b"overwritten"), Some(&b"new".to_vec()));
+        assert_eq!(older.lookup(b"deleted"), Some(None));
+        assert_eq!(older.lookup(b"absent"), Some(None));
+        assert_eq!(older.size(), 4 + 1 + 11 + 3 + 7 + 6);
+        assert_eq!(older.tombstone_count(), 2);
+    }
+
     #[test]
     fn test_scan_sorted_order() {
         let mut table = MemTable::new();
@@ -4,10 +4,19 @@
 //!
 //! These metrics enable runtime introspection into engine
 //! behavior without impacting performance.
+//!
+//! With the `prometheus` feature, [`EngineMetrics::prometheus_text`]
+//! renders every metric in the Prometheus text exposition format and
+//! [`serve_prometheus`] exposes it on a minimal `/metrics` endpoint.
 
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Instant;
 
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::engine::wal::RecoveryStats;
+
 /// Atomic operation counters for the Oblivion engine.
 ///
 /// All counters use `Ordering::Relaxed` since we only need
@@ -30,8 +39,64 @@ pub struct EngineMetrics {
     pub bytes_read: AtomicU64,
     /// Number of WAL recovery operations.
     pub wal_recoveries: AtomicU64,
+    /// Background jobs that failed or panicked.
+    pub background_errors: AtomicU64,
+    /// Duration of the last WAL recovery in microseconds.
+    pub recovery_duration_micros: AtomicU64,
+    /// Bytes replayed by the last WAL recovery.
+    pub recovery_bytes_replayed: AtomicU64,
+    /// Records applied by the last WAL recovery.
+    pub recovery_records_applied: AtomicU64,
+    /// Corrupt or torn records skipped by the last WAL recovery.
+    pub recovery_records_skipped: AtomicU64,
+    /// Number of SSTable filter probes during point lookups.
+    pub filter_probes: AtomicU64,
+    /// Probes where the filter ruled the key out (table skipped).
+    pub filter_negatives: AtomicU64,
+    /// Probes where the filter said "maybe" but the table lacked the key.
+    pub filter_false_positives: AtomicU64,
+    /// Point lookups answered by the row cache.
+    pub row_cache_hits: AtomicU64,
+    /// Point lookups that missed the row cache and went to SSTables.
+    pub row_cache_misses: AtomicU64,
+    /// Data block reads answered by the block cache.
+    pub block_cache_hits: AtomicU64,
+    /// Data block reads that missed the block cache and read the file.
+    pub block_cache_misses: AtomicU64,
+    /// SSTable reads that found their file handle already open.
+    pub table_cache_hits: AtomicU64,
+    /// SSTable reads that had to (re)open the file.
+    pub table_cache_misses: AtomicU64,
+    /// Point lookups that reached the SSTable layer.
+    pub sstable_lookups: AtomicU64,
+    /// SSTables consulted (filter probed) across those lookups.
+    pub sstables_consulted: AtomicU64,
+    /// Data blocks read across those lookups.
+    pub blocks_read: AtomicU64,
+    /// Most SSTables consulted by a single lookup.
+    pub max_sstables_per_get: AtomicU64,
+    /// Most data blocks read by a single lookup.
+    pub max_blocks_per_get: AtomicU64,
+    /// Gauge: current MemTable size in bytes.
+    pub memtable_bytes: AtomicU64,
+    /// Gauge: number of SSTables in compaction tier 0.
+    pub l0_tables: AtomicU64,
+    /// Gauge: on-disk bytes in tiers that have reached the compaction threshold.
+    pub pending_compaction_bytes: AtomicU64,
+    /// Gauge: bytes of live SSTables on disk.
+    pub sstable_bytes: AtomicU64,
+    /// Gauge: current WAL size in bytes.
+    pub wal_bytes: AtomicU64,
+    /// Gauge: bytes of files in the SSTable directory that are no longer live.
+    pub obsolete_bytes: AtomicU64,
+    /// Gauge: 1 while the engine is read-only because its disk filled up.
+    pub degraded: AtomicU64,
+    /// Gauge: live SSTable count per compaction tier.
+    sstables_per_tier: Mutex<Vec<u64>>,
     /// Timestamp when the engine was opened.
     engine_started: Instant,
+    /// Start of the interval the counters cover (open or last reset).
+    counters_since: Mutex<Instant>,
 }
 
 impl EngineMetrics {
@@ -46,7 +111,35 @@ impl EngineMetrics {
             bytes_written: AtomicU64::new(0),
             bytes_read: AtomicU64::new(0),
             wal_recoveries: AtomicU64::new(0),
+            background_errors: AtomicU64::new(0),
+            recovery_duration_micros: AtomicU64::new(0),
+            recovery_bytes_replayed: AtomicU64::new(0),
+            recovery_records_applied: AtomicU64::new(0),
+            recovery_records_skipped: AtomicU64::new(0),
+            filter_probes: AtomicU64::new(0),
+            filter_negatives: AtomicU64::new(0),
+            filter_false_positives: AtomicU64::new(0),
+            row_cache_hits: AtomicU64::new(0),
+            row_cache_misses: AtomicU64::new(0),
+            block_cache_hits: AtomicU64::new(0),
+            block_cache_misses: AtomicU64::new(0),
+            table_cache_hits: AtomicU64::new(0),
+            table_cache_misses: AtomicU64::new(0),
+            sstable_lookups: AtomicU64::new(0),
+            sstables_consulted: AtomicU64::new(0),
+            blocks_read: AtomicU64::new(0),
+            max_sstables_per_get: AtomicU64::new(0),
+            max_blocks_per_get: AtomicU64::new(0),
+            memtable_bytes: AtomicU64::new(0),
+            l0_tables: AtomicU64::new(0),
+            pending_compaction_bytes: AtomicU64::new(0),
+            sstable_bytes: AtomicU64::new(0),
+            wal_bytes: AtomicU64::new(0),
+            obsolete_bytes: AtomicU64::new(0),
+            degraded: AtomicU64::new(0),
+            sstables_per_tier: Mutex::new(Vec::new()),
             engine_started: Instant::now(),
+            counters_since: Mutex::new(Instant::now()),
         }
     }
 
@@ -85,6 +178,155 @@ impl EngineMetrics {
         self.wal_recoveries.fetch_add(1, Ordering::Relaxed);
     }
 
+    /// Record a failed or panicked background job.
+    pub fn record_background_error(&self) {
+        self.background_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record that the engine turned read-only because its disk filled up.
+    pub fn record_degraded(&self) {
+        self.degraded.store(1, Ordering::Relaxed);
+    }
+
+    /// Record the outcome of the WAL replay performed on open.
+    pub fn record_recovery_stats(&self, stats: &RecoveryStats) {
+        self.recovery_duration_micros
+            .store(stats.duration.as_micros() as u64, Ordering::Relaxed);
+        self.recovery_bytes_replayed
+            .store(stats.bytes_replayed, Ordering::Relaxed);
+        self.recovery_records_applied
+            .store(stats.records_applied, Ordering::Relaxed);
+        self.recovery_records_skipped
+            .store(stats.records_skipped, Ordering::Relaxed);
+    }
+
+    /// Record the outcome of an SSTable filter probe.
+    /// `may_contain` is the filter's answer; `found` is whether the table
+    /// actually held the key (only meaningful when the filter said yes).
+    pub fn record_filter_probe(&self, may_contain: bool, found: bool) {
+        self.filter_probes.fetch_add(1, Ordering::Relaxed);
+        if !may_contain {
+            self.filter_negatives.fetch_add(1, Ordering::Relaxed);
+        } else if !found {
+            self.filter_false_positives.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Fraction of filter probes that skipped a table read.
+    pub fn filter_skip_rate(&self) -> f64 {
+        let probes = self.filter_probes.load(Ordering::Relaxed);
+        if probes == 0 {
+            return 0.0;
+        }
+        self.filter_negatives.load(Ordering::Relaxed) as f64 / probes as f64
+    }
+
+    /// Observed false positive rate: false positives among all probes
+    /// for keys the table did not contain.
+    pub fn filter_false_positive_rate(&self) -> f64 {
+        let negatives = self.filter_negatives.load(Ordering::Relaxed);
+        let false_positives = self.filter_false_positives.load(Ordering::Relaxed);
+        if negatives + false_positives == 0 {
+            return 0.0;
+        }
+        false_positives as f64 / (negatives + false_positives) as f64
+    }
+
+    /// Record a row cache lookup.
+    pub fn record_row_cache(&self, hit: bool) {
+        if hit {
+            self.row_cache_hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.row_cache_misses.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Record a block cache lookup.
+    pub fn record_block_cache(&self, hit: bool) {
+        if hit {
+            self.block_cache_hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.block_cache_misses.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Record a table cache (file handle) lookup.
+    pub fn record_table_cache(&self, hit: bool) {
+        if hit {
+            self.table_cache_hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.table_cache_misses.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Record the read amplification of one lookup that searched the
+    /// SSTables: how many tables it consulted and data blocks it read.
+    pub fn record_read_amplification(&self, tables: u64, blocks: u64) {
+        self.sstable_lookups.fetch_add(1, Ordering::Relaxed);
+        self.sstables_consulted.fetch_add(tables, Ordering::Relaxed);
+        self.blocks_read.fetch_add(blocks, Ordering::Relaxed);
+        self.max_sstables_per_get
+            .fetch_max(tables, Ordering::Relaxed);
+        self.max_blocks_per_get.fetch_max(blocks, Ordering::Relaxed);
+    }
+
+    /// Average SSTables consulted per lookup that reached the SSTables.
+    pub fn avg_sstables_per_get(&self) -> f64 {
+        per_lookup(&self.sstables_consulted, &self.sstable_lookups)
+    }
+
+    /// Average data blocks read per lookup that reached the SSTables.
+    pub fn avg_blocks_per_get(&self) -> f64 {
+        per_lookup(&self.blocks_read, &self.sstable_lookups)
+    }
+
+    /// Update the MemTable size gauge.
+    pub fn set_memtable_bytes(&self, bytes: u64) {
+        self.memtable_bytes.store(bytes, Ordering::Relaxed);
+    }
+
+    /// Update the tree shape gauges after the SSTable set changed.
+    pub fn set_tree_shape(&self, l0_tables: u64, pending_compaction_bytes: u64) {
+        self.l0_tables.store(l0_tables, Ordering::Relaxed);
+        self.pending_compaction_bytes
+            .store(pending_compaction_bytes, Ordering::Relaxed);
+    }
+
+    /// Update the WAL size gauge.
+    pub fn set_wal_bytes(&self, bytes: u64) {
+        self.wal_bytes.store(bytes, Ordering::Relaxed);
+    }
+
+    /// Update the disk usage and file inventory gauges.
+    pub fn set_disk_usage(&self, usage: &DiskUsage) {
+        self.sstable_bytes
+            .store(usage.sstable_bytes, Ordering::Relaxed);
+        self.wal_bytes.store(usage.wal_bytes, Ordering::Relaxed);
+        self.obsolete_bytes
+            .store(usage.obsolete_bytes, Ordering::Relaxed);
+        *self.sstables_per_tier.lock() = usage.sstables_per_tier.clone();
+    }
+
+    /// Returns the live SSTable count per compaction tier.
+    pub fn sstables_per_tier(&self) -> Vec<u64> {
+        self.sstables_per_tier.lock().clone()
+    }
+
+    /// Fraction of row cache lookups that were hits.
+    pub fn row_cache_hit_rate(&self) -> f64 {
+        hit_rate(&self.row_cache_hits, &self.row_cache_misses)
+    }
+
+    /// Fraction of block cache lookups that were hits.
+    pub fn block_cache_hit_rate(&self) -> f64 {
+        hit_rate(&self.block_cache_hits, &self.block_cache_misses)
+    }
+
+    /// Fraction of table cache lookups that were hits.
+    pub fn table_cache_hit_rate(&self) -> f64 {
+        hit_rate(&self.table_cache_hits, &self.table_cache_misses)
+    }
+
     /// Get engine uptime in seconds.
     pub fn uptime_secs(&self) -> f64 {
         self.engine_started.elapsed().as_secs_f64()
@@ -98,13 +340,90 @@ impl EngineMetrics {
             + self.scans.load(Ordering::Relaxed)
     }
 
-    /// Get operations per second since engine start.
+    /// Get operations per second since engine start (or the last
+    /// [`snapshot_and_reset`](Self::snapshot_and_reset)).
     pub fn ops_per_sec(&self) -> f64 {
-        let uptime = self.uptime_secs();
-        if uptime < 0.001 {
+        let interval = self.counters_since.lock().elapsed().as_secs_f64();
+        if interval < 0.001 {
             return 0.0;
         }
-        self.total_ops() as f64 / uptime
+        self.total_ops() as f64 / interval
+    }
+
+    /// Capture every counter and derived rate as a plain, serializable value.
+    ///
+    /// Meant for embedding engine stats in a service's own health or
+    /// status endpoints rather than parsing [`report`](Self::report).
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let interval = self.counters_since.lock().elapsed();
+        self.capture(false, interval.as_secs_f64())
+    }
+
+    /// Capture a snapshot and reset every counter (and per-get maximum)
+    /// to zero, so the next snapshot covers only the following interval.
+    /// Gauges are left untouched.
+    ///
+    /// Each counter is swapped atomically, so no increment is lost or
+    /// counted twice between consecutive calls.
+    pub fn snapshot_and_reset(&self) -> MetricsSnapshot {
+        let mut counters_since = self.counters_since.lock();
+        let snapshot = self.capture(true, counters_since.elapsed().as_secs_f64());
+        *counters_since = Instant::now();
+        snapshot
+    }
+
+    /// Read (or, with `reset`, swap out) all metrics into a snapshot
+    /// whose counters cover `interval_secs`.
+    fn capture(&self, reset: bool, interval_secs: f64) -> MetricsSnapshot {
+        let take = |counter: &AtomicU64| {
+            if reset {
+                counter.swap(0, Ordering::Relaxed)
+            } else {
+                counter.load(Ordering::Relaxed)
+            }
+        };
+        let gauge = |gauge: &AtomicU64| gauge.load(Ordering::Relaxed);
+        MetricsSnapshot {
+            puts: take(&self.puts),
+            gets: take(&self.gets),
+            deletes: take(&self.deletes),
+            scans: take(&self.scans),
+            flushes: take(&self.flushes),
+            bytes_written: take(&self.bytes_written),
+            bytes_read: take(&self.bytes_read),
+            wal_recoveries: take(&self.wal_recoveries),
+            background_errors: take(&self.background_errors),
+            recovery_duration_micros: gauge(&self.recovery_duration_micros),
+            recovery_bytes_replayed: gauge(&self.recovery_bytes_replayed),
+            recovery_records_applied: gauge(&self.recovery_records_applied),
+            recovery_records_skipped: gauge(&self.recovery_records_skipped),
+            filter_probes: take(&self.filter_probes),
+            filter_negatives: take(&self.filter_negatives),
+            filter_false_positives: take(&self.filter_false_positives),
+            row_cache_hits: take(&self.row_cache_hits),
+            row_cache_misses: take(&self.row_cache_misses),
+            block_cache_hits: take(&self.block_cache_hits),
+            block_cache_misses: take(&self.block_cache_misses),
+            table_cache_hits: take(&self.table_cache_hits),
+            table_cache_misses: take(&self.table_cache_misses),
+            sstable_lookups: take(&self.sstable_lookups),
+            sstables_consulted: take(&self.sstables_consulted),
+            blocks_read: take(&self.blocks_read),
+            max_sstables_per_get: take(&self.max_sstables_per_get),
+            max_blocks_per_get: take(&self.max_blocks_per_get),
+            memtable_bytes: gauge(&self.memtable_bytes),
+            l0_tables: gauge(&self.l0_tables),
+            pending_compaction_bytes: gauge(&self.pending_compaction_bytes),
+            sstable_bytes: gauge(&self.sstable_bytes),
+            wal_bytes: gauge(&self.wal_bytes),
+            obsolete_bytes: gauge(&self.obsolete_bytes),
+            degraded: gauge(&self.degraded),
+            sstables_per_tier: self.sstables_per_tier(),
+            interval_secs,
+            uptime_secs: self.uptime_secs(),
+            ..MetricsSnapshot::default()
+        }
+        .with_derived()
     }
 
     /// Format metrics as a human-readable report.
@@ -117,6 +436,7 @@ impl EngineMetrics {
                deletes:   {}\n\
                scans:     {}\n\
                flushes:   {}\n\
+               background errors: {}\n\
              Throughput:\n\
                total ops: {}\n\
                ops/sec:   {:.2}\n\
@@ -125,22 +445,658 @@ impl EngineMetrics {
                read:      {} bytes\n\
              Recovery:\n\
                wal recoveries: {}\n\
+               last replay: {} records, {} bytes in {} us ({} skipped)\n\
+             Filters:\n\
+               probes:    {}\n\
+               skipped:   {} ({:.2}%)\n\
+               false pos: {} (observed FPR {:.4})\n\
+             Caches:\n\
+               row:       {} hits / {} misses ({:.2}%)\n\
+               block:     {} hits / {} misses ({:.2}%)\n\
+               table:     {} hits / {} misses ({:.2}%)\n\
+             Read amplification:\n\
+               tables/get: {:.2} avg, {} max\n\
+               blocks/get: {:.2} avg, {} max\n\
+             Tree:\n\
+               memtable:  {} bytes\n\
+               l0 tables: {}\n\
+               pending compaction: {} bytes\n\
+               tables per tier: {:?}\n\
+             Disk:\n\
+               sstables:  {} bytes\n\
+               wal:       {} bytes\n\
+               obsolete:  {} bytes\n\
+               degraded:  {}\n\
              Uptime: {:.2}s",
             self.puts.load(Ordering::Relaxed),
             self.gets.load(Ordering::Relaxed),
             self.deletes.load(Ordering::Relaxed),
             self.scans.load(Ordering::Relaxed),
             self.flushes.load(Ordering::Relaxed),
+            self.background_errors.load(Ordering::Relaxed),
             self.total_ops(),
             self.ops_per_sec(),
             self.bytes_written.load(Ordering::Relaxed),
             self.bytes_read.load(Ordering::Relaxed),
             self.wal_recoveries.load(Ordering::Relaxed),
+            self.recovery_records_applied.load(Ordering::Relaxed),
+            self.recovery_bytes_replayed.load(Ordering::Relaxed),
+            self.recovery_duration_micros.load(Ordering::Relaxed),
+            self.recovery_records_skipped.load(Ordering::Relaxed),
+            self.filter_probes.load(Ordering::Relaxed),
+            self.filter_negatives.load(Ordering::Relaxed),
+            self.filter_skip_rate() * 100.0,
+            self.filter_false_positives.load(Ordering::Relaxed),
+            self.filter_false_positive_rate(),
+            self.row_cache_hits.load(Ordering::Relaxed),
+            self.row_cache_misses.load(Ordering::Relaxed),
+            self.row_cache_hit_rate() * 100.0,
+            self.block_cache_hits.load(Ordering::Relaxed),
+            self.block_cache_misses.load(Ordering::Relaxed),
+            self.block_cache_hit_rate() * 100.0,
+            self.table_cache_hits.load(Ordering::Relaxed),
+            self.table_cache_misses.load(Ordering::Relaxed),
+            self.table_cache_hit_rate() * 100.0,
+            self.avg_sstables_per_get(),
+            self.max_sstables_per_get.load(Ordering::Relaxed),
+            self.avg_blocks_per_get(),
+            self.max_blocks_per_get.load(Ordering::Relaxed),
+            self.memtable_bytes.load(Ordering::Relaxed),
+            self.l0_tables.load(Ordering::Relaxed),
+            self.pending_compaction_bytes.load(Ordering::Relaxed),
+            self.sstables_per_tier(),
+            self.sstable_bytes.load(Ordering::Relaxed),
+            self.wal_bytes.load(Ordering::Relaxed),
+            self.obsolete_bytes.load(Ordering::Relaxed),
+            self.degraded.load(Ordering::Relaxed) == 1,
             self.uptime_secs(),
         )
     }
 }
 
+#[cfg(feature = "prometheus")]
+impl EngineMetrics {
+    /// Render all metrics in the Prometheus text exposition format (v0.0.4).
+    pub fn prometheus_text(&self) -> String {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed) as f64;
+        let families = [
+            (
+                "oblivion_operations_total",
+                "Engine operations by type.",
+                "counter",
+                vec![
+                    ("op=\"put\"", load(&self.puts)),
+                    ("op=\"get\"", load(&self.gets)),
+                    ("op=\"delete\"", load(&self.deletes)),
+                    ("op=\"scan\"", load(&self.scans)),
+                ],
+            ),
+            (
+                "oblivion_flushes_total",
+                "MemTable flushes to SSTables.",
+                "counter",
+                vec![("", load(&self.flushes))],
+            ),
+            (
+                "oblivion_bytes_total",
+                "Key and value bytes written and value bytes read.",
+                "counter",
+                vec![
+                    ("direction=\"written\"", load(&self.bytes_written)),
+                    ("direction=\"read\"", load(&self.bytes_read)),
+                ],
+            ),
+            (
+                "oblivion_background_errors_total",
+                "Background jobs that failed or panicked.",
+                "counter",
+                vec![("", load(&self.background_errors))],
+            ),
+            (
+                "oblivion_wal_recoveries_total",
+                "WAL recoveries performed on open.",
+                "counter",
+                vec![("", load(&self.wal_recoveries))],
+            ),
+            (
+                "oblivion_recovery_duration_seconds",
+                "Duration of the WAL replay on open.",
+                "gauge",
+                vec![("", load(&self.recovery_duration_micros) / 1e6)],
+            ),
+            (
+                "oblivion_recovery_bytes",
+                "Bytes replayed from the WAL on open.",
+                "gauge",
+                vec![("", load(&self.recovery_bytes_replayed))],
+            ),
+            (
+                "oblivion_recovery_records",
+                "WAL records replayed on open, by outcome.",
+                "gauge",
+                vec![
+                    ("outcome=\"applied\"", load(&self.recovery_records_applied)),
+                    ("outcome=\"skipped\"", load(&self.recovery_records_skipped)),
+                ],
+            ),
+            (
+                "oblivion_filter_probes_total",
+                "SSTable filter probes by outcome.",
+                "counter",
+                vec![
+                    ("outcome=\"negative\"", load(&self.filter_negatives)),
+                    (
+                        "outcome=\"false_positive\"",
+                        load(&self.filter_false_positives),
+                    ),
+                    (
+                        "outcome=\"positive\"",
+                        load(&self.filter_probes)
+                            - load(&self.filter_negatives)
+                            - load(&self.filter_false_positives),
+                    ),
+                ],
+            ),
+            (
+                "oblivion_cache_hits_total",
+                "Cache lookups that were hits.",
+                "counter",
+                vec![
+                    ("cache=\"row\"", load(&self.row_cache_hits)),
+                    ("cache=\"block\"", load(&self.block_cache_hits)),
+                    ("cache=\"table\"", load(&self.table_cache_hits)),
+                ],
+            ),
+            (
+                "oblivion_cache_misses_total",
+                "Cache lookups that were misses.",
+                "counter",
+                vec![
+                    ("cache=\"row\"", load(&self.row_cache_misses)),
+                    ("cache=\"block\"", load(&self.block_cache_misses)),
+                    ("cache=\"table\"", load(&self.table_cache_misses)),
+                ],
+            ),
+            (
+                "oblivion_sstable_lookups_total",
+                "Point lookups that reached the SSTable layer.",
+                "counter",
+                vec![("", load(&self.sstable_lookups))],
+            ),
+            (
+                "oblivion_read_amplification_total",
+                "SSTables consulted and data blocks read by point lookups.",
+                "counter",
+                vec![
+                    ("unit=\"table\"", load(&self.sstables_consulted)),
+                    ("unit=\"block\"", load(&self.blocks_read)),
+                ],
+            ),
+            (
+                "oblivion_read_amplification_max",
+                "Most SSTables or blocks touched by a single lookup.",
+                "gauge",
+                vec![
+                    ("unit=\"table\"", load(&self.max_sstables_per_get)),
+                    ("unit=\"block\"", load(&self.max_blocks_per_get)),
+                ],
+            ),
+            (
+                "oblivion_memtable_bytes",
+                "Current MemTable size in bytes.",
+                "gauge",
+                vec![("", load(&self.memtable_bytes))],
+            ),
+            (
+                "oblivion_l0_tables",
+                "SSTables in compaction tier 0.",
+                "gauge",
+                vec![("", load(&self.l0_tables))],
+            ),
+            (
+                "oblivion_pending_compaction_bytes",
+                "On-disk bytes in tiers due for compaction.",
+                "gauge",
+                vec![("", load(&self.pending_compaction_bytes))],
+            ),
+            (
+                "oblivion_disk_bytes",
+                "On-disk bytes by file kind.",
+                "gauge",
+                vec![
+                    ("kind=\"sstable\"", load(&self.sstable_bytes)),
+                    ("kind=\"wal\"", load(&self.wal_bytes)),
+                    ("kind=\"obsolete\"", load(&self.obsolete_bytes)),
+                ],
+            ),
+            (
+                "oblivion_degraded",
+                "1 while the engine is read-only because its disk filled up.",
+                "gauge",
+                vec![("", load(&self.degraded))],
+            ),
+            (
+                "oblivion_ops_per_second",
+                "Average operations per second since open.",
+                "gauge",
+                vec![("", self.ops_per_sec())],
+            ),
+            (
+                "oblivion_uptime_seconds",
+                "Seconds since the engine was opened.",
+                "gauge",
+                vec![("", self.uptime_secs())],
+            ),
+        ];
+
+        let mut out = String::new();
+        for (name, help, kind, samples) in families {
+            out.push_str(&format!(
+                "# HELP {} {}\n# TYPE {} {}\n",
+                name, help, name, kind
+            ));
+            for (labels, value) in samples {
+                if labels.is_empty() {
+                    out.push_str(&format!("{} {}\n", name, value));
+                } else {
+                    out.push_str(&format!("{}{{{}}} {}\n", name, labels, value));
+                }
+            }
+        }
+        out.push_str(
+            "# HELP oblivion_sstables Live SSTables per compaction tier.\n\
+             # TYPE oblivion_sstables gauge\n",
+        );
+        for (tier, count) in self.sstables_per_tier().iter().enumerate() {
+            out.push_str(&format!(
+                "oblivion_sstables{{tier=\"{}\"}} {}\n",
+                tier, count
+            ));
+        }
+        out
+    }
+}
+
+/// Longest request line the metrics listener reads, in bytes.
+#[cfg(feature = "prometheus")]
+const PROMETHEUS_MAX_REQUEST_LINE: u64 = 8192;
+
+/// Give up on a metrics client that stalls reading or writing.
+#[cfg(feature = "prometheus")]
+const PROMETHEUS_IO_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// How often the metrics accept loop checks for shutdown.
+#[cfg(feature = "prometheus")]
+const PROMETHEUS_ACCEPT_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(50);
+
+/// A running `/metrics` listener, from [`serve_prometheus`]. Dropping
+/// it stops the listener, closes open connections and joins its
+/// threads.
+#[cfg(feature = "prometheus")]
+pub struct PrometheusServer {
+    local_addr: std::net::SocketAddr,
+    shared: std::sync::Arc<PrometheusShared>,
+    acceptor: Option<std::thread::JoinHandle<()>>,
+}
+
+#[cfg(feature = "prometheus")]
+struct PrometheusShared {
+    metrics: std::sync::Arc<EngineMetrics>,
+    stop: std::sync::atomic::AtomicBool,
+    /// Open connections, kept to shut them down.
+    connections: Mutex<Vec<(std::net::TcpStream, std::thread::JoinHandle<()>)>>,
+}
+
+/// Serve `metrics` in Prometheus format on `GET /metrics` at `addr`.
+///
+/// One thread accepts connections and each is answered on a thread of
+/// its own, so a slow or idle client does not hold up other scrapes.
+/// A client gets 5 seconds to send a request line of at most 8 KiB and
+/// to read the reply.
+#[cfg(feature = "prometheus")]
+pub fn serve_prometheus(
+    metrics: std::sync::Arc<EngineMetrics>,
+    addr: impl std::net::ToSocketAddrs,
+) -> std::io::Result<PrometheusServer> {
+    let listener = std::net::TcpListener::bind(addr)?;
+    listener.set_nonblocking(true)?;
+    let local_addr = listener.local_addr()?;
+    let shared = std::sync::Arc::new(PrometheusShared {
+        metrics,
+        stop: std::sync::atomic::AtomicBool::new(false),
+        connections: Mutex::new(Vec::new()),
+    });
+    let acceptor = {
+        let shared = std::sync::Arc::clone(&shared);
+        std::thread::Builder::new()
+            .name("oblivion-metrics-accept".to_string())
+            .spawn(move || shared.accept_loop(listener))?
+    };
+    Ok(PrometheusServer {
+        local_addr,
+        shared,
+        acceptor: Some(acceptor),
+    })
+}
+
+#[cfg(feature = "prometheus")]
+impl PrometheusServer {
+    /// Returns the address the listener is bound to (useful with port 0).
+    pub fn local_addr(&self) -> std::net::SocketAddr {
+        self.local_addr
+    }
+}
+
+#[cfg(feature = "prometheus")]
+impl Drop for PrometheusServer {
+    fn drop(&mut self) {
+        self.shared.stop.store(true, Ordering::Release);
+        if let Some(acceptor) = self.acceptor.take() {
+            let _ = acceptor.join();
+        }
+        let connections = std::mem::take(&mut *self.shared.connections.lock());
+        for (stream, handle) in connections {
+            let _ = stream.shutdown(std::net::Shutdown::Both);
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(feature = "prometheus")]
+impl PrometheusShared {
+    fn accept_loop(self: std::sync::Arc<Self>, listener: std::net::TcpListener) {
+        while !self.stop.load(Ordering::Acquire) {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    if let Err(e) = self.spawn_connection(stream) {
+                        log::warn!("metrics listener: {}", e);
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    std::thread::sleep(PROMETHEUS_ACCEPT_POLL_INTERVAL)
+                }
+                Err(e) => {
+                    log::warn!("metrics listener: {}", e);
+                    std::thread::sleep(PROMETHEUS_ACCEPT_POLL_INTERVAL);
+                }
+            }
+        }
+    }
+
+    fn spawn_connection(
+        self: &std::sync::Arc<Self>,
+        stream: std::net::TcpStream,
+    ) -> std::io::Result<()> {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(PROMETHEUS_IO_TIMEOUT))?;
+        stream.set_write_timeout(Some(PROMETHEUS_IO_TIMEOUT))?;
+        let handle = {
+            let shared = std::sync::Arc::clone(self);
+            let stream = stream.try_clone()?;
+            std::thread::Builder::new()
+                .name("oblivion-metrics-conn".to_string())
+                .spawn(move || {
+                    if let Err(e) = shared.respond(stream) {
+                        log::debug!("metrics listener: {}", e);
+                    }
+                })?
+        };
+        let mut connections = self.connections.lock();
+        connections.retain(|(_, handle)| !handle.is_finished());
+        connections.push((stream, handle));
+        Ok(())
+    }
+
+    /// Answer the one request of a connection.
+    fn respond(&self, mut stream: std::net::TcpStream) -> std::io::Result<()> {
+        use std::io::{BufRead, BufReader, Read, Write};
+
+        let mut request_line = String::new();
+        BufReader::new((&stream).take(PROMETHEUS_MAX_REQUEST_LINE)).read_line(&mut request_line)?;
+        let (status, body) = if request_line.starts_with("GET /metrics ") {
+            ("200 OK", self.metrics.prometheus_text())
+        } else {
+            ("404 Not Found", String::from("not found\n"))
+        };
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        );
+        stream.write_all(response.as_bytes())?;
+        // The listener holds a clone of the stream, so dropping ours
+        // would not close the connection
+        stream.shutdown(std::net::Shutdown::Write)
+    }
+}
+
+/// Point-in-time copy of [`EngineMetrics`], serializable with serde.
+///
+/// Counters are loaded individually with relaxed ordering, so a snapshot
+/// taken under concurrent load is approximate across fields.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MetricsSnapshot {
+    /// Total number of `put` operations.
+    pub puts: u64,
+    /// Total number of `get` operations.
+    pub gets: u64,
+    /// Total number of `delete` operations.
+    pub deletes: u64,
+    /// Total number of `scan` operations.
+    pub scans: u64,
+    /// Total number of flush events.
+    pub flushes: u64,
+    /// Total bytes written (keys + values).
+    pub bytes_written: u64,
+    /// Total bytes read (values returned by get).
+    pub bytes_read: u64,
+    /// Number of WAL recovery operations.
+    pub wal_recoveries: u64,
+    /// Background jobs that failed or panicked.
+    pub background_errors: u64,
+    /// Duration of the last WAL recovery in microseconds.
+    pub recovery_duration_micros: u64,
+    /// Bytes replayed by the last WAL recovery.
+    pub recovery_bytes_replayed: u64,
+    /// Records applied by the last WAL recovery.
+    pub recovery_records_applied: u64,
+    /// Corrupt or torn records skipped by the last WAL recovery.
+    pub recovery_records_skipped: u64,
+    /// Number of SSTable filter probes.
+    pub filter_probes: u64,
+    /// Probes where the filter ruled the key out.
+    pub filter_negatives: u64,
+    /// Probes where the filter said "maybe" but the table lacked the key.
+    pub filter_false_positives: u64,
+    /// Row cache hits.
+    pub row_cache_hits: u64,
+    /// Row cache misses.
+    pub row_cache_misses: u64,
+    /// Block cache hits.
+    pub block_cache_hits: u64,
+    /// Block cache misses.
+    pub block_cache_misses: u64,
+    /// Table cache hits.
+    pub table_cache_hits: u64,
+    /// Table cache misses.
+    pub table_cache_misses: u64,
+    /// Point lookups that reached the SSTable layer.
+    pub sstable_lookups: u64,
+    /// SSTables consulted across those lookups.
+    pub sstables_consulted: u64,
+    /// Data blocks read across those lookups.
+    pub blocks_read: u64,
+    /// Most SSTables consulted by a single lookup.
+    pub max_sstables_per_get: u64,
+    /// Most data blocks read by a single lookup.
+    pub max_blocks_per_get: u64,
+    /// Average SSTables consulted per lookup.
+    pub avg_sstables_per_get: f64,
+    /// Average data blocks read per lookup.
+    pub avg_blocks_per_get: f64,
+    /// Current MemTable size in bytes.
+    pub memtable_bytes: u64,
+    /// Number of SSTables in compaction tier 0.
+    pub l0_tables: u64,
+    /// On-disk bytes in tiers that have reached the compaction threshold.
+    pub pending_compaction_bytes: u64,
+    /// Bytes of live SSTables on disk.
+    pub sstable_bytes: u64,
+    /// Current WAL size in bytes.
+    pub wal_bytes: u64,
+    /// Bytes of files in the SSTable directory that are no longer live.
+    pub obsolete_bytes: u64,
+    /// 1 while the engine is read-only because its disk filled up.
+    pub degraded: u64,
+    /// Live SSTable count per compaction tier.
+    pub sstables_per_tier: Vec<u64>,
+    /// Puts + gets + deletes + scans.
+    pub total_ops: u64,
+    /// Average operations per second over `interval_secs`.
+    pub ops_per_sec: f64,
+    /// Fraction of filter probes that skipped a table read.
+    pub filter_skip_rate: f64,
+    /// Observed filter false positive rate.
+    pub filter_false_positive_rate: f64,
+    /// Fraction of row cache lookups that were hits.
+    pub row_cache_hit_rate: f64,
+    /// Fraction of block cache lookups that were hits.
+    pub block_cache_hit_rate: f64,
+    /// Fraction of table cache lookups that were hits.
+    pub table_cache_hit_rate: f64,
+    /// Seconds covered by the counters (since open or the last reset).
+    pub interval_secs: f64,
+    /// Seconds since the engine was opened.
+    pub uptime_secs: f64,
+}
+
+impl MetricsSnapshot {
+    /// Change since an `earlier` snapshot of the same metrics: counters
+    /// become differences, gauges and per-get maximums keep this
+    /// snapshot's values, and rates are recomputed over the interval.
+    ///
+    /// Counters that went backwards (a reset in between) are taken as-is.
+    pub fn delta(&self, earlier: &MetricsSnapshot) -> MetricsSnapshot {
+        let diff = |now: u64, before: u64| now.checked_sub(before).unwrap_or(now);
+        MetricsSnapshot {
+            puts: diff(self.puts, earlier.puts),
+            gets: diff(self.gets, earlier.gets),
+            deletes: diff(self.deletes, earlier.deletes),
+            scans: diff(self.scans, earlier.scans),
+            flushes: diff(self.flushes, earlier.flushes),
+            bytes_written: diff(self.bytes_written, earlier.bytes_written),
+            bytes_read: diff(self.bytes_read, earlier.bytes_read),
+            wal_recoveries: diff(self.wal_recoveries, earlier.wal_recoveries),
+            background_errors: diff(self.background_errors, earlier.background_errors),
+            filter_probes: diff(self.filter_probes, earlier.filter_probes),
+            filter_negatives: diff(self.filter_negatives, earlier.filter_negatives),
+            filter_false_positives: diff(
+                self.filter_false_positives,
+                earlier.filter_false_positives,
+            ),
+            row_cache_hits: diff(self.row_cache_hits, earlier.row_cache_hits),
+            row_cache_misses: diff(self.row_cache_misses, earlier.row_cache_misses),
+            block_cache_hits: diff(self.block_cache_hits, earlier.block_cache_hits),
+            block_cache_misses: diff(self.block_cache_misses, earlier.block_cache_misses),
+            table_cache_hits: diff(self.table_cache_hits, earlier.table_cache_hits),
+            table_cache_misses: diff(self.table_cache_misses, earlier.table_cache_misses),
+            sstable_lookups: diff(self.sstable_lookups, earlier.sstable_lookups),
+            sstables_consulted: diff(self.sstables_consulted, earlier.sstables_consulted),
+            blocks_read: diff(self.blocks_read, earlier.blocks_read),
+            interval_secs: (self.uptime_secs - earlier.uptime_secs).max(0.0),
+            ..self.clone()
+        }
+        .with_derived()
+    }
+
+    /// Recompute the derived rates and totals from the raw counters.
+    fn with_derived(mut self) -> Self {
+        self.total_ops = self.puts + self.gets + self.deletes + self.scans;
+        self.ops_per_sec = if self.interval_secs < 0.001 {
+            0.0
+        } else {
+            self.total_ops as f64 / self.interval_secs
+        };
+        self.filter_skip_rate = ratio(self.filter_negatives, self.filter_probes);
+        self.filter_false_positive_rate = ratio(
+            self.filter_false_positives,
+            self.filter_negatives + self.filter_false_positives,
+        );
+        self.row_cache_hit_rate = ratio(
+            self.row_cache_hits,
+            self.row_cache_hits + self.row_cache_misses,
+        );
+        self.block_cache_hit_rate = ratio(
+            self.block_cache_hits,
+            self.block_cache_hits + self.block_cache_misses,
+        );
+        self.table_cache_hit_rate = ratio(
+            self.table_cache_hits,
+            self.table_cache_hits + self.table_cache_misses,
+        );
+        self.avg_sstables_per_get = ratio(self.sstables_consulted, self.sstable_lookups);
+        self.avg_blocks_per_get = ratio(self.blocks_read, self.sstable_lookups);
+        self
+    }
+}
+
+/// `num / den`, or 0 when `den` is 0.
+fn ratio(num: u64, den: u64) -> f64 {
+    if den == 0 {
+        return 0.0;
+    }
+    num as f64 / den as f64
+}
+
+/// On-disk footprint and file inventory of a database.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiskUsage {
+    /// Live SSTable count per compaction tier (index = tier).
+    pub sstables_per_tier: Vec<u64>,
+    /// Bytes of live SSTables.
+    pub sstable_bytes: u64,
+    /// Bytes in the write-ahead log.
+    pub wal_bytes: u64,
+    /// Bytes of the manifest.
+    pub manifest_bytes: u64,
+    /// Bytes of files in the SSTable directory that are no longer live
+    /// (compaction inputs awaiting deletion, leftover temp files).
+    pub obsolete_bytes: u64,
+}
+
+impl DiskUsage {
+    /// Total number of live SSTables.
+    pub fn live_sstables(&self) -> u64 {
+        self.sstables_per_tier.iter().sum()
+    }
+
+    /// Total bytes on disk, obsolete files included.
+    pub fn total_bytes(&self) -> u64 {
+        self.sstable_bytes + self.wal_bytes + self.manifest_bytes + self.obsolete_bytes
+    }
+}
+
+/// Total over lookups, or 0 when there were no lookups.
+fn per_lookup(total: &AtomicU64, lookups: &AtomicU64) -> f64 {
+    let lookups = lookups.load(Ordering::Relaxed);
+    if lookups == 0 {
+        return 0.0;
+    }
+    total.load(Ordering::Relaxed) as f64 / lookups as f64
+}
+
+/// Hits over total lookups, or 0 when there were no lookups.
+fn hit_rate(hits: &AtomicU64, misses: &AtomicU64) -> f64 {
+    let hits = hits.load(Ordering::Relaxed);
+    let total = hits + misses.load(Ordering::Relaxed);
+    if total == 0 {
+        return 0.0;
+    }
+    hits as f64 / total as f64
+}
+
 impl Default for EngineMetrics {
     fn default() -> Self {
         Self::new()
@@ -192,6 +1148,189 @@ mod tests {
         assert!(report.contains("written:"));
     }
 
+    #[test]
+    fn test_filter_metrics() {
+        let m = EngineMetrics::new();
+        assert_eq!(m.filter_false_positive_rate(), 0.0);
+
+        m.record_filter_probe(false, false); // skipped
+        m.record_filter_probe(false, false); // skipped
+        m.record_filter_probe(true, false); // false positive
+        m.record_filter_probe(true, true); // hit
+
+        assert_eq!(m.filter_probes.load(Ordering::Relaxed), 4);
+        assert_eq!(m.filter_negatives.load(Ordering::Relaxed), 2);
+        assert_eq!(m.filter_false_positives.load(Ordering::Relaxed), 1);
+        assert_eq!(m.filter_skip_rate(), 0.5);
+        assert!((m.filter_false_positive_rate() - 1.0 / 3.0).abs() < 1e-9);
+        assert!(m.report().contains("false pos:"));
+    }
+
+    #[test]
+    fn test_cache_metrics() {
+        let m = EngineMetrics::new();
+        assert_eq!(m.row_cache_hit_rate(), 0.0);
+
+        m.record_row_cache(true);
+        m.record_row_cache(true);
+        m.record_row_cache(true);
+        m.record_row_cache(false);
+        m.record_block_cache(true);
+        m.record_block_cache(false);
+        m.record_table_cache(false);
+
+        assert_eq!(m.row_cache_hits.load(Ordering::Relaxed), 3);
+        assert_eq!(m.row_cache_hit_rate(), 0.75);
+        assert_eq!(m.block_cache_hit_rate(), 0.5);
+        assert_eq!(m.table_cache_hit_rate(), 0.0);
+        assert!(m.report().contains("Caches:"));
+    }
+
+    #[cfg(feature = "prometheus")]
+    #[test]
+    fn test_prometheus_text() {
+        let m = EngineMetrics::new();
+        m.record_put(1, 1);
+        m.record_put(1, 1);
+        m.record_row_cache(true);
+
+        let text = m.prometheus_text();
+        assert!(text.contains("# TYPE oblivion_operations_total counter"));
+        assert!(text.contains("oblivion_operations_total{op=\"put\"} 2\n"));
+        assert!(text.contains("oblivion_cache_hits_total{cache=\"row\"} 1\n"));
+        assert!(text.contains("# TYPE oblivion_uptime_seconds gauge"));
+    }
+
+    #[cfg(feature = "prometheus")]
+    #[test]
+    fn test_serve_prometheus() {
+        use std::io::{Read, Write};
+
+        let m = std::sync::Arc::new(EngineMetrics::new());
+        m.record_flush();
+        let server = serve_prometheus(m, "127.0.0.1:0").unwrap();
+        let addr = server.local_addr();
+
+        // An idle client does not hold up the next scrape
+        let idle = std::net::TcpStream::connect(addr).unwrap();
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(std::time::Duration::from_secs(2)))
+            .unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("oblivion_flushes_total 1\n"));
+
+        // Stopping closes open connections and the listener
+        drop(server);
+        let mut buf = [0u8; 1];
+        assert_eq!((&idle).read(&mut buf).unwrap_or(0), 0);
+        assert!(std::net::TcpStream::connect(addr).is_err());
+    }
+
+    #[test]
+    fn test_snapshot_serializes() {
+        let m = EngineMetrics::new();
+        m.record_put(3, 4);
+        m.record_get(Some(4));
+        m.record_row_cache(true);
+
+        let snapshot = m.snapshot();
+        assert_eq!(snapshot.puts, 1);
+        assert_eq!(snapshot.bytes_written, 7);
+        assert_eq!(snapshot.total_ops, 2);
+        assert_eq!(snapshot.row_cache_hit_rate, 1.0);
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert!(json.contains("\"puts\":1"));
+        let parsed: MetricsSnapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, snapshot);
+    }
+
+    #[test]
+    fn test_disk_usage_gauges() {
+        let m = EngineMetrics::new();
+        let usage = DiskUsage {
+            sstables_per_tier: vec![3, 1],
+            sstable_bytes: 4000,
+            wal_bytes: 100,
+            manifest_bytes: 20,
+            obsolete_bytes: 500,
+        };
+        assert_eq!(usage.live_sstables(), 4);
+        assert_eq!(usage.total_bytes(), 4620);
+
+        m.set_disk_usage(&usage);
+        m.set_wal_bytes(150);
+        let snapshot = m.snapshot();
+        assert_eq!(snapshot.sstables_per_tier, vec![3, 1]);
+        assert_eq!(snapshot.wal_bytes, 150);
+        assert_eq!(snapshot.obsolete_bytes, 500);
+        assert!(m.report().contains("tables per tier: [3, 1]"));
+    }
+
+    #[test]
+    fn test_read_amplification() {
+        let m = EngineMetrics::new();
+        assert_eq!(m.avg_sstables_per_get(), 0.0);
+
+        m.record_read_amplification(1, 1);
+        m.record_read_amplification(5, 2);
+        m.record_read_amplification(3, 0);
+
+        assert_eq!(m.sstable_lookups.load(Ordering::Relaxed), 3);
+        assert_eq!(m.avg_sstables_per_get(), 3.0);
+        assert_eq!(m.avg_blocks_per_get(), 1.0);
+        assert_eq!(m.max_sstables_per_get.load(Ordering::Relaxed), 5);
+        assert_eq!(m.max_blocks_per_get.load(Ordering::Relaxed), 2);
+        assert!(m.report().contains("tables/get: 3.00 avg, 5 max"));
+    }
+
+    #[test]
+    fn test_snapshot_and_reset() {
+        let m = EngineMetrics::new();
+        m.record_put(1, 1);
+        m.record_put(1, 1);
+        m.record_read_amplification(4, 1);
+        m.set_memtable_bytes(100);
+
+        let first = m.snapshot_and_reset();
+        assert_eq!(first.puts, 2);
+        assert_eq!(first.max_sstables_per_get, 4);
+        assert_eq!(m.total_ops(), 0);
+        assert_eq!(m.max_sstables_per_get.load(Ordering::Relaxed), 0);
+
+        // Gauges survive the reset
+        m.record_get(None);
+        let second = m.snapshot_and_reset();
+        assert_eq!(second.puts, 0);
+        assert_eq!(second.gets, 1);
+        assert_eq!(second.memtable_bytes, 100);
+    }
+
+    #[test]
+    fn test_snapshot_delta() {
+        let m = EngineMetrics::new();
+        m.record_put(1, 1);
+        m.record_row_cache(false);
+        let earlier = m.snapshot();
+
+        m.record_put(1, 1);
+        m.record_put(1, 1);
+        m.record_row_cache(true);
+        let delta = m.snapshot().delta(&earlier);
+
+        assert_eq!(delta.puts, 2);
+        assert_eq!(delta.total_ops, 2);
+        assert_eq!(delta.bytes_written, 4);
+        assert_eq!(delta.row_cache_hit_rate, 1.0);
+        assert!(delta.interval_secs <= m.uptime_secs());
+    }
+
     #[test]
     fn test_default() {
         let m = EngineMetrics::default();
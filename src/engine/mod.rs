@@ -1,146 +1,1545 @@
 //! OBLIVION - Storage Engine Module
 //! Top-level module for the LSM-Tree storage engine components.
 
+#[cfg(feature = "tokio")]
+pub mod async_engine;
+pub mod background;
+pub mod backup;
+pub mod batch;
 pub mod bloom;
+pub mod cache;
+pub mod changes;
+pub mod codec;
+pub mod column_family;
 pub mod compaction;
+pub mod comparator;
+#[cfg(feature = "sst-compat")]
+pub mod compat;
+pub mod compression;
 pub mod concurrent;
+pub mod database;
+pub mod env;
+pub mod export;
+pub mod failpoint;
+pub mod fault;
+pub mod filter;
+pub mod glob;
+pub mod hash;
+pub mod import;
+pub mod integrity;
+pub mod io;
+pub mod keys;
+pub mod list;
+pub mod manifest;
 pub mod memtable;
 pub mod metrics;
+#[cfg(feature = "object-store")]
+pub mod object_store;
+pub mod options;
+pub mod pinned;
+pub mod predicate;
+pub mod properties;
+pub mod repair;
+pub mod replication;
+pub mod ribbon;
+pub mod sample;
+pub mod secondary;
+pub mod set;
+pub mod sharded;
+pub mod sizing;
+pub mod snapshot;
+pub mod sorted_set;
 pub mod sstable;
+pub mod stats;
+pub mod timestamp;
+pub mod transaction;
+pub mod tree;
 pub mod ttl;
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+pub mod uring;
+pub mod version;
 pub mod wal;
+pub mod wal_sync;
 
-use crate::config::Config;
-use crate::error::Result;
-use crate::types::{Key, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufRead, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+use std::time::Duration;
 
-use self::memtable::MemTable;
-use self::metrics::EngineMetrics;
+use arc_swap::{ArcSwap, ArcSwapOption};
+use parking_lot::{Mutex, RwLock};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::config::{Config, WAL_FILE_NAME};
+use crate::error::{OblivionError, Result};
+use crate::types::{Key, Value, ValueInfo};
+
+use self::background::{BackgroundPool, JobKind};
+use self::batch::{BatchOp, WriteBatch};
+use self::cache::{BlockCache, RowCache, TableCache};
+use self::changes::{ChangeEvent, ChangeFeed, ChangeOp, ChangeRecord, ChangeStream};
+use self::codec::{Bincode, Codec, Typed};
+use self::column_family::{ColumnFamily, ColumnFamilyOptions, DEFAULT_COLUMN_FAMILY};
+use self::comparator::Comparator;
+use self::database::Database;
+use self::env::{Env, FileLock};
+use self::export::ExportFormat;
+use self::failpoint::fail_point;
+use self::import::ImportOptions;
+use self::integrity::IntegrityReport;
+use self::manifest::Manifest;
+use self::metrics::{DiskUsage, EngineMetrics};
+use self::options::{ReadOptions, DEFAULT_READ_OPTIONS};
+use self::pinned::PinnedValue;
+use self::predicate::Predicate;
+use self::properties::LiveFile;
+use self::replication::Backlog;
+use self::snapshot::Snapshot;
+use self::sstable::SSTable;
+use self::stats::StatsDumper;
+use self::transaction::Transaction;
+use self::tree::Tree;
 use self::ttl::TtlIndex;
-use self::wal::WriteAheadLog;
+use self::version::{ReadState, ScanIter, ScanPage, Version};
+use self::wal::{RecoveryProgress, WriteAheadLog};
+use self::wal_sync::WalSyncer;
+
+/// How often background workers sweep expired keys out of the MemTable.
+const TTL_SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often background workers look for SSTables due for periodic
+/// compaction, so they are rewritten even while no writes come in.
+const PERIODIC_COMPACTION_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Frozen MemTables allowed to wait for a background flush before
+/// writes stall.
+const MAX_PENDING_FLUSHES: usize = 2;
+
+/// Records read per page by [`Oblivion::dbsize_exact`], and so how often
+/// it checks for cancellation.
+const DBSIZE_PAGE_SIZE: usize = 1024;
+
+/// Write-side state, locked by every mutation so WAL order matches
+/// MemTable order.
+struct Writer {
+    /// Write-ahead log for crash recovery.
+    wal: WriteAheadLog,
+    /// WAL segments recovered at open that hold part of the active
+    /// MemTable's writes.
+    active_logs: Vec<PathBuf>,
+}
 
 /// The core Oblivion storage engine.
-/// Coordinates the MemTable, WAL, and (future) SSTables
+/// Coordinates the MemTable, WAL, and SSTables
 /// to provide a durable key-value store based on LSM-Tree architecture.
+///
+/// Every method takes `&self`: writers serialize on an internal lock and
+/// readers never take it, so an `Arc<Oblivion>` can be shared between
+/// threads directly.
 pub struct Oblivion {
-    /// In-memory sorted buffer for recent writes.
-    memtable: MemTable,
-    /// Write-ahead log for crash recovery.
-    wal: WriteAheadLog,
-    /// Engine configuration.
-    config: Config,
-    /// Counter for SSTable file naming.
-    flush_count: u64,
-    /// Runtime operation metrics.
-    metrics: EngineMetrics,
-    /// TTL index for key expiration.
-    ttl_index: TtlIndex,
+    /// MemTables, SSTables, TTL index and row cache, shared with
+    /// lock-free readers.
+    state: Arc<ReadState>,
+    /// Flush and compaction state, shared with background jobs.
+    tree: Arc<Tree>,
+    /// Column families opened with [`open_with_cfs`](Self::open_with_cfs),
+    /// by name.
+    families: BTreeMap<String, ColumnFamily>,
+    /// WAL and recovered segments; its lock serializes writers.
+    /// Shared with the WAL sync thread.
+    writer: Arc<Mutex<Writer>>,
+    /// Runtime operation metrics (shared with the table cache and readers).
+    metrics: Arc<EngineMetrics>,
+    /// Open SSTable file handles, bounded by `max_open_files`.
+    table_cache: Arc<TableCache>,
+    /// Background workers, if `background_threads` is set; otherwise
+    /// flushes and compactions run on the writing thread.
+    pool: Option<BackgroundPool>,
+    /// Periodic stats logger, if `stats_dump_period_secs` is set.
+    stats_dumper: Mutex<Option<StatsDumper>>,
+    /// Periodic WAL sync, while `sync_writes` is off and
+    /// `wal_sync_interval_ms` is set.
+    wal_syncer: Mutex<Option<WalSyncer>>,
+    /// Recent writes for replication followers, once one connects.
+    backlog: Backlog,
+    /// Live channels of change subscribers and watchers (shared with
+    /// the tree, which reports expirations).
+    changes: Arc<ChangeFeed>,
+    /// Set while a [`replication::Follower`] applies a primary's writes;
+    /// writes through the public API are refused.
+    read_only: AtomicBool,
+    /// Serializes the list and sorted-set updates that rewrite records
+    /// they read; see [`list`] and [`sorted_set`].
+    data_types: Mutex<()>,
+    /// Exclusive lock on `data_dir/LOCK`. Declared last so it is only
+    /// released once the background workers have been joined.
+    _lock: FileLock,
 }
 
 impl Oblivion {
     /// Open or create an Oblivion storage engine at the configured path.
+    ///
+    /// Honors `create_if_missing` and `error_if_exists`; a database is
+    /// considered present once it has been opened at least once.
     pub fn open(config: Config) -> Result<Self> {
+        Self::open_with_progress(config, |_| {})
+    }
+
+    /// Like [`open`](Self::open), calling `progress` periodically while
+    /// the WAL is replayed so callers can tell a long recovery from a
+    /// hung startup.
+    pub fn open_with_progress<F>(config: Config, progress: F) -> Result<Self>
+    where
+        F: FnMut(&RecoveryProgress),
+    {
+        Self::open_inner(config, Vec::new(), progress)
+    }
+
+    /// Open or create the database with the column families `cfs`, each
+    /// with its own flush and compaction settings; see the
+    /// [`column_family` module](column_family). Families not created yet
+    /// are, and every existing one must be listed.
+    pub fn open_with_cfs<I, N>(config: Config, cfs: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = (N, ColumnFamilyOptions)>,
+        N: Into<String>,
+    {
+        let cfs = cfs.into_iter().map(|(name, opts)| (name.into(), opts));
+        Self::open_inner(config, cfs.collect(), |_| {})
+    }
+
+    /// Delete the database `config` points at: its WAL segments,
+    /// SSTables, manifest and column families, then the `LOCK` file.
+    ///
+    /// Only files the engine creates are removed, recognised by name;
+    /// anything else in `data_dir` is left alone, along with the
+    /// directories holding it. Fails with [`OblivionError::Locked`]
+    /// while an engine has the database open. A missing `data_dir` is
+    /// not an error, and a destroy cut short can simply be run again.
+    pub fn destroy(config: &Config) -> Result<()> {
+        let env = &config.env;
+        if !env.exists(&config.data_dir) {
+            return Ok(());
+        }
+        let lock = env
+            .try_lock(&config.lock_path())?
+            .ok_or_else(|| OblivionError::Locked(config.data_dir.clone()))?;
+        let families = config.data_dir.join(column_family::FAMILIES_DIR);
+        for dir in list_dir(env, &families)? {
+            let mut family = config.clone();
+            family.data_dir = dir;
+            remove_database_files(&family)?;
+            remove_empty_dir(env, &family.data_dir)?;
+        }
+        remove_empty_dir(env, &families)?;
+        remove_database_files(config)?;
+        // Removed while still held, so no engine opens the database
+        // half deleted
+        env.remove_file(&config.lock_path())?;
+        drop(lock);
+        let leftovers = list_dir(env, &config.data_dir)?;
+        if leftovers.is_empty() {
+            remove_empty_dir(env, &config.data_dir)?;
+        } else {
+            log::warn!(
+                "Left {} unrelated entries in {:?}",
+                leftovers.len(),
+                config.data_dir
+            );
+        }
+        log::info!("Destroyed database at {:?}", config.data_dir);
+        Ok(())
+    }
+
+    fn open_inner<F>(
+        config: Config,
+        cfs: Vec<(String, ColumnFamilyOptions)>,
+        progress: F,
+    ) -> Result<Self>
+    where
+        F: FnMut(&RecoveryProgress),
+    {
+        config.validate()?;
+        let mut family_configs = Vec::with_capacity(cfs.len());
+        for (name, opts) in &cfs {
+            column_family::validate_name(name)?;
+            if family_configs.iter().any(|(other, _)| other == name) {
+                return Err(OblivionError::Config(format!(
+                    "column family '{}' listed twice",
+                    name
+                )));
+            }
+            family_configs.push((name.clone(), opts.apply(&config, name)?));
+        }
+        if config.database_exists() {
+            if config.error_if_exists {
+                return Err(OblivionError::DatabaseExists(config.data_dir.clone()));
+            }
+        } else if !config.create_if_missing {
+            return Err(OblivionError::DatabaseNotFound(config.data_dir.clone()));
+        }
+        // Lock before touching any file, so a second instance cannot
+        // replay or migrate the WAL underneath the first
+        let env = &config.env;
+        env.create_dir_all(&config.data_dir)?;
+        let lock = env
+            .try_lock(&config.lock_path())?
+            .ok_or_else(|| OblivionError::Locked(config.data_dir.clone()))?;
         config.ensure_dirs()?;
+        let family_ids = Self::register_families(&config, &family_configs)?;
+
+        // Segments rotated out for MemTables that were never flushed
+        // replay first, oldest first, then the live log
+        let wal_path = config.wal_path();
+        let segments = WriteAheadLog::segments_in(env, &config.wal_dir())?;
+        let mut logs: Vec<PathBuf> = segments.iter().map(|(_, path)| path.clone()).collect();
+        logs.push(wal_path.clone());
+        let (memtable, mut recovered, recovery) =
+            WriteAheadLog::recover_families(env, &logs, config.recovery_threads, progress)?;
+        logs.pop();
+        let mut wal = WriteAheadLog::open_in(env, wal_path)?;
+        wal.set_sync(config.sync_writes);
+        wal.set_sync_method(config.wal_sync_method);
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        if let Some(ring) = uring::Ring::open_if(config.use_io_uring) {
+            wal.set_ring(ring);
+        }
+        wal.set_last_sequence(recovery.last_sequence);
+        if wal.size() == 0 && recovery.last_sequence > 0 {
+            wal.append_sequence_marker()?;
+        }
+        let metrics = Arc::new(EngineMetrics::new());
+        metrics.record_recovery_stats(&recovery);
+        let table_cache = TableCache::new(config.max_open_files)
+            .with_metrics(Arc::clone(&metrics))
+            .with_env(config.env.clone())
+            .with_direct_io(config.use_direct_io)
+            .with_comparator(config.comparator.clone())
+            .with_paranoid_checks(config.paranoid_checks)
+            .with_block_cache(config.block_cache_capacity);
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        let table_cache = match uring::Ring::open_if(config.use_io_uring) {
+            Some(ring) => table_cache.with_ring(ring),
+            None => table_cache,
+        };
+        let table_cache = Arc::new(table_cache);
+        let (sstables, mut manifest) = Self::load_sstables(&config, &table_cache)?;
+        // Segments share the file number space but are not in the manifest
+        if let Some((last, _)) = segments.last() {
+            manifest.next_file_number = manifest.next_file_number.max(last + 1);
+        }
 
-        let wal_path = config.data_dir.join("oblivion.wal");
-        let memtable = WriteAheadLog::recover(&wal_path)?;
-        let wal = WriteAheadLog::open(wal_path)?;
+        if config.preload_index_and_filter {
+            for table in &sstables {
+                table.preload()?;
+            }
+        }
 
-        let metrics = EngineMetrics::new();
+        let row_cache = (config.row_cache_capacity > 0)
+            .then(|| Arc::new(RowCache::new(config.row_cache_capacity)));
         if !memtable.is_empty() {
             metrics.record_recovery();
         }
 
         log::info!(
-            "Oblivion engine opened at {:?} ({} entries recovered)",
+            "Oblivion engine opened at {:?} ({} entries recovered, {} SSTables)",
             config.data_dir,
-            memtable.len()
+            memtable.len(),
+            sstables.len()
         );
 
-        Ok(Self {
-            memtable,
-            wal,
-            config,
-            flush_count: 0,
+        let state = Arc::new(ReadState {
+            memtable: RwLock::new(memtable),
+            version: ArcSwap::from_pointee(Version::new(Vec::new(), sstables)),
+            ttl_index: RwLock::new(TtlIndex::new()),
+            row_cache: ArcSwapOption::new(row_cache),
+            metrics: Arc::clone(&metrics),
+            snapshot_pins: Arc::new(()),
+            comparator: config.comparator.clone(),
+        });
+        let changes = Arc::new(ChangeFeed::new());
+        let tree = Arc::new(Tree::new(
+            Arc::clone(&state),
+            config.clone(),
+            manifest,
+            Arc::clone(&table_cache),
+            Arc::clone(&changes),
+        ));
+        for log in &logs {
+            tree.add_segment_bytes(config.env.file_size(log)?);
+        }
+        let mut families = BTreeMap::new();
+        for ((name, family_config), id) in family_configs.into_iter().zip(family_ids) {
+            let memtable = recovered.remove(&id).unwrap_or_default();
+            let family = ColumnFamily::open(family_config, id, memtable, &table_cache)?;
+            families.insert(name, family);
+        }
+        let pool = (config.background_threads > 0).then(|| {
+            let trees = families.values().map(|f| Arc::clone(&f.tree)).collect();
+            Self::start_pool(&tree, trees, &config)
+        });
+        let engine = Self {
+            state,
+            tree,
+            families,
+            writer: Arc::new(Mutex::new(Writer {
+                wal,
+                active_logs: logs,
+            })),
             metrics,
-            ttl_index: TtlIndex::new(),
-        })
+            table_cache,
+            pool,
+            stats_dumper: Mutex::new(None),
+            wal_syncer: Mutex::new(None),
+            backlog: Backlog::new(config.replication_backlog_size),
+            changes,
+            read_only: AtomicBool::new(false),
+            data_types: Mutex::new(()),
+            _lock: lock,
+        };
+        engine.update_write_gauges(&engine.writer.lock().wal);
+        engine.tree.update_tree_gauges();
+        engine.restart_stats_dumper(&config);
+        engine.restart_wal_syncer(&config);
+        Ok(engine)
+    }
+
+    /// Check the column families `families` against the registry: every
+    /// registered family must be listed, and new ones are registered.
+    /// Returns the id of each family, in order.
+    fn register_families(config: &Config, families: &[(String, Config)]) -> Result<Vec<u32>> {
+        let path = config.data_dir.join(column_family::REGISTRY_FILE_NAME);
+        let mut registry = column_family::load_registry(&config.env, &path)?;
+        let missing: Vec<&str> = registry
+            .iter()
+            .map(|(_, name)| name.as_str())
+            .filter(|name| !families.iter().any(|(listed, _)| listed == name))
+            .collect();
+        if !missing.is_empty() {
+            return Err(OblivionError::Config(format!(
+                "column families not opened: {} (list them in open_with_cfs)",
+                missing.join(", ")
+            )));
+        }
+        let mut ids = Vec::with_capacity(families.len());
+        let registered = registry.len();
+        for (name, _) in families {
+            let id = match registry.iter().find(|(_, known)| known == name) {
+                Some((id, _)) => *id,
+                None => {
+                    let id = registry.iter().map(|(id, _)| id + 1).max().unwrap_or(1);
+                    log::info!("Creating column family '{}' (id {})", name, id);
+                    registry.push((id, name.clone()));
+                    id
+                }
+            };
+            ids.push(id);
+        }
+        if registry.len() > registered {
+            column_family::save_registry(&config.env, &path, &registry)?;
+        }
+        Ok(ids)
+    }
+
+    /// Start the background workers, the periodic TTL sweep and the
+    /// periodic compaction check of `tree` and the column families'
+    /// `trees`. Job failures are recorded as the engine's background
+    /// error.
+    fn start_pool(tree: &Arc<Tree>, trees: Vec<Arc<Tree>>, config: &Config) -> BackgroundPool {
+        let errors = Arc::clone(tree);
+        let mut pool = BackgroundPool::new(
+            config.background_threads,
+            Arc::new(move |kind, message| errors.record_background_error(kind, message)),
+        );
+        let sweeper = Arc::clone(tree);
+        pool.schedule_every(JobKind::TtlSweep, TTL_SWEEP_INTERVAL, move || {
+            sweeper.sweep_expired()
+        });
+        let trees: Vec<Arc<Tree>> = trees.into_iter().chain([Arc::clone(tree)]).collect();
+        pool.schedule_every(
+            JobKind::Compaction,
+            PERIODIC_COMPACTION_CHECK_INTERVAL,
+            move || {
+                let due = trees
+                    .iter()
+                    .any(|tree| tree.config().periodic_compaction_interval_secs > 0);
+                if due {
+                    compact_trees(&trees)
+                } else {
+                    Ok(())
+                }
+            },
+        );
+        pool
     }
 
     /// Insert a key-value pair into the storage engine.
     /// Write path: WAL (disk) -> MemTable (memory) -> check flush.
-    pub fn put(&mut self, key: Key, value: Value) -> Result<()> {
+    pub fn put(&self, key: impl Into<Key>, value: impl Into<Value>) -> Result<()> {
+        self.check_writable()?;
+        self.write_put(key.into(), value.into(), None)
+    }
+
+    /// Insert a key-value pair with a TTL (time-to-live) in milliseconds.
+    /// The key will be treated as expired after `ttl_ms` milliseconds.
+    pub fn put_with_ttl(
+        &self,
+        key: impl Into<Key>,
+        value: impl Into<Value>,
+        ttl_ms: u64,
+    ) -> Result<()> {
+        self.check_writable()?;
+        self.write_put(key.into(), value.into(), Some(ttl_ms))
+    }
+
+    /// Shared write path of `put`, `put_with_ttl` and replication.
+    pub(crate) fn write_put(&self, key: Key, value: Value, ttl_ms: Option<u64>) -> Result<()> {
+        self.check_sizes(&key, Some(&value))?;
+        self.tree.check_background_error()?;
+        let mut writer = self.writer.lock();
+        self.put_locked(&mut writer, key, value, ttl_ms)
+    }
+
+    /// Set a TTL on an existing key, returning whether the key was live.
+    ///
+    /// Rewrites the current value with the TTL under the writer lock, so
+    /// no concurrent write is lost and followers and change subscribers
+    /// see the new expiry.
+    pub fn expire(&self, key: impl AsRef<[u8]>, ttl_ms: u64) -> Result<bool> {
+        let key = key.as_ref();
+        self.check_writable()?;
+        self.tree.check_background_error()?;
+        let mut writer = self.writer.lock();
+        let Some(value) = self.state.get_opt(key, &ReadOptions::default())? else {
+            return Ok(false);
+        };
+        self.put_locked(&mut writer, key.to_vec(), value, Some(ttl_ms))?;
+        Ok(true)
+    }
+
+    /// Remove the TTL of a live key, returning whether it had one.
+    ///
+    /// TTLs live in memory only, so this writes nothing to the WAL, and
+    /// followers keep the expiry they were sent.
+    pub fn persist(&self, key: impl AsRef<[u8]>) -> Result<bool> {
+        let key = key.as_ref();
+        self.check_writable()?;
+        // Under the writer lock, so no concurrent put sets a new TTL
+        // between the check and the removal
+        let _writer = self.writer.lock();
+        if self.state.get_opt(key, &ReadOptions::default())?.is_none() {
+            return Ok(false);
+        }
+        let mut ttl_index = self.state.ttl_index.write();
+        let had_ttl = ttl_index.get_expiration(key).is_some();
+        ttl_index.remove_ttl(key);
+        Ok(had_ttl)
+    }
+
+    /// Append `suffix` to the value of `key`, storing it as the value of
+    /// a missing key, and return the new length. A TTL the key has is
+    /// kept, as with `put`.
+    ///
+    /// The read and the write happen under the writer lock, so
+    /// concurrent appends are never lost; the same holds for
+    /// [`getset`](Self::getset) and [`getdel`](Self::getdel).
+    pub fn append(&self, key: impl Into<Key>, suffix: impl AsRef<[u8]>) -> Result<usize> {
+        let key = key.into();
+        self.check_writable()?;
+        self.tree.check_background_error()?;
+        let mut writer = self.writer.lock();
+        let mut value = self
+            .state
+            .get_opt(&key, &ReadOptions::default())?
+            .unwrap_or_default();
+        value.extend_from_slice(suffix.as_ref());
+        self.check_sizes(&key, Some(&value))?;
+        let len = value.len();
+        self.put_locked(&mut writer, key, value, None)?;
+        Ok(len)
+    }
+
+    /// Store `value` under `key` and return the value it replaced. A TTL
+    /// the key has is kept, as with `put`.
+    pub fn getset(&self, key: impl Into<Key>, value: impl Into<Value>) -> Result<Option<Value>> {
+        let (key, value) = (key.into(), value.into());
+        self.check_writable()?;
+        self.check_sizes(&key, Some(&value))?;
+        self.tree.check_background_error()?;
+        let mut writer = self.writer.lock();
+        let old = self.state.get_opt(&key, &ReadOptions::default())?;
+        self.put_locked(&mut writer, key, value, None)?;
+        Ok(old)
+    }
+
+    /// Delete `key` and return the value it had; a missing key is left
+    /// alone and returns `None`.
+    pub fn getdel(&self, key: impl Into<Key>) -> Result<Option<Value>> {
+        let key = key.into();
+        self.check_writable()?;
+        self.check_sizes(&key, None)?;
+        self.tree.check_background_error()?;
+        let mut writer = self.writer.lock();
+        let old = self.state.get_opt(&key, &ReadOptions::default())?;
+        if old.is_some() {
+            self.delete_locked(&mut writer, key)?;
+        }
+        Ok(old)
+    }
+
+    /// Returns the length of the value of `key`, or 0 if it is missing.
+    pub fn strlen(&self, key: impl AsRef<[u8]>) -> Result<usize> {
+        Ok(self.value_info(key)?.map_or(0, |info| info.len))
+    }
+
+    /// Returns the length of the value of `key` without reading the value
+    /// out, or `None` if the key is missing or expired. Errors are logged.
+    pub fn value_size(&self, key: impl AsRef<[u8]>) -> Option<usize> {
+        let key = key.as_ref();
+        self.value_info(key)
+            .unwrap_or_else(|e| {
+                log::error!("Read failed for key {:?}: {}", key, e);
+                None
+            })
+            .map(|info| info.len)
+    }
+
+    /// Returns the length and remaining TTL of the value of `key` without
+    /// reading the value out, or `None` if the key is missing or expired;
+    /// e.g. to check a quota before fetching a large value.
+    pub fn value_info(&self, key: impl AsRef<[u8]>) -> Result<Option<ValueInfo>> {
+        let key = key.as_ref();
+        let Some(len) = self.state.value_len(key, &ReadOptions::default())? else {
+            return Ok(None);
+        };
+        Ok(Some(ValueInfo {
+            len,
+            ttl_ms: self.state.ttl(key),
+        }))
+    }
+
+    /// Log and apply a put while the caller holds the writer lock.
+    fn put_locked(
+        &self,
+        writer: &mut Writer,
+        key: Key,
+        value: Value,
+        ttl_ms: Option<u64>,
+    ) -> Result<()> {
+        if let Some(ttl_ms) = ttl_ms {
+            self.state.ttl_index.write().set_ttl(key.clone(), ttl_ms);
+        }
         self.metrics.record_put(key.len(), value.len());
-        self.wal.append_put(&key, &value)?;
-        self.memtable.insert(key, value);
+        let sequence = writer
+            .wal
+            .append_put(&key, &value)
+            .map_err(|e| self.tree.degrade_on_full_disk("WAL append", e))?;
+        fail_point!(self.tree.config(), AfterWalAppend);
+        // Published under the MemTable lock, so events keep MemTable
+        // order relative to expirations
+        let mut memtable = self.state.memtable.write();
+        self.publish_change(sequence, || ChangeOp::Put {
+            key: key.clone(),
+            value: value.clone(),
+            ttl_ms,
+        });
+        memtable.insert(key.clone(), value);
+        drop(memtable);
+        self.invalidate_cached(&key);
 
         // Check if MemTable needs flushing
-        self.maybe_flush()?;
+        self.maybe_flush(writer)?;
+        self.update_write_gauges(&writer.wal);
 
         Ok(())
     }
 
-    /// Insert a key-value pair with a TTL (time-to-live) in milliseconds.
-    /// The key will be treated as expired after `ttl_ms` milliseconds.
-    pub fn put_with_ttl(&mut self, key: Key, value: Value, ttl_ms: u64) -> Result<()> {
-        self.ttl_index.set_ttl(key.clone(), ttl_ms);
-        self.put(key, value)
-    }
-
     /// Get a value by key from the storage engine.
-    /// Read path: MemTable (memory) -> (future: SSTables on disk).
+    /// Read path: MemTable (memory) -> row cache -> SSTables (newest first,
+    /// through the block cache).
     /// Keys with expired TTL will return `None`.
-    pub fn get(&self, key: &[u8]) -> Option<Value> {
-        // Check TTL expiration first
-        if self.ttl_index.is_expired(key) {
-            return None;
+    pub fn get(&self, key: impl AsRef<[u8]>) -> Option<Value> {
+        let key = key.as_ref();
+        self.get_opt(key, &ReadOptions::default())
+            .unwrap_or_else(|e| {
+                log::error!("Read failed for key {:?}: {}", key, e);
+                None
+            })
+    }
+
+    /// Get a value by key with per-read options (snapshot, cache
+    /// filling, checksum verification). Errors are returned instead
+    /// of being logged.
+    pub fn get_opt(&self, key: impl AsRef<[u8]>, opts: &ReadOptions) -> Result<Option<Value>> {
+        self.state.get_opt(key.as_ref(), opts)
+    }
+
+    /// Get a value by key without copying it out of the MemTable or row
+    /// cache; see [`PinnedValue`] for where it is borrowed from. Hold the
+    /// value briefly: one borrowed from the active MemTable makes writes
+    /// wait until it is dropped.
+    pub fn get_pinned(&self, key: impl AsRef<[u8]>) -> Option<PinnedValue<'_>> {
+        let key = key.as_ref();
+        self.state
+            .get_pinned(key, &DEFAULT_READ_OPTIONS)
+            .unwrap_or_else(|e| {
+                log::error!("Read failed for key {:?}: {}", key, e);
+                None
+            })
+    }
+
+    /// Returns whether `key` holds a live value, without reading the
+    /// value out: MemTables answer directly, and SSTables whose filter
+    /// rules the key out are never read.
+    pub fn exists(&self, key: impl AsRef<[u8]>) -> bool {
+        let key = key.as_ref();
+        self.exists_opt(key, &ReadOptions::default())
+            .unwrap_or_else(|e| {
+                log::error!("Read failed for key {:?}: {}", key, e);
+                false
+            })
+    }
+
+    /// Like [`exists`](Self::exists), with per-read options.
+    pub fn exists_opt(&self, key: impl AsRef<[u8]>, opts: &ReadOptions) -> Result<bool> {
+        Ok(self.state.value_len(key.as_ref(), opts)?.is_some())
+    }
+
+    /// Like [`get_pinned`](Self::get_pinned), with per-read options;
+    /// values read from the snapshot of `opts` are borrowed from it.
+    pub fn get_pinned_opt<'a>(
+        &'a self,
+        key: impl AsRef<[u8]>,
+        opts: &'a ReadOptions,
+    ) -> Result<Option<PinnedValue<'a>>> {
+        self.state.get_pinned(key.as_ref(), opts)
+    }
+
+    /// Store `value` under `key`, encoded with [`Bincode`] (see the
+    /// [`codec` module](codec)).
+    pub fn put_as<T: Serialize + ?Sized>(&self, key: impl Into<Key>, value: &T) -> Result<()> {
+        self.typed::<Bincode>().put_as(key, value)
+    }
+
+    /// Load and decode a value stored with [`put_as`](Self::put_as).
+    pub fn get_as<T: DeserializeOwned>(&self, key: impl AsRef<[u8]>) -> Result<Option<T>> {
+        self.typed::<Bincode>().get_as(key)
+    }
+
+    /// Returns a view of the engine storing values encoded with the
+    /// codec `C`, e.g. `engine.typed::<Json>()`.
+    pub fn typed<C: Codec>(&self) -> Typed<'_, C> {
+        Typed::new(self)
+    }
+
+    /// Set `fields` of the hash `key` in database 0 in one batch,
+    /// returning how many were new (see the [`hash` module](hash)).
+    pub fn hset(&self, key: &[u8], fields: Vec<hash::Field>) -> Result<usize> {
+        Database::new(self, 0).hset(key, fields)
+    }
+
+    /// Get the value of `field` in the hash `key` of database 0.
+    pub fn hget(&self, key: &[u8], field: &[u8]) -> Result<Option<Value>> {
+        Database::new(self, 0).hget(key, field)
+    }
+
+    /// Delete `fields` of the hash `key` in database 0 in one batch,
+    /// returning how many existed.
+    pub fn hdel(&self, key: &[u8], fields: &[Vec<u8>]) -> Result<usize> {
+        Database::new(self, 0).hdel(key, fields)
+    }
+
+    /// Returns every field of the hash `key` in database 0, in field
+    /// order.
+    pub fn hgetall(&self, key: &[u8]) -> Result<Vec<hash::Field>> {
+        Database::new(self, 0).hgetall(key)
+    }
+
+    /// Insert `values` at the head of the list `key` in database 0,
+    /// returning its new length (see the [`list` module](list)).
+    pub fn lpush(&self, key: &[u8], values: Vec<Value>) -> Result<usize> {
+        Database::new(self, 0).lpush(key, values)
+    }
+
+    /// Append `values` to the list `key` in database 0, returning its
+    /// new length.
+    pub fn rpush(&self, key: &[u8], values: Vec<Value>) -> Result<usize> {
+        Database::new(self, 0).rpush(key, values)
+    }
+
+    /// Remove and return the first item of the list `key` in database 0.
+    pub fn lpop(&self, key: &[u8]) -> Result<Option<Value>> {
+        Database::new(self, 0).lpop(key)
+    }
+
+    /// Returns the items `start..=stop` of the list `key` in database 0;
+    /// negative indexes count from the end.
+    pub fn lrange(&self, key: &[u8], start: i64, stop: i64) -> Result<Vec<Value>> {
+        Database::new(self, 0).lrange(key, start, stop)
+    }
+
+    /// Add `members` to the set `key` in database 0, returning how many
+    /// were new (see the [`set` module](set)).
+    pub fn sadd(&self, key: &[u8], members: &[Vec<u8>]) -> Result<usize> {
+        Database::new(self, 0).sadd(key, members)
+    }
+
+    /// Remove `members` from the set `key` in database 0, returning how
+    /// many were members.
+    pub fn srem(&self, key: &[u8], members: &[Vec<u8>]) -> Result<usize> {
+        Database::new(self, 0).srem(key, members)
+    }
+
+    /// Returns whether `member` is in the set `key` of database 0.
+    pub fn sismember(&self, key: &[u8], member: &[u8]) -> Result<bool> {
+        Database::new(self, 0).sismember(key, member)
+    }
+
+    /// Returns the members of the set `key` in database 0.
+    pub fn smembers(&self, key: &[u8]) -> Result<Vec<Vec<u8>>> {
+        Database::new(self, 0).smembers(key)
+    }
+
+    /// Add or move `members` of the sorted set `key` in database 0,
+    /// returning how many were new (see the
+    /// [`sorted_set` module](sorted_set)).
+    pub fn zadd(&self, key: &[u8], members: Vec<sorted_set::ScoredMember>) -> Result<usize> {
+        Database::new(self, 0).zadd(key, members)
+    }
+
+    /// Returns the score of `member` in the sorted set `key` of database 0.
+    pub fn zscore(&self, key: &[u8], member: &[u8]) -> Result<Option<f64>> {
+        Database::new(self, 0).zscore(key, member)
+    }
+
+    /// Remove `members` from the sorted set `key` in database 0,
+    /// returning how many were members.
+    pub fn zrem(&self, key: &[u8], members: &[Vec<u8>]) -> Result<usize> {
+        Database::new(self, 0).zrem(key, members)
+    }
+
+    /// Returns the members of the sorted set `key` in database 0 scoring
+    /// from `min` to `max`, inclusive.
+    pub fn zrangebyscore(
+        &self,
+        key: &[u8],
+        min: f64,
+        max: f64,
+    ) -> Result<Vec<sorted_set::ScoredMember>> {
+        Database::new(self, 0).zrangebyscore(key, min, max)
+    }
+
+    /// Set `key` in database 0 to `value` as of the timestamp `ts` (see
+    /// the [`timestamp` module](timestamp)).
+    pub fn put_at(&self, key: &[u8], value: Value, ts: u64) -> Result<()> {
+        Database::new(self, 0).put_at(key, value, ts)
+    }
+
+    /// Delete `key` in database 0 as of the timestamp `ts`.
+    pub fn delete_at(&self, key: &[u8], ts: u64) -> Result<()> {
+        Database::new(self, 0).delete_at(key, ts)
+    }
+
+    /// Returns the value of the timestamped `key` in database 0 as of `ts`.
+    pub fn get_at(&self, key: &[u8], ts: u64) -> Result<Option<Value>> {
+        Database::new(self, 0).get_at(key, ts)
+    }
+
+    /// Returns the timestamped keys of database 0 within the bounds of
+    /// `opts` with their values as of `ts`.
+    pub fn scan_at(&self, opts: &ReadOptions, ts: u64) -> Result<Vec<(Key, Value)>> {
+        Database::new(self, 0).scan_at(opts, ts)
+    }
+
+    /// Take a consistent point-in-time view for use with [`ReadOptions::snapshot`].
+    ///
+    /// Copies the MemTable. SSTables compacted away while the snapshot is
+    /// alive keep their files until it is dropped.
+    pub fn snapshot(&self) -> Snapshot {
+        self.state.snapshot()
+    }
+
+    /// Returns the number of snapshots currently alive.
+    pub fn live_snapshots(&self) -> usize {
+        self.state.live_snapshots()
+    }
+
+    /// Reject keys and values above the configured size limits.
+    fn check_sizes(&self, key: &[u8], value: Option<&[u8]>) -> Result<()> {
+        let config = self.tree.config();
+        if key.len() > config.max_key_size {
+            return Err(OblivionError::KeyTooLarge {
+                size: key.len(),
+                limit: config.max_key_size,
+            });
+        }
+        if let Some(value) = value.filter(|v| v.len() > config.max_value_size) {
+            return Err(OblivionError::ValueTooLarge {
+                size: value.len(),
+                limit: config.max_value_size,
+            });
+        }
+        Ok(())
+    }
+
+    /// Hand a committed write to change subscribers, watchers and the
+    /// replication backlog, if any is listening. Called under the writer
+    /// lock.
+    fn publish_change(&self, sequence: u64, op: impl FnOnce() -> ChangeOp) {
+        let backlog = self.backlog.is_enabled();
+        if !backlog && !self.changes.is_active() {
+            return;
+        }
+        let record = ChangeRecord { sequence, op: op() };
+        self.changes.publish(&record);
+        if backlog {
+            self.backlog.push(record);
+        }
+    }
+
+    /// Refuse writes while the engine follows a replication primary.
+    fn check_writable(&self) -> Result<()> {
+        if self.read_only.load(Ordering::Acquire) {
+            return Err(OblivionError::ReadOnly(
+                "engine is a replication follower".to_string(),
+            ));
         }
+        Ok(())
+    }
 
-        let result = self.memtable.get(key).cloned();
-        self.metrics.record_get(result.as_ref().map(|v| v.len()));
-        result
+    /// Drop a key from the row cache once it has been overwritten or deleted.
+    fn invalidate_cached(&self, key: &[u8]) {
+        if let Some(cache) = &*self.state.row_cache.load() {
+            cache.invalidate(key);
+        }
     }
 
     /// Delete a key from the storage engine.
-    pub fn delete(&mut self, key: Key) -> Result<()> {
+    pub fn delete(&self, key: impl Into<Key>) -> Result<()> {
+        self.check_writable()?;
+        self.write_delete(key.into())
+    }
+
+    /// Shared write path of `delete` and replication.
+    pub(crate) fn write_delete(&self, key: Key) -> Result<()> {
+        self.check_sizes(&key, None)?;
+        self.tree.check_background_error()?;
+        let mut writer = self.writer.lock();
+        self.delete_locked(&mut writer, key)
+    }
+
+    /// Log and apply a delete while the caller holds the writer lock.
+    fn delete_locked(&self, writer: &mut Writer, key: Key) -> Result<()> {
         self.metrics.record_delete();
-        self.ttl_index.remove_ttl(&key);
-        self.wal.append_delete(&key)?;
-        self.memtable.delete(key);
-        self.maybe_flush()?;
+        self.state.ttl_index.write().remove_ttl(&key);
+        let sequence = writer
+            .wal
+            .append_delete(&key)
+            .map_err(|e| self.tree.degrade_on_full_disk("WAL append", e))?;
+        fail_point!(self.tree.config(), AfterWalAppend);
+        let mut memtable = self.state.memtable.write();
+        self.publish_change(sequence, || ChangeOp::Delete { key: key.clone() });
+        memtable.delete(key.clone());
+        drop(memtable);
+        self.invalidate_cached(&key);
+        self.maybe_flush(writer)?;
+        self.update_write_gauges(&writer.wal);
+        Ok(())
+    }
+
+    /// Insert a key-value pair into the column family `cf`.
+    pub fn put_cf(&self, cf: &str, key: impl Into<Key>, value: impl Into<Value>) -> Result<()> {
+        self.check_writable()?;
+        self.write_cf(cf, key.into(), Some(value.into()))
+    }
+
+    /// Get a value by key from the column family `cf`.
+    pub fn get_cf(&self, cf: &str, key: impl AsRef<[u8]>) -> Result<Option<Value>> {
+        self.family(cf)?
+            .state
+            .get_opt(key.as_ref(), &ReadOptions::default())
+    }
+
+    /// Delete a key from the column family `cf`.
+    pub fn delete_cf(&self, cf: &str, key: impl Into<Key>) -> Result<()> {
+        self.check_writable()?;
+        self.write_cf(cf, key.into(), None)
+    }
+
+    /// Shared write path of `put_cf`, `delete_cf` and replication: a put
+    /// of `value` into the column family `cf`, or a delete if it is `None`.
+    pub(crate) fn write_cf(&self, cf: &str, key: Key, value: Option<Value>) -> Result<()> {
+        let family = self.family(cf)?;
+        self.check_sizes(&key, value.as_deref())?;
+        self.tree.check_background_error()?;
+        let mut writer = self.writer.lock();
+        let appended = match &value {
+            Some(value) => {
+                self.metrics.record_put(key.len(), value.len());
+                writer.wal.append_cf_put(family.id, &key, value)
+            }
+            None => {
+                self.metrics.record_delete();
+                writer.wal.append_cf_delete(family.id, &key)
+            }
+        };
+        let sequence = appended.map_err(|e| self.tree.degrade_on_full_disk("WAL append", e))?;
+        let mut memtable = family.state.memtable.write();
+        self.publish_change(sequence, || match &value {
+            Some(value) => ChangeOp::PutCf {
+                cf: cf.to_string(),
+                key: key.clone(),
+                value: value.clone(),
+            },
+            None => ChangeOp::DeleteCf {
+                cf: cf.to_string(),
+                key: key.clone(),
+            },
+        });
+        match value {
+            Some(value) => memtable.insert(key, value),
+            None => memtable.delete(key),
+        }
+        drop(memtable);
+        self.maybe_flush(&mut writer)?;
+        self.update_write_gauges(&writer.wal);
+        Ok(())
+    }
+
+    /// Scan the key-value pairs of the column family `cf` within the
+    /// bounds of `opts`, in sorted order. Snapshots of the default
+    /// keyspace do not apply to column families.
+    pub fn scan_cf(&self, cf: &str, opts: &ReadOptions) -> Result<Vec<(Key, Value)>> {
+        if opts.snapshot.is_some() {
+            return Err(OblivionError::Unsupported(
+                "snapshots of column families".to_string(),
+            ));
+        }
+        self.family(cf)?.state.scan_opt(opts)
+    }
+
+    /// Names of the column families the engine was opened with, sorted.
+    pub fn column_families(&self) -> Vec<String> {
+        self.families.keys().cloned().collect()
+    }
+
+    fn family(&self, cf: &str) -> Result<&ColumnFamily> {
+        self.families
+            .get(cf)
+            .ok_or_else(|| OblivionError::ColumnFamilyNotFound(cf.to_string()))
+    }
+
+    /// Returns a handle for the logical database `index` (see the
+    /// [`database` module](database)). Database 0 is the keyspace
+    /// `put` and `get` use.
+    pub fn database(&self, index: usize) -> Result<Database<'_>> {
+        let databases = self.config().databases;
+        if index >= databases {
+            return Err(OblivionError::DatabaseOutOfRange { index, databases });
+        }
+        Ok(Database::new(self, index))
+    }
+
+    /// Apply the writes of `batch` in order, with one WAL append and
+    /// one sync. Readers see all of them at once; see [`batch`] for
+    /// the guarantees.
+    pub fn write(&self, batch: WriteBatch) -> Result<()> {
+        self.check_writable()?;
+        if batch.is_empty() {
+            return Ok(());
+        }
+        for op in batch.ops() {
+            match op {
+                BatchOp::Put { key, value, .. } => self.check_sizes(key, Some(value))?,
+                BatchOp::Delete { key } => self.check_sizes(key, None)?,
+            }
+        }
+        self.write_ops(batch.into_ops())
+    }
+
+    /// Begin a [`Transaction`] on the plain keyspace: reads see its own
+    /// writes over a snapshot taken now, and
+    /// [`commit`](Transaction::commit) applies them as one batch unless
+    /// a key it read has changed since.
+    pub fn transaction(&self) -> Transaction<'_> {
+        Database::new(self, 0).transaction()
+    }
+
+    /// Commit the writes `ops` of a transaction, as one batch, if every
+    /// key in `reads` still holds the value the transaction read.
+    pub(crate) fn commit_transaction(
+        &self,
+        reads: &HashMap<Key, Option<Value>>,
+        ops: Vec<BatchOp>,
+    ) -> Result<()> {
+        self.check_writable()?;
+        for op in &ops {
+            match op {
+                BatchOp::Put { key, value, .. } => self.check_sizes(key, Some(value))?,
+                BatchOp::Delete { key } => self.check_sizes(key, None)?,
+            }
+        }
+        self.tree.check_background_error()?;
+        // Checked under the writer lock, so no write lands between the
+        // check and the batch
+        let mut writer = self.writer.lock();
+        for (key, seen) in reads {
+            if self.state.get_opt(key, &ReadOptions::default())? != *seen {
+                return Err(OblivionError::TransactionConflict(format!(
+                    "{:?} changed after the transaction read it",
+                    String::from_utf8_lossy(key)
+                )));
+            }
+        }
+        self.write_ops_locked(&mut writer, ops)
+    }
+
+    /// Insert many key-value pairs with one WAL append and one sync,
+    /// for bulk loads that do not need a [`WriteBatch`]. The pairs are
+    /// applied in order, so the last value of a repeated key wins.
+    pub fn put_many<I, K, V>(&self, pairs: I) -> Result<()>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<Key>,
+        V: Into<Value>,
+    {
+        self.check_writable()?;
+        let mut ops = Vec::new();
+        for (key, value) in pairs {
+            let (key, value) = (key.into(), value.into());
+            self.check_sizes(&key, Some(&value))?;
+            ops.push(BatchOp::Put {
+                key,
+                value,
+                ttl_ms: None,
+            });
+        }
+        self.write_ops(ops)
+    }
+
+    /// Delete many keys with one WAL append and one sync.
+    pub fn delete_many<I>(&self, keys: I) -> Result<()>
+    where
+        I: IntoIterator,
+        I::Item: Into<Key>,
+    {
+        self.check_writable()?;
+        let mut ops = Vec::new();
+        for key in keys {
+            let key = key.into();
+            self.check_sizes(&key, None)?;
+            ops.push(BatchOp::Delete { key });
+        }
+        self.write_ops(ops)
+    }
+
+    /// Shared write path of `write`, `put_many` and `delete_many`: log
+    /// checked `ops` in one append, then apply them to the MemTable.
+    fn write_ops(&self, ops: Vec<BatchOp>) -> Result<()> {
+        if ops.is_empty() {
+            return Ok(());
+        }
+        self.tree.check_background_error()?;
+        let mut writer = self.writer.lock();
+        self.write_ops_locked(&mut writer, ops)
+    }
+
+    /// Log and apply `ops` while the caller holds the writer lock.
+    fn write_ops_locked(&self, writer: &mut Writer, ops: Vec<BatchOp>) -> Result<()> {
+        if ops.is_empty() {
+            return Ok(());
+        }
+        let first = writer
+            .wal
+            .append_batch(&ops)
+            .map_err(|e| self.tree.degrade_on_full_disk("WAL append", e))?;
+        fail_point!(self.tree.config(), AfterWalAppend);
+
+        let mut ttl_index = self.state.ttl_index.write();
+        for op in &ops {
+            match op {
+                BatchOp::Put { key, value, ttl_ms } => {
+                    self.metrics.record_put(key.len(), value.len());
+                    if let Some(ttl_ms) = ttl_ms {
+                        ttl_index.set_ttl(key.clone(), *ttl_ms);
+                    }
+                }
+                BatchOp::Delete { key } => {
+                    self.metrics.record_delete();
+                    ttl_index.remove_ttl(key);
+                }
+            }
+        }
+        drop(ttl_index);
+
+        let mut memtable = self.state.memtable.write();
+        for (sequence, op) in (first..).zip(&ops) {
+            self.publish_change(sequence, || match op {
+                BatchOp::Put { key, value, ttl_ms } => ChangeOp::Put {
+                    key: key.clone(),
+                    value: value.clone(),
+                    ttl_ms: *ttl_ms,
+                },
+                BatchOp::Delete { key } => ChangeOp::Delete { key: key.clone() },
+            });
+        }
+        let mut keys = Vec::with_capacity(ops.len());
+        for op in ops {
+            keys.push(op.key().to_vec());
+            match op {
+                BatchOp::Put { key, value, .. } => memtable.insert(key, value),
+                BatchOp::Delete { key } => memtable.delete(key),
+            }
+        }
+        drop(memtable);
+        for key in &keys {
+            self.invalidate_cached(key);
+        }
+
+        self.maybe_flush(writer)?;
+        self.update_write_gauges(&writer.wal);
         Ok(())
     }
 
     /// Scan all key-value pairs in sorted order.
-    /// Excludes keys with expired TTLs.
+    /// Merges SSTables and the MemTable (newer versions win).
+    /// Excludes deleted keys and keys with expired TTLs. Every row is
+    /// copied into the returned `Vec`; [`scan_iter`](Self::scan_iter)
+    /// streams them instead.
     pub fn scan(&self) -> Vec<(Key, Value)> {
+        self.scan_opt(&ReadOptions::default()).unwrap_or_else(|e| {
+            log::error!("Scan failed: {}", e);
+            Vec::new()
+        })
+    }
+
+    /// Scan key-value pairs in sorted order with per-read options.
+    /// Only keys within `[lower_bound, upper_bound)` are returned, and
+    /// SSTable blocks outside the bounds are never read.
+    pub fn scan_opt(&self, opts: &ReadOptions) -> Result<Vec<(Key, Value)>> {
+        self.state.scan_opt(opts)
+    }
+
+    /// Iterate over all key-value pairs in sorted order, like
+    /// [`scan`](Self::scan) but lazily: rows are read from a snapshot
+    /// taken now, a page at a time as the iterator advances, so memory
+    /// use stays bounded however large the store is.
+    pub fn scan_iter(&self) -> ScanIter {
+        self.scan_iter_opt(&ReadOptions::default())
+    }
+
+    /// Like [`scan_iter`](Self::scan_iter), over the range (and
+    /// snapshot, if set) of `opts`.
+    pub fn scan_iter_opt(&self, opts: &ReadOptions) -> ScanIter {
+        ScanIter::new(Arc::clone(&self.state), opts)
+    }
+
+    /// Scan one page of the range selected by `opts`.
+    ///
+    /// Reads at most `limit` records from each source, so a page holds
+    /// roughly `limit` rows (fewer when tombstones or expired keys fall
+    /// in it). Returns the rows and the lower bound to resume from, or
+    /// `None` once the range is exhausted. Pages taken without a snapshot
+    /// each see the latest state; keys present throughout are returned
+    /// exactly once.
+    pub fn scan_page(&self, opts: &ReadOptions, limit: usize) -> Result<ScanPage> {
+        self.state.scan_page(opts, limit.max(1))
+    }
+
+    /// Returns the live keys matching the glob `pattern` (see the
+    /// [`glob` module](glob)), in sorted order, e.g. `user:*:settings`.
+    /// Only keys starting with the pattern's literal prefix are read.
+    pub fn keys(&self, pattern: &[u8]) -> Result<Vec<Key>> {
+        self.keys_opt(pattern, &ReadOptions::default())
+    }
+
+    /// Like [`keys`](Self::keys), restricted to the bounds (and
+    /// snapshot) of `opts`.
+    pub fn keys_opt(&self, pattern: &[u8], opts: &ReadOptions) -> Result<Vec<Key>> {
+        let mut opts = opts.clone();
+        if opts.snapshot.is_none() {
+            opts.snapshot = Some(self.state.snapshot());
+        }
         self.metrics.record_scan();
-        self.memtable
-            .scan()
-            .into_iter()
-            .filter(|(k, _)| !self.ttl_index.is_expired(k))
-            .map(|(k, v)| (k.clone(), v.clone()))
-            .collect()
+        glob::matching_keys(self.comparator(), pattern, opts, |opts, limit| {
+            self.state.scan_page(opts, limit)
+        })
+    }
+
+    /// Returns a live key picked at random (see the [`sample`
+    /// module](sample)), or `None` if none was found in a few picks:
+    /// the store is empty, or nearly all of its entries are tombstones.
+    /// Reads at most a few data blocks, whatever the size of the store.
+    pub fn random_key(&self) -> Result<Option<Key>> {
+        Ok(self.random_keys(1)?.pop())
+    }
+
+    /// Returns up to `count` live keys picked at random, with
+    /// replacement, from one snapshot. Fewer are returned only when
+    /// live keys are too rare among the entries to find in
+    /// [`ATTEMPTS_PER_KEY`](sample::ATTEMPTS_PER_KEY) picks each.
+    pub fn random_keys(&self, count: usize) -> Result<Vec<Key>> {
+        self.random_keys_opt(count, &ReadOptions::default())
+    }
+
+    /// Like [`random_keys`](Self::random_keys), picking keys within the
+    /// bounds (and snapshot) of `opts`, e.g. one prefix.
+    pub fn random_keys_opt(&self, count: usize, opts: &ReadOptions) -> Result<Vec<Key>> {
+        sample::random_keys(&self.state, count, opts)
+    }
+
+    /// Returns the live key-value pairs passing `predicate` (see the
+    /// [`predicate` module](predicate)), in sorted order. Rows are
+    /// filtered as they are read, so only the matches are held in memory;
+    /// under byte order only keys sharing the predicate's required key
+    /// prefix are read.
+    pub fn scan_filtered(&self, predicate: &Predicate) -> Result<Vec<(Key, Value)>> {
+        self.scan_filtered_opt(predicate, &ReadOptions::default())
+    }
+
+    /// Like [`scan_filtered`](Self::scan_filtered), restricted to the
+    /// bounds (and snapshot) of `opts`.
+    pub fn scan_filtered_opt(
+        &self,
+        predicate: &Predicate,
+        opts: &ReadOptions,
+    ) -> Result<Vec<(Key, Value)>> {
+        predicate::matching_rows(&self.state, predicate, opts.clone())
+    }
+
+    /// Write every live key-value pair to `out` as NDJSON or CSV, with
+    /// the remaining TTL of keys that have one. See the [`export`
+    /// module](export) for the formats. Returns the number of rows written.
+    pub fn export<W: Write>(&self, out: &mut W, format: ExportFormat) -> Result<u64> {
+        self.export_opt(out, format, &ReadOptions::default())
+    }
+
+    /// Like [`export`](Self::export), restricted to the bounds (and
+    /// snapshot) of `opts`. Rows are streamed from a snapshot, so the
+    /// dump is consistent without holding the whole database in memory.
+    pub fn export_opt<W: Write>(
+        &self,
+        out: &mut W,
+        format: ExportFormat,
+        opts: &ReadOptions,
+    ) -> Result<u64> {
+        export::export_rows(&self.state, out, format, opts.clone())
+    }
+
+    /// Bulk-load a dump in one of the [`export`](Self::export) formats,
+    /// building SSTables directly instead of going through the WAL and
+    /// MemTable. Imported rows replace existing values of their keys.
+    /// Returns the number of rows read.
+    pub fn import<R: BufRead>(&self, reader: R, format: ExportFormat) -> Result<u64> {
+        self.import_opt(reader, format, &ImportOptions::default())
+    }
+
+    /// Like [`import`](Self::import), with the in-memory chunk size set
+    /// by `opts`. See the [`import` module](import) for the pipeline.
+    pub fn import_opt<R: BufRead>(
+        &self,
+        reader: R,
+        format: ExportFormat,
+        opts: &ImportOptions,
+    ) -> Result<u64> {
+        self.check_writable()?;
+        import::import_rows(self, reader, format, opts)
+    }
+
+    /// Ingest LevelDB or RocksDB table files, oldest first: each is
+    /// converted into an SSTable, then all are added at once and shadow
+    /// existing values of their keys. Returns the number of keys read.
+    /// See the [`compat` module](compat) for what can be read. Foreign
+    /// tables are byte-ordered, so engines with a custom comparator
+    /// refuse them.
+    #[cfg(feature = "sst-compat")]
+    pub fn ingest_foreign_tables<P: AsRef<Path>>(&self, paths: &[P]) -> Result<u64> {
+        self.check_writable()?;
+        if !self.table_cache.comparator().is_bytewise() {
+            return Err(OblivionError::Unsupported(format!(
+                "foreign tables are byte-ordered, not sorted by '{}'",
+                self.table_cache.comparator().name()
+            )));
+        }
+        compat::ingest_foreign_tables(self, paths)
+    }
+
+    /// Change runtime-mutable options (see [`MUTABLE_OPTIONS`](crate::config::MUTABLE_OPTIONS))
+    /// on the live engine, e.g. `[("memtable_max_size", "8388608")]`.
+    ///
+    /// Options are applied all-or-nothing: if any name or value is
+    /// invalid, or the resulting configuration fails validation,
+    /// nothing changes. A lower flush or compaction threshold takes
+    /// effect immediately.
+    pub fn set_options<I, K, V>(&self, options: I) -> Result<()>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let mut writer = self.writer.lock();
+        let previous = self.tree.config();
+        let mut config = (*previous).clone();
+        for (name, value) in options {
+            config.set_option(name.as_ref(), value.as_ref())?;
+        }
+        config.validate()?;
+
+        writer.wal.set_sync(config.sync_writes);
+        writer.wal.set_sync_method(config.wal_sync_method);
+        self.table_cache.set_capacity(config.max_open_files);
+        let row_cache = &self.state.row_cache;
+        match (row_cache.load_full(), config.row_cache_capacity) {
+            (_, 0) => row_cache.store(None),
+            (Some(cache), capacity) => cache.set_capacity(capacity),
+            (None, capacity) => row_cache.store(Some(Arc::new(RowCache::new(capacity)))),
+        }
+        self.table_cache
+            .set_block_cache_capacity(config.block_cache_capacity);
+        if config.stats_dump_period_secs != previous.stats_dump_period_secs {
+            self.restart_stats_dumper(&config);
+        }
+        let restart_wal_syncer = (config.sync_writes, config.wal_sync_interval_ms)
+            != (previous.sync_writes, previous.wal_sync_interval_ms);
+        log::info!("Options updated: {:?}", config);
+        self.tree.set_config(config);
+
+        self.maybe_flush(&mut writer)?;
+        drop(writer);
+        // Outside the writer lock, which the old thread may be waiting on
+        if restart_wal_syncer {
+            self.restart_wal_syncer(&self.tree.config());
+        }
+        self.schedule_compaction()
+    }
+
+    /// Check for compaction work: inline, or as a job for the
+    /// background workers.
+    fn schedule_compaction(&self) -> Result<()> {
+        let trees = self.trees();
+        match &self.pool {
+            Some(pool) => {
+                pool.submit(JobKind::Compaction, move || compact_trees(&trees));
+                Ok(())
+            }
+            None => compact_trees(&trees),
+        }
+    }
+
+    /// The trees of the column families, then the default one.
+    fn trees(&self) -> Vec<Arc<Tree>> {
+        let families = self.families.values().map(|f| Arc::clone(&f.tree));
+        families.chain([Arc::clone(&self.tree)]).collect()
+    }
+
+    /// Returns whether any MemTable, of the default keyspace or a column
+    /// family, holds writes.
+    fn has_unflushed_writes(&self) -> bool {
+        !self.is_empty()
+            || self
+                .families
+                .values()
+                .any(|f| !f.state.memtable.read().is_empty())
+    }
+
+    /// Returns the current engine configuration.
+    pub fn config(&self) -> Arc<Config> {
+        self.tree.config()
+    }
+
+    /// Returns the order scans return keys in.
+    pub fn comparator(&self) -> &Comparator {
+        &self.state.comparator
     }
 
     /// Get the remaining TTL for a key in milliseconds.
-    pub fn ttl(&self, key: &[u8]) -> Option<u64> {
-        self.ttl_index.remaining_ttl(key)
+    pub fn ttl(&self, key: impl AsRef<[u8]>) -> Option<u64> {
+        self.state.ttl(key.as_ref())
     }
 
-    /// Returns the number of entries in the MemTable.
+    /// Returns the number of entries in the active MemTable, tombstones
+    /// included. Frozen MemTables and SSTables are not counted, so this
+    /// is not the number of keys: it drops to 0 at every flush. For
+    /// that, see [`dbsize_estimate`](Self::dbsize_estimate) (cheapest),
+    /// [`estimate_live_keys`](Self::estimate_live_keys) (closer) or
+    /// [`dbsize_exact`](Self::dbsize_exact) (a full scan).
     pub fn len(&self) -> usize {
-        self.memtable.len()
+        self.state.len()
+    }
+
+    /// Estimate the number of keys in the default keyspace, as a full
+    /// scan would count them, without reading any data.
+    ///
+    /// SSTables are counted from their properties: each adds its
+    /// values, less its tombstones if an older table's key range meets
+    /// its own. MemTable entries are looked up in the older MemTables
+    /// and the SSTables' filters, so a MemTable overwrite adds nothing
+    /// and a MemTable delete subtracts a key only if there was one.
+    ///
+    /// The estimate is exact after a full [`compact`](Self::compact)
+    /// with an empty MemTable. Otherwise a key written to several
+    /// SSTables counts once per table until compaction merges them,
+    /// filter false positives can hide a new key, and expired TTL keys
+    /// count until compaction drops them.
+    pub fn estimate_live_keys(&self) -> u64 {
+        self.tree.estimate_live_keys()
+    }
+
+    /// Estimate the number of keys from counters alone, in time that
+    /// grows with the number of SSTables and MemTables but not with
+    /// their size, so it is safe to poll.
+    ///
+    /// SSTables are counted from their properties as in
+    /// [`estimate_live_keys`](Self::estimate_live_keys). A MemTable adds
+    /// its values, and each of its tombstones deletes a key if there is
+    /// older data; unlike `estimate_live_keys`, keys are not looked up,
+    /// so a MemTable overwrite counts as a new key until it is flushed
+    /// and compacted.
+    pub fn dbsize_estimate(&self) -> u64 {
+        self.tree.dbsize_estimate()
+    }
+
+    /// Count the keys a full scan returns, by merging every MemTable
+    /// and SSTable over a snapshot taken now. Values read are not added
+    /// to the row cache, nor their blocks to the block cache.
+    ///
+    /// This reads the whole keyspace, so `cancel` is checked between
+    /// pages; setting it from another thread stops the count with
+    /// [`OblivionError::Cancelled`].
+    pub fn dbsize_exact(&self, cancel: &AtomicBool) -> Result<u64> {
+        let mut opts = ReadOptions::new().fill_cache(false);
+        opts.snapshot = Some(self.state.snapshot());
+        self.state.metrics.record_scan();
+        let mut count = 0u64;
+        loop {
+            if cancel.load(Ordering::Relaxed) {
+                return Err(OblivionError::Cancelled(format!(
+                    "dbsize counted {} keys before it was cancelled",
+                    count
+                )));
+            }
+            let (rows, resume) = self.state.scan_page(&opts, DBSIZE_PAGE_SIZE)?;
+            count += rows.len() as u64;
+            match resume {
+                Some(resume) => opts.lower_bound = Some(resume),
+                None => return Ok(count),
+            }
+        }
     }
 
-    /// Returns true if the engine has no entries.
+    /// Returns true if the active MemTable has no entries; like
+    /// [`len`](Self::len), this ignores flushed data.
     pub fn is_empty(&self) -> bool {
-        self.memtable.is_empty()
+        self.len() == 0
     }
 
     /// Returns the approximate size of the MemTable in bytes.
     pub fn memtable_size(&self) -> usize {
-        self.memtable.size()
+        self.state.memtable_size()
     }
 
     /// Returns a reference to the engine metrics.
@@ -148,49 +1547,687 @@ impl Oblivion {
         &self.metrics
     }
 
-    /// Check if the MemTable exceeds the configured size threshold.
-    /// If so, trigger a flush: simulate writing to SSTable,
-    /// truncate the WAL, and reset the MemTable.
-    fn maybe_flush(&mut self) -> Result<()> {
-        if self.memtable.size() >= self.config.memtable_max_size {
+    /// Returns the row cache, if enabled.
+    pub fn row_cache(&self) -> Option<Arc<RowCache>> {
+        self.state.row_cache.load_full()
+    }
+
+    /// Returns the block cache, if enabled.
+    pub fn block_cache(&self) -> Option<Arc<BlockCache>> {
+        self.table_cache.block_cache()
+    }
+
+    /// Returns the number of SSTable file handles currently open.
+    pub fn open_table_files(&self) -> usize {
+        self.table_cache.len()
+    }
+
+    /// Report the on-disk footprint: live SSTables per tier, SSTable,
+    /// WAL and manifest bytes, and bytes held by obsolete files.
+    pub fn disk_usage(&self) -> Result<DiskUsage> {
+        self.tree.disk_usage()
+    }
+
+    /// Read back every block of every live SSTable, every WAL record and
+    /// the manifests, checking checksums, key order and metadata; see
+    /// the [`integrity` module](integrity). Damage is reported rather
+    /// than returned as an error, and nothing is changed.
+    pub fn verify_integrity(&self) -> Result<IntegrityReport> {
+        let mut report = IntegrityReport::default();
+        let trees = std::iter::once(&self.tree).chain(self.families.values().map(|cf| &cf.tree));
+        for tree in trees {
+            let config = tree.config();
+            let (manifest, version) = tree.stored_manifest();
+            integrity::check_manifest(&config, manifest, version.sstables(), &mut report);
+            for table in version.sstables() {
+                integrity::check_table(table, &config.comparator, &mut report);
+            }
+        }
+        let config = self.tree.config();
+        let mut logs: Vec<PathBuf> = WriteAheadLog::segments_in(&config.env, &config.wal_dir())?
+            .into_iter()
+            .map(|(_, path)| path)
+            .collect();
+        logs.push(config.wal_path());
+        integrity::check_logs(&config.env, &logs, &mut report);
+        Ok(report)
+    }
+
+    /// Returns the live SSTables of the default keyspace, then those of
+    /// each column family by name, oldest first within each; see the
+    /// [`properties` module](properties).
+    pub fn live_files(&self) -> Vec<LiveFile> {
+        let mut files = self.tree.live_files(DEFAULT_COLUMN_FAMILY);
+        for (name, family) in &self.families {
+            files.extend(family.tree.live_files(name));
+        }
+        files
+    }
+
+    /// Returns the property `name` (see the
+    /// [`properties` module](properties)) of the default keyspace, or
+    /// `None` if there is no such property.
+    pub fn get_property(&self, name: &str) -> Option<String> {
+        self.property(&self.tree, name)
+    }
+
+    /// Like [`get_property`](Self::get_property), for the column family
+    /// `cf`.
+    pub fn get_property_cf(&self, cf: &str, name: &str) -> Result<Option<String>> {
+        Ok(self.property(&self.family(cf)?.tree, name))
+    }
+
+    fn property(&self, tree: &Tree, name: &str) -> Option<String> {
+        match name {
+            properties::LAST_SEQUENCE => Some(self.writer.lock().wal.last_sequence().to_string()),
+            properties::BACKGROUND_ERRORS => Some(
+                self.metrics
+                    .background_errors
+                    .load(Ordering::Relaxed)
+                    .to_string(),
+            ),
+            properties::STATS => Some(self.metrics.report()),
+            _ => tree.property(name).map(|value| value.to_string()),
+        }
+    }
+
+    /// Returns the first failure of a background job, if any. Once a
+    /// job has failed, writes are refused with
+    /// [`OblivionError::Background`].
+    pub fn background_error(&self) -> Option<String> {
+        self.tree.background_error()
+    }
+
+    /// Returns what failed if the disk filled up during a write, flush
+    /// or compaction. The engine is then in read-only degraded mode
+    /// until reopened: reads work, writes fail with
+    /// [`OblivionError::DiskFull`] without touching the disk, and
+    /// flushes and compactions stop instead of retrying.
+    pub fn degraded(&self) -> Option<String> {
+        self.tree.degraded()
+    }
+
+    /// Block until every queued flush, compaction and WAL sync has
+    /// finished, then report any background failure. Returns at once
+    /// when `background_threads` is 0.
+    pub fn wait_for_background_work(&self) -> Result<()> {
+        if let Some(pool) = &self.pool {
+            pool.wait_idle();
+        }
+        self.tree.check_background_error()
+    }
+
+    /// Flush the MemTable to an SSTable on the calling thread, along
+    /// with any frozen MemTables still queued. Writes wait until it is
+    /// done.
+    pub fn flush(&self) -> Result<()> {
+        let mut writer = self.writer.lock();
+        self.flush_locked(&mut writer)?;
+        drop(writer);
+        self.schedule_compaction()
+    }
+
+    fn flush_locked(&self, writer: &mut Writer) -> Result<()> {
+        self.tree.check_background_error()?;
+        if self.has_unflushed_writes() {
+            self.freeze(writer)?;
+        }
+        flush_trees(&self.trees())?;
+        self.update_write_gauges(&writer.wal);
+        Ok(())
+    }
+
+    /// Merge all SSTables into one run on the calling thread, dropping
+    /// tombstones and expired keys; the output is split into tables of
+    /// `target_file_size`. The MemTable is not flushed first.
+    ///
+    /// Live snapshots and iterators keep reading the replaced tables,
+    /// whose files are deleted once the last of them is dropped.
+    pub fn compact(&self) -> Result<()> {
+        self.tree.check_background_error()?;
+        self.tree
+            .compact_all()
+            .map_err(|e| self.tree.degrade_on_full_disk("compaction", e))
+    }
+
+    /// Like [`compact`](Self::compact), but only merges the SSTables
+    /// holding keys in `[start, end]` (either bound open when `None`),
+    /// plus any tables between them, so a range with many
+    /// tombstones can be cleaned without rewriting the whole store.
+    pub fn compact_range(&self, start: Option<&[u8]>, end: Option<&[u8]>) -> Result<()> {
+        self.tree.check_background_error()?;
+        self.tree
+            .compact_range(start, end)
+            .map_err(|e| self.tree.degrade_on_full_disk("compaction", e))
+    }
+
+    /// Write a consistent, openable copy of the database to `dir`,
+    /// which must not exist yet.
+    ///
+    /// The MemTable is flushed first; the live SSTables are then
+    /// hard-linked into `dir` (copied across filesystems) and the
+    /// manifest and WAL copied, so the cost grows with the number of
+    /// files rather than their size. Writes wait until it is done.
+    /// Needs the local file system.
+    pub fn create_checkpoint(&self, dir: impl AsRef<Path>) -> Result<()> {
+        self.tree.config().env.require_local("checkpoints")?;
+        let mut writer = self.writer.lock();
+        self.flush_locked(&mut writer)?;
+
+        let config = self.tree.config();
+        let mut target = (*config).clone();
+        target.data_dir = dir.as_ref().to_path_buf();
+        self.tree.checkpoint_tables(&target)?;
+        std::fs::copy(config.wal_path(), target.wal_path())?;
+        if !self.families.is_empty() {
+            let registry = column_family::REGISTRY_FILE_NAME;
+            std::fs::copy(
+                config.data_dir.join(registry),
+                target.data_dir.join(registry),
+            )?;
+            std::fs::create_dir(target.data_dir.join(column_family::FAMILIES_DIR))?;
+            for (name, family) in &self.families {
+                let mut family_target = (*family.tree.config()).clone();
+                family_target.data_dir = column_family::family_dir(&target, name);
+                family.tree.checkpoint_tables(&family_target)?;
+            }
+        }
+        log::info!("Checkpoint created at {:?}", target.data_dir);
+        Ok(())
+    }
+
+    /// Returns the sequence number of the last committed write. Every
+    /// put and delete, column families included, takes the next number,
+    /// across restarts.
+    pub fn latest_sequence(&self) -> u64 {
+        self.writer.lock().wal.last_sequence()
+    }
+
+    /// Flush and sync the WAL, making every committed write durable
+    /// even with `sync_writes` off.
+    pub fn sync_wal(&self) -> Result<()> {
+        self.writer.lock().wal.sync()
+    }
+
+    /// Stream the committed puts and deletes from sequence number
+    /// `from_sequence` on, in order: first those still in the WAL, then
+    /// new ones as they are committed. Pass `latest_sequence() + 1` for
+    /// new writes only.
+    ///
+    /// Writes older than the WAL reaches back fail with
+    /// [`OblivionError::ChangesUnavailable`]; set `wal_retention_size`
+    /// to keep flushed segments for subscribers that resume later. See
+    /// [`changes`] for what the stream does not report.
+    pub fn subscribe_changes(&self, from_sequence: u64) -> Result<ChangeStream> {
+        let writer = self.writer.lock();
+        let until = writer.wal.last_sequence();
+        let families = self
+            .families
+            .iter()
+            .map(|(name, family)| (family.id, name.clone()))
+            .collect();
+        let stream = changes::subscribe(
+            &self.changes,
+            &self.tree.config(),
+            families,
+            from_sequence,
+            until,
+        )?;
+        drop(writer);
+        stream.seek()
+    }
+
+    /// Watch the keys starting with `prefix` (empty for every key):
+    /// the receiver gets a [`ChangeEvent`] for each put, delete and
+    /// expiration from now on, in commit order. Drop it to stop
+    /// watching; see [`changes`] for when expirations are reported.
+    pub fn watch_prefix(&self, prefix: &[u8]) -> Receiver<ChangeEvent> {
+        self.changes.watch(prefix.to_vec())
+    }
+
+    /// Returns whether the engine is a replication follower, which
+    /// refuses writes until it is promoted.
+    pub fn is_read_only(&self) -> bool {
+        self.read_only.load(Ordering::Acquire)
+    }
+
+    /// Mark the engine as a follower or as writable again. Returns the
+    /// previous setting.
+    pub(crate) fn set_read_only(&self, read_only: bool) -> bool {
+        self.read_only.swap(read_only, Ordering::AcqRel)
+    }
+
+    /// Start recording writes in the replication backlog.
+    pub(crate) fn enable_backlog(&self) {
+        let writer = self.writer.lock();
+        self.backlog.enable(writer.wal.last_sequence());
+    }
+
+    /// Start recording writes and take a snapshot of the default
+    /// keyspace and of each column family, by name. Returns the backlog's
+    /// epoch and the sequence number the snapshots include writes up to.
+    pub(crate) fn replication_point(&self) -> (u64, u64, Snapshot, Vec<(String, Snapshot)>) {
+        let writer = self.writer.lock();
+        let sequence = writer.wal.last_sequence();
+        self.backlog.enable(sequence);
+        let (epoch, _) = self.backlog.position();
+        let families = self
+            .families
+            .iter()
+            .map(|(name, family)| (name.clone(), family.state.snapshot()))
+            .collect();
+        (epoch, sequence, self.state.snapshot(), families)
+    }
+
+    /// Returns the number of SSTables on disk.
+    pub fn sstable_count(&self) -> usize {
+        self.state.current().sstables().len()
+    }
+
+    /// Path of the SSTable with the given file number.
+    pub(crate) fn sstable_path(config: &Config, id: u64) -> PathBuf {
+        config.sst_dir().join(format!("sstable_{:06}.sst", id))
+    }
+
+    /// Open the SSTables listed in the manifest, oldest first, and
+    /// remove files it does not list (outputs of interrupted flushes or
+    /// compactions, inputs of finished ones).
+    ///
+    /// Without a manifest (new database, or one migrated from the flat
+    /// layout) every SSTable on disk is adopted in file number order.
+    /// Fails if the manifest names another comparator than the config,
+    /// or if byte-ordered tables would be adopted under a custom one.
+    /// With `rebuild_manifest_on_open`, a missing or unreadable manifest
+    /// is instead rebuilt by [`rebuild_manifest`](Self::rebuild_manifest).
+    fn load_sstables(
+        config: &Config,
+        table_cache: &Arc<TableCache>,
+    ) -> Result<(Vec<Arc<SSTable>>, Manifest)> {
+        let mut on_disk = Vec::new();
+        for path in config.env.list(&config.sst_dir())? {
+            let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            if name.ends_with(".sst.tmp") {
+                log::warn!("Removing incomplete SSTable {:?}", path);
+                config.env.remove_file(&path)?;
+                continue;
+            }
+            if let Some(id) = name
+                .strip_prefix("sstable_")
+                .and_then(|rest| rest.strip_suffix(".sst"))
+                .and_then(|id| id.parse::<u64>().ok())
+            {
+                on_disk.push(id);
+            }
+        }
+        on_disk.sort_unstable();
+
+        let manifest_path = config.manifest_path();
+        let stored = match Manifest::load(&config.env, &manifest_path) {
+            Err(e) if config.rebuild_manifest_on_open => {
+                log::warn!("Rebuilding unreadable manifest: {}", e);
+                None
+            }
+            result => result?,
+        };
+        let manifest = match stored {
+            Some(manifest) => {
+                manifest.check_comparator(&config.comparator)?;
+                for id in on_disk.iter().filter(|id| !manifest.tables.contains(id)) {
+                    let path = Self::sstable_path(config, *id);
+                    log::warn!("Removing SSTable {:?} not listed in the manifest", path);
+                    config.env.remove_file(&path)?;
+                }
+                manifest
+            }
+            None if config.rebuild_manifest_on_open && !on_disk.is_empty() => {
+                let manifest = Self::rebuild_manifest(config, table_cache, &on_disk)?;
+                manifest.save(&config.env, &manifest_path)?;
+                manifest
+            }
+            None => {
+                if !on_disk.is_empty() && !config.comparator.is_bytewise() {
+                    return Err(OblivionError::Config(format!(
+                        "SSTables without a manifest are byte-ordered, not sorted by '{}'",
+                        config.comparator.name()
+                    )));
+                }
+                let manifest = Manifest {
+                    next_file_number: on_disk.last().map_or(0, |id| id + 1),
+                    tables: on_disk,
+                    comparator: config.comparator.name().to_string(),
+                };
+                manifest.save(&config.env, &manifest_path)?;
+                manifest
+            }
+        };
+
+        let tables = manifest
+            .tables
+            .iter()
+            .map(|&id| SSTable::open(Self::sstable_path(config, id), table_cache).map(Arc::new))
+            .collect::<Result<Vec<_>>>()?;
+        Ok((tables, manifest))
+    }
+
+    /// Rebuild the manifest from the SSTables `on_disk`, assumed sorted
+    /// by the configured comparator. Tables whose footer, index or
+    /// properties cannot be read are moved to `lost/`; the rest are
+    /// listed oldest first by sequence range (newest write, then oldest
+    /// write, then file number), so tables without one come first.
+    fn rebuild_manifest(
+        config: &Config,
+        table_cache: &Arc<TableCache>,
+        on_disk: &[u64],
+    ) -> Result<Manifest> {
+        let mut tables = Vec::with_capacity(on_disk.len());
+        for &id in on_disk {
+            let path = Self::sstable_path(config, id);
+            match SSTable::open(path.clone(), table_cache) {
+                Ok(table) => {
+                    let properties = table.properties();
+                    tables.push((properties.largest_seqno, properties.smallest_seqno, id));
+                }
+                Err(e) => {
+                    log::warn!("Quarantining SSTable {:?}: {}", path, e);
+                    let lost = config.data_dir.join(repair::LOST_DIR);
+                    config.env.create_dir_all(&lost)?;
+                    let target = lost.join(path.file_name().unwrap_or_default());
+                    config.env.rename(&path, &target)?;
+                    config.env.sync_dir(&lost)?;
+                    config.env.sync_parent(&path)?;
+                }
+            }
+        }
+        tables.sort_unstable();
+        log::warn!(
+            "Rebuilt manifest from {} of {} SSTables",
+            tables.len(),
+            on_disk.len()
+        );
+        Ok(Manifest {
+            next_file_number: on_disk.last().map_or(0, |id| id + 1),
+            tables: tables.into_iter().map(|(_, _, id)| id).collect(),
+            comparator: config.comparator.name().to_string(),
+        })
+    }
+
+    /// Check if a MemTable (of the default keyspace or a column family)
+    /// exceeds its size threshold. If so, freeze it and flush it to a new
+    /// SSTable: on the writing thread, or on a background worker if
+    /// `background_threads` is set.
+    ///
+    /// Readers keep seeing the frozen MemTable until the version with
+    /// the new SSTable replaces it. With background workers, writes
+    /// stall while more than `MAX_PENDING_FLUSHES` MemTables wait.
+    fn maybe_flush(&self, writer: &mut Writer) -> Result<()> {
+        let keyspaces = || {
+            let families = self.families.values().map(|f| (&f.state, &f.tree));
+            [(&self.state, &self.tree)].into_iter().chain(families)
+        };
+        let full = keyspaces()
+            .map(|(state, tree)| (state.memtable_size(), tree.memtable_threshold()))
+            .find(|(size, limit)| size >= limit);
+        if let Some((size, limit)) = full {
             log::info!(
                 "MemTable size ({} bytes) exceeds threshold ({} bytes), triggering flush...",
-                self.memtable.size(),
-                self.config.memtable_max_size
+                size,
+                limit
             );
+            for (state, tree) in keyspaces() {
+                tree.record_fill(state.memtable_size());
+            }
+            self.freeze(writer)?;
+        }
 
-            // Purge expired keys before flushing
-            self.ttl_index.purge_expired();
-
-            // In production: write MemTable entries to SSTable
-            let sstable_path = self
-                .config
-                .data_dir
-                .join(format!("sstable_{:06}.sst", self.flush_count));
-            let entries = self
-                .memtable
-                .scan()
-                .into_iter()
-                .filter(|(k, _)| !self.ttl_index.is_expired(k))
-                .map(|(k, v)| (k.clone(), v.clone()))
-                .collect::<Vec<_>>();
-            let _sstable = sstable::SSTable::flush_from_memtable(sstable_path, &entries)?;
-
-            // Truncate WAL (data is now in SSTable)
-            self.wal.truncate()?;
-
-            // Reset MemTable
-            self.memtable.clear();
-            self.flush_count += 1;
-            self.metrics.record_flush();
+        let trees = self.trees();
+        match &self.pool {
+            Some(_) => trees
+                .iter()
+                .try_for_each(|tree| tree.wait_for_flushes(MAX_PENDING_FLUSHES)),
+            // A failed flush stays queued and is retried on the next write
+            None if trees.iter().any(|tree| tree.pending_flushes() > 0) => {
+                flush_trees(&trees)?;
+                compact_trees(&trees)
+            }
+            None => Ok(()),
+        }
+    }
 
-            log::info!(
-                "Flush #{} complete. {} entries written to SSTable.",
-                self.flush_count,
-                entries.len()
-            );
+    /// Freeze the active MemTable: rotate the WAL into a segment holding
+    /// exactly its writes, queue it for flushing, and with background
+    /// workers, schedule the flush.
+    ///
+    /// The non-empty MemTables of column families are frozen along with
+    /// it, and the segment is tied to the default keyspace's (even if
+    /// empty), which [`flush_trees`] flushes last.
+    fn freeze(&self, writer: &mut Writer) -> Result<()> {
+        let config = self.tree.config();
+        let segment = WriteAheadLog::segment_path(&config.wal_dir(), self.tree.new_file_number());
+        let rotated = writer
+            .wal
+            .rotate(&segment)
+            .map_err(|e| self.tree.degrade_on_full_disk("WAL rotation", e))?;
+        self.tree.add_segment_bytes(rotated);
+        let mut logs = std::mem::take(&mut writer.active_logs);
+        logs.push(segment.clone());
+        for family in self.families.values() {
+            if !family.state.memtable.read().is_empty() {
+                family.tree.freeze(Vec::new(), 0);
+            }
         }
+        self.tree.freeze(logs, writer.wal.last_sequence());
+        writer
+            .wal
+            .append_sequence_marker()
+            .map_err(|e| self.tree.degrade_on_full_disk("WAL append", e))?;
 
+        if let Some(pool) = &self.pool {
+            // Unsynced writes reach disk without waiting for the flush
+            if !config.sync_writes {
+                let tree = Arc::clone(&self.tree);
+                pool.submit(JobKind::WalSync, move || {
+                    tree.sync_segment(&segment)
+                        .map_err(|e| tree.degrade_on_full_disk("WAL sync", e))
+                });
+            }
+            let trees = self.trees();
+            pool.submit(JobKind::Flush, move || flush_trees(&trees));
+            let trees = self.trees();
+            pool.submit(JobKind::Compaction, move || compact_trees(&trees));
+        }
         Ok(())
     }
+
+    /// Make the SSTables built by an import live: flush the MemTable so
+    /// the imported rows shadow everything written before, then add the
+    /// tables to the manifest in one update and apply the rows' TTLs.
+    fn ingest(&self, tables: Vec<(u64, SSTable)>, ttls: Vec<(Key, u64)>) -> Result<()> {
+        let mut writer = self.writer.lock();
+        self.tree.check_background_error()?;
+        if self.has_unflushed_writes() {
+            self.freeze(&mut writer)?;
+        }
+        flush_trees(&self.trees())?;
+        self.update_write_gauges(&writer.wal);
+        let count = tables.len();
+        self.tree
+            .ingest_tables(tables.into_iter().map(|(id, t)| (id, Arc::new(t))))?;
+        // Ingested rows bypass the WAL; followers need a full sync
+        if self.backlog.is_enabled() {
+            self.backlog.reset(writer.wal.last_sequence());
+        }
+        drop(writer);
+
+        let mut ttl_index = self.state.ttl_index.write();
+        for (key, ttl) in ttls {
+            ttl_index.set_ttl(key, ttl);
+        }
+        drop(ttl_index);
+        if let Some(cache) = &*self.state.row_cache.load() {
+            cache.clear();
+        }
+        log::info!("Ingested {} imported SSTables", count);
+        self.schedule_compaction()
+    }
+
+    /// Start, stop or restart the stats dump thread to match the config.
+    fn restart_stats_dumper(&self, config: &Config) {
+        let mut stats_dumper = self.stats_dumper.lock();
+        // Drop (and join) the old thread first
+        *stats_dumper = None;
+        if config.stats_dump_period_secs > 0 {
+            *stats_dumper = Some(StatsDumper::start(
+                Arc::clone(&self.metrics),
+                Duration::from_secs(config.stats_dump_period_secs),
+            ));
+        }
+    }
+
+    /// (Re)start the WAL sync thread for `config`; it runs only while
+    /// `sync_writes` is off and `wal_sync_interval_ms` is set. Must not
+    /// be called with the writer lock held.
+    fn restart_wal_syncer(&self, config: &Config) {
+        let mut wal_syncer = self.wal_syncer.lock();
+        // Drop (and join) the old thread first
+        *wal_syncer = None;
+        if config.sync_writes || config.wal_sync_interval_ms == 0 {
+            return;
+        }
+        let writer = Arc::clone(&self.writer);
+        let tree = Arc::clone(&self.tree);
+        let period = Duration::from_millis(config.wal_sync_interval_ms);
+        *wal_syncer = Some(WalSyncer::start(period, move || {
+            let mut writer = writer.lock();
+            if !writer.wal.has_unsynced_writes() {
+                return;
+            }
+            if let Err(e) = writer.wal.sync() {
+                let e = tree.degrade_on_full_disk("WAL sync", e);
+                log::error!("Periodic WAL sync failed: {}", e);
+                tree.record_background_error(JobKind::WalSync, &e.to_string());
+            }
+        }));
+    }
+
+    /// Refresh the MemTable and WAL size gauges after a write.
+    fn update_write_gauges(&self, wal: &WriteAheadLog) {
+        self.metrics.set_memtable_bytes(self.memtable_size() as u64);
+        self.tree.set_log_bytes(wal.size());
+        self.metrics.set_wal_bytes(self.tree.wal_bytes());
+    }
+}
+
+/// Remove the files of the database in `config.data_dir` other than
+/// `LOCK`, and the subdirectories they leave empty. The manifest goes
+/// first, so a database destroyed halfway no longer lists its tables.
+fn remove_database_files(config: &Config) -> Result<()> {
+    let env = &config.env;
+    let manifest = config.manifest_path();
+    for path in [
+        manifest.clone(),
+        manifest.with_extension("tmp"),
+        config.data_dir.join(column_family::REGISTRY_FILE_NAME),
+        config.data_dir.join(replication::STATE_FILE),
+    ] {
+        match env.remove_file(&path) {
+            Err(e) if is_missing(&e) => {}
+            result => result?,
+        }
+    }
+    // Subdirectories before their parent; `data_dir` itself for files
+    // left by the old flat layout
+    for dir in [
+        config.wal_archive_dir(),
+        config.wal_dir(),
+        config.sst_dir(),
+        config.data_dir.join(repair::LOST_DIR),
+        config.data_dir.clone(),
+    ] {
+        for path in list_dir(env, &dir)? {
+            let name = path.file_name().and_then(|name| name.to_str());
+            if name.is_some_and(is_engine_file) {
+                env.remove_file(&path)?;
+            }
+        }
+        if dir != config.data_dir {
+            remove_empty_dir(env, &dir)?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns true for the name of a WAL segment, live log, SSTable or
+/// SSTable being written.
+fn is_engine_file(name: &str) -> bool {
+    let numbered = |prefix: &str, suffix: &str| {
+        name.strip_prefix(prefix)
+            .and_then(|rest| rest.strip_suffix(suffix))
+            .is_some_and(|number| number.parse::<u64>().is_ok())
+    };
+    name == WAL_FILE_NAME
+        || numbered("oblivion_", ".wal")
+        || numbered("sstable_", ".sst")
+        || numbered("sstable_", ".sst.tmp")
+}
+
+/// Returns the entries of `dir`, or none if it is missing or a file.
+fn list_dir(env: &Env, dir: &Path) -> Result<Vec<PathBuf>> {
+    match env.list(dir) {
+        Err(e) if is_missing(&e) => Ok(Vec::new()),
+        result => Ok(result?),
+    }
+}
+
+/// Returns true if `error` is a path, or one of its parents, not
+/// being a directory that exists.
+fn is_missing(error: &std::io::Error) -> bool {
+    matches!(error.kind(), ErrorKind::NotFound | ErrorKind::NotADirectory)
+}
+
+/// Remove `dir` if it exists and is empty.
+fn remove_empty_dir(env: &Env, dir: &Path) -> Result<()> {
+    if !list_dir(env, dir)?.is_empty() {
+        return Ok(());
+    }
+    match env.remove_dir(dir) {
+        Err(e) if is_missing(&e) => Ok(()),
+        result => Ok(result?),
+    }
+}
+
+/// Flush the frozen MemTables of `trees`, the column families' followed
+/// by the default keyspace's (last). The default tree only flushes what
+/// was frozen before the families were, so a WAL segment is deleted only
+/// once every MemTable holding its writes is on disk. Skipped once the
+/// disk is full.
+fn flush_trees(trees: &[Arc<Tree>]) -> Result<()> {
+    let Some((default, families)) = trees.split_last() else {
+        return Ok(());
+    };
+    if default.degraded().is_some() {
+        return Ok(());
+    }
+    let freezes = default.freezes();
+    families
+        .iter()
+        .try_for_each(|tree| tree.flush_pending())
+        .and_then(|()| default.flush_until(freezes))
+        .map_err(|e| default.degrade_on_full_disk("flush", e))
+}
+
+/// Run the compaction check of every tree in `trees`; skipped once the
+/// disk is full.
+fn compact_trees(trees: &[Arc<Tree>]) -> Result<()> {
+    let Some(default) = trees.last() else {
+        return Ok(());
+    };
+    if default.degraded().is_some() {
+        return Ok(());
+    }
+    trees
+        .iter()
+        .try_for_each(|tree| tree.maybe_compact())
+        .map_err(|e| default.degrade_on_full_disk("compaction", e))
 }
@@ -0,0 +1,1094 @@
+//! OBLIVION - Object Storage
+//! SSTables kept in an S3-compatible object store instead of on local
+//! disk, so a cold keyspace can grow past the disks of the machine
+//! serving it (`object-store` feature).
+//!
+//! [`ObjectFileSystem`] is a [`FileSystem`] sending the SSTables under
+//! one directory, its root, to an [`ObjectStore`], and everything else
+//! (the WAL, the manifest, the `LOCK` file, tables being written) to a
+//! local file system. A table reaches the store when it is sealed: the
+//! builder writes it locally as `.sst.tmp`, and the rename into place
+//! uploads it and removes the local copy. Tables never change once
+//! sealed, so reads fetch byte ranges and keep the blocks they fetched
+//! in an LRU cache, and table sizes are cached too.
+//!
+//! Rooted at the data directory, every table of the database is remote.
+//! Rooted at a column family's directory (`data_dir/cf/<name>`), only
+//! that family's tables are, and the rest of the database stays local.
+//! Tables written locally before the switch stay readable until
+//! compaction replaces them. Like any file system but the local one, it
+//! does not support checkpoints, backups, repair or secondary instances.
+//!
+//! [`S3Store`] speaks the S3 REST API, signed with AWS Signature V4, to
+//! AWS or a compatible server (MinIO, Ceph RGW, ...). It uses path-style
+//! addressing over plain HTTP, one connection per request; reach TLS
+//! endpoints through a local proxy. A table is uploaded in one `PUT`,
+//! which S3 limits to 5 GB.
+//!
+//! ## Example
+//! ```no_run
+//! use std::sync::Arc;
+//!
+//! use oblivion::config::Config;
+//! use oblivion::engine::env::Env;
+//! use oblivion::engine::object_store::{ObjectFileSystem, S3Store};
+//! use oblivion::engine::Oblivion;
+//!
+//! let store = S3Store::new("http://127.0.0.1:9000", "archive")
+//!     .unwrap()
+//!     .with_credentials("access-key", "secret-key");
+//! let fs = ObjectFileSystem::new(Arc::new(store), "./data");
+//! let engine = Oblivion::open(Config::new("./data").with_env(Env::new(fs))).unwrap();
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use parking_lot::Mutex;
+use ring::{digest, hmac};
+
+use crate::config::Secret;
+use crate::error::{OblivionError, Result};
+
+use super::cache::LruCache;
+use super::env::{Env, FileLock, FileSystem, RandomAccessFile, WritableFile};
+use super::io::SyncMethod;
+
+/// Name of the object storage file system.
+pub const OBJECT: &str = "oblivion.object";
+
+/// Default capacity of the block cache in bytes.
+pub const DEFAULT_CACHE_CAPACITY: usize = 64 * 1024 * 1024;
+
+/// Bytes read from a local table per chunk of an upload.
+const UPLOAD_CHUNK: usize = 1024 * 1024;
+
+/// Timeout of each read from and write to the store's connection.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Payload hash signed for requests without a body (SHA-256 of nothing).
+const EMPTY_PAYLOAD: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+/// Payload hash signed for uploads, which are streamed unhashed.
+const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+
+/// A flat namespace of immutable objects.
+pub trait ObjectStore: Send + Sync {
+    /// Store the `len` bytes read from `body` as the object `key`,
+    /// replacing any object there.
+    fn put(&self, key: &str, body: &mut dyn Read, len: u64) -> io::Result<()>;
+
+    /// Read exactly `len` bytes of the object `key` at `offset`.
+    fn get_range(&self, key: &str, offset: u64, len: usize) -> io::Result<Vec<u8>>;
+
+    /// Returns the size of the object `key`, or fails with `NotFound`.
+    fn size(&self, key: &str) -> io::Result<u64>;
+
+    /// Delete the object `key`; deleting a missing object succeeds.
+    fn delete(&self, key: &str) -> io::Result<()>;
+
+    /// Returns the keys starting with `prefix`, in order.
+    fn list(&self, prefix: &str) -> io::Result<Vec<String>>;
+}
+
+/// An object store held in memory, for tests. Clones share their
+/// objects.
+#[derive(Clone, Default)]
+pub struct MemoryObjectStore {
+    objects: Arc<Mutex<BTreeMap<String, Arc<Vec<u8>>>>>,
+}
+
+impl MemoryObjectStore {
+    /// Create an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the keys of every object, in order.
+    pub fn keys(&self) -> Vec<String> {
+        self.objects.lock().keys().cloned().collect()
+    }
+
+    fn object(&self, key: &str) -> io::Result<Arc<Vec<u8>>> {
+        self.objects
+            .lock()
+            .get(key)
+            .cloned()
+            .ok_or_else(|| missing(key))
+    }
+}
+
+impl ObjectStore for MemoryObjectStore {
+    fn put(&self, key: &str, body: &mut dyn Read, len: u64) -> io::Result<()> {
+        let mut data = Vec::new();
+        body.take(len).read_to_end(&mut data)?;
+        if data.len() as u64 != len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.objects.lock().insert(key.to_string(), Arc::new(data));
+        Ok(())
+    }
+
+    fn get_range(&self, key: &str, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        let object = self.object(key)?;
+        let start = usize::try_from(offset).unwrap_or(usize::MAX);
+        object
+            .get(start..start.saturating_add(len))
+            .map(<[u8]>::to_vec)
+            .ok_or_else(|| io::ErrorKind::UnexpectedEof.into())
+    }
+
+    fn size(&self, key: &str) -> io::Result<u64> {
+        Ok(self.object(key)?.len() as u64)
+    }
+
+    fn delete(&self, key: &str) -> io::Result<()> {
+        self.objects.lock().remove(key);
+        Ok(())
+    }
+
+    fn list(&self, prefix: &str) -> io::Result<Vec<String>> {
+        Ok(self
+            .objects
+            .lock()
+            .range(prefix.to_string()..)
+            .map(|(key, _)| key)
+            .take_while(|key| key.starts_with(prefix))
+            .cloned()
+            .collect())
+    }
+}
+
+/// The bucket of an S3-compatible server, addressed path-style over
+/// plain HTTP.
+#[derive(Debug, Clone)]
+pub struct S3Store {
+    /// `host[:port]` of the server.
+    host: String,
+    bucket: String,
+    region: String,
+    access_key: String,
+    secret_key: Secret,
+    /// Prepended to every key.
+    prefix: String,
+}
+
+/// A response of the server, with lowercase header names.
+struct Response {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+impl S3Store {
+    /// Use `bucket` on the server at `endpoint` (`http://host:port`),
+    /// anonymously and in region `us-east-1` until told otherwise.
+    pub fn new(endpoint: &str, bucket: impl Into<String>) -> Result<Self> {
+        if endpoint.starts_with("https://") {
+            return Err(OblivionError::Unsupported(
+                "S3 endpoints are reached over plain HTTP; use a local TLS proxy".to_string(),
+            ));
+        }
+        let host = endpoint
+            .strip_prefix("http://")
+            .unwrap_or(endpoint)
+            .trim_end_matches('/');
+        if host.is_empty() || host.contains('/') {
+            return Err(OblivionError::Config(format!(
+                "invalid S3 endpoint '{}'",
+                endpoint
+            )));
+        }
+        Ok(Self {
+            host: host.to_string(),
+            bucket: bucket.into(),
+            region: "us-east-1".to_string(),
+            access_key: String::new(),
+            secret_key: Secret::new(""),
+            prefix: String::new(),
+        })
+    }
+
+    /// Sign requests with these credentials.
+    pub fn with_credentials(
+        mut self,
+        access_key: impl Into<String>,
+        secret_key: impl Into<String>,
+    ) -> Self {
+        self.access_key = access_key.into();
+        self.secret_key = Secret::new(secret_key);
+        self
+    }
+
+    /// Sign requests for `region`.
+    pub fn with_region(mut self, region: impl Into<String>) -> Self {
+        self.region = region.into();
+        self
+    }
+
+    /// Keep every object under `prefix` (such as `"archive/"`).
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Send a request for `object` (the bucket itself if `None`),
+    /// streaming `body` if there is one, and read the response.
+    fn request(
+        &self,
+        method: &str,
+        object: Option<&str>,
+        query: &[(&str, &str)],
+        range: Option<String>,
+        body: Option<(&mut dyn Read, u64)>,
+    ) -> io::Result<Response> {
+        let mut path = format!("/{}", uri_encode(&self.bucket, true));
+        if let Some(key) = object {
+            path.push('/');
+            path.push_str(&uri_encode(&format!("{}{}", self.prefix, key), false));
+        }
+        let mut pairs: Vec<(String, String)> = query
+            .iter()
+            .map(|(name, value)| (uri_encode(name, true), uri_encode(value, true)))
+            .collect();
+        pairs.sort();
+        let query = pairs
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join("&");
+
+        let amz_date = amz_date(SystemTime::now());
+        let payload = if body.is_some() {
+            UNSIGNED_PAYLOAD
+        } else {
+            EMPTY_PAYLOAD
+        };
+        let mut headers = vec![("host", self.host.clone())];
+        if let Some(range) = range {
+            headers.push(("range", range));
+        }
+        headers.push(("x-amz-content-sha256", payload.to_string()));
+        headers.push(("x-amz-date", amz_date.clone()));
+
+        let mut head = format!("{} {}", method, path);
+        if !query.is_empty() {
+            head.push('?');
+            head.push_str(&query);
+        }
+        head.push_str(" HTTP/1.1\r\n");
+        for (name, value) in &headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        if !self.access_key.is_empty() {
+            let canonical = canonical_request(method, &path, &query, &headers, payload);
+            let date = &amz_date[..8];
+            let signed: Vec<&str> = headers.iter().map(|(name, _)| *name).collect();
+            head.push_str(&format!(
+                "authorization: AWS4-HMAC-SHA256 Credential={}/{}/{}/s3/aws4_request, SignedHeaders={}, Signature={}\r\n",
+                self.access_key,
+                date,
+                self.region,
+                signed.join(";"),
+                signature(self.secret_key.expose(), &amz_date, &self.region, &canonical)
+            ));
+        }
+        let len = body.as_ref().map_or(0, |(_, len)| *len);
+        head.push_str(&format!(
+            "content-length: {}\r\nconnection: close\r\n\r\n",
+            len
+        ));
+
+        let address = if self.host.contains(':') {
+            self.host.clone()
+        } else {
+            format!("{}:80", self.host)
+        };
+        let mut stream = TcpStream::connect(address)?;
+        stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+        stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+        stream.write_all(head.as_bytes())?;
+        if let Some((body, len)) = body {
+            let sent = io::copy(&mut body.take(len), &mut stream)?;
+            if sent != len {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+        }
+        stream.flush()?;
+        read_response(stream, method == "HEAD")
+    }
+
+    /// Fail unless `response` has one of the `expected` statuses.
+    fn check(response: Response, key: &str, expected: &[u16]) -> io::Result<Response> {
+        if expected.contains(&response.status) {
+            return Ok(response);
+        }
+        if response.status == 404 {
+            return Err(missing(key));
+        }
+        let body = String::from_utf8_lossy(&response.body);
+        Err(io::Error::other(format!(
+            "S3 request for '{}' failed with status {}: {}",
+            key,
+            response.status,
+            body.chars().take(200).collect::<String>()
+        )))
+    }
+}
+
+impl ObjectStore for S3Store {
+    fn put(&self, key: &str, body: &mut dyn Read, len: u64) -> io::Result<()> {
+        let response = self.request("PUT", Some(key), &[], None, Some((body, len)))?;
+        Self::check(response, key, &[200]).map(|_| ())
+    }
+
+    fn get_range(&self, key: &str, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        if len == 0 {
+            return Ok(Vec::new());
+        }
+        let range = format!("bytes={}-{}", offset, offset + len as u64 - 1);
+        let response = self.request("GET", Some(key), &[], Some(range), None)?;
+        let response = Self::check(response, key, &[200, 206])?;
+        let mut data = response.body;
+        if response.status == 200 {
+            // The server sent the whole object
+            let start = usize::try_from(offset)
+                .unwrap_or(usize::MAX)
+                .min(data.len());
+            data.drain(..start);
+            data.truncate(len);
+        }
+        if data.len() != len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(data)
+    }
+
+    fn size(&self, key: &str) -> io::Result<u64> {
+        let response = self.request("HEAD", Some(key), &[], None, None)?;
+        let response = Self::check(response, key, &[200])?;
+        response
+            .header("content-length")
+            .and_then(|len| len.parse().ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "HEAD without a length"))
+    }
+
+    fn delete(&self, key: &str) -> io::Result<()> {
+        let response = self.request("DELETE", Some(key), &[], None, None)?;
+        match Self::check(response, key, &[200, 204]) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result.map(|_| ()),
+        }
+    }
+
+    fn list(&self, prefix: &str) -> io::Result<Vec<String>> {
+        let full_prefix = format!("{}{}", self.prefix, prefix);
+        let mut keys = Vec::new();
+        let mut token: Option<String> = None;
+        loop {
+            let mut query = vec![("list-type", "2"), ("prefix", full_prefix.as_str())];
+            if let Some(token) = &token {
+                query.push(("continuation-token", token.as_str()));
+            }
+            let response = self.request("GET", None, &query, None, None)?;
+            let response = Self::check(response, &self.bucket, &[200])?;
+            let xml = String::from_utf8_lossy(&response.body);
+            for key in xml_values(&xml, "Key") {
+                if let Some(key) = key.strip_prefix(&self.prefix) {
+                    keys.push(key.to_string());
+                }
+            }
+            token = xml_values(&xml, "NextContinuationToken").pop();
+            let truncated =
+                xml_values(&xml, "IsTruncated").first().map(String::as_str) == Some("true");
+            if !truncated || token.is_none() {
+                break;
+            }
+        }
+        Ok(keys)
+    }
+}
+
+/// A block of a table: (key, offset, length).
+type BlockId = (String, u64, usize);
+
+/// Tables in the store, with the blocks and sizes read so far.
+struct RemoteTables {
+    store: Arc<dyn ObjectStore>,
+    blocks: Mutex<LruCache<BlockId, Arc<[u8]>>>,
+    sizes: Mutex<HashMap<String, u64>>,
+}
+
+impl RemoteTables {
+    fn size(&self, key: &str) -> io::Result<u64> {
+        if let Some(&size) = self.sizes.lock().get(key) {
+            return Ok(size);
+        }
+        let size = self.store.size(key)?;
+        self.sizes.lock().insert(key.to_string(), size);
+        Ok(size)
+    }
+
+    fn read(&self, key: &str, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        let block = (key.to_string(), offset, len);
+        if let Some(data) = self.blocks.lock().get(&block) {
+            return Ok(data.to_vec());
+        }
+        let data = self.store.get_range(key, offset, len)?;
+        self.blocks.lock().insert(block, Arc::from(&data[..]), len);
+        Ok(data)
+    }
+}
+
+/// A file system keeping the SSTables under its root in an
+/// [`ObjectStore`] and every other file on a local file system. Clones
+/// share the store and its cache.
+#[derive(Clone)]
+pub struct ObjectFileSystem {
+    local: Env,
+    root: PathBuf,
+    tables: Arc<RemoteTables>,
+}
+
+impl ObjectFileSystem {
+    /// Keep the SSTables under `root` in `store`, as objects named by
+    /// their path below `root` (`sst/sstable_000042.sst`).
+    pub fn new(store: Arc<dyn ObjectStore>, root: impl Into<PathBuf>) -> Self {
+        Self {
+            local: Env::local(),
+            root: root.into(),
+            tables: Arc::new(RemoteTables {
+                store,
+                blocks: Mutex::new(LruCache::new(DEFAULT_CACHE_CAPACITY)),
+                sizes: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+
+    /// Keep the other files on `local` instead of the local file system.
+    pub fn with_local(mut self, local: Env) -> Self {
+        self.local = local;
+        self
+    }
+
+    /// Cache at most `capacity` bytes of table blocks (0 disables it).
+    pub fn with_cache_capacity(self, capacity: usize) -> Self {
+        self.tables.blocks.lock().set_capacity(capacity);
+        self
+    }
+
+    /// Returns the bytes of table blocks cached.
+    pub fn cache_usage(&self) -> usize {
+        self.tables.blocks.lock().usage()
+    }
+
+    /// The object a sealed table at `path` is stored as, or `None` for
+    /// a local file.
+    fn object_key(&self, path: &Path) -> Option<String> {
+        let name = path.file_name()?.to_str()?;
+        if !name.ends_with(".sst") {
+            return None;
+        }
+        relative_key(path.strip_prefix(&self.root).ok()?)
+    }
+
+    /// Returns the size of the object `key`, or `None` if there is none
+    /// and the table at its path is local.
+    fn remote_size(&self, key: &str) -> io::Result<Option<u64>> {
+        match self.tables.size(key) {
+            Ok(size) => Ok(Some(size)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Upload the local file `from` as the object `key`, then remove it.
+    fn seal(&self, from: &Path, key: &str) -> io::Result<()> {
+        let size = self.local.file_size(from)?;
+        let mut upload = Upload {
+            file: self.local.open_read(from, false)?,
+            offset: 0,
+            size,
+        };
+        self.tables.store.put(key, &mut upload, size)?;
+        self.tables.sizes.lock().insert(key.to_string(), size);
+        self.local.remove_file(from)
+    }
+}
+
+impl FileSystem for ObjectFileSystem {
+    fn name(&self) -> &str {
+        OBJECT
+    }
+
+    fn open_read(&self, path: &Path, direct: bool) -> io::Result<Box<dyn RandomAccessFile>> {
+        if let Some(key) = self.object_key(path) {
+            if let Some(size) = self.remote_size(&key)? {
+                return Ok(Box::new(RemoteTable {
+                    tables: Arc::clone(&self.tables),
+                    key,
+                    size,
+                }));
+            }
+        }
+        self.local.open_read(path, direct)
+    }
+
+    fn create(&self, path: &Path, direct: bool) -> io::Result<Box<dyn WritableFile>> {
+        if self.object_key(path).is_some() {
+            return Err(sealed(path));
+        }
+        self.local.create(path, direct)
+    }
+
+    fn open_append(&self, path: &Path) -> io::Result<Box<dyn WritableFile>> {
+        if self.object_key(path).is_some() {
+            return Err(sealed(path));
+        }
+        self.local.open_append(path)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        if let Some(key) = self.object_key(path) {
+            if let Some(size) = self.remote_size(&key)? {
+                return self.tables.store.get_range(&key, 0, size as usize);
+            }
+        }
+        self.local.read(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        match (self.object_key(from), self.object_key(to)) {
+            (None, None) => self.local.rename(from, to),
+            (None, Some(key)) => self.seal(from, &key),
+            (Some(_), _) => Err(sealed(from)),
+        }
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        if let Some(key) = self.object_key(path) {
+            if self.remote_size(&key)?.is_some() {
+                self.tables.store.delete(&key)?;
+                self.tables.sizes.lock().remove(&key);
+                return Ok(());
+            }
+        }
+        self.local.remove_file(path)
+    }
+
+    fn list(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        let prefix = dir.strip_prefix(&self.root).ok().and_then(|relative| {
+            let key = relative_key(relative)?;
+            Some(if key.is_empty() { key } else { key + "/" })
+        });
+        let mut paths = match self.local.list(dir) {
+            Ok(paths) => paths,
+            Err(e) if e.kind() == io::ErrorKind::NotFound && prefix.is_some() => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        if let Some(prefix) = prefix {
+            let mut seen: BTreeSet<PathBuf> = paths.iter().cloned().collect();
+            for key in self.tables.store.list(&prefix)? {
+                let name = &key[prefix.len()..];
+                if name.contains('/') || !name.ends_with(".sst") {
+                    continue;
+                }
+                let path = dir.join(name);
+                if seen.insert(path.clone()) {
+                    paths.push(path);
+                }
+            }
+        }
+        Ok(paths)
+    }
+
+    fn create_dir_all(&self, dir: &Path) -> io::Result<()> {
+        self.local.create_dir_all(dir)
+    }
+
+    fn remove_dir(&self, dir: &Path) -> io::Result<()> {
+        self.local.remove_dir(dir)
+    }
+
+    fn file_size(&self, path: &Path) -> io::Result<u64> {
+        if let Some(key) = self.object_key(path) {
+            if let Some(size) = self.remote_size(&key)? {
+                return Ok(size);
+            }
+        }
+        self.local.file_size(path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        let remote = self
+            .object_key(path)
+            .is_some_and(|key| self.tables.size(&key).is_ok());
+        remote || self.local.exists(path)
+    }
+
+    fn sync_file(&self, path: &Path, method: SyncMethod) -> io::Result<()> {
+        if let Some(key) = self.object_key(path) {
+            if self.remote_size(&key)?.is_some() {
+                // Objects are durable once stored
+                return Ok(());
+            }
+        }
+        self.local.sync_file(path, method)
+    }
+
+    fn sync_dir(&self, dir: &Path) -> io::Result<()> {
+        self.local.sync_dir(dir)
+    }
+
+    fn try_lock(&self, path: &Path) -> io::Result<Option<FileLock>> {
+        self.local.try_lock(path)
+    }
+}
+
+/// A table in the store, opened for reading.
+struct RemoteTable {
+    tables: Arc<RemoteTables>,
+    key: String,
+    size: u64,
+}
+
+impl RandomAccessFile for RemoteTable {
+    fn read_at(&mut self, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        if offset
+            .checked_add(len as u64)
+            .is_none_or(|end| end > self.size)
+        {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.tables.read(&self.key, offset, len)
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        Ok(self.size)
+    }
+}
+
+/// A local table read front to back while it is uploaded.
+struct Upload {
+    file: Box<dyn RandomAccessFile>,
+    offset: u64,
+    size: u64,
+}
+
+impl Read for Upload {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let left = usize::try_from(self.size - self.offset).unwrap_or(usize::MAX);
+        let len = buf.len().min(left).min(UPLOAD_CHUNK);
+        if len == 0 {
+            return Ok(0);
+        }
+        let data = self.file.read_at(self.offset, len)?;
+        buf[..len].copy_from_slice(&data);
+        self.offset += len as u64;
+        Ok(len)
+    }
+}
+
+/// Join the components of a relative path with `/`, or `None` if it
+/// leaves its directory.
+fn relative_key(relative: &Path) -> Option<String> {
+    let mut parts = Vec::new();
+    for component in relative.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_str()?),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    Some(parts.join("/"))
+}
+
+fn missing(key: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("object '{}' not found", key),
+    )
+}
+
+fn sealed(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("{:?} is a sealed table in the object store", path),
+    )
+}
+
+/// Read an HTTP/1.1 response; `head` responses have no body.
+fn read_response(stream: TcpStream, head: bool) -> io::Result<Response> {
+    let invalid = |what: &str| io::Error::new(io::ErrorKind::InvalidData, what.to_string());
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let status = line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(|| invalid("invalid HTTP status line"))?;
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid("HTTP response ends in its headers"));
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+    let mut response = Response {
+        status,
+        headers,
+        body: Vec::new(),
+    };
+    if head {
+        return Ok(response);
+    }
+    if response
+        .header("transfer-encoding")
+        .is_some_and(|encoding| encoding.eq_ignore_ascii_case("chunked"))
+    {
+        loop {
+            line.clear();
+            reader.read_line(&mut line)?;
+            let size = line.trim().split(';').next().unwrap_or_default();
+            let size =
+                usize::from_str_radix(size, 16).map_err(|_| invalid("invalid HTTP chunk size"))?;
+            if size == 0 {
+                break;
+            }
+            let start = response.body.len();
+            response.body.resize(start + size, 0);
+            reader.read_exact(&mut response.body[start..])?;
+            reader.read_exact(&mut [0; 2])?;
+        }
+    } else if let Some(len) = response.header("content-length") {
+        let len = len
+            .parse()
+            .map_err(|_| invalid("invalid HTTP content length"))?;
+        response.body = vec![0; len];
+        reader.read_exact(&mut response.body)?;
+    } else {
+        reader.read_to_end(&mut response.body)?;
+    }
+    Ok(response)
+}
+
+/// The text of each `<tag>` element of `xml`, unescaped.
+fn xml_values(xml: &str, tag: &str) -> Vec<String> {
+    let (open, close) = (format!("<{}>", tag), format!("</{}>", tag));
+    let mut values = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        rest = &rest[start + open.len()..];
+        let Some(end) = rest.find(&close) else {
+            break;
+        };
+        values.push(
+            rest[..end]
+                .replace("&lt;", "<")
+                .replace("&gt;", ">")
+                .replace("&quot;", "\"")
+                .replace("&apos;", "'")
+                .replace("&amp;", "&"),
+        );
+        rest = &rest[end + close.len()..];
+    }
+    values
+}
+
+/// Percent-encode `s` as SigV4 requires, keeping `/` unless
+/// `encode_slash` is set.
+fn uri_encode(s: &str, encode_slash: bool) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for &byte in s.as_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            b'/' if !encode_slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// The SigV4 canonical request; `headers` are lowercase and sorted.
+fn canonical_request(
+    method: &str,
+    path: &str,
+    query: &str,
+    headers: &[(&str, String)],
+    payload: &str,
+) -> String {
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+        .collect();
+    let signed: Vec<&str> = headers.iter().map(|(name, _)| *name).collect();
+    format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        method,
+        path,
+        query,
+        canonical_headers,
+        signed.join(";"),
+        payload
+    )
+}
+
+/// Sign `canonical` for S3 in `region` at `amz_date` (SigV4).
+fn signature(secret_key: &str, amz_date: &str, region: &str, canonical: &str) -> String {
+    let date = &amz_date[..8];
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}/{}/s3/aws4_request\n{}",
+        amz_date,
+        date,
+        region,
+        hex(digest::digest(&digest::SHA256, canonical.as_bytes()).as_ref())
+    );
+    let mut key = format!("AWS4{}", secret_key).into_bytes();
+    for part in [date, region, "s3", "aws4_request"] {
+        key = hmac_sha256(&key, part.as_bytes());
+    }
+    hex(&hmac_sha256(&key, string_to_sign.as_bytes()))
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, key);
+    hmac::sign(&key, data).as_ref().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// `time` as SigV4's `YYYYMMDDTHHMMSSZ`.
+fn amz_date(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (days, rem) = ((secs / 86_400) as i64, secs % 86_400);
+    // Days since the epoch to a civil date (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem / 60 % 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use super::*;
+    use crate::config::Config;
+    use crate::engine::Oblivion;
+
+    #[test]
+    fn test_signature_matches_aws_example() {
+        // "GET Object" example of the S3 SigV4 documentation
+        let time = UNIX_EPOCH + Duration::from_secs(1_369_353_600);
+        assert_eq!(amz_date(time), "20130524T000000Z");
+        let headers = [
+            ("host", "examplebucket.s3.amazonaws.com".to_string()),
+            ("range", "bytes=0-9".to_string()),
+            ("x-amz-content-sha256", EMPTY_PAYLOAD.to_string()),
+            ("x-amz-date", "20130524T000000Z".to_string()),
+        ];
+        let canonical = canonical_request("GET", "/test.txt", "", &headers, EMPTY_PAYLOAD);
+        assert_eq!(
+            signature(
+                "wJalrXUtnFEMI/K7MDENG/bPxRfiCYEXAMPLEKEY",
+                "20130524T000000Z",
+                "us-east-1",
+                &canonical
+            ),
+            "f0e8bdb87c964420e857bd35b5d6ed310bd44f0170aba48dd91039c6036bdb41"
+        );
+        assert_eq!(uri_encode("a b/c~", false), "a%20b/c~");
+        assert_eq!(uri_encode("a/b", true), "a%2Fb");
+    }
+
+    /// Serve a bare-bones S3 bucket named `bucket` over HTTP, ignoring
+    /// signatures. Returns its endpoint.
+    fn fake_s3() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let objects = MemoryObjectStore::new();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                serve_fake(&objects, stream).unwrap();
+            }
+        });
+        endpoint
+    }
+
+    fn serve_fake(objects: &MemoryObjectStore, stream: TcpStream) -> io::Result<()> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let mut parts = line.split_whitespace();
+        let (method, target) = (parts.next().unwrap(), parts.next().unwrap().to_string());
+        let mut headers = HashMap::new();
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header)?;
+            match header.trim_end().split_once(": ") {
+                Some((name, value)) => headers.insert(name.to_string(), value.to_string()),
+                None => break,
+            };
+        }
+        let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+        let key = path.strip_prefix("/bucket/").unwrap_or_default();
+        let len: u64 = headers["content-length"].parse().unwrap();
+
+        let (status, body, chunked) = match method {
+            "PUT" => (
+                200,
+                objects.put(key, &mut reader, len).map(|_| Vec::new()),
+                false,
+            ),
+            "HEAD" => (200, objects.size(key).map(|_| Vec::new()), false),
+            "DELETE" => (204, objects.delete(key).map(|_| Vec::new()), false),
+            "GET" if path == "/bucket" => {
+                let prefix = query
+                    .split('&')
+                    .find_map(|pair| pair.strip_prefix("prefix="))
+                    .unwrap_or_default()
+                    .replace("%2F", "/");
+                let keys: String = objects
+                    .list(&prefix)?
+                    .iter()
+                    .map(|key| format!("<Contents><Key>{}</Key></Contents>", key))
+                    .collect();
+                let xml = format!("<ListBucketResult>{}</ListBucketResult>", keys);
+                (200, Ok(xml.into_bytes()), true)
+            }
+            _ => {
+                let range = headers["range"].strip_prefix("bytes=").unwrap();
+                let (first, last) = range.split_once('-').unwrap();
+                let (first, last): (u64, u64) = (first.parse().unwrap(), last.parse().unwrap());
+                let data = objects.get_range(key, first, (last - first + 1) as usize);
+                (206, data, false)
+            }
+        };
+        let (status, body) = match body {
+            Ok(body) => (status, body),
+            Err(_) => (404, b"<Error>NoSuchKey</Error>".to_vec()),
+        };
+        let mut out = stream;
+        write!(out, "HTTP/1.1 {} X\r\nconnection: close\r\n", status)?;
+        if method == "HEAD" && status == 200 {
+            write!(out, "Content-Length: {}\r\n\r\n", objects.size(key)?)?;
+        } else if chunked {
+            write!(out, "Transfer-Encoding: chunked\r\n\r\n")?;
+            for chunk in body.chunks(7) {
+                write!(out, "{:x}\r\n", chunk.len())?;
+                out.write_all(chunk)?;
+                write!(out, "\r\n")?;
+            }
+            write!(out, "0\r\n\r\n")?;
+        } else {
+            write!(out, "Content-Length: {}\r\n\r\n", body.len())?;
+            out.write_all(&body)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_s3_store_round_trip() {
+        let store = S3Store::new(&fake_s3(), "bucket")
+            .unwrap()
+            .with_credentials("access", "secret")
+            .with_prefix("db/");
+        store
+            .put("sst/a.sst", &mut &b"hello world"[..], 11)
+            .unwrap();
+        store.put("sst/b.sst", &mut &b"bye"[..], 3).unwrap();
+        store.put("other", &mut &b""[..], 0).unwrap();
+
+        assert_eq!(store.size("sst/a.sst").unwrap(), 11);
+        assert_eq!(store.get_range("sst/a.sst", 6, 5).unwrap(), b"world");
+        assert_eq!(store.list("sst/").unwrap(), ["sst/a.sst", "sst/b.sst"]);
+        store.delete("sst/a.sst").unwrap();
+        store.delete("sst/a.sst").unwrap();
+        assert_eq!(
+            store.size("sst/a.sst").unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+        assert_eq!(store.list("").unwrap(), ["other", "sst/b.sst"]);
+
+        assert!(S3Store::new("https://s3.amazonaws.com", "bucket").is_err());
+        assert!(S3Store::new("http://host/path", "bucket").is_err());
+    }
+
+    #[test]
+    fn test_tables_live_in_the_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = MemoryObjectStore::new();
+        let fs = ObjectFileSystem::new(Arc::new(store.clone()), dir.path());
+        let mut config = Config::new(dir.path()).with_env(Env::new(fs.clone()));
+        config.compaction_threshold = 2;
+
+        let engine = Oblivion::open(config.clone()).unwrap();
+        for round in 0..3u32 {
+            for i in 0..50u32 {
+                let key = format!("key_{:03}", i).into_bytes();
+                engine.put(key, round.to_be_bytes().to_vec()).unwrap();
+            }
+            engine.flush().unwrap();
+        }
+        engine.compact().unwrap();
+        assert_eq!(engine.get(b"key_007"), Some(2u32.to_be_bytes().to_vec()));
+        assert!(fs.cache_usage() > 0);
+        drop(engine);
+
+        // Only the sealed, live tables are remote
+        let engine = Oblivion::open(config.clone()).unwrap();
+        let keys = store.keys();
+        assert_eq!(keys.len(), engine.live_files().len());
+        assert!(keys.iter().all(|key| key.starts_with("sst/sstable_")));
+        let local: Vec<PathBuf> = std::fs::read_dir(config.sst_dir())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert!(local.is_empty(), "{:?}", local);
+        assert!(config.manifest_path().exists());
+        assert_eq!(engine.get(b"key_049"), Some(2u32.to_be_bytes().to_vec()));
+        assert!(engine.verify_integrity().unwrap().is_ok());
+    }
+}
@@ -0,0 +1,152 @@
+//! OBLIVION - Per-Operation Options
+//! Knobs that apply to a single read instead of the whole engine.
+
+use crate::types::Key;
+
+use super::snapshot::Snapshot;
+
+/// Options for a single point lookup or scan.
+///
+/// ## Example
+/// ```no_run
+/// use oblivion::config::Config;
+/// use oblivion::engine::{options::ReadOptions, Oblivion};
+///
+/// let engine = Oblivion::open(Config::default()).unwrap();
+///
+/// // Backup-style scan: consistent view, don't pollute the cache
+/// let opts = ReadOptions::new()
+///     .snapshot(engine.snapshot())
+///     .fill_cache(false)
+///     .lower_bound(b"user:".to_vec())
+///     .upper_bound(b"user;".to_vec());
+/// let users = engine.scan_opt(&opts).unwrap();
+/// ```
+#[derive(Clone)]
+pub struct ReadOptions {
+    /// Read the state as of this snapshot instead of the latest state.
+    pub snapshot: Option<Snapshot>,
+    /// Whether values read from SSTables may be inserted into the row
+    /// cache, and the data blocks they were read from into the block cache.
+    pub fill_cache: bool,
+    /// Whether data block CRC32 checksums are verified. Engines with
+    /// `Config::paranoid_checks` verify them regardless.
+    pub verify_checksums: bool,
+    /// Inclusive lower bound for scans, in the engine's
+    /// [comparator](super::comparator) order.
+    pub lower_bound: Option<Key>,
+    /// Exclusive upper bound for scans, in the engine's comparator order.
+    pub upper_bound: Option<Key>,
+}
+
+/// The default options, for reads that borrow them.
+pub(crate) static DEFAULT_READ_OPTIONS: ReadOptions = ReadOptions {
+    snapshot: None,
+    fill_cache: true,
+    verify_checksums: true,
+    lower_bound: None,
+    upper_bound: None,
+};
+
+impl Default for ReadOptions {
+    fn default() -> Self {
+        DEFAULT_READ_OPTIONS.clone()
+    }
+}
+
+impl ReadOptions {
+    /// Create options with the defaults (latest state, fill cache, verify).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read from a snapshot.
+    pub fn snapshot(mut self, snapshot: Snapshot) -> Self {
+        self.snapshot = Some(snapshot);
+        self
+    }
+
+    /// Set whether reads may populate the row and block caches.
+    pub fn fill_cache(mut self, fill_cache: bool) -> Self {
+        self.fill_cache = fill_cache;
+        self
+    }
+
+    /// Set whether data block checksums are verified.
+    pub fn verify_checksums(mut self, verify: bool) -> Self {
+        self.verify_checksums = verify;
+        self
+    }
+
+    /// Set the inclusive lower bound for scans.
+    pub fn lower_bound(mut self, key: Key) -> Self {
+        self.lower_bound = Some(key);
+        self
+    }
+
+    /// Set the exclusive upper bound for scans.
+    pub fn upper_bound(mut self, key: Key) -> Self {
+        self.upper_bound = Some(key);
+        self
+    }
+
+    /// Restrict scans to keys starting with `prefix`.
+    ///
+    /// Sets both bounds; the upper bound is left open when no key sorts
+    /// after the prefix (it is empty or all `0xFF` bytes). The bounds
+    /// select the prefix in byte order only.
+    pub fn prefix(mut self, prefix: &[u8]) -> Self {
+        self.lower_bound = Some(prefix.to_vec());
+        self.upper_bound = prefix_end(prefix);
+        self
+    }
+
+    /// Returns true if `key` lies within the scan bounds, in byte order.
+    pub fn in_bounds(&self, key: &[u8]) -> bool {
+        self.lower_bound.as_deref().is_none_or(|lower| key >= lower)
+            && self.upper_bound.as_deref().is_none_or(|upper| key < upper)
+    }
+}
+
+/// The smallest key greater than every key starting with `prefix`.
+pub(crate) fn prefix_end(prefix: &[u8]) -> Option<Key> {
+    let mut end = prefix.to_vec();
+    while let Some(last) = end.pop() {
+        if last < u8::MAX {
+            end.push(last + 1);
+            return Some(end);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults() {
+        let opts = ReadOptions::new();
+        assert!(opts.snapshot.is_none());
+        assert!(opts.fill_cache);
+        assert!(opts.verify_checksums);
+        assert!(opts.in_bounds(b"anything"));
+    }
+
+    #[test]
+    fn test_bounds() {
+        let opts = ReadOptions::new()
+            .lower_bound(b"b".to_vec())
+            .upper_bound(b"d".to_vec());
+        assert!(!opts.in_bounds(b"a"));
+        assert!(opts.in_bounds(b"b"));
+        assert!(opts.in_bounds(b"c"));
+        assert!(!opts.in_bounds(b"d"));
+
+        let opts = ReadOptions::new().prefix(b"user\xff");
+        assert_eq!(opts.upper_bound.as_deref(), Some(&b"uses"[..]));
+        assert!(opts.in_bounds(b"user\xff\x00"));
+        assert!(!opts.in_bounds(b"user"));
+        assert!(ReadOptions::new().prefix(b"\xff").upper_bound.is_none());
+    }
+}
@@ -0,0 +1,144 @@
+//! OBLIVION - Pinned Values
+//! Values returned by [`Oblivion::get_pinned`](super::Oblivion::get_pinned)
+//! without copying them out of where the read found them.
+//!
+//! ## Sources
+//! - **Active MemTable**: the value is borrowed under the MemTable's
+//!   read lock. Writes wait until the guard is dropped, so hold it only
+//!   as long as needed, and never across a write on the same thread.
+//! - **Snapshot**: borrowed from the snapshot of the read options.
+//! - **Frozen MemTable**: the guard keeps the MemTable alive and looks
+//!   the value up again on access.
+//! - **Row cache**: the guard shares the cached bytes.
+//! - **SSTable**: the guard shares the decoded data block the value was
+//!   found in, with the block cache if it holds the block, and reads
+//!   the value out of it in place.
+
+use std::fmt;
+use std::ops::{Deref, Range};
+use std::sync::Arc;
+
+use parking_lot::MappedRwLockReadGuard;
+
+use crate::types::{Key, Value};
+
+use super::memtable::MemTable;
+
+/// A value read without copying it; dereferences to its bytes.
+pub struct PinnedValue<'a> {
+    inner: Pinned<'a>,
+}
+
+/// Where a pinned value lives.
+enum Pinned<'a> {
+    Borrowed(&'a [u8]),
+    MemTable(MappedRwLockReadGuard<'a, [u8]>),
+    Frozen {
+        table: Arc<MemTable>,
+        key: Key,
+    },
+    Cached(Arc<[u8]>),
+    Block {
+        block: Arc<[u8]>,
+        range: Range<usize>,
+    },
+}
+
+impl<'a> PinnedValue<'a> {
+    pub(crate) fn borrowed(value: &'a [u8]) -> Self {
+        Self::from(Pinned::Borrowed(value))
+    }
+
+    pub(crate) fn memtable(guard: MappedRwLockReadGuard<'a, [u8]>) -> Self {
+        Self::from(Pinned::MemTable(guard))
+    }
+
+    /// The value of `key` in the frozen MemTable `table`, which must
+    /// hold one.
+    pub(crate) fn frozen(table: Arc<MemTable>, key: Key) -> Self {
+        debug_assert!(table.get(&key).is_some());
+        Self::from(Pinned::Frozen { table, key })
+    }
+
+    pub(crate) fn cached(value: Arc<[u8]>) -> Self {
+        Self::from(Pinned::Cached(value))
+    }
+
+    /// The value held by `range` of the data block `block`.
+    pub(crate) fn block(block: Arc<[u8]>, range: Range<usize>) -> Self {
+        debug_assert!(range.end <= block.len());
+        Self::from(Pinned::Block { block, range })
+    }
+
+    fn from(inner: Pinned<'a>) -> Self {
+        Self { inner }
+    }
+
+    /// Returns a copy of the value as an owned `Vec`.
+    pub fn into_vec(self) -> Value {
+        self.to_vec()
+    }
+}
+
+impl Deref for PinnedValue<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match &self.inner {
+            Pinned::Borrowed(value) => value,
+            Pinned::MemTable(guard) => guard,
+            // Frozen MemTables never change, so the value is still there
+            Pinned::Frozen { table, key } => table.get(key).map_or(&[], Vec::as_slice),
+            Pinned::Cached(value) => value,
+            Pinned::Block { block, range } => &block[range.clone()],
+        }
+    }
+}
+
+impl AsRef<[u8]> for PinnedValue<'_> {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl PartialEq<[u8]> for PinnedValue<'_> {
+    fn eq(&self, other: &[u8]) -> bool {
+        **self == *other
+    }
+}
+
+impl fmt::Debug for PinnedValue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("PinnedValue").field(&&**self).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::{RwLock, RwLockReadGuard};
+
+    #[test]
+    fn test_every_source_derefs_to_the_value() {
+        let mut memtable = MemTable::new();
+        memtable.insert(b"key".to_vec(), b"value".to_vec());
+        let lock = RwLock::new(memtable);
+        let guard = RwLockReadGuard::map(lock.read(), |m| m.get(b"key").unwrap().as_slice());
+        let mut table = MemTable::new();
+        table.insert(b"key".to_vec(), b"value".to_vec());
+
+        let values = [
+            PinnedValue::borrowed(b"value"),
+            PinnedValue::memtable(guard),
+            PinnedValue::frozen(Arc::new(table), b"key".to_vec()),
+            PinnedValue::cached(Arc::from(&b"value"[..])),
+            PinnedValue::block(Arc::from(&b"a value!"[..]), 2..7),
+        ];
+        for value in values {
+            assert_eq!(&*value, b"value");
+            assert_eq!(value.into_vec(), b"value".to_vec());
+        }
+        // The MemTable guard released its lock when dropped
+        assert!(lock.try_write().is_some());
+    }
+}
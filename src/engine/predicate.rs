@@ -0,0 +1,490 @@
+//! OBLIVION - Scan Predicates
+//! Filters evaluated inside the engine while scanning, so only the
+//! matching rows are returned to the caller, or sent over the network
+//! by the servers.
+//!
+//! ## Predicates
+//! A [`Predicate`] tests the key or the value of a row:
+//!
+//! - `glob`: a Redis glob over the whole bytes (see the
+//!   [`glob` module](super::glob)).
+//! - `regex`: a regular expression found anywhere in the bytes; anchor
+//!   it with `^` and `$` to match them whole.
+//! - `prefix`, `contains`: a literal the bytes start with or contain.
+//!
+//! Tests combine with `and`, `or` and `not`.
+//!
+//! ## Filter Expressions
+//! [`Predicate::parse`] reads predicates written as text, for the
+//! servers and the CLI:
+//!
+//! ```text
+//! key glob 'user:*' and not (value prefix '{"deleted"' or value contains "tmp")
+//! ```
+//!
+//! `not` binds tighter than `and`, which binds tighter than `or`;
+//! keywords are case-insensitive. Operands are quoted with `'` or `"`,
+//! inside which a backslash escapes the quote or another backslash and
+//! is kept otherwise, so `'^user:\d+$'` needs no doubling. Operands
+//! without spaces, quotes or parentheses may be left bare.
+//!
+//! ## Reading Less
+//! Under byte order, a scan reads only the range sharing the
+//! predicate's [`required_key_prefix`](Predicate::required_key_prefix): the prefix every
+//! matching key starts with, from its key globs and prefixes.
+
+use std::fmt;
+use std::ops::Not;
+use std::str::FromStr;
+
+use regex::bytes::Regex;
+
+use crate::error::{OblivionError, Result};
+use crate::types::{Key, Value};
+
+use super::comparator::Comparator;
+use super::glob::{glob_match, literal_prefix};
+use super::options::ReadOptions;
+use super::version::ReadState;
+
+/// Rows read per page while filtering a scan.
+const FILTER_PAGE_SIZE: usize = 1024;
+
+/// Which part of a row a test looks at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Key,
+    Value,
+}
+
+/// A test of the bytes of one field.
+#[derive(Debug, Clone)]
+pub enum Match {
+    /// A Redis glob matching the whole bytes.
+    Glob(Vec<u8>),
+    /// A regular expression found anywhere in the bytes.
+    Regex(Regex),
+    /// The bytes start with these.
+    Prefix(Vec<u8>),
+    /// The bytes contain these.
+    Contains(Vec<u8>),
+}
+
+impl Match {
+    /// Returns true if `bytes` pass the test.
+    pub fn matches(&self, bytes: &[u8]) -> bool {
+        match self {
+            Match::Glob(pattern) => glob_match(pattern, bytes),
+            Match::Regex(regex) => regex.is_match(bytes),
+            Match::Prefix(prefix) => bytes.starts_with(prefix),
+            Match::Contains(needle) => {
+                needle.is_empty() || bytes.windows(needle.len()).any(|w| w == needle)
+            }
+        }
+    }
+
+    /// The bytes everything passing the test starts with.
+    fn prefix(&self) -> &[u8] {
+        match self {
+            Match::Glob(pattern) => literal_prefix(pattern),
+            Match::Prefix(prefix) => prefix,
+            Match::Regex(_) | Match::Contains(_) => &[],
+        }
+    }
+}
+
+/// A filter over the rows of a scan.
+#[derive(Debug, Clone)]
+pub enum Predicate {
+    /// A test of the key or the value.
+    Test(Field, Match),
+    /// Every predicate holds; true if there are none.
+    And(Vec<Predicate>),
+    /// Any predicate holds; false if there are none.
+    Or(Vec<Predicate>),
+    /// The predicate does not hold.
+    Not(Box<Predicate>),
+}
+
+impl Predicate {
+    /// Keys matching the glob `pattern`.
+    pub fn key_glob(pattern: impl Into<Vec<u8>>) -> Self {
+        Predicate::Test(Field::Key, Match::Glob(pattern.into()))
+    }
+
+    /// Keys in which the regular expression `regex` is found.
+    pub fn key_regex(regex: &str) -> Result<Self> {
+        Ok(Predicate::Test(Field::Key, Match::Regex(compile(regex)?)))
+    }
+
+    /// Keys starting with `prefix`.
+    pub fn key_prefix(prefix: impl Into<Vec<u8>>) -> Self {
+        Predicate::Test(Field::Key, Match::Prefix(prefix.into()))
+    }
+
+    /// Values matching the glob `pattern`.
+    pub fn value_glob(pattern: impl Into<Vec<u8>>) -> Self {
+        Predicate::Test(Field::Value, Match::Glob(pattern.into()))
+    }
+
+    /// Values in which the regular expression `regex` is found.
+    pub fn value_regex(regex: &str) -> Result<Self> {
+        Ok(Predicate::Test(Field::Value, Match::Regex(compile(regex)?)))
+    }
+
+    /// Values starting with `prefix`.
+    pub fn value_prefix(prefix: impl Into<Vec<u8>>) -> Self {
+        Predicate::Test(Field::Value, Match::Prefix(prefix.into()))
+    }
+
+    /// Values containing `needle`.
+    pub fn value_contains(needle: impl Into<Vec<u8>>) -> Self {
+        Predicate::Test(Field::Value, Match::Contains(needle.into()))
+    }
+
+    /// Both this predicate and `other` hold.
+    pub fn and(self, other: Predicate) -> Self {
+        match self {
+            Predicate::And(mut all) => {
+                all.push(other);
+                Predicate::And(all)
+            }
+            first => Predicate::And(vec![first, other]),
+        }
+    }
+
+    /// This predicate or `other` holds.
+    pub fn or(self, other: Predicate) -> Self {
+        match self {
+            Predicate::Or(mut any) => {
+                any.push(other);
+                Predicate::Or(any)
+            }
+            first => Predicate::Or(vec![first, other]),
+        }
+    }
+
+    /// Parse a filter expression (see the module documentation).
+    pub fn parse(expression: &str) -> Result<Self> {
+        let tokens = tokenize(expression)?;
+        let mut parser = Parser { tokens, next: 0 };
+        let predicate = parser.or()?;
+        match parser.tokens.get(parser.next) {
+            None => Ok(predicate),
+            Some(token) => Err(invalid(format!("unexpected {}", token))),
+        }
+    }
+
+    /// Returns true if the row `key`, `value` passes the filter.
+    pub fn matches(&self, key: &[u8], value: &[u8]) -> bool {
+        match self {
+            Predicate::Test(Field::Key, test) => test.matches(key),
+            Predicate::Test(Field::Value, test) => test.matches(value),
+            Predicate::And(all) => all.iter().all(|p| p.matches(key, value)),
+            Predicate::Or(any) => any.iter().any(|p| p.matches(key, value)),
+            Predicate::Not(p) => !p.matches(key, value),
+        }
+    }
+
+    /// The bytes every key passing the filter starts with; empty if
+    /// keys are not restricted to a prefix.
+    pub fn required_key_prefix(&self) -> Vec<u8> {
+        match self {
+            Predicate::Test(Field::Key, test) => test.prefix().to_vec(),
+            Predicate::Test(Field::Value, _) | Predicate::Not(_) => Vec::new(),
+            // All must hold, so the longest prefix of any applies
+            Predicate::And(all) => all
+                .iter()
+                .map(Predicate::required_key_prefix)
+                .max_by_key(Vec::len)
+                .unwrap_or_default(),
+            // Any may hold, so only what all prefixes share applies
+            Predicate::Or(any) => {
+                let mut prefixes = any.iter().map(Predicate::required_key_prefix);
+                let first = prefixes.next().unwrap_or_default();
+                prefixes.fold(first, |shared, prefix| {
+                    let len = shared
+                        .iter()
+                        .zip(&prefix)
+                        .take_while(|(a, b)| a == b)
+                        .count();
+                    shared[..len].to_vec()
+                })
+            }
+        }
+    }
+
+    /// Narrow the bounds of `opts` to the keys that can pass the filter:
+    /// those starting with its [`required_key_prefix`](Self::required_key_prefix),
+    /// if `comparator` is byte order.
+    pub fn narrow(&self, opts: &mut ReadOptions, comparator: &Comparator) {
+        let prefix = self.required_key_prefix();
+        if prefix.is_empty() || !comparator.is_bytewise() {
+            return;
+        }
+        let prefix = ReadOptions::new().prefix(&prefix);
+        opts.lower_bound = opts.lower_bound.take().max(prefix.lower_bound);
+        opts.upper_bound = match (opts.upper_bound.take(), prefix.upper_bound) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+    }
+}
+
+impl Not for Predicate {
+    type Output = Predicate;
+
+    fn not(self) -> Predicate {
+        Predicate::Not(Box::new(self))
+    }
+}
+
+impl FromStr for Predicate {
+    type Err = OblivionError;
+
+    fn from_str(expression: &str) -> Result<Self> {
+        Predicate::parse(expression)
+    }
+}
+
+/// The live rows within the bounds of `opts` that pass `predicate`, in
+/// sorted order. The range is read page by page from a snapshot, keeping
+/// only the matching rows of each page.
+pub(crate) fn matching_rows(
+    state: &ReadState,
+    predicate: &Predicate,
+    mut opts: ReadOptions,
+) -> Result<Vec<(Key, Value)>> {
+    predicate.narrow(&mut opts, &state.comparator);
+    if opts.snapshot.is_none() {
+        opts.snapshot = Some(state.snapshot());
+    }
+    state.metrics.record_scan();
+    let mut rows = Vec::new();
+    loop {
+        let (page, resume) = state.scan_page(&opts, FILTER_PAGE_SIZE)?;
+        rows.extend(
+            page.into_iter()
+                .filter(|(key, value)| predicate.matches(key, value)),
+        );
+        match resume {
+            Some(resume) => opts.lower_bound = Some(resume),
+            None => return Ok(rows),
+        }
+    }
+}
+
+fn compile(regex: &str) -> Result<Regex> {
+    Regex::new(regex).map_err(|e| invalid(format!("bad regex {:?}: {}", regex, e)))
+}
+
+fn invalid(message: String) -> OblivionError {
+    OblivionError::InvalidFilter(message)
+}
+
+/// A token of a filter expression.
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    /// A bare word: a keyword, or an unquoted operand.
+    Word(String),
+    /// A quoted operand.
+    Quoted(String),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Open => f.write_str("'('"),
+            Token::Close => f.write_str("')'"),
+            Token::Word(word) => write!(f, "{:?}", word),
+            Token::Quoted(text) => write!(f, "'{}'", text),
+        }
+    }
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = expression.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            '\'' | '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        None => return Err(invalid("unterminated quote".into())),
+                        Some(q) if q == c => break,
+                        Some('\\') => match chars.next_if(|&next| next == c || next == '\\') {
+                            Some(escaped) => text.push(escaped),
+                            None => text.push('\\'),
+                        },
+                        Some(other) => text.push(other),
+                    }
+                }
+                tokens.push(Token::Quoted(text));
+            }
+            c => {
+                let mut word = c.to_string();
+                while let Some(next) = chars.next_if(|&next| {
+                    !next.is_whitespace() && !matches!(next, '(' | ')' | '\'' | '"')
+                }) {
+                    word.push(next);
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent over the tokens of a filter expression.
+struct Parser {
+    tokens: Vec<Token>,
+    next: usize,
+}
+
+impl Parser {
+    /// Consume the next token if it is the keyword `keyword`.
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.tokens.get(self.next) {
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword) => {
+                self.next += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn take(&mut self) -> Result<Token> {
+        let token = self
+            .tokens
+            .get(self.next)
+            .cloned()
+            .ok_or_else(|| invalid("unexpected end of expression".into()))?;
+        self.next += 1;
+        Ok(token)
+    }
+
+    fn or(&mut self) -> Result<Predicate> {
+        let mut predicate = self.and()?;
+        while self.keyword("or") {
+            predicate = predicate.or(self.and()?);
+        }
+        Ok(predicate)
+    }
+
+    fn and(&mut self) -> Result<Predicate> {
+        let mut predicate = self.unary()?;
+        while self.keyword("and") {
+            predicate = predicate.and(self.unary()?);
+        }
+        Ok(predicate)
+    }
+
+    fn unary(&mut self) -> Result<Predicate> {
+        if self.keyword("not") {
+            return Ok(!self.unary()?);
+        }
+        let field = match self.take()? {
+            Token::Open => {
+                let predicate = self.or()?;
+                return match self.take()? {
+                    Token::Close => Ok(predicate),
+                    token => Err(invalid(format!("expected ')', found {}", token))),
+                };
+            }
+            Token::Word(word) if word.eq_ignore_ascii_case("key") => Field::Key,
+            Token::Word(word) if word.eq_ignore_ascii_case("value") => Field::Value,
+            token => {
+                return Err(invalid(format!(
+                    "expected 'key' or 'value', found {}",
+                    token
+                )))
+            }
+        };
+        let operator = match self.take()? {
+            Token::Word(word) => word.to_ascii_lowercase(),
+            token => return Err(invalid(format!("expected an operator, found {}", token))),
+        };
+        let operand = match self.take()? {
+            Token::Word(text) | Token::Quoted(text) => text,
+            token => return Err(invalid(format!("expected an operand, found {}", token))),
+        };
+        let test = match operator.as_str() {
+            "glob" => Match::Glob(operand.into_bytes()),
+            "regex" => Match::Regex(compile(&operand)?),
+            "prefix" => Match::Prefix(operand.into_bytes()),
+            "contains" => Match::Contains(operand.into_bytes()),
+            _ => {
+                return Err(invalid(format!(
+                    "unknown operator {:?} (glob, regex, prefix or contains)",
+                    operator
+                )))
+            }
+        };
+        Ok(Predicate::Test(field, test))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_match() {
+        let predicate = Predicate::parse(
+            r#"key glob 'user:*' and not (value prefix "{\"deleted" or value contains tmp)"#,
+        )
+        .unwrap();
+        assert!(predicate.matches(b"user:1", b"{\"name\":\"a\"}"));
+        assert!(!predicate.matches(b"user:1", b"{\"deleted\":true}"));
+        assert!(!predicate.matches(b"user:1", b"a tmp value"));
+        assert!(!predicate.matches(b"order:1", b"{}"));
+        assert_eq!(predicate.required_key_prefix(), b"user:");
+
+        let regex: Predicate = r"KEY REGEX '^user:\d+$' OR key prefix order:"
+            .parse()
+            .unwrap();
+        assert!(regex.matches(b"user:42", b""));
+        assert!(!regex.matches(b"user:x", b""));
+        assert!(regex.matches(b"order:7", b""));
+        assert!(regex.required_key_prefix().is_empty());
+        let shared = Predicate::key_prefix("user:1").or(Predicate::key_glob("user:2*"));
+        assert_eq!(shared.required_key_prefix(), b"user:");
+
+        for bad in [
+            "",
+            "key",
+            "key glob",
+            "key like x",
+            "size glob x",
+            "(key glob x",
+            "key glob x y",
+            "key regex '('",
+            "value prefix 'open",
+        ] {
+            assert!(
+                matches!(Predicate::parse(bad), Err(OblivionError::InvalidFilter(_))),
+                "{:?}",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn test_narrow_to_key_prefix() {
+        let predicate = Predicate::key_glob("user:*").and(Predicate::value_contains("x"));
+        let mut opts = ReadOptions::new().upper_bound(b"user:5".to_vec());
+        predicate.narrow(&mut opts, &Comparator::default());
+        assert_eq!(opts.lower_bound.as_deref(), Some(&b"user:"[..]));
+        assert_eq!(opts.upper_bound.as_deref(), Some(&b"user:5"[..]));
+
+        let mut opts = ReadOptions::new();
+        Predicate::value_prefix("x").narrow(&mut opts, &Comparator::default());
+        assert!(opts.lower_bound.is_none() && opts.upper_bound.is_none());
+    }
+}
@@ -0,0 +1,216 @@
+//! OBLIVION - Engine Properties
+//! Engine internals by name, as RocksDB's `GetProperty`, and the live
+//! SSTable inventory, so orchestration and monitoring tools can query
+//! them instead of parsing logs.
+//!
+//! [`Oblivion::get_property`](super::Oblivion::get_property) answers
+//! the names below, with a decimal number for all but [`STATS`], and
+//! `None` for any other name. `get_property_cf` answers for one column
+//! family; the engine-wide properties are the same for every family.
+//!
+//! | Name | Value |
+//! |------|-------|
+//! | `oblivion.num-entries` | Records in the MemTables and live SSTables, tombstones and overwritten versions included |
+//! | `oblivion.estimate-live-keys` | Estimated keys a full scan would return (see `Oblivion::estimate_live_keys`) |
+//! | `oblivion.num-live-files` | Live SSTables |
+//! | `oblivion.live-sst-files-size` | Bytes of live SSTables |
+//! | `oblivion.num-files-at-tier<N>` | Live SSTables in compaction tier `N` |
+//! | `oblivion.estimate-pending-compaction-bytes` | Bytes of live SSTables in tiers that have reached the compaction threshold |
+//! | `oblivion.cur-size-active-mem-table` | Bytes in the active MemTable |
+//! | `oblivion.num-immutable-mem-table` | Frozen MemTables waiting to be flushed |
+//! | `oblivion.memtable-flush-threshold` | Bytes the active MemTable is flushed at (see `adaptive_memtable_sizing`) |
+//! | `oblivion.num-snapshots` | Live snapshots |
+//! | `oblivion.last-sequence` | Sequence number of the last write (engine-wide) |
+//! | `oblivion.background-errors` | Background jobs that failed (engine-wide) |
+//! | `oblivion.stats` | The metrics report (engine-wide) |
+//!
+//! ## Live Files
+//! [`Oblivion::live_files`](super::Oblivion::live_files) lists every
+//! live SSTable as a [`LiveFile`]. Tables are tiered by size rather than
+//! leveled, so a file reports its compaction tier where RocksDB reports
+//! a level. Its sequence range covers the writes it was flushed from;
+//! compaction output spans its inputs' ranges. Column family tables,
+//! imported and ingested tables, and tables written before ranges were
+//! recorded (and their compaction output) report 0 for both ends.
+//!
+//! For tiering and cold-storage decisions a file also reports its age
+//! and temperature. The creation time is stored in the table's
+//! properties, so it survives reopens and is kept by backups and
+//! imports; tables written before it was recorded report 0. Read counts
+//! and the last access time are kept in memory only, counting point
+//! lookups and range scans (not compaction) since the engine opened
+//! the table.
+//!
+//! ## Example
+//! ```no_run
+//! use oblivion::config::Config;
+//! use oblivion::engine::properties;
+//! use oblivion::engine::Oblivion;
+//!
+//! let engine = Oblivion::open(Config::new("./data")).unwrap();
+//! let pending = engine.get_property(properties::ESTIMATE_PENDING_COMPACTION_BYTES);
+//! println!("pending compaction: {} bytes", pending.unwrap());
+//! for file in engine.live_files() {
+//!     println!("{:?} tier {} {} bytes", file.path, file.tier, file.size);
+//! }
+//! ```
+
+use std::path::PathBuf;
+
+use crate::types::Key;
+
+/// Records in the MemTables and live SSTables.
+pub const NUM_ENTRIES: &str = "oblivion.num-entries";
+
+/// Estimated keys a full scan would return.
+pub const ESTIMATE_LIVE_KEYS: &str = "oblivion.estimate-live-keys";
+
+/// Live SSTables.
+pub const NUM_LIVE_FILES: &str = "oblivion.num-live-files";
+
+/// Bytes of live SSTables.
+pub const LIVE_SST_FILES_SIZE: &str = "oblivion.live-sst-files-size";
+
+/// Live SSTables in a compaction tier, with the tier appended
+/// (`oblivion.num-files-at-tier0`).
+pub const NUM_FILES_AT_TIER_PREFIX: &str = "oblivion.num-files-at-tier";
+
+/// Bytes of live SSTables in tiers that have reached the compaction
+/// threshold.
+pub const ESTIMATE_PENDING_COMPACTION_BYTES: &str = "oblivion.estimate-pending-compaction-bytes";
+
+/// Bytes in the active MemTable.
+pub const CUR_SIZE_ACTIVE_MEM_TABLE: &str = "oblivion.cur-size-active-mem-table";
+
+/// Frozen MemTables waiting to be flushed.
+pub const NUM_IMMUTABLE_MEM_TABLE: &str = "oblivion.num-immutable-mem-table";
+
+/// Bytes the active MemTable is flushed at.
+pub const MEMTABLE_FLUSH_THRESHOLD: &str = "oblivion.memtable-flush-threshold";
+
+/// Live snapshots.
+pub const NUM_SNAPSHOTS: &str = "oblivion.num-snapshots";
+
+/// Sequence number of the last write.
+pub const LAST_SEQUENCE: &str = "oblivion.last-sequence";
+
+/// Background jobs that failed.
+pub const BACKGROUND_ERRORS: &str = "oblivion.background-errors";
+
+/// The metrics report, as `EngineMetrics::report` formats it.
+pub const STATS: &str = "oblivion.stats";
+
+/// A live SSTable, as listed by
+/// [`Oblivion::live_files`](super::Oblivion::live_files).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiveFile {
+    /// Column family the table belongs to (`"default"` for the default
+    /// keyspace).
+    pub column_family: String,
+    /// Path of the table.
+    pub path: PathBuf,
+    /// Size of the file in bytes.
+    pub size: u64,
+    /// Compaction tier the table is in.
+    pub tier: usize,
+    /// Smallest key in the table.
+    pub smallest_key: Key,
+    /// Largest key in the table.
+    pub largest_key: Key,
+    /// Sequence number of the oldest write in the table; 0 if unknown.
+    pub smallest_seqno: u64,
+    /// Sequence number of the newest write in the table; 0 if unknown.
+    pub largest_seqno: u64,
+    /// Records in the table, tombstones included.
+    pub num_entries: u64,
+    /// Tombstones in the table.
+    pub num_deletions: u64,
+    /// When the table was written, in seconds since the Unix epoch; 0 if
+    /// unknown.
+    pub creation_time: u64,
+    /// Lookups and range scans the table served since the engine opened
+    /// it.
+    pub num_reads: u64,
+    /// When the table last served a read, in seconds since the Unix
+    /// epoch; 0 if it has not since the engine opened it.
+    pub last_access_time: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::engine::Oblivion;
+
+    #[test]
+    fn test_live_files_and_properties() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::new(dir.path());
+        let engine = Oblivion::open(config.clone()).unwrap();
+        engine.put(b"a".to_vec(), b"1".to_vec()).unwrap();
+        engine.flush().unwrap();
+        engine.put(b"b".to_vec(), b"2".to_vec()).unwrap();
+        engine.delete(b"a".to_vec()).unwrap();
+        engine.flush().unwrap();
+        engine.put(b"c".to_vec(), b"3".to_vec()).unwrap();
+        // Answered by the newer table's tombstone
+        assert_eq!(engine.get(b"a"), None);
+
+        let files = engine.live_files();
+        let ranges: Vec<(u64, u64)> = files
+            .iter()
+            .map(|file| (file.smallest_seqno, file.largest_seqno))
+            .collect();
+        assert_eq!(ranges, [(1, 1), (2, 3)]);
+        assert_eq!(files[1].smallest_key, b"a");
+        assert_eq!(files[1].largest_key, b"b");
+        assert_eq!((files[1].num_entries, files[1].num_deletions), (2, 1));
+        assert_eq!(files[0].column_family, "default");
+        assert_eq!(files[0].tier, 0);
+        assert!(files.iter().all(|file| file.creation_time > 0));
+        assert_eq!((files[0].num_reads, files[0].last_access_time), (0, 0));
+        assert_eq!(files[1].num_reads, 1);
+        assert!(files[1].last_access_time >= files[1].creation_time);
+
+        let property = |name: &str| engine.get_property(name);
+        assert_eq!(property(NUM_ENTRIES).as_deref(), Some("4"));
+        assert_eq!(property(NUM_LIVE_FILES).as_deref(), Some("2"));
+        assert_eq!(
+            property("oblivion.num-files-at-tier0").as_deref(),
+            Some("2")
+        );
+        assert_eq!(
+            property("oblivion.num-files-at-tier1").as_deref(),
+            Some("0")
+        );
+        assert_eq!(property(LAST_SEQUENCE).as_deref(), Some("4"));
+        assert_eq!(property(NUM_IMMUTABLE_MEM_TABLE).as_deref(), Some("0"));
+        assert_eq!(
+            property(MEMTABLE_FLUSH_THRESHOLD),
+            Some(config.memtable_max_size.to_string())
+        );
+        assert_eq!(
+            property(ESTIMATE_PENDING_COMPACTION_BYTES).as_deref(),
+            Some("0")
+        );
+        let size: u64 = files.iter().map(|file| file.size).sum();
+        assert_eq!(property(LIVE_SST_FILES_SIZE), Some(size.to_string()));
+        assert!(property(STATS).unwrap().contains("OBLIVION Engine Metrics"));
+        assert_eq!(property("oblivion.no-such-property"), None);
+        assert!(engine.get_property_cf("missing", NUM_ENTRIES).is_err());
+
+        // Compaction spans its inputs; after a reopen, the replayed write
+        // of c is flushed along with d
+        engine.compact().unwrap();
+        drop(engine);
+        let engine = Oblivion::open(config).unwrap();
+        engine.put(b"d".to_vec(), b"4".to_vec()).unwrap();
+        engine.flush().unwrap();
+        let ranges: Vec<(u64, u64)> = engine
+            .live_files()
+            .iter()
+            .map(|file| (file.smallest_seqno, file.largest_seqno))
+            .collect();
+        assert_eq!(ranges, [(1, 3), (4, 5)]);
+    }
+}
@@ -0,0 +1,258 @@
+//! OBLIVION - Repair
+//! Salvages a damaged database so it can be opened again.
+//!
+//! [`repair`] runs on a closed database (it takes the `LOCK` file) and:
+//! 1. reads every block of every SSTable with its CRC checked, moving
+//!    tables that fail into `data_dir/lost/`;
+//! 2. cuts each WAL segment and the live log at its first damaged
+//!    record, keeping the intact records before it;
+//! 3. writes a new manifest listing the surviving tables.
+//!
+//! A readable manifest keeps its table list and order, minus the
+//! quarantined tables; files it does not list stay leftovers, which the
+//! next open removes. A missing or damaged manifest is rebuilt from
+//! every surviving table, oldest file number first, as for a database
+//! without one. Rows of quarantined tables and truncated logs are lost;
+//! older versions of those keys may show through again.
+
+use std::fs::{self, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::error::{OblivionError, Result};
+
+use super::cache::TableCache;
+use super::io;
+use super::manifest::Manifest;
+use super::sstable::SSTable;
+use super::wal::{RecordStatus, WriteAheadLog};
+
+/// Name of the directory, inside `data_dir`, unreadable tables are moved to.
+pub const LOST_DIR: &str = "lost";
+
+/// What [`repair`] found and did.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepairReport {
+    /// File numbers of the tables listed in the new manifest, oldest
+    /// first.
+    pub tables_kept: Vec<u64>,
+    /// Unreadable tables, as moved into `lost/`, with the error found.
+    pub tables_quarantined: Vec<(PathBuf, String)>,
+    /// Number of logs checked (segments and the live log).
+    pub logs_checked: usize,
+    /// Logs cut at a damaged record, with the bytes removed.
+    pub logs_truncated: Vec<(PathBuf, u64)>,
+    /// Intact PUT and DELETE records left in the logs.
+    pub records_salvaged: u64,
+    /// Whether the manifest was missing or damaged and rebuilt from the
+    /// tables on disk.
+    pub manifest_rebuilt: bool,
+}
+
+/// Check and salvage the database `config` points at. Fails with
+/// [`OblivionError::Locked`] while an engine has it open.
+pub fn repair(config: &Config) -> Result<RepairReport> {
+    config.env.require_local("repair")?;
+    if !config.database_exists() {
+        return Err(OblivionError::DatabaseNotFound(config.data_dir.clone()));
+    }
+    let _lock = io::try_lock_file(&config.lock_path())?
+        .ok_or_else(|| OblivionError::Locked(config.data_dir.clone()))?;
+    config.ensure_dirs()?;
+    let mut report = RepairReport::default();
+
+    let table_cache = Arc::new(TableCache::new(1).with_comparator(config.comparator.clone()));
+    let mut good = Vec::new();
+    let mut last_number = None;
+    for (id, path) in table_files(&config.sst_dir())? {
+        last_number = Some(id);
+        match check_table(&path, &table_cache) {
+            Ok(()) => good.push(id),
+            Err(err) => {
+                log::warn!("Quarantining SSTable {:?}: {}", path, err);
+                let lost = config.data_dir.join(LOST_DIR);
+                fs::create_dir_all(&lost)?;
+                let target = lost.join(path.file_name().unwrap_or_default());
+                fs::rename(&path, &target)?;
+                report.tables_quarantined.push((target, err.to_string()));
+            }
+        }
+    }
+
+    let segments = WriteAheadLog::segments(&config.wal_dir())?;
+    if let Some((id, _)) = segments.last() {
+        last_number = last_number.max(Some(*id));
+    }
+    let mut logs: Vec<PathBuf> = segments.into_iter().map(|(_, path)| path).collect();
+    if config.wal_path().exists() {
+        logs.push(config.wal_path());
+    }
+    for log in &logs {
+        report.logs_checked += 1;
+        let records = WriteAheadLog::inspect(log)?;
+        let damaged = records.iter().position(|r| r.status != RecordStatus::Ok);
+        let intact = &records[..damaged.unwrap_or(records.len())];
+        report.records_salvaged += intact.iter().filter(|r| r.op != 3).count() as u64;
+        if let Some(damaged) = damaged {
+            let cut = records[damaged].offset;
+            let size = fs::metadata(log)?.len();
+            log::warn!(
+                "Truncating {:?} at offset {} ({} bytes)",
+                log,
+                cut,
+                size - cut
+            );
+            let file = OpenOptions::new().write(true).open(log)?;
+            file.set_len(cut)?;
+            file.sync_all()?;
+            report.logs_truncated.push((log.clone(), size - cut));
+        }
+    }
+
+    let manifest_path = config.manifest_path();
+    let old = match Manifest::load(&config.env, &manifest_path) {
+        Ok(manifest) => manifest,
+        Err(err) => {
+            log::warn!("Rebuilding damaged manifest: {}", err);
+            None
+        }
+    };
+    let (tables, next_file_number, comparator) = match old {
+        Some(old) => {
+            let tables = old.tables.into_iter().filter(|id| good.contains(id));
+            (tables.collect(), old.next_file_number, old.comparator)
+        }
+        None => {
+            report.manifest_rebuilt = true;
+            (good, 0, config.comparator.name().to_string())
+        }
+    };
+    let manifest = Manifest {
+        next_file_number: next_file_number.max(last_number.map_or(0, |id| id + 1)),
+        tables,
+        comparator,
+    };
+    manifest.save(&config.env, &manifest_path)?;
+    report.tables_kept = manifest.tables;
+
+    log::info!(
+        "Repaired {:?}: {} SSTables kept, {} quarantined, {} of {} logs truncated",
+        config.data_dir,
+        report.tables_kept.len(),
+        report.tables_quarantined.len(),
+        report.logs_truncated.len(),
+        report.logs_checked
+    );
+    Ok(report)
+}
+
+/// The SSTables in `dir`, by file number. Incomplete tables are removed.
+fn table_files(dir: &Path) -> Result<Vec<(u64, PathBuf)>> {
+    let mut tables = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        if name.ends_with(".sst.tmp") {
+            fs::remove_file(&path)?;
+            continue;
+        }
+        if let Some(id) = name
+            .strip_prefix("sstable_")
+            .and_then(|rest| rest.strip_suffix(".sst"))
+            .and_then(|id| id.parse::<u64>().ok())
+        {
+            tables.push((id, path));
+        }
+    }
+    tables.sort_unstable();
+    Ok(tables)
+}
+
+/// Read every block of the table at `path`, checking each CRC.
+fn check_table(path: &Path, table_cache: &Arc<TableCache>) -> Result<()> {
+    let table = SSTable::open(path.to_path_buf(), table_cache)?;
+    table.preload()?;
+    let mut records = 0;
+    for (_, handle) in table.block_index()? {
+        records += table.read_block_records(handle, true)?.len() as u64;
+    }
+    if records != table.properties().entry_count {
+        return Err(OblivionError::Corruption(format!(
+            "SSTable {:?} holds {} records, its properties say {}",
+            path,
+            records,
+            table.properties().entry_count
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Oblivion;
+
+    #[test]
+    fn test_repair_salvages_tables_and_logs() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::new(dir.path());
+        let engine = Oblivion::open(config.clone()).unwrap();
+        engine.put(b"a".to_vec(), b"1".to_vec()).unwrap();
+        engine.flush().unwrap();
+        engine.put(b"b".to_vec(), b"2".to_vec()).unwrap();
+        engine.flush().unwrap();
+        engine.put(b"c".to_vec(), b"3".to_vec()).unwrap();
+        engine.put(b"d".to_vec(), b"4".to_vec()).unwrap();
+        assert!(matches!(repair(&config), Err(OblivionError::Locked(_))));
+        drop(engine);
+
+        // Damage the second table's data block, the manifest, and the
+        // last WAL record
+        let tables = table_files(&config.sst_dir()).unwrap();
+        assert_eq!(tables.len(), 2);
+        let mut data = fs::read(&tables[1].1).unwrap();
+        data[2] ^= 0xFF;
+        fs::write(&tables[1].1, &data).unwrap();
+        fs::write(config.manifest_path(), b"junk").unwrap();
+        let mut wal = fs::read(config.wal_path()).unwrap();
+        let last = wal.len() - 1;
+        wal[last] ^= 0xFF;
+        fs::write(config.wal_path(), &wal).unwrap();
+
+        let report = repair(&config).unwrap();
+        assert_eq!(report.tables_kept, vec![tables[0].0]);
+        assert_eq!(report.tables_quarantined.len(), 1);
+        assert!(config
+            .data_dir
+            .join(LOST_DIR)
+            .join(tables[1].1.file_name().unwrap())
+            .exists());
+        assert!(report.manifest_rebuilt);
+        assert_eq!(report.logs_truncated.len(), 1);
+        assert_eq!(report.logs_truncated[0].1, 15);
+
+        let engine = Oblivion::open(config.clone()).unwrap();
+        assert_eq!(engine.get(b"a"), Some(b"1".to_vec()));
+        assert_eq!(engine.get(b"b"), None);
+        assert_eq!(engine.get(b"c"), Some(b"3".to_vec()));
+        assert_eq!(engine.get(b"d"), None);
+        drop(engine);
+
+        // A healthy database comes through untouched
+        let report = repair(&config).unwrap();
+        assert!(report.tables_quarantined.is_empty() && report.logs_truncated.is_empty());
+        assert!(!report.manifest_rebuilt);
+    }
+
+    #[test]
+    fn test_repair_requires_database() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(matches!(
+            repair(&Config::new(dir.path().join("missing"))),
+            Err(OblivionError::DatabaseNotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,1156 @@
+//! OBLIVION - Replication
+//! Asynchronous WAL-shipping from a primary engine to warm-standby
+//! followers over TCP.
+//!
+//! ## Protocol
+//! Every message is one frame, `[len: 4 bytes LE][crc: 4 bytes LE][bincode(Message)]`:
+//! ```text
+//! follower                                  primary
+//!   Hello { epoch, next_sequence }  ──────►
+//!                                   ◄──────  SyncStart { epoch, sequence }   full sync only
+//!                                   ◄──────  SyncPage { cf, rows, end } ...
+//!                                   ◄──────  Records { records, last_sequence } | Heartbeat { last_sequence } ...
+//! ```
+//!
+//! Every record batch and heartbeat carries the primary's latest
+//! sequence number, from which followers compute their lag.
+//!
+//! ## Catching Up
+//! The primary keeps its latest writes, numbered by their WAL sequence
+//! numbers, in an in-memory backlog of `replication_backlog_size` bytes.
+//! A follower resumes after the last record it applied if that record
+//! belongs to the backlog's history (its epoch) and everything after it
+//! is still held. Otherwise the primary sends a full copy from a
+//! snapshot, page by page and one keyspace at a time (the column
+//! families, then the default keyspace), then streams the writes made
+//! since. A new
+//! history begins each time the primary opens and after bulk imports,
+//! which bypass the WAL.
+//!
+//! ## Followers
+//! A follower applies records through its own engine and, after each
+//! batch, syncs its WAL and stores the epoch and sequence number it
+//! reached in `data_dir/REPLICATION`, so it resumes after a restart.
+//! Records applied again after a crash replay in order, which is
+//! harmless. While following, the engine refuses writes through its
+//! public API ([`OblivionError::ReadOnly`]).
+//!
+//! Column family writes are replicated like the default keyspace's, so
+//! a follower must be opened with every column family of its primary
+//! (see [`Oblivion::open_with_cfs`]); a write to a family it lacks stops
+//! replication with [`OblivionError::ColumnFamilyNotFound`] until it is
+//! reopened with it.
+//!
+//! ## Failover
+//! [`Follower::status`] and [`ReplicationServer::status`] report each
+//! side's position, lag and connection health for an external
+//! supervisor. To fail over, the supervisor promotes the most
+//! up-to-date follower with [`Follower::promote`], which stops
+//! replication and makes the engine writable, then starts a
+//! [`ReplicationServer`] on it for the remaining followers. Those
+//! resync in full, since the new primary's history is a new epoch.
+//!
+//! Replication is asynchronous: the primary acknowledges writes before
+//! followers receive them, so a failover can lose the latest ones.
+
+use std::collections::hash_map::RandomState;
+use std::collections::{HashSet, VecDeque};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime};
+
+use parking_lot::{Condvar, Mutex};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{OblivionError, Result};
+use crate::types::{Key, Value};
+
+pub use super::changes::{ChangeOp, ChangeRecord};
+use super::env::Env;
+use super::io::SyncMethod;
+use super::options::ReadOptions;
+use super::snapshot::Snapshot;
+use super::version::ReadState;
+use super::Oblivion;
+
+/// Version of the wire protocol, checked in the handshake.
+const PROTOCOL_VERSION: u32 = 2;
+
+/// Name of the follower's state file inside `data_dir`.
+pub(crate) const STATE_FILE: &str = "REPLICATION";
+
+/// Rows per page of a full sync.
+const SYNC_PAGE_SIZE: usize = 1024;
+
+/// Largest batch of records sent in one message, in bytes.
+const MAX_BATCH_BYTES: usize = 1024 * 1024;
+
+/// Largest frame accepted from the network.
+const MAX_FRAME_LEN: usize = 1 << 30;
+
+/// How often an idle primary tells followers it is alive.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Silence after which a connection is considered dead.
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Pause between a follower's connection attempts.
+const RECONNECT_DELAY: Duration = Duration::from_millis(200);
+
+/// How often the primary's accept loop checks for shutdown.
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Result of [`Backlog::read`].
+pub(crate) enum Fetch {
+    /// The next records, in order.
+    Records(Vec<ChangeRecord>),
+    /// Nothing new before the timeout.
+    Idle,
+    /// The history changed, or records were evicted before being read.
+    Lost,
+}
+
+/// The primary's latest writes, kept in memory for followers.
+///
+/// Recording starts with the first follower; until then writes only
+/// pay for an atomic load. Pushes happen under the engine's writer
+/// lock, so records are in sequence order.
+pub(crate) struct Backlog {
+    enabled: AtomicBool,
+    inner: Mutex<BacklogInner>,
+    appended: Condvar,
+}
+
+struct BacklogInner {
+    /// Identifies the history the records belong to.
+    epoch: u64,
+    /// Sequence number of the last write recorded (or skipped).
+    last_sequence: u64,
+    records: VecDeque<ChangeRecord>,
+    bytes: usize,
+    capacity: usize,
+}
+
+impl Backlog {
+    /// Create a disabled backlog holding up to `capacity` bytes.
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            enabled: AtomicBool::new(false),
+            inner: Mutex::new(BacklogInner {
+                epoch: new_epoch(),
+                last_sequence: 0,
+                records: VecDeque::new(),
+                bytes: 0,
+                capacity,
+            }),
+            appended: Condvar::new(),
+        }
+    }
+
+    /// Returns whether writes are being recorded.
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Acquire)
+    }
+
+    /// Start recording the writes after `last_sequence`. Must be called
+    /// under the writer lock; does nothing if already recording.
+    pub(crate) fn enable(&self, last_sequence: u64) {
+        if !self.is_enabled() {
+            self.inner.lock().last_sequence = last_sequence;
+            self.enabled.store(true, Ordering::Release);
+        }
+    }
+
+    /// Record a write, evicting the oldest records beyond the capacity.
+    pub(crate) fn push(&self, record: ChangeRecord) {
+        let mut inner = self.inner.lock();
+        inner.last_sequence = record.sequence;
+        inner.bytes += record.size();
+        inner.records.push_back(record);
+        while inner.bytes > inner.capacity {
+            let Some(evicted) = inner.records.pop_front() else {
+                break;
+            };
+            inner.bytes -= evicted.size();
+        }
+        drop(inner);
+        self.appended.notify_all();
+    }
+
+    /// Start a new history after a change that was not recorded, so
+    /// followers fall back to a full sync.
+    pub(crate) fn reset(&self, last_sequence: u64) {
+        let mut inner = self.inner.lock();
+        inner.epoch = new_epoch();
+        inner.last_sequence = last_sequence;
+        inner.records.clear();
+        inner.bytes = 0;
+        drop(inner);
+        self.appended.notify_all();
+    }
+
+    /// Returns the current epoch and last recorded sequence number.
+    pub(crate) fn position(&self) -> (u64, u64) {
+        let inner = self.inner.lock();
+        (inner.epoch, inner.last_sequence)
+    }
+
+    /// Returns whether every record of `epoch` from `next_sequence` on
+    /// can still be read.
+    pub(crate) fn covers(&self, epoch: u64, next_sequence: u64) -> bool {
+        self.is_enabled() && self.inner.lock().covers(epoch, next_sequence)
+    }
+
+    /// Read the records of `epoch` from `next_sequence` on, up to about
+    /// `max_bytes`, waiting up to `timeout` for one to arrive.
+    pub(crate) fn read(
+        &self,
+        epoch: u64,
+        next_sequence: u64,
+        max_bytes: usize,
+        timeout: Duration,
+    ) -> Fetch {
+        let mut inner = self.inner.lock();
+        if inner.last_sequence < next_sequence {
+            self.appended.wait_for(&mut inner, timeout);
+        }
+        if !inner.covers(epoch, next_sequence) {
+            return Fetch::Lost;
+        }
+        let Some(first) = inner.records.front() else {
+            return Fetch::Idle;
+        };
+        let skip = (next_sequence - first.sequence) as usize;
+        let mut bytes = 0;
+        let records: Vec<ChangeRecord> = inner
+            .records
+            .iter()
+            .skip(skip)
+            .take_while(|record| {
+                let fits = bytes == 0 || bytes + record.size() <= max_bytes;
+                bytes += record.size();
+                fits
+            })
+            .cloned()
+            .collect();
+        if records.is_empty() {
+            Fetch::Idle
+        } else {
+            Fetch::Records(records)
+        }
+    }
+}
+
+impl BacklogInner {
+    fn covers(&self, epoch: u64, next_sequence: u64) -> bool {
+        let oldest = self
+            .records
+            .front()
+            .map_or(self.last_sequence + 1, |record| record.sequence);
+        epoch == self.epoch && (oldest..=self.last_sequence + 1).contains(&next_sequence)
+    }
+}
+
+/// A random, non-zero history id.
+fn new_epoch() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos()),
+    );
+    hasher.finish().max(1)
+}
+
+/// Messages exchanged between a primary and a follower.
+#[derive(Debug, Serialize, Deserialize)]
+enum Message {
+    /// Follower: resume history `epoch` at `next_sequence` (epoch 0
+    /// asks for a full sync).
+    Hello {
+        protocol: u32,
+        epoch: u64,
+        next_sequence: u64,
+    },
+    /// Primary: a full copy as of `sequence` in history `epoch` follows.
+    SyncStart { epoch: u64, sequence: u64 },
+    /// Primary: every live row of the column family `cf` (`None` for
+    /// the default keyspace) from the previous page's end up to `end`
+    /// (exclusive, `None` for the keyspace's last page), with remaining
+    /// TTLs. The default keyspace's last page ends the full sync.
+    SyncPage {
+        cf: Option<String>,
+        rows: Vec<(Key, Value, Option<u64>)>,
+        end: Option<Key>,
+    },
+    /// Primary: the next writes, in sequence order, and its latest
+    /// sequence number.
+    Records {
+        records: Vec<ChangeRecord>,
+        last_sequence: u64,
+    },
+    /// Primary: nothing new; its latest sequence number.
+    Heartbeat { last_sequence: u64 },
+}
+
+fn send(stream: &mut TcpStream, message: &Message) -> Result<()> {
+    let payload =
+        bincode::serialize(message).map_err(|e| OblivionError::Serialization(e.to_string()))?;
+    let mut frame = Vec::with_capacity(payload.len() + 8);
+    frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    frame.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+    frame.extend_from_slice(&payload);
+    stream.write_all(&frame)?;
+    Ok(())
+}
+
+fn receive(stream: &mut TcpStream) -> Result<Message> {
+    let mut header = [0u8; 8];
+    stream.read_exact(&mut header)?;
+    let len = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(OblivionError::Corruption(format!(
+            "replication frame of {} bytes",
+            len
+        )));
+    }
+    let mut payload = vec![0; len];
+    stream.read_exact(&mut payload)?;
+    if crc32fast::hash(&payload).to_le_bytes() != header[4..] {
+        return Err(OblivionError::Corruption(
+            "replication frame failed CRC check".to_string(),
+        ));
+    }
+    bincode::deserialize(&payload)
+        .map_err(|e| OblivionError::Corruption(format!("replication frame: {}", e)))
+}
+
+/// Serves an engine's writes to followers.
+///
+/// ## Example
+/// ```no_run
+/// use std::sync::Arc;
+/// use oblivion::{config::Config, engine::{replication::ReplicationServer, Oblivion}};
+///
+/// let engine = Arc::new(Oblivion::open(Config::new("./primary")).unwrap());
+/// let server = ReplicationServer::start(Arc::clone(&engine), "0.0.0.0:7400").unwrap();
+/// engine.put(b"key".to_vec(), b"value".to_vec()).unwrap();
+/// ```
+pub struct ReplicationServer {
+    local_addr: SocketAddr,
+    shared: Arc<ServerShared>,
+    acceptor: Option<JoinHandle<()>>,
+}
+
+struct ServerShared {
+    engine: Arc<Oblivion>,
+    stop: AtomicBool,
+    /// Connections to followers, kept to report on and shut them down.
+    sessions: Mutex<Vec<Session>>,
+}
+
+/// One follower connection of a primary.
+struct Session {
+    peer: SocketAddr,
+    stream: TcpStream,
+    handle: JoinHandle<()>,
+    progress: Arc<SessionProgress>,
+}
+
+/// How far a session has brought its follower, updated by its thread.
+#[derive(Default)]
+struct SessionProgress {
+    /// Last sequence number sent; a full sync counts once complete.
+    sent: AtomicU64,
+    /// Whether a full sync is in progress.
+    syncing: AtomicBool,
+}
+
+/// A primary's view of its replication, from [`ReplicationServer::status`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrimaryStatus {
+    /// Current history id; changes on open and after bulk imports.
+    pub epoch: u64,
+    /// Sequence number of the latest committed write.
+    pub last_sequence: u64,
+    /// Connected followers.
+    pub followers: Vec<ConnectedFollower>,
+}
+
+/// One connected follower, as seen by the primary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectedFollower {
+    /// The follower's address.
+    pub addr: SocketAddr,
+    /// Whether it is receiving a full copy.
+    pub syncing: bool,
+    /// Last sequence number sent to it.
+    pub sent_sequence: u64,
+    /// Writes not yet sent to it.
+    pub lag: u64,
+}
+
+impl ReplicationServer {
+    /// Listen for followers on `addr` and start recording writes.
+    pub fn start(engine: Arc<Oblivion>, addr: impl ToSocketAddrs) -> Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+        engine.enable_backlog();
+        let shared = Arc::new(ServerShared {
+            engine,
+            stop: AtomicBool::new(false),
+            sessions: Mutex::new(Vec::new()),
+        });
+        let acceptor = {
+            let shared = Arc::clone(&shared);
+            std::thread::Builder::new()
+                .name("oblivion-repl-accept".to_string())
+                .spawn(move || shared.accept_loop(listener))?
+        };
+        log::info!("Replication server listening on {}", local_addr);
+        Ok(Self {
+            local_addr,
+            shared,
+            acceptor: Some(acceptor),
+        })
+    }
+
+    /// Returns the address followers connect to.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Returns the number of connected followers.
+    pub fn follower_count(&self) -> usize {
+        let mut sessions = self.shared.sessions.lock();
+        sessions.retain(|session| !session.handle.is_finished());
+        sessions.len()
+    }
+
+    /// Returns the primary's position and the progress of each
+    /// connected follower.
+    pub fn status(&self) -> PrimaryStatus {
+        let (epoch, last_sequence) = self.shared.engine.backlog.position();
+        let mut sessions = self.shared.sessions.lock();
+        sessions.retain(|session| !session.handle.is_finished());
+        let followers = sessions
+            .iter()
+            .map(|session| {
+                let sent_sequence = session.progress.sent.load(Ordering::Acquire);
+                ConnectedFollower {
+                    addr: session.peer,
+                    syncing: session.progress.syncing.load(Ordering::Acquire),
+                    sent_sequence,
+                    lag: last_sequence.saturating_sub(sent_sequence),
+                }
+            })
+            .collect();
+        PrimaryStatus {
+            epoch,
+            last_sequence,
+            followers,
+        }
+    }
+}
+
+impl Drop for ReplicationServer {
+    fn drop(&mut self) {
+        self.shared.stop.store(true, Ordering::Release);
+        if let Some(acceptor) = self.acceptor.take() {
+            let _ = acceptor.join();
+        }
+        let sessions = std::mem::take(&mut *self.shared.sessions.lock());
+        for session in sessions {
+            let _ = session.stream.shutdown(std::net::Shutdown::Both);
+            let _ = session.handle.join();
+        }
+    }
+}
+
+impl ServerShared {
+    fn accept_loop(self: Arc<Self>, listener: TcpListener) {
+        while !self.stop.load(Ordering::Acquire) {
+            match listener.accept() {
+                Ok((stream, peer)) => {
+                    if let Err(e) = self.spawn_session(stream, peer) {
+                        log::warn!("Failed to start replication to {}: {}", peer, e);
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    std::thread::sleep(ACCEPT_POLL_INTERVAL)
+                }
+                Err(e) => {
+                    log::warn!("Replication accept failed: {}", e);
+                    std::thread::sleep(ACCEPT_POLL_INTERVAL);
+                }
+            }
+        }
+    }
+
+    fn spawn_session(self: &Arc<Self>, stream: TcpStream, peer: SocketAddr) -> Result<()> {
+        stream.set_nonblocking(false)?;
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(IO_TIMEOUT))?;
+        stream.set_write_timeout(Some(IO_TIMEOUT))?;
+        let progress = Arc::new(SessionProgress::default());
+        let handle = {
+            let shared = Arc::clone(self);
+            let stream = stream.try_clone()?;
+            let progress = Arc::clone(&progress);
+            std::thread::Builder::new()
+                .name("oblivion-repl-send".to_string())
+                .spawn(move || match shared.serve(stream, &progress) {
+                    Ok(()) => {}
+                    Err(e) if shared.stop.load(Ordering::Acquire) => {
+                        log::debug!("Replication to {} stopped: {}", peer, e)
+                    }
+                    Err(e) => log::warn!("Replication to {} failed: {}", peer, e),
+                })?
+        };
+        let mut sessions = self.sessions.lock();
+        sessions.retain(|session| !session.handle.is_finished());
+        sessions.push(Session {
+            peer,
+            stream,
+            handle,
+            progress,
+        });
+        Ok(())
+    }
+
+    /// Bring one follower up to date, then stream writes to it.
+    fn serve(&self, mut stream: TcpStream, progress: &SessionProgress) -> Result<()> {
+        let Message::Hello {
+            protocol,
+            epoch,
+            next_sequence,
+        } = receive(&mut stream)?
+        else {
+            return Err(OblivionError::Corruption(
+                "expected a replication handshake".to_string(),
+            ));
+        };
+        if protocol != PROTOCOL_VERSION {
+            return Err(OblivionError::Unsupported(format!(
+                "replication protocol {}",
+                protocol
+            )));
+        }
+
+        let backlog = &self.engine.backlog;
+        let (mut epoch, mut next) = if backlog.covers(epoch, next_sequence) {
+            log::info!("Follower resumed at sequence {}", next_sequence);
+            (epoch, next_sequence)
+        } else {
+            self.full_sync(&mut stream, progress)?
+        };
+        while !self.stop.load(Ordering::Acquire) {
+            progress.sent.store(next - 1, Ordering::Release);
+            match backlog.read(epoch, next, MAX_BATCH_BYTES, HEARTBEAT_INTERVAL) {
+                Fetch::Records(records) => {
+                    next = records[records.len() - 1].sequence + 1;
+                    let (_, last_sequence) = backlog.position();
+                    send(
+                        &mut stream,
+                        &Message::Records {
+                            records,
+                            last_sequence,
+                        },
+                    )?;
+                }
+                Fetch::Idle => {
+                    let (_, last_sequence) = backlog.position();
+                    send(&mut stream, &Message::Heartbeat { last_sequence })?
+                }
+                Fetch::Lost => (epoch, next) = self.full_sync(&mut stream, progress)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Send a copy of every live row as of now. Returns the epoch and
+    /// sequence number streaming continues from.
+    fn full_sync(&self, stream: &mut TcpStream, progress: &SessionProgress) -> Result<(u64, u64)> {
+        let (epoch, sequence, snapshot, families) = self.engine.replication_point();
+        log::info!("Full sync of a follower as of sequence {}", sequence);
+        progress.syncing.store(true, Ordering::Release);
+        send(stream, &Message::SyncStart { epoch, sequence })?;
+        for (name, snapshot) in families {
+            let state = Arc::clone(&self.engine.family(&name)?.state);
+            send_keyspace(stream, Some(name), &state, snapshot)?;
+        }
+        // Last, as its final page ends the sync
+        send_keyspace(stream, None, &self.engine.state, snapshot)?;
+        progress.syncing.store(false, Ordering::Release);
+        Ok((epoch, sequence + 1))
+    }
+}
+
+/// Send every live row of the column family `cf` (`None` for the
+/// default keyspace), read from `state` as of `snapshot`, page by page.
+fn send_keyspace(
+    stream: &mut TcpStream,
+    cf: Option<String>,
+    state: &ReadState,
+    snapshot: Snapshot,
+) -> Result<()> {
+    let mut opts = ReadOptions::new().snapshot(snapshot).fill_cache(false);
+    loop {
+        let (page, resume) = state.scan_page(&opts, SYNC_PAGE_SIZE)?;
+        let rows = page
+            .into_iter()
+            .map(|(key, value)| {
+                let ttl = state.ttl(&key);
+                (key, value, ttl)
+            })
+            .collect();
+        send(
+            stream,
+            &Message::SyncPage {
+                cf: cf.clone(),
+                rows,
+                end: resume.clone(),
+            },
+        )?;
+        match resume {
+            Some(resume) => opts.lower_bound = Some(resume),
+            None => return Ok(()),
+        }
+    }
+}
+
+/// Where a follower is in its primary's history, as stored in
+/// `data_dir/REPLICATION`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct FollowerState {
+    /// History of the primary; 0 before the first full sync completes.
+    epoch: u64,
+    /// Last sequence number applied.
+    applied: u64,
+}
+
+impl FollowerState {
+    fn load(env: &Env, path: &Path) -> Result<Self> {
+        let data = match env.read(path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e.into()),
+        };
+        if data.len() < 4 || crc32fast::hash(&data[4..]).to_le_bytes() != data[..4] {
+            log::warn!("{:?} failed CRC check, starting with a full sync", path);
+            return Ok(Self::default());
+        }
+        bincode::deserialize(&data[4..])
+            .map_err(|e| OblivionError::Corruption(format!("{:?}: {}", path, e)))
+    }
+
+    fn save(&self, env: &Env, path: &Path) -> Result<()> {
+        let payload =
+            bincode::serialize(self).map_err(|e| OblivionError::Serialization(e.to_string()))?;
+        let tmp_path = path.with_extension("tmp");
+        {
+            let mut file = env.create(&tmp_path, false)?;
+            file.write_all(&crc32fast::hash(&payload).to_le_bytes())?;
+            file.write_all(&payload)?;
+            file.sync(SyncMethod::Fsync)?;
+        }
+        env.rename(&tmp_path, path)?;
+        if let Some(dir) = path.parent() {
+            env.sync_dir(dir)?;
+        }
+        Ok(())
+    }
+}
+
+/// Keeps an engine in sync with a primary's [`ReplicationServer`],
+/// reconnecting whenever the connection drops.
+///
+/// ## Example
+/// ```no_run
+/// use std::sync::Arc;
+/// use oblivion::{config::Config, engine::{replication::Follower, Oblivion}};
+///
+/// let engine = Arc::new(Oblivion::open(Config::new("./standby")).unwrap());
+/// let follower = Follower::start(Arc::clone(&engine), "primary.internal:7400").unwrap();
+/// println!("{} writes behind", follower.lag());
+///
+/// // The primary is gone: take over
+/// let status = follower.promote().unwrap();
+/// println!("promoted at sequence {}", status.applied_sequence);
+/// engine.put(b"key".to_vec(), b"value".to_vec()).unwrap();
+/// ```
+pub struct Follower {
+    shared: Arc<FollowerShared>,
+    worker: Option<JoinHandle<()>>,
+}
+
+struct FollowerShared {
+    engine: Arc<Oblivion>,
+    primary: Vec<SocketAddr>,
+    stop: AtomicBool,
+    /// Last primary sequence number applied.
+    applied: AtomicU64,
+    /// Connection state, for [`Follower::status`].
+    link: Mutex<Link>,
+    /// Current connection, kept to shut it down.
+    connection: Mutex<Option<TcpStream>>,
+}
+
+/// What a follower knows about its connection to the primary.
+struct Link {
+    phase: FollowerPhase,
+    /// Latest sequence number the primary reported.
+    primary_sequence: u64,
+    /// When the primary last sent a message.
+    last_contact: Option<Instant>,
+    /// Why the last connection ended or could not be made.
+    last_error: Option<String>,
+}
+
+/// Connection phase of a [`Follower`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FollowerPhase {
+    /// Not connected; retrying every few hundred milliseconds.
+    Connecting,
+    /// Receiving a full copy; the data is incomplete until it ends.
+    FullSync,
+    /// Applying the primary's writes as they arrive.
+    Streaming,
+}
+
+/// A follower's replication health, from [`Follower::status`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FollowerStatus {
+    /// Connection phase.
+    pub phase: FollowerPhase,
+    /// Last primary sequence number applied.
+    pub applied_sequence: u64,
+    /// Latest sequence number the primary reported.
+    pub primary_sequence: u64,
+    /// Writes the primary has committed that are not applied yet, as
+    /// of the primary's last message.
+    pub lag: u64,
+    /// Time since the primary last sent a message (it sends at least a
+    /// heartbeat every 500 ms), or `None` if it never has.
+    pub since_last_contact: Option<Duration>,
+    /// Why the last connection ended or could not be made.
+    pub last_error: Option<String>,
+}
+
+impl FollowerStatus {
+    /// Returns whether the follower is streaming, at most `max_lag`
+    /// writes behind, and heard from the primary within `max_silence`.
+    pub fn is_healthy(&self, max_lag: u64, max_silence: Duration) -> bool {
+        self.phase == FollowerPhase::Streaming
+            && self.lag <= max_lag
+            && self
+                .since_last_contact
+                .is_some_and(|silence| silence <= max_silence)
+    }
+}
+
+impl Follower {
+    /// Start following the primary at `primary`, resuming from the state
+    /// stored in the engine's data directory. The engine refuses writes
+    /// until the follower is promoted or the engine is reopened.
+    pub fn start(engine: Arc<Oblivion>, primary: impl ToSocketAddrs) -> Result<Self> {
+        let primary: Vec<SocketAddr> = primary.to_socket_addrs()?.collect();
+        if primary.is_empty() {
+            return Err(OblivionError::Config(
+                "primary address resolved to nothing".to_string(),
+            ));
+        }
+        let state = FollowerState::load(&engine.config().env, &state_path(&engine))?;
+        if engine.set_read_only(true) {
+            return Err(OblivionError::Config(
+                "engine is already a replication follower".to_string(),
+            ));
+        }
+        let shared = Arc::new(FollowerShared {
+            engine,
+            primary,
+            stop: AtomicBool::new(false),
+            applied: AtomicU64::new(state.applied),
+            link: Mutex::new(Link {
+                phase: FollowerPhase::Connecting,
+                primary_sequence: state.applied,
+                last_contact: None,
+                last_error: None,
+            }),
+            connection: Mutex::new(None),
+        });
+        let worker = {
+            let shared = Arc::clone(&shared);
+            std::thread::Builder::new()
+                .name("oblivion-repl-follow".to_string())
+                .spawn(move || shared.run())
+        };
+        match worker {
+            Ok(worker) => Ok(Self {
+                shared,
+                worker: Some(worker),
+            }),
+            Err(e) => {
+                shared.engine.set_read_only(false);
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Returns the last primary sequence number applied.
+    pub fn applied_sequence(&self) -> u64 {
+        self.shared.applied.load(Ordering::Acquire)
+    }
+
+    /// Returns how many of the primary's writes are not applied yet, as
+    /// of its last message.
+    pub fn lag(&self) -> u64 {
+        self.status().lag
+    }
+
+    /// Returns the follower's position, lag and connection health.
+    pub fn status(&self) -> FollowerStatus {
+        let applied_sequence = self.applied_sequence();
+        let link = self.shared.link.lock();
+        FollowerStatus {
+            phase: link.phase,
+            applied_sequence,
+            primary_sequence: link.primary_sequence,
+            lag: link.primary_sequence.saturating_sub(applied_sequence),
+            since_last_contact: link.last_contact.map(|at| at.elapsed()),
+            last_error: link.last_error.clone(),
+        }
+    }
+
+    /// Stop following and make the engine a writable primary. Returns
+    /// the status at the moment replication stopped; its `lag` counts
+    /// the primary's writes this engine never received.
+    ///
+    /// A full sync still in progress leaves the engine with only part
+    /// of the primary's data, so promote a follower in
+    /// [`FollowerPhase::Streaming`] where possible. The stored
+    /// replication state is removed: if the engine follows a primary
+    /// again, it starts with a full sync.
+    pub fn promote(mut self) -> Result<FollowerStatus> {
+        self.stop();
+        let status = self.status();
+        let engine = &self.shared.engine;
+        engine.sync_wal()?;
+        match engine.config().env.remove_file(&state_path(engine)) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        engine.set_read_only(false);
+        log::info!(
+            "Follower promoted to primary at sequence {} ({} writes behind)",
+            status.applied_sequence,
+            status.lag
+        );
+        Ok(status)
+    }
+
+    /// Shut down the connection and wait for the worker to exit.
+    fn stop(&mut self) {
+        self.shared.stop.store(true, Ordering::Release);
+        if let Some(stream) = self.shared.connection.lock().take() {
+            let _ = stream.shutdown(std::net::Shutdown::Both);
+        }
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Dropping a follower stops replication but leaves the engine
+/// read-only; use [`Follower::promote`] to make it writable.
+impl Drop for Follower {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Path of the follower state file of `engine`.
+fn state_path(engine: &Oblivion) -> std::path::PathBuf {
+    engine.config().data_dir.join(STATE_FILE)
+}
+
+impl FollowerShared {
+    fn stopped(&self) -> bool {
+        self.stop.load(Ordering::Acquire)
+    }
+
+    /// Connect, follow until the connection fails, and retry.
+    fn run(&self) {
+        while !self.stopped() {
+            let error = match self.connect() {
+                Ok(stream) => {
+                    let result = self.follow(stream);
+                    *self.connection.lock() = None;
+                    match result {
+                        Err(e) if !self.stopped() => {
+                            log::warn!("Replication from primary interrupted: {}", e);
+                            e
+                        }
+                        _ => break,
+                    }
+                }
+                Err(e) => {
+                    log::debug!("Cannot reach primary: {}", e);
+                    e
+                }
+            };
+            let mut link = self.link.lock();
+            link.phase = FollowerPhase::Connecting;
+            link.last_error = Some(error.to_string());
+            drop(link);
+            std::thread::sleep(RECONNECT_DELAY);
+        }
+    }
+
+    /// Note a message from the primary, and its latest sequence number
+    /// if the message carries one.
+    fn contact(&self, phase: Option<FollowerPhase>, primary_sequence: Option<u64>) {
+        let mut link = self.link.lock();
+        link.last_contact = Some(Instant::now());
+        if let Some(phase) = phase {
+            link.phase = phase;
+        }
+        if let Some(sequence) = primary_sequence {
+            link.primary_sequence = sequence;
+        }
+    }
+
+    fn connect(&self) -> Result<TcpStream> {
+        let stream = TcpStream::connect(&self.primary[..])?;
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(IO_TIMEOUT))?;
+        stream.set_write_timeout(Some(IO_TIMEOUT))?;
+        *self.connection.lock() = Some(stream.try_clone()?);
+        if self.stopped() {
+            return Err(OblivionError::Io(std::io::ErrorKind::Interrupted.into()));
+        }
+        Ok(stream)
+    }
+
+    /// Handshake, then apply what the primary sends.
+    fn follow(&self, mut stream: TcpStream) -> Result<()> {
+        let path = state_path(&self.engine);
+        let env = self.engine.config().env.clone();
+        let mut state = FollowerState::load(&env, &path)?;
+        send(
+            &mut stream,
+            &Message::Hello {
+                protocol: PROTOCOL_VERSION,
+                epoch: state.epoch,
+                next_sequence: state.applied + 1,
+            },
+        )?;
+
+        // Lower bound of the next page of a full sync
+        let mut lower: Option<Key> = None;
+        loop {
+            let message = receive(&mut stream)?;
+            match &message {
+                Message::SyncStart { sequence, .. } => {
+                    self.contact(Some(FollowerPhase::FullSync), Some(*sequence))
+                }
+                Message::Records { last_sequence, .. } | Message::Heartbeat { last_sequence } => {
+                    self.contact(Some(FollowerPhase::Streaming), Some(*last_sequence))
+                }
+                _ => self.contact(None, None),
+            }
+            match message {
+                Message::SyncStart { epoch, sequence } => {
+                    log::info!("Full sync from primary as of sequence {}", sequence);
+                    // An interrupted sync must start over
+                    FollowerState::default().save(&env, &path)?;
+                    state = FollowerState {
+                        epoch,
+                        applied: sequence,
+                    };
+                    lower = None;
+                }
+                Message::SyncPage { cf, rows, end } => {
+                    let done = cf.is_none() && end.is_none();
+                    self.apply_page(cf.as_deref(), lower.take(), rows, end.clone())?;
+                    lower = end;
+                    if done {
+                        self.save(&state, &path)?;
+                        self.contact(Some(FollowerPhase::Streaming), None);
+                        log::info!("Full sync complete");
+                    }
+                }
+                Message::Records { records, .. } => {
+                    for record in records {
+                        if record.sequence <= state.applied {
+                            continue;
+                        }
+                        if record.sequence != state.applied + 1 {
+                            return Err(OblivionError::Corruption(format!(
+                                "replication gap: expected sequence {}, got {}",
+                                state.applied + 1,
+                                record.sequence
+                            )));
+                        }
+                        match record.op {
+                            ChangeOp::Put { key, value, ttl_ms } => {
+                                self.write(None, key, Some(value), ttl_ms)?
+                            }
+                            ChangeOp::Delete { key } => self.write(None, key, None, None)?,
+                            ChangeOp::PutCf { cf, key, value } => {
+                                self.write(Some(&cf), key, Some(value), None)?
+                            }
+                            ChangeOp::DeleteCf { cf, key } => {
+                                self.write(Some(&cf), key, None, None)?
+                            }
+                        }
+                        state.applied = record.sequence;
+                    }
+                    self.save(&state, &path)?;
+                }
+                Message::Heartbeat { .. } => {}
+                Message::Hello { .. } => {
+                    return Err(OblivionError::Corruption(
+                        "unexpected handshake from primary".to_string(),
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Make the rows of the column family `cf` (`None` for the default
+    /// keyspace) in `[lower, end)` match one page of a full sync.
+    fn apply_page(
+        &self,
+        cf: Option<&str>,
+        lower: Option<Key>,
+        rows: Vec<(Key, Value, Option<u64>)>,
+        end: Option<Key>,
+    ) -> Result<()> {
+        let mut opts = ReadOptions::new().fill_cache(false);
+        opts.lower_bound = lower;
+        opts.upper_bound = end;
+        let existing = match cf {
+            Some(cf) => self.engine.scan_cf(cf, &opts)?,
+            None => self.engine.scan_opt(&opts)?,
+        };
+        let keys: HashSet<&[u8]> = rows.iter().map(|(key, _, _)| key.as_slice()).collect();
+        for (key, _) in existing {
+            if !keys.contains(key.as_slice()) {
+                self.write(cf, key, None, None)?;
+            }
+        }
+        for (key, value, ttl) in rows {
+            self.write(cf, key, Some(value), ttl)?;
+        }
+        Ok(())
+    }
+
+    /// Put `value` with `ttl_ms` into the column family `cf` (`None` for
+    /// the default keyspace), or delete `key` if `value` is `None`.
+    /// Column family writes take no TTL.
+    fn write(
+        &self,
+        cf: Option<&str>,
+        key: Key,
+        value: Option<Value>,
+        ttl_ms: Option<u64>,
+    ) -> Result<()> {
+        match (cf, value) {
+            (Some(cf), value) => self.engine.write_cf(cf, key, value),
+            (None, Some(value)) => self.engine.write_put(key, value, ttl_ms),
+            (None, None) => self.engine.write_delete(key),
+        }
+    }
+
+    /// Make the applied writes durable, then record how far they go.
+    fn save(&self, state: &FollowerState, path: &Path) -> Result<()> {
+        if !self.engine.config().sync_writes {
+            self.engine.sync_wal()?;
+        }
+        state.save(&self.engine.config().env, path)?;
+        self.applied.store(state.applied, Ordering::Release);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put(sequence: u64, key: &[u8]) -> ChangeRecord {
+        ChangeRecord {
+            sequence,
+            op: ChangeOp::Put {
+                key: key.to_vec(),
+                value: vec![0; 100],
+                ttl_ms: None,
+            },
+        }
+    }
+
+    #[test]
+    fn test_backlog_read_and_eviction() {
+        let backlog = Backlog::new(600);
+        backlog.enable(10);
+        let (epoch, last) = backlog.position();
+        assert_eq!(last, 10);
+        assert!(backlog.covers(epoch, 11));
+        assert!(!backlog.covers(epoch, 10));
+        assert!(matches!(
+            backlog.read(epoch, 11, 1024, Duration::ZERO),
+            Fetch::Idle
+        ));
+
+        for sequence in 11..=15 {
+            backlog.push(put(sequence, b"k"));
+        }
+        match backlog.read(epoch, 12, 250, Duration::ZERO) {
+            Fetch::Records(records) => {
+                let sequences: Vec<u64> = records.iter().map(|r| r.sequence).collect();
+                assert_eq!(sequences, vec![12, 13]);
+            }
+            _ => panic!("expected records"),
+        }
+
+        // 600 bytes hold five records; the sixth evicts the first
+        backlog.push(put(16, b"k"));
+        assert!(!backlog.covers(epoch, 11));
+        assert!(backlog.covers(epoch, 12));
+        assert!(matches!(
+            backlog.read(epoch, 11, 1024, Duration::ZERO),
+            Fetch::Lost
+        ));
+
+        backlog.reset(16);
+        assert!(!backlog.covers(epoch, 17));
+        assert!(matches!(
+            backlog.read(epoch, 17, 1024, Duration::ZERO),
+            Fetch::Lost
+        ));
+    }
+
+    #[test]
+    fn test_follower_state_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(STATE_FILE);
+        let env = Env::default();
+        assert_eq!(
+            FollowerState::load(&env, &path).unwrap(),
+            FollowerState::default()
+        );
+        let state = FollowerState {
+            epoch: 7,
+            applied: 42,
+        };
+        state.save(&env, &path).unwrap();
+        assert_eq!(FollowerState::load(&env, &path).unwrap(), state);
+    }
+}
@@ -0,0 +1,328 @@
+//! OBLIVION - Ribbon Filter
+//! A static, space-efficient alternative to the Bloom filter.
+//!
+//! A Ribbon filter answers the same "definitely not / probably yes"
+//! question as a Bloom filter, but stores a solution to a banded linear
+//! system over GF(2) instead of a plain bit array. For the same false
+//! positive rate it needs roughly 25-30% less memory, at the cost of a
+//! slightly more expensive (one-shot) construction.
+
+use super::filter::{hash64, mix64};
+
+/// Width of the coefficient band, in bits.
+const RIBBON_WIDTH: usize = 64;
+
+/// Relative slot overhead over the number of keys.
+/// Keeps the probability of a failed construction negligible.
+pub(crate) const SLOT_OVERHEAD: f64 = 0.08;
+
+/// Number of seeds tried before growing the slot count.
+const MAX_SEEDS: u64 = 16;
+
+/// Serialized header size: num_slots (8) + result_bits (1) + seed (8) + count (8).
+const HEADER_LEN: usize = 25;
+
+/// A Ribbon filter for probabilistic set membership testing.
+///
+/// ## How it works
+/// - Each key hashes to a start slot `s`, a 64-bit coefficient row `c`
+///   and an `r`-bit fingerprint `b`
+/// - Construction solves `XOR(z[s + j] for each set bit j of c) == b`
+///   for every key, using on-the-fly Gaussian elimination
+/// - On lookup the same XOR is recomputed and compared to `b`
+///
+/// ## False Positive Rate
+/// With `r` result bits per slot: `FPR ≈ 2^-r`
+/// Memory: `r * (1 + overhead)` bits per key.
+///
+/// Unlike [`BloomFilter`](super::bloom::BloomFilter), a Ribbon filter is
+/// built once from the full key set and cannot be inserted into afterwards,
+/// which fits immutable SSTables perfectly.
+#[derive(Debug, Clone)]
+pub struct RibbonFilter {
+    /// Solution matrix stored column-wise: `result_bits` columns of `num_slots` bits.
+    solution: Vec<u64>,
+    /// Number of 64-bit words per column.
+    words_per_column: usize,
+    /// Number of slots (rows) in the solution.
+    num_slots: usize,
+    /// Number of fingerprint bits per key.
+    result_bits: u32,
+    /// Hash seed that produced a solvable system.
+    seed: u64,
+    /// Number of keys the filter was built from.
+    count: usize,
+}
+
+impl RibbonFilter {
+    /// Build a Ribbon filter over `keys` targeting `false_positive_rate`.
+    pub fn build(keys: &[&[u8]], false_positive_rate: f64) -> Self {
+        let fp_rate = false_positive_rate.clamp(0.0001, 0.5);
+        let result_bits = (1.0 / fp_rate).log2().ceil().clamp(1.0, 16.0) as u32;
+
+        let mut num_slots =
+            ((keys.len() as f64 * (1.0 + SLOT_OVERHEAD)).ceil() as usize + RIBBON_WIDTH).max(128);
+
+        loop {
+            for seed in 0..MAX_SEEDS {
+                if let Some(filter) = Self::try_build(keys, num_slots, result_bits, seed) {
+                    return filter;
+                }
+            }
+            // Extremely unlikely: grow the system and try again
+            num_slots += num_slots / 10 + RIBBON_WIDTH;
+        }
+    }
+
+    /// Attempt construction with a fixed slot count and seed.
+    /// Returns `None` if the banded system is inconsistent.
+    fn try_build(keys: &[&[u8]], num_slots: usize, result_bits: u32, seed: u64) -> Option<Self> {
+        let num_starts = num_slots - RIBBON_WIDTH + 1;
+        let mut coeff_rows = vec![0u64; num_slots];
+        let mut result_rows = vec![0u32; num_slots];
+
+        // Banding: on-the-fly Gaussian elimination
+        for key in keys {
+            let (mut start, mut coeff, mut result) =
+                Self::hash_key(key, seed, num_starts, result_bits);
+            loop {
+                if coeff_rows[start] == 0 {
+                    coeff_rows[start] = coeff;
+                    result_rows[start] = result;
+                    break;
+                }
+                coeff ^= coeff_rows[start];
+                result ^= result_rows[start];
+                if coeff == 0 {
+                    if result != 0 {
+                        return None;
+                    }
+                    break; // Redundant equation (e.g. duplicate key)
+                }
+                let shift = coeff.trailing_zeros();
+                start += shift as usize;
+                coeff >>= shift;
+            }
+        }
+
+        // Back-substitution into column-wise storage
+        let words_per_column = num_slots.div_ceil(64);
+        let mut solution = vec![0u64; words_per_column * result_bits as usize];
+        for row in (0..num_slots).rev() {
+            let coeff = coeff_rows[row];
+            let filler = mix64(seed ^ row as u64);
+            for bit in 0..result_bits as usize {
+                let column = &solution[bit * words_per_column..(bit + 1) * words_per_column];
+                let value = if coeff == 0 {
+                    // Free variable: any value works, pseudo-random keeps FPR uniform
+                    (filler >> bit) & 1
+                } else {
+                    let parity = (coeff & window(column, row)).count_ones() as u64 & 1;
+                    ((result_rows[row] as u64 >> bit) & 1) ^ parity
+                };
+                solution[bit * words_per_column + row / 64] |= value << (row % 64);
+            }
+        }
+
+        Some(Self {
+            solution,
+            words_per_column,
+            num_slots,
+            result_bits,
+            seed,
+            count: keys.len(),
+        })
+    }
+
+    /// Check if a key **may** be in the set.
+    /// - Returns `false` → key is **definitely not** in the set
+    /// - Returns `true` → key is **probably** in the set (may be false positive)
+    pub fn may_contain(&self, key: &[u8]) -> bool {
+        let num_starts = self.num_slots - RIBBON_WIDTH + 1;
+        let (start, coeff, result) = Self::hash_key(key, self.seed, num_starts, self.result_bits);
+        (0..self.result_bits as usize).all(|bit| {
+            let column =
+                &self.solution[bit * self.words_per_column..(bit + 1) * self.words_per_column];
+            let parity = (coeff & window(column, start)).count_ones() & 1;
+            parity == (result >> bit) & 1
+        })
+    }
+
+    /// Returns the number of keys the filter was built from.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Returns the number of slots in the solution.
+    pub fn num_slots(&self) -> usize {
+        self.num_slots
+    }
+
+    /// Returns the number of fingerprint bits per slot.
+    pub fn result_bits(&self) -> u32 {
+        self.result_bits
+    }
+
+    /// Returns the approximate memory usage in bytes.
+    pub fn memory_usage(&self) -> usize {
+        self.solution.len() * 8
+    }
+
+    /// Estimated false positive rate (`2^-r`).
+    pub fn estimated_fpr(&self) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        0.5f64.powi(self.result_bits as i32)
+    }
+
+    /// Serialize the filter into a self-describing byte buffer.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(HEADER_LEN + self.solution.len() * 8);
+        buf.extend_from_slice(&(self.num_slots as u64).to_le_bytes());
+        buf.push(self.result_bits as u8);
+        buf.extend_from_slice(&self.seed.to_le_bytes());
+        buf.extend_from_slice(&(self.count as u64).to_le_bytes());
+        for word in &self.solution {
+            buf.extend_from_slice(&word.to_le_bytes());
+        }
+        buf
+    }
+
+    /// Deserialize a filter produced by [`RibbonFilter::to_bytes`].
+    /// Returns `None` if the buffer is truncated or inconsistent.
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        if data.len() < HEADER_LEN {
+            return None;
+        }
+        let num_slots = u64::from_le_bytes(data[0..8].try_into().ok()?) as usize;
+        let result_bits = data[8] as u32;
+        let seed = u64::from_le_bytes(data[9..17].try_into().ok()?);
+        let count = u64::from_le_bytes(data[17..25].try_into().ok()?) as usize;
+
+        if num_slots < RIBBON_WIDTH || !(1..=16).contains(&result_bits) {
+            return None;
+        }
+        let words_per_column = num_slots.div_ceil(64);
+        let body = &data[HEADER_LEN..];
+        if body.len() != words_per_column * result_bits as usize * 8 {
+            return None;
+        }
+        let solution = body
+            .chunks_exact(8)
+            .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+
+        Some(Self {
+            solution,
+            words_per_column,
+            num_slots,
+            result_bits,
+            seed,
+            count,
+        })
+    }
+
+    /// Derive the start slot, coefficient row and fingerprint for a key.
+    fn hash_key(key: &[u8], seed: u64, num_starts: usize, result_bits: u32) -> (usize, u64, u32) {
+        let h = hash64(key, seed);
+        let start = ((h as u128 * num_starts as u128) >> 64) as usize;
+        let coeff = mix64(h ^ 0x9E37_79B9_7F4A_7C15) | 1;
+        let result = (mix64(h ^ 0xC2B2_AE3D_27D4_EB4F) as u32) & ((1u32 << result_bits) - 1);
+        (start, coeff, result)
+    }
+}
+
+/// Read 64 bits of a column starting at bit position `start`.
+/// Bits past the end of the column read as zero.
+fn window(column: &[u64], start: usize) -> u64 {
+    let word = start / 64;
+    let offset = start % 64;
+    let low = column[word] >> offset;
+    if offset == 0 {
+        low
+    } else {
+        low | (column.get(word + 1).copied().unwrap_or(0) << (64 - offset))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::bloom::BloomFilter;
+
+    fn make_keys(prefix: &str, n: usize) -> Vec<Vec<u8>> {
+        (0..n)
+            .map(|i| format!("{}_{}", prefix, i).into_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn test_no_false_negatives() {
+        let keys = make_keys("key", 5000);
+        let refs: Vec<&[u8]> = keys.iter().map(|k| k.as_slice()).collect();
+        let filter = RibbonFilter::build(&refs, 0.01);
+
+        for key in &keys {
+            assert!(filter.may_contain(key), "False negative for key: {:?}", key);
+        }
+        assert_eq!(filter.count(), 5000);
+    }
+
+    #[test]
+    fn test_false_positive_rate() {
+        let keys = make_keys("key", 5000);
+        let refs: Vec<&[u8]> = keys.iter().map(|k| k.as_slice()).collect();
+        let filter = RibbonFilter::build(&refs, 0.01);
+
+        let false_positives = make_keys("missing", 10_000)
+            .iter()
+            .filter(|k| filter.may_contain(k))
+            .count();
+
+        // 7 result bits => FPR ≈ 0.78%, expect ~78 out of 10,000
+        assert!(
+            false_positives < 200,
+            "Too many false positives: {}",
+            false_positives
+        );
+    }
+
+    #[test]
+    fn test_smaller_than_bloom() {
+        let keys = make_keys("key", 10_000);
+        let refs: Vec<&[u8]> = keys.iter().map(|k| k.as_slice()).collect();
+        let ribbon = RibbonFilter::build(&refs, 0.01);
+        let bloom = BloomFilter::new(10_000, 0.01);
+
+        assert!(ribbon.memory_usage() < bloom.memory_usage());
+    }
+
+    #[test]
+    fn test_empty_and_duplicate_keys() {
+        let empty = RibbonFilter::build(&[], 0.01);
+        assert_eq!(empty.count(), 0);
+        assert_eq!(empty.estimated_fpr(), 0.0);
+
+        let dupes: Vec<&[u8]> = vec![b"same", b"same", b"other"];
+        let filter = RibbonFilter::build(&dupes, 0.01);
+        assert!(filter.may_contain(b"same"));
+        assert!(filter.may_contain(b"other"));
+    }
+
+    #[test]
+    fn test_bytes_roundtrip() {
+        let keys = make_keys("k", 300);
+        let refs: Vec<&[u8]> = keys.iter().map(|k| k.as_slice()).collect();
+        let filter = RibbonFilter::build(&refs, 0.02);
+
+        let restored = RibbonFilter::from_bytes(&filter.to_bytes()).unwrap();
+        assert_eq!(restored.num_slots(), filter.num_slots());
+        assert_eq!(restored.result_bits(), filter.result_bits());
+        for key in &keys {
+            assert!(restored.may_contain(key));
+        }
+
+        assert!(RibbonFilter::from_bytes(&[1, 2, 3]).is_none());
+    }
+}
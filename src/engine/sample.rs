@@ -0,0 +1,225 @@
+//! OBLIVION - Key Sampling
+//! Keys picked at random from the live keyspace without scanning it,
+//! for key-distribution dashboards and choosing shard split points.
+//!
+//! ## Sampling
+//! Each sample picks a source (the MemTable, a frozen MemTable or an
+//! SSTable) with a probability proportional to the entries it holds
+//! within the bounds, then an entry of that source:
+//!
+//! - MemTables: an entry at random.
+//! - SSTables: a data block at random from the index block, then a
+//!   record of that block. Blocks hold about the same number of bytes,
+//!   so only one data block is read per sample.
+//!
+//! A picked key is kept if it is live in the snapshot read; tombstones,
+//! deleted and expired keys are skipped and another pick is made, up to
+//! [`ATTEMPTS_PER_KEY`] picks per requested key.
+//!
+//! The result is approximately uniform: a key with older versions in
+//! several sources is a little more likely to be picked, and keys in
+//! blocks with smaller records a little less. Keys are picked with
+//! replacement, so a sample may hold a key more than once.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::SystemTime;
+
+use crate::error::Result;
+use crate::types::Key;
+
+use super::comparator::Comparator;
+use super::options::ReadOptions;
+use super::sstable::{BlockHandle, SSTable};
+use super::version::ReadState;
+
+/// Picks made per requested key before giving up on finding it.
+pub const ATTEMPTS_PER_KEY: usize = 8;
+
+/// Where a pick is made from.
+enum Source<'a> {
+    /// The keys of a MemTable within the bounds, tombstones included.
+    MemTable(Vec<&'a Key>),
+    /// The data blocks of an SSTable overlapping the bounds, and the
+    /// entries they hold, estimated from the share of its blocks.
+    Table {
+        table: &'a SSTable,
+        blocks: Vec<BlockHandle>,
+        entries: u64,
+    },
+}
+
+/// Up to `count` live keys within the bounds of `opts` (and in its
+/// snapshot, or one taken now), picked at random.
+pub(crate) fn random_keys(state: &ReadState, count: usize, opts: &ReadOptions) -> Result<Vec<Key>> {
+    let mut opts = opts.clone().fill_cache(false);
+    let snapshot = opts
+        .snapshot
+        .get_or_insert_with(|| state.snapshot())
+        .clone();
+    let bounds = Bounds {
+        lower: opts.lower_bound.as_deref(),
+        upper: opts.upper_bound.as_deref(),
+        comparator: &state.comparator,
+    };
+
+    let version = snapshot.version();
+    let mut sources = vec![Source::MemTable(
+        snapshot
+            .memtable()
+            .keys()
+            .filter(|key| bounds.contains(key))
+            .collect(),
+    )];
+    for frozen in version.frozen() {
+        sources.push(Source::MemTable(
+            frozen
+                .entries()
+                .keys()
+                .filter(|key| bounds.contains(key))
+                .collect(),
+        ));
+    }
+    for table in version.sstables() {
+        if !table.overlaps(bounds.lower, bounds.upper) {
+            continue;
+        }
+        let index = table.block_index()?;
+        // Block `i` holds the keys after the last key of block `i - 1`
+        // up to its own last key
+        let blocks: Vec<BlockHandle> = index
+            .iter()
+            .enumerate()
+            .filter(|(i, (last_key, _))| {
+                bounds
+                    .lower
+                    .is_none_or(|lower| bounds.comparator.compare(last_key, lower).is_ge())
+                    && bounds.upper.is_none_or(|upper| {
+                        *i == 0 || bounds.comparator.compare(&index[i - 1].0, upper).is_lt()
+                    })
+            })
+            .map(|(_, (_, handle))| *handle)
+            .collect();
+        if !blocks.is_empty() {
+            let entries = (table.entry_count() * blocks.len()).div_ceil(index.len()) as u64;
+            sources.push(Source::Table {
+                table,
+                blocks,
+                entries,
+            });
+        }
+    }
+    let weights: Vec<u64> = sources
+        .iter()
+        .map(|source| match source {
+            Source::MemTable(keys) => keys.len() as u64,
+            Source::Table { entries, .. } => *entries,
+        })
+        .collect();
+    let total: u64 = weights.iter().sum();
+    if total == 0 {
+        return Ok(Vec::new());
+    }
+
+    let mut rng = Rng::new();
+    let mut keys = Vec::with_capacity(count);
+    for _ in 0..count.saturating_mul(ATTEMPTS_PER_KEY) {
+        if keys.len() == count {
+            break;
+        }
+        let mut pick = rng.below(total);
+        let position = weights
+            .iter()
+            .position(|&weight| {
+                if pick < weight {
+                    return true;
+                }
+                pick -= weight;
+                false
+            })
+            .unwrap_or(0);
+        let candidate = match &sources[position] {
+            Source::MemTable(entries) => entries[rng.below(entries.len() as u64) as usize].clone(),
+            Source::Table { table, blocks, .. } => {
+                let block = blocks[rng.below(blocks.len() as u64) as usize];
+                let records: Vec<Key> = table
+                    .read_block_records(block, opts.verify_checksums)?
+                    .into_iter()
+                    .filter(|(key, value)| value.is_some() && bounds.contains(key))
+                    .map(|(key, _)| key)
+                    .collect();
+                if records.is_empty() {
+                    continue;
+                }
+                records[rng.below(records.len() as u64) as usize].clone()
+            }
+        };
+        if state.value_len(&candidate, &opts)?.is_some() {
+            keys.push(candidate);
+        }
+    }
+    Ok(keys)
+}
+
+/// The bounds of a read.
+struct Bounds<'a> {
+    lower: Option<&'a [u8]>,
+    upper: Option<&'a [u8]>,
+    comparator: &'a Comparator,
+}
+
+impl Bounds<'_> {
+    fn contains(&self, key: &[u8]) -> bool {
+        self.lower
+            .is_none_or(|lower| self.comparator.compare(key, lower).is_ge())
+            && self
+                .upper
+                .is_none_or(|upper| self.comparator.compare(key, upper).is_lt())
+    }
+}
+
+/// A SplitMix64 generator seeded from the process' hash randomness and
+/// the clock; good enough for sampling, not for anything secret.
+struct Rng(u64);
+
+impl Rng {
+    fn new() -> Self {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |d| d.as_nanos()),
+        );
+        Self(hasher.finish())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..n`, for `n > 0`.
+    fn below(&mut self, n: u64) -> u64 {
+        // Multiply-shift maps the 64 random bits onto the range with a
+        // bias far below what sampling can notice
+        ((self.next_u64() as u128 * n as u128) >> 64) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rng_covers_range() {
+        let mut rng = Rng::new();
+        let mut seen = [0u32; 10];
+        for _ in 0..10_000 {
+            seen[rng.below(10) as usize] += 1;
+        }
+        assert!(seen.iter().all(|&n| (800..1200).contains(&n)), "{:?}", seen);
+    }
+}
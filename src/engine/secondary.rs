@@ -0,0 +1,312 @@
+//! OBLIVION - Secondary Instance
+//! A read-only view of a data directory owned by another (primary)
+//! engine, kept current by tailing its WAL and manifest.
+//!
+//! ## Catching Up
+//! A secondary never writes to the directory and does not take its
+//! `LOCK`. [`Secondary::try_catch_up`] replays the primary's unflushed
+//! WAL segments and live log into a MemTable and reopens the SSTable set
+//! listed in the `MANIFEST`, then publishes both as one version. Only
+//! changed logs are replayed; tables already open are reused.
+//!
+//! The primary keeps working meanwhile: it may rotate its log, flush a
+//! segment or compact tables between the reads. The logs are read
+//! before the manifest, so a concurrent flush only duplicates rows; a
+//! rotation is detected by listing the segments again, and a table
+//! deleted before it could be opened both cause the pass to be retried.
+//!
+//! ## Staleness
+//! Reads see what the primary had written to disk as of the last catch
+//! up: writes still in its WAL buffer (`sync_writes` off) and a record
+//! being appended are picked up on a later pass. TTLs are held in the
+//! primary's memory and do not apply here. A table the primary deletes
+//! after a catch up stays readable while its file is open; reads that
+//! need to reopen it fail until the next catch up.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use arc_swap::{ArcSwap, ArcSwapOption};
+use parking_lot::{Mutex, RwLock};
+
+use crate::config::Config;
+use crate::error::{OblivionError, Result};
+use crate::types::{Key, Value};
+
+use super::cache::TableCache;
+use super::manifest::Manifest;
+use super::memtable::MemTable;
+use super::metrics::EngineMetrics;
+use super::options::ReadOptions;
+use super::snapshot::Snapshot;
+use super::sstable::SSTable;
+use super::ttl::TtlIndex;
+use super::version::{ReadState, Version};
+use super::wal::WriteAheadLog;
+use super::Oblivion;
+
+/// Passes a catch up may take while the primary keeps changing the
+/// directory underneath it.
+const CATCH_UP_ATTEMPTS: usize = 16;
+
+/// Open SSTables and their file numbers, in manifest order.
+type Tables = Vec<(u64, Arc<SSTable>)>;
+
+/// What the current version was built from.
+#[derive(Default)]
+struct Tail {
+    /// Logs replayed into the MemTable, with their sizes at the time.
+    logs: Vec<(PathBuf, u64)>,
+    /// Open SSTables.
+    tables: Tables,
+}
+
+/// Read-only instance following a primary engine's data directory.
+///
+/// ## Example
+/// ```no_run
+/// use oblivion::{config::Config, engine::secondary::Secondary};
+///
+/// let secondary = Secondary::open(Config::new("./oblivion_data")).unwrap();
+/// loop {
+///     secondary.try_catch_up().unwrap();
+///     println!("{} keys", secondary.scan().len());
+///     std::thread::sleep(std::time::Duration::from_secs(5));
+/// }
+/// ```
+pub struct Secondary {
+    config: Config,
+    /// The replayed WAL as a single frozen MemTable, plus the SSTables.
+    state: Arc<ReadState>,
+    table_cache: Arc<TableCache>,
+    /// Held for a whole catch up, so concurrent calls do not interleave.
+    tail: Mutex<Tail>,
+}
+
+impl Secondary {
+    /// Open the database at `config.data_dir` read-only and catch up
+    /// with its primary once. Fails if no database exists there, or if
+    /// it is not on the local file system.
+    pub fn open(config: Config) -> Result<Self> {
+        config.validate()?;
+        config.env.require_local("secondary instances")?;
+        if !config.manifest_path().exists() {
+            return Err(OblivionError::DatabaseNotFound(config.data_dir.clone()));
+        }
+        let metrics = Arc::new(EngineMetrics::new());
+        let table_cache = Arc::new(
+            TableCache::new(config.max_open_files)
+                .with_metrics(Arc::clone(&metrics))
+                .with_direct_io(config.use_direct_io)
+                .with_comparator(config.comparator.clone())
+                .with_paranoid_checks(config.paranoid_checks)
+                .with_block_cache(config.block_cache_capacity),
+        );
+        let state = Arc::new(ReadState {
+            memtable: RwLock::new(MemTable::new()),
+            version: ArcSwap::from_pointee(Version::new(Vec::new(), Vec::new())),
+            ttl_index: RwLock::new(TtlIndex::new()),
+            row_cache: ArcSwapOption::empty(),
+            metrics,
+            snapshot_pins: Arc::new(()),
+            comparator: config.comparator.clone(),
+        });
+        let secondary = Self {
+            config,
+            state,
+            table_cache,
+            tail: Mutex::new(Tail::default()),
+        };
+        secondary.try_catch_up()?;
+        log::info!(
+            "Oblivion secondary opened at {:?} ({} SSTables)",
+            secondary.config.data_dir,
+            secondary.sstable_count()
+        );
+        Ok(secondary)
+    }
+
+    /// Pick up the primary's writes, flushes and compactions since the
+    /// last call. Returns whether anything changed.
+    pub fn try_catch_up(&self) -> Result<bool> {
+        let mut tail = self.tail.lock();
+        let wal_dir = self.config.wal_dir();
+        for _ in 0..CATCH_UP_ATTEMPTS {
+            let segments = WriteAheadLog::segments(&wal_dir)?;
+            let mut logs = Vec::with_capacity(segments.len() + 1);
+            for path in segments
+                .iter()
+                .map(|(_, path)| path)
+                .chain([&self.config.wal_path()])
+            {
+                match std::fs::metadata(path) {
+                    Ok(metadata) => logs.push((path.clone(), metadata.len())),
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            let memtable = if logs == tail.logs {
+                None
+            } else {
+                let paths: Vec<PathBuf> = logs.iter().map(|(path, _)| path.clone()).collect();
+                Some(WriteAheadLog::recover_all(&paths, |_| {})?.0)
+            };
+
+            let manifest = Manifest::load(&self.config.env, &self.config.manifest_path())?
+                .ok_or_else(|| OblivionError::DatabaseNotFound(self.config.data_dir.clone()))?;
+            manifest.check_comparator(&self.config.comparator)?;
+            // A rotation since the listing may have moved writes we did
+            // not replay into a segment, or already into a table
+            if WriteAheadLog::segments(&wal_dir)?
+                .iter()
+                .any(|segment| !segments.contains(segment))
+            {
+                continue;
+            }
+            let Some(tables) = self.open_tables(&tail, &manifest)? else {
+                continue;
+            };
+
+            let ids = |tables: &Tables| -> Vec<u64> { tables.iter().map(|(id, _)| *id).collect() };
+            if memtable.is_none() && ids(&tables) == ids(&tail.tables) {
+                return Ok(false);
+            }
+            let version = self.state.current();
+            let frozen = match memtable {
+                Some(memtable) => vec![Arc::new(memtable)],
+                None => version.frozen().to_vec(),
+            };
+            for id in ids(&tail.tables)
+                .iter()
+                .filter(|id| !manifest.tables.contains(id))
+            {
+                self.table_cache
+                    .evict(&Oblivion::sstable_path(&self.config, *id));
+            }
+            self.state.publish(
+                frozen,
+                tables.iter().map(|(_, table)| Arc::clone(table)).collect(),
+            );
+            *tail = Tail { logs, tables };
+            return Ok(true);
+        }
+        Err(OblivionError::RecoveryFailed(format!(
+            "secondary could not catch up with the primary after {} attempts",
+            CATCH_UP_ATTEMPTS
+        )))
+    }
+
+    /// Open the tables `manifest` lists, reusing those already open.
+    /// Returns `None` if the primary deleted one in the meantime.
+    fn open_tables(&self, tail: &Tail, manifest: &Manifest) -> Result<Option<Tables>> {
+        let mut tables = Vec::with_capacity(manifest.tables.len());
+        for &id in &manifest.tables {
+            let table = match tail.tables.iter().find(|(open, _)| *open == id) {
+                Some((_, table)) => Arc::clone(table),
+                None => {
+                    let path = Oblivion::sstable_path(&self.config, id);
+                    match SSTable::open(path, &self.table_cache) {
+                        Ok(table) => Arc::new(table),
+                        Err(OblivionError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+                            return Ok(None)
+                        }
+                        Err(e) => return Err(e),
+                    }
+                }
+            };
+            tables.push((id, table));
+        }
+        Ok(Some(tables))
+    }
+
+    /// Get a value by key as of the last catch up.
+    pub fn get(&self, key: impl AsRef<[u8]>) -> Option<Value> {
+        let key = key.as_ref();
+        self.get_opt(key, &ReadOptions::default())
+            .unwrap_or_else(|e| {
+                log::error!("Read failed for key {:?}: {}", key, e);
+                None
+            })
+    }
+
+    /// Get a value by key with per-read options.
+    pub fn get_opt(&self, key: impl AsRef<[u8]>, opts: &ReadOptions) -> Result<Option<Value>> {
+        self.state.get_opt(key.as_ref(), opts)
+    }
+
+    /// Scan all live key-value pairs in sorted order.
+    pub fn scan(&self) -> Vec<(Key, Value)> {
+        self.scan_opt(&ReadOptions::default()).unwrap_or_else(|e| {
+            log::error!("Scan failed: {}", e);
+            Vec::new()
+        })
+    }
+
+    /// Scan key-value pairs in sorted order with per-read options.
+    pub fn scan_opt(&self, opts: &ReadOptions) -> Result<Vec<(Key, Value)>> {
+        self.state.scan_opt(opts)
+    }
+
+    /// Take a point-in-time view that later catch ups do not change.
+    pub fn snapshot(&self) -> Snapshot {
+        self.state.snapshot()
+    }
+
+    /// Returns the number of SSTables in the current version.
+    pub fn sstable_count(&self) -> usize {
+        self.state.current().sstables().len()
+    }
+
+    /// Returns the configuration this instance was opened with.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(dir: &std::path::Path) -> Config {
+        Config::builder(dir)
+            .memtable_max_size(1024)
+            .sync_writes(true)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_secondary_follows_primary() {
+        let dir = tempfile::tempdir().unwrap();
+        let primary = Oblivion::open(config(dir.path())).unwrap();
+        primary.put(b"a".to_vec(), b"1".to_vec()).unwrap();
+
+        let secondary = Secondary::open(config(dir.path())).unwrap();
+        assert_eq!(secondary.get(b"a"), Some(b"1".to_vec()));
+        assert!(!secondary.try_catch_up().unwrap());
+
+        primary.put(b"b".to_vec(), b"2".to_vec()).unwrap();
+        primary.delete(b"a".to_vec()).unwrap();
+        assert_eq!(secondary.get(b"b"), None);
+        assert!(secondary.try_catch_up().unwrap());
+        assert_eq!(secondary.get(b"a"), None);
+        assert_eq!(secondary.get(b"b"), Some(b"2".to_vec()));
+
+        // Flushes and compactions replace the table set
+        for i in 0..300 {
+            let key = format!("key_{:04}", i).into_bytes();
+            primary.put(key, vec![b'v'; 20]).unwrap();
+        }
+        assert!(secondary.try_catch_up().unwrap());
+        assert_eq!(secondary.sstable_count(), primary.sstable_count());
+        assert_eq!(secondary.scan(), primary.scan());
+    }
+
+    #[test]
+    fn test_secondary_requires_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = Secondary::open(config(dir.path())).err().unwrap();
+        assert!(matches!(err, OblivionError::DatabaseNotFound(_)));
+        assert!(!dir.path().join("LOCK").exists());
+    }
+}
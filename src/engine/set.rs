@@ -0,0 +1,122 @@
+//! OBLIVION - Sets
+//! Unordered collections of distinct members under one key, as Redis'
+//! sets, for membership tests without loading the whole collection.
+//!
+//! Each member is an empty entry in the database's data-type range (see
+//! [`DATA_TYPE_PREFIX`](super::database::DATA_TYPE_PREFIX)):
+//!
+//! ```text
+//! \xff\xffts <encode(&(key, member))>
+//! ```
+//!
+//! so `sismember` is one point lookup and `smembers` a prefix scan,
+//! returning members in byte order. `sadd` and `srem` apply all their
+//! members in one [`WriteBatch`]; as with [hashes](super::hash), the
+//! counts they return are read just before it. Sets are not plain keys
+//! and are never seen by `get`, `keys` or scans of a [`Database`].
+
+use std::collections::BTreeSet;
+
+use crate::engine::batch::WriteBatch;
+use crate::engine::database::Database;
+use crate::engine::keys::{decode, encode};
+use crate::engine::options::ReadOptions;
+use crate::error::Result;
+use crate::types::Key;
+
+/// First bytes of every set member, within a database.
+pub const SET_PREFIX: &[u8] = b"\xff\xffts";
+
+impl Database<'_> {
+    /// Add `members` to the set `key` in one batch, returning how many
+    /// were not members yet.
+    pub fn sadd(&self, key: &[u8], members: &[Vec<u8>]) -> Result<usize> {
+        let mut batch = WriteBatch::new();
+        let mut added = 0;
+        for member in members.iter().collect::<BTreeSet<_>>() {
+            let stored = self.stored(&member_key(key, member));
+            if self.get_stored(&stored)?.is_none() {
+                added += 1;
+                batch.put(stored, Vec::new());
+            }
+        }
+        self.engine().write(batch)?;
+        Ok(added)
+    }
+
+    /// Remove `members` from the set `key` in one batch, returning how
+    /// many were members.
+    pub fn srem(&self, key: &[u8], members: &[Vec<u8>]) -> Result<usize> {
+        let mut batch = WriteBatch::new();
+        let mut removed = 0;
+        for member in members.iter().collect::<BTreeSet<_>>() {
+            let stored = self.stored(&member_key(key, member));
+            if self.get_stored(&stored)?.is_some() {
+                removed += 1;
+                batch.delete(stored);
+            }
+        }
+        self.engine().write(batch)?;
+        Ok(removed)
+    }
+
+    /// Returns whether `member` is in the set `key`.
+    pub fn sismember(&self, key: &[u8], member: &[u8]) -> Result<bool> {
+        let stored = self.stored(&member_key(key, member));
+        Ok(self.get_stored(&stored)?.is_some())
+    }
+
+    /// Returns the members of the set `key` in byte order; empty if the
+    /// set does not exist.
+    pub fn smembers(&self, key: &[u8]) -> Result<Vec<Vec<u8>>> {
+        let prefix = self.stored(&[SET_PREFIX, &encode(key)].concat());
+        let rows = self
+            .engine()
+            .scan_opt(&ReadOptions::new().prefix(&prefix))?;
+        rows.into_iter()
+            .map(|(stored, _)| decode(&stored[prefix.len()..]))
+            .collect()
+    }
+}
+
+/// The key `member` of the set `key` is stored under, within a database.
+fn member_key(key: &[u8], member: &[u8]) -> Key {
+    [SET_PREFIX, &encode(&(key, member))].concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::engine::database::DATA_TYPE_PREFIX;
+    use crate::engine::Oblivion;
+
+    fn members(names: &[&str]) -> Vec<Vec<u8>> {
+        names.iter().map(|name| name.as_bytes().to_vec()).collect()
+    }
+
+    #[test]
+    fn test_set_membership() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = Oblivion::open(Config::new(dir.path())).unwrap();
+        let db = engine.database(0).unwrap();
+        assert!(SET_PREFIX.starts_with(DATA_TYPE_PREFIX));
+
+        assert_eq!(
+            db.sadd(b"tags", &members(&["rust", "db", "rust"])).unwrap(),
+            2
+        );
+        assert_eq!(db.sadd(b"tags", &members(&["db", "lsm"])).unwrap(), 1);
+        assert!(db.sismember(b"tags", b"lsm").unwrap());
+        assert!(!db.sismember(b"tags", b"go").unwrap());
+        assert_eq!(
+            db.smembers(b"tags").unwrap(),
+            members(&["db", "lsm", "rust"])
+        );
+
+        assert_eq!(db.srem(b"tags", &members(&["db", "go"])).unwrap(), 1);
+        assert_eq!(db.smembers(b"tags").unwrap(), members(&["lsm", "rust"]));
+        assert!(db.smembers(b"other").unwrap().is_empty());
+        assert!(db.keys(b"*").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,355 @@
+//! OBLIVION - Sharded Engine
+//! Partitions the keyspace over `shard_count` independent engines, each
+//! with its own WAL, MemTable, SSTables and write lock.
+//!
+//! ## Routing
+//! A key lives in shard `crc32(key) % shard_count`. The hash is stable
+//! across processes and releases, so the shard count is recorded in
+//! `data_dir/SHARDS` when the database is created and reopening it with
+//! a different count is rejected.
+//!
+//! ## Use Case
+//! `ConcurrentOblivion` serializes every write on one lock; on multi-core
+//! servers with write-heavy traffic, writes to different shards proceed
+//! in parallel. Scans visit every shard and merge the results.
+
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::error::{OblivionError, Result};
+use crate::types::{Key, Value};
+
+use super::comparator::Comparator;
+use super::concurrent::ConcurrentOblivion;
+use super::io::SyncMethod;
+use super::options::ReadOptions;
+use super::predicate::Predicate;
+
+/// File in `data_dir` recording the shard count.
+const SHARDS_FILE_NAME: &str = "SHARDS";
+
+/// Thread-safe engine partitioned by key hash over several shards.
+///
+/// ## Example
+/// ```no_run
+/// use oblivion::config::Config;
+/// use oblivion::engine::sharded::ShardedOblivion;
+///
+/// let config = Config::builder("./data").shard_count(8).build().unwrap();
+/// let engine = ShardedOblivion::open(config).unwrap();
+///
+/// engine.put(b"key".to_vec(), b"value".to_vec()).unwrap();
+/// assert_eq!(engine.get(b"key"), Some(b"value".to_vec()));
+/// ```
+#[derive(Clone)]
+pub struct ShardedOblivion {
+    shards: Arc<[ConcurrentOblivion]>,
+    /// Order merged scans return keys in.
+    comparator: Comparator,
+}
+
+impl ShardedOblivion {
+    /// Open or create a sharded database with `config.shard_count` shards.
+    ///
+    /// Every shard is opened with `config`, rooted at its own directory.
+    pub fn open(config: Config) -> Result<Self> {
+        config.validate()?;
+        config.env.create_dir_all(&config.data_dir)?;
+
+        let shards_path = config.data_dir.join(SHARDS_FILE_NAME);
+        let recorded = match config.env.read(&shards_path) {
+            Ok(contents) => Some(
+                String::from_utf8_lossy(&contents)
+                    .trim()
+                    .parse::<usize>()
+                    .map_err(|_| {
+                        OblivionError::Corruption(format!(
+                            "invalid shard count in {:?}",
+                            shards_path
+                        ))
+                    })?,
+            ),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+        if let Some(recorded) = recorded.filter(|&n| n != config.shard_count) {
+            return Err(OblivionError::Config(format!(
+                "database has {} shards, shard_count is {}",
+                recorded, config.shard_count
+            )));
+        }
+
+        let shards = (0..config.shard_count)
+            .map(|shard| {
+                let mut shard_config = config.clone();
+                shard_config.data_dir = config.shard_dir(shard);
+                ConcurrentOblivion::open(shard_config)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if recorded.is_none() {
+            let mut file = config.env.create(&shards_path, false)?;
+            file.write_all(config.shard_count.to_string().as_bytes())?;
+            file.sync(SyncMethod::Fsync)?;
+            config.env.sync_parent(&shards_path)?;
+        }
+
+        log::info!(
+            "Sharded engine opened at {:?} ({} shards)",
+            config.data_dir,
+            config.shard_count
+        );
+        Ok(Self {
+            shards: shards.into(),
+            comparator: config.comparator,
+        })
+    }
+
+    /// Index of the shard that owns `key`.
+    pub fn shard_for(&self, key: &[u8]) -> usize {
+        crc32fast::hash(key) as usize % self.shards.len()
+    }
+
+    /// The shard that owns `key`.
+    fn shard(&self, key: &[u8]) -> &ConcurrentOblivion {
+        &self.shards[self.shard_for(key)]
+    }
+
+    /// Returns the number of shards.
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Returns the individual shards, e.g. to read their metrics.
+    pub fn shards(&self) -> &[ConcurrentOblivion] {
+        &self.shards
+    }
+
+    /// Insert a key-value pair (locks only the owning shard).
+    pub fn put(&self, key: impl Into<Key>, value: impl Into<Value>) -> Result<()> {
+        let key = key.into();
+        self.shard(&key).put(key, value)
+    }
+
+    /// Insert a key-value pair with TTL (locks only the owning shard).
+    pub fn put_with_ttl(
+        &self,
+        key: impl Into<Key>,
+        value: impl Into<Value>,
+        ttl_ms: u64,
+    ) -> Result<()> {
+        let key = key.into();
+        self.shard(&key).put_with_ttl(key, value, ttl_ms)
+    }
+
+    /// Get a value by key (lock-free).
+    pub fn get(&self, key: impl AsRef<[u8]>) -> Option<Value> {
+        let key = key.as_ref();
+        self.shard(key).get(key)
+    }
+
+    /// Get a value by key with per-read options (lock-free).
+    ///
+    /// Snapshots belong to a single shard and are rejected.
+    pub fn get_opt(&self, key: impl AsRef<[u8]>, opts: &ReadOptions) -> Result<Option<Value>> {
+        let key = key.as_ref();
+        Self::check_no_snapshot(opts)?;
+        self.shard(key).get_opt(key, opts)
+    }
+
+    /// Delete a key (locks only the owning shard).
+    pub fn delete(&self, key: impl Into<Key>) -> Result<()> {
+        let key = key.into();
+        self.shard(&key).delete(key)
+    }
+
+    /// Scan all key-value pairs across every shard, in key order.
+    pub fn scan(&self) -> Vec<(Key, Value)> {
+        self.scan_opt(&ReadOptions::default()).unwrap_or_else(|e| {
+            log::error!("Scan failed: {}", e);
+            Vec::new()
+        })
+    }
+
+    /// Scan key-value pairs across every shard with per-read options,
+    /// in key order. Snapshots belong to a single shard and are rejected.
+    pub fn scan_opt(&self, opts: &ReadOptions) -> Result<Vec<(Key, Value)>> {
+        Self::check_no_snapshot(opts)?;
+        let mut merged = Vec::new();
+        for shard in self.shards.iter() {
+            merged.extend(shard.scan_opt(opts)?);
+        }
+        // Shards hold disjoint keys, so a sort is a complete merge
+        self.comparator.sort(&mut merged);
+        Ok(merged)
+    }
+
+    /// Returns the key-value pairs passing `predicate` across every
+    /// shard, in key order; each shard filters its own rows.
+    pub fn scan_filtered(&self, predicate: &Predicate) -> Result<Vec<(Key, Value)>> {
+        let mut merged = Vec::new();
+        for shard in self.shards.iter() {
+            merged.extend(shard.scan_filtered(predicate)?);
+        }
+        self.comparator.sort(&mut merged);
+        Ok(merged)
+    }
+
+    /// Get remaining TTL for a key.
+    pub fn ttl(&self, key: impl AsRef<[u8]>) -> Option<u64> {
+        let key = key.as_ref();
+        self.shard(key).ttl(key)
+    }
+
+    /// Get number of entries in the active MemTables across all shards,
+    /// tombstones included.
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| shard.len()).sum()
+    }
+
+    /// Estimate the number of keys across all shards without reading
+    /// any data.
+    pub fn estimate_live_keys(&self) -> u64 {
+        self.shards
+            .iter()
+            .map(|shard| shard.estimate_live_keys())
+            .sum()
+    }
+
+    /// Estimate the number of keys across all shards from counters
+    /// alone.
+    pub fn dbsize_estimate(&self) -> u64 {
+        self.shards
+            .iter()
+            .map(|shard| shard.dbsize_estimate())
+            .sum()
+    }
+
+    /// Count the keys across all shards with a full scan of each, until
+    /// `cancel` is set.
+    pub fn dbsize_exact(&self, cancel: &AtomicBool) -> Result<u64> {
+        self.shards
+            .iter()
+            .map(|shard| shard.dbsize_exact(cancel))
+            .sum()
+    }
+
+    /// Check if every shard is empty.
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|shard| shard.is_empty())
+    }
+
+    /// Get the total MemTable size in bytes across all shards.
+    pub fn memtable_size(&self) -> usize {
+        self.shards.iter().map(|shard| shard.memtable_size()).sum()
+    }
+
+    /// Change runtime-mutable options on every shard.
+    ///
+    /// Invalid options are rejected by the first shard before any
+    /// shard changes.
+    pub fn set_options<I, K, V>(&self, options: I) -> Result<()>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let options: Vec<(K, V)> = options.into_iter().collect();
+        for shard in self.shards.iter() {
+            shard.set_options(options.iter().map(|(k, v)| (k.as_ref(), v.as_ref())))?;
+        }
+        Ok(())
+    }
+
+    /// Reject read options carrying a (single-shard) snapshot.
+    fn check_no_snapshot(opts: &ReadOptions) -> Result<()> {
+        if opts.snapshot.is_some() {
+            return Err(OblivionError::Config(
+                "snapshots are not supported across shards".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(dir: &std::path::Path, shards: usize) -> Config {
+        Config::builder(dir)
+            .memtable_max_size(1024)
+            .shard_count(shards)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_keys_spread_over_shards() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = ShardedOblivion::open(config(dir.path(), 4)).unwrap();
+
+        let mut per_shard = [0usize; 4];
+        for i in 0..200 {
+            let key = format!("key_{:03}", i).into_bytes();
+            per_shard[engine.shard_for(&key)] += 1;
+            engine.put(key, b"value".to_vec()).unwrap();
+        }
+        assert!(per_shard.iter().all(|&n| n > 0));
+        for i in 0..200 {
+            let key = format!("key_{:03}", i).into_bytes();
+            assert_eq!(engine.get(&key), Some(b"value".to_vec()));
+        }
+        assert!(dir.path().join("shard_003").is_dir());
+    }
+
+    #[test]
+    fn test_scan_merges_shards_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = ShardedOblivion::open(config(dir.path(), 3)).unwrap();
+        for i in (0..100).rev() {
+            engine
+                .put(format!("key_{:03}", i).into_bytes(), vec![i as u8])
+                .unwrap();
+        }
+        engine.delete(b"key_050".to_vec()).unwrap();
+
+        let rows = engine.scan();
+        assert_eq!(rows.len(), 99);
+        assert!(rows.windows(2).all(|w| w[0].0 < w[1].0));
+
+        let opts = ReadOptions {
+            lower_bound: Some(b"key_010".to_vec()),
+            upper_bound: Some(b"key_020".to_vec()),
+            ..ReadOptions::default()
+        };
+        assert_eq!(engine.scan_opt(&opts).unwrap().len(), 10);
+    }
+
+    #[test]
+    fn test_reopen_checks_shard_count() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let engine = ShardedOblivion::open(config(dir.path(), 2)).unwrap();
+            engine.put(b"key".to_vec(), b"value".to_vec()).unwrap();
+        }
+
+        let err = ShardedOblivion::open(config(dir.path(), 4)).err().unwrap();
+        assert!(err.to_string().contains("database has 2 shards"));
+
+        let engine = ShardedOblivion::open(config(dir.path(), 2)).unwrap();
+        assert_eq!(engine.get(b"key"), Some(b"value".to_vec()));
+    }
+
+    #[test]
+    fn test_rejects_snapshot_reads() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = ShardedOblivion::open(config(dir.path(), 2)).unwrap();
+        let opts = ReadOptions {
+            snapshot: Some(engine.shards()[0].snapshot()),
+            ..ReadOptions::default()
+        };
+        assert!(engine.get_opt(b"key", &opts).is_err());
+        assert!(engine.scan_opt(&opts).is_err());
+    }
+}
@@ -0,0 +1,175 @@
+//! OBLIVION - Adaptive MemTable Sizing
+//! Moves the flush threshold with the traffic when
+//! `adaptive_memtable_sizing` is set, so quiet periods do not keep
+//! large MemTables around and busy ones do not flush tiny tables
+//! back to back.
+//!
+//! ## Model
+//! A tree measures its write rate from the MemTables frozen for being
+//! full (bytes over the time since the previous one) and the time its
+//! flushes take, both smoothed. The threshold is then the bytes written
+//! in [`FILL_PER_FLUSH`] flush times: while one MemTable is written
+//! out, the next fills no more than halfway, so the number of frozen
+//! MemTables stays at one or none. It is clamped to
+//! `adaptive_memtable_min_size..=adaptive_memtable_max_size`; until
+//! both have been measured, `memtable_max_size` applies.
+//!
+//! The threshold is read through
+//! [`get_property`](super::Oblivion::get_property) as
+//! [`MEMTABLE_FLUSH_THRESHOLD`](super::properties::MEMTABLE_FLUSH_THRESHOLD).
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+use crate::config::Config;
+
+/// How many flush times a MemTable should take to fill.
+pub const FILL_PER_FLUSH: f64 = 2.0;
+
+/// Weight of a new measurement in the smoothed write rate and flush time.
+const SMOOTHING: f64 = 0.25;
+
+/// Measurements behind the threshold.
+struct Measurements {
+    /// When the last full MemTable was frozen, or the sizer created.
+    last_fill: Instant,
+    /// Smoothed bytes per second written to the MemTable.
+    write_rate: Option<f64>,
+    /// Smoothed seconds a flush takes.
+    flush_secs: Option<f64>,
+}
+
+/// The flush threshold of one tree.
+pub(crate) struct MemTableSizer {
+    measurements: Mutex<Measurements>,
+    /// Threshold picked from the measurements; 0 until both are known.
+    threshold: AtomicUsize,
+}
+
+impl MemTableSizer {
+    pub(crate) fn new() -> Self {
+        Self {
+            measurements: Mutex::new(Measurements {
+                last_fill: Instant::now(),
+                write_rate: None,
+                flush_secs: None,
+            }),
+            threshold: AtomicUsize::new(0),
+        }
+    }
+
+    /// Bytes the active MemTable is flushed at under `config`.
+    pub(crate) fn threshold(&self, config: &Config) -> usize {
+        if !config.adaptive_memtable_sizing {
+            return config.memtable_max_size;
+        }
+        let threshold = match self.threshold.load(Ordering::Relaxed) {
+            0 => config.memtable_max_size,
+            threshold => threshold,
+        };
+        threshold.clamp(
+            config.adaptive_memtable_min_size,
+            config.adaptive_memtable_max_size,
+        )
+    }
+
+    /// Record that a MemTable holding `bytes` is frozen for being full.
+    pub(crate) fn record_fill(&self, config: &Config, bytes: usize) {
+        self.record_fill_at(config, bytes, Instant::now());
+    }
+
+    fn record_fill_at(&self, config: &Config, bytes: usize, now: Instant) {
+        let mut measurements = self.measurements.lock();
+        let secs = now
+            .saturating_duration_since(measurements.last_fill)
+            .as_secs_f64();
+        measurements.last_fill = now;
+        if secs > 0.0 {
+            let rate = bytes as f64 / secs;
+            measurements.write_rate = Some(smooth(measurements.write_rate, rate));
+            self.adjust(config, &measurements);
+        }
+    }
+
+    /// Record that a flush took `elapsed`.
+    pub(crate) fn record_flush(&self, config: &Config, elapsed: Duration) {
+        let mut measurements = self.measurements.lock();
+        let secs = elapsed.as_secs_f64();
+        measurements.flush_secs = Some(smooth(measurements.flush_secs, secs));
+        self.adjust(config, &measurements);
+    }
+
+    fn adjust(&self, config: &Config, measurements: &Measurements) {
+        let (Some(rate), Some(flush_secs)) = (measurements.write_rate, measurements.flush_secs)
+        else {
+            return;
+        };
+        // Float to int casts saturate
+        let target = (rate * flush_secs * FILL_PER_FLUSH) as usize;
+        let threshold = target.clamp(
+            config.adaptive_memtable_min_size,
+            config.adaptive_memtable_max_size,
+        );
+        let previous = self.threshold.swap(threshold, Ordering::Relaxed);
+        if config.adaptive_memtable_sizing && previous != threshold {
+            log::debug!(
+                "MemTable flush threshold {} -> {} bytes ({:.0} B/s written, {:.3}s per flush)",
+                previous,
+                threshold,
+                rate,
+                flush_secs
+            );
+        }
+    }
+}
+
+/// Blend `sample` into the smoothed value `current`.
+fn smooth(current: Option<f64>, sample: f64) -> f64 {
+    match current {
+        Some(current) => current + SMOOTHING * (sample - current),
+        None => sample,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_threshold_follows_traffic() {
+        let config = Config::new("/db")
+            .with_memtable_max_size(64 * 1024)
+            .with_adaptive_memtable_sizing(1024, 1024 * 1024);
+        let sizer = MemTableSizer::new();
+        let start = Instant::now();
+        assert_eq!(sizer.threshold(&config), 64 * 1024);
+
+        // 64 KB/s with 100 ms flushes: 12.8 KB
+        sizer.record_fill_at(&config, 64 * 1024, start + Duration::from_secs(1));
+        assert_eq!(sizer.threshold(&config), 64 * 1024);
+        sizer.record_flush(&config, Duration::from_millis(100));
+        assert_eq!(sizer.threshold(&config), 13_107);
+
+        // Traffic picks up and the threshold grows, up to the bound
+        let mut now = start + Duration::from_secs(1);
+        for _ in 0..40 {
+            now += Duration::from_millis(10);
+            sizer.record_fill_at(&config, sizer.threshold(&config), now);
+        }
+        assert_eq!(sizer.threshold(&config), 1024 * 1024);
+
+        // Quiet again: back down to the smallest size
+        for _ in 0..40 {
+            now += Duration::from_secs(10);
+            sizer.record_fill_at(&config, sizer.threshold(&config), now);
+        }
+        assert_eq!(sizer.threshold(&config), 1024);
+
+        // Turning the mode off restores the static threshold
+        let mut fixed = config.clone();
+        fixed.adaptive_memtable_sizing = false;
+        assert_eq!(sizer.threshold(&fixed), 64 * 1024);
+    }
+}
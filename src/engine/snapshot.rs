@@ -0,0 +1,53 @@
+//! OBLIVION - Snapshots
+//! Point-in-time, read-only views of the engine.
+//!
+//! A snapshot freezes a copy of the active MemTable and pins the
+//! [`Version`] (frozen MemTables and SSTables) that was current when it
+//! was taken. Versions are immutable, so the pinned view stays valid.
+//! Compaction runs regardless: the tables it replaces stay on disk until
+//! the last snapshot or iterator holding them is dropped.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::types::{Key, Value};
+
+use super::version::Version;
+
+/// A consistent, read-only view of the engine at one point in time.
+///
+/// Cloning is cheap; the view is released when the last clone is dropped.
+#[derive(Clone)]
+pub struct Snapshot {
+    /// Active MemTable contents at snapshot time (tombstones included).
+    memtable: Arc<BTreeMap<Key, Option<Value>>>,
+    /// Frozen MemTables and live SSTables at snapshot time.
+    version: Arc<Version>,
+    /// Registers this snapshot with the engine while alive.
+    _pin: Arc<()>,
+}
+
+impl Snapshot {
+    /// Create a snapshot from the engine's current state.
+    pub(crate) fn new(
+        memtable: BTreeMap<Key, Option<Value>>,
+        version: Arc<Version>,
+        pin: Arc<()>,
+    ) -> Self {
+        Self {
+            memtable: Arc::new(memtable),
+            version,
+            _pin: pin,
+        }
+    }
+
+    /// Active MemTable contents at snapshot time.
+    pub(crate) fn memtable(&self) -> &BTreeMap<Key, Option<Value>> {
+        &self.memtable
+    }
+
+    /// The version pinned by this snapshot.
+    pub(crate) fn version(&self) -> &Arc<Version> {
+        &self.version
+    }
+}
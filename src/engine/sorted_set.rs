@@ -0,0 +1,212 @@
+//! OBLIVION - Sorted Sets
+//! Members ordered by a score under one key, as Redis' sorted sets, so
+//! leaderboard-style queries ("everyone scoring between 100 and 200")
+//! are one range scan.
+//!
+//! A sorted set keeps two entries per member in the database's
+//! data-type range (see
+//! [`DATA_TYPE_PREFIX`](super::database::DATA_TYPE_PREFIX)), with the
+//! score in its order-preserving encoding (see [`keys`](super::keys)):
+//!
+//! ```text
+//! \xff\xfftzm <encode(&(key, member))>         -> encode(&score)
+//! \xff\xfftzs <encode(&(key, score, member))>  -> empty
+//! ```
+//!
+//! The first answers `zscore`; the second sorts members by score, then
+//! by member, so `zrangebyscore` reads exactly the requested range.
+//! `zadd` and `zrem` update both in one [`WriteBatch`], under the engine
+//! lock [lists](super::list) use, as moving a member reads its old
+//! score. Scores are `f64`; NaN is refused and `-0.0` stored as `0.0`.
+//! Sorted sets are not plain keys and are never seen by `get`, `keys`
+//! or scans of a [`Database`].
+//!
+//! ## Example
+//! ```no_run
+//! use oblivion::config::Config;
+//! use oblivion::engine::Oblivion;
+//!
+//! let engine = Oblivion::open(Config::new("./data")).unwrap();
+//! let db = engine.database(0).unwrap();
+//! db.zadd(b"scores", vec![(b"ada".to_vec(), 250.0), (b"bob".to_vec(), 120.0)])
+//!     .unwrap();
+//! let top = db.zrangebyscore(b"scores", 200.0, f64::INFINITY).unwrap();
+//! assert_eq!(top, vec![(b"ada".to_vec(), 250.0)]);
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::engine::batch::WriteBatch;
+use crate::engine::database::Database;
+use crate::engine::keys::{decode, encode};
+use crate::engine::options::{prefix_end, ReadOptions};
+use crate::error::{OblivionError, Result};
+use crate::types::Key;
+
+/// First bytes of the member-to-score entries, within a database.
+pub const ZSET_MEMBER_PREFIX: &[u8] = b"\xff\xfftzm";
+
+/// First bytes of the score-ordered entries, within a database.
+pub const ZSET_SCORE_PREFIX: &[u8] = b"\xff\xfftzs";
+
+/// A member of a sorted set and its score.
+pub type ScoredMember = (Vec<u8>, f64);
+
+impl Database<'_> {
+    /// Add `members` to the sorted set `key`, or move existing ones to
+    /// their new score, in one batch. Returns how many were new.
+    pub fn zadd(&self, key: &[u8], members: Vec<ScoredMember>) -> Result<usize> {
+        // A member listed twice takes its last score, as in Redis
+        let mut scores = BTreeMap::new();
+        for (member, score) in members {
+            scores.insert(member, checked_score(score)?);
+        }
+        let _guard = self.engine().data_types.lock();
+        let mut batch = WriteBatch::new();
+        let mut added = 0;
+        for (member, score) in scores {
+            match self.zscore(key, &member)? {
+                Some(old) if old == score => continue,
+                Some(old) => {
+                    batch.delete(self.stored(&score_key(key, old, &member)));
+                }
+                None => added += 1,
+            }
+            batch.put(self.stored(&score_key(key, score, &member)), Vec::new());
+            batch.put(self.stored(&member_key(key, &member)), encode(&score));
+        }
+        self.engine().write(batch)?;
+        Ok(added)
+    }
+
+    /// Returns the score of `member` in the sorted set `key`.
+    pub fn zscore(&self, key: &[u8], member: &[u8]) -> Result<Option<f64>> {
+        match self.get_stored(&self.stored(&member_key(key, member)))? {
+            Some(score) => decode(&score).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Remove `members` from the sorted set `key` in one batch,
+    /// returning how many were members.
+    pub fn zrem(&self, key: &[u8], members: &[Vec<u8>]) -> Result<usize> {
+        let _guard = self.engine().data_types.lock();
+        let mut batch = WriteBatch::new();
+        let mut removed = 0;
+        for member in members.iter().collect::<BTreeSet<_>>() {
+            if let Some(score) = self.zscore(key, member)? {
+                batch.delete(self.stored(&score_key(key, score, member)));
+                batch.delete(self.stored(&member_key(key, member)));
+                removed += 1;
+            }
+        }
+        self.engine().write(batch)?;
+        Ok(removed)
+    }
+
+    /// Returns the members of the sorted set `key` scoring from `min` to
+    /// `max`, both inclusive, by score and then member.
+    pub fn zrangebyscore(&self, key: &[u8], min: f64, max: f64) -> Result<Vec<ScoredMember>> {
+        let (min, max) = (checked_score(min)?, checked_score(max)?);
+        if min > max {
+            return Ok(Vec::new());
+        }
+        let prefix = self.stored(&[ZSET_SCORE_PREFIX, &encode(key)].concat());
+        let mut opts = ReadOptions::new();
+        opts.lower_bound = Some([&prefix[..], &encode(&min)].concat());
+        opts.upper_bound = prefix_end(&[&prefix[..], &encode(&max)].concat());
+        let rows = self.engine().scan_opt(&opts)?;
+        rows.into_iter()
+            .map(|(stored, _)| {
+                let (score, member) = decode::<(f64, Vec<u8>)>(&stored[prefix.len()..])?;
+                Ok((member, score))
+            })
+            .collect()
+    }
+}
+
+/// `score`, with `-0.0` as `0.0`, or an error if it is NaN.
+fn checked_score(score: f64) -> Result<f64> {
+    if score.is_nan() {
+        return Err(OblivionError::Unsupported(
+            "NaN is not a valid sorted-set score".to_string(),
+        ));
+    }
+    Ok(score + 0.0)
+}
+
+/// The key holding the score of `member` in the sorted set `key`.
+fn member_key(key: &[u8], member: &[u8]) -> Key {
+    [ZSET_MEMBER_PREFIX, &encode(&(key, member))].concat()
+}
+
+/// The key ordering `member` by `score` in the sorted set `key`.
+fn score_key(key: &[u8], score: f64, member: &[u8]) -> Key {
+    [ZSET_SCORE_PREFIX, &encode(&(key, score, member))].concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::engine::database::DATA_TYPE_PREFIX;
+    use crate::engine::Oblivion;
+
+    fn scored(pairs: &[(&str, f64)]) -> Vec<ScoredMember> {
+        pairs
+            .iter()
+            .map(|(member, score)| (member.as_bytes().to_vec(), *score))
+            .collect()
+    }
+
+    #[test]
+    fn test_score_ranges() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = Oblivion::open(Config::new(dir.path())).unwrap();
+        let db = engine.database(0).unwrap();
+        assert!(ZSET_MEMBER_PREFIX.starts_with(DATA_TYPE_PREFIX));
+        assert!(ZSET_SCORE_PREFIX.starts_with(DATA_TYPE_PREFIX));
+
+        let board = scored(&[("ada", 250.0), ("bob", -3.5), ("cy", 120.0), ("di", 120.0)]);
+        assert_eq!(db.zadd(b"board", board).unwrap(), 4);
+        // Moving a member drops its old position
+        assert_eq!(
+            db.zadd(b"board", scored(&[("bob", 300.0), ("ed", -0.0)]))
+                .unwrap(),
+            1
+        );
+        assert_eq!(db.zscore(b"board", b"bob").unwrap(), Some(300.0));
+        assert_eq!(db.zscore(b"board", b"zed").unwrap(), None);
+
+        let all = db
+            .zrangebyscore(b"board", f64::NEG_INFINITY, f64::INFINITY)
+            .unwrap();
+        assert_eq!(
+            all,
+            scored(&[
+                ("ed", 0.0),
+                ("cy", 120.0),
+                ("di", 120.0),
+                ("ada", 250.0),
+                ("bob", 300.0)
+            ])
+        );
+        assert_eq!(
+            db.zrangebyscore(b"board", 120.0, 250.0).unwrap(),
+            scored(&[("cy", 120.0), ("di", 120.0), ("ada", 250.0)])
+        );
+        assert!(db.zrangebyscore(b"board", 5.0, 1.0).unwrap().is_empty());
+        assert!(db.zadd(b"board", scored(&[("x", f64::NAN)])).is_err());
+
+        assert_eq!(
+            db.zrem(b"board", &[b"cy".to_vec(), b"zed".to_vec()])
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            db.zrangebyscore(b"board", 100.0, 200.0).unwrap(),
+            scored(&[("di", 120.0)])
+        );
+        assert!(db.keys(b"*").unwrap().is_empty());
+    }
+}
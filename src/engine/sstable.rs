@@ -1,37 +1,785 @@
 //! OBLIVION - SSTable (Sorted String Table)
 //! Immutable on-disk data structure for persisting flushed MemTable data.
-//! This is a stub/placeholder for the full SSTable implementation.
+//!
+//! ## File Format
+//! ```text
+//! [data block 0] ... [data block N][filter block][index block][properties block][footer]
+//!
+//! data block:       [record]* [crc: 4 bytes]
+//!                   (with a compression dictionary, [raw_len: 4 bytes LE][zstd frame] [crc: 4 bytes])
+//! record:           [key_len: 4 bytes LE][key][val_len: 4 bytes LE][value]
+//!                   (val_len = 0xFFFFFFFF marks a tombstone, no value bytes follow)
+//! filter block:     [name_len: 2 bytes LE][policy name][filter bytes] [crc: 4 bytes]
+//! index block:      ([key_len: 4 bytes LE][last key][offset: 8 bytes LE][size: 8 bytes LE])* [crc: 4 bytes]
+//! properties block: bincode(TableProperties) [crc: 4 bytes]
+//! footer:           [filter handle][index handle][properties handle] [magic: 8 bytes LE]
+//!                   (each handle is [offset: 8 bytes LE][size: 8 bytes LE])
+//! ```
+//!
+//! The filter block records the *name* of the [`FilterPolicy`] that built it,
+//! so readers pick the matching policy and unknown policies degrade to
+//! "no filter" instead of making the table unreadable.
+//!
+//! Tables written with `compression_dict_size` carry a zstd dictionary in
+//! their properties and compress every data block against it (see
+//! [`compression`](super::compression)).
 
-use std::path::PathBuf;
+use std::io::Write;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
 
+use serde::{Deserialize, Serialize};
+
+use crate::engine::cache::TableCache;
+use crate::engine::comparator::Comparator;
+use crate::engine::compression::{BlockCompressor, BlockDecompressor};
+use crate::engine::env::{Env, RandomAccessFile, WritableFile};
+use crate::engine::filter::{self, FilterPolicy};
+use crate::engine::io::{self, SyncMethod};
+use crate::error::{OblivionError, Result};
 use crate::types::{Key, Value};
 
+/// Magic number at the end of every SSTable ("OBLVSST1").
+const TABLE_MAGIC: u64 = 0x4F42_4C56_5353_5431;
+
+/// Fixed footer size: three block handles + magic.
+const FOOTER_LEN: usize = 3 * 16 + 8;
+
+/// Value length marking a tombstone record.
+const TOMBSTONE_LEN: u32 = u32::MAX;
+
+/// Target uncompressed size of a data block.
+pub const DEFAULT_BLOCK_SIZE: usize = 4096;
+
+/// Location of a block inside an SSTable file.
+/// `size` includes the 4-byte CRC trailer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockHandle {
+    pub offset: u64,
+    pub size: u64,
+}
+
+impl BlockHandle {
+    fn encode_to(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.offset.to_le_bytes());
+        buf.extend_from_slice(&self.size.to_le_bytes());
+    }
+
+    fn decode(data: &[u8]) -> Self {
+        Self {
+            offset: u64::from_le_bytes(data[0..8].try_into().unwrap()),
+            size: u64::from_le_bytes(data[8..16].try_into().unwrap()),
+        }
+    }
+}
+
+/// Locations of the metadata blocks, as recorded in an SSTable's footer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Footer {
+    /// Location of the filter block.
+    pub filter: BlockHandle,
+    /// Location of the index block.
+    pub index: BlockHandle,
+    /// Location of the properties block.
+    pub properties: BlockHandle,
+}
+
+/// Summary statistics recorded in every SSTable.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TableProperties {
+    /// Number of records (including tombstones).
+    pub entry_count: u64,
+    /// Number of tombstone records.
+    pub tombstone_count: u64,
+    /// Total bytes of all keys.
+    pub raw_key_size: u64,
+    /// Total bytes of all values.
+    pub raw_value_size: u64,
+    /// Total bytes of all data blocks (including CRC trailers).
+    pub data_size: u64,
+    /// Number of data blocks.
+    pub num_data_blocks: u64,
+    /// Smallest key in the table.
+    pub min_key: Key,
+    /// Largest key in the table.
+    pub max_key: Key,
+    /// Name of the filter policy (empty if the table has no filter).
+    pub filter_policy: String,
+    /// Sequence number of the oldest write in the table; 0 if unknown
+    /// (see [`SSTableBuilder::set_sequence_range`]).
+    pub smallest_seqno: u64,
+    /// Sequence number of the newest write in the table; 0 if unknown.
+    pub largest_seqno: u64,
+    /// Zstd dictionary the data blocks are compressed with; empty if
+    /// they are stored uncompressed.
+    pub compression_dict: Vec<u8>,
+    /// When the table was written, in seconds since the Unix epoch; 0 if
+    /// unknown (tables written before creation times were recorded).
+    pub creation_time: u64,
+}
+
+/// Properties of a table written before creation times were recorded.
+#[derive(Deserialize)]
+struct UndatedTableProperties {
+    entry_count: u64,
+    tombstone_count: u64,
+    raw_key_size: u64,
+    raw_value_size: u64,
+    data_size: u64,
+    num_data_blocks: u64,
+    min_key: Key,
+    max_key: Key,
+    filter_policy: String,
+    smallest_seqno: u64,
+    largest_seqno: u64,
+    compression_dict: Vec<u8>,
+}
+
+impl From<UndatedTableProperties> for TableProperties {
+    fn from(old: UndatedTableProperties) -> Self {
+        Self {
+            entry_count: old.entry_count,
+            tombstone_count: old.tombstone_count,
+            raw_key_size: old.raw_key_size,
+            raw_value_size: old.raw_value_size,
+            data_size: old.data_size,
+            num_data_blocks: old.num_data_blocks,
+            min_key: old.min_key,
+            max_key: old.max_key,
+            filter_policy: old.filter_policy,
+            smallest_seqno: old.smallest_seqno,
+            largest_seqno: old.largest_seqno,
+            compression_dict: old.compression_dict,
+            creation_time: 0,
+        }
+    }
+}
+
+/// Properties of a table written before compression dictionaries were
+/// recorded.
+#[derive(Deserialize)]
+struct UncompressedTableProperties {
+    entry_count: u64,
+    tombstone_count: u64,
+    raw_key_size: u64,
+    raw_value_size: u64,
+    data_size: u64,
+    num_data_blocks: u64,
+    min_key: Key,
+    max_key: Key,
+    filter_policy: String,
+    smallest_seqno: u64,
+    largest_seqno: u64,
+}
+
+impl From<UncompressedTableProperties> for TableProperties {
+    fn from(old: UncompressedTableProperties) -> Self {
+        Self {
+            entry_count: old.entry_count,
+            tombstone_count: old.tombstone_count,
+            raw_key_size: old.raw_key_size,
+            raw_value_size: old.raw_value_size,
+            data_size: old.data_size,
+            num_data_blocks: old.num_data_blocks,
+            min_key: old.min_key,
+            max_key: old.max_key,
+            filter_policy: old.filter_policy,
+            smallest_seqno: old.smallest_seqno,
+            largest_seqno: old.largest_seqno,
+            compression_dict: Vec::new(),
+            creation_time: 0,
+        }
+    }
+}
+
+/// Properties of a table written before sequence ranges were recorded.
+#[derive(Deserialize)]
+struct LegacyTableProperties {
+    entry_count: u64,
+    tombstone_count: u64,
+    raw_key_size: u64,
+    raw_value_size: u64,
+    data_size: u64,
+    num_data_blocks: u64,
+    min_key: Key,
+    max_key: Key,
+    filter_policy: String,
+}
+
+impl From<LegacyTableProperties> for TableProperties {
+    fn from(legacy: LegacyTableProperties) -> Self {
+        Self {
+            entry_count: legacy.entry_count,
+            tombstone_count: legacy.tombstone_count,
+            raw_key_size: legacy.raw_key_size,
+            raw_value_size: legacy.raw_value_size,
+            data_size: legacy.data_size,
+            num_data_blocks: legacy.num_data_blocks,
+            min_key: legacy.min_key,
+            max_key: legacy.max_key,
+            filter_policy: legacy.filter_policy,
+            smallest_seqno: 0,
+            largest_seqno: 0,
+            compression_dict: Vec::new(),
+            creation_time: 0,
+        }
+    }
+}
+
+/// A value inside a decoded data block: the block, and the range of
+/// the block's bytes holding the value.
+pub type BlockValue = (Arc<[u8]>, Range<usize>);
+
+/// Index entry pointing at one data block.
+#[derive(Debug, Clone)]
+struct IndexEntry {
+    /// Largest key stored in the block.
+    last_key: Key,
+    /// Location of the block.
+    handle: BlockHandle,
+}
+
+/// Iterator over the records of a decoded data block.
+struct BlockIter<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BlockIter<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn read_u32(&mut self) -> Result<u32> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + 4)
+            .ok_or_else(|| OblivionError::Corruption("truncated SSTable record".into()))?;
+        self.pos += 4;
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| OblivionError::Corruption("truncated SSTable record".into()))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+}
+
+impl<'a> Iterator for BlockIter<'a> {
+    type Item = Result<(&'a [u8], Option<&'a [u8]>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.data.len() {
+            return None;
+        }
+        let record = (|| {
+            let key_len = self.read_u32()? as usize;
+            let key = self.read_bytes(key_len)?;
+            let value = match self.read_u32()? {
+                TOMBSTONE_LEN => None,
+                len => Some(self.read_bytes(len as usize)?),
+            };
+            Ok((key, value))
+        })();
+        if record.is_err() {
+            self.pos = self.data.len();
+        }
+        Some(record)
+    }
+}
+
+/// Streaming writer producing a new SSTable.
+/// Keys must be added in strictly increasing order (byte order, unless
+/// written through [`SSTable::flush_from_memtable`] under a custom
+/// comparator).
+/// The table is written to a temporary file and renamed into place
+/// by [`SSTableBuilder::finish`], so a crash never leaves a partial table.
+pub struct SSTableBuilder {
+    /// Final path of the table.
+    path: PathBuf,
+    /// Temporary path written to until `finish`.
+    tmp_path: PathBuf,
+    /// File system the table is written to.
+    env: Env,
+    /// Buffered or direct writer on the temporary file.
+    writer: Box<dyn WritableFile>,
+    /// Current (unfinished) data block.
+    block: Vec<u8>,
+    /// Last key added to the current block.
+    block_last_key: Key,
+    /// Bytes written so far.
+    offset: u64,
+    /// Index entries for finished blocks.
+    index: Vec<IndexEntry>,
+    /// All keys, for building the filter.
+    keys: Vec<Key>,
+    /// Target data block size.
+    block_size: usize,
+    /// Policy used to build the filter.
+    filter_policy: Box<dyn FilterPolicy>,
+    /// Statistics accumulated while writing.
+    properties: TableProperties,
+    /// Order keys are added in.
+    comparator: Comparator,
+    /// Compresses data blocks, if the table has a dictionary.
+    compressor: Option<BlockCompressor>,
+}
+
+impl SSTableBuilder {
+    /// Start building a table at `path` with the given filter policy.
+    pub fn new(path: PathBuf, filter_policy: Box<dyn FilterPolicy>) -> Result<Self> {
+        Self::create(path, filter_policy, false)
+    }
+
+    /// Start building a table, writing with `O_DIRECT` if `direct_io` is set.
+    pub fn create(
+        path: PathBuf,
+        filter_policy: Box<dyn FilterPolicy>,
+        direct_io: bool,
+    ) -> Result<Self> {
+        Self::create_in(&Env::default(), path, filter_policy, direct_io)
+    }
+
+    /// Start building a table on the file system `env`.
+    pub fn create_in(
+        env: &Env,
+        path: PathBuf,
+        filter_policy: Box<dyn FilterPolicy>,
+        direct_io: bool,
+    ) -> Result<Self> {
+        let tmp_path = path.with_extension("sst.tmp");
+        let writer = env.create(&tmp_path, direct_io)?;
+
+        Ok(Self {
+            path,
+            tmp_path,
+            env: env.clone(),
+            writer,
+            block: Vec::with_capacity(DEFAULT_BLOCK_SIZE),
+            block_last_key: Vec::new(),
+            offset: 0,
+            index: Vec::new(),
+            keys: Vec::new(),
+            block_size: DEFAULT_BLOCK_SIZE,
+            filter_policy,
+            properties: TableProperties::default(),
+            comparator: Comparator::default(),
+            compressor: None,
+        })
+    }
+
+    /// Record that the table holds the writes numbered `smallest` to
+    /// `largest`. Tables built without a range (column family tables,
+    /// imported and ingested ones) report 0 for both.
+    pub fn set_sequence_range(&mut self, smallest: u64, largest: u64) {
+        self.properties.smallest_seqno = smallest;
+        self.properties.largest_seqno = largest;
+    }
+
+    /// Compress the data blocks against `dictionary`, which is stored in
+    /// the table's properties. Must be called before the first record is
+    /// added; fails without the `zstd` feature.
+    pub fn set_compression_dict(&mut self, dictionary: Vec<u8>) -> Result<()> {
+        debug_assert_eq!(self.properties.entry_count, 0);
+        self.compressor = Some(BlockCompressor::new(&dictionary)?);
+        self.properties.compression_dict = dictionary;
+        Ok(())
+    }
+
+    /// Add a record. `None` writes a tombstone.
+    pub fn add(&mut self, key: &[u8], value: Option<&[u8]>) -> Result<()> {
+        debug_assert!(
+            self.keys
+                .last()
+                .is_none_or(|last| self.comparator.compare(last, key).is_lt()),
+            "SSTable keys must be added in strictly increasing order"
+        );
+
+        self.block
+            .extend_from_slice(&(key.len() as u32).to_le_bytes());
+        self.block.extend_from_slice(key);
+        match value {
+            Some(value) => {
+                self.block
+                    .extend_from_slice(&(value.len() as u32).to_le_bytes());
+                self.block.extend_from_slice(value);
+                self.properties.raw_value_size += value.len() as u64;
+            }
+            None => {
+                self.block.extend_from_slice(&TOMBSTONE_LEN.to_le_bytes());
+                self.properties.tombstone_count += 1;
+            }
+        }
+
+        if self.properties.entry_count == 0 {
+            self.properties.min_key = key.to_vec();
+        }
+        self.properties.entry_count += 1;
+        self.properties.raw_key_size += key.len() as u64;
+        self.block_last_key = key.to_vec();
+        self.keys.push(key.to_vec());
+
+        if self.block.len() >= self.block_size {
+            self.finish_data_block()?;
+        }
+        Ok(())
+    }
+
+    /// Number of records added so far.
+    pub fn entry_count(&self) -> u64 {
+        self.properties.entry_count
+    }
+
+    /// Approximate number of bytes written so far.
+    pub fn estimated_size(&self) -> u64 {
+        self.offset + self.block.len() as u64
+    }
+
+    /// Write the filter, index, properties and footer, then atomically
+    /// move the table into place and open it for reading through `table_cache`.
+    pub fn finish(mut self, table_cache: &Arc<TableCache>) -> Result<SSTable> {
+        self.finish_data_block()?;
+        self.properties.max_key = self.block_last_key.clone();
+
+        // Filter block
+        let key_refs: Vec<&[u8]> = self.keys.iter().map(|k| k.as_slice()).collect();
+        let name = self.filter_policy.name().to_string();
+        let mut filter_block = Vec::new();
+        filter_block.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        filter_block.extend_from_slice(name.as_bytes());
+        let filter_data = self.filter_policy.create_filter(&key_refs);
+        filter_block.extend_from_slice(&filter_data);
+        let filter_handle = self.write_block(&filter_block)?;
+        self.properties.filter_policy = name;
+
+        // Index block
+        let mut index_block = Vec::new();
+        for entry in &self.index {
+            index_block.extend_from_slice(&(entry.last_key.len() as u32).to_le_bytes());
+            index_block.extend_from_slice(&entry.last_key);
+            entry.handle.encode_to(&mut index_block);
+        }
+        let index_handle = self.write_block(&index_block)?;
+
+        // Properties block
+        self.properties.creation_time = unix_time_secs();
+        let props_block = bincode::serialize(&self.properties)
+            .map_err(|e| OblivionError::Serialization(e.to_string()))?;
+        let props_handle = self.write_block(&props_block)?;
+
+        // Footer
+        let mut footer = Vec::with_capacity(FOOTER_LEN);
+        filter_handle.encode_to(&mut footer);
+        index_handle.encode_to(&mut footer);
+        props_handle.encode_to(&mut footer);
+        footer.extend_from_slice(&TABLE_MAGIC.to_le_bytes());
+        self.writer.write_all(&footer)?;
+
+        self.writer.sync(SyncMethod::Fsync)?;
+        self.env.rename(&self.tmp_path, &self.path)?;
+        // The manifest may list the table next; make sure it is there
+        self.env.sync_parent(&self.path)?;
+
+        // A cached handle for this path would still point at the replaced file
+        table_cache.evict(&self.path);
+        let table = SSTable::open(self.path, table_cache)?;
+
+        // The index and filter were just built, so the new table starts warm
+        let _ = table.index.set(self.index);
+        let _ = table.filter.set(Some(TableFilter {
+            policy: self.filter_policy,
+            data: filter_data,
+        }));
+        Ok(table)
+    }
+
+    /// Seal the current data block and record it in the index.
+    fn finish_data_block(&mut self) -> Result<()> {
+        if self.block.is_empty() {
+            return Ok(());
+        }
+        let mut block = std::mem::take(&mut self.block);
+        if let Some(compressor) = &mut self.compressor {
+            block = compressor.compress(&block)?;
+        }
+        let handle = self.write_block(&block)?;
+        self.properties.data_size += handle.size;
+        self.properties.num_data_blocks += 1;
+        self.index.push(IndexEntry {
+            last_key: self.block_last_key.clone(),
+            handle,
+        });
+        self.block = Vec::with_capacity(self.block_size);
+        Ok(())
+    }
+
+    /// Write a block followed by its CRC32 trailer.
+    fn write_block(&mut self, payload: &[u8]) -> Result<BlockHandle> {
+        let crc = crc32fast::hash(payload);
+        self.writer.write_all(payload)?;
+        self.writer.write_all(&crc.to_le_bytes())?;
+        let handle = BlockHandle {
+            offset: self.offset,
+            size: payload.len() as u64 + 4,
+        };
+        self.offset += handle.size;
+        Ok(handle)
+    }
+}
+
 /// Sorted String Table - immutable on-disk storage.
-/// In a full LSM implementation, SSTables are created when the
-/// MemTable exceeds its size threshold and needs to be flushed.
+/// SSTables are created when the MemTable exceeds its size threshold
+/// and needs to be flushed.
 ///
-/// ## Future Implementation
-/// - Block-based format with index
-/// - Bloom filter for fast negative lookups
-/// - Compression (LZ4/Snappy)
-/// - Multi-level compaction (L0 -> L1 -> ... -> LN)
+/// Properties are loaded on open. The index and filter are loaded on
+/// first use (or eagerly via [`SSTable::preload`]) and then kept in
+/// memory; data blocks are read from disk on demand and verified
+/// against their CRC32, or served from the table cache's block cache.
 pub struct SSTable {
     /// Path to the SSTable file on disk.
     path: PathBuf,
-    /// Number of entries in this SSTable.
-    entry_count: usize,
+    /// Id the table's blocks are cached under, unique to this open.
+    id: u64,
+    /// Shared cache of open file handles used for block reads.
+    table_cache: Arc<TableCache>,
     /// Size of the SSTable file in bytes.
     file_size: u64,
+    /// Locations of the filter, index and properties blocks.
+    footer: Footer,
+    /// Block index (one entry per data block), loaded lazily.
+    index: OnceLock<Vec<IndexEntry>>,
+    /// Filter policy and serialized filter, loaded lazily.
+    /// `None` if the policy is unknown.
+    filter: OnceLock<Option<TableFilter>>,
+    /// Table statistics.
+    properties: TableProperties,
+    /// Decompresses data blocks against the table's dictionary, prepared
+    /// on first use.
+    decompressor: OnceLock<BlockDecompressor>,
+    /// Lookups and scans served since the table was opened.
+    reads: AtomicU64,
+    /// When the table last served a read, in seconds since the Unix
+    /// epoch; 0 if it has not since it was opened.
+    last_read: AtomicU64,
+    /// Set once the table is no longer part of the current version; the
+    /// file is deleted when the last reference to the table is dropped.
+    obsolete: AtomicBool,
+}
+
+/// Returns the current time in seconds since the Unix epoch.
+pub(crate) fn unix_time_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// A table's membership filter together with the policy that built it.
+struct TableFilter {
+    policy: Box<dyn FilterPolicy>,
+    data: Vec<u8>,
 }
 
 impl SSTable {
-    /// Create a new SSTable reference (stub).
-    pub fn new(path: PathBuf) -> Self {
-        Self {
+    /// Open an existing SSTable, reading its footer and properties.
+    /// The file handle is obtained from (and stays in) `table_cache`.
+    pub fn open(path: PathBuf, table_cache: &Arc<TableCache>) -> Result<Self> {
+        let handle = table_cache.file(&path)?;
+        let mut file = handle.lock();
+        let file_size = file.size()?;
+        if file_size < FOOTER_LEN as u64 {
+            return Err(OblivionError::Corruption(format!(
+                "SSTable {:?} too small ({} bytes)",
+                path, file_size
+            )));
+        }
+
+        let footer = file.read_at(file_size - FOOTER_LEN as u64, FOOTER_LEN)?;
+        let magic = u64::from_le_bytes(footer[48..56].try_into().unwrap());
+        if magic != TABLE_MAGIC {
+            return Err(OblivionError::Corruption(format!(
+                "SSTable {:?} has bad magic {:#x}",
+                path, magic
+            )));
+        }
+        let footer = Footer {
+            filter: BlockHandle::decode(&footer[0..16]),
+            index: BlockHandle::decode(&footer[16..32]),
+            properties: BlockHandle::decode(&footer[32..48]),
+        };
+
+        // Properties
+        let props_block = read_block(file.as_mut(), footer.properties, file_size, &path)?;
+        let properties = bincode::deserialize(&props_block).or_else(|e| {
+            bincode::deserialize::<UndatedTableProperties>(&props_block)
+                .map(TableProperties::from)
+                .or_else(|_| {
+                    bincode::deserialize::<UncompressedTableProperties>(&props_block)
+                        .map(TableProperties::from)
+                })
+                .or_else(|_| {
+                    bincode::deserialize::<LegacyTableProperties>(&props_block)
+                        .map(TableProperties::from)
+                })
+                .map_err(|_| OblivionError::Corruption(format!("SSTable properties: {}", e)))
+        })?;
+
+        drop(file);
+
+        Ok(Self {
             path,
-            entry_count: 0,
-            file_size: 0,
+            id: table_cache.new_table_id(),
+            table_cache: Arc::clone(table_cache),
+            file_size,
+            footer,
+            index: OnceLock::new(),
+            filter: OnceLock::new(),
+            properties,
+            decompressor: OnceLock::new(),
+            reads: AtomicU64::new(0),
+            last_read: AtomicU64::new(0),
+            obsolete: AtomicBool::new(false),
+        })
+    }
+
+    /// Mark the table as replaced: its file is deleted once the last
+    /// reader (a snapshot, iterator or in-flight read holding the
+    /// version it belongs to) drops it.
+    pub(crate) fn mark_obsolete(&self) {
+        self.obsolete.store(true, Ordering::Release);
+    }
+
+    /// Load the index and filter blocks now instead of on first lookup.
+    pub fn preload(&self) -> Result<()> {
+        self.index()?;
+        self.filter()?;
+        Ok(())
+    }
+
+    /// Returns true if the index and filter are resident in memory.
+    pub fn is_preloaded(&self) -> bool {
+        self.index.get().is_some() && self.filter.get().is_some()
+    }
+
+    /// Returns the block index, loading it on first use.
+    fn index(&self) -> Result<&[IndexEntry]> {
+        if let Some(index) = self.index.get() {
+            return Ok(index);
         }
+
+        let index_block = self.read_block(self.footer.index)?;
+        let mut index = Vec::new();
+        let mut pos = 0;
+        while pos < index_block.len() {
+            let entry = index_block
+                .get(pos..pos + 4)
+                .and_then(|b| {
+                    let key_len = u32::from_le_bytes(b.try_into().unwrap()) as usize;
+                    let key = index_block.get(pos + 4..pos + 4 + key_len)?;
+                    let handle = index_block.get(pos + 4 + key_len..pos + 20 + key_len)?;
+                    pos += 20 + key_len;
+                    Some(IndexEntry {
+                        last_key: key.to_vec(),
+                        handle: BlockHandle::decode(handle),
+                    })
+                })
+                .ok_or_else(|| OblivionError::Corruption("truncated SSTable index".into()))?;
+            index.push(entry);
+        }
+        Ok(self.index.get_or_init(|| index))
+    }
+
+    /// Returns the filter, loading it on first use.
+    fn filter(&self) -> Result<Option<&TableFilter>> {
+        if let Some(filter) = self.filter.get() {
+            return Ok(filter.as_ref());
+        }
+
+        let filter_block = self.read_block(self.footer.filter)?;
+        let name_len = filter_block
+            .get(0..2)
+            .map(|b| u16::from_le_bytes(b.try_into().unwrap()) as usize)
+            .ok_or_else(|| OblivionError::Corruption("truncated SSTable filter".into()))?;
+        let name = filter_block
+            .get(2..2 + name_len)
+            .map(String::from_utf8_lossy)
+            .ok_or_else(|| OblivionError::Corruption("truncated SSTable filter".into()))?;
+        let policy = filter::policy_by_name(&name);
+        if policy.is_none() && !name.is_empty() {
+            log::warn!(
+                "SSTable {:?} uses unknown filter policy '{}', reading without filter",
+                self.path,
+                name
+            );
+        }
+        let filter = policy.map(|policy| TableFilter {
+            policy,
+            data: filter_block[2 + name_len..].to_vec(),
+        });
+        Ok(self.filter.get_or_init(|| filter).as_ref())
+    }
+
+    /// Flush a MemTable's entries to disk as an SSTable.
+    /// Entries must be sorted by key in byte order, and are re-sorted by
+    /// the table cache's comparator if it is a custom one; `None` values
+    /// are written as tombstones.
+    pub fn flush_from_memtable<'a, I>(
+        path: PathBuf,
+        entries: I,
+        filter_policy: Box<dyn FilterPolicy>,
+        table_cache: &Arc<TableCache>,
+    ) -> Result<Self>
+    where
+        I: IntoIterator<Item = (&'a [u8], Option<&'a [u8]>)>,
+    {
+        Self::flush_with_sequences(path, entries, filter_policy, table_cache, (0, 0), None)
+    }
+
+    /// Like [`flush_from_memtable`](Self::flush_from_memtable), recording
+    /// that the entries are the writes numbered `sequences.0` to
+    /// `sequences.1`, and compressing the data blocks against
+    /// `compression_dict` if one is given.
+    pub fn flush_with_sequences<'a, I>(
+        path: PathBuf,
+        entries: I,
+        filter_policy: Box<dyn FilterPolicy>,
+        table_cache: &Arc<TableCache>,
+        sequences: (u64, u64),
+        compression_dict: Option<&[u8]>,
+    ) -> Result<Self>
+    where
+        I: IntoIterator<Item = (&'a [u8], Option<&'a [u8]>)>,
+    {
+        let comparator = table_cache.comparator();
+        let mut builder = SSTableBuilder::create_in(
+            table_cache.env(),
+            path,
+            filter_policy,
+            table_cache.direct_io(),
+        )?;
+        builder.comparator = comparator.clone();
+        builder.set_sequence_range(sequences.0, sequences.1);
+        if let Some(dictionary) = compression_dict {
+            builder.set_compression_dict(dictionary.to_vec())?;
+        }
+        if comparator.is_bytewise() {
+            for (key, value) in entries {
+                builder.add(key, value)?;
+            }
+        } else {
+            let mut entries: Vec<_> = entries.into_iter().collect();
+            entries.sort_by(|a, b| comparator.compare(a.0, b.0));
+            for (key, value) in entries {
+                builder.add(key, value)?;
+            }
+        }
+        builder.finish(table_cache)
     }
 
     /// Returns the path to the SSTable file.
@@ -39,9 +787,9 @@ impl SSTable {
         &self.path
     }
 
-    /// Returns the number of entries.
+    /// Returns the number of entries (including tombstones).
     pub fn entry_count(&self) -> usize {
-        self.entry_count
+        self.properties.entry_count as usize
     }
 
     /// Returns the file size in bytes.
@@ -49,23 +797,763 @@ impl SSTable {
         self.file_size
     }
 
-    /// Flush a MemTable's entries to disk as an SSTable (stub).
-    /// In production, this would write a block-based format
-    /// with an index and optional bloom filter.
-    pub fn flush_from_memtable(
-        _path: PathBuf,
-        _entries: &[(Key, Value)],
-    ) -> crate::error::Result<Self> {
-        // TODO: Implement actual SSTable flush
-        // For now, this is a mock that simulates the flush
-        log::info!(
-            "SSTable flush triggered (stub) - {} entries",
-            _entries.len()
-        );
-        Ok(Self {
-            path: _path,
-            entry_count: _entries.len(),
-            file_size: 0,
+    /// Returns the table statistics.
+    pub fn properties(&self) -> &TableProperties {
+        &self.properties
+    }
+
+    /// Returns the lookups and range scans the table served since it was
+    /// opened. Compaction and other full-table reads are not counted.
+    pub fn num_reads(&self) -> u64 {
+        self.reads.load(Ordering::Relaxed)
+    }
+
+    /// Returns when the table last served a lookup or range scan, in
+    /// seconds since the Unix epoch; 0 if it has not since it was opened.
+    pub fn last_access_time(&self) -> u64 {
+        self.last_read.load(Ordering::Relaxed)
+    }
+
+    fn record_read(&self) {
+        self.reads.fetch_add(1, Ordering::Relaxed);
+        self.last_read.store(unix_time_secs(), Ordering::Relaxed);
+    }
+
+    /// Check if `key` lies within the table's key range. Answered from
+    /// the properties read at open, so keys outside it skip the filter
+    /// and index altogether.
+    pub fn covers(&self, key: &[u8]) -> bool {
+        let props = &self.properties;
+        let comparator = self.table_cache.comparator();
+        props.entry_count > 0
+            && comparator.compare(key, &props.min_key).is_ge()
+            && comparator.compare(key, &props.max_key).is_le()
+    }
+
+    /// Check if the table's key range meets `[lower, upper)` (either
+    /// bound open when `None`), from the properties alone.
+    pub fn overlaps(&self, lower: Option<&[u8]>, upper: Option<&[u8]>) -> bool {
+        let props = &self.properties;
+        let comparator = self.table_cache.comparator();
+        props.entry_count > 0
+            && lower.is_none_or(|lower| comparator.compare(&props.max_key, lower).is_ge())
+            && upper.is_none_or(|upper| comparator.compare(&props.min_key, upper).is_lt())
+    }
+
+    /// Check if a key **may** be stored in this table.
+    /// Tables without a usable (or readable) filter always answer `true`.
+    pub fn may_contain(&self, key: &[u8]) -> bool {
+        match self.filter() {
+            Ok(filter) => filter.is_none_or(|f| f.policy.may_contain(&f.data, key)),
+            Err(e) => {
+                log::warn!("Filter of {:?} unreadable: {}", self.path, e);
+                true
+            }
+        }
+    }
+
+    /// Returns the name of the filter policy, if the table has a usable filter.
+    pub fn filter_policy_name(&self) -> Option<&str> {
+        self.filter().ok().flatten().map(|f| f.policy.name())
+    }
+
+    /// Returns the locations of the metadata blocks.
+    pub fn footer(&self) -> Footer {
+        self.footer
+    }
+
+    /// Returns the size in bytes of the serialized filter (0 when the
+    /// table has no usable filter).
+    pub fn filter_size(&self) -> Result<usize> {
+        Ok(self.filter()?.map_or(0, |f| f.data.len()))
+    }
+
+    /// Returns the last key and location of every data block, in order.
+    pub fn block_index(&self) -> Result<Vec<(Key, BlockHandle)>> {
+        Ok(self
+            .index()?
+            .iter()
+            .map(|entry| (entry.last_key.clone(), entry.handle))
+            .collect())
+    }
+
+    /// Read the records of the data block at `handle`, including
+    /// tombstones, verifying its CRC if asked.
+    pub fn read_block_records(
+        &self,
+        handle: BlockHandle,
+        verify_checksums: bool,
+    ) -> Result<Vec<(Key, Option<Value>)>> {
+        let block = self.read_data_block(handle, verify_checksums)?;
+        let block = self.decompress(block)?;
+        BlockIter::new(&block)
+            .map(|record| record.map(|(k, v)| (k.to_vec(), v.map(|v| v.to_vec()))))
+            .collect()
+    }
+
+    /// Look up a key in this table.
+    /// - `None` → key is not stored here
+    /// - `Some(None)` → key is deleted (tombstone)
+    /// - `Some(Some(v))` → key maps to `v`
+    pub fn get(&self, key: &[u8]) -> Result<Option<Option<Value>>> {
+        self.get_with(key, true)
+    }
+
+    /// Look up a key, optionally skipping the data block CRC check.
+    pub fn get_with(&self, key: &[u8], verify_checksums: bool) -> Result<Option<Option<Value>>> {
+        self.get_traced(key, verify_checksums)
+            .map(|(entry, _)| entry)
+    }
+
+    /// Like [`get_with`](Self::get_with), also returning the number of
+    /// data blocks read (0 when the index rules the key out).
+    pub fn get_traced(
+        &self,
+        key: &[u8],
+        verify_checksums: bool,
+    ) -> Result<(Option<Option<Value>>, usize)> {
+        self.find(key, verify_checksums, true, |_, value| {
+            value.map(<[u8]>::to_vec)
+        })
+    }
+
+    /// Like [`get_traced`](Self::get_traced), returning the block the
+    /// value was found in and its range there instead of a copy of it.
+    /// With `fill_cache` off, a block read from the file is not added
+    /// to the block cache.
+    pub fn get_pinned_traced(
+        &self,
+        key: &[u8],
+        verify_checksums: bool,
+        fill_cache: bool,
+    ) -> Result<(Option<Option<BlockValue>>, usize)> {
+        self.find(key, verify_checksums, fill_cache, |block, value| {
+            value.map(|value| {
+                let start = value.as_ptr() as usize - block.as_ptr() as usize;
+                (Arc::clone(block), start..start + value.len())
+            })
+        })
+    }
+
+    /// Like [`get_pinned_traced`](Self::get_pinned_traced), returning
+    /// the length of the value only.
+    pub fn value_len_traced(
+        &self,
+        key: &[u8],
+        verify_checksums: bool,
+        fill_cache: bool,
+    ) -> Result<(Option<Option<usize>>, usize)> {
+        self.find(key, verify_checksums, fill_cache, |_, value| {
+            value.map(<[u8]>::len)
         })
     }
+
+    /// Find the record of `key` and pass its block and value (`None`
+    /// for a tombstone) to `read`. Returns what `read` made of it, if
+    /// the key is stored here, and the number of data blocks read.
+    fn find<T>(
+        &self,
+        key: &[u8],
+        verify_checksums: bool,
+        fill_cache: bool,
+        read: impl FnOnce(&Arc<[u8]>, Option<&[u8]>) -> T,
+    ) -> Result<(Option<T>, usize)> {
+        self.record_read();
+        let comparator = self.table_cache.comparator();
+        let index = self.index()?;
+        let block_idx =
+            index.partition_point(|entry| comparator.compare(&entry.last_key, key).is_lt());
+        let Some(entry) = index.get(block_idx) else {
+            return Ok((None, 0));
+        };
+
+        let block = self.read_indexed_block(entry, verify_checksums, fill_cache)?;
+        for record in BlockIter::new(&block) {
+            let (k, v) = record?;
+            if k == key {
+                return Ok((Some(read(&block, v)), 1));
+            }
+            if comparator.compare(k, key).is_gt() {
+                break;
+            }
+        }
+        Ok((None, 1))
+    }
+
+    /// Read every record in key order, including tombstones. Unlike
+    /// lookups and range scans, this is not counted as an access (see
+    /// [`num_reads`](Self::num_reads)), and blocks it reads from the
+    /// file are not added to the block cache.
+    pub fn scan(&self) -> Result<Vec<(Key, Option<Value>)>> {
+        self.read_range(None, None, usize::MAX, true, false)
+    }
+
+    /// Read the records with `lower <= key < upper` in key order,
+    /// including tombstones. Blocks outside the range are not read.
+    pub fn scan_range(
+        &self,
+        lower: Option<&[u8]>,
+        upper: Option<&[u8]>,
+        verify_checksums: bool,
+    ) -> Result<Vec<(Key, Option<Value>)>> {
+        self.scan_range_limited(lower, upper, usize::MAX, verify_checksums, true)
+    }
+
+    /// Like [`scan_range`](Self::scan_range), stopping after the first
+    /// `limit` records; blocks past them are not read. With `fill_cache`
+    /// off, blocks read from the file are not added to the block cache.
+    pub fn scan_range_limited(
+        &self,
+        lower: Option<&[u8]>,
+        upper: Option<&[u8]>,
+        limit: usize,
+        verify_checksums: bool,
+        fill_cache: bool,
+    ) -> Result<Vec<(Key, Option<Value>)>> {
+        self.record_read();
+        self.read_range(lower, upper, limit, verify_checksums, fill_cache)
+    }
+
+    /// Read up to `limit` records with `lower <= key < upper`.
+    fn read_range(
+        &self,
+        lower: Option<&[u8]>,
+        upper: Option<&[u8]>,
+        limit: usize,
+        verify_checksums: bool,
+        fill_cache: bool,
+    ) -> Result<Vec<(Key, Option<Value>)>> {
+        let comparator = self.table_cache.comparator();
+        let index = self.index()?;
+        let first_block = lower.map_or(0, |lower| {
+            index.partition_point(|e| comparator.compare(&e.last_key, lower).is_lt())
+        });
+
+        let mut entries = Vec::new();
+        for entry in &index[first_block..] {
+            let block = self.read_indexed_block(entry, verify_checksums, fill_cache)?;
+            for record in BlockIter::new(&block) {
+                let (k, v) = record?;
+                if lower.is_some_and(|lower| comparator.compare(k, lower).is_lt()) {
+                    continue;
+                }
+                if upper.is_some_and(|upper| comparator.compare(k, upper).is_ge())
+                    || entries.len() >= limit
+                {
+                    return Ok(entries);
+                }
+                entries.push((k.to_vec(), v.map(|v| v.to_vec())));
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Read every record in key order, including tombstones, like
+    /// [`scan`](Self::scan), but reading runs of adjacent data blocks
+    /// `readahead` bytes at a time. While one run is decoded the OS is
+    /// asked to fetch the next, so a full scan of a table costs a few
+    /// large sequential reads rather than one small read per block.
+    /// A `readahead` of 0 reads block by block.
+    pub fn scan_readahead(&self, readahead: usize) -> Result<Vec<(Key, Option<Value>)>> {
+        if readahead == 0 {
+            return self.scan();
+        }
+        let index = self.index()?;
+        for entry in index {
+            check_handle(entry.handle, self.file_size, &self.path)?;
+        }
+        let runs = block_runs(index, readahead as u64);
+        let mut entries = Vec::new();
+        for (i, &(first, last)) in runs.iter().enumerate() {
+            if let Some(&(next_first, next_last)) = runs.get(i + 1) {
+                let (offset, len) = run_span(&index[next_first..=next_last]);
+                self.advise_will_need(offset, len);
+            }
+            let (start, len) = run_span(&index[first..=last]);
+            let data = self.read_raw(start, len as usize)?;
+            for entry in &index[first..=last] {
+                let at = (entry.handle.offset - start) as usize;
+                let raw = data[at..at + entry.handle.size as usize].to_vec();
+                let block = verify_block(raw, entry.handle, &self.path, true)?;
+                let block = self.check_indexed_block(entry, self.decompress(block)?)?;
+                for record in BlockIter::new(&block) {
+                    let (k, v) = record?;
+                    entries.push((k.to_vec(), v.map(|v| v.to_vec())));
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Read the data block `entry` points to, decoded, from the block
+    /// cache or else from the file, verifying its CRC if asked. With
+    /// paranoid checks the CRC is always verified, and the records must
+    /// decode, ascend and end at the key the index records. Blocks read
+    /// from the file are cached if `fill_cache` is set and their CRC
+    /// was verified, so a cached block never skips a requested check.
+    fn read_indexed_block(
+        &self,
+        entry: &IndexEntry,
+        verify_checksums: bool,
+        fill_cache: bool,
+    ) -> Result<Arc<[u8]>> {
+        let offset = entry.handle.offset;
+        if let Some(block) = self.table_cache.cached_block(self.id, offset) {
+            return Ok(block);
+        }
+        let verify = verify_checksums || self.table_cache.paranoid_checks();
+        let block = self.read_data_block(entry.handle, verify)?;
+        let block: Arc<[u8]> = self
+            .check_indexed_block(entry, self.decompress(block)?)?
+            .into();
+        if fill_cache && verify {
+            self.table_cache
+                .cache_block(self.id, offset, Arc::clone(&block));
+        }
+        Ok(block)
+    }
+
+    /// Decompress a data block if the table has a compression dictionary.
+    fn decompress(&self, block: Vec<u8>) -> Result<Vec<u8>> {
+        let dictionary = &self.properties.compression_dict;
+        if dictionary.is_empty() {
+            return Ok(block);
+        }
+        let decompressor = match self.decompressor.get() {
+            Some(decompressor) => decompressor,
+            None => {
+                let decompressor = BlockDecompressor::new(dictionary)?;
+                self.decompressor.get_or_init(|| decompressor)
+            }
+        };
+        decompressor.decompress(&block)
+    }
+
+    /// With paranoid checks, verify that the records of `block`, read
+    /// from `entry`, decode, ascend and end at the key the index records.
+    fn check_indexed_block(&self, entry: &IndexEntry, block: Vec<u8>) -> Result<Vec<u8>> {
+        if !self.table_cache.paranoid_checks() {
+            return Ok(block);
+        }
+        let comparator = self.table_cache.comparator();
+        let mut last: Option<&[u8]> = None;
+        for record in BlockIter::new(&block) {
+            let (key, _) = record?;
+            if last.is_some_and(|last| comparator.compare(last, key).is_ge()) {
+                return Err(OblivionError::Corruption(format!(
+                    "SSTable {:?} block at offset {} has keys out of order",
+                    self.path, entry.handle.offset
+                )));
+            }
+            last = Some(key);
+        }
+        if last != Some(entry.last_key.as_slice()) {
+            return Err(OblivionError::Corruption(format!(
+                "SSTable {:?} block at offset {} does not end at its index key",
+                self.path, entry.handle.offset
+            )));
+        }
+        Ok(block)
+    }
+
+    /// Read and verify a block through the table cache.
+    fn read_block(&self, handle: BlockHandle) -> Result<Vec<u8>> {
+        self.read_data_block(handle, true)
+    }
+
+    /// Read a block through the table cache, verifying its CRC if asked.
+    fn read_data_block(&self, handle: BlockHandle, verify_checksums: bool) -> Result<Vec<u8>> {
+        check_handle(handle, self.file_size, &self.path)?;
+        let data = self.read_raw(handle.offset, handle.size as usize)?;
+        verify_block(data, handle, &self.path, verify_checksums)
+    }
+
+    /// Read `len` bytes at `offset` through the table cache.
+    fn read_raw(&self, offset: u64, len: usize) -> Result<Vec<u8>> {
+        let file = self.table_cache.file(&self.path)?;
+        let mut file = file.lock();
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        if let (Some(ring), Some(local)) = (self.table_cache.ring(), file.as_file()) {
+            return Ok(ring.read_at(local, offset, len)?);
+        }
+        Ok(file.read_at(offset, len)?)
+    }
+
+    /// Hint that `len` bytes at `offset` will be read soon, for tables
+    /// on the local file system.
+    fn advise_will_need(&self, offset: u64, len: u64) {
+        if let Ok(file) = self.table_cache.file(&self.path) {
+            if let Some(local) = file.lock().as_file() {
+                io::advise_will_need(local, offset, len);
+            }
+        }
+    }
+}
+
+impl Drop for SSTable {
+    fn drop(&mut self) {
+        if !self.obsolete.load(Ordering::Acquire) {
+            return;
+        }
+        self.table_cache.evict(&self.path);
+        match self.table_cache.env().remove_file(&self.path) {
+            Ok(()) => log::debug!("Deleted obsolete SSTable {:?}", self.path),
+            // Left for the next open, which removes unlisted tables
+            Err(e) => log::warn!("Failed to delete obsolete SSTable {:?}: {}", self.path, e),
+        }
+    }
+}
+
+/// Read a block of the `file_size` bytes long `file` and verify its
+/// CRC32 trailer. Returns the payload only.
+fn read_block(
+    file: &mut dyn RandomAccessFile,
+    handle: BlockHandle,
+    file_size: u64,
+    path: &Path,
+) -> Result<Vec<u8>> {
+    read_block_with(file, handle, file_size, path, true)
+}
+
+/// Read a block, checking its CRC32 trailer only if `verify` is set.
+fn read_block_with(
+    file: &mut dyn RandomAccessFile,
+    handle: BlockHandle,
+    file_size: u64,
+    path: &Path,
+    verify: bool,
+) -> Result<Vec<u8>> {
+    check_handle(handle, file_size, path)?;
+    let data = file.read_at(handle.offset, handle.size as usize)?;
+    verify_block(data, handle, path, verify)
+}
+
+/// Group the data blocks of `index` into runs of adjacent blocks read
+/// together, each spanning at most `readahead` bytes unless a single
+/// block is larger. Returns the first and last index entry of each run.
+fn block_runs(index: &[IndexEntry], readahead: u64) -> Vec<(usize, usize)> {
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    for (i, entry) in index.iter().enumerate() {
+        if let Some((first, last)) = runs.last_mut() {
+            let (start, len) = run_span(&index[*first..=*last]);
+            let adjacent = start + len == entry.handle.offset;
+            if adjacent && len + entry.handle.size <= readahead {
+                *last = i;
+                continue;
+            }
+        }
+        runs.push((i, i));
+    }
+    runs
+}
+
+/// Offset and length of the file range holding the adjacent `blocks`.
+fn run_span(blocks: &[IndexEntry]) -> (u64, u64) {
+    let start = blocks.first().map_or(0, |entry| entry.handle.offset);
+    let end = blocks
+        .last()
+        .map_or(start, |entry| entry.handle.offset + entry.handle.size);
+    (start, end - start)
+}
+
+/// Reject block handles too small to hold a CRC32 trailer or reaching
+/// past the end of the `file_size` bytes long table, so a corrupt index
+/// or footer fails the read instead of allocating whatever it claims.
+fn check_handle(handle: BlockHandle, file_size: u64, path: &Path) -> Result<()> {
+    let end = handle.offset.checked_add(handle.size);
+    if handle.size < 4 || end.is_none_or(|end| end > file_size) {
+        return Err(OblivionError::Corruption(format!(
+            "SSTable {:?} has an invalid block handle (offset {}, size {}, file size {})",
+            path, handle.offset, handle.size, file_size
+        )));
+    }
+    Ok(())
+}
+
+/// Strip a block's CRC32 trailer, checking it only if `verify` is set.
+fn verify_block(
+    mut data: Vec<u8>,
+    handle: BlockHandle,
+    path: &Path,
+    verify: bool,
+) -> Result<Vec<u8>> {
+    let payload_len = data.len() - 4;
+    let stored_crc = u32::from_le_bytes(data[payload_len..].try_into().unwrap());
+    data.truncate(payload_len);
+    if verify && crc32fast::hash(&data) != stored_crc {
+        return Err(OblivionError::Corruption(format!(
+            "SSTable {:?} block at offset {} failed CRC check",
+            path, handle.offset
+        )));
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::engine::filter::{FilterType, DEFAULT_FILTER_FPR};
+
+    fn table_cache() -> Arc<TableCache> {
+        Arc::new(TableCache::new(16))
+    }
+
+    fn build_table(path: PathBuf, n: usize, filter_type: FilterType) -> SSTable {
+        let entries: Vec<(Vec<u8>, Option<Vec<u8>>)> = (0..n)
+            .map(|i| {
+                let key = format!("key_{:05}", i).into_bytes();
+                let value = (i % 10 != 0).then(|| format!("value_{}", i).into_bytes());
+                (key, value)
+            })
+            .collect();
+        SSTable::flush_from_memtable(
+            path,
+            entries.iter().map(|(k, v)| (k.as_slice(), v.as_deref())),
+            filter_type.policy(DEFAULT_FILTER_FPR),
+            &table_cache(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_write_and_read_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let table = build_table(dir.path().join("t.sst"), 2000, FilterType::Bloom);
+
+        assert_eq!(table.entry_count(), 2000);
+        assert!(table.properties().num_data_blocks > 1);
+        assert_eq!(
+            table.get(b"key_00001").unwrap(),
+            Some(Some(b"value_1".to_vec()))
+        );
+        assert_eq!(
+            table.get(b"key_01999").unwrap().unwrap().unwrap(),
+            b"value_1999"
+        );
+        assert_eq!(table.get(b"key_99999").unwrap(), None);
+        assert_eq!(table.get(b"aaa").unwrap(), None);
+    }
+
+    #[test]
+    fn test_tombstones_persisted() {
+        let dir = tempfile::tempdir().unwrap();
+        let table = build_table(dir.path().join("t.sst"), 100, FilterType::Bloom);
+
+        // Every 10th key is a tombstone
+        assert_eq!(table.get(b"key_00010").unwrap(), Some(None));
+        assert_eq!(table.properties().tombstone_count, 10);
+
+        let scanned = table.scan().unwrap();
+        assert_eq!(scanned.len(), 100);
+        assert!(scanned.windows(2).all(|w| w[0].0 < w[1].0));
+    }
+
+    #[test]
+    fn test_scan_range_bounds() {
+        let dir = tempfile::tempdir().unwrap();
+        let table = build_table(dir.path().join("t.sst"), 2000, FilterType::Bloom);
+
+        let range = table
+            .scan_range(Some(b"key_01000"), Some(b"key_01100"), true)
+            .unwrap();
+        assert_eq!(range.len(), 100);
+        assert_eq!(range[0].0, b"key_01000");
+        assert_eq!(range[99].0, b"key_01099");
+
+        assert_eq!(table.scan_range(Some(b"zzz"), None, true).unwrap().len(), 0);
+        assert_eq!(
+            table
+                .scan_range(None, Some(b"key_00010"), true)
+                .unwrap()
+                .len(),
+            10
+        );
+    }
+
+    #[test]
+    fn test_reopen_records_filter_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("t.sst");
+        build_table(path.clone(), 500, FilterType::Ribbon);
+
+        let table = SSTable::open(path, &table_cache()).unwrap();
+        assert_eq!(table.filter_policy_name(), Some("oblivion.RibbonFilter"));
+        assert_eq!(table.properties().min_key, b"key_00000");
+        assert_eq!(table.properties().max_key, b"key_00499");
+        assert!(table.properties().creation_time > 0);
+        assert!(table.may_contain(b"key_00123"));
+    }
+
+    #[test]
+    fn test_index_and_filter_loaded_lazily() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("t.sst");
+        let built = build_table(path.clone(), 500, FilterType::Bloom);
+        assert!(built.is_preloaded());
+
+        let table = SSTable::open(path.clone(), &table_cache()).unwrap();
+        assert!(!table.is_preloaded());
+        assert_eq!(table.properties().entry_count, 500);
+        assert!(table.may_contain(b"key_00007"));
+        assert!(table.get(b"key_00007").unwrap().is_some());
+        assert!(table.is_preloaded());
+
+        let table = SSTable::open(path, &table_cache()).unwrap();
+        table.preload().unwrap();
+        assert!(table.is_preloaded());
+    }
+
+    #[test]
+    fn test_unknown_filter_policy_still_readable() {
+        struct CustomPolicy;
+        impl FilterPolicy for CustomPolicy {
+            fn name(&self) -> &str {
+                "custom.FutureFilter"
+            }
+            fn create_filter(&self, _keys: &[&[u8]]) -> Vec<u8> {
+                vec![1, 2, 3]
+            }
+            fn may_contain(&self, _filter: &[u8], _key: &[u8]) -> bool {
+                false
+            }
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("t.sst");
+        SSTable::flush_from_memtable(
+            path.clone(),
+            [(b"a".as_slice(), Some(b"1".as_slice()))],
+            Box::new(CustomPolicy),
+            &table_cache(),
+        )
+        .unwrap();
+
+        let table = SSTable::open(path, &table_cache()).unwrap();
+        assert_eq!(table.filter_policy_name(), None);
+        assert!(table.may_contain(b"a"));
+        assert_eq!(table.get(b"a").unwrap(), Some(Some(b"1".to_vec())));
+    }
+
+    #[test]
+    fn test_corruption_detected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("t.sst");
+        build_table(path.clone(), 50, FilterType::Bloom);
+
+        let mut data = fs::read(&path).unwrap();
+        data[10] ^= 0xFF;
+        fs::write(&path, &data).unwrap();
+
+        let table = SSTable::open(path, &table_cache()).unwrap();
+        assert!(matches!(
+            table.get(b"key_00001"),
+            Err(OblivionError::Corruption(_))
+        ));
+        // Skipping verification reads the (damaged) block anyway
+        assert!(table.get_with(b"key_00049", false).is_ok());
+
+        // Unless the table cache runs paranoid checks
+        let paranoid = Arc::new(TableCache::new(16).with_paranoid_checks(true));
+        let table = SSTable::open(dir.path().join("t.sst"), &paranoid).unwrap();
+        assert!(matches!(
+            table.get_with(b"key_00049", false),
+            Err(OblivionError::Corruption(_))
+        ));
+        assert!(table.scan_range(None, None, false).is_err());
+        assert!(table.scan_readahead(1024 * 1024).is_err());
+    }
+
+    #[test]
+    fn test_oversized_block_handle_is_corruption() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("t.sst");
+        build_table(path.clone(), 50, FilterType::Bloom);
+
+        // Claim a properties block of 4 GiB, then one wrapping past u64
+        let mut data = fs::read(&path).unwrap();
+        let size_at = data.len() - FOOTER_LEN + 40;
+        for size in [4u64 << 30, u64::MAX] {
+            data[size_at..size_at + 8].copy_from_slice(&size.to_le_bytes());
+            fs::write(&path, &data).unwrap();
+            assert!(matches!(
+                SSTable::open(path.clone(), &table_cache()),
+                Err(OblivionError::Corruption(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_scan_readahead_reads_runs_of_blocks() {
+        let dir = tempfile::tempdir().unwrap();
+        let table = build_table(dir.path().join("t.sst"), 2000, FilterType::Bloom);
+        let index = table.index().unwrap();
+        assert!(index.len() > 4);
+
+        let expected = table.scan().unwrap();
+        for readahead in [0, 1, 10_000, 1024 * 1024] {
+            assert_eq!(table.scan_readahead(readahead).unwrap(), expected);
+        }
+
+        // Runs cover every block once, in order, within the readahead
+        // (a block larger than it is read alone)
+        let runs = block_runs(index, 10_000);
+        assert!(runs.len() > 1 && runs.len() < index.len());
+        assert_eq!(runs.first().unwrap().0, 0);
+        assert_eq!(runs.last().unwrap().1, index.len() - 1);
+        for pair in runs.windows(2) {
+            assert_eq!(pair[0].1 + 1, pair[1].0);
+        }
+        for &(first, last) in &runs {
+            assert!(first == last || run_span(&index[first..=last]).1 <= 10_000);
+        }
+        assert_eq!(block_runs(index, 1).len(), index.len());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_dictionary_compressed_table_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let entries: Vec<(Vec<u8>, Option<Vec<u8>>)> = (0..2000)
+            .map(|i| {
+                let key = format!("key_{:05}", i).into_bytes();
+                let value = (i % 10 != 0)
+                    .then(|| format!("{{\"user\":{},\"plan\":\"basic\"}}", i % 50).into_bytes());
+                (key, value)
+            })
+            .collect();
+        let refs: Vec<(&[u8], Option<&[u8]>)> = entries
+            .iter()
+            .map(|(k, v)| (k.as_slice(), v.as_deref()))
+            .collect();
+        let dictionary = crate::engine::compression::train_dictionary(&refs, 4096).unwrap();
+
+        let cache = table_cache();
+        let flush = |name: &str, dictionary: Option<&[u8]>| {
+            SSTable::flush_with_sequences(
+                dir.path().join(name),
+                refs.iter().copied(),
+                FilterType::Bloom.policy(DEFAULT_FILTER_FPR),
+                &cache,
+                (1, 2000),
+                dictionary,
+            )
+            .unwrap()
+        };
+        let plain = flush("plain.sst", None);
+        let compressed = flush("zstd.sst", Some(&dictionary));
+        assert!(compressed.properties().data_size * 2 < plain.properties().data_size);
+
+        let reopened = SSTable::open(dir.path().join("zstd.sst"), &cache).unwrap();
+        assert_eq!(reopened.properties().compression_dict, dictionary);
+        assert_eq!(
+            reopened.get(b"key_00001").unwrap(),
+            Some(entries[1].1.clone())
+        );
+        assert_eq!(reopened.get(b"key_00010").unwrap(), Some(None));
+        assert_eq!(reopened.scan().unwrap(), entries);
+        assert_eq!(reopened.scan_readahead(16 * 1024).unwrap(), entries);
+        assert!(plain.properties().compression_dict.is_empty());
+    }
 }
@@ -0,0 +1,285 @@
+//! OBLIVION - User Timestamps
+//! Values kept per key and timestamp, as RocksDB's user-defined
+//! timestamps, so a key can be read as it was at any point of its
+//! history ("time travel") instead of only at its latest write.
+//!
+//! A timestamped write is its own entry in the database's data-type
+//! range (see [`DATA_TYPE_PREFIX`](super::database::DATA_TYPE_PREFIX)),
+//! the key followed by a fixed-width suffix the engine manages:
+//!
+//! ```text
+//! \xff\xfftv <encode(&key)> <!ts: u64 BE>  -> 0x01 value | 0x00 (deleted)
+//! ```
+//!
+//! The suffix is the timestamp's bitwise complement, so the versions of
+//! a key sort newest first and `get_at(key, ts)` is the first entry at
+//! or after `<!ts>`. Timestamps are chosen by the caller; writing a key
+//! twice at one timestamp replaces that version. Timestamped keys are
+//! not plain keys and are never seen by `get`, `keys` or scans of a
+//! [`Database`].
+//!
+//! ## Retention
+//! Timestamps are milliseconds since the Unix epoch. With
+//! `history_retention_ms` set, compaction drops every version shadowed
+//! by a newer one at or before the horizon (now minus the retention),
+//! so reads as of the horizon or later are unchanged while reads of
+//! earlier times may no longer find the versions they saw.
+//!
+//! ## Example
+//! ```no_run
+//! use oblivion::config::Config;
+//! use oblivion::engine::Oblivion;
+//!
+//! let engine = Oblivion::open(Config::new("./data")).unwrap();
+//! engine.put_at(b"price", b"10".to_vec(), 1_000).unwrap();
+//! engine.put_at(b"price", b"12".to_vec(), 2_000).unwrap();
+//! assert_eq!(engine.get_at(b"price", 1_500).unwrap(), Some(b"10".to_vec()));
+//! assert_eq!(engine.get_at(b"price", 999).unwrap(), None);
+//! ```
+
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::engine::batch::WriteBatch;
+use crate::engine::database::{Database, DATABASE_PREFIX};
+use crate::engine::keys::{decode, encode};
+use crate::engine::options::{prefix_end, ReadOptions};
+use crate::error::Result;
+use crate::types::{Key, Value};
+
+/// First bytes of every timestamped entry, within a database.
+pub const TIMESTAMPED_PREFIX: &[u8] = b"\xff\xfftv";
+
+/// Width of the timestamp suffix.
+const SUFFIX_LEN: usize = 8;
+
+/// Rows read per page while looking up a version.
+const VERSION_PAGE_SIZE: usize = 16;
+
+/// First byte of a version holding a value.
+const LIVE: u8 = 1;
+
+/// The whole of a version recording a delete.
+const DELETED: &[u8] = &[0];
+
+impl Database<'_> {
+    /// Set `key` to `value` as of `ts`.
+    pub fn put_at(&self, key: &[u8], value: Value, ts: u64) -> Result<()> {
+        let mut batch = WriteBatch::new();
+        batch.put(
+            self.stored(&version_key(key, ts)),
+            [&[LIVE], &value[..]].concat(),
+        );
+        self.engine().write(batch)
+    }
+
+    /// Delete `key` as of `ts`; reads at `ts` or later (until a newer
+    /// version) find nothing.
+    pub fn delete_at(&self, key: &[u8], ts: u64) -> Result<()> {
+        let mut batch = WriteBatch::new();
+        batch.put(self.stored(&version_key(key, ts)), DELETED.to_vec());
+        self.engine().write(batch)
+    }
+
+    /// Returns the value of `key` as of `ts`: that of its newest version
+    /// at or before `ts`.
+    pub fn get_at(&self, key: &[u8], ts: u64) -> Result<Option<Value>> {
+        let prefix = self.stored(&[TIMESTAMPED_PREFIX, &encode(key)].concat());
+        let mut opts = ReadOptions::new();
+        opts.lower_bound = Some([&prefix[..], &(!ts).to_be_bytes()].concat());
+        opts.upper_bound = prefix_end(&prefix);
+        opts.snapshot = Some(self.engine().snapshot());
+        loop {
+            // Pages may come back empty after trimmed versions
+            let (rows, resume) = self.engine().scan_page(&opts, VERSION_PAGE_SIZE)?;
+            if let Some((_, version)) = rows.into_iter().next() {
+                return Ok(live_value(version));
+            }
+            match resume {
+                Some(resume) => opts.lower_bound = Some(resume),
+                None => return Ok(None),
+            }
+        }
+    }
+
+    /// Returns the keys within the bounds of `opts` with their values as
+    /// of `ts`, in key order; keys deleted or not yet written at `ts`
+    /// are left out.
+    pub fn scan_at(&self, opts: &ReadOptions, ts: u64) -> Result<Vec<(Key, Value)>> {
+        let prefix = self.stored(TIMESTAMPED_PREFIX);
+        let mut stored = opts.clone();
+        stored.lower_bound = Some(match &opts.lower_bound {
+            Some(lower) => [&prefix[..], &encode(lower)].concat(),
+            None => prefix.clone(),
+        });
+        stored.upper_bound = match &opts.upper_bound {
+            Some(upper) => Some([&prefix[..], &encode(upper)].concat()),
+            None => prefix_end(&prefix),
+        };
+        let mut rows = Vec::new();
+        let mut done: Option<Key> = None;
+        for (entry, version) in self.engine().scan_opt(&stored)? {
+            let Some(end) = entry.len().checked_sub(SUFFIX_LEN) else {
+                continue;
+            };
+            let (encoded, suffix) = (&entry[prefix.len()..end], &entry[end..]);
+            if done.as_deref() == Some(encoded) || timestamp(suffix) > ts {
+                continue;
+            }
+            done = Some(encoded.to_vec());
+            if let Some(value) = live_value(version) {
+                rows.push((decode(encoded)?, value));
+            }
+        }
+        Ok(rows)
+    }
+}
+
+/// The key of the version of `key` at `ts`, within a database.
+fn version_key(key: &[u8], ts: u64) -> Key {
+    [TIMESTAMPED_PREFIX, &encode(key), &(!ts).to_be_bytes()].concat()
+}
+
+/// The timestamp a version key's suffix stands for.
+fn timestamp(suffix: &[u8]) -> u64 {
+    let mut bytes = [0; SUFFIX_LEN];
+    bytes.copy_from_slice(suffix);
+    !u64::from_be_bytes(bytes)
+}
+
+/// The value a version holds, or `None` if it records a delete.
+fn live_value(mut version: Value) -> Option<Value> {
+    (version.first() == Some(&LIVE)).then(|| version.split_off(1))
+}
+
+/// Split a stored key of any database into the part shared by all
+/// versions of its key and its timestamp, or `None` if it is not
+/// timestamped.
+fn split_version(stored: &[u8]) -> Option<(&[u8], u64)> {
+    let local = match stored.strip_prefix(DATABASE_PREFIX) {
+        Some(tagged) => tagged.get(2..)?,
+        None => stored,
+    };
+    if !local.starts_with(TIMESTAMPED_PREFIX) || local.len() < TIMESTAMPED_PREFIX.len() + SUFFIX_LEN
+    {
+        return None;
+    }
+    let (group, suffix) = stored.split_at(stored.len() - SUFFIX_LEN);
+    Some((group, timestamp(suffix)))
+}
+
+/// The oldest time reads must still see as written, with
+/// `retention_ms` of history kept; `None` keeps all history.
+pub(crate) fn retention_horizon(retention_ms: u64) -> Option<u64> {
+    if retention_ms == 0 {
+        return None;
+    }
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    Some(now.saturating_sub(retention_ms))
+}
+
+/// Turn into tombstones the versions of merged compaction input that a
+/// newer version at or before `horizon` shadows.
+///
+/// Only versions shadowed within `entries` are dropped: a version whose
+/// successor sits in a table outside the compaction is kept.
+pub(crate) fn trim_history(entries: &mut BTreeMap<Key, Option<Value>>, horizon: u64) {
+    let mut settled: Option<Key> = None;
+    for (key, value) in entries.iter_mut() {
+        let Some((group, ts)) = split_version(key) else {
+            continue;
+        };
+        if settled.as_deref() == Some(group) {
+            *value = None;
+        } else if ts <= horizon && value.is_some() {
+            settled = Some(group.to_vec());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::engine::database::DATA_TYPE_PREFIX;
+    use crate::engine::Oblivion;
+
+    #[test]
+    fn test_reads_as_of_a_time() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = Oblivion::open(Config::new(dir.path())).unwrap();
+        let db = engine.database(0).unwrap();
+        assert!(TIMESTAMPED_PREFIX.starts_with(DATA_TYPE_PREFIX));
+
+        db.put_at(b"k", b"v10".to_vec(), 10).unwrap();
+        db.put_at(b"k", b"v20".to_vec(), 20).unwrap();
+        db.delete_at(b"k", 30).unwrap();
+        db.put_at(b"k", b"v40".to_vec(), 40).unwrap();
+        db.put_at(b"k\x00", b"other".to_vec(), 5).unwrap();
+        db.put_at(b"j", b"j15".to_vec(), 15).unwrap();
+
+        assert_eq!(db.get_at(b"k", 9).unwrap(), None);
+        assert_eq!(db.get_at(b"k", 10).unwrap(), Some(b"v10".to_vec()));
+        assert_eq!(db.get_at(b"k", 29).unwrap(), Some(b"v20".to_vec()));
+        assert_eq!(db.get_at(b"k", 35).unwrap(), None);
+        assert_eq!(db.get_at(b"k", u64::MAX).unwrap(), Some(b"v40".to_vec()));
+        assert_eq!(db.get_at(b"k\x00", 5).unwrap(), Some(b"other".to_vec()));
+
+        let at = |ts| db.scan_at(&ReadOptions::new(), ts).unwrap();
+        let row = |key: &[u8], value: &[u8]| (key.to_vec(), value.to_vec());
+        assert_eq!(at(5), vec![row(b"k\x00", b"other")]);
+        assert_eq!(
+            at(20),
+            vec![
+                row(b"j", b"j15"),
+                row(b"k", b"v20"),
+                row(b"k\x00", b"other")
+            ]
+        );
+        assert_eq!(at(30), vec![row(b"j", b"j15"), row(b"k\x00", b"other")]);
+        let bounded = ReadOptions::new()
+            .lower_bound(b"k".to_vec())
+            .upper_bound(b"k\x00".to_vec());
+        assert_eq!(db.scan_at(&bounded, 40).unwrap(), vec![row(b"k", b"v40")]);
+
+        // Timestamped keys are a namespace of their own
+        assert_eq!(db.get(b"k").unwrap(), None);
+        assert!(db.keys(b"*").unwrap().is_empty());
+        assert_eq!(engine.database(1).unwrap().get_at(b"k", 40).unwrap(), None);
+    }
+
+    #[test]
+    fn test_trim_history_keeps_the_horizon_version() {
+        let mut entries = BTreeMap::new();
+        let tagged = [DATABASE_PREFIX, &[0, 1]].concat();
+        for prefix in [&[][..], &tagged] {
+            for ts in [10, 20, 30] {
+                let key = [prefix, &version_key(b"k", ts)].concat();
+                entries.insert(key, Some(vec![LIVE]));
+            }
+        }
+        entries.insert(b"plain".to_vec(), Some(b"v".to_vec()));
+        trim_history(&mut entries, 25);
+
+        let live: Vec<(&[u8], u64)> = entries
+            .iter()
+            .filter(|(_, value)| value.is_some())
+            .filter_map(|(key, _)| split_version(key))
+            .collect();
+        let group = [TIMESTAMPED_PREFIX, &encode(&b"k"[..])].concat();
+        let tagged_group = [&tagged[..], &group].concat();
+        assert_eq!(
+            live,
+            vec![
+                (&tagged_group[..], 30),
+                (&tagged_group[..], 20),
+                (&group[..], 30),
+                (&group[..], 20),
+            ]
+        );
+        assert_eq!(entries.get(&b"plain"[..]), Some(&Some(b"v".to_vec())));
+    }
+}
@@ -0,0 +1,196 @@
+//! OBLIVION - Transactions
+//! Reads and writes of one database committed together, with the
+//! transaction's own writes visible to its reads.
+//!
+//! A [`Transaction`] reads from a snapshot taken when it begins, with
+//! its pending writes layered on top: `get` after `put` returns the new
+//! value, and after `delete` nothing, although neither is applied yet.
+//! [`commit`](Transaction::commit) applies the writes as one
+//! [`WriteBatch`](super::batch::WriteBatch), so readers see all of them
+//! at once; dropping the transaction discards them.
+//!
+//! ## Conflicts
+//! Commits are optimistic. Under the writer lock, `commit` checks that
+//! every key the transaction read from its snapshot still holds the
+//! value it saw. If another writer changed one, nothing is written and
+//! `commit` fails with [`OblivionError::TransactionConflict`]; run the
+//! transaction again from the start to retry. Keys the transaction only
+//! wrote are not checked, and a key changed and changed back does not
+//! conflict.
+//!
+//! ## Example
+//! ```no_run
+//! use oblivion::config::Config;
+//! use oblivion::engine::Oblivion;
+//!
+//! let engine = Oblivion::open(Config::new("./data")).unwrap();
+//! let mut txn = engine.transaction();
+//! let visits = txn
+//!     .get(b"visits")
+//!     .unwrap()
+//!     .map_or(0, |v| String::from_utf8_lossy(&v).parse::<u64>().unwrap());
+//! txn.put(b"visits", (visits + 1).to_string()).unwrap();
+//! assert!(txn.get(b"visits").unwrap().is_some());
+//! txn.commit().unwrap();
+//! ```
+//!
+//! [`OblivionError::TransactionConflict`]: crate::error::OblivionError::TransactionConflict
+
+use std::collections::HashMap;
+
+use crate::error::Result;
+use crate::types::{Key, Value};
+
+use super::batch::BatchOp;
+use super::database::Database;
+use super::options::ReadOptions;
+
+/// Uncommitted reads and writes of one [`Database`].
+pub struct Transaction<'a> {
+    db: Database<'a>,
+    /// Reads go to the snapshot taken when the transaction began.
+    opts: ReadOptions,
+    /// Writes in the order they are committed, under their stored keys.
+    ops: Vec<BatchOp>,
+    /// Latest pending value of each key written; `None` once deleted.
+    written: HashMap<Key, Option<Value>>,
+    /// Values read from the snapshot by stored key, checked at commit.
+    read: HashMap<Key, Option<Value>>,
+}
+
+impl<'a> Database<'a> {
+    /// Begin a [`Transaction`] on this database.
+    pub fn transaction(&self) -> Transaction<'a> {
+        let mut opts = ReadOptions::new();
+        opts.snapshot = Some(self.engine().snapshot());
+        Transaction {
+            db: self.clone(),
+            opts,
+            ops: Vec::new(),
+            written: HashMap::new(),
+            read: HashMap::new(),
+        }
+    }
+}
+
+impl Transaction<'_> {
+    /// Get the value of `key` as the transaction has left it: its own
+    /// latest write, or else the value in its snapshot.
+    pub fn get(&mut self, key: impl AsRef<[u8]>) -> Result<Option<Value>> {
+        let key = key.as_ref();
+        if let Some(value) = self.written.get(key) {
+            return Ok(value.clone());
+        }
+        if self.db.is_reserved(key) {
+            return Ok(None);
+        }
+        let stored = self.db.stored(key);
+        let value = self.db.engine().get_opt(&stored, &self.opts)?;
+        self.read.entry(stored).or_insert_with(|| value.clone());
+        Ok(value)
+    }
+
+    /// Returns whether `key` holds a value as the transaction has left it.
+    pub fn exists(&mut self, key: impl AsRef<[u8]>) -> Result<bool> {
+        Ok(self.get(key)?.is_some())
+    }
+
+    /// Queue a put of `key`.
+    pub fn put(&mut self, key: impl AsRef<[u8]>, value: impl Into<Value>) -> Result<()> {
+        self.push_put(key.as_ref(), value.into(), None)
+    }
+
+    /// Queue a put of `key` that expires `ttl_ms` milliseconds after
+    /// the commit.
+    pub fn put_with_ttl(
+        &mut self,
+        key: impl AsRef<[u8]>,
+        value: impl Into<Value>,
+        ttl_ms: u64,
+    ) -> Result<()> {
+        self.push_put(key.as_ref(), value.into(), Some(ttl_ms))
+    }
+
+    /// Queue a delete of `key`.
+    pub fn delete(&mut self, key: impl AsRef<[u8]>) -> Result<()> {
+        let key = key.as_ref();
+        let stored = self.db.writable(key)?;
+        self.written.insert(key.to_vec(), None);
+        self.ops.push(BatchOp::Delete { key: stored });
+        Ok(())
+    }
+
+    /// Apply the queued writes as one batch, or fail with
+    /// [`OblivionError::TransactionConflict`](crate::error::OblivionError::TransactionConflict)
+    /// without writing anything if a key the transaction read has
+    /// changed since.
+    pub fn commit(self) -> Result<()> {
+        self.db.engine().commit_transaction(&self.read, self.ops)
+    }
+
+    fn push_put(&mut self, key: &[u8], value: Value, ttl_ms: Option<u64>) -> Result<()> {
+        let stored = self.db.writable(key)?;
+        self.written.insert(key.to_vec(), Some(value.clone()));
+        self.ops.push(BatchOp::Put {
+            key: stored,
+            value,
+            ttl_ms,
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::engine::Oblivion;
+    use crate::error::OblivionError;
+
+    #[test]
+    fn test_reads_see_pending_writes_over_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = Oblivion::open(Config::new(dir.path())).unwrap();
+        engine.put("a", "old").unwrap();
+        engine.put("b", "kept").unwrap();
+
+        let mut txn = engine.transaction();
+        txn.put("a", "new").unwrap();
+        txn.delete("b").unwrap();
+        assert_eq!(txn.get("a").unwrap(), Some(b"new".to_vec()));
+        assert!(!txn.exists("b").unwrap());
+        assert_eq!(engine.get(b"a"), Some(b"old".to_vec()));
+
+        txn.commit().unwrap();
+        assert_eq!(engine.get(b"a"), Some(b"new".to_vec()));
+        assert_eq!(engine.get(b"b"), None);
+    }
+
+    #[test]
+    fn test_commit_fails_if_a_read_key_changed() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = Oblivion::open(Config::new(dir.path())).unwrap();
+        engine.put("stock", "1").unwrap();
+
+        let mut txn = engine.transaction();
+        assert_eq!(txn.get("stock").unwrap(), Some(b"1".to_vec()));
+        txn.put("stock", "0").unwrap();
+        txn.put("order", "placed").unwrap();
+        engine.put("stock", "5").unwrap();
+        engine.put("late", "1").unwrap();
+        // Writes after the transaction began are not in its snapshot
+        assert_eq!(txn.get("late").unwrap(), None);
+        assert!(matches!(
+            txn.commit(),
+            Err(OblivionError::TransactionConflict(_))
+        ));
+        assert_eq!(engine.get(b"stock"), Some(b"5".to_vec()));
+        assert_eq!(engine.get(b"order"), None);
+
+        // Keys only written do not conflict
+        let mut txn = engine.transaction();
+        txn.put("stock", "4").unwrap();
+        engine.put("stock", "6").unwrap();
+        txn.commit().unwrap();
+        assert_eq!(engine.get(b"stock"), Some(b"4".to_vec()));
+    }
+}
@@ -0,0 +1,1002 @@
+//! OBLIVION - Tree Maintenance
+//! Flushes and compactions of the LSM tree, shared by the engine and its
+//! background workers.
+//!
+//! ## Flushing
+//! When the active MemTable fills, the engine freezes it and rotates the
+//! WAL into a numbered segment holding exactly its writes. Flushes write
+//! frozen MemTables to SSTables oldest first and then delete their
+//! segments, or move them to `wal/archive/` while `wal_retention_size`
+//! allows. Until then, reads find the frozen MemTable in the current
+//! version and recovery replays the segments.
+//!
+//! ## Version Edits
+//! Freezes, flushes and compactions each build a new version from the
+//! current one while holding the manifest lock, so concurrent edits never
+//! lose each other's changes. Flushes only append tables and compactions
+//! run one at a time, so a compaction's inputs stay contiguous.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use arc_swap::ArcSwap;
+use parking_lot::{Condvar, Mutex};
+
+use crate::config::Config;
+use crate::error::{OblivionError, Result};
+use crate::types::{Key, Value};
+
+use super::background::JobKind;
+use super::cache::TableCache;
+use super::changes::ChangeFeed;
+use super::compaction::{CompactionStrategy, SStableInfo, SizeTieredCompaction};
+use super::compression;
+use super::failpoint::fail_point;
+use super::io;
+use super::manifest::Manifest;
+use super::memtable::MemTable;
+use super::metrics::{DiskUsage, EngineMetrics};
+use super::properties::{self, LiveFile};
+use super::sizing::MemTableSizer;
+use super::sstable::{self, SSTable};
+use super::timestamp;
+use super::version::{ReadState, Version};
+use super::wal::WriteAheadLog;
+use super::Oblivion;
+
+/// A frozen MemTable waiting to be flushed.
+struct FrozenMemTable {
+    memtable: Arc<MemTable>,
+    /// WAL segments holding its writes, deleted after the flush.
+    logs: Vec<PathBuf>,
+    /// Number of the freeze that queued it, counting from 1.
+    number: u64,
+    /// Sequence numbers of its first and last write; 0 if unknown.
+    sequences: (u64, u64),
+}
+
+/// State flush and compaction jobs work on.
+pub(crate) struct Tree {
+    /// MemTables, versions, TTL index and row cache shared with readers.
+    state: Arc<ReadState>,
+    /// Current configuration; replaced by `set_options`.
+    config: ArcSwap<Config>,
+    /// Live SSTable file numbers and the file number allocator. Its lock
+    /// also serializes version edits.
+    manifest: Mutex<Manifest>,
+    metrics: Arc<EngineMetrics>,
+    table_cache: Arc<TableCache>,
+    /// Frozen MemTables, oldest first.
+    pending: Mutex<VecDeque<FrozenMemTable>>,
+    /// Number of MemTables frozen so far.
+    freezes: AtomicU64,
+    /// Sequence number of the last write frozen so far, or flushed
+    /// before the engine was opened.
+    frozen_sequence: AtomicU64,
+    /// Signalled whenever a frozen MemTable has been flushed or a
+    /// background error recorded.
+    flushed: Condvar,
+    /// Serializes flushes so tables are added in MemTable order.
+    flush_lock: Mutex<()>,
+    /// Serializes compactions.
+    compaction_lock: Mutex<()>,
+    /// Size of the live WAL, as last reported by the writer.
+    log_bytes: AtomicU64,
+    /// Bytes in rotated WAL segments not yet deleted.
+    segment_bytes: AtomicU64,
+    /// First failure of a background job; once set, writes are refused.
+    background_error: Mutex<Option<String>>,
+    /// What failed when the disk filled up; once set, the engine is
+    /// read-only and background jobs stop writing.
+    degraded: Mutex<Option<String>>,
+    /// Watchers told about keys removed on expiry.
+    changes: Arc<ChangeFeed>,
+    /// Flush threshold under `adaptive_memtable_sizing`.
+    sizer: MemTableSizer,
+}
+
+impl Tree {
+    /// Create the tree state for an opened engine.
+    pub(crate) fn new(
+        state: Arc<ReadState>,
+        config: Config,
+        manifest: Manifest,
+        table_cache: Arc<TableCache>,
+        changes: Arc<ChangeFeed>,
+    ) -> Self {
+        let flushed_sequence = state
+            .current()
+            .sstables()
+            .iter()
+            .map(|table| table.properties().largest_seqno)
+            .max()
+            .unwrap_or(0);
+        Self {
+            metrics: Arc::clone(&state.metrics),
+            state,
+            config: ArcSwap::from_pointee(config),
+            manifest: Mutex::new(manifest),
+            table_cache,
+            pending: Mutex::new(VecDeque::new()),
+            freezes: AtomicU64::new(0),
+            frozen_sequence: AtomicU64::new(flushed_sequence),
+            flushed: Condvar::new(),
+            flush_lock: Mutex::new(()),
+            compaction_lock: Mutex::new(()),
+            log_bytes: AtomicU64::new(0),
+            segment_bytes: AtomicU64::new(0),
+            background_error: Mutex::new(None),
+            degraded: Mutex::new(None),
+            changes,
+            sizer: MemTableSizer::new(),
+        }
+    }
+
+    /// Load the manifest file together with the current version, while
+    /// no version edit can change either.
+    pub(crate) fn stored_manifest(&self) -> (Result<Option<Manifest>>, Arc<Version>) {
+        let _edit = self.manifest.lock();
+        let config = self.config();
+        let stored = Manifest::load(&config.env, &config.manifest_path());
+        (stored, self.state.current())
+    }
+
+    /// The current configuration.
+    pub(crate) fn config(&self) -> Arc<Config> {
+        self.config.load_full()
+    }
+
+    /// Replace the configuration used by later jobs.
+    pub(crate) fn set_config(&self, config: Config) {
+        self.config.store(Arc::new(config));
+    }
+
+    /// Bytes the active MemTable is flushed at.
+    pub(crate) fn memtable_threshold(&self) -> usize {
+        self.sizer.threshold(&self.config.load())
+    }
+
+    /// Record that the active MemTable, holding `bytes`, is about to be
+    /// frozen for reaching the threshold.
+    pub(crate) fn record_fill(&self, bytes: usize) {
+        self.sizer.record_fill(&self.config.load(), bytes);
+    }
+
+    /// Allocate a file number for an SSTable or WAL segment.
+    pub(crate) fn new_file_number(&self) -> u64 {
+        self.manifest.lock().new_file_number()
+    }
+
+    /// Account for a rotated WAL segment of `bytes` bytes.
+    pub(crate) fn add_segment_bytes(&self, bytes: u64) {
+        self.segment_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Record the size of the live WAL.
+    pub(crate) fn set_log_bytes(&self, bytes: u64) {
+        self.log_bytes.store(bytes, Ordering::Relaxed);
+    }
+
+    /// Bytes in the live WAL and the segments not yet deleted.
+    pub(crate) fn wal_bytes(&self) -> u64 {
+        self.log_bytes.load(Ordering::Relaxed) + self.segment_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Freeze the active MemTable, whose writes are in the WAL segments
+    /// `logs` and end at sequence number `last_sequence` (0 for writes
+    /// that take none), and queue it for flushing.
+    pub(crate) fn freeze(&self, logs: Vec<PathBuf>, last_sequence: u64) {
+        let _edit = self.manifest.lock();
+        let mut memtable = self.state.memtable.write();
+        let frozen = Arc::new(std::mem::take(&mut *memtable));
+        let current = self.state.current();
+        let mut frozen_tables = current.frozen().to_vec();
+        frozen_tables.push(Arc::clone(&frozen));
+        // Publish before releasing the MemTable lock: see `version`
+        self.state
+            .publish(frozen_tables, current.sstables().to_vec());
+        drop(memtable);
+
+        let sequences = match last_sequence {
+            0 => (0, 0),
+            last => match self.frozen_sequence.swap(last, Ordering::AcqRel) + 1 {
+                first if first <= last => (first, last),
+                // Numbering restarted (a log without a sequence marker)
+                _ => (0, 0),
+            },
+        };
+        let mut pending = self.pending.lock();
+        pending.push_back(FrozenMemTable {
+            memtable: frozen,
+            logs,
+            number: self.freezes.load(Ordering::Acquire) + 1,
+            sequences,
+        });
+        self.freezes.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// Returns the number of MemTables frozen so far.
+    pub(crate) fn freezes(&self) -> u64 {
+        self.freezes.load(Ordering::Acquire)
+    }
+
+    /// Returns the number of frozen MemTables waiting to be flushed.
+    pub(crate) fn pending_flushes(&self) -> usize {
+        self.pending.lock().len()
+    }
+
+    /// Block while more than `limit` MemTables wait to be flushed, so
+    /// writers cannot outrun the flush workers without bound.
+    pub(crate) fn wait_for_flushes(&self, limit: usize) -> Result<()> {
+        let mut pending = self.pending.lock();
+        while pending.len() > limit {
+            self.check_background_error()?;
+            self.flushed.wait(&mut pending);
+        }
+        Ok(())
+    }
+
+    /// Flush every frozen MemTable, oldest first.
+    ///
+    /// Tombstones are flushed too, so they keep shadowing older
+    /// SSTables. Expired keys are written as tombstones.
+    pub(crate) fn flush_pending(&self) -> Result<()> {
+        self.flush_until(u64::MAX)
+    }
+
+    /// Flush the MemTables frozen by the first `freezes` freezes (see
+    /// [`freezes`](Self::freezes)), oldest first; later ones stay queued.
+    pub(crate) fn flush_until(&self, freezes: u64) -> Result<()> {
+        let _flushing = self.flush_lock.lock();
+        loop {
+            let Some((frozen, logs, sequences)) = self
+                .pending
+                .lock()
+                .front()
+                .filter(|f| f.number <= freezes)
+                .map(|f| (Arc::clone(&f.memtable), f.logs.clone(), f.sequences))
+            else {
+                return Ok(());
+            };
+
+            let config = self.config.load_full();
+            let started = Instant::now();
+            let expired = self.state.ttl_index.read().collect_expired();
+            // An empty MemTable, frozen only to rotate the WAL along with
+            // column families, leaves no table behind
+            let output = if frozen.is_empty() {
+                Vec::new()
+            } else {
+                let entries = frozen.entries().iter().map(|(k, v)| {
+                    let value = if expired.binary_search(k).is_ok() {
+                        None
+                    } else {
+                        v.as_deref()
+                    };
+                    (k.as_slice(), value)
+                });
+                self.write_tables(&config, entries, 0, sequences)?
+            };
+            fail_point!(config, MidFlush);
+            let written = (!output.is_empty()).then(|| {
+                let ids: Vec<u64> = output.iter().map(|(id, _)| *id).collect();
+                let entries: usize = output.iter().map(|(_, t)| t.entry_count()).sum();
+                (ids, entries)
+            });
+
+            {
+                // The tables are live once the manifest lists them
+                let mut manifest = self.manifest.lock();
+                let current = self.state.current();
+                let mut sstables = current.sstables().to_vec();
+                if !output.is_empty() {
+                    manifest.tables.extend(output.iter().map(|(id, _)| *id));
+                    manifest.commit(&config)?;
+                    sstables.extend(output.into_iter().map(|(_, table)| Arc::new(table)));
+                }
+
+                let frozen_tables = current
+                    .frozen()
+                    .iter()
+                    .filter(|table| !Arc::ptr_eq(table, &frozen))
+                    .cloned()
+                    .collect();
+                self.state.publish(frozen_tables, sstables);
+            }
+            self.pending.lock().pop_front();
+            self.flushed.notify_all();
+            if written.is_some() {
+                self.sizer.record_flush(&config, started.elapsed());
+            }
+
+            // Expired keys are now tombstoned on disk, unless their TTL
+            // was renewed meanwhile
+            let mut ttl_index = self.state.ttl_index.write();
+            let row_cache = self.state.row_cache.load();
+            for key in &expired {
+                if !ttl_index.is_expired(key) {
+                    continue;
+                }
+                ttl_index.remove_ttl(key);
+                if let Some(cache) = &*row_cache {
+                    cache.invalidate(key);
+                }
+                self.changes.publish_expired(key);
+            }
+            drop(ttl_index);
+
+            // The segments' writes are now in the SSTable; keep them for
+            // change subscribers if `wal_retention_size` allows
+            let archive_dir = config.wal_archive_dir();
+            for log in &logs {
+                let bytes = config.env.file_size(log).unwrap_or(0);
+                let retired = if config.wal_retention_size > 0 {
+                    WriteAheadLog::archive(&config.env, log, &archive_dir)
+                } else {
+                    config.env.remove_file(log).map_err(Into::into)
+                };
+                match retired {
+                    Ok(()) => {
+                        self.segment_bytes.fetch_sub(bytes, Ordering::Relaxed);
+                    }
+                    Err(e) => log::warn!("Failed to retire WAL segment {:?}: {}", log, e),
+                }
+            }
+            if let Err(e) =
+                WriteAheadLog::trim_archive(&config.env, &archive_dir, config.wal_retention_size)
+            {
+                log::warn!("Failed to trim WAL archive: {}", e);
+            }
+            self.metrics.record_flush();
+
+            match written {
+                Some((ids, entry_count)) => log::info!(
+                    "Flush complete. {} entries written to SSTables {:?}.",
+                    entry_count,
+                    ids
+                ),
+                None => log::info!("Flush complete. Empty MemTable, no SSTable written."),
+            }
+        }
+    }
+
+    /// Add externally built `tables`, oldest first, as the newest
+    /// SSTables in one manifest update.
+    pub(crate) fn ingest_tables(
+        &self,
+        tables: impl IntoIterator<Item = (u64, Arc<SSTable>)>,
+    ) -> Result<()> {
+        let config = self.config();
+        let mut manifest = self.manifest.lock();
+        let current = self.state.current();
+        let mut sstables = current.sstables().to_vec();
+        let mut edit = manifest.clone();
+        for (id, table) in tables {
+            edit.tables.push(id);
+            sstables.push(table);
+        }
+        edit.commit(&config)?;
+        *manifest = edit;
+        self.state.publish(current.frozen().to_vec(), sstables);
+        drop(manifest);
+        self.update_tree_gauges();
+        Ok(())
+    }
+
+    /// Create `target` and fill it with hard links to the live
+    /// SSTables and a copy of the manifest, forming an openable database
+    /// without a WAL. Flushes and compactions may run meanwhile: the
+    /// tables are pinned by the version taken with the manifest.
+    pub(crate) fn checkpoint_tables(&self, target: &Config) -> Result<()> {
+        std::fs::create_dir(&target.data_dir)?;
+        target.ensure_dirs()?;
+        let (manifest, _pinned) = {
+            let manifest = self.manifest.lock();
+            (manifest.clone(), self.state.current())
+        };
+        let config = self.config();
+        for &id in &manifest.tables {
+            io::link_or_copy(
+                &Oblivion::sstable_path(&config, id),
+                &Oblivion::sstable_path(target, id),
+            )?;
+        }
+        manifest.save(&target.env, &target.manifest_path())
+    }
+
+    /// Tombstone keys whose TTL has expired in the active MemTable and
+    /// drop them from the TTL index, freeing their values from memory.
+    ///
+    /// Like TTLs themselves, these tombstones are not logged: a key
+    /// recovered from the WAL comes back without its TTL either way.
+    pub(crate) fn sweep_expired(&self) -> Result<()> {
+        let expired = self.state.ttl_index.read().collect_expired();
+        if expired.is_empty() {
+            return Ok(());
+        }
+
+        let mut memtable = self.state.memtable.write();
+        let mut ttl_index = self.state.ttl_index.write();
+        let row_cache = self.state.row_cache.load();
+        let mut swept = 0;
+        // Re-check under the locks: a writer may have renewed the TTL
+        for key in expired {
+            if !ttl_index.is_expired(&key) {
+                continue;
+            }
+            ttl_index.remove_ttl(&key);
+            if let Some(cache) = &*row_cache {
+                cache.invalidate(&key);
+            }
+            self.changes.publish_expired(&key);
+            memtable.delete(key);
+            swept += 1;
+        }
+        log::debug!("TTL sweep tombstoned {} expired keys", swept);
+        Ok(())
+    }
+
+    /// Sync a rotated WAL segment; one already flushed and deleted is fine.
+    pub(crate) fn sync_segment(&self, segment: &Path) -> Result<()> {
+        let config = self.config();
+        match config.env.sync_file(segment, config.wal_sync_method) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Record a failed background job; later writes fail with it.
+    pub(crate) fn record_background_error(&self, kind: JobKind, message: &str) {
+        self.metrics.record_background_error();
+        let mut error = self.background_error.lock();
+        if error.is_none() {
+            *error = Some(format!("{} job: {}", kind, message));
+        }
+        drop(error);
+        // Wake writers stalled on flushes that will never complete
+        let _pending = self.pending.lock();
+        self.flushed.notify_all();
+    }
+
+    /// The first background failure, if any.
+    pub(crate) fn background_error(&self) -> Option<String> {
+        self.background_error.lock().clone()
+    }
+
+    /// Fail if the disk filled up or a background job has failed.
+    pub(crate) fn check_background_error(&self) -> Result<()> {
+        if let Some(reason) = self.degraded() {
+            return Err(OblivionError::DiskFull(reason));
+        }
+        match self.background_error() {
+            Some(message) => Err(OblivionError::Background(message)),
+            None => Ok(()),
+        }
+    }
+
+    /// Turn read-only if `error` is the disk filling up during
+    /// `operation`, and return the error to report:
+    /// [`OblivionError::DiskFull`] in place of the I/O error.
+    ///
+    /// Retrying would only fail again until space is freed, so writes
+    /// are refused and flushes and compactions skipped from then on.
+    pub(crate) fn degrade_on_full_disk(
+        &self,
+        operation: &str,
+        error: OblivionError,
+    ) -> OblivionError {
+        if !error.is_disk_full() {
+            return error;
+        }
+        let mut degraded = self.degraded.lock();
+        let reason = degraded.get_or_insert_with(|| {
+            log::error!(
+                "Disk full during {}: {}; the engine is read-only until reopened",
+                operation,
+                error
+            );
+            self.metrics.record_degraded();
+            format!("{} failed: {}", operation, error)
+        });
+        let reason = reason.clone();
+        drop(degraded);
+        // Wake writers stalled on flushes that will never complete
+        let _pending = self.pending.lock();
+        self.flushed.notify_all();
+        OblivionError::DiskFull(reason)
+    }
+
+    /// What failed when the disk filled up, if it did.
+    pub(crate) fn degraded(&self) -> Option<String> {
+        self.degraded.lock().clone()
+    }
+
+    /// Build the compaction strategy from the current configuration.
+    fn compaction_strategy(config: &Config) -> SizeTieredCompaction {
+        // Flushes overshoot the threshold by up to one entry, so give T0 headroom
+        SizeTieredCompaction::new(config.compaction_threshold, config.compaction_size_ratio)
+            .with_base_size(config.memtable_max_size.saturating_mul(2))
+    }
+
+    /// Describe the live SSTables for the compaction strategy, one entry
+    /// per run: the tables a single flush or compaction split its output
+    /// into (see [`write_tables`](Self::write_tables)) count as one, so
+    /// they are never merged back together on their own. Returns the
+    /// runs and the positions of their first and last tables. Sizes are
+    /// raw key + value bytes, matching MemTable accounting.
+    fn sstable_runs(&self) -> (Vec<SStableInfo>, Vec<(usize, usize)>) {
+        let current = self.state.current();
+        let comparator = &self.state.comparator;
+        let mut infos: Vec<SStableInfo> = Vec::new();
+        let mut spans: Vec<(usize, usize)> = Vec::new();
+        let mut previous: Option<&SSTable> = None;
+        for (position, table) in current.sstables().iter().enumerate() {
+            let props = table.properties();
+            let continues_run = previous.is_some_and(|previous| {
+                let last = previous.properties();
+                last.smallest_seqno > 0
+                    && (last.smallest_seqno, last.largest_seqno)
+                        == (props.smallest_seqno, props.largest_seqno)
+                    && comparator.compare(&last.max_key, &props.min_key).is_lt()
+            });
+            previous = Some(table);
+            if let (true, Some(run), Some(span)) =
+                (continues_run, infos.last_mut(), spans.last_mut())
+            {
+                run.size += Self::table_data_size(table);
+                run.max_key = props.max_key.clone();
+                span.1 = position;
+                continue;
+            }
+            infos.push(SStableInfo {
+                id: infos.len(),
+                path: table.path().clone(),
+                size: Self::table_data_size(table),
+                min_key: props.min_key.clone(),
+                max_key: props.max_key.clone(),
+            });
+            spans.push((position, position));
+        }
+        (infos, spans)
+    }
+
+    /// Raw key + value bytes of a table, the size compaction tiers use.
+    fn table_data_size(table: &SSTable) -> usize {
+        let props = table.properties();
+        (props.raw_key_size + props.raw_value_size) as usize
+    }
+
+    /// Report the on-disk footprint: live SSTables per tier, SSTable,
+    /// WAL and manifest bytes, and bytes held by obsolete files.
+    pub(crate) fn disk_usage(&self) -> Result<DiskUsage> {
+        let config = self.config.load_full();
+        let strategy = Self::compaction_strategy(&config);
+        let mut usage = DiskUsage {
+            wal_bytes: self.wal_bytes(),
+            ..DiskUsage::default()
+        };
+        let version = self.state.current();
+        for table in version.sstables() {
+            let tier = strategy.tier_for_size(Self::table_data_size(table));
+            if usage.sstables_per_tier.len() <= tier {
+                usage.sstables_per_tier.resize(tier + 1, 0);
+            }
+            usage.sstables_per_tier[tier] += 1;
+            usage.sstable_bytes += table.file_size();
+        }
+        for path in config.env.list(&config.sst_dir())? {
+            if !version.sstables().iter().any(|table| *table.path() == path) {
+                usage.obsolete_bytes += config.env.file_size(&path)?;
+            }
+        }
+        usage.manifest_bytes = config.env.file_size(&config.manifest_path()).unwrap_or(0);
+        Ok(usage)
+    }
+
+    /// Describe the live SSTables, oldest first, as tables of
+    /// `column_family`.
+    pub(crate) fn live_files(&self, column_family: &str) -> Vec<LiveFile> {
+        let strategy = Self::compaction_strategy(&self.config.load());
+        self.state
+            .current()
+            .sstables()
+            .iter()
+            .map(|table| {
+                let props = table.properties();
+                LiveFile {
+                    column_family: column_family.to_string(),
+                    path: table.path().clone(),
+                    size: table.file_size(),
+                    tier: strategy.tier_for_size(Self::table_data_size(table)),
+                    smallest_key: props.min_key.clone(),
+                    largest_key: props.max_key.clone(),
+                    smallest_seqno: props.smallest_seqno,
+                    largest_seqno: props.largest_seqno,
+                    num_entries: props.entry_count,
+                    num_deletions: props.tombstone_count,
+                    creation_time: props.creation_time,
+                    num_reads: table.num_reads(),
+                    last_access_time: table.last_access_time(),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the numeric property `name` of this tree (see
+    /// [`properties`]), or `None` if it is not one.
+    pub(crate) fn property(&self, name: &str) -> Option<u64> {
+        let version = self.state.current();
+        let tables = version.sstables();
+        let value = match name {
+            properties::NUM_ENTRIES => {
+                let frozen: usize = version.frozen().iter().map(|m| m.len()).sum();
+                let stored: u64 = tables.iter().map(|t| t.properties().entry_count).sum();
+                (self.state.len() + frozen) as u64 + stored
+            }
+            properties::ESTIMATE_LIVE_KEYS => self.estimate_live_keys(),
+            properties::NUM_LIVE_FILES => tables.len() as u64,
+            properties::LIVE_SST_FILES_SIZE => tables.iter().map(|t| t.file_size()).sum(),
+            properties::ESTIMATE_PENDING_COMPACTION_BYTES => self.tree_shape().1,
+            properties::CUR_SIZE_ACTIVE_MEM_TABLE => self.state.memtable_size() as u64,
+            properties::NUM_IMMUTABLE_MEM_TABLE => version.frozen().len() as u64,
+            properties::MEMTABLE_FLUSH_THRESHOLD => self.memtable_threshold() as u64,
+            properties::NUM_SNAPSHOTS => self.state.live_snapshots() as u64,
+            _ => {
+                let tier: usize = name
+                    .strip_prefix(properties::NUM_FILES_AT_TIER_PREFIX)?
+                    .parse()
+                    .ok()?;
+                let strategy = Self::compaction_strategy(&self.config.load());
+                tables
+                    .iter()
+                    .filter(|t| strategy.tier_for_size(Self::table_data_size(t)) == tier)
+                    .count() as u64
+            }
+        };
+        Some(value)
+    }
+
+    /// Estimate the keys a full scan would return; see
+    /// [`Oblivion::estimate_live_keys`].
+    pub(crate) fn estimate_live_keys(&self) -> u64 {
+        let version = self.state.current();
+        let tables = version.sstables();
+        let mut live = self.stored_live_keys(tables);
+
+        // The MemTables are in memory, so each key is looked up in the
+        // older sources: overwrites add nothing, and tombstones delete a
+        // key only if there was one
+        let active = self.state.memtable.read();
+        let frozen = version.frozen();
+        let memtables = frozen.iter().map(Arc::as_ref).chain([&*active]);
+        for (i, memtable) in memtables.enumerate() {
+            for (key, value) in memtable.entries() {
+                let older = frozen[..i]
+                    .iter()
+                    .rev()
+                    .find_map(|m| m.entries().get(key))
+                    .map(Option::is_some)
+                    .unwrap_or_else(|| tables.iter().any(|t| t.covers(key) && t.may_contain(key)));
+                live += match (value.is_some(), older) {
+                    (true, false) => 1,
+                    (false, true) => -1,
+                    _ => 0,
+                };
+            }
+        }
+        live.max(0) as u64
+    }
+
+    /// Estimate the keys a full scan would return from counters only;
+    /// see [`Oblivion::dbsize_estimate`].
+    pub(crate) fn dbsize_estimate(&self) -> u64 {
+        let version = self.state.current();
+        let tables = version.sstables();
+        let mut live = self.stored_live_keys(tables);
+
+        // Without lookups, a MemTable tombstone is taken to delete a key
+        // whenever there is older data it could delete
+        let mut older_data = !tables.is_empty();
+        let active = self.state.memtable.read();
+        for memtable in version.frozen().iter().map(Arc::as_ref).chain([&*active]) {
+            let tombstones = memtable.tombstone_count() as i64;
+            live += memtable.len() as i64 - tombstones;
+            if older_data {
+                live -= tombstones;
+            }
+            older_data |= !memtable.is_empty();
+        }
+        live.max(0) as u64
+    }
+
+    /// Keys in `tables` from their properties: a table adds its values,
+    /// and each of its tombstones deletes a key if an older table's key
+    /// range meets its own.
+    fn stored_live_keys(&self, tables: &[Arc<SSTable>]) -> i64 {
+        let comparator = &self.state.comparator;
+        let mut live: i64 = 0;
+        for (i, table) in tables.iter().enumerate() {
+            let props = table.properties();
+            live += (props.entry_count - props.tombstone_count) as i64;
+            let shadows_older = tables[..i].iter().any(|older| {
+                let older = older.properties();
+                older.entry_count > 0
+                    && comparator.compare(&older.min_key, &props.max_key).is_le()
+                    && comparator.compare(&older.max_key, &props.min_key).is_ge()
+            });
+            if shadows_older {
+                live -= props.tombstone_count as i64;
+            }
+        }
+        live
+    }
+
+    /// Refresh the tree shape and disk usage gauges after the SSTable set changed.
+    pub(crate) fn update_tree_gauges(&self) {
+        match self.disk_usage() {
+            Ok(usage) => self.metrics.set_disk_usage(&usage),
+            Err(e) => log::warn!("Failed to measure disk usage: {}", e),
+        }
+        let (l0_tables, pending) = self.tree_shape();
+        self.metrics.set_tree_shape(l0_tables, pending);
+    }
+
+    /// Returns the number of tier 0 tables and the bytes of the tiers
+    /// that have reached the compaction threshold.
+    fn tree_shape(&self) -> (u64, u64) {
+        let config = self.config.load();
+        let strategy = Self::compaction_strategy(&config);
+        let current = self.state.current();
+        let (runs, spans) = self.sstable_runs();
+        // Per tier: runs, tables and file bytes
+        let mut tiers: BTreeMap<usize, (u64, u64, u64)> = BTreeMap::new();
+        for (run, (first, last)) in runs.iter().zip(spans) {
+            let tier = strategy.tier_for_size(run.size);
+            let (count, tables, bytes) = tiers.entry(tier).or_default();
+            *count += 1;
+            for table in &current.sstables()[first..=last] {
+                *tables += 1;
+                *bytes += table.file_size();
+            }
+        }
+        let l0_tables = tiers.get(&0).map_or(0, |(_, tables, _)| *tables);
+        let pending = tiers
+            .values()
+            .filter(|(count, _, _)| *count >= config.compaction_threshold as u64)
+            .map(|(_, _, bytes)| bytes)
+            .sum();
+        (l0_tables, pending)
+    }
+
+    /// Ask the compaction strategy for work and run it until no tier
+    /// exceeds its threshold, then rewrite the runs that are due for
+    /// periodic compaction.
+    pub(crate) fn maybe_compact(&self) -> Result<()> {
+        let _compacting = self.compaction_lock.lock();
+        let strategy = Self::compaction_strategy(&self.config.load());
+        loop {
+            let (runs, spans) = self.sstable_runs();
+            let Some(selected) = strategy.select_compaction(&runs) else {
+                break;
+            };
+            // Only merge a contiguous run of tables: skipping over a table
+            // would let an older version shadow the skipped, newer one.
+            let first = *selected.iter().min().unwrap_or(&0);
+            let last = *selected.iter().max().unwrap_or(&0);
+            if first == last {
+                break;
+            }
+            self.compact_tables(spans[first].0, spans[last].1)?;
+        }
+        // The rewritten run is new, so each stale run is compacted once
+        while let Some((first, last)) = self.stale_run() {
+            log::info!(
+                "Periodic compaction of SSTables {}..={} (older than {}s)",
+                first,
+                last,
+                self.config.load().periodic_compaction_interval_secs
+            );
+            self.compact_tables(first, last)?;
+        }
+        self.update_tree_gauges();
+        Ok(())
+    }
+
+    /// Returns the positions of the first and last table of the oldest
+    /// run holding a table written more than
+    /// `periodic_compaction_interval_secs` ago, or at an unknown time.
+    fn stale_run(&self) -> Option<(usize, usize)> {
+        let interval = self.config.load().periodic_compaction_interval_secs;
+        if interval == 0 {
+            return None;
+        }
+        let horizon = sstable::unix_time_secs().saturating_sub(interval);
+        let current = self.state.current();
+        let tables = current.sstables();
+        let (_, spans) = self.sstable_runs();
+        // A flush may have added a run since `tables` was taken
+        spans.into_iter().find(|&(first, last)| {
+            tables.get(first..=last).is_some_and(|run| {
+                run.iter()
+                    .any(|table| table.properties().creation_time <= horizon)
+            })
+        })
+    }
+
+    /// Merge every SSTable into one run, regardless of the strategy.
+    pub(crate) fn compact_all(&self) -> Result<()> {
+        self.compact_range(None, None)
+    }
+
+    /// Merge the run of SSTables holding keys in `[start, end]` (either
+    /// bound open when `None`) into one run, regardless of the strategy.
+    /// Tables between two overlapping ones are merged too, since only
+    /// contiguous runs can be.
+    pub(crate) fn compact_range(&self, start: Option<&[u8]>, end: Option<&[u8]>) -> Result<()> {
+        let _compacting = self.compaction_lock.lock();
+        let overlapping: Vec<usize> = self
+            .state
+            .current()
+            .sstables()
+            .iter()
+            .enumerate()
+            .filter(|(_, table)| {
+                let props = table.properties();
+                let comparator = &self.state.comparator;
+                start.is_none_or(|start| comparator.compare(&props.max_key, start).is_ge())
+                    && end.is_none_or(|end| comparator.compare(&props.min_key, end).is_le())
+            })
+            .map(|(position, _)| position)
+            .collect();
+        if let (Some(&first), Some(&last)) = (overlapping.first(), overlapping.last()) {
+            self.compact_tables(first, last)?;
+        }
+        self.update_tree_gauges();
+        Ok(())
+    }
+
+    /// Write `entries` into new SSTables in the given compaction tier,
+    /// starting another table once one holds `target_file_size` raw
+    /// bytes. Returns the tables with their file numbers, in key order;
+    /// there is always at least one, empty if `entries` is.
+    ///
+    /// The tables of one output share its sequence range, which is how
+    /// [`sstable_runs`](Self::sstable_runs) tells them apart from other
+    /// outputs. Output whose sequence range is unknown is not split.
+    fn write_tables<'a>(
+        &self,
+        config: &Config,
+        entries: impl Iterator<Item = (&'a [u8], Option<&'a [u8]>)>,
+        tier: usize,
+        sequences: (u64, u64),
+    ) -> Result<Vec<(u64, SSTable)>> {
+        let mut entries: Vec<_> = entries.collect();
+        let comparator = self.table_cache.comparator();
+        if !comparator.is_bytewise() {
+            entries.sort_by(|a, b| comparator.compare(a.0, b.0));
+        }
+        let target = match config.target_file_size {
+            0 => usize::MAX,
+            _ if sequences.0 == 0 => usize::MAX,
+            size => size,
+        };
+        // One dictionary for the whole flush or compaction, shared by
+        // every output table
+        let dictionary = compression::train_dictionary(&entries, config.compression_dict_size);
+
+        let mut output = Vec::new();
+        let mut rest = entries.as_slice();
+        loop {
+            let mut size = 0;
+            let split = rest
+                .iter()
+                .position(|(key, value)| {
+                    let full = size >= target;
+                    size += key.len() + value.map_or(0, <[u8]>::len);
+                    full
+                })
+                .unwrap_or(rest.len());
+            let (chunk, remaining) = rest.split_at(split);
+            let id = self.new_file_number();
+            let table = SSTable::flush_with_sequences(
+                Oblivion::sstable_path(config, id),
+                chunk.iter().copied(),
+                config.filter_policy_for_tier(tier),
+                &self.table_cache,
+                sequences,
+                dictionary.as_deref(),
+            )?;
+            output.push((id, table));
+            if remaining.is_empty() {
+                return Ok(output);
+            }
+            rest = remaining;
+        }
+    }
+
+    /// Merge the SSTables at positions `first..=last`.
+    ///
+    /// The output, split at `target_file_size`, gets new file numbers and
+    /// replaces the inputs in the manifest in one atomic update; the inputs are deleted once no
+    /// reader holds them. Tombstones are kept unless the run includes the
+    /// oldest table, since otherwise an older table may still hold the
+    /// deleted key. Keys with an expired TTL, and timestamped versions
+    /// past `history_retention_ms`, are treated as deleted.
+    fn compact_tables(&self, first: usize, last: usize) -> Result<()> {
+        let config = self.config.load_full();
+        let inputs = self.state.current().sstables()[first..=last].to_vec();
+        let input_size: usize = inputs.iter().map(|t| Self::table_data_size(t)).sum();
+
+        let mut merged: BTreeMap<Key, Option<Value>> = BTreeMap::new();
+        for table in &inputs {
+            merged.extend(table.scan_readahead(config.compaction_readahead_size)?);
+        }
+        if let Some(horizon) = timestamp::retention_horizon(config.history_retention_ms) {
+            timestamp::trim_history(&mut merged, horizon);
+        }
+
+        let tier = Self::compaction_strategy(&config).tier_for_size(input_size);
+        let drop_tombstones = first == 0;
+        let ttl_index = self.state.ttl_index.read();
+        let entries = merged
+            .iter()
+            .map(|(k, v)| {
+                let value = if ttl_index.is_expired(k) {
+                    None
+                } else {
+                    v.as_deref()
+                };
+                (k.as_slice(), value)
+            })
+            .filter(|(_, v)| v.is_some() || !drop_tombstones);
+        // The output spans its inputs' writes, unless one's are unknown
+        let sequences = inputs
+            .iter()
+            .map(|table| table.properties())
+            .try_fold((u64::MAX, 0), |(first, last), props| {
+                (props.smallest_seqno > 0).then(|| {
+                    (
+                        first.min(props.smallest_seqno),
+                        last.max(props.largest_seqno),
+                    )
+                })
+            })
+            .unwrap_or((0, 0));
+        let output = self.write_tables(&config, entries, tier, sequences)?;
+        drop(ttl_index);
+        fail_point!(config, MidCompaction);
+
+        log::info!(
+            "Compacted {} SSTables into {:?} (tier {}, {} entries)",
+            last - first + 1,
+            output.iter().map(|(_, t)| t.path()).collect::<Vec<_>>(),
+            tier,
+            output.iter().map(|(_, t)| t.entry_count()).sum::<usize>()
+        );
+
+        let mut manifest = self.manifest.lock();
+        manifest
+            .tables
+            .splice(first..=last, output.iter().map(|(id, _)| *id));
+        manifest.commit(&config)?;
+
+        // Snapshots, iterators and in-flight reads may still hold the
+        // inputs; each file is deleted when its last holder drops it
+        let current = self.state.current();
+        let mut sstables = current.sstables().to_vec();
+        let removed = sstables.splice(
+            first..=last,
+            output.into_iter().map(|(_, table)| Arc::new(table)),
+        );
+        for table in removed {
+            table.mark_obsolete();
+        }
+        self.state.publish(current.frozen().to_vec(), sstables);
+
+        Ok(())
+    }
+}
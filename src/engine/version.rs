@@ -0,0 +1,667 @@
+//! OBLIVION - Versions and Lock-Free Reads
+//! Immutable views of the tree, published atomically so reads never wait
+//! for writers.
+//!
+//! ## Read Path
+//! A [`Version`] is the set of frozen MemTables awaiting flush plus the
+//! live SSTables. It is never modified: flushes and compactions build a
+//! new one and publish it with a single atomic swap (`arc-swap`), and
+//! readers load the current one without taking a lock. The active
+//! MemTable and the TTL index are the only state readers lock, and only
+//! for an in-memory lookup.
+//!
+//! ## Publication Order
+//! A flush freezes the active MemTable and publishes the Version holding
+//! it while still holding the MemTable write lock, so a reader that misses
+//! a key in the active MemTable always loads a Version that includes the
+//! frozen one. Row cache fills are tagged with the cache generation read
+//! before the MemTable lookup and dropped if a write invalidated the cache
+//! in the meantime.
+//!
+//! ## Streaming Scans
+//! A [`ScanIter`] takes a snapshot when created and reads it a page at
+//! a time as it is advanced, so walking the whole keyspace holds only
+//! one page of rows (plus the snapshot's copy of the active MemTable)
+//! and never keeps a lock between pages.
+//!
+//! ## Scan Order
+//! Scans return keys in the order of the engine's
+//! [comparator](super::comparator). MemTables are always in byte order,
+//! so under a custom comparator a scan filters and sorts what it reads
+//! from them, and a page resumes from its last key rather than from the
+//! key after it.
+
+use std::collections::BTreeMap;
+use std::ops::Bound;
+use std::sync::Arc;
+
+use arc_swap::{ArcSwap, ArcSwapOption};
+use parking_lot::{RwLock, RwLockReadGuard};
+
+use crate::error::Result;
+use crate::types::{Key, Value};
+
+use super::cache::RowCache;
+use super::comparator::Comparator;
+use super::memtable::MemTable;
+use super::metrics::EngineMetrics;
+use super::options::ReadOptions;
+use super::pinned::PinnedValue;
+use super::snapshot::Snapshot;
+use super::sstable::SSTable;
+use super::ttl::TtlIndex;
+
+/// An immutable view of everything below the active MemTable.
+pub struct Version {
+    /// MemTables frozen for flushing, oldest first.
+    frozen: Vec<Arc<MemTable>>,
+    /// Live SSTables, oldest first.
+    sstables: Vec<Arc<SSTable>>,
+}
+
+impl Version {
+    /// Create a version from frozen MemTables and SSTables, both oldest first.
+    pub(crate) fn new(frozen: Vec<Arc<MemTable>>, sstables: Vec<Arc<SSTable>>) -> Self {
+        Self { frozen, sstables }
+    }
+
+    /// MemTables frozen for flushing, oldest first.
+    pub(crate) fn frozen(&self) -> &[Arc<MemTable>] {
+        &self.frozen
+    }
+
+    /// Live SSTables, oldest first.
+    pub(crate) fn sstables(&self) -> &[Arc<SSTable>] {
+        &self.sstables
+    }
+}
+
+/// Live rows of one scan page, and the lower bound of the next page.
+pub type ScanPage = (Vec<(Key, Value)>, Option<Key>);
+
+/// Records read per source for each page of a [`ScanIter`].
+const SCAN_ITER_PAGE_SIZE: usize = 1024;
+
+/// A lazy scan over the live rows selected by a set of read options, in
+/// key order. Reads a page of rows whenever the previous one is used up,
+/// all from the snapshot of the options (or one taken on creation), so
+/// writes made while iterating are not seen. Yields an error, then
+/// ends, if a page cannot be read.
+pub struct ScanIter {
+    state: Arc<ReadState>,
+    opts: ReadOptions,
+    page_size: usize,
+    rows: std::vec::IntoIter<(Key, Value)>,
+    /// Whether the range may hold rows after the current page.
+    more: bool,
+}
+
+impl ScanIter {
+    pub(crate) fn new(state: Arc<ReadState>, opts: &ReadOptions) -> Self {
+        Self::with_page_size(state, opts, SCAN_ITER_PAGE_SIZE)
+    }
+
+    fn with_page_size(state: Arc<ReadState>, opts: &ReadOptions, page_size: usize) -> Self {
+        let mut opts = opts.clone();
+        if opts.snapshot.is_none() {
+            opts.snapshot = Some(state.snapshot());
+        }
+        state.metrics.record_scan();
+        Self {
+            state,
+            opts,
+            page_size,
+            rows: Vec::new().into_iter(),
+            more: true,
+        }
+    }
+}
+
+impl Iterator for ScanIter {
+    type Item = Result<(Key, Value)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(row) = self.rows.next() {
+                return Some(Ok(row));
+            }
+            if !self.more {
+                return None;
+            }
+            match self.state.scan_page(&self.opts, self.page_size) {
+                Ok((rows, resume)) => {
+                    self.rows = rows.into_iter();
+                    self.more = resume.is_some();
+                    if resume.is_some() {
+                        self.opts.lower_bound = resume;
+                    }
+                }
+                Err(e) => {
+                    self.more = false;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+/// Engine state shared between the writer and lock-free readers.
+pub struct ReadState {
+    /// In-memory sorted buffer for recent writes.
+    pub(crate) memtable: RwLock<MemTable>,
+    /// Frozen MemTables and live SSTables, swapped atomically.
+    pub(crate) version: ArcSwap<Version>,
+    /// TTL index for key expiration.
+    pub(crate) ttl_index: RwLock<TtlIndex>,
+    /// Optional key -> value cache for values read from SSTables.
+    pub(crate) row_cache: ArcSwapOption<RowCache>,
+    /// Runtime operation metrics.
+    pub(crate) metrics: Arc<EngineMetrics>,
+    /// One extra strong reference per live snapshot.
+    pub(crate) snapshot_pins: Arc<()>,
+    /// Order scans return keys in.
+    pub(crate) comparator: Comparator,
+}
+
+impl ReadState {
+    /// The current version.
+    pub(crate) fn current(&self) -> Arc<Version> {
+        self.version.load_full()
+    }
+
+    /// Publish a new version; readers pick it up on their next load.
+    pub(crate) fn publish(&self, frozen: Vec<Arc<MemTable>>, sstables: Vec<Arc<SSTable>>) {
+        self.version.store(Arc::new(Version::new(frozen, sstables)));
+    }
+
+    /// Get a value by key with per-read options. Keys with an expired
+    /// TTL resolve to `None`.
+    pub(crate) fn get_opt(&self, key: &[u8], opts: &ReadOptions) -> Result<Option<Value>> {
+        // Check TTL expiration first
+        if self.ttl_index.read().is_expired(key) {
+            return Ok(None);
+        }
+
+        let result = self.lookup(key, opts)?;
+        self.metrics.record_get(result.as_ref().map(|v| v.len()));
+        Ok(result.map(PinnedValue::into_vec))
+    }
+
+    /// Like [`get_opt`](Self::get_opt), borrowing the value from where
+    /// it was found instead of copying it; see [`PinnedValue`].
+    pub(crate) fn get_pinned<'a>(
+        &'a self,
+        key: &[u8],
+        opts: &'a ReadOptions,
+    ) -> Result<Option<PinnedValue<'a>>> {
+        if self.ttl_index.read().is_expired(key) {
+            return Ok(None);
+        }
+
+        let result = self.lookup(key, opts)?;
+        self.metrics.record_get(result.as_ref().map(|v| v.len()));
+        Ok(result)
+    }
+
+    /// Returns the length of the value of `key`, or `None` if it is not
+    /// live, resolving it like [`get_opt`](Self::get_opt) but never
+    /// copying the value. SSTables whose filter rules the key out are
+    /// skipped; the others read the one block the index points to.
+    pub(crate) fn value_len(&self, key: &[u8], opts: &ReadOptions) -> Result<Option<usize>> {
+        if self.ttl_index.read().is_expired(key) {
+            return Ok(None);
+        }
+
+        let version = match &opts.snapshot {
+            Some(snapshot) => {
+                if let Some(entry) = snapshot.memtable().get(key) {
+                    return Ok(entry.as_ref().map(Vec::len));
+                }
+                Arc::clone(snapshot.version())
+            }
+            None => {
+                if let Some(entry) = self.memtable.read().lookup(key) {
+                    return Ok(entry.map(Vec::len));
+                }
+                self.current()
+            }
+        };
+        for frozen in version.frozen().iter().rev() {
+            if let Some(entry) = frozen.lookup(key) {
+                return Ok(entry.map(Vec::len));
+            }
+        }
+
+        // The row cache reflects the latest state only
+        if opts.snapshot.is_none() {
+            if let Some(cache) = &*self.row_cache.load() {
+                if let Some(value) = cache.get(key) {
+                    return Ok(Some(value.len()));
+                }
+            }
+        }
+
+        for table in version.sstables().iter().rev() {
+            if !table.covers(key) {
+                continue;
+            }
+            if !table.may_contain(key) {
+                self.metrics.record_filter_probe(false, false);
+                continue;
+            }
+            let (entry, _) = table.value_len_traced(key, opts.verify_checksums, opts.fill_cache)?;
+            self.metrics.record_filter_probe(true, entry.is_some());
+            if let Some(len) = entry {
+                return Ok(len);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Resolve the newest version of a key across MemTables and SSTables.
+    /// Tombstones shadow older versions and resolve to `None`.
+    fn lookup<'a>(&'a self, key: &[u8], opts: &'a ReadOptions) -> Result<Option<PinnedValue<'a>>> {
+        // The MemTables are authoritative for recent writes, so the row
+        // cache only ever holds values that live in SSTables. It always
+        // reflects the latest state, so snapshot reads bypass it.
+        let row_cache = self
+            .row_cache
+            .load_full()
+            .filter(|_| opts.snapshot.is_none());
+        let generation = row_cache.as_ref().map_or(0, |cache| cache.generation());
+
+        let version = match &opts.snapshot {
+            Some(snapshot) => {
+                if let Some(entry) = snapshot.memtable().get(key) {
+                    return Ok(entry.as_deref().map(PinnedValue::borrowed));
+                }
+                Arc::clone(snapshot.version())
+            }
+            None => {
+                let memtable = self.memtable.read();
+                match RwLockReadGuard::try_map(memtable, |m| m.get(key).map(Vec::as_slice)) {
+                    Ok(value) => return Ok(Some(PinnedValue::memtable(value))),
+                    // A tombstone
+                    Err(memtable) if memtable.contains_key(key) => return Ok(None),
+                    Err(_) => {}
+                }
+                self.current()
+            }
+        };
+        for frozen in version.frozen().iter().rev() {
+            if let Some(entry) = frozen.lookup(key) {
+                return Ok(entry.map(|_| PinnedValue::frozen(Arc::clone(frozen), key.to_vec())));
+            }
+        }
+
+        if let Some(cache) = &row_cache {
+            let cached = cache.get(key);
+            self.metrics.record_row_cache(cached.is_some());
+            if let Some(value) = cached {
+                return Ok(Some(PinnedValue::cached(value)));
+            }
+        }
+
+        let mut tables_consulted = 0;
+        let mut blocks_read = 0;
+        for table in version.sstables().iter().rev() {
+            // Tables whose key range rules the key out are not consulted
+            if !table.covers(key) {
+                continue;
+            }
+            tables_consulted += 1;
+            if !table.may_contain(key) {
+                self.metrics.record_filter_probe(false, false);
+                continue;
+            }
+            let (entry, blocks) =
+                table.get_pinned_traced(key, opts.verify_checksums, opts.fill_cache)?;
+            blocks_read += blocks as u64;
+            self.metrics.record_filter_probe(true, entry.is_some());
+            if let Some(entry) = entry {
+                self.metrics
+                    .record_read_amplification(tables_consulted, blocks_read);
+                if let (Some(cache), Some((block, range)), true) =
+                    (&row_cache, &entry, opts.fill_cache)
+                {
+                    cache.insert_if_current(
+                        key.to_vec(),
+                        Arc::from(&block[range.clone()]),
+                        generation,
+                    );
+                }
+                return Ok(entry.map(|(block, range)| PinnedValue::block(block, range)));
+            }
+        }
+
+        self.metrics
+            .record_read_amplification(tables_consulted, blocks_read);
+        Ok(None)
+    }
+
+    /// Scan key-value pairs in sorted order with per-read options.
+    /// Only keys within `[lower_bound, upper_bound)` are returned.
+    pub(crate) fn scan_opt(&self, opts: &ReadOptions) -> Result<Vec<(Key, Value)>> {
+        self.metrics.record_scan();
+        self.scan_page(opts, usize::MAX).map(|(rows, _)| rows)
+    }
+
+    /// Scan one page of the range, reading at most `limit` records from
+    /// each MemTable and SSTable. Returns the live rows and, if the range
+    /// may hold more keys, the lower bound to resume from.
+    ///
+    /// Paging should use a snapshot so every page reads the same state.
+    pub(crate) fn scan_page(&self, opts: &ReadOptions, limit: usize) -> Result<ScanPage> {
+        let lower = opts.lower_bound.as_deref();
+        let upper = opts.upper_bound.as_deref();
+        let comparator = &self.comparator;
+        let bytewise = comparator.is_bytewise_from(lower);
+        // A page ending before its last key needs two to make progress
+        let limit = if bytewise { limit } else { limit.max(2) };
+        let collect = |entries: &BTreeMap<Key, Option<Value>>| {
+            if bytewise {
+                let range = (
+                    lower.map_or(Bound::Unbounded, Bound::Included),
+                    upper.map_or(Bound::Unbounded, Bound::Excluded),
+                );
+                Self::collect_range(entries, range, limit)
+            } else {
+                Self::collect_sorted(entries, comparator, lower, upper, limit)
+            }
+        };
+
+        let (recent, version) = match &opts.snapshot {
+            Some(snapshot) => (collect(snapshot.memtable()), Arc::clone(snapshot.version())),
+            None => {
+                let memtable = self.memtable.read();
+                (collect(memtable.entries()), self.current())
+            }
+        };
+
+        // A source cut off at `limit` records is only complete up to its
+        // last key, so the page ends at the smallest such key
+        let mut page_end: Option<Key> = None;
+        let mut merged: BTreeMap<Key, Option<Value>> = BTreeMap::new();
+        let mut add = |records: Vec<(Key, Option<Value>)>| {
+            if records.len() >= limit {
+                let last = &records[records.len() - 1].0;
+                if page_end
+                    .as_ref()
+                    .is_none_or(|end| comparator.compare(last, end).is_lt())
+                {
+                    page_end = Some(last.clone());
+                }
+            }
+            merged.extend(records);
+        };
+        for table in version.sstables() {
+            if table.overlaps(lower, upper) {
+                add(table.scan_range_limited(
+                    lower,
+                    upper,
+                    limit,
+                    opts.verify_checksums,
+                    opts.fill_cache,
+                )?);
+            }
+        }
+        for frozen in version.frozen() {
+            add(collect(frozen.entries()));
+        }
+        add(recent);
+
+        let resume = match page_end {
+            // Resume from the smallest key after the page
+            Some(mut end) if bytewise => {
+                end.push(0);
+                merged.split_off(&end);
+                Some(end)
+            }
+            // No key is known to follow `end`, so the page stops before it
+            Some(end) => {
+                merged.retain(|k, _| comparator.compare(k, &end).is_lt());
+                Some(end)
+            }
+            None => None,
+        };
+
+        let ttl_index = self.ttl_index.read();
+        let mut rows: Vec<_> = merged
+            .into_iter()
+            .filter_map(|(k, v)| v.map(|v| (k, v)))
+            .filter(|(k, _)| !ttl_index.is_expired(k))
+            .collect();
+        if !bytewise {
+            comparator.sort(&mut rows);
+        }
+        Ok((rows, resume))
+    }
+
+    /// Clone the first `limit` MemTable entries within `range`.
+    fn collect_range(
+        entries: &BTreeMap<Key, Option<Value>>,
+        range: (Bound<&[u8]>, Bound<&[u8]>),
+        limit: usize,
+    ) -> Vec<(Key, Option<Value>)> {
+        entries
+            .range::<[u8], _>(range)
+            .take(limit)
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect()
+    }
+
+    /// Clone the first `limit` MemTable entries within `[lower, upper)`
+    /// in `comparator` order.
+    fn collect_sorted(
+        entries: &BTreeMap<Key, Option<Value>>,
+        comparator: &Comparator,
+        lower: Option<&[u8]>,
+        upper: Option<&[u8]>,
+        limit: usize,
+    ) -> Vec<(Key, Option<Value>)> {
+        let mut records: Vec<_> = entries
+            .iter()
+            .filter(|(k, _)| comparator.in_bounds(k, lower, upper))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        comparator.sort(&mut records);
+        records.truncate(limit);
+        records
+    }
+
+    /// Take a consistent point-in-time view of the active MemTable and
+    /// the current version.
+    pub(crate) fn snapshot(&self) -> Snapshot {
+        let memtable = self.memtable.read();
+        Snapshot::new(
+            memtable.entries().clone(),
+            self.current(),
+            Arc::clone(&self.snapshot_pins),
+        )
+    }
+
+    /// Returns the number of snapshots currently alive.
+    pub(crate) fn live_snapshots(&self) -> usize {
+        Arc::strong_count(&self.snapshot_pins) - 1
+    }
+
+    /// Get the remaining TTL for a key in milliseconds.
+    pub(crate) fn ttl(&self, key: &[u8]) -> Option<u64> {
+        self.ttl_index.read().remaining_ttl(key)
+    }
+
+    /// Returns the number of entries in the active MemTable.
+    pub(crate) fn len(&self) -> usize {
+        self.memtable.read().len()
+    }
+
+    /// Returns the approximate size of the active MemTable in bytes.
+    pub(crate) fn memtable_size(&self) -> usize {
+        self.memtable.read().size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_state() -> ReadState {
+        ReadState {
+            memtable: RwLock::new(MemTable::new()),
+            version: ArcSwap::from_pointee(Version::new(Vec::new(), Vec::new())),
+            ttl_index: RwLock::new(TtlIndex::new()),
+            row_cache: ArcSwapOption::empty(),
+            metrics: Arc::new(EngineMetrics::new()),
+            snapshot_pins: Arc::new(()),
+            comparator: Comparator::default(),
+        }
+    }
+
+    #[test]
+    fn test_frozen_memtables_are_read() {
+        let state = read_state();
+        let mut older = MemTable::new();
+        older.insert(b"a".to_vec(), b"old".to_vec());
+        older.insert(b"b".to_vec(), b"old".to_vec());
+        let mut newer = MemTable::new();
+        newer.insert(b"a".to_vec(), b"new".to_vec());
+        newer.delete(b"b".to_vec());
+        state.publish(vec![Arc::new(older), Arc::new(newer)], Vec::new());
+        state
+            .memtable
+            .write()
+            .insert(b"c".to_vec(), b"active".to_vec());
+
+        let opts = ReadOptions::default();
+        assert_eq!(state.get_opt(b"a", &opts).unwrap(), Some(b"new".to_vec()));
+        assert_eq!(state.get_opt(b"b", &opts).unwrap(), None);
+        assert_eq!(
+            state.scan_opt(&opts).unwrap(),
+            vec![
+                (b"a".to_vec(), b"new".to_vec()),
+                (b"c".to_vec(), b"active".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scan_pages_cover_every_source() {
+        let state = read_state();
+        let mut older = MemTable::new();
+        for key in [b"a", b"b", b"c", b"d", b"e"] {
+            older.insert(key.to_vec(), b"old".to_vec());
+        }
+        let mut newer = MemTable::new();
+        newer.insert(b"a".to_vec(), b"new".to_vec());
+        newer.delete(b"b".to_vec());
+        newer.insert(b"f".to_vec(), b"new".to_vec());
+        state.publish(vec![Arc::new(older), Arc::new(newer)], Vec::new());
+
+        let mut opts = ReadOptions::default();
+        let mut rows = Vec::new();
+        loop {
+            let (page, resume) = state.scan_page(&opts, 2).unwrap();
+            rows.extend(page);
+            match resume {
+                Some(resume) => opts.lower_bound = Some(resume),
+                None => break,
+            }
+        }
+        assert_eq!(rows, state.scan_opt(&ReadOptions::default()).unwrap());
+        assert_eq!(rows.len(), 5);
+    }
+
+    #[test]
+    fn test_scan_iter_reads_pages_from_a_snapshot() {
+        let state = Arc::new(read_state());
+        let mut frozen = MemTable::new();
+        for i in 0..10u8 {
+            frozen.insert(vec![b'k', i], b"old".to_vec());
+        }
+        state.publish(vec![Arc::new(frozen)], Vec::new());
+        state
+            .memtable
+            .write()
+            .insert(vec![b'k', 3], b"new".to_vec());
+
+        let opts = ReadOptions {
+            lower_bound: Some(vec![b'k', 2]),
+            ..ReadOptions::default()
+        };
+        let expected = state.scan_opt(&opts).unwrap();
+        let mut iter = ScanIter::with_page_size(Arc::clone(&state), &opts, 3);
+        let first = iter.next().unwrap().unwrap();
+        state
+            .memtable
+            .write()
+            .insert(vec![b'k', 9], b"late".to_vec());
+        state.memtable.write().delete(vec![b'k', 5]);
+        let rows: Vec<_> = std::iter::once(Ok(first))
+            .chain(iter)
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(rows, expected);
+        assert_eq!(rows.len(), 8);
+        assert_eq!(rows[1], (vec![b'k', 3], b"new".to_vec()));
+    }
+
+    #[test]
+    fn test_scan_pages_in_comparator_order() {
+        let state = ReadState {
+            comparator: Comparator::new(super::super::comparator::CaseInsensitive),
+            ..read_state()
+        };
+        let mut frozen = MemTable::new();
+        for key in [&b"b"[..], b"D", b"\xff\xffdb", b"a"] {
+            frozen.insert(key.to_vec(), b"old".to_vec());
+        }
+        state.publish(vec![Arc::new(frozen)], Vec::new());
+        for key in ["C", "A"] {
+            state
+                .memtable
+                .write()
+                .insert(key.as_bytes().to_vec(), b"new".to_vec());
+        }
+
+        let mut opts = ReadOptions::default();
+        let mut keys = Vec::new();
+        loop {
+            // A limit of one still makes progress
+            let (page, resume) = state.scan_page(&opts, 1).unwrap();
+            keys.extend(page.into_iter().map(|(k, _)| k));
+            match resume {
+                Some(resume) => opts.lower_bound = Some(resume),
+                None => break,
+            }
+        }
+        let expected: Vec<&[u8]> = vec![b"A", b"a", b"b", b"C", b"D", b"\xff\xffdb"];
+        assert_eq!(keys, expected);
+        opts.lower_bound = Some(b"B".to_vec());
+        opts.upper_bound = Some(b"d".to_vec());
+        let keys: Vec<Key> = state
+            .scan_opt(&opts)
+            .unwrap()
+            .into_iter()
+            .map(|(k, _)| k)
+            .collect();
+        assert_eq!(keys, [b"b".to_vec(), b"C".to_vec(), b"D".to_vec()]);
+    }
+
+    #[test]
+    fn test_snapshot_keeps_its_version() {
+        let state = read_state();
+        let mut frozen = MemTable::new();
+        frozen.insert(b"k".to_vec(), b"v1".to_vec());
+        state.publish(vec![Arc::new(frozen)], Vec::new());
+        let snapshot = state.snapshot();
+
+        // A later version no longer has the frozen MemTable
+        state.publish(Vec::new(), Vec::new());
+        let opts = ReadOptions {
+            snapshot: Some(snapshot),
+            ..ReadOptions::default()
+        };
+        assert_eq!(state.get_opt(b"k", &opts).unwrap(), Some(b"v1".to_vec()));
+        assert_eq!(state.get_opt(b"k", &ReadOptions::default()).unwrap(), None);
+    }
+}
@@ -2,20 +2,111 @@
 //! Provides durability by logging all mutations to disk
 //! before they are applied to the in-memory MemTable.
 
-use std::fs::{File, OpenOptions};
-use std::io::{BufWriter, Read, Write};
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
 
+use crate::engine::batch::BatchOp;
+use crate::engine::env::{Env, RandomAccessFile, WritableFile};
+use crate::engine::io::SyncMethod;
 use crate::engine::memtable::MemTable;
-use crate::error::Result;
+use crate::error::{OblivionError, Result};
 use crate::types::{Key, Value};
 
+/// Bytes replayed between two recovery progress reports.
+pub const RECOVERY_PROGRESS_INTERVAL: usize = 4 * 1024 * 1024;
+
+/// Progress of an ongoing WAL replay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecoveryProgress {
+    /// Bytes of valid records replayed so far.
+    pub bytes_replayed: u64,
+    /// Size of the WAL being replayed.
+    pub total_bytes: u64,
+    /// Records applied to the MemTable so far.
+    pub records_applied: u64,
+}
+
+/// Outcome of a WAL replay.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RecoveryStats {
+    /// Bytes of valid records replayed.
+    pub bytes_replayed: u64,
+    /// Records applied to the MemTable.
+    pub records_applied: u64,
+    /// Corrupt or torn records at which replay stopped.
+    pub records_skipped: u64,
+    /// Bytes after the last valid record that were not replayed.
+    pub bytes_skipped: u64,
+    /// Wall time spent replaying.
+    pub duration: Duration,
+    /// Sequence number of the last replayed write.
+    pub last_sequence: u64,
+}
+
+/// Whether a record found by [`WriteAheadLog::inspect`] can be replayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordStatus {
+    /// Checksum matches and the operation is known.
+    Ok,
+    /// Checksum does not match the record's bytes.
+    CrcMismatch,
+    /// Checksum matches but the operation byte is unknown.
+    UnknownOp,
+    /// The log ends inside the record.
+    Torn,
+}
+
+/// One record of a log, as found by [`WriteAheadLog::inspect`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordInfo {
+    /// Byte offset of the record in the log.
+    pub offset: u64,
+    /// Length of the record in bytes (what is left of the log for a torn
+    /// record).
+    pub len: u64,
+    /// Operation byte: 1 PUT, 2 DELETE, 3 sequence marker, 4 PUT and
+    /// 5 DELETE in a column family.
+    pub op: u8,
+    /// Key bytes (empty for a marker or a torn record). Column family
+    /// records start with the family id, 4 bytes LE.
+    pub key: Key,
+    /// Length of the value in bytes.
+    pub value_len: usize,
+    /// Sequence number of a PUT or DELETE, or the number recorded by a
+    /// marker. `None` for damaged records.
+    pub sequence: Option<u64>,
+    /// Whether replay accepts the record.
+    pub status: RecordStatus,
+}
+
+/// One log replayed on its own, to be merged after the logs before it.
+struct Replay {
+    memtable: MemTable,
+    families: BTreeMap<u32, MemTable>,
+    stats: RecoveryStats,
+    /// Whether the log's writes are numbered from a sequence marker in
+    /// it, rather than on from the previous log's.
+    marked: bool,
+}
+
 /// Operation type for WAL entries.
 #[derive(Debug, Clone, Copy, PartialEq)]
 #[repr(u8)]
 enum OpType {
     Put = 1,
     Delete = 2,
+    /// Sequence number of the last write before this log, written at
+    /// the start of each new log. Shaped like a PUT of an empty key.
+    Sequence = 3,
+    /// PUT into a column family; the key starts with the family id.
+    PutCf = 4,
+    /// DELETE from a column family; the key starts with the family id.
+    DeleteCf = 5,
 }
 
 /// Write-Ahead Log for crash recovery and durability.
@@ -25,37 +116,115 @@ enum OpType {
 /// [op_type: 1 byte][key_len: 4 bytes LE][key: N bytes][val_len: 4 bytes LE][value: M bytes][crc: 4 bytes]
 /// ```
 ///
+/// Every PUT and DELETE, in the default keyspace or a column family,
+/// takes the next sequence number. Numbers are not stored per record: a
+/// log starts with a sequence marker (the number of the write before
+/// it) and replay counts records from there, so numbers keep increasing
+/// across rotations and restarts.
+///
 /// Uses BufWriter to batch syscalls for improved write throughput.
 pub struct WriteAheadLog {
     /// Path to the WAL file on disk.
     path: PathBuf,
+    /// File system the log is written to.
+    env: Env,
     /// Buffered writer wrapping the file handle.
     /// BufWriter reduces the number of write syscalls by
     /// batching small writes into larger chunks (8KB default).
-    writer: BufWriter<File>,
+    writer: BufWriter<Box<dyn WritableFile>>,
+    /// Whether every append is synced before returning.
+    sync: bool,
+    /// How appends are synced when `sync` is set.
+    sync_method: SyncMethod,
+    /// Current file size in bytes.
+    size: u64,
+    /// Whether appends were written since the log was last synced.
+    unsynced: bool,
+    /// Sequence number of the last appended write.
+    last_sequence: u64,
+    /// io_uring used for appends and syncs, if enabled.
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    ring: Option<std::sync::Arc<crate::engine::uring::Ring>>,
 }
 
 impl WriteAheadLog {
     /// Open or create a WAL file at the specified path.
     /// Uses BufWriter for write batching to reduce syscall overhead.
     pub fn open(path: PathBuf) -> Result<Self> {
-        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Self::open_in(&Env::default(), path)
+    }
+
+    /// Open or create a WAL file at `path` on the file system `env`.
+    pub fn open_in(env: &Env, path: PathBuf) -> Result<Self> {
+        let created = !env.exists(&path);
+        let file = env.open_append(&path)?;
+        if created {
+            env.sync_parent(&path)?;
+        }
+        let size = env.file_size(&path)?;
 
         Ok(Self {
             path,
+            env: env.clone(),
             writer: BufWriter::new(file),
+            sync: true,
+            sync_method: SyncMethod::Fsync,
+            size,
+            unsynced: false,
+            last_sequence: 0,
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            ring: None,
         })
     }
 
+    /// Set whether appends are fsynced (`true`) or only flushed to the OS.
+    pub fn set_sync(&mut self, sync: bool) {
+        self.sync = sync;
+    }
+
+    /// Choose between `fsync` and `fdatasync` for synced appends.
+    pub fn set_sync_method(&mut self, sync_method: SyncMethod) {
+        self.sync_method = sync_method;
+    }
+
+    /// Issue appends and syncs through `ring`, batching each synced
+    /// append into one submission.
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    pub fn set_ring(&mut self, ring: std::sync::Arc<crate::engine::uring::Ring>) {
+        self.ring = Some(ring);
+    }
+
+    /// Returns the size of the WAL file in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Returns true if appends were written since the log was last
+    /// synced, which only happens with syncing off.
+    pub fn has_unsynced_writes(&self) -> bool {
+        self.unsynced
+    }
+
     /// Returns the path to the WAL file.
     pub fn path(&self) -> &PathBuf {
         &self.path
     }
 
-    /// Encode a PUT entry into the binary WAL format.
-    fn encode_put(key: &[u8], value: &[u8]) -> Vec<u8> {
+    /// Returns the sequence number of the last appended write.
+    pub fn last_sequence(&self) -> u64 {
+        self.last_sequence
+    }
+
+    /// Continue numbering after `sequence`, the last write replayed at
+    /// recovery.
+    pub fn set_last_sequence(&mut self, sequence: u64) {
+        self.last_sequence = sequence;
+    }
+
+    /// Encode an entry into the binary WAL format.
+    fn encode(op: OpType, key: &[u8], value: &[u8]) -> Vec<u8> {
         let mut buf = Vec::new();
-        buf.push(OpType::Put as u8);
+        buf.push(op as u8);
         buf.extend_from_slice(&(key.len() as u32).to_le_bytes());
         buf.extend_from_slice(key);
         buf.extend_from_slice(&(value.len() as u32).to_le_bytes());
@@ -65,34 +234,111 @@ impl WriteAheadLog {
         buf
     }
 
+    /// Encode a PUT entry into the binary WAL format.
+    fn encode_put(key: &[u8], value: &[u8]) -> Vec<u8> {
+        Self::encode(OpType::Put, key, value)
+    }
+
     /// Encode a DELETE entry into the binary WAL format.
     fn encode_delete(key: &[u8]) -> Vec<u8> {
-        let mut buf = Vec::new();
-        buf.push(OpType::Delete as u8);
-        buf.extend_from_slice(&(key.len() as u32).to_le_bytes());
-        buf.extend_from_slice(key);
-        buf.extend_from_slice(&0u32.to_le_bytes());
-        let crc = crc32fast::hash(&buf);
-        buf.extend_from_slice(&crc.to_le_bytes());
-        buf
+        Self::encode(OpType::Delete, key, &[])
+    }
+
+    /// Encode a column family write: `key` prefixed with the family id.
+    fn encode_cf(op: OpType, cf_id: u32, key: &[u8], value: &[u8]) -> Vec<u8> {
+        let mut tagged = Vec::with_capacity(4 + key.len());
+        tagged.extend_from_slice(&cf_id.to_le_bytes());
+        tagged.extend_from_slice(key);
+        Self::encode(op, &tagged, value)
     }
 
     /// Append a PUT operation to the WAL and flush to disk.
     /// BufWriter batches the write, then flush + sync ensures durability.
-    pub fn append_put(&mut self, key: &Key, value: &Value) -> Result<()> {
+    /// Returns the write's sequence number.
+    pub fn append_put(&mut self, key: &Key, value: &Value) -> Result<u64> {
         let encoded = Self::encode_put(key, value);
-        self.writer.write_all(&encoded)?;
-        self.writer.flush()?;
-        self.writer.get_ref().sync_all()?;
-        Ok(())
+        self.append(&encoded)?;
+        self.last_sequence += 1;
+        Ok(self.last_sequence)
     }
 
     /// Append a DELETE operation to the WAL and flush to disk.
-    pub fn append_delete(&mut self, key: &Key) -> Result<()> {
+    /// Returns the write's sequence number.
+    pub fn append_delete(&mut self, key: &Key) -> Result<u64> {
         let encoded = Self::encode_delete(key);
-        self.writer.write_all(&encoded)?;
+        self.append(&encoded)?;
+        self.last_sequence += 1;
+        Ok(self.last_sequence)
+    }
+
+    /// Append a PUT into column family `cf_id`.
+    /// Returns the write's sequence number.
+    pub fn append_cf_put(&mut self, cf_id: u32, key: &[u8], value: &[u8]) -> Result<u64> {
+        self.append(&Self::encode_cf(OpType::PutCf, cf_id, key, value))?;
+        self.last_sequence += 1;
+        Ok(self.last_sequence)
+    }
+
+    /// Append a DELETE from column family `cf_id`.
+    /// Returns the write's sequence number.
+    pub fn append_cf_delete(&mut self, cf_id: u32, key: &[u8]) -> Result<u64> {
+        self.append(&Self::encode_cf(OpType::DeleteCf, cf_id, key, &[]))?;
+        self.last_sequence += 1;
+        Ok(self.last_sequence)
+    }
+
+    /// Append every write of a batch with a single write and sync.
+    /// Returns the sequence number of the first write; the others
+    /// follow it.
+    pub fn append_batch(&mut self, ops: &[BatchOp]) -> Result<u64> {
+        let mut encoded = Vec::new();
+        for op in ops {
+            encoded.extend(match op {
+                BatchOp::Put { key, value, .. } => Self::encode_put(key, value),
+                BatchOp::Delete { key } => Self::encode_delete(key),
+            });
+        }
+        self.append(&encoded)?;
+        let first = self.last_sequence + 1;
+        self.last_sequence += ops.len() as u64;
+        Ok(first)
+    }
+
+    /// Record the current sequence number, so a fresh log's writes are
+    /// numbered on from it when replayed.
+    pub fn append_sequence_marker(&mut self) -> Result<()> {
+        let encoded = Self::encode(OpType::Sequence, &[], &self.last_sequence.to_le_bytes());
+        self.append(&encoded)
+    }
+
+    /// Flush buffered writes and sync the log to disk, regardless of
+    /// whether appends are synced.
+    pub fn sync(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        self.writer.get_mut().sync(self.sync_method)?;
+        self.unsynced = false;
+        Ok(())
+    }
+
+    /// Write an encoded entry, flush it to the OS and sync if enabled.
+    fn append(&mut self, encoded: &[u8]) -> Result<()> {
+        // The buffer is always flushed after an append, so it is empty here
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        if let (Some(ring), Some(file)) = (&self.ring, self.writer.get_ref().as_file()) {
+            let sync = self.sync.then_some(self.sync_method);
+            ring.append(file, self.size, encoded, sync)?;
+            self.size += encoded.len() as u64;
+            self.unsynced |= !self.sync;
+            return Ok(());
+        }
+        self.writer.write_all(encoded)?;
         self.writer.flush()?;
-        self.writer.get_ref().sync_all()?;
+        self.size += encoded.len() as u64;
+        if self.sync {
+            self.writer.get_mut().sync(self.sync_method)?;
+        } else {
+            self.unsynced = true;
+        }
         Ok(())
     }
 
@@ -101,115 +347,542 @@ impl WriteAheadLog {
         // Flush any remaining buffered data
         self.writer.flush()?;
         // Truncate
-        let _file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .truncate(true)
-            .open(&self.path)?;
+        let _file = self.env.create(&self.path, false)?;
         // Reopen in append mode with BufWriter
-        let file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&self.path)?;
+        let file = self.env.open_append(&self.path)?;
         self.writer = BufWriter::new(file);
+        self.size = 0;
+        self.unsynced = false;
         Ok(())
     }
 
-    /// Recover the MemTable state from the WAL file.
-    pub fn recover(path: &PathBuf) -> Result<MemTable> {
-        let mut memtable = MemTable::new();
-
-        if !path.exists() {
-            return Ok(memtable);
+    /// Flush buffered writes, move the log to `segment` and continue in a
+    /// fresh, empty log at the original path. Returns the segment's size.
+    ///
+    /// Called when the MemTable is frozen, so the segment holds exactly
+    /// the frozen MemTable's writes and can be deleted once it is flushed.
+    pub fn rotate(&mut self, segment: &Path) -> Result<u64> {
+        self.writer.flush()?;
+        self.env.rename(&self.path, segment)?;
+        let file = self.env.open_append(&self.path)?;
+        self.writer = BufWriter::new(file);
+        // Persist the segment's name and the fresh log's
+        self.env.sync_parent(segment)?;
+        if segment.parent() != self.path.parent() {
+            self.env.sync_parent(&self.path)?;
         }
+        // The segment is synced separately; the fresh log has nothing to sync
+        self.unsynced = false;
+        Ok(std::mem::take(&mut self.size))
+    }
 
-        let mut file = File::open(path)?;
-        let mut data = Vec::new();
-        file.read_to_end(&mut data)?;
+    /// Path of the rotated segment with file number `id` in `dir`.
+    pub fn segment_path(dir: &Path, id: u64) -> PathBuf {
+        dir.join(format!("oblivion_{:06}.wal", id))
+    }
 
-        let mut cursor = 0;
-        let len = data.len();
+    /// File numbers and paths of the rotated segments in `dir` whose
+    /// MemTables were not flushed yet, oldest first.
+    pub fn segments(dir: &Path) -> Result<Vec<(u64, PathBuf)>> {
+        Self::segments_in(&Env::default(), dir)
+    }
 
-        while cursor < len {
-            if cursor + 5 > len {
-                break;
+    /// Like [`segments`](Self::segments), listing `dir` on the file
+    /// system `env`.
+    pub fn segments_in(env: &Env, dir: &Path) -> Result<Vec<(u64, PathBuf)>> {
+        let mut segments = Vec::new();
+        for path in env.list(dir)? {
+            let id = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix("oblivion_"))
+                .and_then(|rest| rest.strip_suffix(".wal"))
+                .and_then(|id| id.parse::<u64>().ok());
+            if let Some(id) = id {
+                segments.push((id, path));
             }
+        }
+        segments.sort_unstable();
+        Ok(segments)
+    }
 
-            let op_byte = data[cursor];
-            cursor += 1;
+    /// Recover the MemTable state from the WAL file.
+    pub fn recover(path: &PathBuf) -> Result<MemTable> {
+        Self::recover_with_progress(path, |_| {}).map(|(memtable, _)| memtable)
+    }
 
-            let key_len = u32::from_le_bytes([
-                data[cursor],
-                data[cursor + 1],
-                data[cursor + 2],
-                data[cursor + 3],
-            ]) as usize;
-            cursor += 4;
+    /// Recover the MemTable, calling `progress` roughly every
+    /// [`RECOVERY_PROGRESS_INTERVAL`] bytes and once at the end, and
+    /// return statistics about the replay.
+    ///
+    /// Replay stops at the first corrupt or torn record; it and
+    /// everything after it count as skipped.
+    pub fn recover_with_progress<F>(
+        path: &PathBuf,
+        progress: F,
+    ) -> Result<(MemTable, RecoveryStats)>
+    where
+        F: FnMut(&RecoveryProgress),
+    {
+        Self::recover_all(std::slice::from_ref(path), progress)
+    }
 
-            if cursor + key_len > len {
-                break;
-            }
-            let key = data[cursor..cursor + key_len].to_vec();
-            cursor += key_len;
+    /// Replay several logs (rotated segments, then the live log) in order
+    /// into one MemTable. Progress is reported per log; the statistics
+    /// cover all of them. Column family writes are skipped.
+    pub fn recover_all<F>(paths: &[PathBuf], progress: F) -> Result<(MemTable, RecoveryStats)>
+    where
+        F: FnMut(&RecoveryProgress),
+    {
+        Self::recover_families(&Env::default(), paths, 1, progress)
+            .map(|(memtable, _, stats)| (memtable, stats))
+    }
 
-            if cursor + 4 > len {
-                break;
-            }
-            let val_len = u32::from_le_bytes([
-                data[cursor],
-                data[cursor + 1],
-                data[cursor + 2],
-                data[cursor + 3],
-            ]) as usize;
-            cursor += 4;
-
-            if cursor + val_len > len {
-                break;
+    /// Like [`recover_all`](Self::recover_all), also returning the
+    /// MemTable of each column family with writes in the logs, by id.
+    /// The logs are read from the file system `env`.
+    ///
+    /// With `threads` above 1, up to that many logs are replayed at once
+    /// into MemTables of their own, which are then merged in log order.
+    /// Progress reports of different logs may then interleave.
+    pub(crate) fn recover_families<F>(
+        env: &Env,
+        paths: &[PathBuf],
+        threads: usize,
+        mut progress: F,
+    ) -> Result<(MemTable, BTreeMap<u32, MemTable>, RecoveryStats)>
+    where
+        F: FnMut(&RecoveryProgress),
+    {
+        let started = Instant::now();
+        let replays = if threads > 1 && paths.len() > 1 {
+            Self::replay_parallel(env, paths, threads, &mut progress)?
+        } else {
+            paths
+                .iter()
+                .map(|path| Self::replay(env, path, &mut progress))
+                .collect::<Result<Vec<_>>>()?
+        };
+
+        // Later logs hold later writes, so they are applied last
+        let mut memtable = MemTable::new();
+        let mut families: BTreeMap<u32, MemTable> = BTreeMap::new();
+        let mut stats = RecoveryStats::default();
+        for replay in replays {
+            memtable.merge(replay.memtable);
+            for (id, family) in replay.families {
+                families.entry(id).or_default().merge(family);
             }
-            let value = data[cursor..cursor + val_len].to_vec();
-            cursor += val_len;
+            let replayed = replay.stats;
+            stats.bytes_replayed += replayed.bytes_replayed;
+            stats.records_applied += replayed.records_applied;
+            stats.records_skipped += replayed.records_skipped;
+            stats.bytes_skipped += replayed.bytes_skipped;
+            stats.last_sequence = if replay.marked {
+                replayed.last_sequence
+            } else {
+                stats.last_sequence + replayed.last_sequence
+            };
+        }
+        stats.duration = started.elapsed();
+        Ok((memtable, families, stats))
+    }
 
-            if cursor + 4 > len {
-                break;
+    /// Replay `paths` on up to `threads` threads, forwarding their
+    /// progress reports to `progress` on the calling thread. Returns the
+    /// replays in the order of `paths`.
+    fn replay_parallel<F>(
+        env: &Env,
+        paths: &[PathBuf],
+        threads: usize,
+        progress: &mut F,
+    ) -> Result<Vec<Replay>>
+    where
+        F: FnMut(&RecoveryProgress),
+    {
+        let next = AtomicUsize::new(0);
+        let (report, reports) = mpsc::channel();
+        let mut replays: Vec<(usize, Result<Replay>)> = thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads.min(paths.len()))
+                .map(|_| {
+                    let report = report.clone();
+                    let next = &next;
+                    scope.spawn(move || {
+                        let mut replays = Vec::new();
+                        loop {
+                            let i = next.fetch_add(1, Ordering::Relaxed);
+                            let Some(path) = paths.get(i) else {
+                                return replays;
+                            };
+                            let mut forward = |p: &RecoveryProgress| {
+                                let _ = report.send(*p);
+                            };
+                            replays.push((i, Self::replay(env, path, &mut forward)));
+                        }
+                    })
+                })
+                .collect();
+            // Ends once every worker has dropped its sender
+            drop(report);
+            for report in reports {
+                progress(&report);
             }
-            let stored_crc = u32::from_le_bytes([
-                data[cursor],
-                data[cursor + 1],
-                data[cursor + 2],
-                data[cursor + 3],
-            ]);
-            cursor += 4;
-
-            let record_start = cursor - 4 - val_len - 4 - key_len - 4 - 1;
-            let record_data = &data[record_start..cursor - 4];
-            let computed_crc = crc32fast::hash(record_data);
-
-            if stored_crc != computed_crc {
-                log::warn!(
-                    "CRC mismatch at offset {}, skipping rest of WAL",
-                    record_start
-                );
-                break;
+            workers
+                .into_iter()
+                .flat_map(|worker| {
+                    worker
+                        .join()
+                        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+                })
+                .collect()
+        });
+        replays.sort_by_key(|(i, _)| *i);
+        replays.into_iter().map(|(_, replay)| replay).collect()
+    }
+
+    /// Replay one log into MemTables of its own, numbering its writes
+    /// from its sequence marker, or from 0 if it has none.
+    fn replay<F>(env: &Env, path: &Path, progress: &mut F) -> Result<Replay>
+    where
+        F: FnMut(&RecoveryProgress),
+    {
+        let started = Instant::now();
+        let mut memtable = MemTable::new();
+        let mut families: BTreeMap<u32, MemTable> = BTreeMap::new();
+        let mut marked = false;
+        let mut stats = RecoveryStats::default();
+
+        // A missing log is empty; a secondary instance may also race
+        // the primary deleting a segment it just flushed
+        let data = match env.read(path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Replay {
+                    memtable,
+                    families,
+                    stats,
+                    marked,
+                })
             }
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut cursor = 0;
+        let len = data.len();
+        let mut next_report = RECOVERY_PROGRESS_INTERVAL;
+
+        while cursor < len {
+            let record_start = cursor;
+            let (op_byte, key, value) = match decode_record(&data[cursor..]) {
+                Decoded::Record {
+                    op,
+                    key,
+                    value,
+                    len,
+                } => {
+                    cursor += len;
+                    (op, key.to_vec(), value.to_vec())
+                }
+                Decoded::Torn => {
+                    stats.records_skipped += 1;
+                    break;
+                }
+                Decoded::Corrupt { .. } => {
+                    log::warn!(
+                        "CRC mismatch at offset {}, skipping rest of WAL",
+                        record_start
+                    );
+                    stats.records_skipped += 1;
+                    break;
+                }
+            };
 
             match op_byte {
                 1 => memtable.insert(key, value),
                 2 => memtable.delete(key),
+                3 if value.len() == 8 => {
+                    stats.last_sequence = u64::from_le_bytes(value[..].try_into().unwrap());
+                    marked = true;
+                    stats.bytes_replayed = cursor as u64;
+                    continue;
+                }
+                4 | 5 if key.len() >= 4 => {
+                    let cf_id = u32::from_le_bytes(key[..4].try_into().unwrap());
+                    let family = families.entry(cf_id).or_default();
+                    let key = key[4..].to_vec();
+                    if op_byte == 4 {
+                        family.insert(key, value);
+                    } else {
+                        family.delete(key);
+                    }
+                }
                 _ => {
                     log::warn!("Unknown op type {} at offset {}", op_byte, record_start);
+                    stats.records_skipped += 1;
                     break;
                 }
+            };
+            stats.records_applied += 1;
+            stats.last_sequence += 1;
+            stats.bytes_replayed = cursor as u64;
+
+            if cursor >= next_report {
+                progress(&RecoveryProgress {
+                    bytes_replayed: stats.bytes_replayed,
+                    total_bytes: len as u64,
+                    records_applied: stats.records_applied,
+                });
+                next_report = cursor + RECOVERY_PROGRESS_INTERVAL;
             }
         }
 
-        log::info!("WAL recovery complete: {} entries restored", memtable.len());
+        stats.bytes_skipped = len as u64 - stats.bytes_replayed;
+        stats.duration = started.elapsed();
+        progress(&RecoveryProgress {
+            bytes_replayed: stats.bytes_replayed,
+            total_bytes: len as u64,
+            records_applied: stats.records_applied,
+        });
+
+        log::info!(
+            "WAL recovery of {:?} complete: {} entries restored ({} records, {} bytes in {:?}, {} skipped)",
+            path,
+            memtable.len(),
+            stats.records_applied,
+            stats.bytes_replayed,
+            stats.duration,
+            stats.records_skipped
+        );
+
+        Ok(Replay {
+            memtable,
+            families,
+            stats,
+            marked,
+        })
+    }
 
-        Ok(memtable)
+    /// Read back the PUTs and DELETEs of one log, column family writes
+    /// included, numbering them after
+    /// `last_sequence` unless the log starts with a sequence marker.
+    /// Returns them with the number of the last one. Reading stops at
+    /// the first torn or corrupt record, like replay.
+    pub(crate) fn read_writes(
+        file: &mut dyn RandomAccessFile,
+        last_sequence: u64,
+    ) -> Result<(Vec<LoggedWrite>, u64)> {
+        let size = file.size()?;
+        let data = file.read_at(0, size as usize)?;
+        let mut writes = Vec::new();
+        let mut sequence = last_sequence;
+        let mut cursor = 0;
+        while let Decoded::Record {
+            op,
+            key,
+            value,
+            len,
+        } = decode_record(&data[cursor..])
+        {
+            cursor += len;
+            let (cf, key) = match op {
+                1 | 2 => (None, key),
+                3 if value.len() == 8 => {
+                    sequence = u64::from_le_bytes(value.try_into().unwrap());
+                    continue;
+                }
+                4 | 5 if key.len() >= 4 => {
+                    let cf_id = u32::from_le_bytes(key[..4].try_into().unwrap());
+                    (Some(cf_id), &key[4..])
+                }
+                _ => break,
+            };
+            sequence += 1;
+            writes.push(LoggedWrite {
+                sequence,
+                cf,
+                key: key.to_vec(),
+                value: (op != 2 && op != 5).then(|| value.to_vec()),
+            });
+        }
+        Ok((writes, sequence))
+    }
+
+    /// List every record of the log at `path`, damaged ones included.
+    /// Unlike replay, the walk goes on past a record with a bad checksum
+    /// as long as its length fields stay inside the file, so damage in
+    /// the middle of a log can be told apart from a torn tail. Records
+    /// after a damaged one are numbered as if it had not been there.
+    pub fn inspect(path: &Path) -> Result<Vec<RecordInfo>> {
+        Self::inspect_in(&Env::default(), path)
+    }
+
+    /// Like [`inspect`](Self::inspect), reading the log from the file
+    /// system `env`.
+    pub fn inspect_in(env: &Env, path: &Path) -> Result<Vec<RecordInfo>> {
+        let data = env.read(path)?;
+        let mut records = Vec::new();
+        let mut sequence = 0;
+        let mut cursor = 0;
+        while cursor < data.len() {
+            let rest = &data[cursor..];
+            let mut record = RecordInfo {
+                offset: cursor as u64,
+                len: rest.len() as u64,
+                op: rest[0],
+                key: Vec::new(),
+                value_len: 0,
+                sequence: None,
+                status: RecordStatus::Torn,
+            };
+            match decode_record(rest) {
+                Decoded::Record {
+                    op,
+                    key,
+                    value,
+                    len,
+                } => {
+                    record.len = len as u64;
+                    record.key = key.to_vec();
+                    record.value_len = value.len();
+                    record.status = RecordStatus::Ok;
+                    match op {
+                        1 | 2 => {
+                            sequence += 1;
+                            record.sequence = Some(sequence);
+                        }
+                        3 if value.len() == 8 => {
+                            sequence = u64::from_le_bytes(value.try_into().unwrap());
+                            record.sequence = Some(sequence);
+                        }
+                        4 | 5 if key.len() >= 4 => {
+                            sequence += 1;
+                            record.sequence = Some(sequence);
+                        }
+                        _ => record.status = RecordStatus::UnknownOp,
+                    }
+                }
+                Decoded::Corrupt { len } => {
+                    record.len = len as u64;
+                    let key_len = u32::from_le_bytes(rest[1..5].try_into().unwrap()) as usize;
+                    record.key = rest[5..5 + key_len].to_vec();
+                    record.value_len = len - key_len - 13;
+                    record.status = RecordStatus::CrcMismatch;
+                }
+                Decoded::Torn => {}
+            }
+            cursor += record.len as usize;
+            records.push(record);
+        }
+        Ok(records)
+    }
+
+    /// Returns the sequence number of the first write of a log that
+    /// starts with a sequence marker.
+    pub(crate) fn first_sequence(file: &mut dyn RandomAccessFile) -> Result<Option<u64>> {
+        // A marker is 21 bytes: op, key_len, val_len, 8-byte value, crc
+        let len = file.size()?.min(21);
+        let head = file.read_at(0, len as usize)?;
+        Ok(match decode_record(&head) {
+            Decoded::Record { op: 3, value, .. } if value.len() == 8 => {
+                Some(u64::from_le_bytes(value.try_into().unwrap()) + 1)
+            }
+            _ => None,
+        })
+    }
+
+    /// Move a flushed segment into `archive_dir`, keeping its name.
+    pub(crate) fn archive(env: &Env, segment: &Path, archive_dir: &Path) -> Result<()> {
+        env.create_dir_all(archive_dir)?;
+        let name = segment.file_name().unwrap_or_default();
+        env.rename(segment, &archive_dir.join(name))?;
+        env.sync_dir(archive_dir)?;
+        env.sync_parent(segment)?;
+        Ok(())
+    }
+
+    /// Delete the oldest archived segments until the rest take at most
+    /// `limit` bytes.
+    pub(crate) fn trim_archive(env: &Env, archive_dir: &Path, limit: u64) -> Result<()> {
+        let segments = match Self::segments_in(env, archive_dir) {
+            Ok(segments) => segments,
+            Err(OblivionError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let mut sizes = Vec::with_capacity(segments.len());
+        for (_, path) in &segments {
+            sizes.push(env.file_size(path)?);
+        }
+        let mut total: u64 = sizes.iter().sum();
+        for ((_, path), size) in segments.iter().zip(sizes) {
+            if total <= limit {
+                break;
+            }
+            env.remove_file(path)?;
+            total -= size;
+        }
+        Ok(())
+    }
+}
+
+/// A PUT or DELETE read back from a log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LoggedWrite {
+    pub(crate) sequence: u64,
+    /// Id of the column family written, `None` for the default keyspace.
+    pub(crate) cf: Option<u32>,
+    pub(crate) key: Key,
+    /// `None` for a DELETE.
+    pub(crate) value: Option<Value>,
+}
+
+/// Outcome of decoding the record at the start of a buffer.
+enum Decoded<'a> {
+    /// A complete record with a valid checksum, `len` bytes long.
+    Record {
+        op: u8,
+        key: &'a [u8],
+        value: &'a [u8],
+        len: usize,
+    },
+    /// The buffer ends inside the record.
+    Torn,
+    /// The record's checksum does not match; `len` is what its length
+    /// fields claim.
+    Corrupt { len: usize },
+}
+
+/// Decode the record at the start of `data`.
+fn decode_record(data: &[u8]) -> Decoded<'_> {
+    let read_len = |at: usize| {
+        data.get(at..at + 4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize)
+    };
+    let Some(key_len) = read_len(1) else {
+        return Decoded::Torn;
+    };
+    let key_end = 5 + key_len;
+    let Some(val_len) = read_len(key_end) else {
+        return Decoded::Torn;
+    };
+    let val_end = key_end + 4 + val_len;
+    let Some(stored_crc) = read_len(val_end) else {
+        return Decoded::Torn;
+    };
+    if crc32fast::hash(&data[..val_end]) != stored_crc as u32 {
+        return Decoded::Corrupt { len: val_end + 4 };
+    }
+    Decoded::Record {
+        op: data[0],
+        key: &data[5..key_end],
+        value: &data[key_end + 4..val_end],
+        len: val_end + 4,
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::fs::OpenOptions;
+
     use super::*;
 
     #[test]
@@ -237,4 +910,290 @@ mod tests {
         assert_eq!(memtable.get(b"key1"), None);
         assert_eq!(memtable.get(b"key2"), Some(&b"value2".to_vec()));
     }
+
+    #[test]
+    fn test_wal_size_tracking() {
+        let dir = tempfile::tempdir().unwrap();
+        let wal_path = dir.path().join("test.wal");
+
+        let mut wal = WriteAheadLog::open(wal_path.clone()).unwrap();
+        wal.append_put(&b"hello".to_vec(), &b"world".to_vec())
+            .unwrap();
+        assert_eq!(wal.size(), 23);
+        drop(wal);
+
+        // Size survives reopening, and resets on truncate
+        let mut wal = WriteAheadLog::open(wal_path).unwrap();
+        assert_eq!(wal.size(), 23);
+        wal.truncate().unwrap();
+        assert_eq!(wal.size(), 0);
+    }
+
+    #[test]
+    fn test_recovery_stats_and_progress() {
+        let dir = tempfile::tempdir().unwrap();
+        let wal_path = dir.path().join("test.wal");
+        {
+            let mut wal = WriteAheadLog::open(wal_path.clone()).unwrap();
+            wal.append_put(&b"hello".to_vec(), &b"world".to_vec())
+                .unwrap();
+            wal.append_delete(&b"gone".to_vec()).unwrap();
+        }
+        // Torn tail from a crash mid-append
+        let mut file = OpenOptions::new().append(true).open(&wal_path).unwrap();
+        file.write_all(&[1, 9, 0]).unwrap();
+        drop(file);
+
+        let mut reports = Vec::new();
+        let (memtable, stats) =
+            WriteAheadLog::recover_with_progress(&wal_path, |p| reports.push(*p)).unwrap();
+        assert_eq!(memtable.get(b"hello"), Some(&b"world".to_vec()));
+        assert_eq!(stats.records_applied, 2);
+        assert_eq!(stats.records_skipped, 1);
+        assert_eq!(stats.bytes_replayed, 23 + 17);
+        assert_eq!(stats.bytes_skipped, 3);
+
+        let last = reports.last().unwrap();
+        assert_eq!(last.bytes_replayed, 40);
+        assert_eq!(last.total_bytes, 43);
+        assert_eq!(last.records_applied, 2);
+    }
+
+    #[test]
+    fn test_rotate_and_recover_segments() {
+        let dir = tempfile::tempdir().unwrap();
+        let wal_path = dir.path().join("oblivion.wal");
+        let mut wal = WriteAheadLog::open(wal_path.clone()).unwrap();
+        wal.append_put(&b"a".to_vec(), &b"1".to_vec()).unwrap();
+        wal.append_put(&b"b".to_vec(), &b"1".to_vec()).unwrap();
+
+        let segment = WriteAheadLog::segment_path(dir.path(), 7);
+        let rotated = wal.rotate(&segment).unwrap();
+        assert!(rotated > 0);
+        assert_eq!(wal.size(), 0);
+        wal.append_put(&b"a".to_vec(), &b"2".to_vec()).unwrap();
+        wal.append_delete(&b"b".to_vec()).unwrap();
+        drop(wal);
+
+        assert_eq!(
+            WriteAheadLog::segments(dir.path()).unwrap(),
+            vec![(7, segment.clone())]
+        );
+        let (memtable, stats) = WriteAheadLog::recover_all(&[segment, wal_path], |_| {}).unwrap();
+        assert_eq!(memtable.get(b"a"), Some(&b"2".to_vec()));
+        assert_eq!(memtable.lookup(b"b"), Some(None));
+        assert_eq!(stats.records_applied, 4);
+        assert_eq!(stats.last_sequence, 4);
+    }
+
+    #[test]
+    fn test_column_family_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let wal_path = dir.path().join("oblivion.wal");
+        let mut wal = WriteAheadLog::open(wal_path.clone()).unwrap();
+        wal.append_put(&b"a".to_vec(), &b"1".to_vec()).unwrap();
+        wal.append_cf_put(7, b"a", b"cf").unwrap();
+        wal.append_cf_put(7, b"b", b"cf").unwrap();
+        assert_eq!(wal.append_cf_delete(7, b"b").unwrap(), 4);
+        assert_eq!(wal.append_delete(&b"z".to_vec()).unwrap(), 5);
+        drop(wal);
+
+        let paths = [wal_path.clone()];
+        let env = Env::default();
+        let (memtable, families, stats) =
+            WriteAheadLog::recover_families(&env, &paths, 1, |_| {}).unwrap();
+        assert_eq!(memtable.get(b"a"), Some(&b"1".to_vec()));
+        assert_eq!(families[&7].get(b"a"), Some(&b"cf".to_vec()));
+        assert_eq!(families[&7].lookup(b"b"), Some(None));
+        assert_eq!(stats.records_applied, 5);
+        assert_eq!(stats.last_sequence, 5);
+
+        // They are numbered along with the default keyspace's
+        let (memtable, _) = WriteAheadLog::recover_all(&paths, |_| {}).unwrap();
+        assert_eq!(memtable.len(), 2);
+        let (writes, last) =
+            WriteAheadLog::read_writes(env.open_read(&wal_path, false).unwrap().as_mut(), 0)
+                .unwrap();
+        assert_eq!(last, 5);
+        assert_eq!(
+            writes[3],
+            LoggedWrite {
+                sequence: 4,
+                cf: Some(7),
+                key: b"b".to_vec(),
+                value: None,
+            }
+        );
+        assert_eq!(writes[4].cf, None);
+        let records = WriteAheadLog::inspect(&wal_path).unwrap();
+        assert!(records.iter().all(|r| r.status == RecordStatus::Ok));
+        assert_eq!(records[1].sequence, Some(2));
+    }
+
+    #[test]
+    fn test_parallel_recovery_matches_sequential() {
+        let dir = tempfile::tempdir().unwrap();
+        let wal_path = dir.path().join("oblivion.wal");
+        let mut wal = WriteAheadLog::open(wal_path.clone()).unwrap();
+        let mut paths = Vec::new();
+        for segment in 0..6u64 {
+            for i in 0..50u64 {
+                let key = format!("key_{:02}", (segment * 7 + i) % 60).into_bytes();
+                if i % 9 == 0 {
+                    wal.append_delete(&key).unwrap();
+                } else {
+                    wal.append_put(&key, &segment.to_le_bytes().to_vec())
+                        .unwrap();
+                }
+            }
+            wal.append_cf_put(3, b"cf", &segment.to_le_bytes()).unwrap();
+            let path = WriteAheadLog::segment_path(dir.path(), segment);
+            wal.rotate(&path).unwrap();
+            // The first rotation predates sequence markers
+            if segment > 0 {
+                wal.append_sequence_marker().unwrap();
+            }
+            paths.push(path);
+        }
+        wal.append_put(&b"key_00".to_vec(), &b"live".to_vec())
+            .unwrap();
+        drop(wal);
+        paths.push(wal_path);
+
+        let env = Env::default();
+        let (memtable, families, stats) =
+            WriteAheadLog::recover_families(&env, &paths, 1, |_| {}).unwrap();
+        let mut reports = 0;
+        let (parallel, parallel_families, parallel_stats) =
+            WriteAheadLog::recover_families(&env, &paths, 4, |_| reports += 1).unwrap();
+        assert_eq!(parallel.entries(), memtable.entries());
+        assert_eq!(parallel.size(), memtable.size());
+        assert_eq!(parallel_families[&3].entries(), families[&3].entries());
+        assert_eq!(
+            RecoveryStats {
+                duration: Duration::ZERO,
+                ..parallel_stats
+            },
+            RecoveryStats {
+                duration: Duration::ZERO,
+                ..stats
+            }
+        );
+        assert_eq!(stats.last_sequence, 6 * 51 + 1);
+        assert_eq!(memtable.get(b"key_00"), Some(&b"live".to_vec()));
+        assert_eq!(families[&3].get(b"cf"), Some(&5u64.to_le_bytes().to_vec()));
+        assert_eq!(reports, paths.len());
+    }
+
+    #[test]
+    fn test_sequence_survives_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let wal_path = dir.path().join("oblivion.wal");
+        let mut wal = WriteAheadLog::open(wal_path.clone()).unwrap();
+        assert_eq!(wal.append_put(&b"a".to_vec(), &b"1".to_vec()).unwrap(), 1);
+        assert_eq!(wal.append_delete(&b"a".to_vec()).unwrap(), 2);
+
+        // The rotated segment is gone (flushed); the marker carries on
+        wal.rotate(&WriteAheadLog::segment_path(dir.path(), 1))
+            .unwrap();
+        wal.append_sequence_marker().unwrap();
+        assert_eq!(wal.append_put(&b"b".to_vec(), &b"1".to_vec()).unwrap(), 3);
+        drop(wal);
+
+        let (memtable, stats) = WriteAheadLog::recover_all(&[wal_path], |_| {}).unwrap();
+        assert_eq!(memtable.len(), 1);
+        assert_eq!(stats.records_applied, 1);
+        assert_eq!(stats.last_sequence, 3);
+        assert_eq!(stats.bytes_skipped, 0);
+    }
+
+    #[test]
+    fn test_read_writes_and_archive() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = Env::default();
+        let wal_path = dir.path().join("oblivion.wal");
+        let mut wal = WriteAheadLog::open(wal_path.clone()).unwrap();
+        wal.append_put(&b"a".to_vec(), &b"1".to_vec()).unwrap();
+        let segment = WriteAheadLog::segment_path(dir.path(), 1);
+        wal.rotate(&segment).unwrap();
+        wal.append_sequence_marker().unwrap();
+        wal.append_delete(&b"a".to_vec()).unwrap();
+        drop(wal);
+
+        let mut file = env.open_read(&wal_path, false).unwrap();
+        assert_eq!(
+            WriteAheadLog::first_sequence(file.as_mut()).unwrap(),
+            Some(2)
+        );
+        let (writes, last) = WriteAheadLog::read_writes(file.as_mut(), 0).unwrap();
+        assert_eq!(last, 2);
+        assert_eq!(
+            writes,
+            vec![LoggedWrite {
+                sequence: 2,
+                cf: None,
+                key: b"a".to_vec(),
+                value: None,
+            }]
+        );
+        let mut file = env.open_read(&segment, false).unwrap();
+        assert_eq!(WriteAheadLog::first_sequence(file.as_mut()).unwrap(), None);
+
+        // The archive keeps the newest segments within its limit
+        let archive = dir.path().join("archive");
+        WriteAheadLog::archive(&env, &segment, &archive).unwrap();
+        let newer = WriteAheadLog::segment_path(dir.path(), 2);
+        std::fs::copy(&wal_path, &newer).unwrap();
+        WriteAheadLog::archive(&env, &newer, &archive).unwrap();
+        let newer_size = std::fs::metadata(archive.join("oblivion_000002.wal"))
+            .unwrap()
+            .len();
+        WriteAheadLog::trim_archive(&env, &archive, newer_size).unwrap();
+        let ids: Vec<u64> = WriteAheadLog::segments(&archive)
+            .unwrap()
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        assert_eq!(ids, vec![2]);
+        WriteAheadLog::trim_archive(&env, &dir.path().join("missing"), 0).unwrap();
+    }
+
+    #[test]
+    fn test_inspect_walks_past_damage() {
+        let dir = tempfile::tempdir().unwrap();
+        let wal_path = dir.path().join("test.wal");
+        let mut wal = WriteAheadLog::open(wal_path.clone()).unwrap();
+        wal.set_last_sequence(40);
+        wal.append_sequence_marker().unwrap();
+        wal.append_put(&b"k1".to_vec(), &b"v1".to_vec()).unwrap();
+        wal.append_put(&b"k2".to_vec(), &b"v2".to_vec()).unwrap();
+        wal.append_delete(&b"k3".to_vec()).unwrap();
+        drop(wal);
+
+        // Damage the value of k1 and leave half a record at the end
+        let mut data = std::fs::read(&wal_path).unwrap();
+        data[21 + 11] ^= 0xFF;
+        data.extend_from_slice(&WriteAheadLog::encode_put(b"k4", b"v4")[..9]);
+        std::fs::write(&wal_path, &data).unwrap();
+
+        let records = WriteAheadLog::inspect(&wal_path).unwrap();
+        let summary: Vec<(u64, Option<u64>, RecordStatus)> = records
+            .iter()
+            .map(|r| (r.offset, r.sequence, r.status))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (0, Some(40), RecordStatus::Ok),
+                (21, None, RecordStatus::CrcMismatch),
+                (38, Some(41), RecordStatus::Ok),
+                (55, Some(42), RecordStatus::Ok),
+                (70, None, RecordStatus::Torn),
+            ]
+        );
+        assert_eq!(records[1].key, b"k1");
+        assert_eq!(records[1].value_len, 2);
+        assert_eq!((records[3].op, records[3].value_len), (2, 0));
+        assert_eq!(records[4].len, 9);
+    }
 }
@@ -0,0 +1,91 @@
+//! OBLIVION - Periodic WAL Sync
+//! Background thread syncing the WAL every `wal_sync_interval_ms` while
+//! `sync_writes` is off.
+//!
+//! Without it, unsynced appends sit in the OS page cache until the log
+//! is rotated, so a power loss can take every write since the last
+//! MemTable freeze. Syncing on a timer bounds that window to one
+//! interval, at the cost of one fsync per interval (skipped when nothing
+//! was written).
+
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Handle to the WAL sync thread. Dropping it stops the thread.
+pub struct WalSyncer {
+    /// Dropping the sender wakes and stops the thread.
+    stop: Option<Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+    period: Duration,
+}
+
+impl WalSyncer {
+    /// Start calling `sync` every `period`. `sync` handles its own
+    /// failures; the thread keeps ticking regardless.
+    pub fn start<F>(period: Duration, sync: F) -> Self
+    where
+        F: Fn() + Send + 'static,
+    {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let handle = thread::Builder::new()
+            .name("oblivion-wal-sync".into())
+            .spawn(move || {
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(period) {
+                    sync();
+                }
+            })
+            .ok();
+        if handle.is_none() {
+            log::warn!("Failed to spawn WAL sync thread");
+        }
+        Self {
+            stop: Some(stop),
+            handle,
+            period,
+        }
+    }
+
+    /// Returns the sync interval.
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+}
+
+impl Drop for WalSyncer {
+    fn drop(&mut self) {
+        self.stop.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    #[test]
+    fn test_syncs_periodically_and_stops_on_drop() {
+        let syncs = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&syncs);
+        let syncer = WalSyncer::start(Duration::from_millis(5), move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        assert_eq!(syncer.period(), Duration::from_millis(5));
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while syncs.load(Ordering::SeqCst) < 3 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert!(syncs.load(Ordering::SeqCst) >= 3);
+
+        drop(syncer);
+        let after_drop = syncs.load(Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(30));
+        assert_eq!(syncs.load(Ordering::SeqCst), after_drop);
+        assert_eq!(Arc::strong_count(&syncs), 1);
+    }
+}
@@ -32,4 +32,91 @@ pub enum OblivionError {
     /// Configuration error.
     #[error("Configuration error: {0}")]
     Config(String),
+
+    /// No database at the path and `create_if_missing` is off.
+    #[error("Database not found at {0:?} (create_if_missing is disabled)")]
+    DatabaseNotFound(std::path::PathBuf),
+
+    /// A database already exists at the path and `error_if_exists` is on.
+    #[error("Database already exists at {0:?} (error_if_exists is enabled)")]
+    DatabaseExists(std::path::PathBuf),
+
+    /// Another engine instance, in this process or another, holds the
+    /// database's `LOCK` file.
+    #[error("Database at {0:?} is locked by another instance")]
+    Locked(std::path::PathBuf),
+
+    /// Input uses a feature this build cannot read.
+    #[error("Unsupported: {0}")]
+    Unsupported(String),
+
+    /// The engine refuses writes, e.g. while it is a replication follower.
+    #[error("Database is read-only: {0}")]
+    ReadOnly(String),
+
+    /// The disk filled up during a write, flush or compaction; the
+    /// engine is read-only until it is reopened.
+    #[error("Disk full: {0}")]
+    DiskFull(String),
+
+    /// The WAL no longer holds the writes from this sequence number on,
+    /// or a change subscriber fell too far behind to receive them.
+    #[error("Changes from sequence {0} are no longer available")]
+    ChangesUnavailable(u64),
+
+    /// No complete backup with this id exists.
+    #[error("Backup {0} not found")]
+    BackupNotFound(u64),
+
+    /// Key exceeds the configured `max_key_size`.
+    #[error("Key too large: {size} bytes (limit {limit})")]
+    KeyTooLarge { size: usize, limit: usize },
+
+    /// Value exceeds the configured `max_value_size`.
+    #[error("Value too large: {size} bytes (limit {limit})")]
+    ValueTooLarge { size: usize, limit: usize },
+
+    /// A background flush or compaction failed; writes are refused
+    /// until the engine is reopened.
+    #[error("Background job failed: {0}")]
+    Background(String),
+
+    /// No column family of this name was opened.
+    #[error("Column family '{0}' not found")]
+    ColumnFamilyNotFound(String),
+
+    /// No logical database of this number is configured.
+    #[error("Database index {index} is out of range (databases = {databases})")]
+    DatabaseOutOfRange { index: usize, databases: usize },
+
+    /// A blocking task of the async API panicked or was cancelled.
+    #[error("Async task failed: {0}")]
+    AsyncTask(String),
+
+    /// A scan filter expression or regular expression that does not parse.
+    #[error("Invalid filter: {0}")]
+    InvalidFilter(String),
+
+    /// A long-running operation was cancelled by its caller.
+    #[error("Cancelled: {0}")]
+    Cancelled(String),
+
+    /// A transaction read a key that another writer changed before it
+    /// committed; nothing was written.
+    #[error("Transaction conflict: {0}")]
+    TransactionConflict(String),
+}
+
+impl OblivionError {
+    /// Returns true if this is the disk, or a quota, running out of space.
+    pub fn is_disk_full(&self) -> bool {
+        match self {
+            OblivionError::DiskFull(_) => true,
+            OblivionError::Io(e) => matches!(
+                e.kind(),
+                std::io::ErrorKind::StorageFull | std::io::ErrorKind::QuotaExceeded
+            ),
+            _ => false,
+        }
+    }
 }
@@ -10,14 +10,14 @@
 //! - **TTL Support**: Redis-like key expiration
 //! - **Metrics**: Lock-free atomic counters for observability
 //! - **Compaction**: Size-tiered LSM compaction strategy
-//! - **Concurrency**: Thread-safe Arc + RwLock wrapper
+//! - **Concurrency**: Thread-safe wrapper with lock-free reads
 //!
 //! ## Example
 //! ```no_run
 //! use oblivion::{config::Config, engine::Oblivion};
 //!
 //! let config = Config::default();
-//! let mut engine = Oblivion::open(config).unwrap();
+//! let engine = Oblivion::open(config).unwrap();
 //!
 //! engine.put(b"key".to_vec(), b"value".to_vec()).unwrap();
 //! assert_eq!(engine.get(b"key"), Some(b"value".to_vec()));
@@ -26,4 +26,5 @@
 pub mod config;
 pub mod engine;
 pub mod error;
+pub mod server;
 pub mod types;
@@ -1,20 +1,197 @@
 //! OBLIVION - LSM-Tree Key-Value Storage Engine
 //! A high-performance, crash-recoverable storage engine
 //! based on Log-Structured Merge Tree architecture.
+//!
+//! ```text
+//! oblivion [--config <file>] [--data-dir <dir>] [--memtable-size <bytes>]
+//!          [--no-sync] [--format pretty|raw|json] [--exec <file>]
+//! oblivion sst-dump [--entries] <file>
+//! oblivion wal-dump [--stop-at-corruption] [--damaged-only] <file>
+//! oblivion repair <data_dir>
+//! ```
+//!
+//! The engine starts from the defaults (`./data`, synced writes), or
+//! from the options file given by `--config` (see `config`); the other
+//! flags override either.
+//!
+//! Without `--exec`, commands are read from the interactive shell, or
+//! from stdin in batch mode when it is not a terminal. `--exec -` reads
+//! stdin explicitly.
+//!
+//! Replies are printed in the pretty format in the shell and the raw
+//! format in batch mode, unless `--format` picks one; the `output`
+//! command switches formats mid-session.
+//!
+//! ## Batch Mode
+//! Commands run one per line without prompts, results are printed in
+//! the raw format by default (see `cli::output`), and the first failing
+//! command stops the batch. Exit codes: 0 when every command succeeded, 1 when
+//! a command failed or the engine could not be opened, 2 for invalid
+//! arguments or an unreadable command file.
+//!
+//! ## Tools
+//! `sst-dump` prints an SSTable's footer, properties, filter, index and
+//! optionally its records, checking every block's CRC (see
+//! `cli::sst_dump`). `wal-dump` lists a WAL's records with their
+//! sequence numbers and checksum status (see `cli::wal_dump`).
+//! `repair` salvages a damaged, closed database (see `cli::repair`).
 
-use std::io::{self, BufRead, Write};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, IsTerminal, Write};
 
+pub mod cli;
 pub mod config;
 pub mod engine;
 pub mod error;
 pub mod types;
 
+use rustyline::error::ReadlineError;
+use rustyline::history::FileHistory;
+use rustyline::Editor;
+
+use cli::editor::{self, ShellHelper};
+use cli::output::{self, Format};
+use cli::{repair, sst_dump, wal_dump};
+use cli::{Reply, Session};
 use config::Config;
 use engine::Oblivion;
 
+/// Exit code when a command fails or the engine cannot be opened.
+const EXIT_FAILURE: i32 = 1;
+
+/// Exit code for invalid arguments or an unreadable command file.
+const EXIT_USAGE: i32 = 2;
+
+const USAGE: &str = "usage: oblivion [--config <file>] [--data-dir <dir>] \
+                     [--memtable-size <bytes>] [--no-sync] [--format pretty|raw|json] \
+                     [--exec <file>]\n       oblivion sst-dump [--entries] <file>\
+                     \n       oblivion wal-dump [--stop-at-corruption] [--damaged-only] <file>\
+                     \n       oblivion repair <data_dir>";
+
+/// Engine settings given on the command line, applied over the
+/// defaults or the `--config` file.
+#[derive(Default)]
+struct Overrides {
+    config: Option<String>,
+    data_dir: Option<String>,
+    memtable_size: Option<usize>,
+    no_sync: bool,
+}
+
 fn main() {
     env_logger::init();
 
+    let mut exec: Option<String> = None;
+    let mut format: Option<Format> = None;
+    let mut overrides = Overrides::default();
+    let mut args = std::env::args().skip(1).peekable();
+    match args.peek().map(String::as_str) {
+        Some("sst-dump") => std::process::exit(sst_dump::run(args.skip(1))),
+        Some("wal-dump") => std::process::exit(wal_dump::run(args.skip(1))),
+        Some("repair") => std::process::exit(repair::run(args.skip(1))),
+        _ => {}
+    }
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                return;
+            }
+            "--no-sync" => {
+                overrides.no_sync = true;
+                continue;
+            }
+            _ => {}
+        }
+        match (arg.as_str(), args.next()) {
+            ("--exec" | "-e", Some(path)) => exec = Some(path),
+            ("--format" | "-f", Some(name)) => match name.parse() {
+                Ok(name) => format = Some(name),
+                Err(err) => {
+                    eprintln!("[ERROR] {}", err);
+                    std::process::exit(EXIT_USAGE);
+                }
+            },
+            ("--config" | "-c", Some(path)) => overrides.config = Some(path),
+            ("--data-dir" | "-d", Some(dir)) => overrides.data_dir = Some(dir),
+            ("--memtable-size", Some(size)) => match size.parse() {
+                Ok(size) => overrides.memtable_size = Some(size),
+                Err(_) => {
+                    eprintln!("[ERROR] Invalid --memtable-size '{}'", size);
+                    std::process::exit(EXIT_USAGE);
+                }
+            },
+            _ => {
+                eprintln!("{}", USAGE);
+                std::process::exit(EXIT_USAGE);
+            }
+        }
+    }
+    let config = match load_config(overrides) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("[ERROR] Invalid configuration: {}", err);
+            std::process::exit(EXIT_USAGE);
+        }
+    };
+
+    let input: Option<Box<dyn BufRead>> = match exec.as_deref() {
+        Some("-") => Some(Box::new(io::stdin().lock())),
+        Some(path) => match File::open(path) {
+            Ok(file) => Some(Box::new(BufReader::new(file))),
+            Err(err) => {
+                eprintln!("[ERROR] Cannot read {}: {}", path, err);
+                std::process::exit(EXIT_USAGE);
+            }
+        },
+        None if !io::stdin().is_terminal() => Some(Box::new(io::stdin().lock())),
+        None => None,
+    };
+    if input.is_none() && format.is_none_or(|format| format == Format::Pretty) {
+        print_banner();
+    }
+
+    let engine = match Oblivion::open(config) {
+        Ok(e) => e,
+        Err(err) => {
+            eprintln!("[ERROR] Failed to open engine: {}", err);
+            std::process::exit(EXIT_FAILURE);
+        }
+    };
+
+    let code = match input {
+        Some(input) => run_batch(&engine, input, format.unwrap_or(Format::Raw)),
+        None => {
+            run_shell(&engine, format.unwrap_or(Format::Pretty));
+            0
+        }
+    };
+    // Close the engine before exiting, which skips destructors
+    drop(engine);
+    std::process::exit(code);
+}
+
+/// Build the engine configuration from the `--config` file, or the
+/// defaults, and the command-line overrides.
+fn load_config(overrides: Overrides) -> error::Result<Config> {
+    let mut config = match overrides.config {
+        Some(path) => Config::from_file(path)?,
+        None => Config::default(),
+    };
+    if let Some(dir) = overrides.data_dir {
+        config.data_dir = dir.into();
+    }
+    if let Some(size) = overrides.memtable_size {
+        config.memtable_max_size = size;
+    }
+    if overrides.no_sync {
+        config.sync_writes = false;
+    }
+    config.validate()?;
+    Ok(config)
+}
+
+fn print_banner() {
     println!();
     println!("  ╔═══════════════════════════════════════════╗");
     println!("  ║         OBLIVION Storage Engine           ║");
@@ -22,102 +199,121 @@ fn main() {
     println!("  ╚═══════════════════════════════════════════╝");
     println!();
     println!("  Commands:");
-    println!("    set <key> <value>  - Store a key-value pair");
-    println!("    get <key>          - Retrieve a value by key");
-    println!("    del <key>          - Delete a key");
-    println!("    scan               - List all key-value pairs");
-    println!("    info               - Show engine statistics");
-    println!("    exit               - Shutdown engine");
+    println!("    set <key> <value>        - Store a key-value pair");
+    println!("    get <key>                - Retrieve a value by key");
+    println!("    del <key>                - Delete a key");
+    println!("    setex <key> <ms> <value> - Store a pair that expires");
+    println!("    expire <key> <ms>        - Set a key's TTL");
+    println!("    ttl | persist <key>      - Show or clear a key's TTL");
+    println!("    append | getset | ...    - Edit a value and read it back");
+    println!("    scan [cursor]            - Page through key-value pairs");
+    println!("    keys <pattern>           - List keys matching a glob");
+    println!("    hset | hget | hdel ...   - Edit and read hash fields");
+    println!("    lpush | rpush | lpop ... - Use lists as queues");
+    println!("    sadd | srem | smembers   - Edit and read sets");
+    println!("    zadd | zrangebyscore ... - Query sorted sets by score");
+    println!("    select <n>               - Switch to logical database n");
+    println!("    info [section]           - Show engine statistics");
+    println!("    flush | compact          - Flush the MemTable / merge SSTables");
+    println!("    bgsave <dir>             - Write a checkpoint to <dir>");
+    println!("    metrics                  - Print the full metrics report");
+    println!("    output [pretty|raw|json] - Show or set the output format");
+    println!("    exit                     - Shutdown engine");
+    println!();
+    println!("  Quote keys and values with spaces (\"a b\"); \\xNN writes any byte.");
     println!();
+}
 
-    let config = Config::default();
-    let mut engine = match Oblivion::open(config) {
-        Ok(e) => e,
+/// The interactive shell: prompt, run, print, until `exit` or EOF.
+/// Ctrl-C clears the line being edited; Ctrl-D ends the session.
+fn run_shell(engine: &Oblivion, format: Format) {
+    let mut stdout = io::stdout();
+    let mut editor: Editor<ShellHelper, FileHistory> = match Editor::new() {
+        Ok(editor) => editor,
         Err(err) => {
-            eprintln!("[ERROR] Failed to open engine: {}", err);
-            std::process::exit(1);
+            eprintln!("[ERROR] Cannot start the line editor: {}", err);
+            return;
         }
     };
-
-    let stdin = io::stdin();
-    let mut stdout = io::stdout();
+    editor.set_helper(Some(ShellHelper::default()));
+    let history = editor::history_path();
+    let mut session = Session::new(format);
+    if let Some(path) = &history {
+        // A missing history file is normal on the first run
+        let _ = editor.load_history(path);
+    }
 
     loop {
-        print!("oblivion> ");
-        stdout.flush().unwrap();
-
-        let mut line = String::new();
-        if stdin.lock().read_line(&mut line).unwrap() == 0 {
-            break; // EOF
+        // Show the database once another than 0 is selected, as redis-cli does
+        let prompt = match session.database() {
+            0 => "oblivion> ".to_string(),
+            db => format!("oblivion[{}]> ", db),
+        };
+        let line = match editor.readline(&prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(err) => {
+                eprintln!("[ERROR] Failed to read input: {}", err);
+                break;
+            }
+        };
+        let Some(reply) = session.execute(engine, &line) else {
+            continue;
+        };
+        let _ = editor.add_history_entry(line.as_str());
+        if let (Some(helper), Some(key)) = (editor.helper_mut(), cli::key_argument(&line)) {
+            helper.record_key(&key);
+        }
+        output::render(&reply, session.format(), &mut stdout, &mut io::stderr()).unwrap();
+        if reply == Reply::Exit {
+            break;
         }
+    }
 
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.is_empty() {
-            continue;
+    if let Some(path) = &history {
+        if let Err(err) = editor.save_history(path) {
+            eprintln!("[ERROR] Cannot save history to {}: {}", path.display(), err);
         }
+    }
+}
 
-        match parts[0].to_lowercase().as_str() {
-            "set" | "put" => {
-                if parts.len() < 3 {
-                    println!("  Usage: set <key> <value>");
-                    continue;
-                }
-                let key = parts[1].as_bytes().to_vec();
-                let value = parts[2..].join(" ").as_bytes().to_vec();
-                match engine.put(key, value) {
-                    Ok(()) => println!("  OK"),
-                    Err(e) => println!("  ERROR: {}", e),
-                }
-            }
-            "get" => {
-                if parts.len() < 2 {
-                    println!("  Usage: get <key>");
-                    continue;
-                }
-                let key = parts[1].as_bytes();
-                match engine.get(key) {
-                    Some(value) => match String::from_utf8(value) {
-                        Ok(s) => println!("  \"{}\"", s),
-                        Err(_) => println!("  <binary data>"),
-                    },
-                    None => println!("  (nil)"),
-                }
-            }
-            "del" | "delete" => {
-                if parts.len() < 2 {
-                    println!("  Usage: del <key>");
-                    continue;
-                }
-                let key = parts[1].as_bytes().to_vec();
-                match engine.delete(key) {
-                    Ok(()) => println!("  OK (deleted)"),
-                    Err(e) => println!("  ERROR: {}", e),
-                }
-            }
-            "scan" | "list" => {
-                let entries = engine.scan();
-                if entries.is_empty() {
-                    println!("  (empty)");
-                } else {
-                    for (key, value) in &entries {
-                        let k = String::from_utf8_lossy(key);
-                        let v = String::from_utf8_lossy(value);
-                        println!("  {} -> {}", k, v);
-                    }
-                    println!("  ({} entries)", entries.len());
-                }
-            }
-            "info" | "stats" => {
-                println!("  Entries:       {}", engine.len());
-                println!("  MemTable size: {} bytes", engine.memtable_size());
-            }
-            "exit" | "quit" | "q" => {
-                println!("  Shutting down OBLIVION...");
-                break;
-            }
-            _ => {
-                println!("  Unknown command: '{}'. Type 'exit' to quit.", parts[0]);
+/// Run each line of `input` as a command, stopping at the first
+/// failure. Returns the process exit code.
+fn run_batch(engine: &Oblivion, input: Box<dyn BufRead>, format: Format) -> i32 {
+    let mut stdout = io::stdout().lock();
+    let mut stderr = io::stderr();
+    let mut session = Session::new(format);
+    for (number, line) in input.lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => {
+                eprintln!("[ERROR] Failed to read line {}: {}", number + 1, err);
+                return EXIT_USAGE;
             }
+        };
+        let Some(reply) = session.execute(engine, &line) else {
+            continue;
+        };
+        if let Err(err) = output::render(&reply, session.format(), &mut stdout, &mut stderr) {
+            // A closed pipe (e.g. `| head`) ends the batch quietly
+            return if err.kind() == io::ErrorKind::BrokenPipe {
+                0
+            } else {
+                EXIT_FAILURE
+            };
+        }
+        if reply.is_failure() {
+            let _ = stdout.flush();
+            eprintln!("[ERROR] Batch stopped at line {}", number + 1);
+            return EXIT_FAILURE;
         }
+        if reply == Reply::Exit {
+            break;
+        }
+    }
+    match stdout.flush() {
+        Ok(()) => 0,
+        Err(_) => EXIT_FAILURE,
     }
 }
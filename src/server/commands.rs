@@ -0,0 +1,539 @@
+//! OBLIVION - RESP Commands
+//! Maps Redis commands onto engine calls.
+//!
+//! `SET`, `GETSET` and `APPEND` keep a TTL the key already has, as
+//! engine puts do. `SET`'s `NX`, `XX`, `GET` and `KEEPTTL` flags are
+//! rejected.
+//!
+//! ## Cursors
+//! Redis clients treat `SCAN` cursors as integers, but a page of the
+//! engine resumes from a key. The server keeps the resume key of the
+//! latest [`MAX_CURSORS`] pages under increasing ids and hands out the
+//! id; an evicted cursor is an error. Keys present during the whole
+//! scan are returned exactly once, in key order.
+
+use std::collections::{HashMap, VecDeque};
+
+use parking_lot::Mutex;
+
+use super::resp::Reply;
+use crate::engine::batch::WriteBatch;
+use crate::engine::database::Database;
+use crate::engine::options::ReadOptions;
+use crate::engine::predicate::Predicate;
+use crate::engine::Oblivion;
+use crate::error::OblivionError;
+use crate::types::{Key, Value};
+
+/// Number of `SCAN` cursors remembered across all connections.
+pub(crate) const MAX_CURSORS: usize = 16384;
+
+/// Keys examined per `SCAN` call without `COUNT`, as in Redis.
+const DEFAULT_SCAN_COUNT: usize = 10;
+
+/// Resume keys of recent `SCAN` pages, by cursor id.
+#[derive(Default)]
+pub(crate) struct Cursors {
+    inner: Mutex<CursorTable>,
+}
+
+#[derive(Default)]
+struct CursorTable {
+    last_id: u64,
+    resume_keys: HashMap<u64, Key>,
+    order: VecDeque<u64>,
+}
+
+impl Cursors {
+    /// Remember `resume` and return its cursor id (never 0).
+    fn save(&self, resume: Key) -> u64 {
+        let mut table = self.inner.lock();
+        table.last_id += 1;
+        let id = table.last_id;
+        table.resume_keys.insert(id, resume);
+        table.order.push_back(id);
+        while table.order.len() > MAX_CURSORS {
+            if let Some(old) = table.order.pop_front() {
+                table.resume_keys.remove(&old);
+            }
+        }
+        id
+    }
+
+    /// The resume key of cursor `id`, if it is still remembered.
+    fn resume_key(&self, id: u64) -> Option<Key> {
+        self.inner.lock().resume_keys.get(&id).cloned()
+    }
+}
+
+/// Run one command against the connection's database.
+pub(crate) fn execute(db: &Database, cursors: &Cursors, args: &[Vec<u8>]) -> Reply {
+    let name = String::from_utf8_lossy(&args[0]).to_ascii_lowercase();
+    let args = &args[1..];
+    let result = match name.as_str() {
+        "ping" => match args {
+            [] => Ok(Reply::Simple("PONG".to_string())),
+            [message] => Ok(Reply::bulk(message.clone())),
+            _ => Err(wrong_arity(&name)),
+        },
+        "echo" => match args {
+            [message] => Ok(Reply::bulk(message.clone())),
+            _ => Err(wrong_arity(&name)),
+        },
+        "get" => match args {
+            [key] => get(db, key),
+            _ => Err(wrong_arity(&name)),
+        },
+        "set" if args.len() >= 2 => set(db, args),
+        "append" => match args {
+            [key, suffix] => append(db, key, suffix),
+            _ => Err(wrong_arity(&name)),
+        },
+        "getset" => match args {
+            [key, value] => getset(db, key, value),
+            _ => Err(wrong_arity(&name)),
+        },
+        "getdel" => match args {
+            [key] => getdel(db, key),
+            _ => Err(wrong_arity(&name)),
+        },
+        "strlen" => match args {
+            [key] => strlen(db, key),
+            _ => Err(wrong_arity(&name)),
+        },
+        "mset" if !args.is_empty() && args.len().is_multiple_of(2) => mset(db, args),
+        "del" if !args.is_empty() => del(db, args),
+        "exists" if !args.is_empty() => exists(db, args),
+        "expire" | "pexpire" => match args {
+            [key, ttl] => {
+                let unit = if name == "expire" { 1000 } else { 1 };
+                expire(db, key, ttl, unit)
+            }
+            _ => Err(wrong_arity(&name)),
+        },
+        "ttl" | "pttl" => match args {
+            [key] => {
+                let unit = if name == "ttl" { 1000 } else { 1 };
+                ttl(db, key, unit)
+            }
+            _ => Err(wrong_arity(&name)),
+        },
+        "scan" if !args.is_empty() => scan(db, cursors, args),
+        "hset" if args.len() >= 3 && args.len() % 2 == 1 => hset(db, args),
+        "hget" => match args {
+            [key, field] => hget(db, key, field),
+            _ => Err(wrong_arity(&name)),
+        },
+        "hdel" if args.len() >= 2 => hdel(db, args),
+        "hgetall" => match args {
+            [key] => hgetall(db, key),
+            _ => Err(wrong_arity(&name)),
+        },
+        "lpush" | "rpush" if args.len() >= 2 => push(db, &name, args),
+        "lpop" => match args {
+            [key] => lpop(db, key),
+            _ => Err(wrong_arity(&name)),
+        },
+        "lrange" => match args {
+            [key, start, stop] => lrange(db, key, start, stop),
+            _ => Err(wrong_arity(&name)),
+        },
+        "sadd" if args.len() >= 2 => sadd(db, args),
+        "srem" if args.len() >= 2 => srem(db, args),
+        "sismember" => match args {
+            [key, member] => sismember(db, key, member),
+            _ => Err(wrong_arity(&name)),
+        },
+        "smembers" => match args {
+            [key] => smembers(db, key),
+            _ => Err(wrong_arity(&name)),
+        },
+        "zadd" if args.len() >= 3 && args.len() % 2 == 1 => zadd(db, args),
+        "zscore" => match args {
+            [key, member] => zscore(db, key, member),
+            _ => Err(wrong_arity(&name)),
+        },
+        "zrem" if args.len() >= 2 => zrem(db, args),
+        "zrangebyscore" if args.len() == 3 || args.len() == 4 => zrangebyscore(db, args),
+        "set" | "mset" | "del" | "exists" | "scan" | "hset" | "hdel" | "lpush" | "rpush"
+        | "sadd" | "srem" | "zadd" | "zrem" | "zrangebyscore" => Err(wrong_arity(&name)),
+        _ => Err(Reply::error(format!("ERR unknown command '{}'", name))),
+    };
+    result.unwrap_or_else(|reply| reply)
+}
+
+/// `SELECT index`: the database the connection's later commands use.
+pub(crate) fn select<'a>(engine: &'a Oblivion, args: &[Vec<u8>]) -> Result<Database<'a>, Reply> {
+    let [index] = args else {
+        return Err(wrong_arity("select"));
+    };
+    let index = parse_int(index)?;
+    usize::try_from(index)
+        .ok()
+        .and_then(|index| engine.database(index).ok())
+        .ok_or_else(|| Reply::error("ERR DB index is out of range"))
+}
+
+fn get(db: &Database, key: &[u8]) -> Result<Reply, Reply> {
+    let value = db.get(key).map_err(engine_error)?;
+    Ok(Reply::Bulk(value))
+}
+
+fn set(db: &Database, args: &[Vec<u8>]) -> Result<Reply, Reply> {
+    let (key, value, ttl_ms) = parse_set(args)?;
+    match ttl_ms {
+        Some(ttl_ms) => db.put_with_ttl(key, value, ttl_ms),
+        None => db.put(key, value),
+    }
+    .map_err(engine_error)?;
+    Ok(Reply::ok())
+}
+
+/// Parse `SET`'s arguments (after the name) into the key, the value
+/// and the TTL in milliseconds.
+pub(crate) fn parse_set(args: &[Vec<u8>]) -> Result<(Key, Value, Option<u64>), Reply> {
+    let [key, value, options @ ..] = args else {
+        return Err(wrong_arity("set"));
+    };
+    let mut ttl_ms = None;
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        let unit = match option.to_ascii_lowercase().as_slice() {
+            b"ex" => 1000,
+            b"px" => 1,
+            _ => return Err(syntax_error()),
+        };
+        if ttl_ms.is_some() {
+            return Err(syntax_error());
+        }
+        let amount = options.next().ok_or_else(syntax_error)?;
+        match parse_int(amount)?.checked_mul(unit) {
+            Some(ms) if ms > 0 => ttl_ms = Some(ms as u64),
+            _ => return Err(Reply::error("ERR invalid expire time in 'set' command")),
+        }
+    }
+    Ok((key.clone(), value.clone(), ttl_ms))
+}
+
+fn append(db: &Database, key: &[u8], suffix: &[u8]) -> Result<Reply, Reply> {
+    let len = db.append(key, suffix).map_err(engine_error)?;
+    Ok(Reply::Integer(len as i64))
+}
+
+fn getset(db: &Database, key: &[u8], value: &[u8]) -> Result<Reply, Reply> {
+    let old = db.getset(key, value.to_vec()).map_err(engine_error)?;
+    Ok(Reply::Bulk(old))
+}
+
+fn getdel(db: &Database, key: &[u8]) -> Result<Reply, Reply> {
+    let old = db.getdel(key).map_err(engine_error)?;
+    Ok(Reply::Bulk(old))
+}
+
+fn strlen(db: &Database, key: &[u8]) -> Result<Reply, Reply> {
+    let len = db.strlen(key).map_err(engine_error)?;
+    Ok(Reply::Integer(len as i64))
+}
+
+/// `MSET key value [key value ...]` as one write batch.
+fn mset(db: &Database, args: &[Vec<u8>]) -> Result<Reply, Reply> {
+    let mut batch = WriteBatch::new();
+    for pair in args.chunks(2) {
+        batch.put(pair[0].clone(), pair[1].clone());
+    }
+    db.write(batch).map_err(engine_error)?;
+    Ok(Reply::ok())
+}
+
+fn del(db: &Database, keys: &[Vec<u8>]) -> Result<Reply, Reply> {
+    let mut deleted = 0;
+    for key in keys {
+        // Deleting an absent key is a no-op, as in Redis
+        if db.exists(key).map_err(engine_error)? {
+            db.delete(key.clone()).map_err(engine_error)?;
+            deleted += 1;
+        }
+    }
+    Ok(Reply::Integer(deleted))
+}
+
+fn exists(db: &Database, keys: &[Vec<u8>]) -> Result<Reply, Reply> {
+    let mut found = 0;
+    for key in keys {
+        if db.exists(key).map_err(engine_error)? {
+            found += 1;
+        }
+    }
+    Ok(Reply::Integer(found))
+}
+
+fn expire(db: &Database, key: &[u8], ttl: &[u8], unit: i64) -> Result<Reply, Reply> {
+    let ms = parse_int(ttl)?
+        .checked_mul(unit)
+        .ok_or_else(|| Reply::error("ERR invalid expire time in 'expire' command"))?;
+    // A TTL in the past deletes the key, as in Redis
+    if ms <= 0 {
+        return del(db, &[key.to_vec()]);
+    }
+    let existed = db.expire(key, ms as u64).map_err(engine_error)?;
+    Ok(Reply::Integer(existed as i64))
+}
+
+fn ttl(db: &Database, key: &[u8], unit: u64) -> Result<Reply, Reply> {
+    if db.get(key).map_err(engine_error)?.is_none() {
+        return Ok(Reply::Integer(-2));
+    }
+    match db.ttl(key) {
+        // Round to the nearest unit, as Redis does for TTL
+        Some(ms) => Ok(Reply::Integer(((ms + unit / 2) / unit) as i64)),
+        None => Ok(Reply::Integer(-1)),
+    }
+}
+
+fn scan(db: &Database, cursors: &Cursors, args: &[Vec<u8>]) -> Result<Reply, Reply> {
+    let cursor: u64 = std::str::from_utf8(&args[0])
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| Reply::error("ERR invalid cursor"))?;
+    let mut filters = Vec::new();
+    let mut count = DEFAULT_SCAN_COUNT;
+    let mut with_values = false;
+    let mut options = args[1..].iter();
+    while let Some(option) = options.next() {
+        let option = option.to_ascii_lowercase();
+        if option == b"withvalues" {
+            with_values = true;
+            continue;
+        }
+        let value = options.next().ok_or_else(syntax_error)?;
+        match option.as_slice() {
+            b"match" => filters.push(Predicate::key_glob(value.as_slice())),
+            b"where" => {
+                let expression = std::str::from_utf8(value)
+                    .map_err(|_| Reply::error("ERR filter expression is not UTF-8"))?;
+                filters.push(Predicate::parse(expression).map_err(engine_error)?);
+            }
+            b"count" => match parse_int(value)? {
+                n if n >= 1 => count = n as usize,
+                _ => return Err(syntax_error()),
+            },
+            _ => return Err(syntax_error()),
+        }
+    }
+    let filter = Predicate::And(filters);
+
+    // Only keys starting with the filter's required prefix can match
+    let comparator = db.engine().comparator();
+    let mut opts = ReadOptions::new().fill_cache(false);
+    filter.narrow(&mut opts, comparator);
+    if cursor != 0 {
+        let resume = cursors
+            .resume_key(cursor)
+            .ok_or_else(|| Reply::error("ERR invalid cursor"))?;
+        if opts
+            .lower_bound
+            .as_ref()
+            .is_none_or(|lower| comparator.compare(&resume, lower).is_gt())
+        {
+            opts.lower_bound = Some(resume);
+        }
+    }
+    let (rows, resume) = db.scan_page(&opts, count).map_err(engine_error)?;
+    let mut items = Vec::new();
+    for (key, value) in rows {
+        if filter.matches(&key, &value) {
+            items.push(Reply::bulk(key));
+            if with_values {
+                items.push(Reply::bulk(value));
+            }
+        }
+    }
+    let next = resume.map_or(0, |resume| cursors.save(resume));
+    Ok(Reply::Array(vec![
+        Reply::bulk(next.to_string()),
+        Reply::Array(items),
+    ]))
+}
+
+/// `HSET key field value [field value ...]` as one write batch.
+fn hset(db: &Database, args: &[Vec<u8>]) -> Result<Reply, Reply> {
+    let fields = args[1..]
+        .chunks(2)
+        .map(|pair| (pair[0].clone(), pair[1].clone()))
+        .collect();
+    let added = db.hset(&args[0], fields).map_err(engine_error)?;
+    Ok(Reply::Integer(added as i64))
+}
+
+fn hget(db: &Database, key: &[u8], field: &[u8]) -> Result<Reply, Reply> {
+    let value = db.hget(key, field).map_err(engine_error)?;
+    Ok(Reply::Bulk(value))
+}
+
+fn hdel(db: &Database, args: &[Vec<u8>]) -> Result<Reply, Reply> {
+    let removed = db.hdel(&args[0], &args[1..]).map_err(engine_error)?;
+    Ok(Reply::Integer(removed as i64))
+}
+
+/// `HGETALL key`: fields and values, alternating, in field order.
+fn hgetall(db: &Database, key: &[u8]) -> Result<Reply, Reply> {
+    let fields = db.hgetall(key).map_err(engine_error)?;
+    Ok(Reply::Array(
+        fields
+            .into_iter()
+            .flat_map(|(field, value)| [Reply::bulk(field), Reply::bulk(value)])
+            .collect(),
+    ))
+}
+
+/// `LPUSH` or `RPUSH key value [value ...]` as one write batch.
+fn push(db: &Database, name: &str, args: &[Vec<u8>]) -> Result<Reply, Reply> {
+    let values = args[1..].to_vec();
+    let len = match name {
+        "lpush" => db.lpush(&args[0], values),
+        _ => db.rpush(&args[0], values),
+    }
+    .map_err(engine_error)?;
+    Ok(Reply::Integer(len as i64))
+}
+
+fn lpop(db: &Database, key: &[u8]) -> Result<Reply, Reply> {
+    let value = db.lpop(key).map_err(engine_error)?;
+    Ok(Reply::Bulk(value))
+}
+
+fn lrange(db: &Database, key: &[u8], start: &[u8], stop: &[u8]) -> Result<Reply, Reply> {
+    let items = db
+        .lrange(key, parse_int(start)?, parse_int(stop)?)
+        .map_err(engine_error)?;
+    Ok(Reply::Array(items.into_iter().map(Reply::bulk).collect()))
+}
+
+fn sadd(db: &Database, args: &[Vec<u8>]) -> Result<Reply, Reply> {
+    let added = db.sadd(&args[0], &args[1..]).map_err(engine_error)?;
+    Ok(Reply::Integer(added as i64))
+}
+
+fn srem(db: &Database, args: &[Vec<u8>]) -> Result<Reply, Reply> {
+    let removed = db.srem(&args[0], &args[1..]).map_err(engine_error)?;
+    Ok(Reply::Integer(removed as i64))
+}
+
+fn sismember(db: &Database, key: &[u8], member: &[u8]) -> Result<Reply, Reply> {
+    let found = db.sismember(key, member).map_err(engine_error)?;
+    Ok(Reply::Integer(found as i64))
+}
+
+fn smembers(db: &Database, key: &[u8]) -> Result<Reply, Reply> {
+    let members = db.smembers(key).map_err(engine_error)?;
+    Ok(Reply::Array(members.into_iter().map(Reply::bulk).collect()))
+}
+
+/// `ZADD key score member [score member ...]` as one write batch.
+fn zadd(db: &Database, args: &[Vec<u8>]) -> Result<Reply, Reply> {
+    let mut members = Vec::with_capacity(args.len() / 2);
+    for pair in args[1..].chunks(2) {
+        let score = parse_score(&pair[0])?;
+        members.push((pair[1].clone(), score));
+    }
+    let added = db.zadd(&args[0], members).map_err(engine_error)?;
+    Ok(Reply::Integer(added as i64))
+}
+
+fn zscore(db: &Database, key: &[u8], member: &[u8]) -> Result<Reply, Reply> {
+    let score = db.zscore(key, member).map_err(engine_error)?;
+    Ok(Reply::Bulk(
+        score.map(|score| format_score(score).into_bytes()),
+    ))
+}
+
+fn zrem(db: &Database, args: &[Vec<u8>]) -> Result<Reply, Reply> {
+    let removed = db.zrem(&args[0], &args[1..]).map_err(engine_error)?;
+    Ok(Reply::Integer(removed as i64))
+}
+
+/// `ZRANGEBYSCORE key min max [WITHSCORES]`. A bound prefixed with `(`
+/// is exclusive.
+fn zrangebyscore(db: &Database, args: &[Vec<u8>]) -> Result<Reply, Reply> {
+    let with_scores = match args.get(3) {
+        Some(option) if option.eq_ignore_ascii_case(b"withscores") => true,
+        Some(_) => return Err(syntax_error()),
+        None => false,
+    };
+    let bound = |arg: &[u8], exclusive: fn(f64) -> f64| match arg.strip_prefix(b"(") {
+        Some(score) => parse_score(score).map(exclusive),
+        None => parse_score(arg),
+    };
+    let min = bound(&args[1], f64::next_up)?;
+    let max = bound(&args[2], f64::next_down)?;
+    let members = db.zrangebyscore(&args[0], min, max).map_err(engine_error)?;
+    let mut replies = Vec::new();
+    for (member, score) in members {
+        replies.push(Reply::bulk(member));
+        if with_scores {
+            replies.push(Reply::bulk(format_score(score)));
+        }
+    }
+    Ok(Reply::Array(replies))
+}
+
+/// A sorted-set score; `inf`, `+inf` and `-inf` are accepted.
+fn parse_score(arg: &[u8]) -> Result<f64, Reply> {
+    std::str::from_utf8(arg)
+        .ok()
+        .and_then(|s| s.parse::<f64>().ok())
+        .filter(|score| !score.is_nan())
+        .ok_or_else(|| Reply::error("ERR value is not a valid float"))
+}
+
+/// A score as Redis writes it: `inf` and `-inf` for infinities.
+fn format_score(score: f64) -> String {
+    match score {
+        f64::INFINITY => "inf".to_string(),
+        f64::NEG_INFINITY => "-inf".to_string(),
+        score => score.to_string(),
+    }
+}
+
+fn parse_int(arg: &[u8]) -> Result<i64, Reply> {
+    std::str::from_utf8(arg)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| Reply::error("ERR value is not an integer or out of range"))
+}
+
+pub(crate) fn wrong_arity(name: &str) -> Reply {
+    Reply::error(format!(
+        "ERR wrong number of arguments for '{}' command",
+        name
+    ))
+}
+
+fn syntax_error() -> Reply {
+    Reply::error("ERR syntax error")
+}
+
+/// Map an engine error to a Redis-style error reply.
+pub(crate) fn engine_error(error: OblivionError) -> Reply {
+    match error {
+        OblivionError::ReadOnly(reason) => Reply::error(format!("READONLY {}", reason)),
+        other => Reply::error(format!("ERR {}", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_eviction() {
+        let cursors = Cursors::default();
+        let first = cursors.save(b"a".to_vec());
+        assert_ne!(first, 0);
+        assert_eq!(cursors.resume_key(first), Some(b"a".to_vec()));
+        for _ in 0..MAX_CURSORS {
+            cursors.save(b"b".to_vec());
+        }
+        assert_eq!(cursors.resume_key(first), None);
+    }
+}
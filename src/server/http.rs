@@ -0,0 +1,732 @@
+//! OBLIVION - HTTP API
+//! JSON REST endpoints over HTTP/1.1 (feature `http`), for clients
+//! without a Rust or Redis library.
+//!
+//! ## Endpoints
+//! | Request                                   | Response                                      |
+//! |-------------------------------------------|-----------------------------------------------|
+//! | `GET /keys/{key}`                         | `{"key","value","encoding","ttl_ms"?}`, or 404 |
+//! | `PUT /keys/{key}[?ttl_ms=N]`, body = value | `{"ok":true}`                                 |
+//! | `DELETE /keys/{key}`                      | `{"deleted":bool}`                            |
+//! | `GET /keys?prefix=&start=&end=&filter=&limit=&cursor=` | `{"items":[...],"next_cursor":...}` |
+//! | `GET /stats`                              | Metrics snapshot and engine sizes             |
+//! | `POST /admin/flush`, `POST /admin/compact` | `{"ok":true}` once done                      |
+//!
+//! The `/keys` endpoints address logical database 0 unless the query
+//! names another with `db=N` (see the
+//! [`database` module](crate::engine::database)); an unknown database
+//! is a 400.
+//!
+//! Keys in paths and query values are percent-decoded, so any byte
+//! string can be addressed; request bodies are stored as-is. Rows are
+//! returned as in NDJSON exports: as text when key and value are UTF-8,
+//! else both in base64 with `"encoding":"base64"`. Errors are returned
+//! as `{"error": message}` with a 4xx or 5xx status.
+//!
+//! ## Range Scans
+//! `GET /keys` returns up to `limit` (default 100, at most 1000) rows
+//! in key order, within `prefix` and the `[start, end)` range. While
+//! more rows may follow, `next_cursor` holds an opaque cursor to pass
+//! back as `cursor`; it is `null` on the last page. Pages see the
+//! latest state, and keys present throughout are returned exactly once.
+//!
+//! `filter` takes a filter expression over keys and values (see the
+//! [`predicate` module](crate::engine::predicate)), e.g.
+//! `key glob 'user:*' and value contains active`. Rows are filtered by
+//! the server, so a page may hold fewer than `limit` items, or none,
+//! while `next_cursor` is not `null`; a malformed expression is a 400.
+//!
+//! ## Connections
+//! Each connection carries one request and is closed after the
+//! response (`Connection: close`).
+//!
+//! ## Authentication
+//! With `server_password` set, every request must carry
+//! `Authorization: Bearer <password>`; others get 401.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::{json, Value as Json};
+
+use super::listener::{Client, Listener};
+use super::{ConnectionInfo, ServerStats};
+use crate::config::Secret;
+use crate::engine::database::Database;
+use crate::engine::export::encode_pair;
+use crate::engine::options::ReadOptions;
+use crate::engine::predicate::Predicate;
+use crate::engine::Oblivion;
+use crate::error::{OblivionError, Result};
+
+/// Rows per `GET /keys` page without `limit`.
+const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// Largest accepted `limit`.
+const MAX_PAGE_SIZE: usize = 1000;
+
+/// Longest accepted request line or header.
+const MAX_HEADER_LINE: usize = 8 * 1024;
+
+/// Most headers accepted in one request.
+const MAX_HEADERS: usize = 100;
+
+/// Give up on a client that stops sending its request.
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A running HTTP API server.
+///
+/// Dropping it stops accepting clients, closes their connections and
+/// joins the server threads.
+///
+/// ## Example
+/// ```no_run
+/// use std::sync::Arc;
+/// use oblivion::{config::Config, engine::Oblivion, server::http::HttpServer};
+///
+/// let engine = Arc::new(Oblivion::open(Config::new("./data")).unwrap());
+/// let server = HttpServer::start(engine, "127.0.0.1:8080").unwrap();
+/// // curl -X PUT --data-binary alice http://127.0.0.1:8080/keys/user:1
+/// ```
+pub struct HttpServer {
+    listener: Listener,
+}
+
+impl HttpServer {
+    /// Listen for HTTP clients on `addr`, with the password and TLS
+    /// settings of the engine's configuration.
+    pub fn start(engine: Arc<Oblivion>, addr: impl ToSocketAddrs) -> Result<Self> {
+        let config = engine.config();
+        let password = config.server_password.clone();
+        let listener = Listener::start(
+            "HTTP",
+            addr,
+            &config,
+            Box::new(move |client| serve(&engine, password.as_ref(), client)),
+        )?;
+        Ok(Self { listener })
+    }
+
+    /// Returns the address clients connect to.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.listener.local_addr()
+    }
+
+    /// Returns the number of open connections.
+    pub fn connection_count(&self) -> usize {
+        self.listener.connection_count()
+    }
+
+    /// Returns the peer and traffic of each open connection.
+    pub fn connections(&self) -> Vec<ConnectionInfo> {
+        self.listener.connections()
+    }
+
+    /// Returns the totals since the server started.
+    pub fn stats(&self) -> ServerStats {
+        self.listener.stats()
+    }
+}
+
+/// A parsed request.
+struct Request {
+    method: String,
+    path: String,
+    query: Vec<(String, Vec<u8>)>,
+    /// The `Authorization: Bearer` token, if sent.
+    bearer: Option<String>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    /// The decoded value of query parameter `name`.
+    fn param(&self, name: &str) -> Option<&[u8]> {
+        self.query
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_slice())
+    }
+}
+
+/// A JSON response.
+struct Response {
+    status: u16,
+    body: Json,
+}
+
+impl Response {
+    fn ok(body: Json) -> Self {
+        Self { status: 200, body }
+    }
+
+    fn error(status: u16, message: impl std::fmt::Display) -> Self {
+        Self {
+            status,
+            body: json!({ "error": message.to_string() }),
+        }
+    }
+
+    fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let mut body = serde_json::to_vec(&self.body).map_err(io::Error::other)?;
+        body.push(b'\n');
+        write!(
+            out,
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\n\
+             Content-Length: {}\r\nConnection: close\r\n",
+            self.status,
+            reason(self.status),
+            body.len()
+        )?;
+        if self.status == 401 {
+            out.write_all(b"WWW-Authenticate: Bearer\r\n")?;
+        }
+        out.write_all(b"\r\n")?;
+        out.write_all(&body)?;
+        out.flush()
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        431 => "Request Header Fields Too Large",
+        501 => "Not Implemented",
+        _ => "Internal Server Error",
+    }
+}
+
+/// Answer the one request of a connection.
+fn serve(engine: &Oblivion, password: Option<&Secret>, client: &mut Client) -> io::Result<()> {
+    client.set_read_timeout(Some(READ_TIMEOUT))?;
+    let max_body = engine.config().max_value_size;
+    let mut reader = BufReader::new(&mut *client);
+    let result = read_request(&mut reader, max_body)?;
+    let response = match result {
+        Ok(Some(request)) => {
+            client.record_request();
+            match password {
+                Some(secret)
+                    if !request
+                        .bearer
+                        .as_ref()
+                        .is_some_and(|token| secret.matches(token.as_bytes())) =>
+                {
+                    client.record_auth_failure();
+                    Response::error(401, "authentication required")
+                }
+                _ => {
+                    client.set_authenticated();
+                    route(engine, &request)
+                }
+            }
+        }
+        Ok(None) => return Ok(()),
+        Err(response) => response,
+    };
+    response.write_to(client)
+}
+
+/// Read a request; malformed or oversized ones give the error response.
+fn read_request<S: Read + Write>(
+    reader: &mut BufReader<S>,
+    max_body: usize,
+) -> io::Result<std::result::Result<Option<Request>, Response>> {
+    let Some(line) = read_line(reader)? else {
+        return Ok(Ok(None));
+    };
+    let mut parts = line.split(' ');
+    let (Some(method), Some(target), Some(_version), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Ok(Err(Response::error(400, "malformed request line")));
+    };
+
+    let mut content_length = 0;
+    let mut expect_continue = false;
+    let mut bearer = None;
+    for count in 0.. {
+        let Some(header) = read_line(reader)? else {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+        };
+        if header.is_empty() {
+            break;
+        }
+        if count == MAX_HEADERS || header.len() > MAX_HEADER_LINE {
+            return Ok(Err(Response::error(431, "too many or too long headers")));
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            return Ok(Err(Response::error(400, "malformed header")));
+        };
+        let value = value.trim();
+        match name.trim().to_ascii_lowercase().as_str() {
+            "content-length" => match value.parse::<usize>() {
+                Ok(len) if len <= max_body => content_length = len,
+                Ok(_) => return Ok(Err(Response::error(413, "request body too large"))),
+                Err(_) => return Ok(Err(Response::error(400, "invalid Content-Length"))),
+            },
+            "transfer-encoding" => {
+                return Ok(Err(Response::error(
+                    501,
+                    "chunked bodies are not supported",
+                )))
+            }
+            "expect" => expect_continue = value.eq_ignore_ascii_case("100-continue"),
+            "authorization" => {
+                bearer = value
+                    .split_once(' ')
+                    .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+                    .map(|(_, token)| token.trim().to_string())
+            }
+            _ => {}
+        }
+    }
+
+    // curl waits for this before sending larger bodies
+    if expect_continue && content_length > 0 {
+        let writer = reader.get_mut();
+        writer.write_all(b"HTTP/1.1 100 Continue\r\n\r\n")?;
+        writer.flush()?;
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let mut params = Vec::new();
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+        match (percent_decode(name, true), percent_decode(value, true)) {
+            (Some(name), Some(value)) => {
+                params.push((String::from_utf8_lossy(&name).into_owned(), value))
+            }
+            _ => return Ok(Err(Response::error(400, "malformed query string"))),
+        }
+    }
+    Ok(Ok(Some(Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        query: params,
+        bearer,
+        body,
+    })))
+}
+
+/// Read one header line without its terminator.
+fn read_line<R: BufRead>(reader: &mut R) -> io::Result<Option<String>> {
+    let mut line = Vec::new();
+    reader
+        .take(MAX_HEADER_LINE as u64 + 2)
+        .read_until(b'\n', &mut line)?;
+    if line.is_empty() {
+        return Ok(None);
+    }
+    while matches!(line.last(), Some(b'\n' | b'\r')) {
+        line.pop();
+    }
+    Ok(Some(String::from_utf8_lossy(&line).into_owned()))
+}
+
+fn route(engine: &Oblivion, request: &Request) -> Response {
+    let method = request.method.as_str();
+    match (method, request.path.as_str()) {
+        ("GET", "/stats") => stats(engine),
+        ("POST", "/admin/flush") => done(engine.flush()),
+        ("POST", "/admin/compact") => done(engine.compact()),
+        ("GET", "/keys") => match database(engine, request) {
+            Ok(db) => scan(&db, request),
+            Err(response) => response,
+        },
+        (_, "/stats" | "/admin/flush" | "/admin/compact" | "/keys") => {
+            Response::error(405, "method not allowed")
+        }
+        (_, path) if path.starts_with("/keys/") => {
+            let Some(key) = percent_decode(&path["/keys/".len()..], false) else {
+                return Response::error(400, "malformed key");
+            };
+            let db = match database(engine, request) {
+                Ok(db) => db,
+                Err(response) => return response,
+            };
+            match method {
+                "GET" => get(&db, &key),
+                "PUT" => put(&db, key, request),
+                "DELETE" => delete(&db, key),
+                _ => Response::error(405, "method not allowed"),
+            }
+        }
+        _ => Response::error(404, "not found"),
+    }
+}
+
+/// The logical database named by the `db` query parameter (0 without it).
+fn database<'a>(
+    engine: &'a Oblivion,
+    request: &Request,
+) -> std::result::Result<Database<'a>, Response> {
+    let index = match request.param("db") {
+        None => 0,
+        Some(db) => match std::str::from_utf8(db).ok().and_then(|s| s.parse().ok()) {
+            Some(index) => index,
+            None => return Err(Response::error(400, "db must be a database number")),
+        },
+    };
+    engine.database(index).map_err(|e| Response::error(400, e))
+}
+
+fn get(db: &Database, key: &[u8]) -> Response {
+    match db.get(key) {
+        Ok(Some(value)) => Response::ok(row_json(db, key, &value)),
+        Ok(None) => Response::error(404, "key not found"),
+        Err(e) => engine_error(e),
+    }
+}
+
+fn put(db: &Database, key: Vec<u8>, request: &Request) -> Response {
+    let value = request.body.clone();
+    let result = match request.param("ttl_ms") {
+        None => db.put(key, value),
+        Some(ttl) => match std::str::from_utf8(ttl).ok().and_then(|s| s.parse().ok()) {
+            Some(ttl_ms) if ttl_ms > 0 => db.put_with_ttl(key, value, ttl_ms),
+            _ => return Response::error(400, "ttl_ms must be a positive integer"),
+        },
+    };
+    done(result)
+}
+
+fn delete(db: &Database, key: Vec<u8>) -> Response {
+    // Deleting an absent key writes no tombstone
+    match db.get(&key) {
+        Ok(Some(_)) => {}
+        Ok(None) => return Response::ok(json!({ "deleted": false })),
+        Err(e) => return engine_error(e),
+    }
+    match db.delete(key) {
+        Ok(()) => Response::ok(json!({ "deleted": true })),
+        Err(e) => engine_error(e),
+    }
+}
+
+fn scan(db: &Database, request: &Request) -> Response {
+    let limit = match request.param("limit") {
+        None => DEFAULT_PAGE_SIZE,
+        Some(limit) => match std::str::from_utf8(limit).ok().and_then(|s| s.parse().ok()) {
+            Some(limit @ 1..=MAX_PAGE_SIZE) => limit,
+            _ => {
+                return Response::error(
+                    400,
+                    format!("limit must be between 1 and {}", MAX_PAGE_SIZE),
+                )
+            }
+        },
+    };
+
+    let filter = match request.param("filter").map(std::str::from_utf8) {
+        None => None,
+        Some(Ok(expression)) => match Predicate::parse(expression) {
+            Ok(filter) => Some(filter),
+            Err(e) => return Response::error(400, e.to_string()),
+        },
+        Some(Err(_)) => return Response::error(400, "filter is not UTF-8"),
+    };
+
+    let comparator = db.engine().comparator();
+    let mut opts = ReadOptions::new().fill_cache(false);
+    if let Some(filter) = &filter {
+        filter.narrow(&mut opts, comparator);
+    }
+    // Only byte order keeps the keys sharing a prefix together; under
+    // another order the rows are filtered instead
+    let prefix = request.param("prefix");
+    if let Some(prefix) = prefix.filter(|_| comparator.is_bytewise()) {
+        Predicate::key_prefix(prefix).narrow(&mut opts, comparator);
+    }
+    let cursor = match request.param("cursor").map(hex_decode) {
+        Some(None) => return Response::error(400, "malformed cursor"),
+        Some(Some(resume)) => Some(resume),
+        None => None,
+    };
+    let starts = [request.param("start").map(<[u8]>::to_vec), cursor];
+    for start in starts.into_iter().flatten() {
+        if opts
+            .lower_bound
+            .as_ref()
+            .is_none_or(|lower| comparator.compare(&start, lower).is_gt())
+        {
+            opts.lower_bound = Some(start);
+        }
+    }
+    if let Some(end) = request.param("end") {
+        if opts
+            .upper_bound
+            .as_deref()
+            .is_none_or(|upper| comparator.compare(end, upper).is_lt())
+        {
+            opts.upper_bound = Some(end.to_vec());
+        }
+    }
+
+    let (mut rows, mut resume) = match db.scan_page(&opts, limit) {
+        Ok(page) => page,
+        Err(e) => return engine_error(e),
+    };
+    // A page merges several sources and may exceed `limit`; the next
+    // page starts at the first row left out
+    if rows.len() > limit {
+        resume = Some(rows[limit].0.clone());
+        rows.truncate(limit);
+    }
+    if let Some(prefix) = prefix {
+        rows.retain(|(key, _)| key.starts_with(prefix));
+    }
+    if let Some(filter) = &filter {
+        rows.retain(|(key, value)| filter.matches(key, value));
+    }
+    let items: Vec<Json> = rows
+        .iter()
+        .map(|(key, value)| row_json(db, key, value))
+        .collect();
+    Response::ok(json!({
+        "items": items,
+        "next_cursor": resume.map(|resume| hex_encode(&resume)),
+    }))
+}
+
+fn stats(engine: &Oblivion) -> Response {
+    Response::ok(json!({
+        "metrics": engine.metrics().snapshot(),
+        "memtable_entries": engine.len(),
+        "memtable_size": engine.memtable_size(),
+        "sstable_count": engine.sstable_count(),
+        "latest_sequence": engine.latest_sequence(),
+        "read_only": engine.is_read_only(),
+        "background_error": engine.background_error(),
+    }))
+}
+
+/// One row as in NDJSON exports.
+fn row_json(db: &Database, key: &[u8], value: &[u8]) -> Json {
+    let (key_text, value_text, encoding) = encode_pair(key, value);
+    let mut row = json!({ "key": key_text, "value": value_text, "encoding": encoding });
+    if let Some(ttl_ms) = db.ttl(key) {
+        row["ttl_ms"] = json!(ttl_ms);
+    }
+    row
+}
+
+fn done(result: Result<()>) -> Response {
+    match result {
+        Ok(()) => Response::ok(json!({ "ok": true })),
+        Err(e) => engine_error(e),
+    }
+}
+
+/// Map an engine error to a status code.
+fn engine_error(error: OblivionError) -> Response {
+    let status = match error {
+        OblivionError::ReadOnly(_) => 403,
+        // Writes to keys reserved for logical databases
+        OblivionError::Unsupported(_) => 400,
+        OblivionError::KeyTooLarge { .. } | OblivionError::ValueTooLarge { .. } => 413,
+        _ => 500,
+    };
+    Response::error(status, error)
+}
+
+/// Decode `%XX` escapes (and `+` as a space in query strings).
+fn percent_decode(s: &str, plus_as_space: bool) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(s.len());
+    let mut bytes = s.bytes();
+    while let Some(b) = bytes.next() {
+        match b {
+            b'%' => {
+                let hex = [bytes.next()?, bytes.next()?];
+                out.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+            }
+            b'+' if plus_as_space => out.push(b' '),
+            b => out.push(b),
+        }
+    }
+    Some(out)
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn hex_decode(hex: &[u8]) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    hex.chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use std::net::TcpStream;
+
+    /// Send one raw request and return the status and JSON body.
+    fn call(server: &HttpServer, request: &str) -> (u16, Json) {
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head[9..12].parse().unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    fn temp_server() -> (tempfile::TempDir, Arc<Oblivion>, HttpServer) {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = Arc::new(Oblivion::open(Config::new(dir.path())).unwrap());
+        let server = HttpServer::start(Arc::clone(&engine), "127.0.0.1:0").unwrap();
+        (dir, engine, server)
+    }
+
+    #[test]
+    fn test_key_endpoints() {
+        let (_dir, engine, server) = temp_server();
+        let (status, body) = call(
+            &server,
+            "PUT /keys/user%3A1?ttl_ms=60000 HTTP/1.1\r\nContent-Length: 5\r\n\r\nalice",
+        );
+        assert_eq!((status, body), (200, json!({ "ok": true })));
+        assert_eq!(engine.get(b"user:1"), Some(b"alice".to_vec()));
+
+        let (status, body) = call(&server, "GET /keys/user:1 HTTP/1.1\r\n\r\n");
+        assert_eq!(status, 200);
+        assert_eq!(body["value"], "alice");
+        assert!(body["ttl_ms"].as_u64().unwrap() > 0);
+
+        engine.put(vec![0xff], vec![0x00]).unwrap();
+        let (_, body) = call(&server, "GET /keys/%FF HTTP/1.1\r\n\r\n");
+        assert_eq!(body["encoding"], "base64");
+        assert_eq!(body["value"], "AA==");
+
+        let (status, body) = call(&server, "DELETE /keys/user%3A1 HTTP/1.1\r\n\r\n");
+        assert_eq!((status, body), (200, json!({ "deleted": true })));
+        let (status, _) = call(&server, "GET /keys/user:1 HTTP/1.1\r\n\r\n");
+        assert_eq!(status, 404);
+        let (status, _) = call(&server, "PATCH /keys/user:1 HTTP/1.1\r\n\r\n");
+        assert_eq!(status, 405);
+        let (status, _) = call(&server, "PUT /keys/k?ttl_ms=soon HTTP/1.1\r\n\r\n");
+        assert_eq!(status, 400);
+
+        let (status, _) = call(
+            &server,
+            "PUT /keys/user:1?db=3 HTTP/1.1\r\nContent-Length: 4\r\n\r\ntest",
+        );
+        assert_eq!(status, 200);
+        let (_, body) = call(&server, "GET /keys?db=3 HTTP/1.1\r\n\r\n");
+        assert_eq!(body["items"][0]["key"], "user:1");
+        assert_eq!(body["items"].as_array().unwrap().len(), 1);
+        let (status, _) = call(&server, "GET /keys/user:1 HTTP/1.1\r\n\r\n");
+        assert_eq!(status, 404);
+        let (status, _) = call(&server, "GET /keys/user:1?db=99 HTTP/1.1\r\n\r\n");
+        assert_eq!(status, 400);
+    }
+
+    #[test]
+    fn test_filtered_scan() {
+        let (_dir, engine, server) = temp_server();
+        for (key, value) in [
+            ("user:1", "active"),
+            ("user:2", "inactive"),
+            ("user:3", "active"),
+            ("order:1", "active"),
+        ] {
+            engine.put(key, value).unwrap();
+        }
+        let (status, body) = call(
+            &server,
+            "GET /keys?filter=key+glob+user:*+and+value+prefix+%27act%27 HTTP/1.1\r\n\r\n",
+        );
+        assert_eq!(status, 200);
+        let keys: Vec<&str> = body["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["key"].as_str().unwrap())
+            .collect();
+        assert_eq!(keys, ["user:1", "user:3"]);
+
+        let (status, body) = call(&server, "GET /keys?filter=key+like+x HTTP/1.1\r\n\r\n");
+        assert_eq!(status, 400);
+        assert!(body["error"].as_str().unwrap().contains("like"));
+    }
+
+    #[test]
+    fn test_paginated_scan_and_admin() {
+        let (_dir, engine, server) = temp_server();
+        for i in 0..25 {
+            engine
+                .put(format!("row:{:02}", i).into_bytes(), b"v".to_vec())
+                .unwrap();
+        }
+        engine.put(b"other".to_vec(), b"v".to_vec()).unwrap();
+        let (status, _) = call(&server, "POST /admin/flush HTTP/1.1\r\n\r\n");
+        assert_eq!(status, 200);
+        assert_eq!(engine.sstable_count(), 1);
+
+        let mut keys = Vec::new();
+        let mut target = "/keys?prefix=row%3A&start=row:03&limit=10".to_string();
+        loop {
+            let (status, body) = call(&server, &format!("GET {} HTTP/1.1\r\n\r\n", target));
+            assert_eq!(status, 200);
+            let items = body["items"].as_array().unwrap();
+            assert!(items.len() <= 10);
+            keys.extend(
+                items
+                    .iter()
+                    .map(|item| item["key"].as_str().unwrap().to_string()),
+            );
+            match body["next_cursor"].as_str() {
+                Some(cursor) => target = format!("/keys?prefix=row:&limit=10&cursor={}", cursor),
+                None => break,
+            }
+        }
+        let expected: Vec<String> = (3..25).map(|i| format!("row:{:02}", i)).collect();
+        assert_eq!(keys, expected);
+
+        let (status, body) = call(&server, "GET /stats HTTP/1.1\r\n\r\n");
+        assert_eq!(status, 200);
+        assert_eq!(body["sstable_count"], 1);
+        assert_eq!(body["metrics"]["puts"], 26);
+        let (status, _) = call(&server, "POST /admin/compact HTTP/1.1\r\n\r\n");
+        assert_eq!(status, 200);
+        let (status, _) = call(&server, "GET /nowhere HTTP/1.1\r\n\r\n");
+        assert_eq!(status, 404);
+    }
+
+    #[test]
+    fn test_bearer_auth() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::new(dir.path()).with_server_password("s3cret");
+        let engine = Arc::new(Oblivion::open(config).unwrap());
+        let server = HttpServer::start(engine, "127.0.0.1:0").unwrap();
+        let (status, _) = call(&server, "GET /stats HTTP/1.1\r\n\r\n");
+        assert_eq!(status, 401);
+        let (status, _) = call(
+            &server,
+            "GET /stats HTTP/1.1\r\nAuthorization: Bearer nope\r\n\r\n",
+        );
+        assert_eq!(status, 401);
+        let (status, _) = call(
+            &server,
+            "GET /stats HTTP/1.1\r\nAuthorization: Bearer s3cret\r\n\r\n",
+        );
+        assert_eq!(status, 200);
+        let stats = server.stats();
+        assert_eq!((stats.requests, stats.auth_failures), (3, 2));
+    }
+}
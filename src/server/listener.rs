@@ -0,0 +1,437 @@
+//! OBLIVION - Connection Listener
+//! Accept loop, TLS termination and connection accounting shared by
+//! the server frontends.
+//!
+//! ## Threads
+//! One thread accepts connections, polling a non-blocking listener so
+//! it notices shutdown; each connection is then served by a thread of
+//! its own, which first completes the TLS handshake when
+//! `server_tls_cert` is configured. Stopping the listener shuts down
+//! every open connection, so blocked reads return, and joins all
+//! threads.
+//!
+//! ## Metrics
+//! Every connection counts the bytes it reads and writes (before TLS
+//! encryption) and the requests it serves; [`ConnectionInfo`] reports
+//! them per open connection and [`ServerStats`] as running totals.
+
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::error::Result;
+
+/// How often the accept loop checks for shutdown.
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Give up on a client that stops reading its replies.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Give up on a client that stalls during the TLS handshake.
+#[cfg(feature = "tls")]
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Serves one connection until the client is done with it.
+pub(crate) type Handler = dyn Fn(&mut Client) -> io::Result<()> + Send + Sync;
+
+/// One open connection of a server.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConnectionInfo {
+    /// Address of the client.
+    pub peer: SocketAddr,
+    /// Whether the connection is encrypted.
+    pub tls: bool,
+    /// Whether the client has authenticated (always true without
+    /// `server_password`).
+    pub authenticated: bool,
+    /// Time since the connection was accepted.
+    pub connected_for: Duration,
+    /// Requests served on the connection.
+    pub requests: u64,
+    /// Bytes received from the client.
+    pub bytes_read: u64,
+    /// Bytes sent to the client.
+    pub bytes_written: u64,
+}
+
+/// Running totals of a server since it started.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServerStats {
+    /// Connections accepted.
+    pub connections_accepted: u64,
+    /// Connections currently open.
+    pub connections_active: u64,
+    /// Requests served.
+    pub requests: u64,
+    /// Bytes received from clients.
+    pub bytes_read: u64,
+    /// Bytes sent to clients.
+    pub bytes_written: u64,
+    /// Requests refused for a missing or wrong password.
+    pub auth_failures: u64,
+    /// Connections dropped during the TLS handshake.
+    pub tls_handshake_failures: u64,
+}
+
+/// Counters kept per connection and per server.
+#[derive(Default)]
+struct Counters {
+    requests: AtomicU64,
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+}
+
+/// A running accept loop and its connections.
+pub(crate) struct Listener {
+    local_addr: SocketAddr,
+    shared: Arc<ListenerShared>,
+    acceptor: Option<JoinHandle<()>>,
+}
+
+struct ListenerShared {
+    /// Frontend name used in thread names and log lines.
+    name: &'static str,
+    handler: Box<Handler>,
+    #[cfg(feature = "tls")]
+    tls: Option<Arc<rustls::ServerConfig>>,
+    /// Whether clients must authenticate.
+    requires_auth: bool,
+    stop: AtomicBool,
+    totals: Counters,
+    accepted: AtomicU64,
+    auth_failures: AtomicU64,
+    tls_failures: AtomicU64,
+    /// Open connections, kept to report on and shut them down.
+    connections: Mutex<Vec<Connection>>,
+}
+
+/// One client connection.
+struct Connection {
+    peer: SocketAddr,
+    stream: TcpStream,
+    handle: JoinHandle<()>,
+    since: Instant,
+    state: Arc<ConnectionState>,
+}
+
+/// What a connection's thread reports about it.
+#[derive(Default)]
+struct ConnectionState {
+    counters: Counters,
+    tls: AtomicBool,
+    authenticated: AtomicBool,
+}
+
+/// The transport under a [`Client`].
+enum Transport {
+    Plain(TcpStream),
+    #[cfg(feature = "tls")]
+    Tls(Box<rustls::StreamOwned<rustls::ServerConnection, TcpStream>>),
+}
+
+/// A client connection as seen by a [`Handler`]: a byte stream, plain
+/// or TLS, that counts its traffic.
+pub(crate) struct Client {
+    transport: Transport,
+    shared: Arc<ListenerShared>,
+    state: Arc<ConnectionState>,
+}
+
+impl Listener {
+    /// Listen on `addr`, serving each connection with `handler`.
+    ///
+    /// `config` supplies the TLS certificate, if any; `requires_auth`
+    /// marks connections unauthenticated until the handler says
+    /// otherwise.
+    pub(crate) fn start(
+        name: &'static str,
+        addr: impl ToSocketAddrs,
+        config: &Config,
+        handler: Box<Handler>,
+    ) -> Result<Self> {
+        #[cfg(feature = "tls")]
+        let tls = super::tls::server_config(config)?;
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+        let shared = Arc::new(ListenerShared {
+            name,
+            handler,
+            #[cfg(feature = "tls")]
+            tls,
+            requires_auth: config.server_password.is_some(),
+            stop: AtomicBool::new(false),
+            totals: Counters::default(),
+            accepted: AtomicU64::new(0),
+            auth_failures: AtomicU64::new(0),
+            tls_failures: AtomicU64::new(0),
+            connections: Mutex::new(Vec::new()),
+        });
+        let acceptor = {
+            let shared = Arc::clone(&shared);
+            std::thread::Builder::new()
+                .name(format!("oblivion-{}-accept", name))
+                .spawn(move || shared.accept_loop(listener))?
+        };
+        log::info!("{} server listening on {}", name, local_addr);
+        Ok(Self {
+            local_addr,
+            shared,
+            acceptor: Some(acceptor),
+        })
+    }
+
+    /// Returns the address clients connect to.
+    pub(crate) fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Returns the number of open connections.
+    pub(crate) fn connection_count(&self) -> usize {
+        let mut connections = self.shared.connections.lock();
+        connections.retain(|connection| !connection.handle.is_finished());
+        connections.len()
+    }
+
+    /// Returns the traffic of each open connection.
+    pub(crate) fn connections(&self) -> Vec<ConnectionInfo> {
+        let mut connections = self.shared.connections.lock();
+        connections.retain(|connection| !connection.handle.is_finished());
+        connections
+            .iter()
+            .map(|connection| {
+                let state = &connection.state;
+                ConnectionInfo {
+                    peer: connection.peer,
+                    tls: state.tls.load(Ordering::Relaxed),
+                    authenticated: state.authenticated.load(Ordering::Relaxed),
+                    connected_for: connection.since.elapsed(),
+                    requests: state.counters.requests.load(Ordering::Relaxed),
+                    bytes_read: state.counters.bytes_read.load(Ordering::Relaxed),
+                    bytes_written: state.counters.bytes_written.load(Ordering::Relaxed),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the server's running totals.
+    pub(crate) fn stats(&self) -> ServerStats {
+        let shared = &self.shared;
+        ServerStats {
+            connections_accepted: shared.accepted.load(Ordering::Relaxed),
+            connections_active: self.connection_count() as u64,
+            requests: shared.totals.requests.load(Ordering::Relaxed),
+            bytes_read: shared.totals.bytes_read.load(Ordering::Relaxed),
+            bytes_written: shared.totals.bytes_written.load(Ordering::Relaxed),
+            auth_failures: shared.auth_failures.load(Ordering::Relaxed),
+            tls_handshake_failures: shared.tls_failures.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        self.shared.stop.store(true, Ordering::Release);
+        if let Some(acceptor) = self.acceptor.take() {
+            let _ = acceptor.join();
+        }
+        let connections = std::mem::take(&mut *self.shared.connections.lock());
+        for connection in connections {
+            let _ = connection.stream.shutdown(Shutdown::Both);
+            let _ = connection.handle.join();
+        }
+    }
+}
+
+impl ListenerShared {
+    fn accept_loop(self: Arc<Self>, listener: TcpListener) {
+        while !self.stop.load(Ordering::Acquire) {
+            match listener.accept() {
+                Ok((stream, peer)) => {
+                    self.accepted.fetch_add(1, Ordering::Relaxed);
+                    if let Err(e) = self.spawn_connection(stream, peer) {
+                        log::warn!("Failed to serve {} client {}: {}", self.name, peer, e);
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    std::thread::sleep(ACCEPT_POLL_INTERVAL)
+                }
+                Err(e) => {
+                    log::warn!("{} accept failed: {}", self.name, e);
+                    std::thread::sleep(ACCEPT_POLL_INTERVAL);
+                }
+            }
+        }
+    }
+
+    fn spawn_connection(self: &Arc<Self>, stream: TcpStream, peer: SocketAddr) -> Result<()> {
+        stream.set_nonblocking(false)?;
+        stream.set_nodelay(true)?;
+        stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        let state = Arc::new(ConnectionState::default());
+        state
+            .authenticated
+            .store(!self.requires_auth, Ordering::Relaxed);
+        let handle = {
+            let shared = Arc::clone(self);
+            let stream = stream.try_clone()?;
+            let state = Arc::clone(&state);
+            std::thread::Builder::new()
+                .name(format!("oblivion-{}-conn", self.name))
+                .spawn(move || {
+                    let result = shared.serve(stream.try_clone(), state);
+                    // The listener's clone of the stream would keep it
+                    // open otherwise
+                    let _ = stream.shutdown(Shutdown::Both);
+                    match result {
+                        Ok(()) => log::debug!("{} client {} disconnected", shared.name, peer),
+                        Err(e) if shared.stop.load(Ordering::Acquire) => {
+                            log::debug!("{} client {} closed: {}", shared.name, peer, e)
+                        }
+                        Err(e) => log::warn!("{} client {} failed: {}", shared.name, peer, e),
+                    }
+                })?
+        };
+        let mut connections = self.connections.lock();
+        connections.retain(|connection| !connection.handle.is_finished());
+        connections.push(Connection {
+            peer,
+            stream,
+            handle,
+            since: Instant::now(),
+            state,
+        });
+        Ok(())
+    }
+
+    /// Set up the transport and hand the connection to the handler.
+    fn serve(
+        self: &Arc<Self>,
+        stream: io::Result<TcpStream>,
+        state: Arc<ConnectionState>,
+    ) -> io::Result<()> {
+        let transport = self.handshake(stream?, &state)?;
+        let mut client = Client {
+            transport,
+            shared: Arc::clone(self),
+            state,
+        };
+        (self.handler)(&mut client)
+    }
+
+    #[cfg(feature = "tls")]
+    fn handshake(&self, mut stream: TcpStream, state: &ConnectionState) -> io::Result<Transport> {
+        let Some(tls) = &self.tls else {
+            return Ok(Transport::Plain(stream));
+        };
+        let mut connection =
+            rustls::ServerConnection::new(Arc::clone(tls)).map_err(io::Error::other)?;
+        stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        while connection.is_handshaking() {
+            if let Err(e) = connection.complete_io(&mut stream) {
+                self.tls_failures.fetch_add(1, Ordering::Relaxed);
+                return Err(e);
+            }
+        }
+        stream.set_read_timeout(None)?;
+        state.tls.store(true, Ordering::Relaxed);
+        Ok(Transport::Tls(Box::new(rustls::StreamOwned::new(
+            connection, stream,
+        ))))
+    }
+
+    #[cfg(not(feature = "tls"))]
+    fn handshake(&self, stream: TcpStream, _state: &ConnectionState) -> io::Result<Transport> {
+        Ok(Transport::Plain(stream))
+    }
+}
+
+impl Client {
+    /// The underlying socket.
+    fn socket(&self) -> &TcpStream {
+        match &self.transport {
+            Transport::Plain(stream) => stream,
+            #[cfg(feature = "tls")]
+            Transport::Tls(stream) => stream.get_ref(),
+        }
+    }
+
+    /// Set the socket's read timeout (`None` blocks indefinitely).
+    pub(crate) fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.socket().set_read_timeout(timeout)
+    }
+
+    /// Count one request served.
+    pub(crate) fn record_request(&self) {
+        self.state.counters.requests.fetch_add(1, Ordering::Relaxed);
+        self.shared.totals.requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Mark the client as authenticated.
+    pub(crate) fn set_authenticated(&self) {
+        self.state.authenticated.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns whether the client may run commands.
+    pub(crate) fn is_authenticated(&self) -> bool {
+        self.state.authenticated.load(Ordering::Relaxed)
+    }
+
+    /// Count a request refused for a missing or wrong password.
+    pub(crate) fn record_auth_failure(&self) {
+        self.shared.auth_failures.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Read for Client {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = match &mut self.transport {
+            Transport::Plain(stream) => stream.read(buf)?,
+            #[cfg(feature = "tls")]
+            Transport::Tls(stream) => stream.read(buf)?,
+        };
+        self.state
+            .counters
+            .bytes_read
+            .fetch_add(n as u64, Ordering::Relaxed);
+        self.shared
+            .totals
+            .bytes_read
+            .fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+impl Write for Client {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = match &mut self.transport {
+            Transport::Plain(stream) => stream.write(buf)?,
+            #[cfg(feature = "tls")]
+            Transport::Tls(stream) => stream.write(buf)?,
+        };
+        self.state
+            .counters
+            .bytes_written
+            .fetch_add(n as u64, Ordering::Relaxed);
+        self.shared
+            .totals
+            .bytes_written
+            .fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.transport {
+            Transport::Plain(stream) => stream.flush(),
+            #[cfg(feature = "tls")]
+            Transport::Tls(stream) => stream.flush(),
+        }
+    }
+}
@@ -0,0 +1,583 @@
+//! OBLIVION - RESP Server
+//! Serves an engine over TCP in the Redis protocol, so existing Redis
+//! clients and `redis-cli` can use it.
+//!
+//! ## Connections
+//! Each client connection is served by its own thread, which reads a
+//! command, runs it against the shared engine and writes the reply.
+//! Replies are flushed once no further command is buffered, so
+//! pipelined commands are answered in one write. Pipelining saves round
+//! trips, but each write is still its own WAL append; `MSET` and
+//! `MULTI`/`EXEC` blocks share one append and one sync. A malformed
+//! request gets a `Protocol error` reply and closes the connection, as
+//! in Redis.
+//!
+//! ## Commands
+//! | Command                                  | Reply                                     |
+//! |------------------------------------------|-------------------------------------------|
+//! | `PING [message]`, `ECHO message`         | `PONG` or the message                     |
+//! | `SELECT index`                           | `OK`; later commands use that database    |
+//! | `GET key`                                | The value, or nil                         |
+//! | `SET key value [EX seconds\|PX millis]`  | `OK`                                      |
+//! | `MSET key value [key value...]`          | `OK`; all pairs in one write batch        |
+//! | `APPEND key value`, `STRLEN key`         | Length of the value, 0 if absent          |
+//! | `GETSET key value`, `GETDEL key`         | The replaced or deleted value, or nil     |
+//! | `DEL key...`, `EXISTS key...`            | Number of existing keys                   |
+//! | `EXPIRE key seconds`, `PEXPIRE key ms`   | 1 if the key exists, else 0               |
+//! | `TTL key`, `PTTL key`                    | Remaining time, -1 without TTL, -2 absent |
+//! | `SCAN cursor [MATCH pattern] [WHERE filter] [COUNT n] [WITHVALUES]` | Next cursor and a page of keys |
+//! | `HSET key field value [field value...]`  | Number of new fields; one write batch     |
+//! | `HGET key field`                         | The field's value, or nil                 |
+//! | `HDEL key field...`                      | Number of existing fields deleted         |
+//! | `HGETALL key`                            | Fields and values, alternating            |
+//! | `LPUSH key value...`, `RPUSH key value...` | Length of the list; one write batch     |
+//! | `LPOP key`                               | The first item, or nil                    |
+//! | `LRANGE key start stop`                  | The items from `start` to `stop`          |
+//! | `SADD key member...`, `SREM key member...` | Number of members added or removed      |
+//! | `SISMEMBER key member`, `SMEMBERS key`   | 1 or 0; every member                      |
+//! | `ZADD key score member [score member...]` | Number of new members; one write batch   |
+//! | `ZSCORE key member`, `ZREM key member...` | The score, or nil; number removed        |
+//! | `ZRANGEBYSCORE key min max [WITHSCORES]` | Members by score, `(` for exclusive bounds |
+//! | `SUBSCRIBE channel...`, `PSUBSCRIBE pattern...` | Keyspace notifications, see below  |
+//! | `UNSUBSCRIBE [channel...]`, `PUNSUBSCRIBE [pattern...]` | One confirmation per channel |
+//! | `MULTI`, then commands, then `EXEC`      | Their replies; writes applied as one batch |
+//!
+//! `SCAN ... WHERE` takes a filter expression over keys and values (see
+//! the [`predicate` module](crate::engine::predicate)), evaluated by the
+//! server, so only matching rows are sent; `WITHVALUES` returns each key
+//! followed by its value. A page may hold fewer than `COUNT` matches, or
+//! none, while the cursor is not 0.
+//!
+//! Each connection starts in database 0; `SELECT` switches it to one of
+//! the configured `databases` (see the
+//! [`database` module](crate::engine::database)), as in Redis.
+//!
+//! Hashes, lists, sets and sorted sets (see the
+//! [`hash`](crate::engine::hash), [`list`](crate::engine::list),
+//! [`set`](crate::engine::set) and
+//! [`sorted_set`](crate::engine::sorted_set) modules) are kept apart
+//! from plain keys and each other: `GET`, `DEL`, `EXISTS` and `SCAN` do
+//! not see them, and one key can name a value and one of each type at
+//! once, where Redis would reply `WRONGTYPE`.
+//!
+//! `QUIT` closes the connection. Engine errors are returned as `ERR`
+//! replies, writes to a read-only engine (e.g. a replication follower)
+//! as `READONLY`.
+//!
+//! ## Keyspace Notifications
+//! Puts, deletes and expirations are published on Redis' keyspace
+//! channels (`__keyspace@<db>__:<key>` and `__keyevent@<db>__:<event>`), so
+//! clients can react to changes without polling. A subscribed
+//! connection only accepts pub/sub commands, `PING` and `QUIT`, as in
+//! Redis; it checks for new events every [`EVENT_POLL_INTERVAL`] while
+//! waiting for commands.
+//!
+//! ## Security
+//! With `server_password` set, clients must send `AUTH password` (or
+//! `AUTH default password`) before anything but `AUTH` and `QUIT`;
+//! other commands get `NOAUTH`, a wrong password `WRONGPASS`. With
+//! `server_tls_cert` and `server_tls_key` set (feature `tls`), the
+//! server accepts TLS connections only. Both settings apply to the HTTP
+//! API too.
+//!
+//! ## Metrics
+//! [`RespServer::connections`] reports each open connection's peer,
+//! traffic and request count; [`RespServer::stats`] the totals since
+//! the server started, including refused passwords and failed TLS
+//! handshakes.
+//!
+//! ## Example
+//! ```no_run
+//! use std::sync::Arc;
+//! use oblivion::{config::Config, engine::Oblivion, server::RespServer};
+//!
+//! let engine = Arc::new(Oblivion::open(Config::new("./data")).unwrap());
+//! let server = RespServer::start(engine, "127.0.0.1:6379").unwrap();
+//! println!("listening on {}", server.local_addr());
+//! ```
+
+mod commands;
+#[cfg(feature = "http")]
+pub mod http;
+mod listener;
+mod pubsub;
+pub mod resp;
+#[cfg(feature = "tls")]
+mod tls;
+mod transaction;
+
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+
+use self::commands::Cursors;
+use self::listener::{Client, Listener};
+use self::pubsub::{Delivery, Subscriptions};
+use self::resp::Reply;
+use self::transaction::Transaction;
+use crate::config::Secret;
+use crate::engine::Oblivion;
+use crate::error::Result;
+
+pub use self::listener::{ConnectionInfo, ServerStats};
+
+/// How often a subscribed connection checks for keyspace notifications
+/// while waiting for commands.
+const EVENT_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// A running RESP server.
+///
+/// Dropping it stops accepting clients, closes their connections and
+/// joins the server threads.
+pub struct RespServer {
+    listener: Listener,
+}
+
+/// State shared by all connections of a [`RespServer`].
+struct RespState {
+    engine: Arc<Oblivion>,
+    cursors: Cursors,
+    password: Option<Secret>,
+}
+
+impl RespServer {
+    /// Listen for clients on `addr`, with the password and TLS settings
+    /// of the engine's configuration.
+    pub fn start(engine: Arc<Oblivion>, addr: impl ToSocketAddrs) -> Result<Self> {
+        let config = engine.config();
+        let state = RespState {
+            engine,
+            cursors: Cursors::default(),
+            password: config.server_password.clone(),
+        };
+        let listener = Listener::start(
+            "RESP",
+            addr,
+            &config,
+            Box::new(move |client| state.serve(client)),
+        )?;
+        Ok(Self { listener })
+    }
+
+    /// Returns the address clients connect to.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.listener.local_addr()
+    }
+
+    /// Returns the number of connected clients.
+    pub fn connection_count(&self) -> usize {
+        self.listener.connection_count()
+    }
+
+    /// Returns the peer and traffic of each connected client.
+    pub fn connections(&self) -> Vec<ConnectionInfo> {
+        self.listener.connections()
+    }
+
+    /// Returns the totals since the server started.
+    pub fn stats(&self) -> ServerStats {
+        self.listener.stats()
+    }
+}
+
+impl RespState {
+    /// Answer one client's commands until it disconnects or quits.
+    fn serve(&self, client: &mut Client) -> io::Result<()> {
+        let mut reader = BufReader::new(client);
+        // Replies are buffered here and written once the pipeline drains
+        let mut out = Vec::new();
+        let mut subscriptions = Subscriptions::default();
+        let mut transaction = Transaction::default();
+        let mut database = self.engine.database(0).map_err(io::Error::other)?;
+        loop {
+            if subscriptions.is_active() && !forward_events(&mut reader, &subscriptions)? {
+                return Ok(());
+            }
+            let args = match resp::read_command(&mut reader) {
+                Ok(Some(args)) => args,
+                Ok(None) => return Ok(()),
+                Err(e) if e.kind() == ErrorKind::InvalidData => {
+                    Reply::error(format!("ERR Protocol error: {}", e)).write_to(&mut out)?;
+                    return send(reader.get_mut(), &mut out);
+                }
+                Err(e) => return Err(e),
+            };
+            let client = reader.get_mut();
+            client.record_request();
+            if args[0].eq_ignore_ascii_case(b"quit") {
+                Reply::ok().write_to(&mut out)?;
+                return send(client, &mut out);
+            }
+            let replies = if args[0].eq_ignore_ascii_case(b"auth") {
+                vec![self.auth(client, &args)]
+            } else if !client.is_authenticated() {
+                client.record_auth_failure();
+                vec![Reply::error("NOAUTH Authentication required.")]
+            } else if subscriptions.is_active() {
+                // Subscribed connections only take pub/sub commands
+                subscriptions
+                    .execute(&self.engine, &args)
+                    .unwrap_or_default()
+            } else if let Some(reply) = transaction.execute(&database, &args) {
+                vec![reply]
+            } else if let Some(replies) = subscriptions.execute(&self.engine, &args) {
+                replies
+            } else if args[0].eq_ignore_ascii_case(b"select") {
+                match commands::select(&self.engine, &args[1..]) {
+                    Ok(selected) => {
+                        database = selected;
+                        vec![Reply::ok()]
+                    }
+                    Err(reply) => vec![reply],
+                }
+            } else {
+                vec![commands::execute(&database, &self.cursors, &args)]
+            };
+            for reply in replies {
+                reply.write_to(&mut out)?;
+            }
+            if reader.buffer().is_empty() {
+                send(reader.get_mut(), &mut out)?;
+            }
+        }
+    }
+
+    /// `AUTH [username] password`; the only user is `default`.
+    fn auth(&self, client: &Client, args: &[Vec<u8>]) -> Reply {
+        let (user, password) = match args {
+            [_, password] => (&b"default"[..], password),
+            [_, user, password] => (user.as_slice(), password),
+            _ => return Reply::error("ERR wrong number of arguments for 'auth' command"),
+        };
+        let Some(secret) = &self.password else {
+            return Reply::error(
+                "ERR AUTH <password> called without any password configured for the default \
+                 user. Are you sure your configuration is correct?",
+            );
+        };
+        if user == b"default" && secret.matches(password) {
+            client.set_authenticated();
+            Reply::ok()
+        } else {
+            client.record_auth_failure();
+            Reply::error("WRONGPASS invalid username-password pair or user is disabled.")
+        }
+    }
+}
+
+/// Send pending keyspace notifications until the client sends a
+/// command. Returns `false` once the client has disconnected, or has
+/// been disconnected for falling behind.
+fn forward_events(
+    reader: &mut BufReader<&mut Client>,
+    subscriptions: &Subscriptions,
+) -> io::Result<bool> {
+    let mut out = Vec::new();
+    reader
+        .get_ref()
+        .set_read_timeout(Some(EVENT_POLL_INTERVAL))?;
+    let readable = loop {
+        match subscriptions.drain() {
+            Delivery::Messages(messages) => {
+                for message in messages {
+                    message.write_to(&mut out)?;
+                }
+                if !out.is_empty() {
+                    send(reader.get_mut(), &mut out)?;
+                }
+            }
+            Delivery::Lagged => {
+                log::warn!("Disconnecting a RESP subscriber that fell behind");
+                break false;
+            }
+        }
+        if !reader.buffer().is_empty() {
+            break true;
+        }
+        match reader.fill_buf() {
+            Ok(buffered) => break !buffered.is_empty(),
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(e) => return Err(e),
+        }
+    };
+    reader.get_ref().set_read_timeout(None)?;
+    Ok(readable)
+}
+
+/// Write out and clear the buffered replies.
+fn send(client: &mut Client, out: &mut Vec<u8>) -> io::Result<()> {
+    client.write_all(out)?;
+    out.clear();
+    client.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use std::io::{BufRead, Read};
+    use std::net::TcpStream;
+
+    fn temp_engine() -> (tempfile::TempDir, Arc<Oblivion>) {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = Arc::new(Oblivion::open(Config::new(dir.path())).unwrap());
+        (dir, engine)
+    }
+
+    #[test]
+    fn test_pipelined_commands_and_quit() {
+        let (_dir, engine) = temp_engine();
+        let server = RespServer::start(Arc::clone(&engine), "127.0.0.1:0").unwrap();
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        stream
+            .write_all(
+                b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$1\r\nv\r\nGET k\r\nDEL k missing\r\n\
+                  MSET a 1 b 2\r\n\
+                  BOGUS\r\nQUIT\r\n",
+            )
+            .unwrap();
+        let mut replies = String::new();
+        stream.read_to_string(&mut replies).unwrap();
+        assert_eq!(
+            replies,
+            "+OK\r\n$1\r\nv\r\n:1\r\n+OK\r\n-ERR unknown command 'bogus'\r\n+OK\r\n"
+        );
+        assert_eq!(engine.get(b"k"), None);
+        assert_eq!(engine.get(b"b"), Some(b"2".to_vec()));
+    }
+
+    #[test]
+    fn test_select_switches_databases() {
+        let (_dir, engine) = temp_engine();
+        let server = RespServer::start(Arc::clone(&engine), "127.0.0.1:0").unwrap();
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        stream
+            .write_all(
+                b"SET k zero\r\nSELECT 2\r\nGET k\r\nSET k two\r\nSCAN 0\r\n\
+                  SELECT 16\r\nSELECT 0\r\nGET k\r\nQUIT\r\n",
+            )
+            .unwrap();
+        let mut replies = String::new();
+        stream.read_to_string(&mut replies).unwrap();
+        assert_eq!(
+            replies,
+            "+OK\r\n+OK\r\n$-1\r\n+OK\r\n*2\r\n$1\r\n0\r\n*1\r\n$1\r\nk\r\n\
+             -ERR DB index is out of range\r\n+OK\r\n$4\r\nzero\r\n+OK\r\n"
+        );
+        let db = engine.database(2).unwrap();
+        assert_eq!(db.get(b"k").unwrap(), Some(b"two".to_vec()));
+    }
+
+    #[test]
+    fn test_scan_where_with_values() {
+        let (_dir, engine) = temp_engine();
+        for (key, value) in [
+            ("n:10", "even"),
+            ("n:11", "odd"),
+            ("n:13", "odd"),
+            ("n:2", "odd"),
+        ] {
+            engine.put(key, value).unwrap();
+        }
+        let server = RespServer::start(Arc::clone(&engine), "127.0.0.1:0").unwrap();
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        stream
+            .write_all(
+                b"*9\r\n$4\r\nSCAN\r\n$1\r\n0\r\n$5\r\nMATCH\r\n$4\r\nn:1*\r\n\
+                  $5\r\nWHERE\r\n$16\r\nvalue prefix odd\r\n$5\r\nCOUNT\r\n$3\r\n100\r\n\
+                  $10\r\nWITHVALUES\r\n\
+                  *4\r\n$4\r\nSCAN\r\n$1\r\n0\r\n$5\r\nWHERE\r\n$6\r\nsize x\r\nQUIT\r\n",
+            )
+            .unwrap();
+        let mut replies = String::new();
+        stream.read_to_string(&mut replies).unwrap();
+        assert_eq!(
+            replies,
+            "*2\r\n$1\r\n0\r\n*4\r\n$4\r\nn:11\r\n$3\r\nodd\r\n$4\r\nn:13\r\n$3\r\nodd\r\n\
+             -ERR Invalid filter: expected 'key' or 'value', found \"size\"\r\n+OK\r\n"
+        );
+    }
+
+    #[test]
+    fn test_string_commands() {
+        let (_dir, engine) = temp_engine();
+        let server = RespServer::start(Arc::clone(&engine), "127.0.0.1:0").unwrap();
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        stream
+            .write_all(
+                b"APPEND k ab\r\nAPPEND k cd\r\nSTRLEN k\r\nSTRLEN nothing\r\n\
+                  GETSET k x\r\nGETSET new y\r\nGETDEL k\r\nGETDEL k\r\nSTRLEN\r\nQUIT\r\n",
+            )
+            .unwrap();
+        let mut replies = String::new();
+        stream.read_to_string(&mut replies).unwrap();
+        assert_eq!(
+            replies,
+            ":2\r\n:4\r\n:4\r\n:0\r\n$4\r\nabcd\r\n$-1\r\n$1\r\nx\r\n$-1\r\n\
+             -ERR wrong number of arguments for 'strlen' command\r\n+OK\r\n"
+        );
+        assert_eq!(engine.get(b"k"), None);
+        assert_eq!(engine.get(b"new"), Some(b"y".to_vec()));
+    }
+
+    #[test]
+    fn test_hash_commands() {
+        let (_dir, engine) = temp_engine();
+        let server = RespServer::start(Arc::clone(&engine), "127.0.0.1:0").unwrap();
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        stream
+            .write_all(
+                b"HSET h b 1 a 2\r\nHSET h a 3\r\nHGET h a\r\nHGET h z\r\n\
+                  HDEL h b z\r\nHGETALL h\r\nHSET h a\r\nEXISTS h\r\nQUIT\r\n",
+            )
+            .unwrap();
+        let mut replies = String::new();
+        stream.read_to_string(&mut replies).unwrap();
+        assert_eq!(
+            replies,
+            ":2\r\n:0\r\n$1\r\n3\r\n$-1\r\n:1\r\n*2\r\n$1\r\na\r\n$1\r\n3\r\n\
+             -ERR wrong number of arguments for 'hset' command\r\n:0\r\n+OK\r\n"
+        );
+        assert_eq!(engine.hget(b"h", b"a").unwrap(), Some(b"3".to_vec()));
+    }
+
+    #[test]
+    fn test_list_commands() {
+        let (_dir, engine) = temp_engine();
+        let server = RespServer::start(Arc::clone(&engine), "127.0.0.1:0").unwrap();
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        stream
+            .write_all(
+                b"RPUSH q b c\r\nLPUSH q a\r\nLRANGE q 0 -1\r\nLPOP q\r\nLRANGE q -1 5\r\n\
+                  LPOP nothing\r\nLRANGE q x 1\r\nQUIT\r\n",
+            )
+            .unwrap();
+        let mut replies = String::new();
+        stream.read_to_string(&mut replies).unwrap();
+        assert_eq!(
+            replies,
+            ":2\r\n:3\r\n*3\r\n$1\r\na\r\n$1\r\nb\r\n$1\r\nc\r\n$1\r\na\r\n\
+             *1\r\n$1\r\nc\r\n$-1\r\n-ERR value is not an integer or out of range\r\n+OK\r\n"
+        );
+        assert_eq!(engine.lrange(b"q", 0, -1).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_set_and_sorted_set_commands() {
+        let (_dir, engine) = temp_engine();
+        let server = RespServer::start(Arc::clone(&engine), "127.0.0.1:0").unwrap();
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        stream
+            .write_all(
+                b"SADD s b a b\r\nSISMEMBER s a\r\nSREM s a z\r\nSMEMBERS s\r\n\
+                  ZADD z 2.5 bob 10 ada -inf low\r\nZSCORE z bob\r\n\
+                  ZRANGEBYSCORE z (2.5 +inf WITHSCORES\r\nZRANGEBYSCORE z -inf 2.5\r\n\
+                  ZREM z low\r\nZADD z x bob\r\nQUIT\r\n",
+            )
+            .unwrap();
+        let mut replies = String::new();
+        stream.read_to_string(&mut replies).unwrap();
+        assert_eq!(
+            replies,
+            ":2\r\n:1\r\n:1\r\n*1\r\n$1\r\nb\r\n:3\r\n$3\r\n2.5\r\n\
+             *2\r\n$3\r\nada\r\n$2\r\n10\r\n*2\r\n$3\r\nlow\r\n$3\r\nbob\r\n\
+             :1\r\n-ERR value is not a valid float\r\n+OK\r\n"
+        );
+        assert_eq!(engine.zscore(b"z", b"ada").unwrap(), Some(10.0));
+    }
+
+    #[test]
+    fn test_protocol_error_closes_connection() {
+        let (_dir, engine) = temp_engine();
+        let server = RespServer::start(engine, "127.0.0.1:0").unwrap();
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        stream.write_all(b"*1\r\n:5\r\n").unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        assert!(line.starts_with("-ERR Protocol error"));
+        line.clear();
+        assert_eq!(reader.read_line(&mut line).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_auth_and_connection_stats() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::new(dir.path()).with_server_password("s3cret");
+        let engine = Arc::new(Oblivion::open(config).unwrap());
+        let server = RespServer::start(Arc::clone(&engine), "127.0.0.1:0").unwrap();
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        let request = b"SET k v\r\nAUTH wrong\r\nAUTH default s3cret\r\nSET k v\r\n";
+        stream.write_all(request).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut replies = Vec::new();
+        for _ in 0..4 {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            replies.push(line);
+        }
+        assert!(replies[0].starts_with("-NOAUTH"));
+        assert!(replies[1].starts_with("-WRONGPASS"));
+        assert_eq!(replies[2..], ["+OK\r\n", "+OK\r\n"]);
+        assert_eq!(engine.get(b"k"), Some(b"v".to_vec()));
+
+        // The reply can arrive before the server thread counts it
+        let written: usize = replies.iter().map(String::len).sum();
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while server.stats().bytes_written < written as u64 {
+            assert!(std::time::Instant::now() < deadline);
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        let connections = server.connections();
+        assert_eq!(connections.len(), 1);
+        assert!(connections[0].authenticated && !connections[0].tls);
+        assert_eq!(connections[0].requests, 4);
+        assert_eq!(connections[0].bytes_read, request.len() as u64);
+        let stats = server.stats();
+        assert_eq!(stats.connections_accepted, 1);
+        assert_eq!(stats.auth_failures, 2);
+    }
+
+    #[test]
+    fn test_keyspace_notifications() {
+        let (_dir, engine) = temp_engine();
+        let server = RespServer::start(Arc::clone(&engine), "127.0.0.1:0").unwrap();
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        stream
+            .write_all(b"PSUBSCRIBE __keyspace@0__:user:*\r\n")
+            .unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut read_lines = |count: usize| -> String {
+            let mut text = String::new();
+            for _ in 0..count {
+                reader.read_line(&mut text).unwrap();
+            }
+            text
+        };
+        assert_eq!(
+            read_lines(6),
+            "*3\r\n$10\r\npsubscribe\r\n$21\r\n__keyspace@0__:user:*\r\n:1\r\n"
+        );
+
+        engine.put(b"user:1".to_vec(), b"alice".to_vec()).unwrap();
+        engine.put(b"order:1".to_vec(), b"x".to_vec()).unwrap();
+        engine.delete(b"user:1".to_vec()).unwrap();
+        let message = |event: &str| {
+            format!(
+                "*4\r\n$8\r\npmessage\r\n$21\r\n__keyspace@0__:user:*\r\n\
+                 $21\r\n__keyspace@0__:user:1\r\n${}\r\n{}\r\n",
+                event.len(),
+                event
+            )
+        };
+        assert_eq!(read_lines(18), message("set") + &message("del"));
+
+        stream
+            .write_all(b"GET user:1\r\nPUNSUBSCRIBE\r\nGET user:1\r\n")
+            .unwrap();
+        let replies = read_lines(8);
+        assert!(replies.starts_with("-ERR Can't execute 'get'"));
+        assert!(replies.ends_with(":0\r\n$-1\r\n"));
+    }
+}
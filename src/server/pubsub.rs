@@ -0,0 +1,324 @@
+//! OBLIVION - Keyspace Notifications
+//! Redis-style pub/sub of key changes, fed by
+//! [`Oblivion::watch_prefix`](crate::engine::Oblivion::watch_prefix).
+//!
+//! ## Channels
+//! Each change is published on two channels, as Redis' keyspace
+//! notifications are:
+//!
+//! | Channel                     | Message    |
+//! |-----------------------------|------------|
+//! | `__keyspace@<db>__:<key>`   | event name |
+//! | `__keyevent@<db>__:<event>` | key        |
+//!
+//! where `<db>` is the logical database of the key (see
+//! [`database`](crate::engine::database)), and `<key>` the key within
+//! it. The events are `set` (any put), `del` and `expired`, and `hset`
+//! and `hdel` for changes to a field of a
+//! [hash](crate::engine::hash), named by the hash's key. Changes to
+//! lists, sets and sorted sets are not published. Clients
+//! `SUBSCRIBE` to channels or `PSUBSCRIBE` to glob patterns over them;
+//! other channel names are accepted but never receive messages. There
+//! is no `notify-keyspace-events` setting: notifications are always on,
+//! and cost nothing until a client subscribes.
+//!
+//! ## Delivery
+//! A subscribed connection watches every key and filters events
+//! against its subscriptions. A subscriber more than
+//! [`WATCH_BUFFER`](crate::engine::changes::WATCH_BUFFER) events behind
+//! is disconnected, as Redis disconnects clients over their output
+//! buffer limit.
+
+use std::collections::BTreeSet;
+use std::sync::mpsc::{Receiver, TryRecvError};
+
+use super::resp::Reply;
+use crate::engine::changes::ChangeEvent;
+use crate::engine::database::{split_key, DATA_TYPE_PREFIX};
+use crate::engine::glob::glob_match;
+use crate::engine::hash::split_field_key;
+use crate::engine::Oblivion;
+
+/// The channels and patterns one connection is subscribed to.
+#[derive(Default)]
+pub(crate) struct Subscriptions {
+    channels: BTreeSet<Vec<u8>>,
+    patterns: BTreeSet<Vec<u8>>,
+    /// Engine events, while anything is subscribed.
+    events: Option<Receiver<ChangeEvent>>,
+}
+
+/// Outcome of draining pending events.
+pub(crate) enum Delivery {
+    /// Messages to send to the client (possibly none).
+    Messages(Vec<Reply>),
+    /// The engine cancelled the watch; the client must be disconnected.
+    Lagged,
+}
+
+impl Subscriptions {
+    /// Returns whether the connection is in subscribed mode, where only
+    /// pub/sub commands are allowed.
+    pub(crate) fn is_active(&self) -> bool {
+        !self.channels.is_empty() || !self.patterns.is_empty()
+    }
+
+    /// Run a pub/sub command, or return `None` if `args` is not one.
+    pub(crate) fn execute(&mut self, engine: &Oblivion, args: &[Vec<u8>]) -> Option<Vec<Reply>> {
+        let name = String::from_utf8_lossy(&args[0]).to_ascii_lowercase();
+        let targets = &args[1..];
+        let replies = match name.as_str() {
+            "subscribe" | "psubscribe" if targets.is_empty() => vec![wrong_arity(&name)],
+            "subscribe" | "psubscribe" => {
+                let pattern = name == "psubscribe";
+                let replies = targets
+                    .iter()
+                    .map(|target| {
+                        let set = self.set_mut(pattern);
+                        set.insert(target.clone());
+                        self.confirmation(&name, Some(target))
+                    })
+                    .collect();
+                if self.events.is_none() {
+                    self.events = Some(engine.watch_prefix(b""));
+                }
+                replies
+            }
+            "unsubscribe" | "punsubscribe" => {
+                let pattern = name == "punsubscribe";
+                let targets: Vec<Vec<u8>> = match targets {
+                    [] => self.set_mut(pattern).iter().cloned().collect(),
+                    targets => targets.to_vec(),
+                };
+                let mut replies: Vec<Reply> = targets
+                    .iter()
+                    .map(|target| {
+                        self.set_mut(pattern).remove(target);
+                        self.confirmation(&name, Some(target))
+                    })
+                    .collect();
+                if replies.is_empty() {
+                    replies.push(self.confirmation(&name, None));
+                }
+                if !self.is_active() {
+                    self.events = None;
+                }
+                replies
+            }
+            "ping" if self.is_active() => match targets {
+                [] => vec![pong(b"")],
+                [message] => vec![pong(message)],
+                _ => vec![wrong_arity(&name)],
+            },
+            _ if self.is_active() => vec![Reply::error(format!(
+                "ERR Can't execute '{}': only (P)SUBSCRIBE / (P)UNSUBSCRIBE / PING / QUIT are \
+                 allowed in this context",
+                name
+            ))],
+            _ => return None,
+        };
+        Some(replies)
+    }
+
+    /// Turn the events received so far into messages.
+    pub(crate) fn drain(&self) -> Delivery {
+        let Some(events) = &self.events else {
+            return Delivery::Messages(Vec::new());
+        };
+        let mut messages = Vec::new();
+        loop {
+            match events.try_recv() {
+                Ok(event) => self.messages(&event, &mut messages),
+                Err(TryRecvError::Empty) => return Delivery::Messages(messages),
+                Err(TryRecvError::Disconnected) => return Delivery::Lagged,
+            }
+        }
+    }
+
+    /// The messages `event` produces for this connection.
+    fn messages(&self, event: &ChangeEvent, out: &mut Vec<Reply>) {
+        let (name, key) = match event {
+            ChangeEvent::Put { key, .. } => ("set", key),
+            ChangeEvent::Delete { key, .. } => ("del", key),
+            ChangeEvent::Expired { key } => ("expired", key),
+        };
+        let (db, key) = split_key(key);
+        let hash = split_field_key(key);
+        let (name, key) = match (&hash, name) {
+            (Some((hash, _)), "set") => ("hset", hash.as_slice()),
+            (Some((hash, _)), _) => ("hdel", hash.as_slice()),
+            (None, _) if key.starts_with(DATA_TYPE_PREFIX) => return,
+            (None, _) => (name, key),
+        };
+        let notifications = [
+            (
+                [format!("__keyspace@{}__:", db).as_bytes(), key].concat(),
+                name.as_bytes(),
+            ),
+            (format!("__keyevent@{}__:{}", db, name).into_bytes(), key),
+        ];
+        for (channel, payload) in notifications {
+            if self.channels.contains(&channel) {
+                out.push(Reply::Array(vec![
+                    Reply::bulk("message"),
+                    Reply::bulk(channel.clone()),
+                    Reply::bulk(payload),
+                ]));
+            }
+            for pattern in &self.patterns {
+                if glob_match(pattern, &channel) {
+                    out.push(Reply::Array(vec![
+                        Reply::bulk("pmessage"),
+                        Reply::bulk(pattern.clone()),
+                        Reply::bulk(channel.clone()),
+                        Reply::bulk(payload),
+                    ]));
+                }
+            }
+        }
+    }
+
+    fn set_mut(&mut self, pattern: bool) -> &mut BTreeSet<Vec<u8>> {
+        if pattern {
+            &mut self.patterns
+        } else {
+            &mut self.channels
+        }
+    }
+
+    /// `[kind, target, subscription count]`, as each (un)subscribe
+    /// acknowledges one target.
+    fn confirmation(&self, kind: &str, target: Option<&Vec<u8>>) -> Reply {
+        let count = self.channels.len() + self.patterns.len();
+        Reply::Array(vec![
+            Reply::bulk(kind),
+            Reply::Bulk(target.cloned()),
+            Reply::Integer(count as i64),
+        ])
+    }
+}
+
+/// `PING` while subscribed answers as a message, as in Redis.
+fn pong(message: &[u8]) -> Reply {
+    Reply::Array(vec![Reply::bulk("pong"), Reply::bulk(message)])
+}
+
+fn wrong_arity(name: &str) -> Reply {
+    Reply::error(format!(
+        "ERR wrong number of arguments for '{}' command",
+        name
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn args(words: &str) -> Vec<Vec<u8>> {
+        words
+            .split(' ')
+            .map(|word| word.as_bytes().to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn test_subscriptions_receive_matching_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = Oblivion::open(Config::new(dir.path())).unwrap();
+        let mut subs = Subscriptions::default();
+        assert!(subs.execute(&engine, &args("GET k")).is_none());
+
+        let replies = subs
+            .execute(&engine, &args("SUBSCRIBE __keyspace@0__:user:1"))
+            .unwrap();
+        assert_eq!(replies.len(), 1);
+        subs.execute(&engine, &args("PSUBSCRIBE __keyevent@0__:*"))
+            .unwrap();
+        assert!(subs.is_active());
+        let refused = subs.execute(&engine, &args("GET k")).unwrap();
+        assert!(matches!(&refused[0], Reply::Error(e) if e.contains("only (P)SUBSCRIBE")));
+
+        engine.put(b"user:1".to_vec(), b"a".to_vec()).unwrap();
+        engine.delete(b"user:2".to_vec()).unwrap();
+        let Delivery::Messages(messages) = subs.drain() else {
+            panic!("watch cancelled");
+        };
+        assert_eq!(
+            messages,
+            vec![
+                Reply::Array(vec![
+                    Reply::bulk("message"),
+                    Reply::bulk("__keyspace@0__:user:1"),
+                    Reply::bulk("set"),
+                ]),
+                Reply::Array(vec![
+                    Reply::bulk("pmessage"),
+                    Reply::bulk("__keyevent@0__:*"),
+                    Reply::bulk("__keyevent@0__:set"),
+                    Reply::bulk("user:1"),
+                ]),
+                Reply::Array(vec![
+                    Reply::bulk("pmessage"),
+                    Reply::bulk("__keyevent@0__:*"),
+                    Reply::bulk("__keyevent@0__:del"),
+                    Reply::bulk("user:2"),
+                ]),
+            ]
+        );
+
+        // Keys of other databases are published on their own channels
+        subs.execute(&engine, &args("PSUBSCRIBE __keyevent@3__:*"))
+            .unwrap();
+        let db = engine.database(3).unwrap();
+        db.put(b"user:1", b"b".to_vec()).unwrap();
+        let Delivery::Messages(messages) = subs.drain() else {
+            panic!("watch cancelled");
+        };
+        assert_eq!(
+            messages,
+            vec![Reply::Array(vec![
+                Reply::bulk("pmessage"),
+                Reply::bulk("__keyevent@3__:*"),
+                Reply::bulk("__keyevent@3__:set"),
+                Reply::bulk("user:1"),
+            ])]
+        );
+
+        // Hash fields are published under the hash's key
+        db.hset(b"h", vec![(b"f".to_vec(), b"v".to_vec())]).unwrap();
+        db.hdel(b"h", &[b"f".to_vec()]).unwrap();
+        let Delivery::Messages(messages) = subs.drain() else {
+            panic!("watch cancelled");
+        };
+        let events: Vec<_> = ["hset", "hdel"]
+            .iter()
+            .map(|event| {
+                Reply::Array(vec![
+                    Reply::bulk("pmessage"),
+                    Reply::bulk("__keyevent@3__:*"),
+                    Reply::bulk(format!("__keyevent@3__:{}", event)),
+                    Reply::bulk("h"),
+                ])
+            })
+            .collect();
+        assert_eq!(messages, events);
+        db.rpush(b"l", vec![b"v".to_vec()]).unwrap();
+        let Delivery::Messages(messages) = subs.drain() else {
+            panic!("watch cancelled");
+        };
+        assert!(messages.is_empty());
+
+        let replies = subs.execute(&engine, &args("UNSUBSCRIBE")).unwrap();
+        assert_eq!(
+            replies,
+            vec![Reply::Array(vec![
+                Reply::bulk("unsubscribe"),
+                Reply::bulk("__keyspace@0__:user:1"),
+                Reply::Integer(2),
+            ])]
+        );
+        subs.execute(&engine, &args("PUNSUBSCRIBE")).unwrap();
+        assert!(!subs.is_active() && subs.events.is_none());
+    }
+}
@@ -0,0 +1,240 @@
+//! OBLIVION - RESP Codec
+//! Reads commands and writes replies in the Redis serialization
+//! protocol (RESP2).
+//!
+//! ## Requests
+//! Clients send each command as an array of bulk strings
+//! (`*2\r\n$3\r\nGET\r\n$3\r\nkey\r\n`). Inline commands, one line of
+//! space-separated words as typed into `telnet`, are accepted too.
+//!
+//! ## Replies
+//! [`Reply`] covers the RESP2 types: simple strings, errors, integers,
+//! bulk strings (or nil) and arrays.
+
+use std::io::{self, BufRead, Read, Write};
+
+/// Largest accepted bulk string, as Redis' default `proto-max-bulk-len`.
+pub const MAX_BULK_LEN: usize = 512 * 1024 * 1024;
+
+/// Largest accepted number of arguments in one command.
+pub const MAX_ARGS: usize = 1024 * 1024;
+
+/// Longest accepted inline command or header line.
+pub const MAX_LINE_LEN: usize = 64 * 1024;
+
+/// A reply to one command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reply {
+    /// `+OK`-style status line.
+    Simple(String),
+    /// `-ERR ...` error line; the message starts with the error code.
+    Error(String),
+    /// `:n` integer.
+    Integer(i64),
+    /// Binary-safe string, or nil (`$-1`).
+    Bulk(Option<Vec<u8>>),
+    /// Array of nested replies.
+    Array(Vec<Reply>),
+}
+
+impl Reply {
+    /// The `+OK` status reply.
+    pub fn ok() -> Self {
+        Reply::Simple("OK".to_string())
+    }
+
+    /// The nil bulk string reply.
+    pub fn nil() -> Self {
+        Reply::Bulk(None)
+    }
+
+    /// A bulk string reply holding `bytes`.
+    pub fn bulk(bytes: impl Into<Vec<u8>>) -> Self {
+        Reply::Bulk(Some(bytes.into()))
+    }
+
+    /// An error reply; `message` starts with its code, e.g. `ERR` or
+    /// `READONLY`.
+    pub fn error(message: impl Into<String>) -> Self {
+        Reply::Error(message.into())
+    }
+
+    /// Encode the reply onto `out`.
+    pub fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        match self {
+            Reply::Simple(line) => write!(out, "+{}\r\n", single_line(line)),
+            Reply::Error(line) => write!(out, "-{}\r\n", single_line(line)),
+            Reply::Integer(n) => write!(out, ":{}\r\n", n),
+            Reply::Bulk(None) => out.write_all(b"$-1\r\n"),
+            Reply::Bulk(Some(bytes)) => {
+                write!(out, "${}\r\n", bytes.len())?;
+                out.write_all(bytes)?;
+                out.write_all(b"\r\n")
+            }
+            Reply::Array(items) => {
+                write!(out, "*{}\r\n", items.len())?;
+                items.iter().try_for_each(|item| item.write_to(out))
+            }
+        }
+    }
+}
+
+/// Status and error lines cannot carry line breaks.
+fn single_line(line: &str) -> String {
+    line.replace(['\r', '\n'], " ")
+}
+
+/// Read the next command as its list of arguments.
+///
+/// Returns `None` at a clean end of stream. Malformed input yields an
+/// [`io::ErrorKind::InvalidData`] error, after which the stream is out
+/// of sync and should be closed.
+pub fn read_command<R: BufRead>(reader: &mut R) -> io::Result<Option<Vec<Vec<u8>>>> {
+    loop {
+        let Some(line) = read_line(reader)? else {
+            return Ok(None);
+        };
+        let args = match line.strip_prefix(b"*") {
+            Some(count) => read_array(reader, parse_len(count, MAX_ARGS, "multibulk length")?)?,
+            None => line
+                .split(|b| b.is_ascii_whitespace())
+                .filter(|word| !word.is_empty())
+                .map(<[u8]>::to_vec)
+                .collect(),
+        };
+        // Empty commands are skipped, as Redis does
+        if !args.is_empty() {
+            return Ok(Some(args));
+        }
+    }
+}
+
+/// Read `count` bulk strings following a `*count` header.
+fn read_array<R: BufRead>(reader: &mut R, count: Option<usize>) -> io::Result<Vec<Vec<u8>>> {
+    let count = count.unwrap_or(0);
+    let mut args = Vec::with_capacity(count.min(64));
+    for _ in 0..count {
+        let line = read_line(reader)?.ok_or_else(truncated)?;
+        let Some(len) = line.strip_prefix(b"$") else {
+            return Err(protocol(format!(
+                "expected '$', got '{}'",
+                String::from_utf8_lossy(&line[..line.len().min(1)])
+            )));
+        };
+        let len = parse_len(len, MAX_BULK_LEN, "bulk length")?
+            .ok_or_else(|| protocol("invalid bulk length".to_string()))?;
+        // Grow with the data actually received, not the announced length
+        let mut arg = Vec::with_capacity(len.min(MAX_LINE_LEN) + 2);
+        reader.take(len as u64 + 2).read_to_end(&mut arg)?;
+        if arg.len() < len + 2 {
+            return Err(truncated());
+        }
+        if !arg.ends_with(b"\r\n") {
+            return Err(protocol("bulk string not terminated by CRLF".to_string()));
+        }
+        arg.truncate(len);
+        args.push(arg);
+    }
+    Ok(args)
+}
+
+/// Read one line without its `\r\n` (or bare `\n`) terminator.
+fn read_line<R: BufRead>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut line = Vec::new();
+    reader
+        .take(MAX_LINE_LEN as u64 + 2)
+        .read_until(b'\n', &mut line)?;
+    if line.is_empty() {
+        return Ok(None);
+    }
+    if line.pop() != Some(b'\n') {
+        return Err(if line.len() > MAX_LINE_LEN {
+            protocol("too big request line".to_string())
+        } else {
+            truncated()
+        });
+    }
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    Ok(Some(line))
+}
+
+/// Parse a length header; negative lengths (null values) give `None`.
+fn parse_len(digits: &[u8], max: usize, what: &str) -> io::Result<Option<usize>> {
+    let len: i64 = std::str::from_utf8(digits)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| protocol(format!("invalid {}", what)))?;
+    if len < 0 {
+        return Ok(None);
+    }
+    match usize::try_from(len) {
+        Ok(len) if len <= max => Ok(Some(len)),
+        _ => Err(protocol(format!("invalid {}", what))),
+    }
+}
+
+fn protocol(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn truncated() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "connection closed mid-command",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commands(input: &[u8]) -> io::Result<Vec<Vec<Vec<u8>>>> {
+        let mut reader = input;
+        let mut out = Vec::new();
+        while let Some(args) = read_command(&mut reader)? {
+            out.push(args);
+        }
+        Ok(out)
+    }
+
+    #[test]
+    fn test_read_array_and_inline_commands() {
+        let input =
+            b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$4\r\nv\r\nv\r\n\r\nPING  hello\r\n*0\r\nget k\n";
+        let parsed = commands(input).unwrap();
+        assert_eq!(
+            parsed,
+            vec![
+                vec![b"SET".to_vec(), b"k".to_vec(), b"v\r\nv".to_vec()],
+                vec![b"PING".to_vec(), b"hello".to_vec()],
+                vec![b"get".to_vec(), b"k".to_vec()],
+            ]
+        );
+
+        let err = commands(b"*1\r\n+PING\r\n").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let err = commands(b"*2\r\n$4\r\nPING\r\n").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        let err = commands(b"*1\r\n$2\r\nabcd\r\n").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_write_replies() {
+        let reply = Reply::Array(vec![
+            Reply::ok(),
+            Reply::error("ERR bad\r\nthing"),
+            Reply::Integer(-2),
+            Reply::nil(),
+            Reply::bulk(b"a\r\nb".to_vec()),
+        ]);
+        let mut out = Vec::new();
+        reply.write_to(&mut out).unwrap();
+        assert_eq!(
+            out,
+            b"*5\r\n+OK\r\n-ERR bad  thing\r\n:-2\r\n$-1\r\n$4\r\na\r\nb\r\n".to_vec()
+        );
+    }
+}
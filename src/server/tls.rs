@@ -0,0 +1,117 @@
+//! OBLIVION - TLS Termination
+//! Server-side TLS for the network frontends (feature `tls`), on rustls
+//! with the `ring` crypto provider.
+//!
+//! ## Certificates
+//! `server_tls_cert` holds the PEM certificate chain, leaf first, and
+//! `server_tls_key` its private key in PKCS#8, PKCS#1 or SEC1 PEM. Both
+//! are read once when a server starts; restart it to rotate them.
+//! Clients are not asked for certificates.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::ServerConfig;
+
+use crate::config::Config;
+use crate::error::{OblivionError, Result};
+
+/// Build the TLS configuration `config` asks for, if any.
+pub(crate) fn server_config(config: &Config) -> Result<Option<Arc<ServerConfig>>> {
+    let (Some(cert), Some(key)) = (&config.server_tls_cert, &config.server_tls_key) else {
+        return Ok(None);
+    };
+    let certs = CertificateDer::pem_file_iter(cert)
+        .and_then(|certs| certs.collect::<std::result::Result<Vec<_>, _>>())
+        .map_err(|e| pem_error(cert, e))?;
+    if certs.is_empty() {
+        return Err(OblivionError::Config(format!(
+            "no certificates in {:?}",
+            cert
+        )));
+    }
+    let key = PrivateKeyDer::from_pem_file(key).map_err(|e| pem_error(key, e))?;
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let tls = ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .and_then(|builder| builder.with_no_client_auth().with_single_cert(certs, key))
+        .map_err(|e| OblivionError::Config(format!("invalid TLS certificate or key: {}", e)))?;
+    Ok(Some(Arc::new(tls)))
+}
+
+fn pem_error(path: &Path, error: rustls::pki_types::pem::Error) -> OblivionError {
+    OblivionError::Config(format!("cannot read {:?}: {}", path, error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TLS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/tls");
+
+    #[test]
+    fn test_server_config_from_pem_files() {
+        let config = Config::new("/tmp/oblivion");
+        assert!(server_config(&config).unwrap().is_none());
+
+        let config = Config::new("/tmp/oblivion").with_server_tls(
+            format!("{}/server.pem", TLS_DIR),
+            format!("{}/server.key", TLS_DIR),
+        );
+        assert!(server_config(&config).unwrap().is_some());
+
+        // A certificate is not a key
+        let config = Config::new("/tmp/oblivion").with_server_tls(
+            format!("{}/server.pem", TLS_DIR),
+            format!("{}/ca.pem", TLS_DIR),
+        );
+        let err = server_config(&config).unwrap_err();
+        assert!(err.to_string().contains("ca.pem"));
+    }
+
+    #[test]
+    fn test_resp_over_tls() {
+        use crate::engine::Oblivion;
+        use crate::server::RespServer;
+        use rustls::pki_types::ServerName;
+        use std::io::{Read, Write};
+        use std::net::TcpStream;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::new(dir.path()).with_server_tls(
+            format!("{}/server.pem", TLS_DIR),
+            format!("{}/server.key", TLS_DIR),
+        );
+        let engine = Arc::new(Oblivion::open(config).unwrap());
+        let server = RespServer::start(engine, "127.0.0.1:0").unwrap();
+
+        let mut roots = rustls::RootCertStore::empty();
+        let ca = CertificateDer::from_pem_file(format!("{}/ca.pem", TLS_DIR)).unwrap();
+        roots.add(ca).unwrap();
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let client_config = rustls::ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        let name = ServerName::try_from("localhost").unwrap();
+        let connection = rustls::ClientConnection::new(Arc::new(client_config), name).unwrap();
+        let socket = TcpStream::connect(server.local_addr()).unwrap();
+        let mut stream = rustls::StreamOwned::new(connection, socket);
+        stream.write_all(b"SET k v\r\nGET k\r\nQUIT\r\n").unwrap();
+        let mut replies = Vec::new();
+        // The server closes without close_notify after QUIT
+        let _ = stream.read_to_end(&mut replies);
+        assert_eq!(replies, b"+OK\r\n$1\r\nv\r\n+OK\r\n");
+        assert_eq!(server.stats().tls_handshake_failures, 0);
+
+        // A plaintext client fails the handshake
+        let mut plain = TcpStream::connect(server.local_addr()).unwrap();
+        plain.write_all(b"PING\r\n").unwrap();
+        let _ = plain.read_to_end(&mut Vec::new());
+        assert_eq!(server.stats().tls_handshake_failures, 1);
+    }
+}
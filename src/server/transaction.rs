@@ -0,0 +1,311 @@
+//! OBLIVION - RESP Transactions
+//! `MULTI` / `EXEC` blocks run as one engine
+//! [`Transaction`](crate::engine::transaction::Transaction).
+//!
+//! ## Queued Commands
+//! Between `MULTI` and `EXEC` a connection queues `SET` (with `EX` or
+//! `PX`), `MSET`, `DEL`, `GET` and `EXISTS`; each is answered `QUEUED`.
+//! Other commands are refused, and a refused or malformed command makes
+//! `EXEC` fail with `EXECABORT`, as in Redis. `DISCARD` drops the queue.
+//!
+//! ## Execution
+//! `EXEC` runs the queued commands in a transaction over a snapshot
+//! taken as it starts, and commits their writes as one batch: one WAL
+//! append and one sync, and other readers see all of its writes at
+//! once. Reads in the block see its own writes queued before them
+//! layered over that snapshot: `GET` after `SET` in the same block
+//! returns the new value, and after `DEL` nil, although neither is
+//! applied until the batch is. `DEL` likewise counts, and only deletes,
+//! the keys that exist at that point of the block.
+//!
+//! Every read of the block comes from the one snapshot, and the commit
+//! fails if another client changed a key the block read in the
+//! meantime. `EXEC` then runs the block again on a new snapshot, so it
+//! replies as if no other write had interleaved with it, as in Redis;
+//! after repeated conflicts it gives up with an error and writes
+//! nothing.
+
+use super::commands::{engine_error, parse_set, wrong_arity};
+use super::resp::Reply;
+use crate::engine::database::Database;
+use crate::error::OblivionError;
+use crate::types::{Key, Value};
+
+/// A command queued by `MULTI`.
+enum Queued {
+    Set {
+        key: Key,
+        value: Value,
+        ttl_ms: Option<u64>,
+    },
+    MSet(Vec<(Key, Value)>),
+    Del(Vec<Key>),
+    Get(Key),
+    Exists(Vec<Key>),
+}
+
+/// A connection's open `MULTI` block, if any.
+#[derive(Default)]
+pub(crate) struct Transaction {
+    /// Writes queued since `MULTI`; `None` outside a block.
+    queued: Option<Vec<Queued>>,
+    /// Whether a command was refused since `MULTI`.
+    failed: bool,
+}
+
+impl Transaction {
+    /// Handle `MULTI`, `EXEC`, `DISCARD`, and commands inside a block.
+    /// Returns `None` for a command that runs normally.
+    pub(crate) fn execute(&mut self, db: &Database, args: &[Vec<u8>]) -> Option<Reply> {
+        let name = String::from_utf8_lossy(&args[0]).to_ascii_lowercase();
+        let reply = match (name.as_str(), &mut self.queued) {
+            ("multi", Some(_)) => Reply::error("ERR MULTI calls can not be nested"),
+            ("multi", None) => {
+                self.queued = Some(Vec::new());
+                self.failed = false;
+                Reply::ok()
+            }
+            ("exec", None) => Reply::error("ERR EXEC without MULTI"),
+            ("discard", None) => Reply::error("ERR DISCARD without MULTI"),
+            ("exec", Some(_)) => {
+                let queued = self.queued.take().unwrap_or_default();
+                if self.failed {
+                    Reply::error("EXECABORT Transaction discarded because of previous errors.")
+                } else {
+                    exec(db, queued)
+                }
+            }
+            ("discard", Some(_)) => {
+                self.queued = None;
+                Reply::ok()
+            }
+            (_, None) => return None,
+            (_, Some(queued)) => match parse(&name, &args[1..]) {
+                Ok(write) => {
+                    queued.push(write);
+                    Reply::Simple("QUEUED".to_string())
+                }
+                Err(reply) => {
+                    self.failed = true;
+                    reply
+                }
+            },
+        };
+        Some(reply)
+    }
+}
+
+/// Check a command queued inside `MULTI`.
+fn parse(name: &str, args: &[Vec<u8>]) -> Result<Queued, Reply> {
+    match name {
+        "set" => {
+            let (key, value, ttl_ms) = parse_set(args)?;
+            Ok(Queued::Set { key, value, ttl_ms })
+        }
+        "mset" if !args.is_empty() && args.len().is_multiple_of(2) => Ok(Queued::MSet(
+            args.chunks(2)
+                .map(|pair| (pair[0].clone(), pair[1].clone()))
+                .collect(),
+        )),
+        "del" if !args.is_empty() => Ok(Queued::Del(args.to_vec())),
+        "get" => match args {
+            [key] => Ok(Queued::Get(key.clone())),
+            _ => Err(wrong_arity(name)),
+        },
+        "exists" if !args.is_empty() => Ok(Queued::Exists(args.to_vec())),
+        "mset" | "del" | "exists" => Err(wrong_arity(name)),
+        _ => Err(Reply::error(format!(
+            "ERR '{}' cannot be queued: MULTI blocks hold only SET, MSET, DEL, GET and EXISTS",
+            name
+        ))),
+    }
+}
+
+/// Attempts at a block whose commit conflicts with concurrent writes
+/// before `EXEC` gives up.
+const EXEC_ATTEMPTS: usize = 16;
+
+/// Run the queued commands in one engine transaction and reply to each,
+/// running them again if a concurrent write invalidated their reads.
+fn exec(db: &Database, queued: Vec<Queued>) -> Reply {
+    for _ in 0..EXEC_ATTEMPTS {
+        match run_block(db, &queued) {
+            Ok(replies) => return Reply::Array(replies),
+            Err(OblivionError::TransactionConflict(_)) => continue,
+            Err(e) => return engine_error(e),
+        }
+    }
+    Reply::error("ERR EXEC kept conflicting with concurrent writes; try again")
+}
+
+/// Run the queued commands once, committing their writes if no key the
+/// block read has changed since its snapshot.
+fn run_block(db: &Database, queued: &[Queued]) -> crate::error::Result<Vec<Reply>> {
+    let mut txn = db.transaction();
+    let mut replies = Vec::with_capacity(queued.len());
+    for command in queued {
+        let reply = match command {
+            Queued::Set { key, value, ttl_ms } => {
+                match ttl_ms {
+                    Some(ttl_ms) => txn.put_with_ttl(key, value.clone(), *ttl_ms)?,
+                    None => txn.put(key, value.clone())?,
+                }
+                Reply::ok()
+            }
+            Queued::MSet(pairs) => {
+                for (key, value) in pairs {
+                    txn.put(key, value.clone())?;
+                }
+                Reply::ok()
+            }
+            Queued::Del(keys) => {
+                let mut deleted = 0;
+                for key in keys {
+                    if txn.exists(key)? {
+                        txn.delete(key)?;
+                        deleted += 1;
+                    }
+                }
+                Reply::Integer(deleted)
+            }
+            Queued::Get(key) => Reply::Bulk(txn.get(key)?),
+            Queued::Exists(keys) => {
+                let mut found = 0;
+                for key in keys {
+                    if txn.exists(key)? {
+                        found += 1;
+                    }
+                }
+                Reply::Integer(found)
+            }
+        };
+        replies.push(reply);
+    }
+    txn.commit()?;
+    Ok(replies)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::engine::Oblivion;
+
+    fn run(engine: &Oblivion, tx: &mut Transaction, words: &str) -> Option<Reply> {
+        let args: Vec<Vec<u8>> = words.split(' ').map(|w| w.as_bytes().to_vec()).collect();
+        tx.execute(&engine.database(0).unwrap(), &args)
+    }
+
+    #[test]
+    fn test_multi_exec_applies_one_batch() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = Oblivion::open(Config::new(dir.path())).unwrap();
+        engine.put(b"old".to_vec(), b"v".to_vec()).unwrap();
+        let mut tx = Transaction::default();
+        assert!(run(&engine, &mut tx, "SET a 1").is_none());
+
+        assert_eq!(run(&engine, &mut tx, "MULTI"), Some(Reply::ok()));
+        let queued = Some(Reply::Simple("QUEUED".to_string()));
+        assert_eq!(run(&engine, &mut tx, "SET a 1 EX 60"), queued);
+        assert_eq!(run(&engine, &mut tx, "MSET b 2 c 3"), queued);
+        assert_eq!(run(&engine, &mut tx, "DEL a old missing"), queued);
+        assert_eq!(engine.get(b"b"), None);
+        let sequence = engine.latest_sequence();
+        assert_eq!(
+            run(&engine, &mut tx, "EXEC"),
+            Some(Reply::Array(vec![
+                Reply::ok(),
+                Reply::ok(),
+                Reply::Integer(2)
+            ]))
+        );
+        assert_eq!(engine.latest_sequence(), sequence + 5);
+        assert_eq!(engine.get(b"a"), None);
+        assert_eq!(engine.get(b"c"), Some(b"3".to_vec()));
+        assert_eq!(engine.get(b"old"), None);
+
+        // A refused command aborts the whole block
+        run(&engine, &mut tx, "MULTI");
+        run(&engine, &mut tx, "SET d 4");
+        assert!(matches!(
+            run(&engine, &mut tx, "APPEND d 5"),
+            Some(Reply::Error(_))
+        ));
+        let reply = run(&engine, &mut tx, "EXEC");
+        assert!(matches!(reply, Some(Reply::Error(e)) if e.starts_with("EXECABORT")));
+        assert_eq!(engine.get(b"d"), None);
+        assert!(matches!(
+            run(&engine, &mut tx, "EXEC"),
+            Some(Reply::Error(_))
+        ));
+    }
+
+    #[test]
+    fn test_reads_in_block_see_its_own_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = Oblivion::open(Config::new(dir.path())).unwrap();
+        engine.put(b"a".to_vec(), b"old".to_vec()).unwrap();
+        engine.put(b"b".to_vec(), b"kept".to_vec()).unwrap();
+        let mut tx = Transaction::default();
+
+        run(&engine, &mut tx, "MULTI");
+        for command in [
+            "GET a",
+            "SET a new",
+            "GET a",
+            "GET b",
+            "DEL b",
+            "EXISTS a b c",
+            "GET b",
+            "MSET c 1",
+            "EXISTS c",
+        ] {
+            run(&engine, &mut tx, command);
+        }
+        assert_eq!(engine.get(b"a"), Some(b"old".to_vec()));
+        let bulk = |value: &str| Reply::Bulk(Some(value.as_bytes().to_vec()));
+        assert_eq!(
+            run(&engine, &mut tx, "EXEC"),
+            Some(Reply::Array(vec![
+                bulk("old"),
+                Reply::ok(),
+                bulk("new"),
+                bulk("kept"),
+                Reply::Integer(1),
+                Reply::Integer(1),
+                Reply::Bulk(None),
+                Reply::ok(),
+                Reply::Integer(1),
+            ]))
+        );
+        assert_eq!(engine.get(b"a"), Some(b"new".to_vec()));
+        assert_eq!(engine.get(b"b"), None);
+    }
+
+    #[test]
+    fn test_exec_reads_writes_made_before_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = Oblivion::open(Config::new(dir.path())).unwrap();
+        engine.put(b"a".to_vec(), b"old".to_vec()).unwrap();
+        let mut tx = Transaction::default();
+
+        run(&engine, &mut tx, "MULTI");
+        for command in ["GET a", "DEL a b", "EXISTS a"] {
+            run(&engine, &mut tx, command);
+        }
+        // Another client writes after the commands were queued
+        let other = engine.database(0).unwrap();
+        other.put(b"a", b"fresh".to_vec()).unwrap();
+        other.put(b"b", b"new".to_vec()).unwrap();
+        assert_eq!(
+            run(&engine, &mut tx, "EXEC"),
+            Some(Reply::Array(vec![
+                Reply::Bulk(Some(b"fresh".to_vec())),
+                Reply::Integer(2),
+                Reply::Integer(0),
+            ]))
+        );
+        assert_eq!(engine.get(b"a"), None);
+        assert_eq!(engine.get(b"b"), None);
+    }
+}
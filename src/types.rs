@@ -9,6 +9,16 @@ pub type Key = Vec<u8>;
 /// Using Vec<u8> allows arbitrary binary values.
 pub type Value = Vec<u8>;
 
+/// What is known about a stored value without reading it; see
+/// [`Oblivion::value_info`](crate::engine::Oblivion::value_info).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValueInfo {
+    /// Length of the value in bytes.
+    pub len: usize,
+    /// Remaining time to live in milliseconds, if the key has a TTL.
+    pub ttl_ms: Option<u64>,
+}
+
 /// Represents a single entry in the storage engine.
 /// A `None` value indicates a tombstone (deletion marker).
 #[derive(Debug, Clone)]
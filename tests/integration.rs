@@ -8,11 +8,11 @@ mod common {
 
     /// Create a Config pointing to a temporary directory.
     pub fn temp_config(dir: &std::path::Path) -> oblivion::config::Config {
-        oblivion::config::Config {
-            data_dir: dir.to_path_buf(),
-            memtable_max_size: 1024, // 1KB threshold for easy flush testing
-            sync_writes: true,
-        }
+        oblivion::config::Config::builder(dir)
+            .memtable_max_size(1024) // 1KB threshold for easy flush testing
+            .sync_writes(true)
+            .build()
+            .unwrap()
     }
 }
 
@@ -21,7 +21,7 @@ fn test_basic_put_get_delete() {
     let dir = tempfile::tempdir().unwrap();
     let config = common::temp_config(dir.path());
 
-    let mut engine = oblivion::engine::Oblivion::open(config).unwrap();
+    let engine = oblivion::engine::Oblivion::open(config).unwrap();
 
     // Put
     engine.put(b"name".to_vec(), b"oblivion".to_vec()).unwrap();
@@ -44,7 +44,7 @@ fn test_basic_put_get_delete() {
 fn test_overwrite_value() {
     let dir = tempfile::tempdir().unwrap();
     let config = common::temp_config(dir.path());
-    let mut engine = oblivion::engine::Oblivion::open(config).unwrap();
+    let engine = oblivion::engine::Oblivion::open(config).unwrap();
 
     engine.put(b"key".to_vec(), b"old".to_vec()).unwrap();
     assert_eq!(engine.get(b"key"), Some(b"old".to_vec()));
@@ -59,7 +59,7 @@ fn test_overwrite_value() {
 fn test_scan_sorted_order() {
     let dir = tempfile::tempdir().unwrap();
     let config = common::temp_config(dir.path());
-    let mut engine = oblivion::engine::Oblivion::open(config).unwrap();
+    let engine = oblivion::engine::Oblivion::open(config).unwrap();
 
     engine.put(b"charlie".to_vec(), b"3".to_vec()).unwrap();
     engine.put(b"alpha".to_vec(), b"1".to_vec()).unwrap();
@@ -72,6 +72,43 @@ fn test_scan_sorted_order() {
     assert_eq!(entries[2].0, b"charlie");
 }
 
+#[test]
+fn test_keys_glob_pattern() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = common::temp_config(dir.path());
+    let engine = oblivion::engine::Oblivion::open(config).unwrap();
+
+    for i in 0..40 {
+        let user = format!("user:{:02}", i);
+        engine
+            .put(format!("{}:settings", user).into_bytes(), b"{}".to_vec())
+            .unwrap();
+        engine
+            .put(format!("{}:name", user).into_bytes(), vec![b'n'; 16])
+            .unwrap();
+    }
+    engine.put(b"session:1".to_vec(), b"s".to_vec()).unwrap();
+    engine.delete(b"user:07:settings".to_vec()).unwrap();
+    assert!(engine.sstable_count() > 0);
+
+    let keys = engine.keys(b"user:*:settings").unwrap();
+    assert_eq!(keys.len(), 39);
+    assert_eq!(keys[0], b"user:00:settings");
+    assert!(!keys.contains(&b"user:07:settings".to_vec()));
+    assert_eq!(
+        engine.keys(b"user:1[0-2]:name").unwrap(),
+        vec![
+            b"user:10:name".to_vec(),
+            b"user:11:name".to_vec(),
+            b"user:12:name".to_vec()
+        ]
+    );
+    assert_eq!(engine.keys(b"*").unwrap().len(), 80);
+
+    let opts = oblivion::engine::options::ReadOptions::new().upper_bound(b"user:02".to_vec());
+    assert_eq!(engine.keys_opt(b"user:*:name", &opts).unwrap().len(), 2);
+}
+
 #[test]
 fn test_crash_recovery() {
     let dir = tempfile::tempdir().unwrap();
@@ -79,12 +116,12 @@ fn test_crash_recovery() {
 
     // Phase 1: Write data and drop engine (simulates crash)
     {
-        let config = oblivion::config::Config {
-            data_dir: data_path.clone(),
-            memtable_max_size: 64 * 1024, // large threshold, no flush
-            sync_writes: true,
-        };
-        let mut engine = oblivion::engine::Oblivion::open(config).unwrap();
+        let config = oblivion::config::Config::builder(data_path.clone())
+            .memtable_max_size(64 * 1024) // large threshold, no flush
+            .sync_writes(true)
+            .build()
+            .unwrap();
+        let engine = oblivion::engine::Oblivion::open(config).unwrap();
 
         engine
             .put(b"persistent_key".to_vec(), b"persistent_value".to_vec())
@@ -97,11 +134,11 @@ fn test_crash_recovery() {
 
     // Phase 2: Reopen and verify WAL recovery
     {
-        let config = oblivion::config::Config {
-            data_dir: data_path,
-            memtable_max_size: 64 * 1024,
-            sync_writes: true,
-        };
+        let config = oblivion::config::Config::builder(data_path)
+            .memtable_max_size(64 * 1024)
+            .sync_writes(true)
+            .build()
+            .unwrap();
         let engine = oblivion::engine::Oblivion::open(config).unwrap();
 
         // Persistent key should be recovered
@@ -131,12 +168,12 @@ fn test_empty_engine() {
 #[test]
 fn test_large_values() {
     let dir = tempfile::tempdir().unwrap();
-    let config = oblivion::config::Config {
-        data_dir: dir.path().to_path_buf(),
-        memtable_max_size: 1024 * 1024, // 1MB
-        sync_writes: true,
-    };
-    let mut engine = oblivion::engine::Oblivion::open(config).unwrap();
+    let config = oblivion::config::Config::builder(dir.path())
+        .memtable_max_size(1024 * 1024) // 1MB
+        .sync_writes(true)
+        .build()
+        .unwrap();
+    let engine = oblivion::engine::Oblivion::open(config).unwrap();
 
     // Write a 10KB value
     let large_value = vec![0xABu8; 10_000];
@@ -149,7 +186,7 @@ fn test_large_values() {
 fn test_unicode_keys() {
     let dir = tempfile::tempdir().unwrap();
     let config = common::temp_config(dir.path());
-    let mut engine = oblivion::engine::Oblivion::open(config).unwrap();
+    let engine = oblivion::engine::Oblivion::open(config).unwrap();
 
     engine
         .put("café".as_bytes().to_vec(), b"coffee".to_vec())
@@ -170,12 +207,12 @@ fn test_unicode_keys() {
 fn test_many_writes_trigger_info() {
     let dir = tempfile::tempdir().unwrap();
     // Use larger threshold to prevent flush during test
-    let config = oblivion::config::Config {
-        data_dir: dir.path().to_path_buf(),
-        memtable_max_size: 64 * 1024, // 64KB - enough for 100 writes
-        sync_writes: true,
-    };
-    let mut engine = oblivion::engine::Oblivion::open(config).unwrap();
+    let config = oblivion::config::Config::builder(dir.path())
+        .memtable_max_size(64 * 1024) // 64KB - enough for 100 writes
+        .sync_writes(true)
+        .build()
+        .unwrap();
+    let engine = oblivion::engine::Oblivion::open(config).unwrap();
 
     for i in 0..100 {
         let key = format!("key_{:04}", i).into_bytes();
@@ -188,3 +225,3472 @@ fn test_many_writes_trigger_info() {
     assert_eq!(engine.get(b"key_0050"), Some(b"value_0050".to_vec()));
     assert_eq!(engine.get(b"key_0099"), Some(b"value_0099".to_vec()));
 }
+
+#[test]
+fn test_reads_survive_flush_and_reopen() {
+    let dir = tempfile::tempdir().unwrap();
+
+    {
+        let engine = oblivion::engine::Oblivion::open(common::temp_config(dir.path())).unwrap();
+        for i in 0..200 {
+            let key = format!("key_{:04}", i).into_bytes();
+            let value = format!("value_{:04}", i).into_bytes();
+            engine.put(key, value).unwrap();
+        }
+        // Delete a key that has already been flushed to an SSTable
+        engine.delete(b"key_0000".to_vec()).unwrap();
+
+        assert!(engine.sstable_count() > 0);
+        assert_eq!(engine.get(b"key_0000"), None);
+        assert_eq!(engine.get(b"key_0001"), Some(b"value_0001".to_vec()));
+        assert_eq!(engine.scan().len(), 199);
+    }
+
+    let engine = oblivion::engine::Oblivion::open(common::temp_config(dir.path())).unwrap();
+    assert!(engine.sstable_count() > 0);
+    assert_eq!(engine.get(b"key_0000"), None);
+    assert_eq!(engine.get(b"key_0150"), Some(b"value_0150".to_vec()));
+    assert_eq!(engine.get(b"key_0199"), Some(b"value_0199".to_vec()));
+    assert_eq!(engine.scan().len(), 199);
+}
+
+#[test]
+fn test_compaction_with_per_tier_filters() {
+    use oblivion::engine::filter::{FilterSizing, FilterType};
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = common::temp_config(dir.path())
+        .with_filter_per_tier(vec![FilterType::Bloom, FilterType::Ribbon])
+        .with_filter_sizing_per_tier(vec![
+            FilterSizing::BitsPerKey(10.0),
+            FilterSizing::FalsePositiveRate(0.05),
+        ]);
+    let engine = oblivion::engine::Oblivion::open(config).unwrap();
+
+    for i in 0..1000 {
+        let key = format!("key_{:04}", i % 400).into_bytes();
+        let value = format!("value_{:04}", i).into_bytes();
+        engine.put(key, value).unwrap();
+    }
+
+    // Size-tiered compaction keeps the table count bounded
+    assert!(engine.sstable_count() < 10, "{}", engine.sstable_count());
+    assert_eq!(engine.get(b"key_0399"), Some(b"value_0799".to_vec()));
+    assert_eq!(engine.get(b"key_0000"), Some(b"value_0800".to_vec()));
+    assert_eq!(engine.scan().len(), 400);
+
+    // Compacted (deeper tier) tables use the Ribbon policy
+    let table_cache = std::sync::Arc::new(oblivion::engine::cache::TableCache::new(16));
+    let policies: Vec<String> = std::fs::read_dir(dir.path().join("sst"))
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "sst"))
+        .map(|p| {
+            let table = oblivion::engine::sstable::SSTable::open(p, &table_cache).unwrap();
+            table.filter_policy_name().unwrap().to_string()
+        })
+        .collect();
+    assert!(policies.iter().any(|p| p == "oblivion.RibbonFilter"));
+}
+
+#[test]
+fn test_filter_metrics_on_lookups() {
+    use std::sync::atomic::Ordering;
+
+    let dir = tempfile::tempdir().unwrap();
+    let engine = oblivion::engine::Oblivion::open(common::temp_config(dir.path())).unwrap();
+    // Written out of order, so every table spans most of the key space
+    // and misses within it reach the filters
+    for i in 0..100 {
+        let key = format!("key_{:04}", i * 37 % 100).into_bytes();
+        engine.put(key, b"value".to_vec()).unwrap();
+    }
+    engine.flush().unwrap();
+    assert!(engine.sstable_count() > 0);
+
+    for i in 0..99 {
+        engine.get(format!("key_{:04}x", i).as_bytes());
+    }
+    engine.get(b"key_0000");
+
+    let metrics = engine.metrics();
+    let probes = metrics.filter_probes.load(Ordering::Relaxed);
+    let negatives = metrics.filter_negatives.load(Ordering::Relaxed);
+    assert!(probes >= 99);
+    // The vast majority of misses never touch the table
+    assert!(
+        negatives >= 90,
+        "only {} of {} probes skipped",
+        negatives,
+        probes
+    );
+}
+
+#[test]
+fn test_row_cache_invalidated_on_writes() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = common::temp_config(dir.path()).with_row_cache_capacity(64 * 1024);
+    let engine = oblivion::engine::Oblivion::open(config).unwrap();
+    engine.put(b"hot".to_vec(), b"v1".to_vec()).unwrap();
+    for i in 0..100 {
+        let key = format!("key_{:04}", i).into_bytes();
+        engine.put(key, b"value".to_vec()).unwrap();
+    }
+    assert!(engine.sstable_count() > 0);
+
+    // First read comes from an SSTable and populates the cache
+    assert_eq!(engine.get(b"hot"), Some(b"v1".to_vec()));
+    let cache = engine.row_cache().unwrap();
+    assert!(cache.get(b"hot").is_some());
+    assert_eq!(engine.get(b"hot"), Some(b"v1".to_vec()));
+
+    engine.put(b"hot".to_vec(), b"v2".to_vec()).unwrap();
+    assert!(engine.row_cache().unwrap().get(b"hot").is_none());
+    assert_eq!(engine.get(b"hot"), Some(b"v2".to_vec()));
+
+    engine.delete(b"hot".to_vec()).unwrap();
+    assert_eq!(engine.get(b"hot"), None);
+}
+
+#[test]
+fn test_max_open_files_bounds_table_handles() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = common::temp_config(dir.path()).with_max_open_files(2);
+    config.compaction_threshold = 100;
+    let engine = oblivion::engine::Oblivion::open(config.clone()).unwrap();
+    for i in 0..400 {
+        let key = format!("key_{:04}", i).into_bytes();
+        engine
+            .put(key, format!("value_{}", i).into_bytes())
+            .unwrap();
+    }
+    assert!(engine.sstable_count() > 2);
+    assert!(engine.open_table_files() <= 2);
+    drop(engine);
+
+    // Every table stays readable, reopening handles on demand
+    let engine = oblivion::engine::Oblivion::open(config).unwrap();
+    assert!(engine.open_table_files() <= 2);
+    for i in (0..400).step_by(7) {
+        let key = format!("key_{:04}", i);
+        assert_eq!(
+            engine.get(key.as_bytes()),
+            Some(format!("value_{}", i).into_bytes()),
+            "{}",
+            key
+        );
+    }
+    assert!(engine.open_table_files() <= 2);
+}
+
+#[test]
+fn test_cache_metrics_and_preload_on_open() {
+    use std::sync::atomic::Ordering;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = common::temp_config(dir.path())
+        .with_row_cache_capacity(64 * 1024)
+        .with_preload_index_and_filter(true);
+    let engine = oblivion::engine::Oblivion::open(config.clone()).unwrap();
+    for i in 0..100 {
+        let key = format!("key_{:04}", i).into_bytes();
+        engine.put(key, b"value".to_vec()).unwrap();
+    }
+    drop(engine);
+
+    let engine = oblivion::engine::Oblivion::open(config).unwrap();
+    let metrics = engine.metrics();
+    // Opening (and preloading) went through the table cache
+    let opened = metrics.table_cache_misses.load(Ordering::Relaxed);
+    assert!(opened as usize >= engine.sstable_count());
+
+    for _ in 0..4 {
+        assert_eq!(engine.get(b"key_0000"), Some(b"value".to_vec()));
+    }
+    assert_eq!(metrics.row_cache_misses.load(Ordering::Relaxed), 1);
+    assert_eq!(metrics.row_cache_hits.load(Ordering::Relaxed), 3);
+    assert_eq!(metrics.row_cache_hit_rate(), 0.75);
+    assert!(metrics.table_cache_hits.load(Ordering::Relaxed) > 0);
+}
+
+#[test]
+fn test_block_cache_serves_repeated_reads() {
+    use oblivion::engine::options::ReadOptions;
+    use std::sync::atomic::Ordering;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = common::temp_config(dir.path()).with_block_cache_capacity(1024 * 1024);
+    let engine = oblivion::engine::Oblivion::open(config.clone()).unwrap();
+    for i in 0..100 {
+        let key = format!("key_{:04}", i).into_bytes();
+        engine.put(key, b"value".to_vec()).unwrap();
+    }
+    drop(engine);
+
+    let engine = oblivion::engine::Oblivion::open(config).unwrap();
+    let metrics = engine.metrics();
+    let cache = engine.block_cache().unwrap();
+    let hits = || metrics.block_cache_hits.load(Ordering::Relaxed);
+    let misses = || metrics.block_cache_misses.load(Ordering::Relaxed);
+    let (hits_before, misses_before) = (hits(), misses());
+
+    // Reads that must not fill the cache look blocks up but add none
+    let no_fill = ReadOptions::new().fill_cache(false);
+    for _ in 0..2 {
+        let value = engine.get_opt(b"key_0000", &no_fill).unwrap();
+        assert_eq!(value, Some(b"value".to_vec()));
+    }
+    assert!(cache.is_empty());
+    assert_eq!(misses() - misses_before, 2);
+
+    for _ in 0..4 {
+        assert_eq!(engine.get(b"key_0000"), Some(b"value".to_vec()));
+    }
+    assert_eq!(cache.len(), 1);
+    assert_eq!(misses() - misses_before, 3);
+    assert_eq!(hits() - hits_before, 3);
+    assert!(metrics.block_cache_hit_rate() > 0.0);
+    assert!(metrics.report().contains("block:"));
+
+    // Pinned reads borrow the cached block
+    let pinned = engine.get_pinned(b"key_0050").unwrap();
+    assert_eq!(&*pinned, b"value");
+    assert_eq!(hits() - hits_before, 4);
+    drop(pinned);
+
+    engine.set_options([("block_cache_capacity", "0")]).unwrap();
+    assert!(engine.block_cache().is_none());
+    assert_eq!(engine.get(b"key_0000"), Some(b"value".to_vec()));
+    assert_eq!(hits() - hits_before, 4);
+}
+
+#[test]
+fn test_open_rejects_invalid_config() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = common::temp_config(dir.path());
+    config.compaction_threshold = 1;
+
+    let err = oblivion::engine::Oblivion::open(config).err().unwrap();
+    assert!(matches!(err, oblivion::error::OblivionError::Config(_)));
+    assert!(err.to_string().contains("compaction_threshold"));
+}
+
+#[test]
+fn test_set_options_on_live_engine() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = oblivion::config::Config::builder(dir.path())
+        .memtable_max_size(64 * 1024)
+        .build()
+        .unwrap();
+    let engine = oblivion::engine::Oblivion::open(config).unwrap();
+    for i in 0..50 {
+        let key = format!("key_{:04}", i).into_bytes();
+        engine.put(key, b"value".to_vec()).unwrap();
+    }
+    assert_eq!(engine.sstable_count(), 0);
+
+    // Invalid updates are rejected without changing anything
+    assert!(engine
+        .set_options([("memtable_max_size", "512"), ("compaction_threshold", "1")])
+        .is_err());
+    assert_eq!(engine.config().memtable_max_size, 64 * 1024);
+
+    // Lowering the flush threshold flushes right away
+    let mut options = std::collections::HashMap::new();
+    options.insert("memtable_max_size".to_string(), "512".to_string());
+    options.insert("sync_writes".to_string(), "false".to_string());
+    engine.set_options(&options).unwrap();
+    assert_eq!(engine.config().memtable_max_size, 512);
+    assert!(!engine.config().sync_writes);
+    assert_eq!(engine.sstable_count(), 1);
+    assert_eq!(engine.get(b"key_0042"), Some(b"value".to_vec()));
+}
+
+#[test]
+fn test_read_options_snapshot_and_bounds() {
+    use oblivion::engine::options::ReadOptions;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = common::temp_config(dir.path()).with_row_cache_capacity(64 * 1024);
+    let engine = oblivion::engine::Oblivion::open(config).unwrap();
+    for i in 0..100 {
+        let key = format!("key_{:04}", i).into_bytes();
+        engine.put(key, b"old".to_vec()).unwrap();
+    }
+
+    let snapshot = engine.snapshot();
+    assert_eq!(engine.live_snapshots(), 1);
+
+    // Overwrite, delete and flush after the snapshot was taken
+    for i in 0..100 {
+        let key = format!("key_{:04}", i).into_bytes();
+        engine.put(key, b"new".to_vec()).unwrap();
+    }
+    engine.delete(b"key_0007".to_vec()).unwrap();
+
+    let at_snapshot = ReadOptions::new().snapshot(snapshot.clone());
+    assert_eq!(
+        engine.get_opt(b"key_0007", &at_snapshot).unwrap(),
+        Some(b"old".to_vec())
+    );
+    assert_eq!(engine.get(b"key_0007"), None);
+    assert_eq!(engine.get(b"key_0050"), Some(b"new".to_vec()));
+
+    let bounded = at_snapshot
+        .lower_bound(b"key_0010".to_vec())
+        .upper_bound(b"key_0020".to_vec());
+    let rows = engine.scan_opt(&bounded).unwrap();
+    assert_eq!(rows.len(), 10);
+    assert!(rows.iter().all(|(_, v)| v == b"old"));
+
+    drop(bounded);
+    drop(snapshot);
+    assert_eq!(engine.live_snapshots(), 0);
+
+    // Reads with fill_cache disabled leave the row cache untouched
+    let no_fill = ReadOptions::new().fill_cache(false);
+    engine.get_opt(b"key_0001", &no_fill).unwrap();
+    assert!(engine.row_cache().unwrap().get(b"key_0001").is_none());
+    engine.get(b"key_0001");
+    assert!(engine.row_cache().unwrap().get(b"key_0001").is_some());
+}
+
+#[test]
+fn test_oversized_writes_rejected() {
+    use oblivion::error::OblivionError;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = oblivion::config::Config::builder(dir.path())
+        .max_key_size(16)
+        .max_value_size(1024)
+        .build()
+        .unwrap();
+    let engine = oblivion::engine::Oblivion::open(config.clone()).unwrap();
+
+    let err = engine.put(vec![b'k'; 17], b"v".to_vec()).unwrap_err();
+    assert!(matches!(
+        err,
+        OblivionError::KeyTooLarge {
+            size: 17,
+            limit: 16
+        }
+    ));
+    let err = engine
+        .put_with_ttl(b"key".to_vec(), vec![0u8; 1025], 1000)
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        OblivionError::ValueTooLarge { size: 1025, .. }
+    ));
+    assert_eq!(engine.ttl(b"key"), None);
+
+    engine.put(b"key".to_vec(), vec![0u8; 1024]).unwrap();
+    drop(engine);
+
+    // Rejected writes never reached the WAL
+    let engine = oblivion::engine::Oblivion::open(config).unwrap();
+    assert_eq!(engine.len(), 1);
+}
+
+#[test]
+fn test_structured_layout_and_flat_migration() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = common::temp_config(dir.path());
+    let engine = oblivion::engine::Oblivion::open(config.clone()).unwrap();
+    for i in 0..100 {
+        let key = format!("key_{:04}", i).into_bytes();
+        engine
+            .put(key, format!("value_{}", i).into_bytes())
+            .unwrap();
+    }
+    drop(engine);
+
+    assert!(config.wal_path().exists());
+    assert!(config.manifest_path().exists());
+    let tables: Vec<_> = std::fs::read_dir(config.sst_dir())
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .collect();
+    assert!(!tables.is_empty());
+
+    // Recreate the old flat layout: everything directly in data_dir
+    for table in &tables {
+        std::fs::rename(table, dir.path().join(table.file_name().unwrap())).unwrap();
+    }
+    std::fs::rename(config.wal_path(), dir.path().join("oblivion.wal")).unwrap();
+    std::fs::remove_file(config.manifest_path()).unwrap();
+    std::fs::remove_dir(config.sst_dir()).unwrap();
+    std::fs::remove_dir(config.wal_dir()).unwrap();
+
+    let engine = oblivion::engine::Oblivion::open(config.clone()).unwrap();
+    assert_eq!(engine.sstable_count(), tables.len());
+    for i in 0..100 {
+        let key = format!("key_{:04}", i);
+        assert_eq!(
+            engine.get(key.as_bytes()),
+            Some(format!("value_{}", i).into_bytes())
+        );
+    }
+    drop(engine);
+
+    // Tables the manifest does not list are leftovers and get removed
+    let stray = config.sst_dir().join("sstable_999999.sst");
+    std::fs::copy(
+        config.sst_dir().join(tables[0].file_name().unwrap()),
+        &stray,
+    )
+    .unwrap();
+    let engine = oblivion::engine::Oblivion::open(config).unwrap();
+    assert!(!stray.exists());
+    assert_eq!(engine.sstable_count(), tables.len());
+}
+
+#[test]
+fn test_create_if_missing_and_error_if_exists() {
+    use oblivion::error::OblivionError;
+
+    let dir = tempfile::tempdir().unwrap();
+    let missing = dir.path().join("typo");
+    let strict = oblivion::config::Config::builder(&missing)
+        .create_if_missing(false)
+        .build()
+        .unwrap();
+    let err = oblivion::engine::Oblivion::open(strict.clone())
+        .err()
+        .unwrap();
+    assert!(matches!(err, OblivionError::DatabaseNotFound(_)));
+    assert!(!missing.exists());
+
+    // Create it once, then the strict open succeeds
+    let config = common::temp_config(&missing);
+    oblivion::engine::Oblivion::open(config.clone()).unwrap();
+    oblivion::engine::Oblivion::open(strict).unwrap();
+
+    let exclusive = oblivion::config::Config::builder(&missing)
+        .error_if_exists(true)
+        .build()
+        .unwrap();
+    let err = oblivion::engine::Oblivion::open(exclusive).err().unwrap();
+    assert!(matches!(err, OblivionError::DatabaseExists(_)));
+}
+
+#[test]
+fn test_direct_io_and_fdatasync() {
+    use oblivion::engine::io::SyncMethod;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = oblivion::config::Config::builder(dir.path())
+        .memtable_max_size(1024)
+        .compaction_threshold(2)
+        .wal_sync_method(SyncMethod::Fdatasync)
+        .use_direct_io(true)
+        .build()
+        .unwrap();
+
+    {
+        let engine = oblivion::engine::Oblivion::open(config.clone()).unwrap();
+        for i in 0..300 {
+            let key = format!("key_{:04}", i).into_bytes();
+            engine.put(key, vec![b'v'; 50]).unwrap();
+        }
+        engine.delete(b"key_0007".to_vec()).unwrap();
+        assert!(engine.sstable_count() > 0);
+        assert_eq!(engine.get(b"key_0123"), Some(vec![b'v'; 50]));
+    }
+
+    // Tables written with aligned direct I/O read back after reopening
+    let engine = oblivion::engine::Oblivion::open(config).unwrap();
+    assert_eq!(engine.get(b"key_0000"), Some(vec![b'v'; 50]));
+    assert_eq!(engine.get(b"key_0299"), Some(vec![b'v'; 50]));
+    assert_eq!(engine.get(b"key_0007"), None);
+    assert_eq!(engine.scan().len(), 299);
+}
+
+#[test]
+fn test_tree_gauges_and_stats_dump_option() {
+    use std::sync::atomic::Ordering;
+
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = oblivion::config::Config::builder(dir.path())
+        .memtable_max_size(1024)
+        .compaction_threshold(100)
+        .stats_dump_period_secs(60)
+        .build()
+        .unwrap();
+    let engine = oblivion::engine::Oblivion::open(config.clone()).unwrap();
+
+    engine.put(b"a".to_vec(), b"1".to_vec()).unwrap();
+    let metrics = engine.metrics();
+    assert_eq!(
+        metrics.memtable_bytes.load(Ordering::Relaxed),
+        engine.memtable_size() as u64
+    );
+    for i in 0..100 {
+        let key = format!("key_{:04}", i).into_bytes();
+        engine.put(key, vec![b'v'; 50]).unwrap();
+    }
+    assert!(engine.sstable_count() >= 2);
+    assert_eq!(
+        engine
+            .metrics()
+            .pending_compaction_bytes
+            .load(Ordering::Relaxed),
+        0
+    );
+    drop(engine);
+
+    // Opening does not compact, so with a lower threshold tier 0 is pending
+    config.compaction_threshold = 2;
+    let engine = oblivion::engine::Oblivion::open(config).unwrap();
+    let metrics = engine.metrics();
+    assert_eq!(
+        metrics.l0_tables.load(Ordering::Relaxed),
+        engine.sstable_count() as u64
+    );
+    assert!(metrics.pending_compaction_bytes.load(Ordering::Relaxed) > 0);
+    assert!(metrics.report().contains("pending compaction:"));
+
+    // Compaction catches up on the next check
+    engine
+        .set_options([("stats_dump_period_secs", "0")])
+        .unwrap();
+    assert_eq!(
+        engine
+            .metrics()
+            .pending_compaction_bytes
+            .load(Ordering::Relaxed),
+        0
+    );
+}
+
+#[test]
+fn test_disk_usage_reports_inventory() {
+    use std::sync::atomic::Ordering;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = common::temp_config(dir.path());
+    let engine = oblivion::engine::Oblivion::open(config.clone()).unwrap();
+    for i in 0..60 {
+        let key = format!("key_{:04}", i).into_bytes();
+        engine.put(key, vec![b'v'; 50]).unwrap();
+    }
+
+    let usage = engine.disk_usage().unwrap();
+    assert_eq!(usage.live_sstables(), engine.sstable_count() as u64);
+    assert!(usage.sstable_bytes > 0);
+    assert!(usage.wal_bytes > 0);
+    assert!(usage.manifest_bytes > 0);
+    assert_eq!(usage.obsolete_bytes, 0);
+    assert_eq!(
+        engine.metrics().wal_bytes.load(Ordering::Relaxed),
+        usage.wal_bytes
+    );
+    assert_eq!(
+        engine.metrics().sstable_bytes.load(Ordering::Relaxed),
+        usage.sstable_bytes
+    );
+
+    // Files in the SSTable directory that are not live count as obsolete
+    std::fs::write(config.sst_dir().join("leftover.sst.tmp"), [0u8; 100]).unwrap();
+    let usage = engine.disk_usage().unwrap();
+    assert_eq!(usage.obsolete_bytes, 100);
+    assert!(usage.total_bytes() > usage.sstable_bytes + usage.wal_bytes);
+}
+
+#[test]
+fn test_read_amplification_metrics() {
+    use std::sync::atomic::Ordering;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = oblivion::config::Config::builder(dir.path())
+        .memtable_max_size(1024)
+        .compaction_threshold(100)
+        .build()
+        .unwrap();
+    let engine = oblivion::engine::Oblivion::open(config).unwrap();
+    // Written from the middle outwards, so every table's key range
+    // covers the middle keys
+    for i in 0..50 {
+        for key in [49 - i, 50 + i] {
+            let key = format!("key_{:04}", key).into_bytes();
+            engine.put(key, vec![b'v'; 50]).unwrap();
+        }
+    }
+    let tables = engine.sstable_count() as u64;
+    assert!(tables > 2);
+
+    // A middle key sits in the oldest table, so every table is consulted
+    assert!(engine.get(b"key_0049").is_some());
+    let metrics = engine.metrics();
+    assert_eq!(metrics.sstable_lookups.load(Ordering::Relaxed), 1);
+    assert_eq!(metrics.max_sstables_per_get.load(Ordering::Relaxed), tables);
+    assert_eq!(metrics.max_blocks_per_get.load(Ordering::Relaxed), 1);
+    assert_eq!(metrics.avg_sstables_per_get(), tables as f64);
+}
+
+#[test]
+fn test_recovery_metrics_and_progress() {
+    use std::sync::atomic::Ordering;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = oblivion::config::Config::builder(dir.path())
+        .build()
+        .unwrap();
+    {
+        let engine = oblivion::engine::Oblivion::open(config.clone()).unwrap();
+        for i in 0..10 {
+            let key = format!("key_{}", i).into_bytes();
+            engine.put(key, b"value".to_vec()).unwrap();
+        }
+    }
+
+    let mut reports = Vec::new();
+    let engine =
+        oblivion::engine::Oblivion::open_with_progress(config, |p| reports.push(*p)).unwrap();
+    assert_eq!(engine.len(), 10);
+
+    let last = reports.last().unwrap();
+    assert_eq!(last.records_applied, 10);
+    assert_eq!(last.bytes_replayed, last.total_bytes);
+
+    let metrics = engine.metrics();
+    assert_eq!(metrics.recovery_records_applied.load(Ordering::Relaxed), 10);
+    assert_eq!(metrics.recovery_records_skipped.load(Ordering::Relaxed), 0);
+    assert_eq!(
+        metrics.recovery_bytes_replayed.load(Ordering::Relaxed),
+        last.total_bytes
+    );
+}
+
+#[test]
+fn test_background_flush_and_compaction() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = oblivion::config::Config::builder(dir.path())
+        .memtable_max_size(1024)
+        .background_threads(2)
+        .build()
+        .unwrap();
+    {
+        let engine = oblivion::engine::Oblivion::open(config.clone()).unwrap();
+        for i in 0..500 {
+            let key = format!("key_{:04}", i).into_bytes();
+            engine.put(key, vec![b'v'; 40]).unwrap();
+            // Reads see every write while flushes run in the background
+            assert_eq!(
+                engine.get(format!("key_{:04}", i / 2).as_bytes()),
+                Some(vec![b'v'; 40])
+            );
+        }
+        engine.wait_for_background_work().unwrap();
+        assert!(engine.sstable_count() > 0);
+        assert_eq!(engine.background_error(), None);
+        assert_eq!(engine.scan().len(), 500);
+
+        // Flushed segments are deleted
+        let segments = oblivion::engine::wal::WriteAheadLog::segments(&config.wal_dir()).unwrap();
+        assert!(segments.is_empty());
+    }
+
+    let engine = oblivion::engine::Oblivion::open(config).unwrap();
+    assert_eq!(engine.scan().len(), 500);
+    assert_eq!(engine.get(b"key_0499"), Some(vec![b'v'; 40]));
+}
+
+#[test]
+fn test_background_failure_refuses_writes() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = oblivion::config::Config::builder(dir.path())
+        .memtable_max_size(1024)
+        .background_threads(1)
+        .build()
+        .unwrap();
+    let engine = oblivion::engine::Oblivion::open(config.clone()).unwrap();
+
+    // Flushes cannot create their SSTables
+    std::fs::remove_dir_all(config.sst_dir()).unwrap();
+    let mut failed = None;
+    for i in 0..500 {
+        if let Err(e) = engine.put(format!("key_{:04}", i).into_bytes(), vec![b'v'; 40]) {
+            failed = Some(e);
+            break;
+        }
+    }
+    let failed = failed.unwrap_or_else(|| engine.wait_for_background_work().unwrap_err());
+    assert!(matches!(
+        failed,
+        oblivion::error::OblivionError::Background(_)
+    ));
+    assert!(engine.background_error().unwrap().starts_with("flush job"));
+    assert!(engine.put(b"key".to_vec(), b"value".to_vec()).is_err());
+    assert!(
+        engine
+            .metrics()
+            .background_errors
+            .load(std::sync::atomic::Ordering::Relaxed)
+            > 0
+    );
+
+    // Unflushed writes are still in the WAL
+    drop(engine);
+    std::fs::create_dir_all(config.sst_dir()).unwrap();
+    let engine = oblivion::engine::Oblivion::open(config).unwrap();
+    assert_eq!(engine.get(b"key_0000"), Some(vec![b'v'; 40]));
+}
+
+#[test]
+fn test_unflushed_wal_segments_are_recovered() {
+    use oblivion::engine::wal::WriteAheadLog;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = common::temp_config(dir.path());
+    drop(oblivion::engine::Oblivion::open(config.clone()).unwrap());
+
+    // A MemTable frozen before a crash: its segment, then the live log
+    let segment = WriteAheadLog::segment_path(&config.wal_dir(), 3);
+    let mut wal = WriteAheadLog::open(segment.clone()).unwrap();
+    wal.append_put(&b"a".to_vec(), &b"old".to_vec()).unwrap();
+    wal.append_put(&b"b".to_vec(), &b"old".to_vec()).unwrap();
+    drop(wal);
+    let mut wal = WriteAheadLog::open(config.wal_path()).unwrap();
+    wal.append_put(&b"a".to_vec(), &b"new".to_vec()).unwrap();
+    drop(wal);
+
+    let engine = oblivion::engine::Oblivion::open(config.clone()).unwrap();
+    assert_eq!(engine.get(b"a"), Some(b"new".to_vec()));
+    assert_eq!(engine.get(b"b"), Some(b"old".to_vec()));
+
+    // The segment is deleted once its writes reach an SSTable
+    for i in 0..100 {
+        engine
+            .put(format!("key_{:04}", i).into_bytes(), vec![b'v'; 40])
+            .unwrap();
+    }
+    assert!(engine.sstable_count() > 0);
+    assert!(!segment.exists());
+    drop(engine);
+
+    let engine = oblivion::engine::Oblivion::open(config).unwrap();
+    assert_eq!(engine.get(b"a"), Some(b"new".to_vec()));
+    assert_eq!(engine.get(b"b"), Some(b"old".to_vec()));
+}
+
+#[test]
+fn test_io_uring_option() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = oblivion::config::Config::builder(dir.path())
+        .memtable_max_size(1024)
+        .use_io_uring(true)
+        .build();
+    if !cfg!(all(target_os = "linux", feature = "io-uring")) {
+        assert!(config.unwrap_err().to_string().contains("io-uring feature"));
+        return;
+    }
+
+    // Falls back to regular syscalls where io_uring is unavailable
+    let config = config.unwrap();
+    {
+        let engine = oblivion::engine::Oblivion::open(config.clone()).unwrap();
+        for i in 0..200 {
+            let key = format!("key_{:04}", i).into_bytes();
+            engine.put(key, vec![b'v'; 20]).unwrap();
+        }
+        engine.delete(b"key_0007".to_vec()).unwrap();
+        assert!(engine.sstable_count() > 0);
+        assert_eq!(engine.get(b"key_0000"), Some(vec![b'v'; 20]));
+    }
+
+    let engine = oblivion::engine::Oblivion::open(config).unwrap();
+    assert_eq!(engine.scan().len(), 199);
+    assert_eq!(engine.get(b"key_0007"), None);
+    assert_eq!(engine.get(b"key_0199"), Some(vec![b'v'; 20]));
+}
+
+#[test]
+fn test_engine_shared_behind_arc() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = common::temp_config(dir.path());
+    let engine = std::sync::Arc::new(oblivion::engine::Oblivion::open(config.clone()).unwrap());
+
+    let handles: Vec<_> = (0..4)
+        .map(|t| {
+            let engine = std::sync::Arc::clone(&engine);
+            std::thread::spawn(move || {
+                for i in 0..50 {
+                    let key = format!("t{}_key_{:03}", t, i).into_bytes();
+                    engine.put(key, vec![b'v'; 20]).unwrap();
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    assert!(engine.sstable_count() > 0);
+    assert_eq!(engine.scan().len(), 200);
+    drop(engine);
+
+    let engine = oblivion::engine::Oblivion::open(config).unwrap();
+    assert_eq!(engine.scan().len(), 200);
+    assert_eq!(engine.get(b"t3_key_049"), Some(vec![b'v'; 20]));
+}
+
+#[test]
+fn test_data_dir_is_locked_while_open() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = common::temp_config(dir.path());
+    let engine = oblivion::engine::Oblivion::open(config.clone()).unwrap();
+    engine.put(b"k".to_vec(), b"v".to_vec()).unwrap();
+
+    let err = oblivion::engine::Oblivion::open(config.clone())
+        .err()
+        .unwrap();
+    assert!(matches!(err, oblivion::error::OblivionError::Locked(_)));
+    assert!(err.to_string().contains("locked by another instance"));
+    assert!(config.lock_path().exists());
+
+    // Dropping the engine releases the lock
+    drop(engine);
+    let engine = oblivion::engine::Oblivion::open(config).unwrap();
+    assert_eq!(engine.get(b"k"), Some(b"v".to_vec()));
+}
+
+#[test]
+fn test_create_checkpoint() {
+    let dir = tempfile::tempdir().unwrap();
+    let checkpoint = dir.path().join("checkpoint");
+    let config = common::temp_config(&dir.path().join("db"));
+    let engine = oblivion::engine::Oblivion::open(config).unwrap();
+    for i in 0..200 {
+        let key = format!("key_{:04}", i).into_bytes();
+        engine.put(key, vec![b'v'; 20]).unwrap();
+    }
+    engine.delete(b"key_0003".to_vec()).unwrap();
+    engine.put(b"tail".to_vec(), b"unflushed".to_vec()).unwrap();
+
+    engine.create_checkpoint(&checkpoint).unwrap();
+    assert!(engine.create_checkpoint(&checkpoint).is_err());
+
+    // Later writes do not reach the checkpoint
+    engine.put(b"after".to_vec(), b"x".to_vec()).unwrap();
+    let expected: Vec<_> = engine
+        .scan()
+        .into_iter()
+        .filter(|(k, _)| k != b"after")
+        .collect();
+
+    let copy = oblivion::engine::Oblivion::open(common::temp_config(&checkpoint)).unwrap();
+    assert_eq!(copy.scan(), expected);
+    assert_eq!(copy.get(b"tail"), Some(b"unflushed".to_vec()));
+    assert_eq!(copy.get(b"key_0003"), None);
+    assert_eq!(copy.get(b"after"), None);
+}
+
+#[test]
+fn test_export_ndjson_and_csv() {
+    use oblivion::engine::export::ExportFormat;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = common::temp_config(dir.path());
+    let engine = oblivion::engine::Oblivion::open(config).unwrap();
+    for i in 0..100 {
+        let key = format!("key_{:04}", i).into_bytes();
+        engine.put(key, vec![b'v'; 20]).unwrap();
+    }
+    engine.delete(b"key_0005".to_vec()).unwrap();
+    engine
+        .put_with_ttl(b"session".to_vec(), b"a,\"b\"".to_vec(), 60_000)
+        .unwrap();
+    engine.put(b"raw".to_vec(), vec![0xff, 0x00]).unwrap();
+
+    let mut out = Vec::new();
+    assert_eq!(engine.export(&mut out, ExportFormat::Ndjson).unwrap(), 101);
+    let rows: Vec<serde_json::Value> = String::from_utf8(out)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(rows.len(), 101);
+    assert_eq!(rows[0]["key"], "key_0000");
+    let raw = rows.iter().find(|row| row["key"] == "cmF3").unwrap();
+    assert_eq!(
+        (&raw["value"], &raw["encoding"]),
+        (&"/wA=".into(), &"base64".into())
+    );
+    let session = rows.iter().find(|row| row["key"] == "session").unwrap();
+    assert_eq!(session["value"], "a,\"b\"");
+    assert!(session["ttl_ms"].as_u64().unwrap() > 0);
+
+    let mut out = Vec::new();
+    let opts = oblivion::engine::options::ReadOptions::new()
+        .lower_bound(b"s".to_vec())
+        .upper_bound(b"t".to_vec());
+    engine
+        .export_opt(&mut out, ExportFormat::Csv, &opts)
+        .unwrap();
+    let csv = String::from_utf8(out).unwrap();
+    let mut lines = csv.lines();
+    assert_eq!(lines.next(), Some("key,value,ttl_ms,encoding"));
+    let line = lines.next().unwrap();
+    assert!(line.starts_with("session,\"a,\"\"b\"\"\",") && line.ends_with(",utf8"));
+    assert_eq!(lines.next(), None);
+}
+
+#[test]
+fn test_import_round_trips_export() {
+    use oblivion::engine::export::ExportFormat;
+    use oblivion::engine::import::ImportOptions;
+
+    let dir = tempfile::tempdir().unwrap();
+    let source =
+        oblivion::engine::Oblivion::open(common::temp_config(&dir.path().join("a"))).unwrap();
+    for i in 0..300 {
+        let key = format!("key_{:04}", i).into_bytes();
+        source
+            .put(key, format!("value,\"{}\"\n", i).into_bytes())
+            .unwrap();
+    }
+    source.put(b"raw".to_vec(), vec![0xff, 0x00]).unwrap();
+    source
+        .put_with_ttl(b"session".to_vec(), b"s".to_vec(), 60_000)
+        .unwrap();
+
+    for format in [ExportFormat::Ndjson, ExportFormat::Csv] {
+        let mut dump = Vec::new();
+        source.export(&mut dump, format).unwrap();
+
+        let target_dir = dir.path().join(format.name());
+        let target = oblivion::engine::Oblivion::open(common::temp_config(&target_dir)).unwrap();
+        target.put(b"key_0000".to_vec(), b"stale".to_vec()).unwrap();
+        target.put(b"other".to_vec(), b"kept".to_vec()).unwrap();
+
+        let opts = ImportOptions::new().chunk_bytes(4096);
+        assert_eq!(target.import_opt(&dump[..], format, &opts).unwrap(), 302);
+        assert!(target.sstable_count() > 1);
+        assert_eq!(target.get(b"key_0000"), source.get(b"key_0000"));
+        assert_eq!(target.get(b"raw"), Some(vec![0xff, 0x00]));
+        assert_eq!(target.get(b"other"), Some(b"kept".to_vec()));
+        assert!(target.ttl(b"session").is_some());
+        drop(target);
+
+        let target = oblivion::engine::Oblivion::open(common::temp_config(&target_dir)).unwrap();
+        let mut expected = source.scan();
+        expected.push((b"other".to_vec(), b"kept".to_vec()));
+        expected.sort();
+        assert_eq!(target.scan(), expected);
+    }
+}
+
+#[test]
+fn test_failed_import_changes_nothing() {
+    use oblivion::engine::export::ExportFormat;
+    use oblivion::engine::import::ImportOptions;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = common::temp_config(dir.path());
+    let engine = oblivion::engine::Oblivion::open(config.clone()).unwrap();
+    engine.put(b"k".to_vec(), b"v".to_vec()).unwrap();
+
+    let mut dump = String::new();
+    for i in 0..100 {
+        dump.push_str(&format!(
+            "{{\"key\":\"key_{:04}\",\"value\":\"{}\"}}\n",
+            i, i
+        ));
+    }
+    dump.push_str("{\"key\":\"broken\"}\n");
+    let opts = ImportOptions::new().chunk_bytes(256);
+    let err = engine
+        .import_opt(dump.as_bytes(), ExportFormat::Ndjson, &opts)
+        .unwrap_err();
+    assert!(err.to_string().contains("line 101"));
+
+    assert_eq!(engine.scan(), vec![(b"k".to_vec(), b"v".to_vec())]);
+    assert_eq!(std::fs::read_dir(config.sst_dir()).unwrap().count(), 0);
+}
+
+#[test]
+fn test_sequence_numbers_survive_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = common::temp_config(dir.path());
+    let engine = oblivion::engine::Oblivion::open(config.clone()).unwrap();
+    assert_eq!(engine.latest_sequence(), 0);
+    for i in 0..100 {
+        let key = format!("key_{:04}", i).into_bytes();
+        engine.put(key, vec![b'v'; 20]).unwrap();
+    }
+    engine.delete(b"key_0000".to_vec()).unwrap();
+    assert!(engine.sstable_count() > 0);
+    assert_eq!(engine.latest_sequence(), 101);
+    drop(engine);
+
+    let engine = oblivion::engine::Oblivion::open(config).unwrap();
+    assert_eq!(engine.latest_sequence(), 101);
+    engine.put(b"next".to_vec(), b"v".to_vec()).unwrap();
+    assert_eq!(engine.latest_sequence(), 102);
+}
+
+#[test]
+fn test_write_batch() {
+    use oblivion::engine::batch::WriteBatch;
+    use oblivion::engine::Oblivion;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = common::temp_config(dir.path());
+    let engine = Oblivion::open(config.clone()).unwrap();
+    engine.put(b"stale".to_vec(), b"v".to_vec()).unwrap();
+    let mut changes = engine.subscribe_changes(2).unwrap();
+
+    let mut batch = WriteBatch::new();
+    for i in 0..100 {
+        batch.put(format!("key_{:04}", i).into_bytes(), vec![b'v'; 20]);
+    }
+    batch.put_with_ttl(b"session".to_vec(), b"s".to_vec(), 60_000);
+    batch.delete(b"stale".to_vec());
+    engine.write(batch).unwrap();
+    assert_eq!(engine.latest_sequence(), 103);
+    assert_eq!(engine.get(b"key_0099"), Some(vec![b'v'; 20]));
+    assert_eq!(engine.get(b"stale"), None);
+    assert!(engine.ttl(b"session").is_some());
+
+    // Subscribers see every write of the batch, in order
+    let sequences: Vec<u64> = (0..102)
+        .map(|_| changes.next().unwrap().unwrap().sequence)
+        .collect();
+    assert_eq!(sequences, (2..=103).collect::<Vec<_>>());
+
+    // Oversized writes reject the whole batch
+    let mut batch = WriteBatch::new();
+    batch.put(b"fine".to_vec(), b"v".to_vec());
+    batch.put(vec![b'k'; config.max_key_size + 1], b"v".to_vec());
+    assert!(engine.write(batch).is_err());
+    assert_eq!(engine.get(b"fine"), None);
+    drop(changes);
+    drop(engine);
+
+    let engine = Oblivion::open(config).unwrap();
+    assert_eq!(engine.latest_sequence(), 103);
+    assert_eq!(engine.get(b"key_0000"), Some(vec![b'v'; 20]));
+    assert_eq!(engine.get(b"stale"), None);
+}
+
+#[test]
+fn test_replication_to_follower() {
+    use oblivion::engine::replication::{Follower, ReplicationServer};
+    use oblivion::engine::Oblivion;
+    use std::sync::Arc;
+
+    fn wait_for(follower: &Follower, sequence: u64) {
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        while follower.applied_sequence() < sequence {
+            assert!(std::time::Instant::now() < deadline, "follower stalled");
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let primary =
+        Arc::new(Oblivion::open(common::temp_config(&dir.path().join("primary"))).unwrap());
+    for i in 0..100 {
+        let key = format!("key_{:04}", i).into_bytes();
+        primary.put(key, vec![b'v'; 20]).unwrap();
+    }
+    let server = ReplicationServer::start(Arc::clone(&primary), "127.0.0.1:0").unwrap();
+
+    // A stale key on the follower is removed by the full sync
+    let standby_config = common::temp_config(&dir.path().join("standby"));
+    let standby = Arc::new(Oblivion::open(standby_config.clone()).unwrap());
+    standby.put(b"stale".to_vec(), b"x".to_vec()).unwrap();
+    let follower = Follower::start(Arc::clone(&standby), server.local_addr()).unwrap();
+    wait_for(&follower, primary.latest_sequence());
+    assert_eq!(standby.scan(), primary.scan());
+
+    // Live writes stream through the backlog
+    primary.delete(b"key_0001".to_vec()).unwrap();
+    primary
+        .put_with_ttl(b"session".to_vec(), b"s".to_vec(), 60_000)
+        .unwrap();
+    wait_for(&follower, primary.latest_sequence());
+    assert_eq!(standby.get(b"key_0001"), None);
+    assert!(standby.ttl(b"session").is_some());
+    assert_eq!(server.follower_count(), 1);
+
+    // A restarted follower resumes where it stopped
+    drop(follower);
+    drop(standby);
+    primary.put(b"while_down".to_vec(), b"1".to_vec()).unwrap();
+    let standby = Arc::new(Oblivion::open(standby_config).unwrap());
+    let follower = Follower::start(Arc::clone(&standby), server.local_addr()).unwrap();
+    wait_for(&follower, primary.latest_sequence());
+    assert_eq!(standby.scan(), primary.scan());
+}
+
+#[test]
+fn test_follower_status_and_promotion() {
+    use oblivion::engine::replication::{Follower, FollowerPhase, ReplicationServer};
+    use oblivion::engine::Oblivion;
+    use oblivion::error::OblivionError;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    fn wait_until(condition: impl Fn() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !condition() {
+            assert!(Instant::now() < deadline, "condition not reached");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let primary =
+        Arc::new(Oblivion::open(common::temp_config(&dir.path().join("primary"))).unwrap());
+    for i in 0..20 {
+        let key = format!("key_{:04}", i).into_bytes();
+        primary.put(key, b"v".to_vec()).unwrap();
+    }
+    let server = ReplicationServer::start(Arc::clone(&primary), "127.0.0.1:0").unwrap();
+    let standby =
+        Arc::new(Oblivion::open(common::temp_config(&dir.path().join("standby"))).unwrap());
+    let follower = Follower::start(Arc::clone(&standby), server.local_addr()).unwrap();
+    wait_until(|| follower.status().is_healthy(0, Duration::from_secs(5)));
+    assert_eq!(follower.applied_sequence(), primary.latest_sequence());
+
+    // Followers refuse direct writes, and only one may run per engine
+    assert!(standby.is_read_only());
+    assert!(matches!(
+        standby.put(b"direct".to_vec(), b"x".to_vec()),
+        Err(OblivionError::ReadOnly(_))
+    ));
+    assert!(Follower::start(Arc::clone(&standby), server.local_addr()).is_err());
+
+    let status = server.status();
+    assert_eq!(status.last_sequence, primary.latest_sequence());
+    assert_eq!(status.followers.len(), 1);
+    assert!(!status.followers[0].syncing);
+    wait_until(|| server.status().followers[0].lag == 0);
+
+    // The primary fails; the follower notices and is promoted
+    primary.put(b"last".to_vec(), b"1".to_vec()).unwrap();
+    wait_until(|| follower.lag() == 0);
+    drop(server);
+    wait_until(|| follower.status().phase == FollowerPhase::Connecting);
+    assert!(follower.status().last_error.is_some());
+    let promoted = follower.promote().unwrap();
+    assert_eq!(promoted.applied_sequence, primary.latest_sequence());
+    assert_eq!(promoted.lag, 0);
+    assert!(!standby.is_read_only());
+    standby.put(b"after".to_vec(), b"2".to_vec()).unwrap();
+
+    // The old primary rejoins as a follower of the new one
+    let server = ReplicationServer::start(Arc::clone(&standby), "127.0.0.1:0").unwrap();
+    let rejoined = Follower::start(Arc::clone(&primary), server.local_addr()).unwrap();
+    wait_until(|| rejoined.status().is_healthy(0, Duration::from_secs(5)));
+    assert_eq!(primary.get(b"after"), Some(b"2".to_vec()));
+    assert_eq!(primary.scan(), standby.scan());
+}
+
+#[test]
+fn test_subscribe_changes() {
+    use oblivion::engine::changes::{ChangeOp, ChangeRecord};
+    use oblivion::engine::Oblivion;
+    use oblivion::error::OblivionError;
+    use std::time::Duration;
+
+    fn expect_put(record: ChangeRecord, sequence: u64, key: &[u8]) {
+        assert_eq!(record.sequence, sequence);
+        assert!(matches!(record.op, ChangeOp::Put { key: k, .. } if k == key));
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = common::temp_config(dir.path());
+    config.wal_retention_size = 1024 * 1024;
+    let engine = Oblivion::open(config).unwrap();
+    for i in 0..100u64 {
+        let key = format!("key_{:04}", i).into_bytes();
+        engine.put(key, vec![b'v'; 30]).unwrap();
+    }
+    engine.delete(b"key_0000".to_vec()).unwrap();
+    assert!(
+        engine.sstable_count() > 0,
+        "history should span flushed segments"
+    );
+
+    // History from the archived segments, then live writes
+    let mut changes = engine.subscribe_changes(1).unwrap();
+    for i in 0..100u64 {
+        let record = changes.next().unwrap().unwrap();
+        expect_put(record, i + 1, format!("key_{:04}", i).as_bytes());
+    }
+    let record = changes.next().unwrap().unwrap();
+    assert_eq!(record.sequence, 101);
+    assert_eq!(
+        record.op,
+        ChangeOp::Delete {
+            key: b"key_0000".to_vec()
+        }
+    );
+    assert!(changes.next_timeout(Duration::from_millis(20)).is_none());
+    engine
+        .put_with_ttl(b"live".to_vec(), b"x".to_vec(), 60_000)
+        .unwrap();
+    let record = changes
+        .next_timeout(Duration::from_secs(5))
+        .unwrap()
+        .unwrap();
+    assert_eq!(record.sequence, 102);
+    assert!(matches!(
+        record.op,
+        ChangeOp::Put {
+            ttl_ms: Some(60_000),
+            ..
+        }
+    ));
+
+    // Resuming mid-history, and subscribing to new writes only
+    let mut resumed = engine.subscribe_changes(50).unwrap();
+    expect_put(resumed.next().unwrap().unwrap(), 50, b"key_0049");
+    let mut fresh = engine
+        .subscribe_changes(engine.latest_sequence() + 1)
+        .unwrap();
+    engine.put(b"after".to_vec(), b"y".to_vec()).unwrap();
+    expect_put(fresh.next().unwrap().unwrap(), 103, b"after");
+
+    // Streams end when the engine closes
+    drop(engine);
+    assert!(fresh.next().is_none());
+
+    // Without retention, flushed writes are gone
+    let dir = tempfile::tempdir().unwrap();
+    let engine = Oblivion::open(common::temp_config(dir.path())).unwrap();
+    for i in 0..100u64 {
+        let key = format!("key_{:04}", i).into_bytes();
+        engine.put(key, vec![b'v'; 30]).unwrap();
+    }
+    assert!(matches!(
+        engine.subscribe_changes(1),
+        Err(OblivionError::ChangesUnavailable(1))
+    ));
+    let latest = engine.latest_sequence();
+    let mut recent = engine.subscribe_changes(latest).unwrap();
+    expect_put(recent.next().unwrap().unwrap(), latest, b"key_0099");
+}
+
+#[test]
+fn test_watch_prefix() {
+    use oblivion::engine::changes::ChangeEvent;
+    use oblivion::engine::Oblivion;
+    use std::time::Duration;
+
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = common::temp_config(dir.path());
+    config.background_threads = 1;
+    let engine = Oblivion::open(config).unwrap();
+    let config_events = engine.watch_prefix(b"config/");
+    let everything = engine.watch_prefix(b"");
+
+    engine.put(b"config/a".to_vec(), b"1".to_vec()).unwrap();
+    engine.put(b"other".to_vec(), b"x".to_vec()).unwrap();
+    engine.delete(b"config/a".to_vec()).unwrap();
+    engine
+        .put_with_ttl(b"config/lease".to_vec(), b"2".to_vec(), 50)
+        .unwrap();
+
+    let timeout = Duration::from_secs(5);
+    assert_eq!(
+        config_events.recv_timeout(timeout).unwrap(),
+        ChangeEvent::Put {
+            sequence: 1,
+            key: b"config/a".to_vec(),
+            value: b"1".to_vec(),
+        }
+    );
+    assert_eq!(
+        config_events.recv_timeout(timeout).unwrap(),
+        ChangeEvent::Delete {
+            sequence: 3,
+            key: b"config/a".to_vec(),
+        }
+    );
+    assert_eq!(
+        config_events.recv_timeout(timeout).unwrap().key(),
+        b"config/lease"
+    );
+    // The background TTL sweep reports the expiration
+    assert_eq!(
+        config_events.recv_timeout(timeout).unwrap(),
+        ChangeEvent::Expired {
+            key: b"config/lease".to_vec(),
+        }
+    );
+    assert!(config_events
+        .recv_timeout(Duration::from_millis(20))
+        .is_err());
+    let keys: Vec<Vec<u8>> = everything
+        .try_iter()
+        .map(|event| event.key().to_vec())
+        .collect();
+    assert_eq!(
+        keys[..3],
+        [
+            b"config/a".to_vec(),
+            b"other".to_vec(),
+            b"config/a".to_vec()
+        ]
+    );
+
+    // Dropped receivers are cancelled; closing the engine disconnects the rest
+    drop(everything);
+    engine.put(b"config/b".to_vec(), b"3".to_vec()).unwrap();
+    drop(engine);
+    assert!(matches!(
+        config_events.recv().unwrap(),
+        ChangeEvent::Put { sequence: 5, .. }
+    ));
+    assert!(config_events.recv().is_err());
+}
+
+#[test]
+fn test_resp_server() {
+    use oblivion::server::resp::Reply;
+    use oblivion::server::RespServer;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpStream;
+    use std::sync::Arc;
+
+    /// Parse one reply, as a Redis client would.
+    fn read_reply(reader: &mut impl BufRead) -> Reply {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let (kind, rest) = line.trim_end().split_at(1);
+        match kind {
+            "+" => Reply::Simple(rest.to_string()),
+            "-" => Reply::Error(rest.to_string()),
+            ":" => Reply::Integer(rest.parse().unwrap()),
+            "$" if rest == "-1" => Reply::nil(),
+            "$" => {
+                let mut bulk = vec![0; rest.parse::<usize>().unwrap() + 2];
+                reader.read_exact(&mut bulk).unwrap();
+                bulk.truncate(bulk.len() - 2);
+                Reply::bulk(bulk)
+            }
+            "*" => Reply::Array(
+                (0..rest.parse::<usize>().unwrap())
+                    .map(|_| read_reply(reader))
+                    .collect(),
+            ),
+            other => panic!("unexpected reply type {:?}", other),
+        }
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let engine =
+        Arc::new(oblivion::engine::Oblivion::open(common::temp_config(dir.path())).unwrap());
+    let server = RespServer::start(Arc::clone(&engine), "127.0.0.1:0").unwrap();
+    let stream = TcpStream::connect(server.local_addr()).unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut writer = stream;
+    let mut call = |args: &[&[u8]]| {
+        let mut request = format!("*{}\r\n", args.len()).into_bytes();
+        for arg in args {
+            request.extend(format!("${}\r\n", arg.len()).bytes());
+            request.extend_from_slice(arg);
+            request.extend_from_slice(b"\r\n");
+        }
+        writer.write_all(&request).unwrap();
+        read_reply(&mut reader)
+    };
+
+    assert_eq!(call(&[b"PING"]), Reply::Simple("PONG".to_string()));
+    assert_eq!(call(&[b"SET", b"bin\r\nkey", b"\x00\xff"]), Reply::ok());
+    assert_eq!(
+        call(&[b"get", b"bin\r\nkey"]),
+        Reply::bulk(b"\x00\xff".to_vec())
+    );
+    assert_eq!(call(&[b"GET", b"missing"]), Reply::nil());
+    assert_eq!(
+        call(&[b"SET", b"k", b"v", b"NX"]),
+        Reply::error("ERR syntax error")
+    );
+
+    // Expiry
+    assert_eq!(
+        call(&[b"SET", b"session", b"s", b"EX", b"100"]),
+        Reply::ok()
+    );
+    assert_eq!(call(&[b"TTL", b"session"]), Reply::Integer(100));
+    assert_eq!(call(&[b"TTL", b"bin\r\nkey"]), Reply::Integer(-1));
+    assert_eq!(call(&[b"TTL", b"missing"]), Reply::Integer(-2));
+    assert_eq!(call(&[b"EXPIRE", b"bin\r\nkey", b"50"]), Reply::Integer(1));
+    assert_eq!(call(&[b"EXPIRE", b"missing", b"50"]), Reply::Integer(0));
+    match call(&[b"PTTL", b"bin\r\nkey"]) {
+        Reply::Integer(ms) => assert!(ms > 49_000 && ms <= 50_000),
+        other => panic!("unexpected {:?}", other),
+    }
+    assert_eq!(engine.get(b"bin\r\nkey"), Some(b"\x00\xff".to_vec()));
+    assert_eq!(call(&[b"PEXPIRE", b"session", b"0"]), Reply::Integer(1));
+    assert_eq!(
+        call(&[b"EXISTS", b"session", b"bin\r\nkey"]),
+        Reply::Integer(1)
+    );
+    assert_eq!(
+        call(&[b"DEL", b"bin\r\nkey", b"missing"]),
+        Reply::Integer(1)
+    );
+
+    // SCAN walks every matching key once, across pages and flushed tables
+    for i in 0..40 {
+        engine
+            .put(format!("user:{:02}", i).into_bytes(), vec![b'x'; 40])
+            .unwrap();
+        engine
+            .put(format!("item:{:02}", i).into_bytes(), b"y".to_vec())
+            .unwrap();
+    }
+    engine.delete(b"user:07".to_vec()).unwrap();
+    let mut cursor = b"0".to_vec();
+    let mut users = Vec::new();
+    let mut pages = 0;
+    loop {
+        let reply = call(&[b"SCAN", &cursor, b"MATCH", b"user:*", b"COUNT", b"8"]);
+        let Reply::Array(mut parts) = reply else {
+            panic!("unexpected {:?}", reply);
+        };
+        let Reply::Array(keys) = parts.pop().unwrap() else {
+            panic!("expected keys");
+        };
+        users.extend(keys);
+        let Reply::Bulk(Some(next)) = parts.pop().unwrap() else {
+            panic!("expected cursor");
+        };
+        pages += 1;
+        if next == b"0" {
+            break;
+        }
+        cursor = next;
+    }
+    assert!(pages > 1);
+    let expected: Vec<Reply> = (0..40)
+        .filter(|&i| i != 7)
+        .map(|i| Reply::bulk(format!("user:{:02}", i)))
+        .collect();
+    assert_eq!(users, expected);
+    assert_eq!(
+        call(&[b"SCAN", b"999999"]),
+        Reply::error("ERR invalid cursor")
+    );
+
+    assert_eq!(server.connection_count(), 1);
+    drop(server);
+    assert_eq!(reader.read_line(&mut String::new()).unwrap(), 0);
+}
+
+#[test]
+fn test_manual_flush_and_compact() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = oblivion::config::Config::builder(dir.path())
+        .memtable_max_size(1 << 20)
+        .compaction_threshold(100)
+        .build()
+        .unwrap();
+    let engine = oblivion::engine::Oblivion::open(config).unwrap();
+
+    for round in 0..3u8 {
+        engine.put(vec![b'k', round], vec![round]).unwrap();
+        engine.put(b"shared".to_vec(), vec![round]).unwrap();
+        engine.flush().unwrap();
+    }
+    engine.delete(b"k\x00".to_vec()).unwrap();
+    engine.flush().unwrap();
+    assert_eq!(engine.sstable_count(), 4);
+    assert!(engine.is_empty());
+
+    engine.compact().unwrap();
+    assert_eq!(engine.sstable_count(), 1);
+    assert_eq!(engine.get(b"shared"), Some(vec![2]));
+    assert_eq!(engine.get(b"k\x00"), None);
+    assert_eq!(engine.scan().len(), 3);
+
+    // Compaction does not wait for snapshots, which keep their view
+    engine.put(b"late".to_vec(), b"x".to_vec()).unwrap();
+    engine.flush().unwrap();
+    let snapshot = engine.snapshot();
+    engine.delete(b"late".to_vec()).unwrap();
+    engine.flush().unwrap();
+    engine.compact().unwrap();
+    assert_eq!(engine.sstable_count(), 1);
+    let opts = oblivion::engine::options::ReadOptions::new().snapshot(snapshot);
+    assert_eq!(engine.get_opt(b"late", &opts).unwrap(), Some(b"x".to_vec()));
+    drop(opts);
+    assert_eq!(engine.get(b"late"), None);
+
+    // A range compaction only merges the tables overlapping the range
+    for key in [&b"m1"[..], b"x1", b"y1"] {
+        engine.put(key.to_vec(), b"v".to_vec()).unwrap();
+        engine.flush().unwrap();
+    }
+    assert_eq!(engine.sstable_count(), 4);
+    engine.compact_range(Some(b"x"), Some(b"z")).unwrap();
+    assert_eq!(engine.sstable_count(), 3);
+    engine.compact_range(Some(b"zz"), None).unwrap();
+    assert_eq!(engine.sstable_count(), 3);
+    assert_eq!(engine.get(b"x1"), Some(b"v".to_vec()));
+    assert_eq!(engine.scan().len(), 6);
+}
+
+#[test]
+fn test_cli_batch_mode() {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    let dir = tempfile::tempdir().unwrap();
+    let script = dir.path().join("commands.txt");
+    std::fs::write(
+        &script,
+        "# seed\nset user:1 alice smith\nset user:2 bob\nget user:1\nget missing\ndel user:2\nscan\n",
+    )
+    .unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_oblivion"))
+        .current_dir(dir.path())
+        .args(["--exec", "commands.txt"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "OK\nOK\nalice smith\n\nOK\n0\nuser:1\talice smith\n"
+    );
+
+    // Piped stdin runs in batch mode too; the first failure stops it
+    let mut child = Command::new(env!("CARGO_BIN_EXE_oblivion"))
+        .current_dir(dir.path())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"get user:1\nbogus\nset never reached\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "alice smith\n");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("unknown command 'bogus'"));
+    assert!(stderr.contains("line 2"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_oblivion"))
+        .current_dir(dir.path())
+        .args(["--exec", "missing.txt"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+}
+
+// ==================== CLI Flag Tests ====================
+
+#[test]
+fn test_cli_engine_flags() {
+    use std::process::Command;
+
+    let dir = tempfile::tempdir().unwrap();
+    let conf = dir.path().join("oblivion.conf");
+    std::fs::write(&conf, "data_dir = from_file\nmemtable_max_size = 1024\n").unwrap();
+    let script = dir.path().join("commands.txt");
+    std::fs::write(&script, "set k v\n").unwrap();
+
+    // Flags override the options file
+    let output = Command::new(env!("CARGO_BIN_EXE_oblivion"))
+        .current_dir(dir.path())
+        .args(["--data-dir", "from_flag", "--config", "oblivion.conf"])
+        .args(["--no-sync", "--exec", "commands.txt"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert!(!dir.path().join("from_file").exists());
+    assert!(!dir.path().join("data").exists());
+    let config = oblivion::config::Config::new(dir.path().join("from_flag"));
+    let engine = oblivion::engine::Oblivion::open(config).unwrap();
+    assert_eq!(engine.get(b"k"), Some(b"v".to_vec()));
+    drop(engine);
+
+    for args in [
+        &["--memtable-size", "0"][..],
+        &["--memtable-size", "lots"],
+        &["--config", "missing.conf"],
+        &["--data-dir"],
+    ] {
+        let output = Command::new(env!("CARGO_BIN_EXE_oblivion"))
+            .current_dir(dir.path())
+            .args(args)
+            .args(["--exec", "commands.txt"])
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+    }
+}
+
+// ==================== TTL Tests ====================
+
+#[test]
+fn test_expire_and_persist() {
+    let dir = tempfile::tempdir().unwrap();
+    let engine = oblivion::engine::Oblivion::open(common::temp_config(dir.path())).unwrap();
+    engine.put(b"k".to_vec(), b"v".to_vec()).unwrap();
+    assert!(!engine.persist(b"k").unwrap());
+    assert!(engine.expire(b"k", 60_000).unwrap());
+    assert!(engine.ttl(b"k").is_some());
+    assert!(engine.persist(b"k").unwrap());
+    assert_eq!(engine.ttl(b"k"), None);
+    assert!(!engine.persist(b"missing").unwrap());
+
+    // A persisted key outlives its old expiry
+    engine.expire(b"k", 1).unwrap();
+    engine.persist(b"k").unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(10));
+    assert_eq!(engine.get(b"k"), Some(b"v".to_vec()));
+}
+
+// ==================== String Tests ====================
+
+#[test]
+fn test_concurrent_appends_are_not_lost() {
+    use oblivion::engine::Oblivion;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = common::temp_config(dir.path());
+    let engine = Oblivion::open(config.clone()).unwrap();
+    engine
+        .put_with_ttl(b"log".to_vec(), Vec::new(), 60_000)
+        .unwrap();
+    std::thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| {
+                for _ in 0..50 {
+                    engine.append(b"log".to_vec(), b"ab").unwrap();
+                }
+            });
+        }
+    });
+    assert_eq!(engine.strlen(b"log").unwrap(), 400);
+    // Appending keeps the key's TTL, as a put does
+    assert!(engine.ttl(b"log").is_some());
+
+    let old = engine.getset(b"log".to_vec(), b"fresh".to_vec()).unwrap();
+    assert_eq!(old.map(|value| value.len()), Some(400));
+    drop(engine);
+
+    let engine = Oblivion::open(config).unwrap();
+    assert_eq!(
+        engine.getdel(b"log".to_vec()).unwrap(),
+        Some(b"fresh".to_vec())
+    );
+    assert_eq!(engine.getdel(b"log".to_vec()).unwrap(), None);
+    assert_eq!(engine.strlen(b"log").unwrap(), 0);
+}
+
+// ==================== Column Family Tests ====================
+
+#[test]
+fn test_column_families_are_isolated_and_recovered() {
+    use oblivion::engine::column_family::ColumnFamilyOptions;
+    use oblivion::engine::Oblivion;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = common::temp_config(dir.path());
+    let cfs = || {
+        [
+            ("users", ColumnFamilyOptions::new()),
+            ("events", ColumnFamilyOptions::new().memtable_max_size(256)),
+        ]
+    };
+    let engine = Oblivion::open_with_cfs(config.clone(), cfs()).unwrap();
+    assert_eq!(engine.column_families(), vec!["events", "users"]);
+
+    engine.put(b"k".to_vec(), b"default".to_vec()).unwrap();
+    engine
+        .put_cf("users", b"k".to_vec(), b"user".to_vec())
+        .unwrap();
+    engine
+        .put_cf("users", b"gone".to_vec(), b"x".to_vec())
+        .unwrap();
+    engine.delete_cf("users", b"gone".to_vec()).unwrap();
+    assert_eq!(engine.get(b"k"), Some(b"default".to_vec()));
+    assert_eq!(
+        engine.get_cf("users", b"k").unwrap(),
+        Some(b"user".to_vec())
+    );
+    assert_eq!(engine.get_cf("events", b"k").unwrap(), None);
+    assert!(engine.get_cf("missing", b"k").is_err());
+
+    // The small events MemTable flushes on its own schedule
+    for i in 0..50 {
+        let key = format!("event:{:03}", i).into_bytes();
+        engine.put_cf("events", key, vec![b'e'; 32]).unwrap();
+    }
+    let events = engine
+        .scan_cf("events", &oblivion::engine::options::ReadOptions::new())
+        .unwrap();
+    assert_eq!(events.len(), 50);
+    let event_tables = std::fs::read_dir(dir.path().join("cf/events/sst"))
+        .unwrap()
+        .count();
+    assert!(event_tables > 0);
+    assert_eq!(engine.get(b"event:000"), None);
+    // Every flushed segment is gone once all MemTables are on disk
+    engine.flush().unwrap();
+    let segments = std::fs::read_dir(dir.path().join("wal"))
+        .unwrap()
+        .filter(|e| {
+            let name = e.as_ref().unwrap().file_name();
+            name.to_string_lossy().starts_with("oblivion_")
+        })
+        .count();
+    assert_eq!(segments, 0);
+    engine
+        .put_cf("users", b"late".to_vec(), b"1".to_vec())
+        .unwrap();
+    drop(engine);
+
+    // Every family must be listed to reopen
+    assert!(Oblivion::open(config.clone()).is_err());
+    assert!(
+        Oblivion::open_with_cfs(config.clone(), [("users", ColumnFamilyOptions::new())]).is_err()
+    );
+
+    let engine = Oblivion::open_with_cfs(config, cfs()).unwrap();
+    assert_eq!(engine.get(b"k"), Some(b"default".to_vec()));
+    assert_eq!(
+        engine.get_cf("users", b"k").unwrap(),
+        Some(b"user".to_vec())
+    );
+    assert_eq!(
+        engine.get_cf("users", b"late").unwrap(),
+        Some(b"1".to_vec())
+    );
+    assert_eq!(engine.get_cf("users", b"gone").unwrap(), None);
+    assert_eq!(
+        engine.get_cf("events", b"event:049").unwrap(),
+        Some(vec![b'e'; 32])
+    );
+}
+
+#[test]
+fn test_column_families_in_checkpoint() {
+    use oblivion::engine::column_family::ColumnFamilyOptions;
+    use oblivion::engine::Oblivion;
+
+    let dir = tempfile::tempdir().unwrap();
+    let cfs = || [("users", ColumnFamilyOptions::new())];
+    let engine =
+        Oblivion::open_with_cfs(common::temp_config(&dir.path().join("db")), cfs()).unwrap();
+    engine
+        .put_cf("users", b"a".to_vec(), b"1".to_vec())
+        .unwrap();
+    let checkpoint = dir.path().join("checkpoint");
+    engine.create_checkpoint(&checkpoint).unwrap();
+    drop(engine);
+
+    let copy = Oblivion::open_with_cfs(common::temp_config(&checkpoint), cfs()).unwrap();
+    assert_eq!(copy.get_cf("users", b"a").unwrap(), Some(b"1".to_vec()));
+}
+
+#[test]
+fn test_column_families_replicate_to_follower() {
+    use oblivion::engine::changes::ChangeOp;
+    use oblivion::engine::column_family::ColumnFamilyOptions;
+    use oblivion::engine::replication::{Follower, ReplicationServer};
+    use oblivion::engine::Oblivion;
+    use std::sync::Arc;
+
+    fn wait_for(follower: &Follower, sequence: u64) {
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        while follower.applied_sequence() < sequence {
+            assert!(std::time::Instant::now() < deadline, "follower stalled");
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let cfs = || [("users", ColumnFamilyOptions::new())];
+    let open = |name: &str| {
+        let config = common::temp_config(&dir.path().join(name));
+        Arc::new(Oblivion::open_with_cfs(config, cfs()).unwrap())
+    };
+    let primary = open("primary");
+    primary
+        .put_cf("users", b"alice".to_vec(), b"admin".to_vec())
+        .unwrap();
+    primary.put(b"k".to_vec(), b"v".to_vec()).unwrap();
+    assert_eq!(primary.latest_sequence(), 2);
+    let server = ReplicationServer::start(Arc::clone(&primary), "127.0.0.1:0").unwrap();
+
+    // The full sync copies the family and removes what the primary lacks
+    let standby = open("standby");
+    standby
+        .put_cf("users", b"stale".to_vec(), b"x".to_vec())
+        .unwrap();
+    let follower = Follower::start(Arc::clone(&standby), server.local_addr()).unwrap();
+    wait_for(&follower, primary.latest_sequence());
+    assert_eq!(
+        standby.get_cf("users", b"alice").unwrap(),
+        Some(b"admin".to_vec())
+    );
+    assert_eq!(standby.get_cf("users", b"stale").unwrap(), None);
+
+    // Live writes stream with their own sequence numbers
+    primary
+        .put_cf("users", b"bob".to_vec(), b"guest".to_vec())
+        .unwrap();
+    primary.delete_cf("users", b"alice".to_vec()).unwrap();
+    wait_for(&follower, primary.latest_sequence());
+    assert_eq!(
+        standby.get_cf("users", b"bob").unwrap(),
+        Some(b"guest".to_vec())
+    );
+    assert_eq!(standby.get_cf("users", b"alice").unwrap(), None);
+    assert_eq!(standby.get(b"bob"), None);
+
+    // A promoted follower keeps the family
+    drop(server);
+    follower.promote().unwrap();
+    let opts = oblivion::engine::options::ReadOptions::new();
+    assert_eq!(
+        standby.scan_cf("users", &opts).unwrap(),
+        primary.scan_cf("users", &opts).unwrap()
+    );
+
+    // Subscribers read them back from the WAL, tagged with the family
+    let mut changes = primary.subscribe_changes(1).unwrap();
+    assert_eq!(
+        changes.next().unwrap().unwrap().op,
+        ChangeOp::PutCf {
+            cf: "users".to_string(),
+            key: b"alice".to_vec(),
+            value: b"admin".to_vec(),
+        }
+    );
+}
+
+// ==================== Logical Database Tests ====================
+
+#[test]
+fn test_logical_databases_survive_flush_and_restart() {
+    use oblivion::engine::Oblivion;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = common::temp_config(dir.path());
+    let engine = Oblivion::open(config.clone()).unwrap();
+    for index in 0..3 {
+        let db = engine.database(index).unwrap();
+        for i in 0..20 {
+            db.put(
+                format!("key:{:02}", i).into_bytes(),
+                index.to_string().into_bytes(),
+            )
+            .unwrap();
+        }
+    }
+    engine.flush().unwrap();
+    engine.database(1).unwrap().delete(b"key:05").unwrap();
+    drop(engine);
+
+    let engine = Oblivion::open(config).unwrap();
+    let (zero, one, two) = (
+        engine.database(0).unwrap(),
+        engine.database(1).unwrap(),
+        engine.database(2).unwrap(),
+    );
+    assert_eq!(zero.get(b"key:05").unwrap(), Some(b"0".to_vec()));
+    assert_eq!(one.get(b"key:05").unwrap(), None);
+    assert_eq!(two.get(b"key:05").unwrap(), Some(b"2".to_vec()));
+    assert_eq!(zero.keys(b"key:*").unwrap().len(), 20);
+    assert_eq!(one.keys(b"key:*").unwrap().len(), 19);
+    // The raw keyspace holds every database
+    assert_eq!(engine.scan().len(), 59);
+}
+
+// ==================== Hash Tests ====================
+
+#[test]
+fn test_hashes_survive_flush_and_restart() {
+    use oblivion::engine::Oblivion;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = common::temp_config(dir.path());
+    let engine = Oblivion::open(config.clone()).unwrap();
+    for user in 0..10u8 {
+        let key = format!("user:{}", user).into_bytes();
+        let fields = (0..10)
+            .map(|i| (format!("attr:{}", i).into_bytes(), vec![user; 32]))
+            .collect();
+        assert_eq!(engine.hset(&key, fields).unwrap(), 10);
+    }
+    engine.flush().unwrap();
+    engine
+        .hset(b"user:3", vec![(b"attr:0".to_vec(), b"new".to_vec())])
+        .unwrap();
+    engine
+        .hdel(b"user:3", &[b"attr:9".to_vec(), b"attr:8".to_vec()])
+        .unwrap();
+    drop(engine);
+
+    let engine = Oblivion::open(config).unwrap();
+    let fields = engine.hgetall(b"user:3").unwrap();
+    assert_eq!(fields.len(), 8);
+    assert_eq!(fields[0], (b"attr:0".to_vec(), b"new".to_vec()));
+    assert_eq!(
+        engine.hget(b"user:7", b"attr:9").unwrap(),
+        Some(vec![7; 32])
+    );
+    assert_eq!(engine.hget(b"user:3", b"attr:9").unwrap(), None);
+    // Hash fields are not plain keys
+    assert!(engine.database(0).unwrap().keys(b"*").unwrap().is_empty());
+}
+
+// ==================== List Tests ====================
+
+#[test]
+fn test_list_queue_survives_restart() {
+    use oblivion::engine::Oblivion;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = common::temp_config(dir.path());
+    let engine = Oblivion::open(config.clone()).unwrap();
+    for i in 0..50 {
+        let job = format!("job:{:02}", i).into_bytes();
+        assert_eq!(engine.rpush(b"jobs", vec![job]).unwrap(), i + 1);
+    }
+    engine.flush().unwrap();
+    for i in 0..20 {
+        let job = engine.lpop(b"jobs").unwrap().unwrap();
+        assert_eq!(job, format!("job:{:02}", i).into_bytes());
+    }
+    drop(engine);
+
+    let engine = Oblivion::open(config).unwrap();
+    let jobs = engine.lrange(b"jobs", 0, -1).unwrap();
+    assert_eq!(jobs.len(), 30);
+    assert_eq!(jobs[0], b"job:20".to_vec());
+    assert_eq!(
+        engine.lrange(b"jobs", -1, -1).unwrap(),
+        vec![b"job:49".to_vec()]
+    );
+    assert_eq!(engine.rpush(b"jobs", vec![b"job:50".to_vec()]).unwrap(), 31);
+}
+
+// ==================== Set Tests ====================
+
+#[test]
+fn test_sorted_set_leaderboard_survives_restart() {
+    use oblivion::engine::Oblivion;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = common::temp_config(dir.path());
+    let engine = Oblivion::open(config.clone()).unwrap();
+    let players: Vec<(Vec<u8>, f64)> = (0..100)
+        .map(|i| {
+            (
+                format!("player:{:03}", i).into_bytes(),
+                (i * 7 % 100) as f64,
+            )
+        })
+        .collect();
+    assert_eq!(engine.zadd(b"board", players).unwrap(), 100);
+    engine.sadd(b"banned", &[b"player:004".to_vec()]).unwrap();
+    engine.flush().unwrap();
+    // Move a player to the top after the flush
+    engine
+        .zadd(b"board", vec![(b"player:001".to_vec(), 1000.0)])
+        .unwrap();
+    drop(engine);
+
+    let engine = Oblivion::open(config).unwrap();
+    let top = engine.zrangebyscore(b"board", 95.0, f64::INFINITY).unwrap();
+    let names: Vec<&[u8]> = top.iter().map(|(member, _)| member.as_slice()).collect();
+    assert_eq!(names.last(), Some(&&b"player:001"[..]));
+    assert_eq!(top.len(), 6);
+    assert!(top.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+    assert_eq!(engine.zrangebyscore(b"board", 7.0, 7.0).unwrap().len(), 0);
+    assert!(engine.sismember(b"banned", b"player:004").unwrap());
+    assert_eq!(engine.smembers(b"banned").unwrap().len(), 1);
+}
+
+// ==================== Comparator Tests ====================
+
+#[test]
+fn test_case_insensitive_order_survives_compaction_and_restart() {
+    use oblivion::engine::comparator::{CaseInsensitive, Comparator};
+    use oblivion::engine::options::ReadOptions;
+    use oblivion::engine::Oblivion;
+    use oblivion::error::OblivionError;
+
+    let dir = tempfile::tempdir().unwrap();
+    let comparator = Comparator::new(CaseInsensitive);
+    let config = common::temp_config(dir.path()).with_comparator(comparator.clone());
+    let engine = Oblivion::open(config.clone()).unwrap();
+    let mut expected: Vec<Vec<u8>> = Vec::new();
+    for i in 0..200 {
+        let key = match i % 3 {
+            0 => format!("User:{:03}", i),
+            1 => format!("user:{:03}", i),
+            _ => format!("ADMIN:{:03}", i),
+        };
+        engine
+            .put(key.clone().into_bytes(), vec![b'v'; 16])
+            .unwrap();
+        expected.push(key.into_bytes());
+    }
+    expected.sort_by(|a, b| comparator.compare(a, b));
+    assert!(engine.sstable_count() > 0);
+    engine.compact().unwrap();
+
+    let keys = |engine: &Oblivion| -> Vec<Vec<u8>> {
+        engine.scan().into_iter().map(|(key, _)| key).collect()
+    };
+    assert_eq!(keys(&engine), expected);
+    assert!(engine.get(b"User:000").is_some());
+    assert!(engine.get(b"user:000").is_none());
+    drop(engine);
+
+    let engine = Oblivion::open(config).unwrap();
+    assert_eq!(keys(&engine), expected);
+    // Bounds are in comparator order: "admin:" < "b" takes every ADMIN key
+    let opts = ReadOptions::new()
+        .lower_bound(b"admin:".to_vec())
+        .upper_bound(b"b".to_vec());
+    assert_eq!(engine.scan_opt(&opts).unwrap().len(), 66);
+
+    let db = engine.database(0).unwrap();
+    let mut opts = ReadOptions::new();
+    let mut paged = Vec::new();
+    loop {
+        let (page, resume) = db.scan_page(&opts, 7).unwrap();
+        paged.extend(page.into_iter().map(|(key, _)| key));
+        match resume {
+            Some(resume) => opts.lower_bound = Some(resume),
+            None => break,
+        }
+    }
+    assert_eq!(paged, expected);
+    drop(engine);
+
+    // The tables are not in byte order
+    let bytewise = common::temp_config(dir.path());
+    assert!(matches!(
+        Oblivion::open(bytewise),
+        Err(OblivionError::Config(_))
+    ));
+}
+
+// ==================== Timestamp Tests ====================
+
+#[test]
+fn test_history_is_trimmed_past_retention() {
+    use oblivion::engine::options::ReadOptions;
+    use oblivion::engine::Oblivion;
+
+    let dir = tempfile::tempdir().unwrap();
+    let hour = 60 * 60 * 1000;
+    let config = common::temp_config(dir.path()).with_history_retention_ms(hour);
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    let engine = Oblivion::open(config.clone()).unwrap();
+    for (hours_ago, value) in [(10, "v1"), (5, "v2"), (0, "v3")] {
+        let ts = now - hours_ago * hour;
+        engine.put_at(b"price", value.into(), ts).unwrap();
+        engine.put_at(b"other", value.into(), ts + 1).unwrap();
+    }
+    engine.delete_at(b"other", now - 2 * hour).unwrap();
+    engine.flush().unwrap();
+    assert_eq!(
+        engine.get_at(b"price", now - 9 * hour).unwrap(),
+        Some(b"v1".to_vec())
+    );
+    drop(engine);
+
+    let engine = Oblivion::open(config).unwrap();
+    engine.compact().unwrap();
+    // v2 is the newest version at the horizon, so v1 is gone
+    assert_eq!(engine.get_at(b"price", now - 9 * hour).unwrap(), None);
+    assert_eq!(
+        engine.get_at(b"price", now - 4 * hour).unwrap(),
+        Some(b"v2".to_vec())
+    );
+    assert_eq!(engine.get_at(b"price", now).unwrap(), Some(b"v3".to_vec()));
+    let at = |ts| -> Vec<Vec<u8>> {
+        engine
+            .scan_at(&ReadOptions::new(), ts)
+            .unwrap()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect()
+    };
+    assert_eq!(at(now - hour), vec![b"price".to_vec()]);
+    assert_eq!(at(now + 1), vec![b"other".to_vec(), b"price".to_vec()]);
+}
+
+// ==================== Integrity Tests ====================
+
+#[test]
+fn test_paranoid_checks_refuse_damaged_blocks() {
+    use oblivion::engine::options::ReadOptions;
+    use oblivion::engine::Oblivion;
+    use oblivion::error::OblivionError;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = common::temp_config(dir.path());
+    let engine = Oblivion::open(config.clone()).unwrap();
+    engine.put(b"key".to_vec(), b"value".to_vec()).unwrap();
+    engine.flush().unwrap();
+    drop(engine);
+
+    let table = std::fs::read_dir(config.sst_dir())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| path.extension().is_some_and(|ext| ext == "sst"))
+        .unwrap();
+    let mut data = std::fs::read(&table).unwrap();
+    // The last byte of the value
+    data[15] ^= 0x01;
+    std::fs::write(&table, &data).unwrap();
+
+    let unchecked = ReadOptions::new().verify_checksums(false);
+    let engine = Oblivion::open(config.clone()).unwrap();
+    assert_eq!(
+        engine.get_opt(b"key", &unchecked).unwrap(),
+        Some(b"valud".to_vec())
+    );
+    drop(engine);
+
+    let engine = Oblivion::open(config.with_paranoid_checks(true)).unwrap();
+    assert!(matches!(
+        engine.get_opt(b"key", &unchecked),
+        Err(OblivionError::Corruption(_))
+    ));
+    assert!(engine.scan_opt(&unchecked).is_err());
+}
+
+#[test]
+fn test_verify_integrity_reports_damage() {
+    use oblivion::engine::integrity::ProblemKind;
+    use oblivion::engine::Oblivion;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = common::temp_config(dir.path());
+    let engine = Oblivion::open(config.clone()).unwrap();
+    engine.put(b"key".to_vec(), b"value".to_vec()).unwrap();
+    engine.flush().unwrap();
+    engine.put(b"other".to_vec(), b"value".to_vec()).unwrap();
+    engine.put(b"last".to_vec(), b"value".to_vec()).unwrap();
+
+    let report = engine.verify_integrity().unwrap();
+    assert!(report.is_ok(), "{:?}", report.problems);
+    assert_eq!(report.tables_checked, 1);
+    assert_eq!(report.blocks_checked, 1);
+    assert_eq!(report.manifests_checked, 1);
+    assert!(report.logs_checked >= 1);
+    assert!(report.records_checked >= 2);
+
+    // Damage the table's block and the first write of the live log
+    // under the open engine
+    let table = std::fs::read_dir(config.sst_dir())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| path.extension().is_some_and(|ext| ext == "sst"))
+        .unwrap();
+    let mut data = std::fs::read(&table).unwrap();
+    data[15] ^= 0x01;
+    std::fs::write(&table, &data).unwrap();
+    let records = oblivion::engine::wal::WriteAheadLog::inspect(&config.wal_path()).unwrap();
+    let write = records.iter().find(|record| record.op == 1).unwrap();
+    let mut wal = std::fs::read(config.wal_path()).unwrap();
+    wal[write.offset as usize + 9] ^= 0x01;
+    std::fs::write(config.wal_path(), &wal).unwrap();
+
+    let report = engine.verify_integrity().unwrap();
+    let found: Vec<_> = report
+        .problems
+        .iter()
+        .map(|problem| (problem.path.clone(), problem.offset, problem.kind))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            (table, Some(0), ProblemKind::Checksum),
+            (config.wal_path(), Some(write.offset), ProblemKind::Checksum),
+        ]
+    );
+}
+
+// ==================== Env Tests ====================
+
+#[test]
+fn test_memory_env_keeps_database_off_disk() {
+    use oblivion::engine::env::Env;
+    use oblivion::engine::Oblivion;
+    use oblivion::error::OblivionError;
+
+    let dir = tempfile::tempdir().unwrap();
+    let env = Env::memory();
+    let config = common::temp_config(&dir.path().join("db")).with_env(env.clone());
+
+    let engine = Oblivion::open(config.clone()).unwrap();
+    for i in 0..200u32 {
+        engine
+            .put(format!("key_{:03}", i).into_bytes(), vec![b'v'; 32])
+            .unwrap();
+    }
+    engine.delete(b"key_000".to_vec()).unwrap();
+    engine.flush().unwrap();
+    engine.compact().unwrap();
+    engine.put(b"unflushed".to_vec(), b"wal".to_vec()).unwrap();
+    assert!(matches!(
+        Oblivion::open(config.clone()),
+        Err(OblivionError::Locked(_))
+    ));
+    assert!(matches!(
+        engine.create_checkpoint(dir.path().join("checkpoint")),
+        Err(OblivionError::Unsupported(_))
+    ));
+    assert!(engine.verify_integrity().unwrap().is_ok());
+    drop(engine);
+
+    // Tables, manifest and WAL all come back from the shared Env
+    let engine = Oblivion::open(config.clone()).unwrap();
+    assert_eq!(engine.get(b"key_000"), None);
+    assert_eq!(engine.get(b"key_199"), Some(vec![b'v'; 32]));
+    assert_eq!(engine.get(b"unflushed"), Some(b"wal".to_vec()));
+    assert!(!engine.live_files().is_empty());
+    assert!(env.exists(&config.manifest_path()));
+    assert!(!dir.path().join("db").exists());
+
+    // Another Env is another file system
+    drop(engine);
+    let mut other = config.with_env(Env::memory());
+    other.create_if_missing = false;
+    assert!(matches!(
+        Oblivion::open(other),
+        Err(OblivionError::DatabaseNotFound(_))
+    ));
+}
+
+// ==================== Disk Full Tests ====================
+
+#[test]
+fn test_full_disk_makes_engine_read_only() {
+    use oblivion::engine::env::Env;
+    use oblivion::engine::fault::FaultFileSystem;
+    use oblivion::engine::Oblivion;
+    use oblivion::error::OblivionError;
+    use std::sync::atomic::Ordering;
+
+    let fs = FaultFileSystem::new();
+    let config = common::temp_config(std::path::Path::new("/db")).with_env(Env::new(fs.clone()));
+    let engine = Oblivion::open(config.clone()).unwrap();
+    engine.put(b"before".to_vec(), b"1".to_vec()).unwrap();
+    assert_eq!(engine.degraded(), None);
+
+    fs.set_full(true);
+    let err = engine.put(b"full".to_vec(), b"2".to_vec()).unwrap_err();
+    assert!(matches!(err, OblivionError::DiskFull(_)), "{:?}", err);
+    assert!(engine.degraded().unwrap().contains("WAL append"));
+    assert_eq!(engine.metrics().degraded.load(Ordering::Relaxed), 1);
+
+    // Freeing space does not resume writes; reads keep working
+    fs.set_full(false);
+    assert!(matches!(
+        engine.delete(b"before".to_vec()),
+        Err(OblivionError::DiskFull(_))
+    ));
+    assert!(matches!(engine.flush(), Err(OblivionError::DiskFull(_))));
+    assert_eq!(engine.get(b"before"), Some(b"1".to_vec()));
+
+    drop(engine);
+    let engine = Oblivion::open(config).unwrap();
+    assert_eq!(engine.degraded(), None);
+    engine.put(b"after".to_vec(), b"3".to_vec()).unwrap();
+    assert_eq!(engine.get(b"before"), Some(b"1".to_vec()));
+    assert_eq!(engine.get(b"after"), Some(b"3".to_vec()));
+}
+
+#[test]
+fn test_full_disk_stops_background_flushes() {
+    use oblivion::engine::env::Env;
+    use oblivion::engine::fault::{FaultFileSystem, FaultOp};
+    use oblivion::engine::Oblivion;
+    use oblivion::error::OblivionError;
+
+    let fs = FaultFileSystem::new();
+    let mut config =
+        common::temp_config(std::path::Path::new("/db")).with_env(Env::new(fs.clone()));
+    config.background_threads = 1;
+    let engine = Oblivion::open(config.clone()).unwrap();
+
+    // Enough for one freeze, which creates a WAL segment; the
+    // background flush then fails creating the SSTable
+    fs.fail_with(FaultOp::Create, 2, std::io::ErrorKind::StorageFull);
+    let key = |i: usize| format!("key_{:03}", i).into_bytes();
+    // Writes racing the failed flush may already be refused
+    let mut acknowledged = 0;
+    while acknowledged < 15 && engine.put(key(acknowledged), vec![b'v'; 100]).is_ok() {
+        acknowledged += 1;
+    }
+    assert!(matches!(
+        engine.wait_for_background_work(),
+        Err(OblivionError::DiskFull(_))
+    ));
+    assert!(engine.degraded().unwrap().starts_with("flush"));
+    assert_eq!(fs.faults_fired(), 1);
+    assert!(engine.put(b"more".to_vec(), b"v".to_vec()).is_err());
+    for i in 0..acknowledged {
+        assert_eq!(engine.get(key(i)), Some(vec![b'v'; 100]));
+    }
+
+    drop(engine);
+    let engine = Oblivion::open(config).unwrap();
+    engine.put(b"more".to_vec(), b"v".to_vec()).unwrap();
+    for i in 0..acknowledged {
+        assert_eq!(engine.get(key(i)), Some(vec![b'v'; 100]));
+    }
+    assert!(engine.verify_integrity().unwrap().is_ok());
+}
+
+// ==================== Fault Injection Tests ====================
+
+#[test]
+fn test_failed_wal_sync_is_not_acknowledged() {
+    use oblivion::engine::env::Env;
+    use oblivion::engine::fault::{CrashMode, FaultFileSystem, FaultOp};
+    use oblivion::engine::Oblivion;
+
+    let fs = FaultFileSystem::new();
+    let config = common::temp_config(std::path::Path::new("/db")).with_env(Env::new(fs.clone()));
+    let engine = Oblivion::open(config.clone()).unwrap();
+    engine.put(b"before".to_vec(), b"1".to_vec()).unwrap();
+
+    fs.fail(FaultOp::Sync, 1);
+    assert!(engine.put(b"failed".to_vec(), b"2".to_vec()).is_err());
+    assert_eq!(fs.faults_fired(), 1);
+
+    // Power is lost before anything else reaches the disk
+    fs.crash();
+    drop(engine);
+    fs.restart(CrashMode::DropUnsynced);
+    let engine = Oblivion::open(config).unwrap();
+    assert_eq!(engine.get(b"before"), Some(b"1".to_vec()));
+    assert_eq!(engine.get(b"failed"), None);
+    assert!(engine.verify_integrity().unwrap().is_ok());
+}
+
+#[test]
+fn test_crash_harness_recovers_latest_overwrites() {
+    use oblivion::engine::fault::{CrashHarness, CrashMode};
+    use oblivion::error::OblivionError;
+    use parking_lot::Mutex;
+
+    // Rounds of overwrites, flushed and compacted along the way: after
+    // any crash each key holds its last acknowledged round or the one
+    // in flight, never an older one replayed from a retired segment
+    const KEYS: usize = 16;
+    let acknowledged = Mutex::new([None::<u8>; KEYS]);
+    let key = |i: usize| format!("key_{:02}", i).into_bytes();
+    let mut config = common::temp_config(std::path::Path::new("/db"));
+    config.compaction_threshold = 2;
+
+    let crash_points = CrashHarness::new(config)
+        .with_mode(CrashMode::TornWrites { seed: 7 })
+        .run(
+            |engine| {
+                *acknowledged.lock() = [None; KEYS];
+                for round in 0..4u8 {
+                    for i in 0..KEYS {
+                        engine.put(key(i), vec![round; 100])?;
+                        acknowledged.lock()[i] = Some(round);
+                    }
+                }
+                Ok(())
+            },
+            |engine, point| {
+                let acknowledged = acknowledged.lock();
+                for (i, acked) in acknowledged.iter().enumerate() {
+                    let round = engine.get(key(i)).map(|value| value[0]);
+                    let ok = match (acked, round) {
+                        (None, None) | (None, Some(0)) => true,
+                        (Some(acked), Some(round)) => round == *acked || round == acked + 1,
+                        _ => false,
+                    };
+                    if !ok {
+                        return Err(OblivionError::Corruption(format!(
+                            "point {}: key {} acknowledged {:?}, found {:?}",
+                            point, i, acked, round
+                        )));
+                    }
+                }
+                Ok(())
+            },
+        )
+        .unwrap();
+    assert!(crash_points > 64, "{} crash points", crash_points);
+}
+
+// ==================== Destroy Tests ====================
+
+#[test]
+fn test_destroy_removes_only_database_files() {
+    use oblivion::engine::column_family::ColumnFamilyOptions;
+    use oblivion::engine::Oblivion;
+    use oblivion::error::OblivionError;
+
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("db");
+    let config = common::temp_config(&db);
+    let engine =
+        Oblivion::open_with_cfs(config.clone(), [("users", ColumnFamilyOptions::new())]).unwrap();
+    for i in 0..50u32 {
+        engine
+            .put(i.to_be_bytes().to_vec(), vec![b'v'; 100])
+            .unwrap();
+        engine
+            .put_cf("users", i.to_be_bytes().to_vec(), vec![b'u'; 100])
+            .unwrap();
+    }
+    engine.flush().unwrap();
+    std::fs::write(db.join("notes.txt"), b"keep").unwrap();
+    std::fs::write(db.join("sst").join("README"), b"keep").unwrap();
+
+    assert!(matches!(
+        Oblivion::destroy(&config),
+        Err(OblivionError::Locked(_))
+    ));
+    drop(engine);
+    Oblivion::destroy(&config).unwrap();
+
+    let mut left: Vec<_> = walkdir(&db);
+    left.sort();
+    assert_eq!(left, [db.join("notes.txt"), db.join("sst").join("README")]);
+    assert!(!config.database_exists());
+    let mut reopen = config.clone();
+    reopen.create_if_missing = false;
+    assert!(matches!(
+        Oblivion::open(reopen),
+        Err(OblivionError::DatabaseNotFound(_))
+    ));
+
+    // Once only the database's files are left, nothing is
+    std::fs::remove_file(db.join("notes.txt")).unwrap();
+    std::fs::remove_file(db.join("sst").join("README")).unwrap();
+    Oblivion::open(config.clone())
+        .unwrap()
+        .put(b"k".to_vec(), b"v".to_vec())
+        .unwrap();
+    Oblivion::destroy(&config).unwrap();
+    assert!(!db.exists());
+    Oblivion::destroy(&config).unwrap();
+}
+
+/// Every file under `dir`, recursively.
+fn walkdir(dir: &std::path::Path) -> Vec<std::path::PathBuf> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            files.extend(walkdir(&path));
+        } else {
+            files.push(path);
+        }
+    }
+    files
+}
+
+#[test]
+fn test_destroy_in_memory_env() {
+    use oblivion::engine::env::Env;
+    use oblivion::engine::Oblivion;
+
+    let env = Env::memory();
+    let config = common::temp_config(std::path::Path::new("/db")).with_env(env.clone());
+    let engine = Oblivion::open(config.clone()).unwrap();
+    for i in 0..50u32 {
+        engine
+            .put(i.to_be_bytes().to_vec(), vec![b'v'; 100])
+            .unwrap();
+    }
+    drop(engine);
+    Oblivion::destroy(&config).unwrap();
+    assert!(!env.exists(std::path::Path::new("/db")));
+}
+
+// ==================== Manifest Rebuild Tests ====================
+
+#[test]
+fn test_rebuild_unreadable_manifest_on_open() {
+    use oblivion::engine::Oblivion;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = common::temp_config(dir.path());
+    let engine = Oblivion::open(config.clone()).unwrap();
+    engine.put(b"k".to_vec(), b"old".to_vec()).unwrap();
+    engine.put(b"a".to_vec(), b"1".to_vec()).unwrap();
+    engine.flush().unwrap();
+    engine.put(b"k".to_vec(), b"new".to_vec()).unwrap();
+    engine.flush().unwrap();
+    drop(engine);
+
+    // Swap the tables' file numbers, so only their sequence ranges
+    // tell which is newer, add a table with no valid footer, and
+    // damage the manifest
+    let sst = config.sst_dir();
+    let mut tables: Vec<_> = std::fs::read_dir(&sst)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    tables.sort();
+    assert_eq!(tables.len(), 2);
+    let swap = sst.join("swap");
+    std::fs::rename(&tables[0], &swap).unwrap();
+    std::fs::rename(&tables[1], &tables[0]).unwrap();
+    std::fs::rename(&swap, &tables[1]).unwrap();
+    std::fs::write(sst.join("sstable_999999.sst"), b"not a table").unwrap();
+    std::fs::write(config.manifest_path(), b"junk").unwrap();
+
+    assert!(Oblivion::open(config.clone()).is_err());
+    let mut rebuild = config.clone();
+    rebuild.rebuild_manifest_on_open = true;
+    let engine = Oblivion::open(rebuild).unwrap();
+    assert_eq!(engine.get(b"k"), Some(b"new".to_vec()));
+    assert_eq!(engine.get(b"a"), Some(b"1".to_vec()));
+    assert!(dir.path().join("lost").join("sstable_999999.sst").exists());
+    assert!(engine.verify_integrity().unwrap().is_ok());
+    engine.put(b"b".to_vec(), b"2".to_vec()).unwrap();
+    engine.flush().unwrap();
+    drop(engine);
+
+    // The rebuilt manifest is saved; later opens need no fallback
+    let engine = Oblivion::open(config).unwrap();
+    assert_eq!(engine.get(b"k"), Some(b"new".to_vec()));
+    assert_eq!(engine.get(b"b"), Some(b"2".to_vec()));
+}
+
+#[test]
+fn test_rebuild_missing_manifest_on_open() {
+    use oblivion::config::Config;
+    use oblivion::engine::Oblivion;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::builder(dir.path())
+        .memtable_max_size(1024)
+        .rebuild_manifest_on_open(true)
+        .build()
+        .unwrap();
+    let engine = Oblivion::open(config.clone()).unwrap();
+    for round in 0..3u8 {
+        engine.put(b"k".to_vec(), vec![round]).unwrap();
+        engine.flush().unwrap();
+    }
+    drop(engine);
+
+    std::fs::remove_file(config.manifest_path()).unwrap();
+    let engine = Oblivion::open(config).unwrap();
+    assert_eq!(engine.get(b"k"), Some(vec![2]));
+    assert_eq!(engine.sstable_count(), 3);
+}
+
+// ==================== Adaptive MemTable Sizing Tests ====================
+
+#[test]
+fn test_adaptive_memtable_shrinks_when_traffic_is_slow() {
+    use oblivion::engine::properties::MEMTABLE_FLUSH_THRESHOLD;
+    use oblivion::engine::Oblivion;
+    use std::sync::atomic::Ordering;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = common::temp_config(dir.path())
+        .with_memtable_max_size(8 * 1024)
+        .with_adaptive_memtable_sizing(1024, 1024 * 1024);
+    let engine = Oblivion::open(config).unwrap();
+    let threshold = || engine.get_property(MEMTABLE_FLUSH_THRESHOLD).unwrap();
+    let flushes = || engine.metrics().flushes.load(Ordering::Relaxed);
+    assert_eq!(threshold(), "8192");
+
+    // MemTables filled a second apart: about 8 KB/s against
+    // millisecond flushes calls for the smallest size
+    let mut i = 0u32;
+    for round in 1..=2 {
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        while flushes() < round {
+            engine
+                .put(i.to_be_bytes().to_vec(), vec![b'v'; 100])
+                .unwrap();
+            i += 1;
+        }
+    }
+    assert_eq!(threshold(), "1024");
+
+    for _ in 0..20 {
+        engine
+            .put(i.to_be_bytes().to_vec(), vec![b'v'; 100])
+            .unwrap();
+        i += 1;
+    }
+    assert!(flushes() > 2);
+    for key in 0..i {
+        assert_eq!(engine.get(key.to_be_bytes()), Some(vec![b'v'; 100]));
+    }
+}
+
+// ==================== Streaming Scan Tests ====================
+
+#[test]
+fn test_scan_iter_streams_every_source() {
+    use oblivion::engine::options::ReadOptions;
+    use oblivion::engine::Oblivion;
+
+    let dir = tempfile::tempdir().unwrap();
+    let engine = Oblivion::open(common::temp_config(dir.path())).unwrap();
+    for i in 0..3000u32 {
+        engine
+            .put(
+                format!("key_{:05}", i).into_bytes(),
+                i.to_be_bytes().to_vec(),
+            )
+            .unwrap();
+    }
+    for i in (0..3000u32).step_by(7) {
+        engine.delete(format!("key_{:05}", i).into_bytes()).unwrap();
+    }
+    assert!(engine.sstable_count() > 0);
+
+    let rows: Vec<_> = engine.scan_iter().map(|row| row.unwrap()).collect();
+    assert_eq!(rows, engine.scan());
+    assert_eq!(rows.len(), 3000 - 3000usize.div_ceil(7));
+
+    // The iterator owns its snapshot: it outlives later writes and can
+    // move to another thread
+    let opts = ReadOptions {
+        lower_bound: Some(b"key_01000".to_vec()),
+        upper_bound: Some(b"key_02000".to_vec()),
+        ..ReadOptions::default()
+    };
+    let expected = engine.scan_opt(&opts).unwrap();
+    let iter = engine.scan_iter_opt(&opts);
+    engine.delete(b"key_01002".to_vec()).unwrap();
+    let streamed = std::thread::spawn(move || iter.collect::<Result<Vec<_>, _>>())
+        .join()
+        .unwrap()
+        .unwrap();
+    assert_eq!(streamed, expected);
+    assert!(streamed.iter().any(|(key, _)| key == b"key_01002"));
+}
+
+// ==================== Bulk Write Tests ====================
+
+#[test]
+fn test_put_many_and_delete_many() {
+    use oblivion::engine::Oblivion;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = common::temp_config(dir.path());
+    let engine = Oblivion::open(config.clone()).unwrap();
+
+    let pairs = (0..500).map(|i| (format!("key_{:04}", i).into_bytes(), vec![b'v'; 20]));
+    engine.put_many(pairs).unwrap();
+    assert_eq!(engine.latest_sequence(), 500);
+    engine
+        .put_many(vec![
+            (b"key_0000".to_vec(), b"first".to_vec()),
+            (b"key_0000".to_vec(), b"last".to_vec()),
+        ])
+        .unwrap();
+    assert_eq!(engine.get(b"key_0000"), Some(b"last".to_vec()));
+
+    engine
+        .delete_many(
+            (0..500)
+                .step_by(2)
+                .map(|i| format!("key_{:04}", i).into_bytes()),
+        )
+        .unwrap();
+    assert_eq!(engine.latest_sequence(), 752);
+    assert_eq!(engine.scan().len(), 250);
+
+    // Nothing is written when any record is too large, or none is given
+    let oversized = vec![
+        (b"fine".to_vec(), b"v".to_vec()),
+        (vec![b'k'; config.max_key_size + 1], b"v".to_vec()),
+    ];
+    assert!(engine.put_many(oversized).is_err());
+    engine.delete_many(Vec::<Vec<u8>>::new()).unwrap();
+    assert_eq!(engine.latest_sequence(), 752);
+    assert_eq!(engine.get(b"fine"), None);
+    drop(engine);
+
+    let engine = Oblivion::open(config).unwrap();
+    assert_eq!(engine.latest_sequence(), 752);
+    assert_eq!(engine.get(b"key_0000"), None);
+    assert_eq!(engine.get(b"key_0001"), Some(vec![b'v'; 20]));
+    assert_eq!(engine.scan().len(), 250);
+}
+
+// ==================== Borrowed Key Tests ====================
+
+#[test]
+fn test_api_accepts_borrowed_keys_and_values() {
+    use oblivion::engine::batch::WriteBatch;
+    use oblivion::engine::Oblivion;
+
+    let dir = tempfile::tempdir().unwrap();
+    let engine = Oblivion::open(common::temp_config(dir.path())).unwrap();
+
+    // Literals, strings and borrowed buffers all work without `to_vec`
+    let buffer = vec![b'b'; 4];
+    engine.put("str", "value").unwrap();
+    engine.put(b"bytes", &buffer[..]).unwrap();
+    engine.put(String::from("owned"), buffer.clone()).unwrap();
+    assert_eq!(engine.get("str"), Some(b"value".to_vec()));
+    assert_eq!(engine.get(b"bytes"), Some(buffer.clone()));
+    assert_eq!(engine.get(String::from("owned")), Some(buffer.clone()));
+    assert_eq!(engine.strlen(&buffer).unwrap(), 0);
+
+    assert_eq!(engine.append("str", "!").unwrap(), 6);
+    assert_eq!(
+        engine.getset("str", "new").unwrap(),
+        Some(b"value!".to_vec())
+    );
+    assert!(engine.expire("str", 60_000).unwrap());
+    assert!(engine.ttl("str").is_some());
+    assert!(engine.persist("str").unwrap());
+
+    let mut batch = WriteBatch::new();
+    batch
+        .put("a", "1")
+        .put_with_ttl("b", "2", 60_000)
+        .delete("bytes");
+    engine.write(batch).unwrap();
+    engine.put_many([("c", "3"), ("d", "4")]).unwrap();
+    engine.delete_many(["c"]).unwrap();
+    assert_eq!(engine.getdel("d").unwrap(), Some(b"4".to_vec()));
+    engine.delete("owned").unwrap();
+
+    let keys: Vec<_> = engine.scan().into_iter().map(|(key, _)| key).collect();
+    assert_eq!(keys, vec![b"a".to_vec(), b"b".to_vec(), b"str".to_vec()]);
+
+    let db = engine.database(1).unwrap();
+    db.put("k", "v").unwrap();
+    assert_eq!(db.get("k").unwrap(), Some(b"v".to_vec()));
+    db.delete("k").unwrap();
+    assert_eq!(db.get(b"k").unwrap(), None);
+}
+
+// ==================== Pinned Read Tests ====================
+
+#[test]
+fn test_get_pinned_from_every_source() {
+    use oblivion::engine::options::ReadOptions;
+    use oblivion::engine::Oblivion;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = common::temp_config(dir.path()).with_row_cache_capacity(1024 * 1024);
+    let engine = Oblivion::open(config).unwrap();
+    engine.put("flushed", "on disk").unwrap();
+    engine.put("deleted", "gone").unwrap();
+    engine.flush().unwrap();
+    engine.delete("deleted").unwrap();
+    engine.put("fresh", "in memory").unwrap();
+
+    // From an SSTable, then from the row cache it filled
+    for _ in 0..2 {
+        let value = engine.get_pinned("flushed").unwrap();
+        assert_eq!(&*value, b"on disk");
+    }
+    assert_eq!(engine.get_pinned("fresh").unwrap().into_vec(), b"in memory");
+    assert!(engine.get_pinned("deleted").is_none());
+    assert!(engine.get_pinned("missing").is_none());
+
+    // Writes go through once the pinned value is dropped
+    let pinned = engine.get_pinned("fresh").unwrap();
+    assert_eq!(pinned.len(), 9);
+    drop(pinned);
+    engine.put_with_ttl("short", "lived", 1).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    assert!(engine.get_pinned("short").is_none());
+
+    // Values read at a snapshot are borrowed from it
+    let opts = ReadOptions::new().snapshot(engine.snapshot());
+    engine.put("fresh", "overwritten").unwrap();
+    let pinned = engine.get_pinned_opt("fresh", &opts).unwrap().unwrap();
+    assert_eq!(&*pinned, b"in memory");
+    engine.put("fresh", "again").unwrap();
+    assert_eq!(&*pinned, b"in memory");
+    assert_eq!(engine.get("fresh"), Some(b"again".to_vec()));
+}
+
+// ==================== Existence Check Tests ====================
+
+#[test]
+fn test_exists_checks_membership_at_every_level() {
+    use oblivion::engine::options::ReadOptions;
+    use oblivion::engine::Oblivion;
+    use std::sync::atomic::Ordering;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = common::temp_config(dir.path()).with_row_cache_capacity(1024 * 1024);
+    let engine = Oblivion::open(config).unwrap();
+    for i in 0..200 {
+        engine
+            .put(format!("key_{:04}", i), vec![b'v'; 100])
+            .unwrap();
+    }
+    engine.flush().unwrap();
+    engine.delete("key_0007").unwrap();
+    engine.put("fresh", "v").unwrap();
+    engine.put_with_ttl("short", "v", 1).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(5));
+
+    let snapshot = ReadOptions::new().snapshot(engine.snapshot());
+    assert!(engine.exists("key_0000"));
+    assert!(engine.exists("fresh"));
+    assert!(!engine.exists("key_0007"));
+    assert!(!engine.exists("short"));
+    assert!(!engine.exists("missing"));
+
+    // Misses never read a block unless the filter lets them through
+    let metrics = engine.metrics();
+    let probes = metrics.filter_probes.load(Ordering::Relaxed);
+    let negatives = metrics.filter_negatives.load(Ordering::Relaxed);
+    for i in 0..100 {
+        assert!(!engine.exists(format!("key_{:04}x", i)));
+    }
+    let probes = metrics.filter_probes.load(Ordering::Relaxed) - probes;
+    let negatives = metrics.filter_negatives.load(Ordering::Relaxed) - negatives;
+    assert!(probes >= 100);
+    assert!(negatives >= probes * 9 / 10);
+
+    // Rows cached by a get answer from the row cache; snapshots see
+    // their own state
+    assert!(engine.get("key_0001").is_some());
+    assert!(engine.exists("key_0001"));
+    engine.delete("key_0001").unwrap();
+    assert!(!engine.exists("key_0001"));
+    assert!(engine.exists_opt("key_0001", &snapshot).unwrap());
+    assert!(!engine.exists_opt("key_0007", &snapshot).unwrap());
+
+    let db = engine.database(0).unwrap();
+    assert!(db.exists("key_0002").unwrap());
+    assert!(!db.exists("key_0001").unwrap());
+}
+
+// ==================== Value Metadata Tests ====================
+
+#[test]
+fn test_value_info_without_reading_values() {
+    use oblivion::engine::Oblivion;
+    use oblivion::types::ValueInfo;
+
+    let dir = tempfile::tempdir().unwrap();
+    let engine = Oblivion::open(common::temp_config(dir.path())).unwrap();
+    engine.put("blob", vec![0u8; 4096]).unwrap();
+    engine.put("gone", "v").unwrap();
+    engine.flush().unwrap();
+    engine.delete("gone").unwrap();
+    engine.put_with_ttl("session", "abc", 60_000).unwrap();
+
+    assert_eq!(engine.value_size("blob"), Some(4096));
+    assert_eq!(engine.strlen("blob").unwrap(), 4096);
+    assert_eq!(engine.value_size("gone"), None);
+    assert_eq!(engine.value_size("missing"), None);
+    assert_eq!(engine.strlen("missing").unwrap(), 0);
+    assert_eq!(
+        engine.value_info("blob").unwrap(),
+        Some(ValueInfo {
+            len: 4096,
+            ttl_ms: None
+        })
+    );
+    let info = engine.value_info("session").unwrap().unwrap();
+    assert_eq!(info.len, 3);
+    assert!(info.ttl_ms.unwrap() <= 60_000);
+
+    // Reading metadata does not count as a get
+    let metrics = engine.metrics().snapshot();
+    engine.value_size("blob");
+    assert_eq!(engine.metrics().snapshot().gets, metrics.gets);
+}
+
+// ==================== Output Splitting Tests ====================
+
+#[test]
+fn test_flush_and_compaction_split_at_target_file_size() {
+    use oblivion::config::Config;
+    use oblivion::engine::Oblivion;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::builder(dir.path())
+        .memtable_max_size(64 * 1024)
+        .target_file_size(8 * 1024)
+        .build()
+        .unwrap();
+    let engine = Oblivion::open(config.clone()).unwrap();
+    let key = |i: u32| format!("key_{:05}", i).into_bytes();
+
+    // One flush writes a run of disjoint tables of about the target size
+    for i in 0..500 {
+        engine.put(key(i), vec![b'v'; 100]).unwrap();
+    }
+    engine.flush().unwrap();
+    // 500 rows of 110 raw bytes: 6 full tables of 8 KB and the rest
+    let files = engine.live_files();
+    assert_eq!(files.len(), 7);
+    for pair in files.windows(2) {
+        assert!(pair[0].largest_key < pair[1].smallest_key);
+        assert_eq!(pair[0].largest_seqno, pair[1].largest_seqno);
+    }
+
+    // Runs, not their tables, count toward compaction, which terminates
+    // and splits its output too
+    for round in 1..4 {
+        for i in 0..500 {
+            engine.put(key(i), vec![b'0' + round; 50]).unwrap();
+        }
+        engine.flush().unwrap();
+    }
+    engine.wait_for_background_work().unwrap();
+    engine.compact().unwrap();
+    // 500 rows of 59 raw bytes
+    let compacted = engine.live_files();
+    assert_eq!(compacted.len(), 4);
+    for pair in compacted.windows(2) {
+        assert!(pair[0].largest_key < pair[1].smallest_key);
+    }
+    assert_eq!(engine.get(key(499)), Some(vec![b'3'; 50]));
+    drop(engine);
+
+    let engine = Oblivion::open(config).unwrap();
+    assert_eq!(engine.sstable_count(), compacted.len());
+    assert_eq!(engine.scan().len(), 500);
+    assert_eq!(engine.get(key(0)), Some(vec![b'3'; 50]));
+}
+
+// ==================== Key Range Pruning Tests ====================
+
+#[test]
+fn test_reads_skip_tables_outside_their_key_range() {
+    use oblivion::config::Config;
+    use oblivion::engine::options::ReadOptions;
+    use oblivion::engine::Oblivion;
+    use std::sync::atomic::Ordering;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config::builder(dir.path())
+        .memtable_max_size(1024 * 1024)
+        .build()
+        .unwrap();
+    let engine = Oblivion::open(config).unwrap();
+    // Three tables with disjoint key ranges
+    for prefix in ["a", "m", "z"] {
+        for i in 0..100 {
+            engine.put(format!("{}_{:03}", prefix, i), "v").unwrap();
+        }
+        engine.flush().unwrap();
+    }
+    assert_eq!(engine.sstable_count(), 3);
+
+    // Only the table covering the key is probed
+    let metrics = engine.metrics();
+    let probes = metrics.filter_probes.load(Ordering::Relaxed);
+    assert!(engine.get("m_050").is_some());
+    assert!(engine.get("m_050x").is_none());
+    assert!(engine.get("m_500").is_none());
+    assert!(!engine.exists("b"));
+    assert!(engine.get("zz").is_none());
+    assert_eq!(metrics.filter_probes.load(Ordering::Relaxed), probes + 2);
+
+    // Bounded scans read only the tables they overlap, and still see
+    // every row in range
+    let opts = ReadOptions::new()
+        .lower_bound(b"m_090".to_vec())
+        .upper_bound(b"z_005".to_vec());
+    let keys: Vec<_> = engine
+        .scan_opt(&opts)
+        .unwrap()
+        .into_iter()
+        .map(|(key, _)| String::from_utf8(key).unwrap())
+        .collect();
+    assert_eq!(keys.len(), 15);
+    assert_eq!(keys.first().map(String::as_str), Some("m_090"));
+    assert_eq!(keys.last().map(String::as_str), Some("z_004"));
+}
+
+// ==================== Dictionary Compression Tests ====================
+
+#[cfg(feature = "zstd")]
+#[test]
+fn test_compression_dictionary_shrinks_small_values() {
+    use oblivion::config::Config;
+    use oblivion::engine::Oblivion;
+    use std::path::Path;
+
+    let open = |dir: &Path, dict_size: usize| {
+        let config = Config::builder(dir)
+            .memtable_max_size(1024 * 1024)
+            .compression_dict_size(dict_size)
+            .build()
+            .unwrap();
+        Oblivion::open(config).unwrap()
+    };
+    let value = |i: usize| format!("{{\"user\":{},\"plan\":\"basic\",\"active\":true}}", i % 50);
+    let disk_size = |engine: &Oblivion| engine.live_files().iter().map(|f| f.size).sum::<u64>();
+
+    let plain_dir = tempfile::tempdir().unwrap();
+    let zstd_dir = tempfile::tempdir().unwrap();
+    let plain = open(plain_dir.path(), 0);
+    let compressed = open(zstd_dir.path(), 16 * 1024);
+    for engine in [&plain, &compressed] {
+        for round in 0..2 {
+            for i in (round..3000).step_by(2) {
+                engine.put(format!("user:{:06}", i), value(i)).unwrap();
+            }
+            engine.flush().unwrap();
+        }
+    }
+    assert!(disk_size(&compressed) * 2 < disk_size(&plain));
+
+    // Compaction trains a fresh dictionary over its merged output
+    compressed.compact().unwrap();
+    drop(compressed);
+    let compressed = open(zstd_dir.path(), 16 * 1024);
+    assert_eq!(compressed.sstable_count(), 1);
+    for i in [0, 1, 1500, 2999] {
+        assert_eq!(
+            compressed.get(format!("user:{:06}", i)),
+            Some(value(i).into_bytes())
+        );
+    }
+    assert_eq!(compressed.scan().len(), 3000);
+}
+
+// ==================== Periodic WAL Sync Tests ====================
+
+#[test]
+fn test_periodic_wal_sync_bounds_loss_without_sync_writes() {
+    use oblivion::engine::env::Env;
+    use oblivion::engine::fault::{CrashMode, FaultFileSystem};
+    use oblivion::engine::Oblivion;
+    use std::time::Duration;
+
+    // Writes acknowledged before a power cut survive it only if the
+    // background sync got to them
+    let survives = |interval_ms: u64| {
+        let fs = FaultFileSystem::new();
+        let mut config = common::temp_config(std::path::Path::new("/db"))
+            .with_env(Env::new(fs.clone()))
+            .with_wal_sync_interval_ms(interval_ms);
+        config.sync_writes = false;
+        let engine = Oblivion::open(config.clone()).unwrap();
+        engine.put("key", "value").unwrap();
+        std::thread::sleep(Duration::from_millis(200));
+
+        fs.crash();
+        drop(engine);
+        fs.restart(CrashMode::DropUnsynced);
+        let engine = Oblivion::open(config).unwrap();
+        engine.get("key").is_some()
+    };
+    assert!(survives(10));
+    assert!(!survives(0));
+}
+
+#[test]
+fn test_wal_sync_interval_is_a_live_option() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = common::temp_config(dir.path());
+    config.sync_writes = false;
+    let engine = oblivion::engine::Oblivion::open(config).unwrap();
+    engine.set_options([("wal_sync_interval_ms", "5")]).unwrap();
+    engine.put("key", "value").unwrap();
+    engine
+        .set_options([("sync_writes", "true"), ("wal_sync_interval_ms", "0")])
+        .unwrap();
+    assert_eq!(engine.config().wal_sync_interval_ms, 0);
+    assert_eq!(engine.get("key"), Some(b"value".to_vec()));
+}
+
+// ==================== Fail Point Tests ====================
+
+#[cfg(feature = "failpoints")]
+#[test]
+fn test_crash_at_each_fail_point_recovers_acknowledged_writes() {
+    use oblivion::engine::env::Env;
+    use oblivion::engine::failpoint::{FailPoint, FailPoints};
+    use oblivion::engine::fault::{CrashMode, FaultFileSystem};
+    use oblivion::engine::Oblivion;
+
+    let points = [
+        FailPoint::AfterWalAppend,
+        FailPoint::MidFlush,
+        FailPoint::MidCompaction,
+        FailPoint::MidManifestWrite,
+    ];
+    for point in points {
+        for n in [1, 3] {
+            let fs = FaultFileSystem::new();
+            let failpoints = FailPoints::new();
+            let mut config = common::temp_config(std::path::Path::new("/db"))
+                .with_env(Env::new(fs.clone()))
+                .with_failpoints(failpoints.clone());
+            config.compaction_threshold = 2;
+            let engine = Oblivion::open(config.clone()).unwrap();
+            let power = fs.clone();
+            failpoints.run_at(point, n, move || power.crash());
+
+            // Writes, flushes and a compaction until the power cut
+            let mut acknowledged = 0;
+            let result = (|| {
+                for i in 0..200 {
+                    engine.put(format!("key_{:03}", i), vec![i as u8; 100])?;
+                    acknowledged = i + 1;
+                }
+                engine.flush()?;
+                engine.compact()
+            })();
+            assert!(result.is_err(), "{} #{}: no crash", point, n);
+            assert!(failpoints.hits(point) >= n, "{} #{}", point, n);
+            drop(engine);
+
+            fs.restart(CrashMode::DropUnsynced);
+            let engine = Oblivion::open(config).unwrap();
+            for i in 0..acknowledged {
+                assert_eq!(
+                    engine.get(format!("key_{:03}", i)),
+                    Some(vec![i as u8; 100]),
+                    "{} #{}: key {} of {} acknowledged",
+                    point,
+                    n,
+                    i,
+                    acknowledged
+                );
+            }
+            let report = engine.verify_integrity().unwrap();
+            assert!(report.is_ok(), "{} #{}: {:?}", point, n, report.problems);
+        }
+    }
+}
+
+#[cfg(feature = "failpoints")]
+#[test]
+fn test_failed_fail_point_surfaces_as_write_error() {
+    use oblivion::engine::failpoint::{FailPoint, FailPoints};
+    use oblivion::engine::Oblivion;
+
+    let dir = tempfile::tempdir().unwrap();
+    let failpoints = FailPoints::new();
+    let config = common::temp_config(dir.path()).with_failpoints(failpoints.clone());
+    let engine = Oblivion::open(config).unwrap();
+
+    failpoints.fail(FailPoint::AfterWalAppend, 1);
+    assert!(engine.put("key", "value").is_err());
+    engine.put("other", "value").unwrap();
+    assert_eq!(failpoints.hits(FailPoint::AfterWalAppend), 2);
+}
+
+// ==================== Live Key Estimate Tests ====================
+
+#[test]
+fn test_estimate_live_keys_discounts_tombstones_and_overwrites() {
+    use oblivion::config::Config;
+    use oblivion::engine::properties;
+    use oblivion::engine::Oblivion;
+
+    let dir = tempfile::tempdir().unwrap();
+    let engine = Oblivion::open(Config::new(dir.path())).unwrap();
+    let key = |i: usize| format!("key_{:03}", i);
+    for i in 0..100 {
+        engine.put(key(i), "v1").unwrap();
+    }
+    engine.flush().unwrap();
+    assert_eq!(engine.len(), 0);
+    assert_eq!(engine.estimate_live_keys(), 100);
+
+    // Overwrites and deletes in the MemTable are looked up in the table
+    for i in 0..20 {
+        engine.put(key(i), "v2").unwrap();
+    }
+    for i in 90..100 {
+        engine.delete(key(i)).unwrap();
+    }
+    engine.delete("never_written").unwrap();
+    assert_eq!(engine.len(), 31);
+    assert_eq!(engine.estimate_live_keys(), 90);
+    assert_eq!(
+        engine
+            .get_property(properties::ESTIMATE_LIVE_KEYS)
+            .as_deref(),
+        Some("90")
+    );
+
+    // Across tables the overwrites count twice until compaction
+    engine.flush().unwrap();
+    assert_eq!(engine.estimate_live_keys(), 100 + 20 - 11);
+    engine.compact().unwrap();
+    assert_eq!(engine.estimate_live_keys(), 90);
+    assert_eq!(engine.scan().len(), 90);
+}
+
+// ==================== Periodic Compaction Tests ====================
+
+#[test]
+fn test_periodic_compaction_rewrites_old_tables() {
+    use oblivion::config::Config;
+    use oblivion::engine::Oblivion;
+
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = Config::new(dir.path());
+    config.background_threads = 0;
+    config.compaction_threshold = 100;
+    let engine = Oblivion::open(config).unwrap();
+    engine.put("kept", "v").unwrap();
+    engine.put_with_ttl("expiring", "v", 100).unwrap();
+    engine.flush().unwrap();
+    let old = engine.live_files();
+    assert_eq!(old[0].num_entries, 2);
+
+    // Below the tier threshold, only the table's age gets it rewritten,
+    // dropping the expired key
+    std::thread::sleep(std::time::Duration::from_millis(2100));
+    engine
+        .set_options([("periodic_compaction_interval_secs", "1")])
+        .unwrap();
+    let files = engine.live_files();
+    assert_eq!(files.len(), 1);
+    assert_ne!(files[0].path, old[0].path);
+    assert_eq!(files[0].num_entries, 1);
+    assert_eq!(engine.get("kept"), Some(b"v".to_vec()));
+
+    // The rewritten table is not due again until it ages
+    engine.put("new", "v").unwrap();
+    engine.flush().unwrap();
+    let files_after = engine.live_files();
+    assert_eq!(files_after.len(), 2);
+    assert_eq!(files_after[0].path, files[0].path);
+}
+
+// ==================== SSTable Lifetime Tests ====================
+
+#[test]
+fn test_compacted_tables_outlive_their_readers() {
+    use oblivion::config::Config;
+    use oblivion::engine::Oblivion;
+
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = Config::new(dir.path());
+    config.compaction_threshold = 100;
+    let engine = Oblivion::open(config).unwrap();
+    for round in 0..3 {
+        for i in 0..50 {
+            let key = format!("key_{:03}", i);
+            engine.put(key, format!("v{}", round)).unwrap();
+        }
+        engine.flush().unwrap();
+    }
+    let old: Vec<_> = engine.live_files().into_iter().map(|f| f.path).collect();
+    assert_eq!(old.len(), 3);
+
+    // An iterator and a snapshot taken before the compaction keep
+    // reading the tables it replaced
+    let mut iter = engine.scan_iter();
+    let snapshot = engine.snapshot();
+    engine.compact().unwrap();
+    assert_eq!(engine.sstable_count(), 1);
+    assert!(old.iter().all(|path| path.exists()));
+
+    let first = iter.next().unwrap().unwrap();
+    assert_eq!(first, (b"key_000".to_vec(), b"v2".to_vec()));
+    assert_eq!(iter.count(), 49);
+    assert!(old.iter().all(|path| path.exists()));
+    let opts = oblivion::engine::options::ReadOptions::new().snapshot(snapshot);
+    assert_eq!(
+        engine.get_opt("key_049", &opts).unwrap(),
+        Some(b"v2".to_vec())
+    );
+
+    // The files go once the last reader does
+    drop(opts);
+    assert!(old.iter().all(|path| !path.exists()));
+    assert_eq!(engine.scan().len(), 50);
+}
+
+// ==================== Filtered Scan Tests ====================
+
+#[test]
+fn test_scan_filtered_by_key_and_value() {
+    use oblivion::engine::predicate::Predicate;
+    use oblivion::engine::Oblivion;
+
+    let dir = tempfile::tempdir().unwrap();
+    let engine = Oblivion::open(common::temp_config(dir.path())).unwrap();
+    for i in 0..200 {
+        let status = if i % 3 == 0 { "active" } else { "idle" };
+        engine
+            .put(
+                format!("user:{:03}", i),
+                format!("{{\"status\":\"{}\"}}", status),
+            )
+            .unwrap();
+        engine.put(format!("order:{:03}", i), "active").unwrap();
+    }
+    engine.delete("user:003").unwrap();
+
+    let filter =
+        Predicate::parse(r#"key regex '^user:\d+$' and value contains '"active"'"#).unwrap();
+    let rows = engine.scan_filtered(&filter).unwrap();
+    assert_eq!(rows.len(), 66);
+    assert!(rows.iter().all(|(key, _)| key.starts_with(b"user:")));
+    assert_eq!(rows[0].0, b"user:000");
+    assert_eq!(rows[1].0, b"user:006");
+
+    // Key globs and prefixes narrow the range that is read
+    let filter = Predicate::key_glob("order:1*").and(!Predicate::key_prefix("order:10"));
+    assert_eq!(filter.required_key_prefix(), b"order:1");
+    assert_eq!(engine.scan_filtered(&filter).unwrap().len(), 90);
+
+    drop(engine);
+    let engine = Oblivion::open(common::temp_config(dir.path())).unwrap();
+    let filter = Predicate::value_prefix("idle").or(Predicate::value_glob("*idle*"));
+    assert_eq!(engine.scan_filtered(&filter).unwrap().len(), 133);
+}
+
+// ==================== Key Sampling Tests ====================
+
+#[test]
+fn test_random_keys_are_live_and_spread() {
+    use oblivion::config::Config;
+    use oblivion::engine::options::ReadOptions;
+    use oblivion::engine::Oblivion;
+    use std::collections::HashSet;
+
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = Config::new(dir.path());
+    config.compaction_threshold = 100;
+    let engine = Oblivion::open(config).unwrap();
+    assert_eq!(engine.random_key().unwrap(), None);
+
+    // Keys spread over several tables and the MemTable, half deleted
+    for i in 0..2000 {
+        engine.put(format!("key:{:04}", i), vec![b'v'; 32]).unwrap();
+        if i % 500 == 499 {
+            engine.flush().unwrap();
+        }
+    }
+    for i in (0..2000).step_by(2) {
+        engine.delete(format!("key:{:04}", i)).unwrap();
+    }
+    engine.put("key:9999", "late").unwrap();
+    assert!(engine.sstable_count() >= 4);
+
+    let keys = engine.random_keys(400).unwrap();
+    assert_eq!(keys.len(), 400);
+    for key in &keys {
+        assert!(engine.get(key).is_some(), "{:?} is not live", key);
+    }
+    // Picks come from every table, not just the newest
+    let hundreds: HashSet<&[u8]> = keys.iter().map(|key| &key[4..6]).collect();
+    assert!(hundreds.len() >= 15, "{:?}", hundreds);
+
+    // Bounds restrict the picks
+    let opts = ReadOptions::new().prefix(b"key:15");
+    let keys = engine.random_keys_opt(50, &opts).unwrap();
+    assert_eq!(keys.len(), 50);
+    assert!(keys.iter().all(|key| key.starts_with(b"key:15")));
+    let none = ReadOptions::new().prefix(b"other:");
+    assert!(engine.random_keys_opt(5, &none).unwrap().is_empty());
+}
+
+// ==================== DB Size Tests ====================
+
+#[test]
+fn test_dbsize_estimate_and_exact_count() {
+    use oblivion::config::Config;
+    use oblivion::engine::Oblivion;
+    use oblivion::error::OblivionError;
+    use std::sync::atomic::AtomicBool;
+
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = Config::new(dir.path());
+    config.compaction_threshold = 100;
+    let engine = Oblivion::open(config).unwrap();
+    let running = AtomicBool::new(false);
+    assert_eq!(engine.dbsize_estimate(), 0);
+    assert_eq!(engine.dbsize_exact(&running).unwrap(), 0);
+
+    for i in 0..1000 {
+        engine.put(format!("key:{:04}", i), "v").unwrap();
+    }
+    engine.flush().unwrap();
+    for i in 0..100 {
+        engine.delete(format!("key:{:04}", i)).unwrap();
+    }
+    for i in 500..550 {
+        engine.put(format!("key:{:04}", i), "v2").unwrap();
+    }
+
+    // The estimate takes every MemTable write for a new key; the exact
+    // count merges them with the table
+    assert_eq!(engine.dbsize_exact(&running).unwrap(), 900);
+    assert_eq!(engine.dbsize_estimate(), 950);
+    engine.flush().unwrap();
+    assert_eq!(engine.dbsize_estimate(), 950);
+    engine.compact().unwrap();
+    assert_eq!(engine.dbsize_estimate(), 900);
+    assert_eq!(engine.dbsize_exact(&running).unwrap(), 900);
+
+    let cancelled = AtomicBool::new(true);
+    assert!(matches!(
+        engine.dbsize_exact(&cancelled),
+        Err(OblivionError::Cancelled(_))
+    ));
+}
+
+// ==================== Transaction Tests ====================
+
+#[test]
+fn test_transaction_conflicts_with_concurrent_writer() {
+    use oblivion::config::Config;
+    use oblivion::engine::concurrent::ConcurrentOblivion;
+    use oblivion::error::OblivionError;
+
+    let dir = tempfile::tempdir().unwrap();
+    let engine = ConcurrentOblivion::open(Config::new(dir.path())).unwrap();
+    engine.put(b"balance".to_vec(), b"100".to_vec()).unwrap();
+    let other = engine.clone();
+
+    // Check-then-update, with a second handle writing in between
+    let mut txn = engine.transaction();
+    let balance: u64 = String::from_utf8(txn.get(b"balance").unwrap().unwrap())
+        .unwrap()
+        .parse()
+        .unwrap();
+    txn.put(b"balance", (balance - 30).to_string()).unwrap();
+    assert_eq!(txn.get(b"balance").unwrap(), Some(b"70".to_vec()));
+    std::thread::spawn(move || other.put(b"balance".to_vec(), b"50".to_vec()).unwrap())
+        .join()
+        .unwrap();
+    assert!(matches!(
+        txn.commit(),
+        Err(OblivionError::TransactionConflict(_))
+    ));
+    assert_eq!(engine.get(b"balance"), Some(b"50".to_vec()));
+
+    // A retry reads the new value
+    let mut txn = engine.transaction();
+    let balance: u64 = String::from_utf8(txn.get(b"balance").unwrap().unwrap())
+        .unwrap()
+        .parse()
+        .unwrap();
+    txn.put(b"balance", (balance - 30).to_string()).unwrap();
+    txn.commit().unwrap();
+    assert_eq!(engine.get(b"balance"), Some(b"20".to_vec()));
+}